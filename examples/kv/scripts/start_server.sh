@@ -9,6 +9,7 @@ if [[ ! -d "$mydir" ]]; then mydir="$PWD"; fi
 DEFINE_string 'host' '127.0.0.1' 'network host' 'host'
 DEFINE_integer 'server_num' '3' 'Number of servers' 'server_num'
 DEFINE_integer 'port' '50051' "Port of the first server" 'port'
+DEFINE_integer 'admin_port' '8081' "Admin http port of the first server" 'admin_port'
 DEFINE_string 'path' '/tmp' 'runtime path' 'path'
 
 # parse the command-line
@@ -27,6 +28,7 @@ for ((i=0; i<$FLAGS_server_num; ++i)); do
         --node-id=$((i+1)) \
         --addr=${FLAGS_host}:$((${FLAGS_port}+i)) \
         --nodes=${nodes} \
+        --admin-addr=${FLAGS_host}:$((${FLAGS_admin_port}+i)) \
         --log-storage-path=${FLAGS_path}/oceanraft_runtime/log_$((i+1)) \
         --kv-storage-path=${FLAGS_path}/oceanraft_runtime/kv_$((i+1))
     
@@ -34,6 +36,7 @@ for ((i=0; i<$FLAGS_server_num; ++i)); do
         --node-id=$((i+1)) \
         --addr=${FLAGS_host}:$((${FLAGS_port}+i)) \
         --nodes=${nodes} \
+        --admin-addr=${FLAGS_host}:$((${FLAGS_admin_port}+i)) \
         --log-storage-path=${FLAGS_path}/oceanraft_runtime/log_$((i+1)) \
         --kv-storage-path=${FLAGS_path}/oceanraft_runtime/kv_$((i+1)) &
 done
@@ -0,0 +1,258 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use hyper::service::make_service_fn;
+use hyper::service::service_fn;
+use hyper::Body;
+use hyper::Method;
+use hyper::Request;
+use hyper::Response;
+use hyper::Server;
+use hyper::StatusCode;
+use oceanraft::prelude::ConfChangeType;
+use oceanraft::prelude::MembershipChangeData;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::prelude::SingleMembershipChange;
+use oceanraft::storage::RaftSnapshotWriter;
+use oceanraft::MultiRaft;
+use oceanraft::NodeTopology;
+use serde_json::json;
+use serde_json::Value;
+
+use crate::server::KVAppType;
+use crate::storage::MemKvStorage;
+use crate::transport::KVTransport;
+
+/// The admin HTTP endpoint of the node, the responses are json.
+///
+/// - `GET /status`: the summary of the groups and peers on the node.
+/// - `GET /topology`: the membership of the groups on the node.
+/// - `GET /groups/{group}`: the state of the replica of the group on the node.
+/// - `POST /groups/{group}/leader`: transfer the leadership of the group to
+///   the replica on the node.
+/// - `POST /groups/{group}/snapshot`: build the snapshot of the group, which is
+///   sent to the followers lagging behind.
+/// - `POST /groups/{group}/replicas/{node}`: add a replica of the group on the
+///   node, it must be called on the leader.
+/// - `DELETE /groups/{group}/replicas/{node}/{replica}`: remove the replica of
+///   the group, it must be called on the leader.
+pub struct AdminService {
+    multiraft: Arc<MultiRaft<KVAppType, KVTransport>>,
+    kv_storage: MemKvStorage,
+}
+
+impl AdminService {
+    pub fn new(
+        multiraft: Arc<MultiRaft<KVAppType, KVTransport>>,
+        kv_storage: MemKvStorage,
+    ) -> Self {
+        Self {
+            multiraft,
+            kv_storage,
+        }
+    }
+
+    pub async fn serve(self: Arc<Self>, addr: SocketAddr) -> Result<(), hyper::Error> {
+        let make_svc = make_service_fn(move |_| {
+            let admin = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let admin = admin.clone();
+                    async move { Ok::<_, Infallible>(admin.handle(req).await) }
+                }))
+            }
+        });
+        Server::bind(&addr).serve(make_svc).await
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        let (status, body) = match self.route(&req).await {
+            Ok(body) => (StatusCode::OK, body),
+            Err((status, reason)) => (status, json!({ "error": reason })),
+        };
+        Response::builder()
+            .status(status)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    }
+
+    async fn route(&self, req: &Request<Body>) -> Result<Value, (StatusCode, String)> {
+        let path = req
+            .uri()
+            .path()
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        let segments = path.iter().map(String::as_str).collect::<Vec<_>>();
+        match (req.method(), segments.as_slice()) {
+            (&Method::GET, ["status"]) => self.status().await,
+            (&Method::GET, ["topology"]) => self.topology().await,
+            (&Method::GET, ["groups", group]) => self.group(parse(group)?).await,
+            (&Method::POST, ["groups", group, "leader"]) => {
+                self.transfer_leader(parse(group)?).await
+            }
+            (&Method::POST, ["groups", group, "snapshot"]) => {
+                self.build_snapshot(parse(group)?).await
+            }
+            (&Method::POST, ["groups", group, "replicas", node]) => {
+                self.add_replica(parse(group)?, parse(node)?).await
+            }
+            (&Method::DELETE, ["groups", group, "replicas", node, replica]) => {
+                self.remove_replica(parse(group)?, parse(node)?, parse(replica)?)
+                    .await
+            }
+            _ => Err((StatusCode::NOT_FOUND, "not found".to_owned())),
+        }
+    }
+
+    async fn status(&self) -> Result<Value, (StatusCode, String)> {
+        let status = self.multiraft.node_status().await.map_err(internal)?;
+        Ok(json!({
+            "node_id": status.node_id,
+            "leaders": status.leaders,
+            "followers": status.followers,
+            "candidates": status.candidates,
+            "parked": status.parked,
+            "pending_proposals": status.pending_proposals,
+            "apply_backlog": status.apply_backlog,
+            "storage_bytes": status.storage_bytes,
+            "peers": status.peers.iter().map(|peer| json!({
+                "node_id": peer.node_id,
+                "groups": peer.groups,
+                "last_contact_ms": peer.last_contact.map(|d| d.as_millis() as u64),
+            })).collect::<Vec<_>>(),
+            "uptime_secs": status.uptime.as_secs(),
+        }))
+    }
+
+    async fn topology(&self) -> Result<Value, (StatusCode, String)> {
+        let topology = self.multiraft.topology().await.map_err(internal)?;
+        Ok(topology_json(&topology))
+    }
+
+    async fn group(&self, group_id: u64) -> Result<Value, (StatusCode, String)> {
+        let state = self.multiraft.group_state(group_id).ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("group {} not found", group_id),
+            )
+        })?;
+        let (applied_index, applied_term) = self.kv_storage.get_applied(group_id);
+        Ok(json!({
+            "group_id": group_id,
+            "replica_id": state.get_replica_id(),
+            "role": format!("{:?}", state.get_role()),
+            "leader_id": state.get_leader_id(),
+            "commit_index": state.get_commit_index(),
+            "applied_index": applied_index,
+            "applied_term": applied_term,
+            "keys": self.kv_storage.len(group_id),
+        }))
+    }
+
+    /// The replica on the node campaigns to take over the leadership.
+    async fn transfer_leader(&self, group_id: u64) -> Result<Value, (StatusCode, String)> {
+        self.multiraft
+            .campaign_group(group_id)
+            .await
+            .map_err(internal)?;
+        Ok(json!({ "group_id": group_id }))
+    }
+
+    async fn build_snapshot(&self, group_id: u64) -> Result<Value, (StatusCode, String)> {
+        let state = self.multiraft.group_state(group_id).ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("group {} not found", group_id),
+            )
+        })?;
+        let (applied_index, applied_term) = self.kv_storage.get_applied(group_id);
+        self.kv_storage
+            .build_snapshot(
+                group_id,
+                state.get_replica_id(),
+                applied_index,
+                applied_term,
+                state.get_conf_state(),
+            )
+            .await
+            .map_err(internal)?;
+        Ok(json!({ "group_id": group_id, "index": applied_index, "term": applied_term }))
+    }
+
+    async fn add_replica(
+        &self,
+        group_id: u64,
+        node_id: u64,
+    ) -> Result<Value, (StatusCode, String)> {
+        let (replica_id, _, _) = self
+            .multiraft
+            .add_replica(group_id, node_id, None, None)
+            .await
+            .map_err(internal)?;
+        Ok(json!({ "group_id": group_id, "node_id": node_id, "replica_id": replica_id }))
+    }
+
+    async fn remove_replica(
+        &self,
+        group_id: u64,
+        node_id: u64,
+        replica_id: u64,
+    ) -> Result<Value, (StatusCode, String)> {
+        let data = MembershipChangeData {
+            changes: vec![SingleMembershipChange {
+                node_id,
+                replica_id,
+                change_type: ConfChangeType::RemoveNode as i32,
+                witness: false,
+            }],
+            replicas: vec![ReplicaDesc {
+                node_id,
+                group_id,
+                replica_id,
+                witness: false,
+            }],
+            ..Default::default()
+        };
+        self.multiraft
+            .membership(group_id, None, None, data)
+            .await
+            .map_err(internal)?;
+        Ok(json!({ "group_id": group_id, "node_id": node_id, "replica_id": replica_id }))
+    }
+}
+
+fn parse(segment: &str) -> Result<u64, (StatusCode, String)> {
+    segment
+        .parse()
+        .map_err(|_| (StatusCode::BAD_REQUEST, format!("invalid id {}", segment)))
+}
+
+fn internal(err: impl std::fmt::Display) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+fn topology_json(topology: &NodeTopology) -> Value {
+    json!({
+        "node_id": topology.node_id,
+        "groups": topology.groups.iter().map(|group| json!({
+            "group_id": group.group_id,
+            "replica_id": group.replica_id,
+            "role": format!("{:?}", group.role),
+            "leader_id": group.leader_id,
+            "leader_node_id": group.leader_node_id,
+            "parked": group.parked,
+            "replicas": group.replicas.iter().map(|replica| json!({
+                "replica_id": replica.replica_id,
+                "node_id": replica.node_id,
+                "learner": replica.learner,
+                "witness": replica.witness,
+                "reachable": replica.reachable,
+                "matched": replica.matched,
+            })).collect::<Vec<_>>(),
+        })).collect::<Vec<_>>(),
+    })
+}
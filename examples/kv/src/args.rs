@@ -15,7 +15,6 @@ pub fn parse_nodes(
     Ok(nodes)
 }
 
-
 /// Define server command args
 #[derive(clap::Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -39,6 +38,15 @@ pub struct ServerArgs {
     /// Server should know other nodes.
     #[arg(long)]
     pub nodes: String,
+
+    /// Admin http listend network address, the admin server is not started
+    /// if it's empty.
+    #[arg(long, default_value = "")]
+    pub admin_addr: String,
+
+    /// The threads encoding and sending the raft messages.
+    #[arg(long, default_value_t = 2)]
+    pub encode_workers: usize,
 }
 
 impl ServerArgs {
@@ -55,10 +63,18 @@ impl ServerArgs {
             return Err(format!("{} is not valid network addr", self.addr));
         }
 
+        if !self.admin_addr.is_empty() && self.admin_addr.parse::<std::net::SocketAddr>().is_err() {
+            return Err(format!("{} is not valid network addr", self.admin_addr));
+        }
+
         if let Err(err) = parse_nodes(&self.nodes) {
             return Err(err.to_string());
         }
 
+        if self.encode_workers == 0 {
+            return Err("encode_workers must be more than 0".to_string());
+        }
+
         Ok(())
     }
 }
@@ -83,4 +99,3 @@ impl ClientArgs {
         Ok(())
     }
 }
-
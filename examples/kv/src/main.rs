@@ -1,5 +1,6 @@
 #![feature(type_alias_impl_trait)]
 #![feature(impl_trait_in_assoc_type)]
+mod admin;
 mod args;
 mod server;
 mod state_machine;
@@ -10,8 +11,6 @@ pub mod grpc {
     tonic::include_proto!("kv");
 }
 
-use std::time::Duration;
-
 use args::ServerArgs;
 use clap::Parser;
 use oceanraft::log;
@@ -27,21 +26,5 @@ async fn main() {
     let mut server = server::KVServer::new(arg).await;
     server.event_consumer();
     server.start();
-    tokio::time::sleep(Duration::from_secs(5)).await;
-    if server.node_id == 1 {
-        let mut members = vec![];
-        for (peer_id, _) in server.peers.iter() {
-            if *peer_id == server.node_id {
-                continue;
-            }
-
-            members.push((*peer_id, *peer_id));
-        }
-
-        // for (peer_id, _) in server.peers.iter() {
-        //     println!("group({}) add members({:?})", *peer_id, members);
-        //     server.add_members(*peer_id, members.clone()).await;
-        // }
-    }
     server.join().await;
 }
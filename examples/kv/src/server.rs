@@ -3,6 +3,7 @@ use std::collections::HashMap;
 use std::hash::Hash;
 use std::hash::Hasher;
 use std::sync::Arc;
+use std::time::Duration;
 
 use oceanraft::prelude::CreateGroupRequest;
 use oceanraft::prelude::ReplicaDesc;
@@ -14,7 +15,9 @@ use oceanraft::storage::Storage;
 use oceanraft::storage::StorageExt;
 use oceanraft::transport::MultiRaftServiceImpl;
 use oceanraft::transport::MultiRaftServiceServer;
+use oceanraft::transport::OffloadTransport;
 use oceanraft::Config;
+use oceanraft::ConsistencyLevel;
 use oceanraft::MultiRaft;
 
 use tokio::task::JoinHandle;
@@ -23,15 +26,19 @@ use tonic::Request;
 use tonic::Response;
 use tonic::Status;
 
+use crate::admin::AdminService;
 use crate::args::parse_nodes;
 use crate::args::ServerArgs;
 use crate::grpc::kv_service_server::KvService;
 use crate::grpc::kv_service_server::KvServiceServer;
+use crate::grpc::GetRequest;
+use crate::grpc::GetResponse;
 use crate::grpc::PutRequest;
 use crate::grpc::PutResponse;
 use crate::state_machine::KVStateMachine;
 use crate::storage::MemKvStorage;
 use crate::transport::GRPCTransport;
+use crate::transport::KVTransport;
 
 use oceanraft::define_multiraft;
 
@@ -58,15 +65,49 @@ pub struct KVResponse {
     pub term: u64,
 }
 
+/// The staleness of the follower reads, the follower serves the read if it
+/// heard from the leader within the duration.
+const FOLLOWER_READ_STALENESS: Duration = Duration::from_secs(1);
+
+/// The max duration for the replica to apply the entries committed before the
+/// read is served.
+const READ_APPLY_TIMEOUT: Duration = Duration::from_secs(3);
+
 pub struct KvServiceImpl {
-    multiraft: Arc<MultiRaft<KVAppType, GRPCTransport>>,
+    multiraft: Arc<MultiRaft<KVAppType, KVTransport>>,
+    kv_storage: MemKvStorage,
+    // The number of groups, the keys are partitioned to the groups.
+    groups: u64,
+}
+
+impl KvServiceImpl {
+    /// Wait the kv storage applied the entries committed when the read is
+    /// confirmed.
+    async fn wait_applied(&self, group_id: u64) -> Result<(), String> {
+        let state = self
+            .multiraft
+            .group_state(group_id)
+            .ok_or_else(|| format!("group {} not found", group_id))?;
+        let commit_index = state.get_commit_index();
+        let deadline = tokio::time::Instant::now() + READ_APPLY_TIMEOUT;
+        while self.kv_storage.get_applied(group_id).0 < commit_index {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(format!(
+                    "group {} apply timeout, commit index = {}",
+                    group_id, commit_index
+                ));
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok(())
+    }
 }
 
 #[tonic::async_trait]
 impl KvService for KvServiceImpl {
     async fn put(&self, request: Request<PutRequest>) -> Result<Response<PutResponse>, Status> {
         let put_req = request.into_inner();
-        let group_id = partition(&put_req.key, 3);
+        let group_id = partition(&put_req.key, self.groups);
         let res = self
             .multiraft
             .write(
@@ -84,6 +125,31 @@ impl KvService for KvServiceImpl {
         resp.messages = format!("{:?}", res);
         Ok(Response::new(resp))
     }
+
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        let get_req = request.into_inner();
+        let group_id = partition(&get_req.key, self.groups);
+        let consistency = if get_req.follower_read {
+            ConsistencyLevel::BoundedStaleness(FOLLOWER_READ_STALENESS)
+        } else {
+            ConsistencyLevel::Linearizable
+        };
+
+        let mut resp = GetResponse::default();
+        if let Err(err) = self.multiraft.read(group_id, consistency, None).await {
+            resp.messages = format!("{:?}", err);
+            return Ok(Response::new(resp));
+        }
+        if let Err(reason) = self.wait_applied(group_id).await {
+            resp.messages = reason;
+            return Ok(Response::new(resp));
+        }
+        if let Some(value) = self.kv_storage.get(group_id, &get_req.key) {
+            resp.found = true;
+            resp.value = value;
+        }
+        Ok(Response::new(resp))
+    }
 }
 
 fn partition(key: &str, partition: u64) -> u64 {
@@ -103,9 +169,11 @@ pub struct KVServer {
 
     kv_storage: MemKvStorage,
 
-    multiraft: Arc<MultiRaft<KVAppType, GRPCTransport>>,
+    multiraft: Arc<MultiRaft<KVAppType, KVTransport>>,
 
     jh: Option<JoinHandle<Result<(), tonic::transport::Error>>>,
+
+    admin_jh: Option<JoinHandle<Result<(), hyper::Error>>>,
 }
 
 impl KVServer {
@@ -114,6 +182,8 @@ impl KVServer {
         let mut cfg = Config::default();
         cfg.node_id = arg.node_id;
         cfg.tick_interval = 100;
+        // the replicas added by the membership changes are created on demand.
+        cfg.buffer_unknown_group_msgs = false;
 
         let kv_storage = MemKvStorage::new();
         let rock_storage = RockStore::new(
@@ -124,10 +194,11 @@ impl KVServer {
         );
         let kv_state_machine = KVStateMachine::new(rock_storage.clone(), kv_storage.clone());
 
-        let grpc_transport = GRPCTransport::new(peers.clone());
-        let multiraft = MultiRaft::<KVAppType, GRPCTransport>::new(
+        let transport =
+            OffloadTransport::new(GRPCTransport::new(peers.clone()), arg.encode_workers);
+        let multiraft = MultiRaft::<KVAppType, KVTransport>::new(
             cfg,
-            grpc_transport,
+            transport,
             rock_storage.clone(),
             kv_state_machine,
             None,
@@ -142,6 +213,7 @@ impl KVServer {
             kv_storage,
             multiraft: Arc::new(multiraft),
             jh: None,
+            admin_jh: None,
         };
 
         // every node initial replica desc
@@ -154,6 +226,7 @@ impl KVServer {
                     node_id,
                     group_id: *group_id,
                     replica_id,
+                    witness: false,
                 };
 
                 println!(
@@ -184,7 +257,7 @@ impl KVServer {
                 snap.mut_metadata().mut_conf_state().voters = voters.clone();
                 snap.mut_metadata().index = 1;
                 snap.mut_metadata().term = 1;
-                gs.install_snapshot(snap).unwrap();
+                gs.install_snapshot(snap).await.unwrap();
 
                 if let Err(err) = server
                     .multiraft
@@ -193,6 +266,12 @@ impl KVServer {
                         replica_id,
                         replicas: replicas.clone(),
                         applied_hint: 0,
+                        priority: 0,
+                        lazy: false,
+                        storage_domain: String::new(),
+                        idempotency_key: String::new(),
+                        archive: false,
+                        learners: vec![],
                     })
                     .await
                 {
@@ -204,25 +283,6 @@ impl KVServer {
         server
     }
 
-    // pub async fn add_members(&self, multiraft: &Weak<MultiRaft<KVAppType, GRPCTransport>>, storage: &RockStore<SledStorage, SledStorage>, group_id: u64, members: Vec<(u64, u64)>) {
-    //     storage
-
-    //     let mut data = MembershipChangeData::default();
-    //     for (node_id, replica_id) in members.iter() {
-    //         let mut change = SingleMembershipChange::default();
-    //         change.set_node_id(*node_id);
-    //         change.set_replica_id(*replica_id);
-    //         change.set_change_type(ConfChangeType::AddNode);
-    //         data.changes.push(change);
-    //     }
-    //     let term = None;
-    //     let context = None;
-    //     self.multiraft
-    //         .membership(group_id, term, context, data)
-    //         .await
-    //         .unwrap();
-    // }
-
     pub fn event_consumer(&self) {
         let rx = self.multiraft.subscribe();
         tokio::spawn(async move {
@@ -232,7 +292,7 @@ impl KVServer {
                     Ok(event) => event,
                 };
 
-                match event {
+                match &*event {
                     oceanraft::Event::LederElection(_event) => {
                         // TODO: check and add members if need
                     }
@@ -245,6 +305,9 @@ impl KVServer {
     /// Start server in spearted tokio task.
     pub fn start(&mut self) {
         self.start_server();
+        if !self.arg.admin_addr.is_empty() {
+            self.start_admin_server();
+        }
     }
 
     fn start_server(&mut self) {
@@ -252,6 +315,8 @@ impl KVServer {
         let addr = self.arg.addr.clone();
         let kv_service = KvServiceServer::new(KvServiceImpl {
             multiraft: self.multiraft.clone(),
+            kv_storage: self.kv_storage.clone(),
+            groups: self.peers.len() as u64,
         });
         let multiraft_service =
             MultiRaftServiceServer::new(MultiRaftServiceImpl::new(self.multiraft.message_sender()));
@@ -266,7 +331,23 @@ impl KVServer {
         self.jh = Some(jh)
     }
 
+    fn start_admin_server(&mut self) {
+        let addr = self.arg.admin_addr.parse().unwrap();
+        let admin = Arc::new(AdminService::new(
+            self.multiraft.clone(),
+            self.kv_storage.clone(),
+        ));
+        self.admin_jh = Some(tokio::spawn(admin.serve(addr)));
+    }
+
+    /// Serve until the server exited or the process is interrupted, then the
+    /// node is shut down with its transport and storage.
     pub async fn join(mut self) {
-        self.jh.take().unwrap().await.unwrap().unwrap();
+        let jh = self.jh.take().unwrap();
+        tokio::select! {
+            res = jh => res.unwrap().unwrap(),
+            _ = tokio::signal::ctrl_c() => {}
+        }
+        self.multiraft.shutdown().await.unwrap();
     }
 }
@@ -34,7 +34,7 @@ impl StateMachine<KVData, KVResponse> for KVStateMachine {
     ) -> Self::ApplyFuture<'life0> {
         async move {
             for apply in applys {
-                let apply_index = apply.get_index();
+                let (apply_index, apply_term) = (apply.get_index(), apply.get_term());
                 println!(
                     "group({}), replica({}) apply index = {}",
                     group_id, replica_id, apply_index
@@ -46,7 +46,8 @@ impl StateMachine<KVData, KVResponse> for KVStateMachine {
                             index: apply_index,
                             term: apply.term,
                         };
-                        self.kv_storage.put(apply.data.key, apply.data.value);
+                        self.kv_storage
+                            .put(group_id, apply.data.key, apply.data.value);
                         // TODO: this call as method
                         apply
                             .tx
@@ -63,14 +64,27 @@ impl StateMachine<KVData, KVResponse> for KVStateMachine {
                             )))
                         });
                     }
+                    Apply::Admin(apply) => {
+                        apply.tx.map(|tx| {
+                            tx.send(Ok((
+                                KVResponse {
+                                    index: apply.index,
+                                    term: apply.term,
+                                },
+                                None,
+                            )))
+                        });
+                    }
                 }
+                self.kv_storage
+                    .set_applied(group_id, apply_index, apply_term);
                 // TODO: consider more easy api
                 let gs = self
                     .storage
                     .group_storage(group_id, replica_id)
                     .await
                     .unwrap();
-                gs.set_applied(apply_index).unwrap();
+                gs.set_applied(apply_index).await.unwrap();
             }
         }
     }
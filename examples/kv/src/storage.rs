@@ -1,56 +1,133 @@
 use std::collections::HashMap;
+use std::future::Future;
 use std::sync::Arc;
 use std::sync::RwLock;
 
 use oceanraft::prelude::ConfState;
+use oceanraft::storage::Error;
 use oceanraft::storage::RaftSnapshotReader;
 use oceanraft::storage::RaftSnapshotWriter;
 use oceanraft::storage::Result;
 
-type MemStorage = Arc<RwLock<HashMap<String, Vec<u8>>>>;
+/// The key values and the applied state of a group.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct GroupData {
+    pub applied_index: u64,
+    pub applied_term: u64,
+    pub kvs: HashMap<String, Vec<u8>>,
+}
+
+type MemStorage = Arc<RwLock<HashMap<u64, GroupData>>>;
 
+/// The in-memory kv storage of the groups on the node, which is also the
+/// snapshot reader and writer of the log storage. The snapshot of a group is
+/// the json of its `GroupData`.
 #[derive(Clone)]
 pub struct MemKvStorage {
     pub mem_map: MemStorage,
+    /// The snapshots built by `build_snapshot`, which are sent to the
+    /// followers lagging behind.
+    snapshots: Arc<RwLock<HashMap<u64, Vec<u8>>>>,
 }
 
 impl MemKvStorage {
     pub fn new() -> Self {
         Self {
             mem_map: MemStorage::default(),
+            snapshots: Default::default(),
         }
     }
 
-    pub fn put(&self, key: String, value: Vec<u8>) {
+    pub fn put(&self, group_id: u64, key: String, value: Vec<u8>) {
+        let mut wl = self.mem_map.write().unwrap();
+        wl.entry(group_id).or_default().kvs.insert(key, value);
+    }
+
+    pub fn get(&self, group_id: u64, key: &str) -> Option<Vec<u8>> {
+        let rl = self.mem_map.read().unwrap();
+        rl.get(&group_id)
+            .and_then(|data| data.kvs.get(key).cloned())
+    }
+
+    pub fn set_applied(&self, group_id: u64, index: u64, term: u64) {
         let mut wl = self.mem_map.write().unwrap();
-        let _ = wl.entry(key).or_insert(value);
+        let data = wl.entry(group_id).or_default();
+        data.applied_index = index;
+        data.applied_term = term;
     }
 
-    pub fn get(&self, key: &str) -> Option<Vec<u8>> {
+    /// Returns the applied (index, term) of the group.
+    pub fn get_applied(&self, group_id: u64) -> (u64, u64) {
         let rl = self.mem_map.read().unwrap();
-        rl.get(key).map(|v| v.clone())
+        rl.get(&group_id)
+            .map_or((0, 0), |data| (data.applied_index, data.applied_term))
+    }
+
+    pub fn len(&self, group_id: u64) -> usize {
+        let rl = self.mem_map.read().unwrap();
+        rl.get(&group_id).map_or(0, |data| data.kvs.len())
+    }
+
+    fn serialize(&self, group_id: u64) -> Result<Vec<u8>> {
+        let rl = self.mem_map.read().unwrap();
+        let data = rl.get(&group_id).cloned().unwrap_or_default();
+        serde_json::to_vec(&data).map_err(|err| Error::Other(Box::new(err)))
     }
 }
 
 impl RaftSnapshotReader for MemKvStorage {
-    fn load_snapshot(&self, group_id: u64, replica_id: u64) -> Result<Vec<u8>> {
-        Ok(vec![])
+    fn load_snapshot(&self, group_id: u64, _replica_id: u64) -> Result<Vec<u8>> {
+        match self.snapshots.read().unwrap().get(&group_id) {
+            Some(data) => Ok(data.clone()),
+            None => self.serialize(group_id),
+        }
     }
 }
 
 impl RaftSnapshotWriter for MemKvStorage {
+    type BuildSnapshotFuture<'life0>
+        = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
     fn build_snapshot(
         &self,
         group_id: u64,
-        replica_id: u64,
-        applied_index: u64,
-        applied_term: u64,
-        last_conf_state: ConfState,
-    ) -> Result<()> {
-        todo!()
+        _replica_id: u64,
+        _applied_index: u64,
+        _applied_term: u64,
+        _last_conf_state: ConfState,
+    ) -> Self::BuildSnapshotFuture<'_> {
+        async move {
+            let data = self.serialize(group_id)?;
+            self.snapshots.write().unwrap().insert(group_id, data);
+            Ok(())
+        }
     }
 
-    fn install_snapshot(&self, group_id: u64, replica_id: u64, data: Vec<u8>) -> Result<()> {
-        Ok(())
+    type InstallSnapshotDataFuture<'life0>
+        = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn install_snapshot(
+        &self,
+        group_id: u64,
+        _replica_id: u64,
+        data: Vec<u8>,
+    ) -> Self::InstallSnapshotDataFuture<'_> {
+        async move {
+            if data.is_empty() {
+                return Ok(());
+            }
+            let group_data = serde_json::from_slice::<GroupData>(&data)
+                .map_err(|err| Error::Other(Box::new(err)))?;
+            println!(
+                "group({}) restore {} keys from snapshot at index = {}",
+                group_id,
+                group_data.kvs.len(),
+                group_data.applied_index
+            );
+            self.mem_map.write().unwrap().insert(group_id, group_data);
+            Ok(())
+        }
     }
 }
@@ -1,39 +1,120 @@
 use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
 
 use oceanraft::prelude::MultiRaftMessage;
-use oceanraft::transport::{MultiRaftServiceClient, Transport};
+use oceanraft::transport::{send_encoded, EncodedTransport, MessageCompressor, OffloadTransport};
+use oceanraft::Lifecycle;
+use tokio::runtime::Handle;
+use tokio::sync::Notify;
+use tonic::transport::Endpoint;
+
+/// The raft messages larger than the threshold are compressed.
+const COMPRESSION_THRESHOLD: usize = 4096;
+
+/// The raft messages are compressed, encoded and sent on the threads of
+/// `OffloadTransport` instead of the node loop.
+pub type KVTransport = OffloadTransport<GRPCTransport>;
 
 #[derive(Clone)]
 pub struct GRPCTransport {
+    runtime: Handle,
     peers: Arc<HashMap<u64, String>>,
+    compressor: Arc<MessageCompressor>,
+    /// The number of messages being sent, which are waited by `flush`.
+    sending: Arc<AtomicUsize>,
+    sent: Arc<Notify>,
+    stopped: Arc<AtomicBool>,
 }
 
 impl GRPCTransport {
+    /// Must be called in the runtime, the messages are sent by the tasks
+    /// spawned on it.
     pub fn new(peers: Arc<HashMap<u64, String>>) -> Self {
-        Self { peers }
+        Self {
+            runtime: Handle::current(),
+            peers,
+            compressor: Arc::new(MessageCompressor::new(COMPRESSION_THRESHOLD)),
+            sending: Arc::default(),
+            sent: Arc::default(),
+            stopped: Arc::default(),
+        }
     }
 }
 
-impl Transport for GRPCTransport {
-    fn send(&self, msg: MultiRaftMessage) -> Result<(), oceanraft::Error> {
-        let to = msg.to_node;
+impl EncodedTransport for GRPCTransport {
+    fn before_encode(&self, msg: MultiRaftMessage) -> MultiRaftMessage {
+        self.compressor.compress(msg)
+    }
+
+    fn send_encoded(&self, _: u64, to: u64, data: Vec<u8>) -> Result<(), oceanraft::Error> {
+        // the messages after the transport stopped are dropped.
+        if self.stopped.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
         let addr = self.peers.get(&to).unwrap().to_string();
+        let compressor = self.compressor.clone();
+        let sending = self.sending.clone();
+        let sent = self.sent.clone();
 
-        tokio::spawn(async move {
-            let client = MultiRaftServiceClient::connect(addr.to_string()).await;
-            match client {
+        sending.fetch_add(1, Ordering::SeqCst);
+        self.runtime.spawn(async move {
+            let channel = match Endpoint::from_shared(addr) {
+                Err(err) => Err(err),
+                Ok(endpoint) => endpoint.connect().await,
+            };
+            match channel {
                 Err(err) => {
                     // println!("connect({}) got err({:?})",addr.to_string(), err);
                 }
-                Ok(mut client) => {
-                    if let Err(err) = client.send(msg).await {
-                        println!("err({:?})", err);
-                    }
-                }
+                Ok(channel) => match send_encoded(channel, data).await {
+                    Err(err) => println!("err({:?})", err),
+                    Ok(response) => compressor.observe_response(to, &response),
+                },
+            }
+            if sending.fetch_sub(1, Ordering::SeqCst) == 1 {
+                sent.notify_waiters();
             }
         });
 
         Ok(())
     }
 }
+
+/// Flushing waits the messages being sent, stopping drops the messages sent
+/// later.
+impl Lifecycle for GRPCTransport {
+    type StartFuture<'life0> = impl Future<Output = Result<(), oceanraft::Error>> + 'life0
+    where
+        Self: 'life0;
+    fn start(&self) -> Self::StartFuture<'_> {
+        async move { Ok(()) }
+    }
+
+    type FlushFuture<'life0> = impl Future<Output = Result<(), oceanraft::Error>> + 'life0
+    where
+        Self: 'life0;
+    fn flush(&self) -> Self::FlushFuture<'_> {
+        async move {
+            loop {
+                let sent = self.sent.notified();
+                if self.sending.load(Ordering::SeqCst) == 0 {
+                    return Ok(());
+                }
+                sent.await;
+            }
+        }
+    }
+
+    type StopFuture<'life0> = impl Future<Output = Result<(), oceanraft::Error>> + 'life0
+    where
+        Self: 'life0;
+    fn stop(&self) -> Self::StopFuture<'_> {
+        async move {
+            self.stopped.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Runs the kv example as a 3-node cluster and drives the writes, the follower
+//! reads and the admin operations against it.
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::Child;
+use std::process::Command;
+use std::time::Duration;
+use std::time::Instant;
+
+use serde_json::Value;
+use tokio::io::AsyncReadExt;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use tonic::transport::Channel;
+
+use crate::grpc::kv_service_client::KvServiceClient;
+use crate::grpc::GetRequest;
+use crate::grpc::PutRequest;
+
+pub mod grpc {
+    tonic::include_proto!("kv");
+}
+
+const NODES: u64 = 3;
+
+const WAIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn free_port() -> u16 {
+    TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+struct Node {
+    addr: String,
+    admin_addr: String,
+    child: Child,
+}
+
+/// The processes of the nodes are killed and the data is removed on drop.
+struct Cluster {
+    nodes: Vec<Node>,
+    dir: PathBuf,
+}
+
+impl Cluster {
+    fn start() -> Self {
+        let dir = std::env::temp_dir().join(format!("oceanraft_kv_cluster_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let addrs = (0..NODES)
+            .map(|_| {
+                (
+                    format!("127.0.0.1:{}", free_port()),
+                    format!("127.0.0.1:{}", free_port()),
+                )
+            })
+            .collect::<Vec<_>>();
+        let peers = addrs
+            .iter()
+            .enumerate()
+            .map(|(i, (addr, _))| format!("{}=http://{}", i + 1, addr))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let nodes = addrs
+            .into_iter()
+            .enumerate()
+            .map(|(i, (addr, admin_addr))| {
+                let node_id = i as u64 + 1;
+                let log_path = dir.join(format!("log_{}", node_id));
+                let kv_path = dir.join(format!("kv_{}", node_id));
+                std::fs::create_dir_all(&log_path).unwrap();
+                std::fs::create_dir_all(&kv_path).unwrap();
+                let child = Command::new(env!("CARGO_BIN_EXE_oceanraft-kv-example"))
+                    .arg(format!("--node-id={}", node_id))
+                    .arg(format!("--addr={}", addr))
+                    .arg(format!("--admin-addr={}", admin_addr))
+                    .arg(format!("--nodes={}", peers))
+                    .arg(format!("--log-storage-path={}", log_path.display()))
+                    .arg(format!("--kv-storage-path={}", kv_path.display()))
+                    .spawn()
+                    .unwrap();
+                Node {
+                    addr,
+                    admin_addr,
+                    child,
+                }
+            })
+            .collect();
+
+        Self { nodes, dir }
+    }
+
+    fn node(&self, node_id: u64) -> &Node {
+        &self.nodes[node_id as usize - 1]
+    }
+
+    async fn client(&self, node_id: u64) -> KvServiceClient<Channel> {
+        let endpoint = format!("http://{}", self.node(node_id).addr);
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+        loop {
+            match KvServiceClient::connect(endpoint.clone()).await {
+                Ok(client) => return client,
+                Err(err) if Instant::now() >= deadline => panic!("connect {}: {}", endpoint, err),
+                Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+            }
+        }
+    }
+
+    /// Put the key to the leader of the group the key partitioned to, the
+    /// nodes are tried in turn until the leader accepts it.
+    async fn put(&self, key: &str, value: &[u8]) {
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+        loop {
+            for node_id in 1..=NODES {
+                let resp = self
+                    .client(node_id)
+                    .await
+                    .put(PutRequest {
+                        key: key.to_owned(),
+                        value: value.to_vec(),
+                    })
+                    .await
+                    .unwrap()
+                    .into_inner();
+                if resp.messages.starts_with("Ok") {
+                    return;
+                }
+            }
+            assert!(Instant::now() < deadline, "put {} timeout", key);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    async fn follower_read(&self, node_id: u64, key: &str) -> Vec<u8> {
+        let mut client = self.client(node_id).await;
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+        loop {
+            let resp = client
+                .get(GetRequest {
+                    key: key.to_owned(),
+                    follower_read: true,
+                })
+                .await
+                .unwrap()
+                .into_inner();
+            if resp.found {
+                return resp.value;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "node {}: read {} timeout: {}",
+                node_id,
+                key,
+                resp.messages
+            );
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Send the request to the admin endpoint of the node, returns the status
+    /// code and the json body.
+    async fn admin(&self, node_id: u64, method: &str, path: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(&self.node(node_id).admin_addr)
+            .await
+            .unwrap();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\nContent-Length: 0\r\n\r\n",
+            method, path
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    /// Poll the admin endpoint until the body satisfies the predicate.
+    async fn wait_admin<F>(&self, node_id: u64, path: &str, predicate: F) -> Value
+    where
+        F: Fn(&Value) -> bool,
+    {
+        let deadline = Instant::now() + WAIT_TIMEOUT;
+        loop {
+            let (status, body) = self.admin(node_id, "GET", path).await;
+            if status == 200 && predicate(&body) {
+                return body;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "node {}: wait {} timeout: {}",
+                node_id,
+                path,
+                body
+            );
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+impl Drop for Cluster {
+    fn drop(&mut self) {
+        for node in self.nodes.iter_mut() {
+            let _ = node.child.kill();
+            let _ = node.child.wait();
+        }
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_kv_cluster() {
+    let cluster = Cluster::start();
+
+    // writes are accepted by the leaders and the followers serve the reads.
+    let keys = (0..20).map(|i| format!("key_{}", i)).collect::<Vec<_>>();
+    for key in keys.iter() {
+        cluster.put(key, key.as_bytes()).await;
+    }
+    for node_id in 1..=NODES {
+        for key in keys.iter() {
+            assert_eq!(cluster.follower_read(node_id, key).await, key.as_bytes());
+        }
+    }
+
+    // transfer the leadership of group 1 to node 2.
+    let (status, body) = cluster.admin(2, "POST", "/groups/1/leader").await;
+    assert_eq!(status, 200, "{}", body);
+    cluster
+        .wait_admin(2, "/groups/1", |body| body["role"] == "Leader")
+        .await;
+
+    // build the snapshot of group 1 on the leader.
+    let (status, body) = cluster.admin(2, "POST", "/groups/1/snapshot").await;
+    assert_eq!(status, 200, "{}", body);
+    assert!(body["index"].as_u64().unwrap() > 0);
+
+    // remove the replica of group 1 on node 3, the replica id equals the node id.
+    let (status, body) = cluster.admin(2, "DELETE", "/groups/1/replicas/3/3").await;
+    assert_eq!(status, 200, "{}", body);
+    cluster
+        .wait_admin(2, "/topology", |body| {
+            body["groups"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|group| group["group_id"] == 1)
+                .all(|group| {
+                    group["replicas"]
+                        .as_array()
+                        .unwrap()
+                        .iter()
+                        .all(|replica| replica["node_id"] != 3)
+                })
+        })
+        .await;
+
+    // the cluster keeps serving the writes after the membership change.
+    for key in keys.iter() {
+        cluster.put(key, b"updated").await;
+    }
+    for key in keys.iter() {
+        assert_eq!(cluster.follower_read(2, key).await, b"updated");
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use futures::StreamExt;
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::prelude::Snapshot;
+use oceanraft::storage::MemStorage;
+use oceanraft::storage::MultiRaftMemoryStorage;
+use oceanraft::storage::MultiRaftStorage;
+use oceanraft::storage::StorageExt;
+use oceanraft::transport::LocalTransport;
+use oceanraft::Config;
+use oceanraft::Error;
+use oceanraft::MultiRaft;
+use oceanraft::MultiRaftMessageSenderImpl;
+use oceanraft::MultiRaftTypeSpecialization;
+use oceanraft::ProposeError;
+use tokio::time::sleep;
+
+use crate::command::Command;
+use crate::command::CommandResponse;
+use crate::state_machine::MetaStateMachine;
+use crate::store::MetaStore;
+
+const MAX_RETRIES: usize = 100;
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+pub struct MetaTypes;
+
+impl MultiRaftTypeSpecialization for MetaTypes {
+    type D = Command;
+    type R = CommandResponse;
+    type M = MetaStateMachine;
+    type S = MemStorage;
+    type MS = MultiRaftMemoryStorage;
+}
+
+type MetaNode = MultiRaft<MetaTypes, LocalTransport<MultiRaftMessageSenderImpl>>;
+
+/// Cluster runs the nodes in the process, the nodes communicate with each
+/// other by `LocalTransport`. Node ids start from 1.
+pub struct Cluster {
+    nodes: Vec<MetaNode>,
+    storages: Vec<MultiRaftMemoryStorage>,
+    stores: Vec<MetaStore>,
+}
+
+impl Cluster {
+    pub async fn start(node_nums: u64) -> Result<Self, String> {
+        let transport = LocalTransport::new();
+        let mut nodes = vec![];
+        let mut storages = vec![];
+        let mut stores = vec![];
+        for node_id in 1..=node_nums {
+            let cfg = Config {
+                node_id,
+                election_tick: 10,
+                heartbeat_tick: 1,
+                tick_interval: 10,
+                // the replicas added by membership changes are created by the
+                // messages from the leader.
+                buffer_unknown_group_msgs: false,
+                ..Default::default()
+            };
+            let storage = MultiRaftMemoryStorage::new(node_id);
+            let store = MetaStore::new();
+            let node = MultiRaft::new(
+                cfg,
+                transport.clone(),
+                storage.clone(),
+                MetaStateMachine::new(node_id, store.clone()),
+                None,
+            )
+            .map_err(|err| err.to_string())?;
+            transport
+                .listen(
+                    node_id,
+                    format!("metastore://node/{}", node_id).as_str(),
+                    node.message_sender(),
+                )
+                .await?;
+            nodes.push(node);
+            storages.push(storage);
+            stores.push(store);
+        }
+
+        Ok(Self {
+            nodes,
+            storages,
+            stores,
+        })
+    }
+
+    /// Create the group with a replica on each of `node_ids` and wait the
+    /// replica on the first node elected.
+    pub async fn create_group(&self, group_id: u64, node_ids: &[u64]) -> Result<(), String> {
+        let replicas = node_ids
+            .iter()
+            .enumerate()
+            .map(|(i, node_id)| ReplicaDesc {
+                node_id: *node_id,
+                group_id,
+                replica_id: i as u64 + 1,
+                witness: false,
+            })
+            .collect::<Vec<_>>();
+        let voters = replicas
+            .iter()
+            .map(|replica| replica.replica_id)
+            .collect::<Vec<_>>();
+
+        for replica in replicas.iter() {
+            // bootstrap the membership of group by the snapshot.
+            let gs = self
+                .storage(replica.node_id)
+                .group_storage(group_id, replica.replica_id)
+                .await
+                .map_err(|err| err.to_string())?;
+            let mut ss = Snapshot::default();
+            ss.mut_metadata().mut_conf_state().voters = voters.clone();
+            ss.mut_metadata().index = 1;
+            ss.mut_metadata().term = 1;
+            gs.install_snapshot(ss)
+                .await
+                .map_err(|err| err.to_string())?;
+
+            self.node(replica.node_id)
+                .create_group(CreateGroupRequest {
+                    group_id,
+                    replica_id: replica.replica_id,
+                    replicas: replicas.clone(),
+                    ..Default::default()
+                })
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+
+        let first = self.node(node_ids[0]);
+        first
+            .campaign_group(group_id)
+            .await
+            .map_err(|err| err.to_string())?;
+        for _ in 0..MAX_RETRIES {
+            if first
+                .group_state(group_id)
+                .map_or(false, |state| state.is_leader())
+            {
+                return Ok(());
+            }
+            sleep(RETRY_INTERVAL).await;
+        }
+        Err(format!("group {} has no leader", group_id))
+    }
+
+    /// Add a replica of the group on the node by the membership change.
+    pub async fn add_replica(&self, group_id: u64, node_id: u64) -> Result<u64, String> {
+        self.retry(group_id, |node| async move {
+            node.add_replica(group_id, node_id, None, None)
+                .await
+                .map(|(replica_id, _, _)| replica_id)
+        })
+        .await
+    }
+
+    /// Propose the command to the leader of group, retry if the leader is
+    /// changed or unknown.
+    pub async fn write(&self, group_id: u64, command: Command) -> Result<CommandResponse, String> {
+        self.retry(group_id, |node| {
+            let command = command.clone();
+            async move {
+                node.write(group_id, 0, None, command)
+                    .await
+                    .map(|res| res.data)
+            }
+        })
+        .await
+    }
+
+    /// Confirm the leadership of group by the read index and wait the leader
+    /// to apply the committed entries, then the rows of the group can be read
+    /// from the returned store of the leader node.
+    pub async fn read_index(&self, group_id: u64) -> Result<&MetaStore, String> {
+        let node_id = self
+            .retry(group_id, |node| async move {
+                node.read_index(group_id, None).await.map(|_| node)
+            })
+            .await
+            .map(|node| self.node_id_of(node))?;
+        self.wait_applied(node_id, group_id).await?;
+        Ok(self.store(node_id))
+    }
+
+    /// Same as `read_index`, but the reads of the groups led by the same node
+    /// are confirmed together. Returns the stores in the order of `group_ids`.
+    pub async fn read_index_many(&self, group_ids: &[u64]) -> Result<Vec<&MetaStore>, String> {
+        let mut by_leader = HashMap::<u64, Vec<(u64, Option<Vec<u8>>)>>::new();
+        for group_id in group_ids {
+            by_leader
+                .entry(self.leader(*group_id))
+                .or_default()
+                .push((*group_id, None));
+        }
+
+        let mut stores = HashMap::new();
+        for (node_id, reads) in by_leader {
+            let results = self
+                .node(node_id)
+                .read_index_many(reads)
+                .map_err(|err| err.to_string())?
+                .collect::<Vec<_>>()
+                .await;
+            for (group_id, res) in results {
+                let store = match res {
+                    Ok(_) => self
+                        .wait_applied(node_id, group_id)
+                        .await
+                        .map(|_| self.store(node_id))?,
+                    // fall back to the read of single group which retries.
+                    Err(_) => self.read_index(group_id).await?,
+                };
+                stores.insert(group_id, store);
+            }
+        }
+        Ok(group_ids.iter().map(|group_id| stores[group_id]).collect())
+    }
+
+    pub async fn stop(&self) -> Result<(), String> {
+        for node in self.nodes.iter() {
+            node.shutdown().await.map_err(|err| err.to_string())?;
+        }
+        Ok(())
+    }
+
+    async fn retry<'a, T, F, Fut>(&'a self, group_id: u64, f: F) -> Result<T, String>
+    where
+        F: Fn(&'a MetaNode) -> Fut,
+        Fut: std::future::Future<Output = Result<T, Error>>,
+    {
+        for _ in 0..MAX_RETRIES {
+            match f(self.node(self.leader(group_id))).await {
+                Ok(res) => return Ok(res),
+                Err(Error::Propose(ProposeError::NotLeader { .. }))
+                | Err(Error::Propose(ProposeError::LeaderUnknown { .. })) => {
+                    sleep(RETRY_INTERVAL).await
+                }
+                Err(err) => return Err(err.to_string()),
+            }
+        }
+        Err(format!("group {} is unavailable", group_id))
+    }
+
+    /// Returns the node of the leader of group known by the nodes, or the
+    /// first node if the leader is unknown.
+    fn leader(&self, group_id: u64) -> u64 {
+        self.nodes
+            .iter()
+            .filter_map(|node| node.group_state(group_id))
+            .map(|state| state.get_leader_node_id())
+            .find(|node_id| *node_id != 0)
+            .unwrap_or(1)
+    }
+
+    /// Wait the replica of group on the node to apply the entries committed
+    /// when called.
+    async fn wait_applied(&self, node_id: u64, group_id: u64) -> Result<(), String> {
+        let state = self
+            .node(node_id)
+            .group_state(group_id)
+            .ok_or_else(|| format!("group {} does not exist on node {}", group_id, node_id))?;
+        let commit_index = state.get_commit_index();
+        for _ in 0..MAX_RETRIES {
+            if state.get_applied_index() >= commit_index {
+                return Ok(());
+            }
+            sleep(RETRY_INTERVAL).await;
+        }
+        Err(format!(
+            "group {} is not applied on node {}",
+            group_id, node_id
+        ))
+    }
+
+    fn node_id_of(&self, node: &MetaNode) -> u64 {
+        self.nodes
+            .iter()
+            .position(|n| std::ptr::eq(n, node))
+            .expect("node of cluster") as u64
+            + 1
+    }
+
+    fn node(&self, node_id: u64) -> &MetaNode {
+        &self.nodes[node_id as usize - 1]
+    }
+
+    fn storage(&self, node_id: u64) -> &MultiRaftMemoryStorage {
+        &self.storages[node_id as usize - 1]
+    }
+
+    fn store(&self, node_id: u64) -> &MetaStore {
+        &self.stores[node_id as usize - 1]
+    }
+}
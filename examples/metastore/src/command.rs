@@ -0,0 +1,66 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The key of a row, rows are ordered by table and then by key.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct RowKey {
+    pub table: String,
+    pub key: String,
+}
+
+impl RowKey {
+    pub fn new(table: &str, key: &str) -> Self {
+        Self {
+            table: table.to_owned(),
+            key: key.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Mutation {
+    Put { key: RowKey, value: String },
+    Delete { key: RowKey },
+}
+
+impl Mutation {
+    pub fn key(&self) -> &RowKey {
+        match self {
+            Mutation::Put { key, .. } => key,
+            Mutation::Delete { key } => key,
+        }
+    }
+}
+
+/// The command proposed to the group which holds the rows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Command {
+    /// Apply the mutations atomically in the group.
+    Write(Vec<Mutation>),
+    /// The first phase of the transaction across groups, the keys of the
+    /// mutations are locked until the transaction is committed or aborted.
+    Prepare {
+        txn_id: u64,
+        mutations: Vec<Mutation>,
+    },
+    /// Apply the prepared mutations of the transaction and unlock the keys.
+    Commit { txn_id: u64 },
+    /// Discard the prepared mutations of the transaction and unlock the keys.
+    Abort { txn_id: u64 },
+    /// Load the rows exported from another group, see `MetaDb::split`.
+    Restore(Vec<(RowKey, String)>),
+    /// Delete the rows of table in `[start, end)` which are moved to another
+    /// group, the range is unbounded if `end` is `None`.
+    DeleteRange {
+        table: String,
+        start: String,
+        end: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandResponse {
+    Ok,
+    /// The key is locked by the other prepared transaction.
+    Conflict(RowKey),
+}
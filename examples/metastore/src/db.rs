@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+
+use crate::cluster::Cluster;
+use crate::command::Command;
+use crate::command::CommandResponse;
+use crate::command::Mutation;
+use crate::command::RowKey;
+use crate::route::RouteTable;
+use crate::sql::parse;
+use crate::sql::Statement;
+
+/// The nodes which hold the replicas of the groups created for tables, the
+/// groups created by splits are created on the first two nodes and the third
+/// replica is added by the membership change.
+const TABLE_NODES: &[u64] = &[1, 2, 3];
+const SPLIT_NODES: &[u64] = &[1, 2];
+const SPLIT_ADD_NODE: u64 = 3;
+
+#[derive(Debug, PartialEq)]
+pub enum Output {
+    Ok,
+    Rows(Vec<(String, String)>),
+}
+
+/// MetaDb executes the statements on the groups of the cluster, the rows of
+/// a table are partitioned into the groups by the key ranges of `RouteTable`.
+///
+/// The writes between `BEGIN` and `COMMIT` are buffered and committed as a
+/// transaction, the transaction in a single group is proposed in one command,
+/// otherwise the two-phase commit is used across the groups.
+pub struct MetaDb {
+    cluster: Cluster,
+    routes: RouteTable,
+    next_group_id: u64,
+    next_txn_id: u64,
+    txn: Option<Vec<Mutation>>,
+}
+
+impl MetaDb {
+    pub fn new(cluster: Cluster) -> Self {
+        Self {
+            cluster,
+            routes: RouteTable::default(),
+            next_group_id: 1,
+            next_txn_id: 1,
+            txn: None,
+        }
+    }
+
+    pub async fn execute(&mut self, sql: &str) -> Result<Output, String> {
+        match parse(sql)? {
+            Statement::CreateTable { table } => {
+                let group_id = self.alloc_group_id();
+                self.cluster.create_group(group_id, TABLE_NODES).await?;
+                self.routes.create_table(&table, group_id)?;
+            }
+            Statement::Insert { table, key, value } => {
+                let key = RowKey::new(&table, &key);
+                self.mutate(Mutation::Put { key, value }).await?;
+            }
+            Statement::Delete { table, key } => {
+                let key = RowKey::new(&table, &key);
+                self.mutate(Mutation::Delete { key }).await?;
+            }
+            Statement::Select {
+                table,
+                key: Some(key),
+            } => {
+                let group_id = self.routes.route(&table, &key)?.group_id;
+                let store = self.cluster.read_index(group_id).await?;
+                let rows = store
+                    .get(group_id, &RowKey::new(&table, &key))
+                    .map(|value| vec![(key, value)])
+                    .unwrap_or_default();
+                return Ok(Output::Rows(rows));
+            }
+            Statement::Select { table, key: None } => return self.scan(&table).await,
+            Statement::Split { table, at } => self.split(&table, &at).await?,
+            Statement::Begin => {
+                if self.txn.is_some() {
+                    return Err("transaction already begun".to_owned());
+                }
+                self.txn = Some(vec![]);
+            }
+            Statement::Commit => {
+                let mutations = self.txn.take().ok_or("no transaction begun")?;
+                self.commit(mutations).await?;
+            }
+        }
+        Ok(Output::Ok)
+    }
+
+    pub fn cluster(&self) -> &Cluster {
+        &self.cluster
+    }
+
+    fn alloc_group_id(&mut self) -> u64 {
+        let group_id = self.next_group_id;
+        self.next_group_id += 1;
+        group_id
+    }
+
+    /// Buffer the mutation if in the transaction, otherwise commit it.
+    async fn mutate(&mut self, mutation: Mutation) -> Result<(), String> {
+        match self.txn.as_mut() {
+            Some(mutations) => {
+                mutations.push(mutation);
+                Ok(())
+            }
+            None => self.commit(vec![mutation]).await,
+        }
+    }
+
+    async fn commit(&mut self, mutations: Vec<Mutation>) -> Result<(), String> {
+        let mut groups = BTreeMap::<u64, Vec<Mutation>>::new();
+        for mutation in mutations {
+            let key = mutation.key();
+            let group_id = self.routes.route(&key.table, &key.key)?.group_id;
+            groups.entry(group_id).or_default().push(mutation);
+        }
+
+        match groups.len() {
+            0 => Ok(()),
+            1 => {
+                let (group_id, mutations) = groups.into_iter().next().unwrap();
+                check(
+                    self.cluster
+                        .write(group_id, Command::Write(mutations))
+                        .await?,
+                )
+            }
+            _ => {
+                let txn_id = self.next_txn_id;
+                self.next_txn_id += 1;
+                self.commit_across_groups(txn_id, groups).await
+            }
+        }
+    }
+
+    /// Commit the transaction across the groups by the two-phase commit. The
+    /// keys are locked in each group by the prepare, if any prepare conflicts
+    /// the transaction is aborted in all groups, otherwise committed.
+    async fn commit_across_groups(
+        &self,
+        txn_id: u64,
+        groups: BTreeMap<u64, Vec<Mutation>>,
+    ) -> Result<(), String> {
+        let mut conflict = None;
+        for (group_id, mutations) in groups.iter() {
+            let prepare = Command::Prepare {
+                txn_id,
+                mutations: mutations.clone(),
+            };
+            if let Err(err) = check(self.cluster.write(*group_id, prepare).await?) {
+                conflict = Some(err);
+                break;
+            }
+        }
+
+        let (finish, res) = match conflict {
+            Some(err) => (Command::Abort { txn_id }, Err(err)),
+            None => (Command::Commit { txn_id }, Ok(())),
+        };
+        for group_id in groups.keys() {
+            self.cluster.write(*group_id, finish.clone()).await?;
+        }
+        res
+    }
+
+    async fn scan(&self, table: &str) -> Result<Output, String> {
+        let routes = self.routes.routes(table)?;
+        let group_ids = routes
+            .iter()
+            .map(|route| route.group_id)
+            .collect::<Vec<_>>();
+        let stores = self.cluster.read_index_many(&group_ids).await?;
+        let rows = routes
+            .iter()
+            .zip(stores)
+            .flat_map(|(route, store)| {
+                store.export(route.group_id, table, &route.start, route.end.as_deref())
+            })
+            .map(|(key, value)| (key.key, value))
+            .collect();
+        Ok(Output::Rows(rows))
+    }
+
+    /// Split the range of table which contains `at` into a new group. The new
+    /// group is created on `SPLIT_NODES` and the replica on `SPLIT_ADD_NODE`
+    /// is added by the membership change, then the rows in `[at, end)` are
+    /// moved from the source group to the new group.
+    ///
+    /// The writes to the range are not blocked while moving, which is fine
+    /// for the example but a real system should fence the range first.
+    async fn split(&mut self, table: &str, at: &str) -> Result<(), String> {
+        let source = self.routes.route(table, at)?;
+        let group_id = self.alloc_group_id();
+        self.cluster.create_group(group_id, SPLIT_NODES).await?;
+        self.cluster.add_replica(group_id, SPLIT_ADD_NODE).await?;
+
+        let rows = self.cluster.read_index(source.group_id).await?.export(
+            source.group_id,
+            table,
+            at,
+            source.end.as_deref(),
+        );
+        check(self.cluster.write(group_id, Command::Restore(rows)).await?)?;
+
+        self.routes.split(table, at, group_id)?;
+        let delete = Command::DeleteRange {
+            table: table.to_owned(),
+            start: at.to_owned(),
+            end: source.end,
+        };
+        check(self.cluster.write(source.group_id, delete).await?)
+    }
+}
+
+fn check(res: CommandResponse) -> Result<(), String> {
+    match res {
+        CommandResponse::Ok => Ok(()),
+        CommandResponse::Conflict(key) => Err(format!(
+            "key {} of table {} is locked by other transaction",
+            key.key, key.table
+        )),
+    }
+}
@@ -0,0 +1,98 @@
+#![feature(type_alias_impl_trait)]
+#![feature(impl_trait_in_assoc_type)]
+//! A metadata store built on oceanraft, the rows of tables are partitioned
+//! into raft groups by key ranges and the example runs a tiny SQL dialect
+//! on a 3-node cluster in the process, showing:
+//!
+//! - the transactions across groups committed by the two-phase commit,
+//! - the linearizable reads of multiple groups by one batched read index,
+//! - the range split which moves rows to a new group whose third replica is
+//!   added by the membership change.
+mod cluster;
+mod command;
+mod db;
+mod route;
+mod sql;
+mod state_machine;
+mod store;
+
+use oceanraft::log;
+
+use crate::cluster::Cluster;
+use crate::command::Command;
+use crate::command::Mutation;
+use crate::command::RowKey;
+use crate::db::MetaDb;
+use crate::db::Output;
+
+fn rows(rows: &[(&str, &str)]) -> Output {
+    Output::Rows(
+        rows.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect(),
+    )
+}
+
+async fn run(db: &mut MetaDb, sql: &str) -> Result<Output, String> {
+    let res = db.execute(sql).await;
+    println!("> {}\n{:?}", sql, res);
+    res
+}
+
+#[tokio::main]
+async fn main() -> Result<(), String> {
+    log::init_global_console_tracing("warn");
+    let cluster = Cluster::start(3).await?;
+    let mut db = MetaDb::new(cluster);
+
+    run(&mut db, "CREATE TABLE schemas").await?;
+    run(&mut db, "INSERT INTO schemas VALUES ('apple', '1')").await?;
+    run(&mut db, "INSERT INTO schemas VALUES ('mango', '2')").await?;
+    run(&mut db, "INSERT INTO schemas VALUES ('peach', '3')").await?;
+
+    // move the keys from `m` to a new group.
+    run(&mut db, "SPLIT TABLE schemas AT 'm'").await?;
+    assert_eq!(
+        run(&mut db, "SELECT * FROM schemas").await?,
+        rows(&[("apple", "1"), ("mango", "2"), ("peach", "3")])
+    );
+
+    // the transaction writes to both groups.
+    run(&mut db, "BEGIN").await?;
+    run(&mut db, "INSERT INTO schemas VALUES ('banana', '4')").await?;
+    run(&mut db, "DELETE FROM schemas WHERE key = 'mango'").await?;
+    run(&mut db, "COMMIT").await?;
+    assert_eq!(
+        run(&mut db, "SELECT * FROM schemas").await?,
+        rows(&[("apple", "1"), ("banana", "4"), ("peach", "3")])
+    );
+
+    // the key locked by a prepared transaction aborts the other transaction
+    // across groups, neither group applies the writes.
+    let locked = Command::Prepare {
+        txn_id: u64::MAX,
+        mutations: vec![Mutation::Delete {
+            key: RowKey::new("schemas", "peach"),
+        }],
+    };
+    db.cluster().write(2, locked).await?;
+    run(&mut db, "BEGIN").await?;
+    run(&mut db, "INSERT INTO schemas VALUES ('cherry', '5')").await?;
+    run(&mut db, "INSERT INTO schemas VALUES ('peach', '6')").await?;
+    assert!(run(&mut db, "COMMIT").await.is_err());
+    assert_eq!(
+        run(&mut db, "SELECT * FROM schemas WHERE key = 'cherry'").await?,
+        rows(&[])
+    );
+    db.cluster()
+        .write(2, Command::Abort { txn_id: u64::MAX })
+        .await?;
+
+    assert_eq!(
+        run(&mut db, "SELECT * FROM schemas WHERE key = 'peach'").await?,
+        rows(&[("peach", "3")])
+    );
+
+    db.cluster().stop().await?;
+    Ok(())
+}
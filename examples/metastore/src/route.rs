@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ops::Bound;
+
+/// The key range of a table held by a group, the range is `[start, end)` and
+/// unbounded if `end` is `None`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    pub group_id: u64,
+    pub start: String,
+    pub end: Option<String>,
+}
+
+/// RouteTable maps the key ranges of tables to the groups, a table starts
+/// with one group holding all keys and the range is split by `split`.
+#[derive(Debug, Default)]
+pub struct RouteTable {
+    /// The start key of ranges to the group of table.
+    tables: HashMap<String, BTreeMap<String, u64>>,
+}
+
+impl RouteTable {
+    pub fn create_table(&mut self, table: &str, group_id: u64) -> Result<(), String> {
+        if self.tables.contains_key(table) {
+            return Err(format!("table {} already exists", table));
+        }
+        self.tables.insert(
+            table.to_owned(),
+            BTreeMap::from([(String::new(), group_id)]),
+        );
+        Ok(())
+    }
+
+    /// Returns the route of the range which contains the key.
+    pub fn route(&self, table: &str, key: &str) -> Result<Route, String> {
+        let ranges = self.ranges(table)?;
+        let (start, group_id) = ranges
+            .range::<str, _>((Bound::Unbounded, Bound::Included(key)))
+            .next_back()
+            .expect("the first range starts with empty key");
+        let end = ranges
+            .range::<str, _>((Bound::Excluded(key), Bound::Unbounded))
+            .next()
+            .map(|(end, _)| end.clone());
+        Ok(Route {
+            group_id: *group_id,
+            start: start.clone(),
+            end,
+        })
+    }
+
+    /// Returns the routes of all ranges of the table in the order of keys.
+    pub fn routes(&self, table: &str) -> Result<Vec<Route>, String> {
+        let ranges = self.ranges(table)?;
+        let starts = ranges.iter().collect::<Vec<_>>();
+        Ok(starts
+            .iter()
+            .enumerate()
+            .map(|(i, (start, group_id))| Route {
+                group_id: **group_id,
+                start: (*start).clone(),
+                end: starts.get(i + 1).map(|(end, _)| (*end).clone()),
+            })
+            .collect())
+    }
+
+    /// Split the range which contains `at` into `[start, at)` and `[at, end)`,
+    /// the latter is held by `group_id`. Returns the route before split.
+    pub fn split(&mut self, table: &str, at: &str, group_id: u64) -> Result<Route, String> {
+        let route = self.route(table, at)?;
+        if route.start == at {
+            return Err(format!("table {} is already split at {}", table, at));
+        }
+        self.tables
+            .get_mut(table)
+            .expect("table checked by route")
+            .insert(at.to_owned(), group_id);
+        Ok(route)
+    }
+
+    fn ranges(&self, table: &str) -> Result<&BTreeMap<String, u64>, String> {
+        self.tables
+            .get(table)
+            .ok_or_else(|| format!("table {} does not exist", table))
+    }
+}
@@ -0,0 +1,141 @@
+/// The statements supported by the metastore, the grammar is a tiny subset
+/// of SQL where each table has a string key and a string value:
+///
+/// ```text
+/// CREATE TABLE t
+/// INSERT INTO t VALUES ('k', 'v')
+/// DELETE FROM t WHERE key = 'k'
+/// SELECT * FROM t [WHERE key = 'k']
+/// SPLIT TABLE t AT 'k'
+/// BEGIN
+/// COMMIT
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    CreateTable {
+        table: String,
+    },
+    Insert {
+        table: String,
+        key: String,
+        value: String,
+    },
+    Delete {
+        table: String,
+        key: String,
+    },
+    Select {
+        table: String,
+        key: Option<String>,
+    },
+    Split {
+        table: String,
+        at: String,
+    },
+    Begin,
+    Commit,
+}
+
+pub fn parse(sql: &str) -> Result<Statement, String> {
+    let tokens = tokenize(sql);
+    let tokens = tokens
+        .iter()
+        .map(|token| token.as_str())
+        .collect::<Vec<_>>();
+    let keyword = |i: usize| tokens.get(i).map(|token| token.to_uppercase());
+    let err = || format!("unsupported statement: {}", sql);
+
+    let stmt = match (keyword(0).as_deref(), &tokens[..]) {
+        (Some("CREATE"), [_, table_kw, table]) if table_kw.eq_ignore_ascii_case("TABLE") => {
+            Statement::CreateTable {
+                table: table.to_string(),
+            }
+        }
+        (Some("INSERT"), [_, into, table, values, key, value])
+            if into.eq_ignore_ascii_case("INTO") && values.eq_ignore_ascii_case("VALUES") =>
+        {
+            Statement::Insert {
+                table: table.to_string(),
+                key: key.to_string(),
+                value: value.to_string(),
+            }
+        }
+        (Some("DELETE"), [_, from, table, rest @ ..]) if from.eq_ignore_ascii_case("FROM") => {
+            Statement::Delete {
+                table: table.to_string(),
+                key: parse_where(rest).ok_or_else(err)?,
+            }
+        }
+        (Some("SELECT"), ["*", from, table]) if from.eq_ignore_ascii_case("FROM") => {
+            Statement::Select {
+                table: table.to_string(),
+                key: None,
+            }
+        }
+        (Some("SELECT"), ["*", from, table, rest @ ..]) if from.eq_ignore_ascii_case("FROM") => {
+            Statement::Select {
+                table: table.to_string(),
+                key: Some(parse_where(rest).ok_or_else(err)?),
+            }
+        }
+        (Some("SPLIT"), [_, table_kw, table, at, key])
+            if table_kw.eq_ignore_ascii_case("TABLE") && at.eq_ignore_ascii_case("AT") =>
+        {
+            Statement::Split {
+                table: table.to_string(),
+                at: key.to_string(),
+            }
+        }
+        (Some("BEGIN"), [_]) => Statement::Begin,
+        (Some("COMMIT"), [_]) => Statement::Commit,
+        _ => return Err(err()),
+    };
+    Ok(stmt)
+}
+
+/// Parse `WHERE key = 'k'` and returns the key.
+fn parse_where(tokens: &[&str]) -> Option<String> {
+    match tokens {
+        [where_kw, key_kw, "=", key]
+            if where_kw.eq_ignore_ascii_case("WHERE") && key_kw.eq_ignore_ascii_case("key") =>
+        {
+            Some(key.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Split the statement by whitespaces, commas and parentheses, the quotes of
+/// string literals are stripped and the literals can contain the separators.
+fn tokenize(sql: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut token = String::new();
+    let mut quoted = false;
+    for c in sql.trim().trim_end_matches(';').chars() {
+        match c {
+            '\'' => {
+                if quoted {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                quoted = !quoted;
+            }
+            _ if quoted => token.push(c),
+            '=' => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+                tokens.push("=".to_owned());
+            }
+            c if c.is_whitespace() || c == ',' || c == '(' || c == ')' => {
+                if !token.is_empty() {
+                    tokens.push(std::mem::take(&mut token));
+                }
+            }
+            _ => token.push(c),
+        }
+    }
+    if !token.is_empty() {
+        tokens.push(token);
+    }
+    tokens
+}
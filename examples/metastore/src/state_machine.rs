@@ -0,0 +1,74 @@
+use std::future::Future;
+
+use oceanraft::prelude::ConfState;
+use oceanraft::Apply;
+use oceanraft::ApplyNormal;
+use oceanraft::GroupState;
+use oceanraft::StateMachine;
+use tracing::info;
+
+use crate::command::Command;
+use crate::command::CommandResponse;
+use crate::store::MetaStore;
+
+pub struct MetaStateMachine {
+    node_id: u64,
+    store: MetaStore,
+}
+
+impl MetaStateMachine {
+    pub fn new(node_id: u64, store: MetaStore) -> Self {
+        Self { node_id, store }
+    }
+}
+
+impl StateMachine<Command, CommandResponse> for MetaStateMachine {
+    type ApplyFuture<'life0> = impl Future<Output = ()> + 'life0
+    where
+        Self: 'life0;
+
+    fn apply<'life0>(
+        &'life0 self,
+        group_id: u64,
+        _replica_id: u64,
+        _state: &GroupState,
+        applys: Vec<Apply<Command, CommandResponse>>,
+    ) -> Self::ApplyFuture<'life0> {
+        async move {
+            for apply in applys {
+                match apply {
+                    Apply::NoOp(_) => {}
+                    Apply::Normal(ApplyNormal {
+                        data, context, tx, ..
+                    }) => {
+                        let res = self.store.apply(group_id, data);
+                        if let Some(tx) = tx {
+                            let _ = tx.send(Ok((res, context)));
+                        }
+                    }
+                    Apply::Membership(membership) => {
+                        if let Some(tx) = membership.tx {
+                            let _ = tx.send(Ok((CommandResponse::Ok, membership.ctx)));
+                        }
+                    }
+                    Apply::Admin(admin) => {
+                        if let Some(tx) = admin.tx {
+                            let _ = tx.send(Ok((CommandResponse::Ok, None)));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn on_group_created(&self, group_id: u64, replica_id: u64, conf_state: &ConfState) {
+        info!(
+            "node {}: replica {} of group {} created, voters = {:?}",
+            self.node_id, replica_id, group_id, conf_state.voters
+        );
+    }
+
+    fn on_group_removed(&self, group_id: u64, _replica_id: u64) {
+        self.store.drop_group(group_id);
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::ops::Bound;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use crate::command::Command;
+use crate::command::CommandResponse;
+use crate::command::Mutation;
+use crate::command::RowKey;
+
+#[derive(Default)]
+struct GroupData {
+    rows: BTreeMap<RowKey, String>,
+    /// The keys locked by the prepared transactions.
+    locks: HashMap<RowKey, u64>,
+    prepared: HashMap<u64, Vec<Mutation>>,
+}
+
+impl GroupData {
+    fn mutate(&mut self, mutation: Mutation) {
+        match mutation {
+            Mutation::Put { key, value } => {
+                self.rows.insert(key, value);
+            }
+            Mutation::Delete { key } => {
+                self.rows.remove(&key);
+            }
+        }
+    }
+
+    fn unlock(&mut self, txn_id: u64) -> Vec<Mutation> {
+        let mutations = self.prepared.remove(&txn_id).unwrap_or_default();
+        for mutation in mutations.iter() {
+            self.locks.remove(mutation.key());
+        }
+        mutations
+    }
+}
+
+/// MetaStore holds the rows of the groups on a node, each group holds the
+/// rows of a key range of tables, see `RouteTable`.
+#[derive(Clone, Default)]
+pub struct MetaStore {
+    groups: Arc<RwLock<HashMap<u64, GroupData>>>,
+}
+
+impl MetaStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply the committed command of the group.
+    pub fn apply(&self, group_id: u64, command: Command) -> CommandResponse {
+        let mut groups = self.groups.write().unwrap();
+        let data = groups.entry(group_id).or_default();
+        match command {
+            Command::Write(mutations) => {
+                if let Some(key) = mutations
+                    .iter()
+                    .map(|mutation| mutation.key())
+                    .find(|key| data.locks.contains_key(*key))
+                {
+                    return CommandResponse::Conflict(key.clone());
+                }
+                mutations
+                    .into_iter()
+                    .for_each(|mutation| data.mutate(mutation));
+            }
+            Command::Prepare { txn_id, mutations } => {
+                if let Some(key) = mutations
+                    .iter()
+                    .map(|mutation| mutation.key())
+                    .find(|key| data.locks.get(*key).map_or(false, |id| *id != txn_id))
+                {
+                    return CommandResponse::Conflict(key.clone());
+                }
+                for mutation in mutations.iter() {
+                    data.locks.insert(mutation.key().clone(), txn_id);
+                }
+                data.prepared.insert(txn_id, mutations);
+            }
+            Command::Commit { txn_id } => {
+                for mutation in data.unlock(txn_id) {
+                    data.mutate(mutation);
+                }
+            }
+            Command::Abort { txn_id } => {
+                let _ = data.unlock(txn_id);
+            }
+            Command::Restore(rows) => data.rows.extend(rows),
+            Command::DeleteRange { table, start, end } => {
+                let keys = range(&data.rows, &table, &start, end.as_deref())
+                    .map(|(key, _)| key.clone())
+                    .collect::<Vec<_>>();
+                for key in keys {
+                    data.rows.remove(&key);
+                }
+            }
+        }
+        CommandResponse::Ok
+    }
+
+    pub fn get(&self, group_id: u64, key: &RowKey) -> Option<String> {
+        let groups = self.groups.read().unwrap();
+        groups
+            .get(&group_id)
+            .and_then(|data| data.rows.get(key).cloned())
+    }
+
+    /// Export the rows of table in `[start, end)` of the group, the range is
+    /// unbounded if `end` is `None`.
+    pub fn export(
+        &self,
+        group_id: u64,
+        table: &str,
+        start: &str,
+        end: Option<&str>,
+    ) -> Vec<(RowKey, String)> {
+        let groups = self.groups.read().unwrap();
+        match groups.get(&group_id) {
+            None => vec![],
+            Some(data) => range(&data.rows, table, start, end)
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+        }
+    }
+
+    /// Drop the rows of the group removed from the node.
+    pub fn drop_group(&self, group_id: u64) {
+        self.groups.write().unwrap().remove(&group_id);
+    }
+}
+
+fn range<'a>(
+    rows: &'a BTreeMap<RowKey, String>,
+    table: &'a str,
+    start: &str,
+    end: Option<&str>,
+) -> impl Iterator<Item = (&'a RowKey, &'a String)> {
+    let end = match end {
+        Some(end) => Bound::Excluded(RowKey::new(table, end)),
+        None => Bound::Unbounded,
+    };
+    rows.range((Bound::Included(RowKey::new(table, start)), end))
+        .take_while(move |(key, _)| key.table == table)
+}
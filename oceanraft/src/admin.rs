@@ -0,0 +1,70 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::utils::flexbuffer_serialize;
+use crate::Error;
+
+/// The prefix of the data of the admin entries, the admin entries are not
+/// decoded by the codec of the proposals.
+const ADMIN_MAGIC: &[u8; 4] = b"\xffOCA";
+
+/// The control command of the application replicated through the log of the
+/// group, e.g. "reconfigure compaction", which is distinct from the data of
+/// the group. It's proposed by `MultiRaft::propose_admin` and applied as
+/// `Apply::Admin` in the order of the log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdminPayload {
+    pub command: String,
+    pub data: Vec<u8>,
+}
+
+impl AdminPayload {
+    pub fn new<C: Into<String>>(command: C, data: Vec<u8>) -> Self {
+        Self {
+            command: command.into(),
+            data,
+        }
+    }
+
+    /// Encode the payload into the data of the admin entry.
+    pub(crate) fn encode(&self) -> Result<Vec<u8>, Error> {
+        let payload = flexbuffer_serialize(self)?.take_buffer();
+        let mut data = Vec::with_capacity(ADMIN_MAGIC.len() + payload.len());
+        data.extend_from_slice(ADMIN_MAGIC);
+        data.extend_from_slice(&payload);
+        Ok(data)
+    }
+
+    /// Decode the payload from the data of the entry, e.g. the entries of
+    /// `AppliedBatch`. Returns `None` if the entry is not an admin entry.
+    pub fn decode(data: &[u8]) -> Option<AdminPayload> {
+        if !is_admin_entry(data) {
+            return None;
+        }
+        flexbuffers::from_slice(&data[ADMIN_MAGIC.len()..]).ok()
+    }
+}
+
+/// Returns true if the data of the entry is of the admin entry.
+pub(crate) fn is_admin_entry(data: &[u8]) -> bool {
+    data.starts_with(ADMIN_MAGIC)
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_admin_entry;
+    use super::AdminPayload;
+
+    #[test]
+    fn test_admin_payload() {
+        let payload = AdminPayload::new("reconfigure compaction", b"count=1000".to_vec());
+        let data = payload.encode().unwrap();
+        assert!(is_admin_entry(&data));
+        assert_eq!(AdminPayload::decode(&data), Some(payload));
+
+        // the data of the proposals is not the admin entry.
+        assert!(!is_admin_entry(b"data"));
+        assert_eq!(AdminPayload::decode(b"data"), None);
+        assert_eq!(AdminPayload::decode(b""), None);
+    }
+}
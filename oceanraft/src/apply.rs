@@ -1,6 +1,9 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::collections::VecDeque;
 use std::marker::PhantomData;
+use std::mem::take;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
@@ -8,52 +11,92 @@ use prost::Message;
 use raft::prelude::ConfChangeTransition;
 use raft::prelude::ConfState;
 use raft::prelude::Entry;
+use raft::GetEntriesContext;
 use raft_proto::ConfChangeI;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot;
+use tokio::sync::watch;
+use tokio::time::Instant;
 use tracing::error;
 use tracing::info;
 use tracing::trace;
+use tracing::warn;
 use tracing::Span;
 
 use crate::Apply;
+use crate::ApplyAdmin;
 use crate::ApplyMembership;
 use crate::ApplyNoOp;
 use crate::ApplyNormal;
 use crate::Config;
+use crate::EntryGate;
 use crate::Error;
+use crate::GateAction;
+use crate::GateDecision;
 use crate::GroupState;
 use crate::GroupStates;
 use crate::ProposeData;
 use crate::ProposeError;
 use crate::ProposeResponse;
+use crate::RaftGroupError;
 use crate::StateMachine;
 
+use crate::admin::is_admin_entry;
+use crate::admin::AdminPayload;
+use crate::compression::decompress_entry_data;
 use crate::msg::MembershipRequestContext;
 use crate::prelude::ConfChange;
 use crate::prelude::ConfChangeV2;
 use crate::prelude::EntryType;
 use crate::storage::MultiRaftStorage;
 use crate::storage::RaftStorage;
+use crate::utils::compute_entry_size;
+use crate::utils::decode_propose_data;
 use crate::utils::flexbuffer_deserialize;
 
+use super::consumer::AppliedBatch;
+use super::consumer::AppliedConsumers;
 use super::error::ChannelError;
 use super::error::DeserializationError;
+use super::event::Event;
+use super::event::EventChannel;
+use super::fatal::FatalError;
+use super::gate::decode_gate_policy;
+use super::invariant::ApplyInvariants;
+use super::latency::LatencyRecorder;
+use super::latency::ProposalTimeline;
 use super::msg::ApplyCommitMessage;
 use super::msg::ApplyData;
 use super::msg::ApplyMessage;
 use super::msg::ApplyResultMessage;
 use super::msg::CommitMembership;
 use super::proposal::Proposal;
+use super::proposer::Proposer;
+use super::pull::PullApplys;
 
 #[derive(Debug, Default)]
 struct LocalApplyState {
     applied_term: u64,
     applied_index: u64,
+    /// True if `Event::ApplyBacklogHigh` has been emitted and the backlog
+    /// has not dropped below the threshold yet.
+    backlog_high: bool,
+    /// The index of the entry held by the entry gate, `0` means the apply
+    /// of the group is not held.
+    held_index: u64,
+    /// The index and term of the last entry dropped while the apply is held,
+    /// the entries up to it are read again once the hold is released.
+    held_commit: (u64, u64),
+    /// The version of the gate policy replicated by the group, it's loaded
+    /// from the group metadata when the state is created.
+    gate_policy: u64,
 }
 
-pub struct ApplyActor;
+pub struct ApplyActor {
+    /// Closed once the worker exited.
+    exited: watch::Receiver<()>,
+}
 
 impl ApplyActor {
     pub(crate) fn spawn<W, R, RSM, S, MS>(
@@ -64,6 +107,10 @@ impl ApplyActor {
         request_rx: UnboundedReceiver<(Span, ApplyMessage<R>)>,
         response_tx: UnboundedSender<ApplyResultMessage>,
         commit_tx: UnboundedSender<ApplyCommitMessage>,
+        event_chan: &EventChannel,
+        latency: Arc<LatencyRecorder>,
+        applied_consumers: AppliedConsumers,
+        pull_applys: PullApplys<W, R>,
         stopped: Arc<AtomicBool>,
     ) -> Self
     where
@@ -81,12 +128,31 @@ impl ApplyActor {
             request_rx,
             response_tx,
             commit_tx,
+            event_chan,
+            latency,
+            applied_consumers,
+            pull_applys,
         );
-        tokio::spawn(async move {
+        let (exited_tx, exited) = watch::channel(());
+        cfg.spawn_apply(async move {
             worker.main_loop(stopped).await;
+            drop(exited_tx);
         });
 
-        Self {}
+        Self { exited }
+    }
+
+    /// The actor of the worker driven by `NodeHandle::poll`, which has no
+    /// task to wait for.
+    pub(crate) fn polled() -> Self {
+        let (_, exited) = watch::channel(());
+        Self { exited }
+    }
+
+    /// Wait until the worker exited.
+    pub(crate) async fn join(&self) {
+        let mut exited = self.exited.clone();
+        while exited.changed().await.is_ok() {}
     }
 }
 
@@ -104,8 +170,13 @@ where
     tx: UnboundedSender<ApplyResultMessage>,
     delegate: ApplyDelegate<W, R, RSM>,
     local_apply_states: HashMap<u64, LocalApplyState>,
+    /// The groups halted by the fatal errors of apply, the following applys
+    /// of them are dropped.
+    halted_groups: HashSet<u64>,
     shared_states: GroupStates,
     storage: MS,
+    event_chan: EventChannel,
+    latency: Arc<LatencyRecorder>,
     _m: PhantomData<S>,
 }
 
@@ -139,7 +210,7 @@ where
     // Note: This method provides scalability for us to make more flexible apply decisions in the future.
     fn batch_msgs(
         &mut self,
-        msgs: std::vec::Drain<'_, ApplyMessage<R>>,
+        msgs: impl IntoIterator<Item = ApplyMessage<R>>,
     ) -> HashMap<(u64, u64), Vec<ApplyData<R>>> {
         let mut pending_applys = HashMap::new();
         let mut batch_applys: HashMap<u64, Option<ApplyData<R>>> = HashMap::new();
@@ -179,6 +250,8 @@ where
                         }
                     }
                 }
+                // the lifecycle messages are handled by `handle_msgs`.
+                _ => {}
             }
         }
 
@@ -197,23 +270,254 @@ where
     }
 
     async fn handle_msgs(&mut self, msgs: std::vec::Drain<'_, ApplyMessage<R>>) {
+        // the lifecycle hooks are called in order with the applies, the applies
+        // before the lifecycle message are handled first.
+        let mut apply_msgs = vec![];
+        for msg in msgs {
+            match msg {
+                ApplyMessage::Apply { .. } => apply_msgs.push(msg),
+                ApplyMessage::ReleaseHold {
+                    group_id,
+                    replica_id,
+                } => {
+                    if !apply_msgs.is_empty() {
+                        self.handle_apply_msgs(apply_msgs.drain(..)).await;
+                    }
+                    self.release_hold(group_id, replica_id).await;
+                }
+                msg => {
+                    if !apply_msgs.is_empty() {
+                        self.handle_apply_msgs(apply_msgs.drain(..)).await;
+                    }
+                    self.handle_lifecycle_msg(msg).await;
+                }
+            }
+        }
+
+        if !apply_msgs.is_empty() {
+            self.handle_apply_msgs(apply_msgs.drain(..)).await;
+        }
+    }
+
+    /// Apply the entries held by the entry gate again, they are consulted by
+    /// the gate again and may be held again.
+    async fn release_hold(&mut self, group_id: u64, replica_id: u64) {
+        let state = match self.local_apply_states.get_mut(&group_id) {
+            Some(state) if state.held_index != 0 => state,
+            _ => return,
+        };
+        let gs = match self.storage.group_storage(group_id, replica_id).await {
+            Ok(gs) => gs,
+            Err(err) => {
+                error!(
+                    "node {}: group {} release apply held by entry gate failed: {}",
+                    self.node_id, group_id, err
+                );
+                return;
+            }
+        };
+        let apply = match ApplyDelegate::<W, R, RSM>::take_held(group_id, replica_id, state, &gs) {
+            Ok(Some(apply)) => apply,
+            Ok(None) => return,
+            Err(err) => {
+                error!(
+                    "node {}: group {} release apply held by entry gate failed: {}",
+                    self.node_id, group_id, err
+                );
+                return;
+            }
+        };
+        info!(
+            "node {}: group {} release apply held by entry gate, apply entries to {}",
+            self.node_id, group_id, apply.commit_index
+        );
+        let mut msgs = vec![ApplyMessage::Apply {
+            applys: HashMap::from([(group_id, apply)]),
+        }];
+        self.handle_apply_msgs(msgs.drain(..)).await;
+    }
+
+    async fn handle_lifecycle_msg(&mut self, msg: ApplyMessage<R>) {
+        match msg {
+            ApplyMessage::Apply { .. } | ApplyMessage::ReleaseHold { .. } => unreachable!(),
+            ApplyMessage::GroupCreated {
+                group_id,
+                replica_id,
+                conf_state,
+            } => {
+                self.delegate
+                    .rsm
+                    .on_group_created(group_id, replica_id, &conf_state);
+            }
+            ApplyMessage::GroupRemoved {
+                group_id,
+                replica_id,
+            } => {
+                self.local_apply_states.remove(&group_id);
+                self.halted_groups.remove(&group_id);
+                self.delegate.pull_applys.remove(group_id);
+                self.delegate.invariants.forget(group_id);
+                self.delegate.rsm.on_group_removed(group_id, replica_id);
+            }
+            ApplyMessage::SnapshotInstalled {
+                group_id,
+                replica_id,
+                metadata,
+            } => {
+                // the entries after the snapshot are applied continuously.
+                self.load_apply_state(group_id, replica_id).await;
+                let apply_state = self
+                    .local_apply_states
+                    .entry(group_id)
+                    .or_insert(LocalApplyState::default());
+                if metadata.index > apply_state.applied_index {
+                    apply_state.applied_index = metadata.index;
+                    apply_state.applied_term = metadata.term;
+                }
+                self.delegate
+                    .pull_applys
+                    .snapshot_installed(group_id, metadata.index);
+                self.delegate
+                    .rsm
+                    .on_snapshot_installed(group_id, replica_id, &metadata);
+            }
+        }
+    }
+
+    async fn handle_apply_msgs(&mut self, msgs: std::vec::Drain<'_, ApplyMessage<R>>) {
         let pending_applys = self.batch_msgs(msgs);
         for ((group_id, replica_id), applys) in pending_applys {
+            if self.halted_groups.contains(&group_id) {
+                let node_id = self.node_id;
+                for apply in applys {
+                    ApplyDelegate::<W, R, RSM>::fail_proposals(apply.proposals, || {
+                        Error::RaftGroup(RaftGroupError::Halted(node_id, group_id))
+                    });
+                }
+                continue;
+            }
+
             let gs = self
                 .storage
                 .group_storage(group_id, replica_id)
                 .await
                 .unwrap();
 
+            self.load_apply_state(group_id, replica_id).await;
             let apply_state = self
                 .local_apply_states
                 .entry(group_id)
                 .or_insert(LocalApplyState::default());
 
-            let _ = self
+            // update the commit-apply backlog gauge before applying.
+            let shared_state = self.shared_states.get(group_id);
+            let commit_index = applys
+                .last()
+                .map_or(apply_state.applied_index, |apply| apply.commit_index);
+            let backlog = commit_index.saturating_sub(apply_state.applied_index);
+            let backlog_bytes = applys
+                .iter()
+                .map(|apply| apply.entries_size as u64)
+                .sum::<u64>();
+            if let Some(state) = shared_state.as_ref() {
+                state.set_apply_backlog_bytes(backlog_bytes);
+            }
+
+            let threshold = self.cfg.apply_backlog_threshold;
+            if threshold != 0 && backlog > threshold {
+                if !apply_state.backlog_high {
+                    apply_state.backlog_high = true;
+                    self.event_chan.push(Event::ApplyBacklogHigh {
+                        group_id,
+                        replica_id,
+                        leader_id: shared_state
+                            .as_ref()
+                            .map_or(0, |state| state.get_leader_id()),
+                        backlog,
+                        backlog_bytes,
+                    });
+                    self.event_chan.flush();
+                }
+            } else {
+                apply_state.backlog_high = false;
+            }
+
+            let prev_applied = apply_state.applied_index;
+            let prev_gate_policy = apply_state.gate_policy;
+            let res = self
                 .delegate
                 .handle_applys(group_id, replica_id, applys, apply_state, &gs)
                 .await;
+            if apply_state.gate_policy != prev_gate_policy {
+                Self::persist_gate_policy(
+                    &self.storage,
+                    self.node_id,
+                    group_id,
+                    replica_id,
+                    apply_state.gate_policy,
+                )
+                .await;
+            }
+            if !self.delegate.gated_events.is_empty() {
+                for event in self.delegate.gated_events.drain(..) {
+                    self.event_chan.push(event);
+                }
+                self.event_chan.flush();
+            }
+
+            if !self.delegate.timelines.is_empty() {
+                if res.is_ok() {
+                    self.latency
+                        .record(group_id, self.delegate.timelines.iter(), Instant::now());
+                }
+                self.delegate.timelines.clear();
+            }
+
+            if let Err(err) = res {
+                error!(
+                    "node {}: group {} apply failed: {}",
+                    self.node_id, group_id, err
+                );
+                match err {
+                    Error::ApplyGap { expected, got } => {
+                        self.event_chan.push(Event::ApplyGap {
+                            group_id,
+                            replica_id,
+                            expected,
+                            got,
+                        });
+                        self.event_chan.flush();
+                    }
+                    Error::Fatal(err) => {
+                        self.halted_groups.insert(group_id);
+                        if let Err(_) = self.delegate.commit_tx.send(ApplyCommitMessage::Halt(err))
+                        {
+                            error!(
+                                "node {}: send halt group {} failed, the node actor dropped",
+                                self.node_id, group_id
+                            );
+                        }
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            self.delegate.invariants.check_applied(
+                group_id,
+                prev_applied,
+                apply_state.applied_index,
+            );
+
+            // the applied index of the group in pull mode is reported when the
+            // applys are acknowledged, see `MultiRaft::acknowledge_applied`.
+            if self.delegate.pull_applys.is_pull(group_id) {
+                continue;
+            }
+
+            if let Some(state) = shared_state.as_ref() {
+                state.set_applied_index(apply_state.applied_index);
+                state.set_apply_backlog_bytes(0);
+            }
 
             let res = ApplyResultMessage {
                 group_id,
@@ -230,6 +534,57 @@ where
         }
     }
 
+    /// Create the apply state of the group if it doesn't exist, the gate
+    /// policy replicated by the group is restored from the group metadata.
+    async fn load_apply_state(&mut self, group_id: u64, replica_id: u64) {
+        if self.local_apply_states.contains_key(&group_id) {
+            return;
+        }
+
+        let gate_policy = match self.storage.get_group_metadata(group_id, replica_id).await {
+            Ok(meta) => meta.map_or(0, |meta| meta.gate_policy),
+            Err(err) => {
+                warn!(
+                    "node {}: group {} load gate policy failed: {}",
+                    self.node_id, group_id, err
+                );
+                0
+            }
+        };
+        self.local_apply_states.insert(
+            group_id,
+            LocalApplyState {
+                gate_policy,
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Persist the gate policy replicated by the group to the group metadata,
+    /// so it's restored when the node restarts.
+    async fn persist_gate_policy(
+        storage: &MS,
+        node_id: u64,
+        group_id: u64,
+        replica_id: u64,
+        policy: u64,
+    ) {
+        let res = match storage.get_group_metadata(group_id, replica_id).await {
+            Ok(Some(mut meta)) if meta.gate_policy != policy => {
+                meta.gate_policy = policy;
+                storage.set_group_metadata(meta).await
+            }
+            Ok(_) => Ok(()),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = res {
+            warn!(
+                "node {}: group {} persist gate policy {} failed: {}",
+                node_id, group_id, policy, err
+            );
+        }
+    }
+
     async fn main_loop(mut self, stopped: Arc<AtomicBool>) {
         info!("node {}: start apply main_loop", self.node_id);
         let mut pending_msgs = Vec::with_capacity(self.cfg.max_batch_apply_msgs);
@@ -254,7 +609,22 @@ where
         }
     }
 
-    fn new(
+    /// Apply all pending messages without waiting, returns the number of
+    /// handled messages.
+    pub(crate) async fn poll_once(&mut self) -> usize {
+        let mut pending_msgs = vec![];
+        while let Ok((_span, msg)) = self.rx.try_recv() {
+            pending_msgs.push(msg);
+        }
+
+        let handled = pending_msgs.len();
+        if handled != 0 {
+            self.handle_msgs(pending_msgs.drain(..)).await;
+        }
+        handled
+    }
+
+    pub(crate) fn new(
         cfg: &Config,
         rsm: RSM,
         storage: MS,
@@ -262,16 +632,31 @@ where
         request_rx: UnboundedReceiver<(Span, ApplyMessage<R>)>,
         response_tx: UnboundedSender<ApplyResultMessage>,
         commit_tx: UnboundedSender<ApplyCommitMessage>,
+        event_chan: &EventChannel,
+        latency: Arc<LatencyRecorder>,
+        applied_consumers: AppliedConsumers,
+        pull_applys: PullApplys<W, R>,
     ) -> Self {
         Self {
             local_apply_states: HashMap::default(),
+            halted_groups: HashSet::default(),
             node_id: cfg.node_id,
             cfg: cfg.clone(),
             rx: request_rx,
             tx: response_tx,
             shared_states,
             storage,
-            delegate: ApplyDelegate::new(cfg.node_id, rsm, commit_tx),
+            event_chan: event_chan.clone(),
+            latency,
+            delegate: ApplyDelegate::new(
+                cfg.node_id,
+                rsm,
+                commit_tx,
+                cfg.entry_gate.clone(),
+                cfg.apply_checkpoint_entries,
+                applied_consumers,
+                pull_applys,
+            ),
             _m: PhantomData,
         }
     }
@@ -288,6 +673,9 @@ where
     index: u64,
     term: u64,
     tx: Option<oneshot::Sender<Result<(RES, Option<Vec<u8>>), Error>>>,
+    /// The context kept locally by the proposer, see `WriteRequest::local_context`.
+    context: Option<Vec<u8>>,
+    timeline: Option<ProposalTimeline>,
 }
 
 impl<RES> PendingSender<RES>
@@ -298,8 +686,16 @@ where
         index: u64,
         term: u64,
         tx: Option<oneshot::Sender<Result<(RES, Option<Vec<u8>>), Error>>>,
+        context: Option<Vec<u8>>,
+        timeline: Option<ProposalTimeline>,
     ) -> Self {
-        Self { index, term, tx }
+        Self {
+            index,
+            term,
+            tx,
+            context,
+            timeline,
+        }
     }
 }
 
@@ -356,6 +752,26 @@ where
         self.conf_change.take()
     }
 
+    /// Take at most `max` of the last pushed normals and the conf change
+    /// whose index is not less than `index`.
+    pub fn take_from(&mut self, index: u64, max: usize) -> Vec<PendingSender<RES>> {
+        let mut senders = vec![];
+        while senders.len() < max {
+            match self.normals.back() {
+                Some(p) if p.index >= index => senders.push(self.normals.pop_back().unwrap()),
+                _ => break,
+            }
+        }
+        if self
+            .conf_change
+            .as_ref()
+            .map_or(false, |p| p.index >= index)
+        {
+            senders.extend(self.conf_change.take());
+        }
+        senders
+    }
+
     pub fn remove_stales(&mut self, index: u64, term: u64) {
         while let Some(p) = self.pop_normal(index, term) {
             p.tx.map(|tx| {
@@ -377,6 +793,20 @@ where
     pending_senders: PendingSenderQueue<R>,
     rsm: RSM,
     commit_tx: UnboundedSender<ApplyCommitMessage>,
+    gate: Arc<dyn EntryGate>,
+    /// The `Event::EntryGated` of the entries gated since last drained.
+    gated_events: Vec<Event>,
+    /// See `Config::apply_checkpoint_entries`.
+    checkpoint_entries: usize,
+    /// The timelines of the writes applied by the current apply, see
+    /// `Config::record_latency`.
+    timelines: Vec<ProposalTimeline>,
+    /// The secondary consumers which the applied entries are published to.
+    applied_consumers: AppliedConsumers,
+    /// The groups applied in pull mode, their applys are buffered instead of
+    /// applied by the state machine.
+    pull_applys: PullApplys<W, R>,
+    invariants: ApplyInvariants,
     _m1: PhantomData<W>,
     _m2: PhantomData<R>,
 }
@@ -387,12 +817,27 @@ where
     R: ProposeResponse,
     RSM: StateMachine<W, R>,
 {
-    fn new(node_id: u64, rsm: RSM, commit_tx: UnboundedSender<ApplyCommitMessage>) -> Self {
+    fn new(
+        node_id: u64,
+        rsm: RSM,
+        commit_tx: UnboundedSender<ApplyCommitMessage>,
+        gate: Arc<dyn EntryGate>,
+        checkpoint_entries: usize,
+        applied_consumers: AppliedConsumers,
+        pull_applys: PullApplys<W, R>,
+    ) -> Self {
         Self {
             node_id,
             pending_senders: PendingSenderQueue::new(),
             rsm,
             commit_tx,
+            gate,
+            gated_events: vec![],
+            checkpoint_entries,
+            timelines: vec![],
+            applied_consumers,
+            pull_applys,
+            invariants: ApplyInvariants::new(node_id),
             _m1: PhantomData,
             _m2: PhantomData,
         }
@@ -418,7 +863,13 @@ where
 
     fn push_pending_proposals(&mut self, proposals: Vec<Proposal<R>>) {
         for mut p in proposals {
-            let sender = PendingSender::new(p.index, p.term, p.tx.take());
+            let sender = PendingSender::new(
+                p.index,
+                p.term,
+                p.tx.take(),
+                p.context.take(),
+                p.timeline.take(),
+            );
             if p.is_conf_change {
                 self.set_pending_conf_change(sender);
             } else {
@@ -440,6 +891,20 @@ where
     }
 
     fn find_pending(
+        &mut self,
+        group_id: u64,
+        term: u64,
+        index: u64,
+        is_conf_change: bool,
+    ) -> Option<PendingSender<R>> {
+        let pending = self.pop_pending(term, index, is_conf_change);
+        if pending.is_some() {
+            self.invariants.check_resolved(group_id, index);
+        }
+        pending
+    }
+
+    fn pop_pending(
         &mut self,
         term: u64,
         index: u64,
@@ -507,7 +972,9 @@ where
             }));
         }
 
-        let tx = self.find_pending(term, index, true).map_or(None, |p| p.tx);
+        let tx = self
+            .find_pending(group_id, term, index, true)
+            .map_or(None, |p| p.tx);
         let (conf_change, mut request_ctx) = match parse_conf_change(&ent) {
             Err(err) => {
                 tx.map(|tx| {
@@ -599,12 +1066,32 @@ where
             ent.term
         );
 
-        let tx = self
-            .find_pending(ent.term, ent.index, false)
-            .map_or(None, |p| p.tx);
+        if let Some(payload) = AdminPayload::decode(&ent.data) {
+            let tx = self
+                .find_pending(group_id, term, index, false)
+                .and_then(|p| p.tx);
+            let (proposer, _) = Proposer::decode(&ent.context);
+            return Some(Apply::Admin(ApplyAdmin {
+                group_id,
+                index,
+                term,
+                payload,
+                proposer,
+                tx,
+            }));
+        }
+
+        let (tx, local_context) = match self.find_pending(group_id, ent.term, ent.index, false) {
+            None => (None, None),
+            Some(p) => {
+                self.timelines.extend(p.timeline);
+                (p.tx, p.context)
+            }
+        };
 
         // TODO: handle this error
-        let write_data = flexbuffer_deserialize(&ent.data).unwrap();
+        let write_data = decode_propose_data(&ent.data).unwrap();
+        let (proposer, context) = Proposer::decode(&ent.context);
 
         Some(Apply::Normal(ApplyNormal {
             group_id,
@@ -613,69 +1100,305 @@ where
             index,
             term,
             data: write_data,
-            context: if ent.context.is_empty() {
-                None
+            context: if context.is_empty() {
+                local_context
             } else {
-                Some(ent.context)
+                Some(context.to_vec())
             },
+            proposer,
             tx,
         }))
     }
 
+    /// Reply the rejection to the proposer of the entry and apply it as no-op,
+    /// so that all replicas skip the entry in the same way.
+    fn handle_rejected(&mut self, group_id: u64, ent: Entry, reason: String) -> Apply<W, R> {
+        let (index, term) = (ent.index, ent.term);
+        info!(
+            "node {}: group = {} entry index = {}, term = {} rejected by entry gate: {}",
+            self.node_id, group_id, index, term, reason
+        );
+        if let Some(tx) = self
+            .find_pending(group_id, term, index, false)
+            .and_then(|p| p.tx)
+        {
+            let _ = tx.send(Err(Error::Propose(ProposeError::GateRejected {
+                node_id: self.node_id,
+                group_id,
+                index,
+                reason,
+            })));
+        }
+
+        Apply::NoOp(ApplyNoOp {
+            group_id,
+            index,
+            term,
+        })
+    }
+
+    /// Consult the gate for the entry. The entry is held instead of rejected
+    /// or transformed if the policy of the gate doesn't match the policy
+    /// replicated by the group, since the replicas of the replicated policy
+    /// may decide otherwise.
+    fn check_gate(&self, group_id: u64, replicated_policy: u64, ent: &Entry) -> GateDecision {
+        match self.gate.check(group_id, ent) {
+            GateDecision::Reject(_) | GateDecision::Transform(_)
+                if self.gate.policy() != replicated_policy =>
+            {
+                info!(
+                    "node {}: group = {} entry index = {} is held, the gate policy {} doesn't match the replicated policy {}",
+                    self.node_id, group_id, ent.index, self.gate.policy(), replicated_policy
+                );
+                GateDecision::Hold
+            }
+            decision => decision,
+        }
+    }
+
+    fn record_gated(&mut self, group_id: u64, replica_id: u64, ent: &Entry, action: GateAction) {
+        self.gated_events.push(Event::EntryGated {
+            group_id,
+            replica_id,
+            index: ent.index,
+            term: ent.term,
+            policy: self.gate.policy(),
+            action,
+        });
+    }
+
+    /// Find the first gap or regression of the entries index comparing with the
+    /// applied state, returns `(expected, got)` if found.
+    fn find_apply_gap(prev_applied_index: u64, entries: &[Entry]) -> Option<(u64, u64)> {
+        // Notes:
+        // If the `LocalApplyState` applied_index is equal to 0, it means the `Storage` **is not**
+        // created with a configuration, and its last index and term should be equal to 0. This
+        // case can happen when a consensus group is started with a membership change.
+        // In this case, we give up continue check and then catch up leader state.
+        let mut expected = match prev_applied_index {
+            0 => entries[0].index,
+            _ => prev_applied_index + 1,
+        };
+
+        for ent in entries.iter() {
+            if ent.index != expected {
+                return Some((expected, ent.index));
+            }
+            expected += 1;
+        }
+
+        None
+    }
+
+    /// Clear the hold of the entry gate, returns the apply of the entries
+    /// dropped while the apply is held, which are read from the storage.
+    fn take_held<S: RaftStorage>(
+        group_id: u64,
+        replica_id: u64,
+        state: &mut LocalApplyState,
+        gs: &S,
+    ) -> Result<Option<ApplyData<R>>, Error> {
+        let (commit_index, commit_term) = state.held_commit;
+        state.held_index = 0;
+        state.held_commit = (0, 0);
+        if commit_index <= state.applied_index {
+            return Ok(None);
+        }
+
+        let entries = gs.entries(
+            state.applied_index + 1,
+            commit_index + 1,
+            None,
+            GetEntriesContext::empty(false),
+        )?;
+        Ok(Some(ApplyData {
+            replica_id,
+            group_id,
+            term: commit_term,
+            commit_index,
+            commit_term,
+            entries_size: entries.iter().map(|ent| compute_entry_size(ent)).sum(),
+            entries,
+            proposals: vec![],
+        }))
+    }
+
+    /// Reply the error to the proposals that will never be applied.
+    fn fail_proposals<F: Fn() -> Error>(proposals: Vec<Proposal<R>>, err: F) {
+        for proposal in proposals {
+            if let Some(tx) = proposal.tx {
+                let _ = tx.send(Err(err()));
+            }
+        }
+    }
+
     async fn handle_apply<S: RaftStorage>(
         &mut self,
         mut apply: ApplyData<R>,
         state: &mut LocalApplyState,
         gs: &S,
-    ) {
+    ) -> Result<(), Error> {
         let group_id = apply.group_id;
+        let replica_id = apply.replica_id;
         let (prev_applied_index, prev_applied_term) = (state.applied_index, state.applied_term);
         let (curr_commit_index, curr_commit_term) = (apply.commit_index, apply.commit_term);
         // check if the state machine is backword
         if prev_applied_index > curr_commit_index || prev_applied_term > curr_commit_term {
-            panic!(
-                "commit state jump backward {:?} -> {:?}",
-                (prev_applied_index, prev_applied_term),
-                (curr_commit_index, curr_commit_term)
-            );
+            let node_id = self.node_id;
+            Self::fail_proposals(std::mem::take(&mut apply.proposals), || {
+                Error::RaftGroup(RaftGroupError::Halted(node_id, group_id))
+            });
+            return Err(Error::Fatal(FatalError::CommitBackward {
+                node_id,
+                group_id,
+                applied: (prev_applied_index, prev_applied_term),
+                commit: (curr_commit_index, curr_commit_term),
+            }));
         }
 
         if apply.entries.is_empty() {
-            return;
+            return Ok(());
+        }
+        // the last entry handed to the apply, the entries after it are not
+        // handed yet even if they are committed.
+        let handed_last = apply
+            .entries
+            .last()
+            .map_or((0, 0), |ent| (ent.index, ent.term));
+
+        // The apply of group is held by the entry gate, the entries are dropped
+        // and read again once the hold is released, the proposals are failed
+        // instead of waiting the release.
+        if state.held_index != 0 {
+            state.held_commit = handed_last;
+            for proposal in std::mem::take(&mut apply.proposals) {
+                if let Some(tx) = proposal.tx {
+                    let err = gate_held(self.node_id, group_id, proposal.index, state.held_index);
+                    let _ = tx.send(Err(err));
+                }
+            }
+            return Ok(());
         }
 
-        // Helps applications establish monotonically increasing apply constraints for each batch.
-        //
-        // Notes:
-        // If the `LocalApplyState` applied_index is equal to 0, it means the `Storage` **is not**
-        // created with a configuration, and its last index and term should be equal to 0. This
-        // case can happen when a consensus group is started with a membership change.
-        // In this case, we give up continue check and then catch up leader state.
-        // if prev_applied_index != 0 && apply.entries[0].index != prev_applied_index + 1 {
-        //     panic!(
-        //         "node {}: group {} apply entries index does not match, expect {}, but got {}",
-        //         self.node_id,
-        //         group_id,
-        //         prev_applied_index + 1,
-        //         apply.entries[0].index
-        //     );
-        // }
-
-        self.push_pending_proposals(std::mem::take(&mut apply.proposals));
-        let last_index = apply.entries.last().expect("unreachable").index;
-        let last_term = apply.entries.last().expect("unreachable").term;
+        // Helps applications establish monotonically increasing apply constraints for each batch,
+        // fail fast if there is a gap or regression rather than feeding bad sequences to the
+        // state machine.
+        if let Some((expected, got)) = Self::find_apply_gap(prev_applied_index, &apply.entries) {
+            Self::fail_proposals(std::mem::take(&mut apply.proposals), || Error::ApplyGap {
+                expected,
+                got,
+            });
+            return Err(Error::ApplyGap { expected, got });
+        }
+
+        // the entries are decompressed before any of the batch is applied, the
+        // batch is failed if one of them is corrupt or its codec isn't built.
+        for ent in apply.entries.iter_mut() {
+            if ent.entry_type() != EntryType::EntryNormal {
+                continue;
+            }
+            match decompress_entry_data(&ent.data) {
+                Ok(Cow::Borrowed(_)) => {}
+                Ok(Cow::Owned(data)) => ent.data = data,
+                Err(err) => {
+                    let reason = format!("entry {}: {}", ent.index, err);
+                    error!(
+                        "node {}: group = {} decompress {}",
+                        self.node_id, group_id, reason
+                    );
+                    Self::fail_proposals(std::mem::take(&mut apply.proposals), || {
+                        undecodable_entry(&reason)
+                    });
+                    return Err(undecodable_entry(&reason));
+                }
+            }
+        }
+
+        let proposals = std::mem::take(&mut apply.proposals);
+        let pushed_proposals = proposals.len();
+        self.push_pending_proposals(proposals);
+        let (mut last_index, mut last_term) = (prev_applied_index, prev_applied_term);
+        let mut checkpoint_index = prev_applied_index;
         let mut applys = vec![];
-        for ent in apply.entries.into_iter() {
+        let mut applied_entries = self.applied_consumers.is_active().then(Vec::new);
+        for mut ent in apply.entries.into_iter() {
+            // bound the entries applied again after crash in the huge batch.
+            if self.checkpoint_entries != 0
+                && last_index - checkpoint_index >= self.checkpoint_entries as u64
+            {
+                self.checkpoint_apply(group_id, replica_id, take(&mut applys), last_index, gs)
+                    .await;
+                checkpoint_index = last_index;
+            }
+
+            let (index, term) = (ent.index, ent.term);
+            // the admin entries are control commands, they are not gated. The
+            // entries after the gate policy are gated by it.
+            if ent.entry_type() == EntryType::EntryNormal && is_admin_entry(&ent.data) {
+                if let Some(policy) = AdminPayload::decode(&ent.data)
+                    .as_ref()
+                    .and_then(decode_gate_policy)
+                {
+                    info!(
+                        "node {}: group = {} gate policy {} replicated at index = {}",
+                        self.node_id, group_id, policy, index
+                    );
+                    state.gate_policy = policy;
+                }
+            } else if ent.entry_type() == EntryType::EntryNormal && !ent.data.is_empty() {
+                match self.check_gate(group_id, state.gate_policy, &ent) {
+                    GateDecision::Accept => {}
+                    GateDecision::Hold => {
+                        info!(
+                            "node {}: group = {} apply held by entry gate at index = {}, term = {}",
+                            self.node_id, group_id, index, term
+                        );
+                        self.record_gated(group_id, replica_id, &ent, GateAction::Hold);
+                        state.held_index = index;
+                        state.held_commit = handed_last;
+                        let node_id = self.node_id;
+                        for p in self.pending_senders.take_from(index, pushed_proposals) {
+                            p.tx.map(|tx| {
+                                tx.send(Err(gate_held(node_id, group_id, p.index, index)))
+                            });
+                        }
+                        break;
+                    }
+                    GateDecision::Reject(reason) => {
+                        self.record_gated(group_id, replica_id, &ent, GateAction::Reject);
+                        applys.push(self.handle_rejected(group_id, ent, reason));
+                        (last_index, last_term) = (index, term);
+                        continue;
+                    }
+                    GateDecision::Transform(data) => {
+                        self.record_gated(group_id, replica_id, &ent, GateAction::Transform);
+                        ent.data = data;
+                    }
+                }
+            }
+
+            if let Some(applied_entries) = applied_entries.as_mut() {
+                applied_entries.push(ent.clone());
+            }
             let apply = match ent.entry_type() {
                 EntryType::EntryNormal => self.handle_normal(group_id, ent),
                 EntryType::EntryConfChange | EntryType::EntryConfChangeV2 => {
-                    self.handle_conf_change(group_id, ent).await
+                    // the fence of the conf change: the applys before it are
+                    // drained before the membership is committed, and the
+                    // membership is handed to the state machine alone, so no
+                    // normal apply reads the membership concurrently.
+                    self.deliver(group_id, replica_id, take(&mut applys)).await;
+                    if let Some(apply) = self.handle_conf_change(group_id, ent).await {
+                        self.deliver(group_id, replica_id, vec![apply]).await;
+                    }
+                    None
                 }
             };
 
             if let Some(apply) = apply {
                 applys.push(apply)
             }
+            (last_index, last_term) = (index, term);
         }
 
         // Since we feed the state machine probably a batch of entry logs, represented by IntoIter,
@@ -688,12 +1411,59 @@ where
         // Edge case: If index is 1, no logging has been applied, and applied is set to 0
 
         // TODO: handle apply error: setting applied to error before
-        self.rsm
-            .apply(group_id, apply.replica_id, &GroupState::default(), applys)
-            .await;
+        self.deliver(group_id, replica_id, applys).await;
+        if last_index > prev_applied_index {
+            if let Some(entries) = applied_entries {
+                self.applied_consumers.publish(AppliedBatch {
+                    group_id,
+                    replica_id,
+                    first_index: prev_applied_index + 1,
+                    last_index,
+                    entries,
+                });
+            }
+        }
         // gs.set_applied(last_index, last_term).unwrap();
         state.applied_index = last_index;
         state.applied_term = last_term;
+        Ok(())
+    }
+
+    /// Apply the applys by the state machine, or buffer them if the group is
+    /// applied in pull mode.
+    async fn deliver(&self, group_id: u64, replica_id: u64, applys: Vec<Apply<W, R>>) {
+        if applys.is_empty() {
+            return;
+        }
+        self.invariants.check_fenced(group_id, &applys);
+        if let Some(applys) = self.pull_applys.push(group_id, applys) {
+            self.rsm
+                .apply(group_id, replica_id, &GroupState::default(), applys)
+                .await;
+        }
+    }
+
+    /// Apply the entries of the sub-batch to the state machine, then record
+    /// the applied index to storage as the checkpoint of the batch.
+    async fn checkpoint_apply<S: RaftStorage>(
+        &mut self,
+        group_id: u64,
+        replica_id: u64,
+        applys: Vec<Apply<W, R>>,
+        applied_index: u64,
+        gs: &S,
+    ) {
+        self.deliver(group_id, replica_id, applys).await;
+        // the applys of the group in pull mode may be not applied yet.
+        if self.pull_applys.is_pull(group_id) {
+            return;
+        }
+        if let Err(err) = gs.set_applied(applied_index).await {
+            warn!(
+                "node {}: group = {} checkpoint applied index = {} error: {}",
+                self.node_id, group_id, applied_index, err
+            );
+        }
     }
 
     async fn handle_applys<S: RaftStorage>(
@@ -703,13 +1473,60 @@ where
         applys: Vec<ApplyData<R>>,
         apply_state: &mut LocalApplyState,
         gs: &S,
-    ) {
-        for apply in applys {
-            self.handle_apply(apply, apply_state, gs).await;
+    ) -> Result<(), Error> {
+        let mut applys = applys.into_iter();
+        while let Some(apply) = applys.next() {
+            if let Err(err) = self.handle_apply(apply, apply_state, gs).await {
+                // the remaining applys will never be applied.
+                match err {
+                    Error::ApplyGap { expected, got } => {
+                        for apply in applys {
+                            Self::fail_proposals(apply.proposals, || Error::ApplyGap {
+                                expected,
+                                got,
+                            });
+                        }
+                    }
+                    Error::Fatal(_) => {
+                        let node_id = self.node_id;
+                        for apply in applys {
+                            Self::fail_proposals(apply.proposals, || {
+                                Error::RaftGroup(RaftGroupError::Halted(node_id, group_id))
+                            });
+                        }
+                    }
+                    Error::Deserialization(_) => {
+                        let reason = err.to_string();
+                        for apply in applys {
+                            Self::fail_proposals(apply.proposals, || undecodable_entry(&reason));
+                        }
+                    }
+                    _ => {}
+                }
+                return Err(err);
+            }
         }
+        Ok(())
     }
 }
 
+/// The error of the committed entry whose data can't be decompressed.
+fn undecodable_entry(reason: &str) -> Error {
+    Error::Deserialization(DeserializationError::Decompress(std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        reason.to_owned(),
+    )))
+}
+
+fn gate_held(node_id: u64, group_id: u64, index: u64, held_index: u64) -> Error {
+    Error::Propose(ProposeError::GateHeld {
+        node_id,
+        group_id,
+        index,
+        held_index,
+    })
+}
+
 /// Parse out ConfChangeV2 and MembershipChangeData from entry.
 /// Return Error if serialization error.
 fn parse_conf_change(
@@ -754,12 +1571,19 @@ fn parse_conf_change(
 mod test {
     use futures::Future;
     use std::collections::HashMap;
+    use std::sync::Arc;
     use tokio::sync::mpsc::unbounded_channel;
+    use tokio::sync::oneshot;
 
+    use crate::gate::gate_policy_payload;
+    use crate::proposal::Proposal;
     use crate::state::GroupState;
     use crate::state::GroupStates;
     use crate::storage::MemStorage;
     use crate::storage::MultiRaftMemoryStorage;
+    use crate::storage::MultiRaftStorage;
+    use crate::storage::RaftStorage;
+    use crate::storage::StorageExt;
     use crate::utils::compute_entry_size;
     use crate::Config;
     // use crate::multiraft::MultiStateMachine;
@@ -768,9 +1592,22 @@ mod test {
     use crate::Apply;
     use crate::StateMachine;
 
+    use super::AppliedConsumers;
     use super::ApplyData;
+    use super::ApplyDelegate;
     use super::ApplyMessage;
     use super::ApplyWorker;
+    use super::EventChannel;
+    use super::FatalError;
+    use super::LatencyRecorder;
+    use super::LocalApplyState;
+    use super::PullApplys;
+    use crate::EntryGate;
+    use crate::Error;
+    use crate::Event;
+    use crate::GateAction;
+    use crate::GateDecision;
+    use crate::ProposeError;
 
     struct NoOpStateMachine {}
     impl StateMachine<(), ()> for NoOpStateMachine {
@@ -832,6 +1669,7 @@ mod test {
         let (_request_tx, request_rx) = unbounded_channel();
         let (response_tx, _response_rx) = unbounded_channel();
         let (callback_tx, _callback_rx) = unbounded_channel();
+        let pull_applys = PullApplys::new(response_tx.clone());
         let cfg = Config {
             batch_apply,
             batch_size,
@@ -841,6 +1679,7 @@ mod test {
         let storage = MultiRaftMemoryStorage::new(1);
         let rsm = NoOpStateMachine {};
         let shared_states = GroupStates::new();
+        let event_chan = EventChannel::new(1);
         ApplyWorker::new(
             &cfg,
             rsm,
@@ -849,6 +1688,10 @@ mod test {
             request_rx,
             response_tx,
             callback_tx,
+            &event_chan,
+            Arc::new(LatencyRecorder::default()),
+            AppliedConsumers::default(),
+            pull_applys,
         )
     }
     #[test]
@@ -948,4 +1791,292 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn test_find_apply_gap() {
+        type Delegate = ApplyDelegate<(), (), NoOpStateMachine>;
+        let cases = vec![
+            // continuous with applied state
+            (0, new_entries(1, 5, 1, 0), None),
+            (4, new_entries(5, 8, 1, 0), None),
+            // applied state is unknown, give up check the first entry
+            (0, new_entries(10, 12, 1, 0), None),
+            // gap with applied state
+            (4, new_entries(6, 8, 1, 0), Some((5, 6))),
+            // regression with applied state
+            (4, new_entries(3, 8, 1, 0), Some((5, 3))),
+        ];
+
+        for (prev_applied_index, ents, expect) in cases {
+            assert_eq!(Delegate::find_apply_gap(prev_applied_index, &ents), expect);
+        }
+
+        // gap in entries
+        let mut ents = new_entries(5, 7, 1, 0);
+        ents.append(&mut new_entries(8, 10, 1, 0));
+        assert_eq!(Delegate::find_apply_gap(4, &ents), Some((7, 8)));
+    }
+
+    #[derive(Debug)]
+    struct RollingGate;
+
+    impl EntryGate for RollingGate {
+        fn policy(&self) -> u64 {
+            1
+        }
+
+        fn check(&self, _: u64, entry: &Entry) -> GateDecision {
+            match entry.index {
+                3 => GateDecision::Hold,
+                _ => GateDecision::Reject("unsupported".to_owned()),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct UpgradedGate;
+
+    impl EntryGate for UpgradedGate {
+        fn policy(&self) -> u64 {
+            2
+        }
+
+        fn check(&self, _: u64, _: &Entry) -> GateDecision {
+            GateDecision::Reject("unsupported".to_owned())
+        }
+    }
+
+    fn new_proposal(
+        index: u64,
+        term: u64,
+    ) -> (
+        Proposal<()>,
+        oneshot::Receiver<Result<((), Option<Vec<u8>>), Error>>,
+    ) {
+        let (tx, rx) = oneshot::channel();
+        let proposal = Proposal {
+            index,
+            term,
+            is_conf_change: false,
+            tx: Some(tx),
+            commit_tx: None,
+            context: None,
+            timeline: None,
+        };
+        (proposal, rx)
+    }
+
+    #[tokio::test]
+    async fn test_entry_gate() {
+        type Delegate = ApplyDelegate<(), (), NoOpStateMachine>;
+        let mut worker = new_worker(false, 0);
+        worker.delegate.gate = Arc::new(RollingGate);
+        let gs = MemStorage::new();
+        gs.append(&new_entries(1, 8, 1, 8)).await.unwrap();
+        let mut state = LocalApplyState {
+            gate_policy: 1,
+            ..Default::default()
+        };
+
+        // entries 1 and 2 are rejected as no-op, the apply is held at entry 3,
+        // the proposal of the held entry is failed.
+        let mut apply = new_apply(1, 1, 1, 1, 6, 8);
+        let (proposal, mut held_rx) = new_proposal(4, 1);
+        apply.proposals.push(proposal);
+        worker
+            .delegate
+            .handle_apply(apply, &mut state, &gs)
+            .await
+            .unwrap();
+        assert_eq!(state.applied_index, 2);
+        assert_eq!(state.held_index, 3);
+
+        let actions = worker
+            .delegate
+            .gated_events
+            .drain(..)
+            .map(|event| match event {
+                Event::EntryGated {
+                    index,
+                    policy,
+                    action,
+                    ..
+                } => (index, policy, action),
+                _ => unreachable!(),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            vec![
+                (1, 1, GateAction::Reject),
+                (2, 1, GateAction::Reject),
+                (3, 1, GateAction::Hold)
+            ]
+        );
+
+        assert!(matches!(
+            held_rx.try_recv().unwrap(),
+            Err(Error::Propose(ProposeError::GateHeld {
+                index: 4,
+                held_index: 3,
+                ..
+            }))
+        ));
+
+        // the following entries are not applied until the hold is released.
+        let mut apply = new_apply(1, 1, 1, 6, 8, 8);
+        let (proposal, mut held_rx) = new_proposal(6, 1);
+        apply.proposals.push(proposal);
+        worker
+            .delegate
+            .handle_apply(apply, &mut state, &gs)
+            .await
+            .unwrap();
+        assert_eq!(state.applied_index, 2);
+        assert_eq!(state.held_commit, (7, 1));
+        assert!(worker.delegate.gated_events.is_empty());
+        assert!(matches!(
+            held_rx.try_recv().unwrap(),
+            Err(Error::Propose(ProposeError::GateHeld {
+                index: 6,
+                held_index: 3,
+                ..
+            }))
+        ));
+
+        // the held entries are read from the storage and consulted by the
+        // upgraded gate once the hold is released, the policy of the upgraded
+        // gate is replicated.
+        worker.delegate.gate = Arc::new(UpgradedGate);
+        state.gate_policy = 2;
+        let apply = Delegate::take_held(1, 1, &mut state, &gs).unwrap().unwrap();
+        assert_eq!(state.held_index, 0);
+        assert_eq!(
+            apply
+                .entries
+                .iter()
+                .map(|ent| ent.index)
+                .collect::<Vec<_>>(),
+            vec![3, 4, 5, 6, 7]
+        );
+        worker
+            .delegate
+            .handle_apply(apply, &mut state, &gs)
+            .await
+            .unwrap();
+        assert_eq!(state.applied_index, 7);
+        assert_eq!(worker.delegate.gated_events.len(), 5);
+        assert!(Delegate::take_held(1, 1, &mut state, &gs)
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_entry_gate_policy() {
+        type Worker = ApplyWorker<(), (), NoOpStateMachine, MemStorage, MultiRaftMemoryStorage>;
+        let gs = MemStorage::new();
+        // entry 1 replicates the policy 2, the entries after it are gated by
+        // the policy.
+        let mut entries = new_entries(1, 4, 1, 8);
+        entries[0].data = gate_policy_payload(2).encode().unwrap();
+        gs.append(&entries).await.unwrap();
+        let new_apply = || {
+            let mut apply = new_apply(1, 1, 1, 1, 4, 8);
+            apply.entries = entries.clone();
+            apply
+        };
+
+        // the node of the old gate holds the entry instead of rejecting it.
+        let mut worker = new_worker(false, 0);
+        worker.delegate.gate = Arc::new(RollingGate);
+        let mut state = LocalApplyState::default();
+        worker
+            .delegate
+            .handle_apply(new_apply(), &mut state, &gs)
+            .await
+            .unwrap();
+        assert_eq!(state.gate_policy, 2);
+        assert_eq!(state.applied_index, 1);
+        assert_eq!(state.held_index, 2);
+        assert!(matches!(
+            worker.delegate.gated_events.as_slice(),
+            [Event::EntryGated {
+                index: 2,
+                policy: 1,
+                action: GateAction::Hold,
+                ..
+            }]
+        ));
+
+        // the node of the replicated policy rejects the entries.
+        let mut worker = new_worker(false, 0);
+        worker.delegate.gate = Arc::new(UpgradedGate);
+        let mut state = LocalApplyState::default();
+        worker
+            .delegate
+            .handle_apply(new_apply(), &mut state, &gs)
+            .await
+            .unwrap();
+        assert_eq!(state.applied_index, 3);
+        assert_eq!(state.held_index, 0);
+        assert!(worker.delegate.gated_events.iter().all(|event| matches!(
+            event,
+            Event::EntryGated {
+                action: GateAction::Reject,
+                ..
+            }
+        )));
+
+        // the replicated policy is restored from the group metadata.
+        worker.storage.group_storage(1, 1).await.unwrap();
+        Worker::persist_gate_policy(&worker.storage, 1, 1, 1, 2).await;
+        worker.load_apply_state(1, 1).await;
+        assert_eq!(worker.local_apply_states.get(&1).unwrap().gate_policy, 2);
+    }
+
+    #[tokio::test]
+    async fn test_apply_checkpoint() {
+        let mut worker = new_worker(false, 0);
+        worker.delegate.checkpoint_entries = 2;
+        let gs = MemStorage::new();
+        let mut state = LocalApplyState::default();
+
+        // the applied index is checkpointed after entries 2 and 4, the
+        // entries without data are applied as the noops.
+        let apply = new_apply(1, 1, 1, 1, 6, 0);
+        worker
+            .delegate
+            .handle_apply(apply, &mut state, &gs)
+            .await
+            .unwrap();
+        assert_eq!(state.applied_index, 5);
+        assert_eq!(gs.get_applied().await.unwrap(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_apply_commit_backward() {
+        let mut worker = new_worker(false, 0);
+        let gs = MemStorage::new();
+        let mut state = LocalApplyState::default();
+        state.applied_index = 10;
+        state.applied_term = 1;
+
+        // the group is halted instead of panic.
+        let apply = new_apply(1, 1, 1, 1, 6, 8);
+        let err = worker
+            .delegate
+            .handle_apply(apply, &mut state, &gs)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Fatal(FatalError::CommitBackward {
+                group_id: 1,
+                applied: (10, 1),
+                commit: (5, 1),
+                ..
+            })
+        ));
+        assert_eq!(state.applied_index, 10);
+    }
 }
@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+
+use crate::multiraft::ProposeResponse;
+
+use super::msg::ApplyData;
+
+/// The flow control of the applies of a group, see
+/// `Config::max_apply_unapplied_size`. The applies are held on the node once
+/// the unapplied bytes sent to the state machine reach the high watermark,
+/// the held applies are sent in one apply after the unapplied bytes drop to
+/// the low watermark, which is half of the high one.
+pub(crate) struct ApplyFlow<R: ProposeResponse> {
+    /// The (last index, bytes) of the applies sent to the state machine in
+    /// the order of index.
+    inflight: VecDeque<(u64, usize)>,
+    unapplied: usize,
+    /// The applies held while the flow is paused, they are merged into one.
+    held: Option<ApplyData<R>>,
+    paused: bool,
+}
+
+impl<R: ProposeResponse> Default for ApplyFlow<R> {
+    fn default() -> Self {
+        Self {
+            inflight: VecDeque::new(),
+            unapplied: 0,
+            held: None,
+            paused: false,
+        }
+    }
+}
+
+impl<R: ProposeResponse> ApplyFlow<R> {
+    /// Returns the apply to send to the state machine, or `None` if it's held.
+    /// `limit` is the high watermark, `0` is unlimited.
+    pub(crate) fn admit(&mut self, mut apply: ApplyData<R>, limit: usize) -> Option<ApplyData<R>> {
+        if let Some(held) = self.held.as_mut() {
+            held.try_batch(&mut apply, usize::MAX);
+            return None;
+        }
+        if limit != 0 && self.paused {
+            self.held = Some(apply);
+            return None;
+        }
+        self.track(&apply, limit);
+        Some(apply)
+    }
+
+    /// Release the bytes of the applies up to `applied_index`, returns the
+    /// held applies to send if the flow is resumed.
+    pub(crate) fn on_applied(&mut self, applied_index: u64, limit: usize) -> Option<ApplyData<R>> {
+        while let Some((index, bytes)) = self.inflight.front() {
+            if *index > applied_index {
+                break;
+            }
+            self.unapplied = self.unapplied.saturating_sub(*bytes);
+            self.inflight.pop_front();
+        }
+
+        if !self.paused || self.unapplied > limit / 2 {
+            return None;
+        }
+        self.paused = false;
+        let apply = self.held.take()?;
+        self.track(&apply, limit);
+        Some(apply)
+    }
+
+    /// Drop the held applies and the inflight bytes, the entries of them are
+    /// covered by the snapshot installed.
+    pub(crate) fn reset(&mut self) {
+        self.inflight.clear();
+        self.unapplied = 0;
+        self.held = None;
+        self.paused = false;
+    }
+
+    /// The bytes of the entries sent to the state machine but not applied.
+    #[inline]
+    pub(crate) fn unapplied_size(&self) -> usize {
+        self.unapplied
+    }
+
+    /// The bytes of the entries cached on the node until they are applied,
+    /// including the entries of the held applies.
+    #[inline]
+    pub(crate) fn cached_size(&self) -> usize {
+        self.unapplied + self.held.as_ref().map_or(0, |held| held.entries_size)
+    }
+
+    fn track(&mut self, apply: &ApplyData<R>, limit: usize) {
+        let last_index = match apply.entries.last() {
+            None => return,
+            Some(entry) => entry.index,
+        };
+        self.inflight.push_back((last_index, apply.entries_size));
+        self.unapplied += apply.entries_size;
+        self.paused = limit != 0 && self.unapplied >= limit;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ApplyFlow;
+    use crate::msg::ApplyData;
+    use crate::prelude::Entry;
+
+    fn new_apply(first: u64, last: u64, entry_size: usize) -> ApplyData<()> {
+        let entries = (first..=last)
+            .map(|index| Entry {
+                index,
+                term: 1,
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        ApplyData {
+            replica_id: 1,
+            group_id: 1,
+            term: 1,
+            commit_index: last,
+            commit_term: 1,
+            entries_size: entries.len() * entry_size,
+            entries,
+            proposals: vec![],
+        }
+    }
+
+    #[test]
+    fn test_apply_flow() {
+        let mut flow = ApplyFlow::default();
+        // the unlimited flow is never paused.
+        assert!(flow.admit(new_apply(1, 10, 10), 0).is_some());
+        assert!(!flow.paused);
+        assert!(flow.on_applied(10, 0).is_none());
+        assert_eq!(flow.unapplied_size(), 0);
+
+        // the flow is paused at the high watermark.
+        let limit = 100;
+        assert!(flow.admit(new_apply(11, 15, 10), limit).is_some());
+        assert!(flow.admit(new_apply(16, 20, 10), limit).is_some());
+        assert!(flow.paused);
+        assert!(flow.admit(new_apply(21, 22, 10), limit).is_none());
+        assert!(flow.admit(new_apply(23, 23, 10), limit).is_none());
+
+        // the flow is resumed at the low watermark with the held applies.
+        assert!(flow.on_applied(14, limit).is_none());
+        assert_eq!(flow.unapplied_size(), 100);
+        let apply = flow.on_applied(15, limit).unwrap();
+        assert!(!flow.paused);
+        assert_eq!(
+            apply
+                .entries
+                .iter()
+                .map(|entry| entry.index)
+                .collect::<Vec<_>>(),
+            (21..=23).collect::<Vec<_>>()
+        );
+        assert_eq!(flow.unapplied_size(), 80);
+        assert!(flow.on_applied(23, limit).is_none());
+        assert_eq!(flow.unapplied_size(), 0);
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use crate::Error;
+
+/// The operations on the groups authorized by `Authorizer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    CreateGroup,
+    RemoveGroup,
+    /// Write to the group.
+    Propose,
+    /// Change the membership of the group.
+    Membership,
+    /// Propose the admin entry to the group, see `MultiRaft::propose_admin`.
+    Admin,
+}
+
+/// Authorizer decides whether the operation on the group is permitted, it
+/// allows the multi-tenant embedders to gate the groups of tenants without
+/// wrapping every API. The management operations are always authorized, the
+/// proposals are authorized only if `Config::authorize_proposals` is enabled.
+pub trait Authorizer: Debug + Send + Sync + 'static {
+    /// Authorize the operation on the group of the node. Returns the reason
+    /// if the operation is denied.
+    fn authorize(&self, node_id: u64, group_id: u64, op: Operation) -> Result<(), String>;
+
+    /// The authenticated principal of the proposals to the group of the node,
+    /// it's recorded with the proposer of the entries if
+    /// `Config::annotate_proposer` is enabled. default is `None`.
+    fn principal(&self, _node_id: u64, _group_id: u64) -> Option<String> {
+        None
+    }
+}
+
+/// Permits all operations, it's the default authorizer.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AllowAll;
+
+impl Authorizer for AllowAll {
+    fn authorize(&self, _: u64, _: u64, _: Operation) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Authorize the operations with the authorizer, the result is returned as
+/// `Error::PermissionDenied` if denied.
+pub(crate) fn authorize(
+    authorizer: &dyn Authorizer,
+    node_id: u64,
+    group_id: u64,
+    op: Operation,
+) -> Result<(), Error> {
+    authorizer
+        .authorize(node_id, group_id, op)
+        .map_err(|reason| Error::PermissionDenied {
+            group_id,
+            operation: op,
+            reason,
+        })
+}
+
+/// Caches the decisions of the proposals per group, so that the authorizer
+/// is consulted only once for the group until invalidated.
+#[derive(Debug)]
+pub(crate) struct AuthorizationCache {
+    node_id: u64,
+    authorizer: Arc<dyn Authorizer>,
+    decisions: RwLock<HashMap<(u64, Operation), Result<(), String>>>,
+}
+
+impl AuthorizationCache {
+    pub(crate) fn new(node_id: u64, authorizer: Arc<dyn Authorizer>) -> Self {
+        Self {
+            node_id,
+            authorizer,
+            decisions: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub(crate) fn authorize(&self, group_id: u64, op: Operation) -> Result<(), Error> {
+        let decision = self.decisions.read().unwrap().get(&(group_id, op)).cloned();
+        let decision = match decision {
+            Some(decision) => decision,
+            None => {
+                let decision = self.authorizer.authorize(self.node_id, group_id, op);
+                self.decisions
+                    .write()
+                    .unwrap()
+                    .insert((group_id, op), decision.clone());
+                decision
+            }
+        };
+        decision.map_err(|reason| Error::PermissionDenied {
+            group_id,
+            operation: op,
+            reason,
+        })
+    }
+
+    /// Drop the cached decisions of the group.
+    pub(crate) fn invalidate(&self, group_id: u64) {
+        self.decisions
+            .write()
+            .unwrap()
+            .retain(|(id, _), _| *id != group_id);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use super::AuthorizationCache;
+    use super::Authorizer;
+    use super::Operation;
+    use crate::Error;
+
+    #[derive(Debug, Default)]
+    struct OddGroups {
+        calls: AtomicUsize,
+    }
+
+    impl Authorizer for OddGroups {
+        fn authorize(&self, _: u64, group_id: u64, _: Operation) -> Result<(), String> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            if group_id % 2 == 1 {
+                Ok(())
+            } else {
+                Err("even group".to_owned())
+            }
+        }
+    }
+
+    #[test]
+    fn test_authorization_cache() {
+        let authorizer = Arc::new(OddGroups::default());
+        let cache = AuthorizationCache::new(1, authorizer.clone());
+        for _ in 0..3 {
+            assert!(cache.authorize(1, Operation::Propose).is_ok());
+            match cache.authorize(2, Operation::Propose) {
+                Err(Error::PermissionDenied {
+                    group_id: 2,
+                    operation: Operation::Propose,
+                    ..
+                }) => {}
+                res => panic!("expected permission denied, got {:?}", res),
+            }
+        }
+        assert_eq!(authorizer.calls.load(Ordering::SeqCst), 2);
+
+        cache.invalidate(2);
+        assert!(cache.authorize(2, Operation::Propose).is_err());
+        assert!(cache.authorize(1, Operation::Propose).is_ok());
+        assert_eq!(authorizer.calls.load(Ordering::SeqCst), 3);
+    }
+}
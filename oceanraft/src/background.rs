@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The kind of the background jobs which share the budget of the node, see
+/// `Config::background_budget`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum JobKind {
+    /// The raft log compaction, see `Config::compaction_policy`.
+    Compaction,
+    /// The snapshot builds of the state machine, they are run by the
+    /// application with the permits of `MultiRaft::background_scheduler`.
+    Snapshot,
+    /// The replica descriptor gc, see `Config::replica_desc_gc_interval_ticks`.
+    ReplicaDescGc,
+    /// The scrubbing of the storage, it's run by the application with the
+    /// permits of `MultiRaft::background_scheduler`.
+    Scrub,
+}
+
+const JOB_KINDS: [JobKind; 4] = [
+    JobKind::Compaction,
+    JobKind::Snapshot,
+    JobKind::ReplicaDescGc,
+    JobKind::Scrub,
+];
+
+/// The budget of the background jobs of the node per tick. The cost of a job
+/// is charged after it's done, so a job is started while the budget is left
+/// and the overdraft is paid by the following ticks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundBudget {
+    /// The bytes the jobs may read and write per tick, `0` is unlimited.
+    /// default is `0`.
+    pub io_bytes_per_tick: u64,
+    /// The time the jobs may run per tick, it's measured while the permit of
+    /// the job is held. `Duration::ZERO` is unlimited. default is
+    /// `Duration::ZERO`.
+    pub cpu_time_per_tick: Duration,
+    /// The kinds in the order of priority, the kinds absent are the lowest in
+    /// the default order. A job is deferred while a kind of higher priority
+    /// is waiting for the budget. default is compaction, snapshot, replica
+    /// descriptor gc and then scrubbing.
+    pub priorities: Vec<JobKind>,
+}
+
+impl Default for BackgroundBudget {
+    fn default() -> Self {
+        Self {
+            io_bytes_per_tick: 0,
+            cpu_time_per_tick: Duration::ZERO,
+            priorities: JOB_KINDS.to_vec(),
+        }
+    }
+}
+
+/// The metrics of the jobs of a kind, see `MultiRaft::background_stats`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct JobStats {
+    pub kind: JobKind,
+    pub runs: u64,
+    /// The ticks in which the jobs were deferred for the budget.
+    pub deferred: u64,
+    pub io_bytes: u64,
+    pub cpu_time: Duration,
+}
+
+impl JobStats {
+    fn new(kind: JobKind) -> Self {
+        Self {
+            kind,
+            runs: 0,
+            deferred: 0,
+            io_bytes: 0,
+            cpu_time: Duration::ZERO,
+        }
+    }
+}
+
+struct SchedulerState {
+    tick: u64,
+    io_bytes: i64,
+    cpu_time_us: i64,
+    /// The kinds denied by the budget with the tick they were last denied.
+    waiting: HashMap<JobKind, u64>,
+    stats: HashMap<JobKind, JobStats>,
+}
+
+/// BackgroundScheduler is shared by the maintenance of the node and the jobs
+/// of the application, a job runs while it holds the permit of the scheduler.
+/// The budget is refilled by the ticks of the node.
+pub struct BackgroundScheduler {
+    budget: BackgroundBudget,
+    /// The kinds in the order of priority.
+    order: Vec<JobKind>,
+    state: Mutex<SchedulerState>,
+}
+
+impl BackgroundScheduler {
+    pub(crate) fn new(budget: BackgroundBudget) -> Self {
+        let mut order = vec![];
+        for kind in budget.priorities.iter().chain(JOB_KINDS.iter()) {
+            if !order.contains(kind) {
+                order.push(*kind);
+            }
+        }
+        let state = SchedulerState {
+            tick: 0,
+            io_bytes: budget.io_bytes_per_tick as i64,
+            cpu_time_us: budget.cpu_time_per_tick.as_micros() as i64,
+            waiting: HashMap::new(),
+            stats: order
+                .iter()
+                .map(|kind| (*kind, JobStats::new(*kind)))
+                .collect(),
+        };
+        Self {
+            budget,
+            order,
+            state: Mutex::new(state),
+        }
+    }
+
+    /// The kinds in the order of priority.
+    pub(crate) fn priorities(&self) -> &[JobKind] {
+        &self.order
+    }
+
+    /// Refill the budget of a tick, the overdraft of the previous ticks is
+    /// paid first.
+    pub(crate) fn tick(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.tick += 1;
+        let io_bytes = self.budget.io_bytes_per_tick as i64;
+        state.io_bytes = (state.io_bytes + io_bytes).min(io_bytes);
+        let cpu_time_us = self.budget.cpu_time_per_tick.as_micros() as i64;
+        state.cpu_time_us = (state.cpu_time_us + cpu_time_us).min(cpu_time_us);
+    }
+
+    /// Acquire the permit to run the job of the kind, returns `None` if the
+    /// budget of the tick is spent or a kind of higher priority is waiting
+    /// for the budget. The cost of the job is charged when the permit is
+    /// dropped.
+    pub fn try_acquire(self: &Arc<Self>, kind: JobKind) -> Option<BackgroundPermit> {
+        let mut state = self.state.lock().unwrap();
+        let tick = state.tick;
+        let spent = (self.budget.io_bytes_per_tick != 0 && state.io_bytes <= 0)
+            || (!self.budget.cpu_time_per_tick.is_zero() && state.cpu_time_us <= 0);
+        // the waiting expires after a tick, so the kind which no longer
+        // retries does not starve the others.
+        let yielded = self
+            .order
+            .iter()
+            .take_while(|higher| **higher != kind)
+            .any(|higher| {
+                state
+                    .waiting
+                    .get(higher)
+                    .map_or(false, |since| since + 1 >= tick)
+            });
+
+        if spent || yielded {
+            if state.waiting.insert(kind, tick) != Some(tick) {
+                if let Some(stats) = state.stats.get_mut(&kind) {
+                    stats.deferred += 1;
+                }
+            }
+            return None;
+        }
+
+        state.waiting.remove(&kind);
+        if let Some(stats) = state.stats.get_mut(&kind) {
+            stats.runs += 1;
+        }
+        Some(BackgroundPermit {
+            scheduler: self.clone(),
+            kind,
+            io_bytes: 0,
+            started: Instant::now(),
+        })
+    }
+
+    /// The metrics of the jobs in the order of priority.
+    pub fn stats(&self) -> Vec<JobStats> {
+        let state = self.state.lock().unwrap();
+        self.order
+            .iter()
+            .filter_map(|kind| state.stats.get(kind).cloned())
+            .collect()
+    }
+
+    fn release(&self, kind: JobKind, io_bytes: u64, cpu_time: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.io_bytes = state.io_bytes.saturating_sub(io_bytes as i64);
+        state.cpu_time_us = state
+            .cpu_time_us
+            .saturating_sub(cpu_time.as_micros() as i64);
+        if let Some(stats) = state.stats.get_mut(&kind) {
+            stats.io_bytes += io_bytes;
+            stats.cpu_time += cpu_time;
+        }
+    }
+}
+
+/// The permit of a background job, see `BackgroundScheduler::try_acquire`.
+/// The time is charged from the permit is acquired to it's dropped.
+pub struct BackgroundPermit {
+    scheduler: Arc<BackgroundScheduler>,
+    kind: JobKind,
+    io_bytes: u64,
+    started: Instant,
+}
+
+impl BackgroundPermit {
+    #[inline]
+    pub fn kind(&self) -> JobKind {
+        self.kind
+    }
+
+    /// Record the bytes read or written by the job.
+    pub fn record_io(&mut self, bytes: u64) {
+        self.io_bytes += bytes;
+    }
+}
+
+impl Drop for BackgroundPermit {
+    fn drop(&mut self) {
+        self.scheduler
+            .release(self.kind, self.io_bytes, self.started.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::BackgroundBudget;
+    use super::BackgroundScheduler;
+    use super::JobKind;
+
+    #[test]
+    fn test_background_budget() {
+        // the unlimited budget never defers the jobs.
+        let scheduler = Arc::new(BackgroundScheduler::new(BackgroundBudget::default()));
+        for _ in 0..3 {
+            let mut permit = scheduler.try_acquire(JobKind::Scrub).unwrap();
+            permit.record_io(u64::MAX / 4);
+        }
+        assert_eq!(scheduler.stats()[3].runs, 3);
+
+        let scheduler = Arc::new(BackgroundScheduler::new(BackgroundBudget {
+            io_bytes_per_tick: 100,
+            priorities: vec![JobKind::Scrub],
+            ..Default::default()
+        }));
+        assert_eq!(
+            scheduler.priorities(),
+            &[
+                JobKind::Scrub,
+                JobKind::Compaction,
+                JobKind::Snapshot,
+                JobKind::ReplicaDescGc
+            ]
+        );
+
+        // the overdraft of the job is paid by the following ticks.
+        let mut permit = scheduler.try_acquire(JobKind::Compaction).unwrap();
+        permit.record_io(250);
+        drop(permit);
+        assert!(scheduler.try_acquire(JobKind::Compaction).is_none());
+        assert!(scheduler.try_acquire(JobKind::Compaction).is_none());
+        scheduler.tick();
+        assert!(scheduler.try_acquire(JobKind::Compaction).is_none());
+
+        // the kind of lower priority yields to the waiting compaction.
+        scheduler.tick();
+        assert!(scheduler.try_acquire(JobKind::Snapshot).is_none());
+        assert!(scheduler.try_acquire(JobKind::Scrub).is_some());
+        assert!(scheduler.try_acquire(JobKind::Compaction).is_some());
+        assert!(scheduler.try_acquire(JobKind::Snapshot).is_some());
+
+        // the waiting kind expires after a tick.
+        let mut permit = scheduler.try_acquire(JobKind::Scrub).unwrap();
+        permit.record_io(100);
+        drop(permit);
+        assert!(scheduler.try_acquire(JobKind::Scrub).is_none());
+        scheduler.tick();
+        assert!(scheduler.try_acquire(JobKind::Compaction).is_none());
+        scheduler.tick();
+        scheduler.tick();
+        assert!(scheduler.try_acquire(JobKind::Compaction).is_some());
+
+        let stats = scheduler.stats();
+        assert_eq!(stats[0].kind, JobKind::Scrub);
+        assert_eq!((stats[0].runs, stats[0].deferred), (2, 1));
+        assert_eq!(stats[0].io_bytes, 100);
+        // the compaction is deferred once per tick.
+        assert_eq!((stats[1].runs, stats[1].deferred), (3, 3));
+        assert_eq!(stats[1].io_bytes, 250);
+        assert_eq!((stats[2].runs, stats[2].deferred), (1, 1));
+    }
+
+    #[test]
+    fn test_background_cpu_budget() {
+        let scheduler = Arc::new(BackgroundScheduler::new(BackgroundBudget {
+            cpu_time_per_tick: Duration::from_micros(1),
+            ..Default::default()
+        }));
+        let permit = scheduler.try_acquire(JobKind::ReplicaDescGc).unwrap();
+        std::thread::sleep(Duration::from_millis(1));
+        drop(permit);
+        assert!(scheduler.try_acquire(JobKind::ReplicaDescGc).is_none());
+        assert!(scheduler.stats()[2].cpu_time >= Duration::from_millis(1));
+    }
+}
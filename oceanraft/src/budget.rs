@@ -0,0 +1,404 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::collections::VecDeque;
+
+/// The priority used when the group is created without priority.
+pub const DEFAULT_PRIORITY: u32 = 1;
+
+/// The budget is under pressure when the used memory of all groups
+/// exceeds `PRESSURE_PERCENT` percent of the capacity, or any acquire
+/// failed since last rebalance.
+const PRESSURE_PERCENT: usize = 80;
+
+/// Memory budget state of a group.
+#[derive(Debug, Default)]
+struct GroupBudget {
+    priority: u32,
+    /// Bytes of the proposals that are queued but not yet applied and the
+    /// bytes of the cached entries.
+    used: usize,
+    /// Bytes of the committed entries cached on the node until they are
+    /// applied, they are included in `used`.
+    cached: usize,
+    /// Bytes allotted to the group by the last rebalance.
+    allotted: usize,
+    /// Number of proposals since last rebalance, decayed by half on
+    /// every rebalance.
+    activity: u64,
+    /// The tick of the budget when the group last acquired memory.
+    active_tick: usize,
+    /// (index, bytes) of proposals waiting to be applied.
+    inflights: VecDeque<(u64, usize)>,
+}
+
+impl GroupBudget {
+    #[inline]
+    fn weight(&self, idle: bool, pressure: bool) -> u64 {
+        if idle && pressure {
+            return 0;
+        }
+        self.priority as u64 * (1 + self.activity)
+    }
+}
+
+/// MemoryBudget allots memory of the proposal queues and entry caches of the node
+/// to groups by configured priority and recent activity. Groups that stay idle
+/// for `idle_ticks` are reclaimed to their used bytes under pressure.
+///
+/// The totals of the allotted and used bytes are kept up to date, so the
+/// groups are only walked by the rebalance, which runs once an acquire failed,
+/// otherwise every `idle_ticks` ticks if the budget changed or is under
+/// pressure.
+///
+/// A capacity of `0` means unlimited and all acquires succeed.
+pub struct MemoryBudget {
+    capacity: usize,
+    idle_ticks: usize,
+    groups: HashMap<u64, GroupBudget>,
+    /// The sum of the allotted bytes of all groups.
+    allotted: usize,
+    /// The sum of the used bytes of all groups.
+    used: usize,
+    ticks: usize,
+    starved: bool,
+    /// True if any group acquired or released memory since last rebalance.
+    dirty: bool,
+    /// The groups whose allocation changed since last taken.
+    changed: HashSet<u64>,
+}
+
+impl MemoryBudget {
+    pub fn new(capacity: usize, idle_ticks: usize) -> Self {
+        Self {
+            capacity,
+            idle_ticks,
+            groups: HashMap::new(),
+            allotted: 0,
+            used: 0,
+            ticks: 0,
+            starved: false,
+            dirty: false,
+            changed: HashSet::new(),
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.capacity != 0
+    }
+
+    /// Register the group with priority, `0` means `DEFAULT_PRIORITY`.
+    pub fn register(&mut self, group_id: u64, priority: u32) {
+        let priority = if priority == 0 {
+            DEFAULT_PRIORITY
+        } else {
+            priority
+        };
+        let ticks = self.ticks;
+        let budget = self.groups.entry(group_id).or_insert_with(|| GroupBudget {
+            active_tick: ticks,
+            ..Default::default()
+        });
+        budget.priority = priority;
+        self.rebalance();
+    }
+
+    pub fn unregister(&mut self, group_id: u64) {
+        if let Some(budget) = self.groups.remove(&group_id) {
+            self.allotted -= budget.allotted;
+            self.used -= budget.used;
+            self.changed.remove(&group_id);
+            self.rebalance();
+        }
+    }
+
+    #[inline]
+    pub fn priority(&self, group_id: u64) -> Option<u32> {
+        self.groups.get(&group_id).map(|budget| budget.priority)
+    }
+
+    /// Get (allotted, used) bytes of the group.
+    #[inline]
+    pub fn allocation(&self, group_id: u64) -> Option<(usize, usize)> {
+        self.groups
+            .get(&group_id)
+            .map(|budget| (budget.allotted, budget.used))
+    }
+
+    /// Returns the groups whose allocation changed since last taken.
+    pub fn take_changed(&mut self) -> HashSet<u64> {
+        std::mem::take(&mut self.changed)
+    }
+
+    /// Reserve `bytes` for the group. If the allotment of the group is not
+    /// enough, the group borrows from the unallotted memory of the node.
+    /// Returns `false` if the budget is exhausted.
+    pub fn acquire(&mut self, group_id: u64, bytes: usize) -> bool {
+        if !self.is_enabled() {
+            return true;
+        }
+
+        let free = self.capacity.saturating_sub(self.allotted);
+        let budget = match self.groups.get_mut(&group_id) {
+            None => return false,
+            Some(budget) => budget,
+        };
+        budget.active_tick = self.ticks;
+        budget.activity += 1;
+        self.dirty = true;
+
+        let need = budget.used + bytes;
+        if need > budget.allotted {
+            if need - budget.allotted > free {
+                self.starved = true;
+                return false;
+            }
+            self.allotted += need - budget.allotted;
+            budget.allotted = need;
+        }
+        budget.used = need;
+        self.used += bytes;
+        self.changed.insert(group_id);
+        true
+    }
+
+    /// Release the reserved `bytes` of the group which is not proposed.
+    pub fn release(&mut self, group_id: u64, bytes: usize) {
+        if let Some(budget) = self.groups.get_mut(&group_id) {
+            let bytes = std::cmp::min(bytes, budget.used - budget.cached);
+            budget.used -= bytes;
+            self.used -= bytes;
+            self.dirty = true;
+            self.changed.insert(group_id);
+        }
+    }
+
+    /// Set the bytes of the committed entries of the group cached on the node
+    /// until they are applied. The entries are committed already, so the
+    /// group exceeds its allotment instead of failing, and puts the budget
+    /// under pressure if it can't borrow the unallotted memory.
+    pub fn set_cached(&mut self, group_id: u64, bytes: usize) {
+        if !self.is_enabled() {
+            return;
+        }
+        let free = self.capacity.saturating_sub(self.allotted);
+        let budget = match self.groups.get_mut(&group_id) {
+            None => return,
+            Some(budget) => budget,
+        };
+        if budget.cached == bytes {
+            return;
+        }
+
+        let used = budget.used - budget.cached + bytes;
+        self.used = self.used - budget.used + used;
+        budget.used = used;
+        budget.cached = bytes;
+        if used > budget.allotted {
+            if used - budget.allotted > free {
+                self.starved = true;
+            }
+            self.allotted += used - budget.allotted;
+            budget.allotted = used;
+        }
+        self.dirty = true;
+        self.changed.insert(group_id);
+    }
+
+    /// Track the reserved `bytes` of the proposal at `index` until it is applied.
+    pub fn track(&mut self, group_id: u64, index: u64, bytes: usize) {
+        if !self.is_enabled() {
+            return;
+        }
+        if let Some(budget) = self.groups.get_mut(&group_id) {
+            budget.inflights.push_back((index, bytes));
+        }
+    }
+
+    /// Release the bytes of proposals of the group which index less than
+    /// or equal to `applied_index`.
+    pub fn release_to(&mut self, group_id: u64, applied_index: u64) {
+        let budget = match self.groups.get_mut(&group_id) {
+            None => return,
+            Some(budget) => budget,
+        };
+
+        while let Some((index, bytes)) = budget.inflights.front() {
+            if *index > applied_index {
+                break;
+            }
+            let bytes = std::cmp::min(*bytes, budget.used - budget.cached);
+            budget.used -= bytes;
+            self.used -= bytes;
+            self.dirty = true;
+            self.changed.insert(group_id);
+            budget.inflights.pop_front();
+        }
+    }
+
+    /// Advance the ticks and rebalance the allotments if it's due. Returns the
+    /// groups whose allotment was reclaimed.
+    pub fn tick(&mut self) -> Vec<u64> {
+        if !self.is_enabled() {
+            return vec![];
+        }
+
+        self.ticks += 1;
+        let interval = std::cmp::max(self.idle_ticks, 1);
+        let due =
+            self.starved || (self.ticks % interval == 0 && (self.dirty || self.is_pressure()));
+        if !due {
+            return vec![];
+        }
+        self.rebalance()
+    }
+
+    #[inline]
+    fn is_pressure(&self) -> bool {
+        self.starved || self.used * 100 >= self.capacity * PRESSURE_PERCENT
+    }
+
+    /// Distribute capacity to groups proportional to their weight, the allotment
+    /// of a group is never less than its used bytes.
+    fn rebalance(&mut self) -> Vec<u64> {
+        if !self.is_enabled() || self.groups.is_empty() {
+            return vec![];
+        }
+
+        let pressure = self.is_pressure();
+        self.starved = false;
+        self.dirty = false;
+        let (ticks, idle_ticks) = (self.ticks, self.idle_ticks);
+        let is_idle = |budget: &GroupBudget| ticks - budget.active_tick >= idle_ticks;
+        let total_weight: u64 = self
+            .groups
+            .values()
+            .map(|budget| budget.weight(is_idle(budget), pressure))
+            .sum();
+
+        let mut reclaimed = vec![];
+        self.allotted = 0;
+        for (group_id, budget) in self.groups.iter_mut() {
+            let weight = budget.weight(is_idle(budget), pressure);
+            let share = if total_weight == 0 {
+                0
+            } else {
+                (self.capacity as u128 * weight as u128 / total_weight as u128) as usize
+            };
+            let allotted = std::cmp::max(share, budget.used);
+            if allotted < budget.allotted && weight == 0 {
+                reclaimed.push(*group_id);
+            }
+            if allotted != budget.allotted {
+                self.changed.insert(*group_id);
+            }
+            budget.allotted = allotted;
+            budget.activity /= 2;
+            self.allotted += allotted;
+        }
+        reclaimed
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MemoryBudget;
+
+    #[test]
+    fn test_budget_priority_allotment() {
+        let mut budget = MemoryBudget::new(1000, 10);
+        budget.register(1, 1);
+        budget.register(2, 3);
+        assert_eq!(budget.allocation(1), Some((250, 0)));
+        assert_eq!(budget.allocation(2), Some((750, 0)));
+
+        // group 1 can borrow nothing since all memory is allotted.
+        assert!(budget.acquire(1, 250));
+        assert!(!budget.acquire(1, 1));
+        budget.track(1, 1, 250);
+        budget.release_to(1, 1);
+        assert_eq!(budget.allocation(1), Some((250, 0)));
+
+        budget.unregister(2);
+        assert_eq!(budget.allocation(1), Some((1000, 0)));
+        assert_eq!(budget.allocation(2), None);
+    }
+
+    #[test]
+    fn test_budget_reclaim_idle() {
+        let mut budget = MemoryBudget::new(1000, 2);
+        budget.register(1, 1);
+        budget.register(2, 1);
+        assert_eq!(budget.allocation(1), Some((500, 0)));
+
+        // the active group 2 gets more allotment than the quiet group 1 once
+        // the rebalance is due.
+        assert!(budget.acquire(2, 400));
+        budget.track(2, 1, 400);
+        assert!(budget.tick().is_empty());
+        assert_eq!(budget.allocation(1), Some((500, 0)));
+        assert!(budget.tick().is_empty());
+        assert_eq!(budget.allocation(1), Some((333, 0)));
+        assert_eq!(budget.allocation(2), Some((666, 400)));
+
+        // group 1 is idle but is not reclaimed without pressure.
+        assert!(budget.acquire(2, 250));
+        budget.track(2, 2, 250);
+        assert!(budget.tick().is_empty());
+        assert!(budget.tick().is_empty());
+        assert_eq!(budget.allocation(1), Some((333, 0)));
+
+        // the failed acquire puts the budget under pressure, idle group 1 is reclaimed.
+        assert!(!budget.acquire(2, 200));
+        assert_eq!(budget.tick(), vec![1]);
+        assert_eq!(budget.allocation(1), Some((0, 0)));
+        assert_eq!(budget.allocation(2), Some((1000, 650)));
+
+        assert!(budget.acquire(2, 200));
+        budget.track(2, 3, 200);
+        budget.release_to(2, 2);
+        assert_eq!(budget.allocation(2), Some((1000, 200)));
+    }
+
+    #[test]
+    fn test_budget_cached_entries() {
+        let mut budget = MemoryBudget::new(1000, 10);
+        budget.register(1, 1);
+        budget.register(2, 1);
+        budget.take_changed();
+
+        // the cached entries exceed the allotment instead of failing, the
+        // proposals of the group can't borrow more and the release of the
+        // proposals doesn't release the cached entries.
+        budget.set_cached(1, 600);
+        assert_eq!(budget.allocation(1), Some((600, 600)));
+        assert_eq!((budget.allotted, budget.used), (1100, 600));
+        assert!(!budget.acquire(1, 1));
+        budget.release(1, 600);
+        assert_eq!(budget.allocation(1), Some((600, 600)));
+        assert_eq!(
+            budget.take_changed().into_iter().collect::<Vec<_>>(),
+            vec![1]
+        );
+
+        // the failed acquire rebalances on the next tick.
+        assert!(budget.tick().is_empty());
+        assert_eq!(budget.allocation(1), Some((666, 600)));
+        assert_eq!(budget.allocation(2), Some((333, 0)));
+        assert_eq!((budget.allotted, budget.used), (999, 600));
+
+        budget.set_cached(1, 0);
+        assert_eq!(budget.allocation(1), Some((666, 0)));
+        assert_eq!(budget.used, 0);
+        budget.unregister(1);
+        assert_eq!((budget.allotted, budget.used), (1000, 0));
+    }
+
+    #[test]
+    fn test_budget_disabled() {
+        let mut budget = MemoryBudget::new(0, 10);
+        budget.register(1, 1);
+        assert!(budget.acquire(1, usize::MAX));
+        assert!(budget.tick().is_empty());
+    }
+}
@@ -0,0 +1,157 @@
+use std::fmt::Debug;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// TimeSource is the clock of the leader lease, see `Config::time_source`.
+///
+/// The lease is safe only if it expires on the leader before the followers
+/// which acknowledged the leader stop rejecting the other candidates, that is
+/// the clocks of the nodes advance at the bounded different rates. The time
+/// source reports the bounds, and the leader shortens the lease by the margin
+/// of them, see `lease_margin`.
+pub trait TimeSource: Debug + Send + Sync + 'static {
+    /// The current time, it must never go backwards.
+    fn now(&self) -> Instant;
+
+    /// The maximum rate the clock drifts from the true time in parts per
+    /// million, e.g. `200` for the typical crystal oscillator.
+    fn max_drift_ppm(&self) -> u64 {
+        0
+    }
+
+    /// The maximum error of the clock at the moment, e.g. the offset bound
+    /// reported by the PTP or NTP daemon. `Duration::MAX` if the error is
+    /// unbounded, the lease is never held then.
+    fn max_error(&self) -> Duration {
+        Duration::ZERO
+    }
+}
+
+/// The monotonic clock of the node (`CLOCK_MONOTONIC` on linux), it's the
+/// default time source. The clock is not disciplined, so the drift is bounded
+/// by the oscillator of the node only, which is configured by
+/// `with_max_drift_ppm`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MonotonicClock {
+    max_drift_ppm: u64,
+}
+
+impl MonotonicClock {
+    pub fn with_max_drift_ppm(max_drift_ppm: u64) -> Self {
+        Self { max_drift_ppm }
+    }
+}
+
+impl TimeSource for MonotonicClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn max_drift_ppm(&self) -> u64 {
+        self.max_drift_ppm
+    }
+}
+
+/// The clock disciplined by PTP or NTP, whose drift is bounded by the daemon
+/// instead of the oscillator. The time is read from the monotonic clock, which
+/// the daemon slews without steps, and the application updates the error
+/// bound from the status of the daemon, e.g. the offset of `ptp4l` or the root
+/// dispersion of `chronyd`, by `set_max_error`.
+///
+/// The lease is not held while the clock is not synchronized, the reads of
+/// `ConsistencyLevel::Lease` fall back to read index then.
+#[derive(Debug)]
+pub struct DisciplinedClock {
+    max_drift_ppm: u64,
+    max_error_us: AtomicU64,
+    synchronized: AtomicBool,
+}
+
+impl DisciplinedClock {
+    /// Create the clock which is synchronized with the error bound.
+    pub fn new(max_drift_ppm: u64, max_error: Duration) -> Self {
+        Self {
+            max_drift_ppm,
+            max_error_us: AtomicU64::new(max_error.as_micros() as u64),
+            synchronized: AtomicBool::new(true),
+        }
+    }
+
+    pub fn set_max_error(&self, max_error: Duration) {
+        self.max_error_us
+            .store(max_error.as_micros() as u64, Ordering::Release);
+    }
+
+    /// Set the clock lost or regained the synchronization with the source.
+    pub fn set_synchronized(&self, synchronized: bool) {
+        self.synchronized.store(synchronized, Ordering::Release);
+    }
+
+    pub fn is_synchronized(&self) -> bool {
+        self.synchronized.load(Ordering::Acquire)
+    }
+}
+
+impl TimeSource for DisciplinedClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn max_drift_ppm(&self) -> u64 {
+        self.max_drift_ppm
+    }
+
+    fn max_error(&self) -> Duration {
+        if !self.is_synchronized() {
+            return Duration::MAX;
+        }
+        Duration::from_micros(self.max_error_us.load(Ordering::Acquire))
+    }
+}
+
+/// The margin the leader shortens the lease of `duration` by. The clocks of
+/// the leader and the followers drift in the opposite directions in the worst
+/// case, so the margin is twice of the drift over the duration plus the error
+/// of the clock.
+pub(crate) fn lease_margin(source: &dyn TimeSource, duration: Duration) -> Duration {
+    let drift_ns = duration.as_nanos() * 2 * source.max_drift_ppm() as u128 / 1_000_000;
+    let drift = Duration::from_nanos(drift_ns.min(u64::MAX as u128) as u64);
+    drift.saturating_add(source.max_error())
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::lease_margin;
+    use super::DisciplinedClock;
+    use super::MonotonicClock;
+
+    #[test]
+    fn test_lease_margin() {
+        let duration = Duration::from_secs(1);
+        assert_eq!(
+            lease_margin(&MonotonicClock::default(), duration),
+            Duration::ZERO
+        );
+        assert_eq!(
+            lease_margin(&MonotonicClock::with_max_drift_ppm(500), duration),
+            Duration::from_millis(1)
+        );
+
+        let clock = DisciplinedClock::new(10, Duration::from_micros(100));
+        assert_eq!(lease_margin(&clock, duration), Duration::from_micros(120));
+        clock.set_max_error(Duration::from_millis(1));
+        assert_eq!(lease_margin(&clock, duration), Duration::from_micros(1020));
+
+        // the error of the unsynchronized clock is unbounded.
+        clock.set_synchronized(false);
+        assert_eq!(lease_margin(&clock, duration), Duration::MAX);
+        clock.set_synchronized(true);
+        assert!(lease_margin(&clock, duration) < duration);
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use tokio::sync::oneshot;
+use uuid::Uuid;
+
+use crate::msg::WriteCommit;
+use crate::multiraft::ProposeResponse;
+use crate::Error;
+use crate::ProposeError;
+
+type ResponseSender<RES> = oneshot::Sender<Result<(RES, Option<Vec<u8>>), Error>>;
+
+/// The write waiting for the result of a coalesced proposal.
+pub(crate) struct CoalescedWaiter<RES: ProposeResponse> {
+    pub(crate) request_id: Uuid,
+    pub(crate) tx: ResponseSender<RES>,
+    pub(crate) commit_tx: Option<oneshot::Sender<WriteCommit>>,
+}
+
+/// The writes resolved by the result of a coalesced proposal, the first is
+/// the write proposed.
+struct Waiters<RES: ProposeResponse> {
+    waiters: Vec<CoalescedWaiter<RES>>,
+    /// True after the proposal is committed, no more writes join it.
+    closed: bool,
+}
+
+struct CoalescedWrite<RES: ProposeResponse> {
+    index: u64,
+    term: u64,
+    data: Vec<u8>,
+    waiters: Arc<Mutex<Waiters<RES>>>,
+}
+
+/// WriteCoalescer collapses the identical writes to a group by the coalescing
+/// keys provided by the application, see `MultiRaft::write_coalesced`. The
+/// write queued behind an uncommitted proposal of the same key and data joins
+/// the proposal instead of appending a new entry, and it's resolved by the
+/// result of the proposal.
+pub(crate) struct WriteCoalescer<RES: ProposeResponse> {
+    group_id: u64,
+    writes: HashMap<String, CoalescedWrite<RES>>,
+}
+
+impl<RES: ProposeResponse> WriteCoalescer<RES> {
+    pub(crate) fn new(group_id: u64) -> Self {
+        Self {
+            group_id,
+            writes: HashMap::new(),
+        }
+    }
+
+    /// Join the write to the uncommitted proposal of the key proposed in the
+    /// `term` with the identical data. The waiter is returned back if there
+    /// is no such proposal, the write should be proposed then.
+    pub(crate) fn join(
+        &mut self,
+        key: &str,
+        data: &[u8],
+        term: u64,
+        committed: u64,
+        waiter: CoalescedWaiter<RES>,
+    ) -> Result<(), CoalescedWaiter<RES>> {
+        let write = match self.writes.get(key) {
+            Some(write) if write.term == term && write.index > committed && write.data == data => {
+                write
+            }
+            _ => return Err(waiter),
+        };
+
+        let mut waiters = write.waiters.lock().unwrap();
+        if waiters.closed {
+            return Err(waiter);
+        }
+        waiters.waiters.push(waiter);
+        Ok(())
+    }
+
+    /// Track the proposal of the key at `index`, the waiter is the write
+    /// proposed. Returns the senders of the proposal which fan out the
+    /// results to the writes joined.
+    pub(crate) fn track(
+        &mut self,
+        key: String,
+        data: Vec<u8>,
+        index: u64,
+        term: u64,
+        waiter: CoalescedWaiter<RES>,
+    ) -> (ResponseSender<RES>, oneshot::Sender<WriteCommit>) {
+        let waiters = Arc::new(Mutex::new(Waiters {
+            waiters: vec![waiter],
+            closed: false,
+        }));
+        let (tx, rx) = oneshot::channel();
+        let (commit_tx, commit_rx) = oneshot::channel();
+        tokio::spawn(fan_out(
+            self.group_id,
+            index,
+            waiters.clone(),
+            rx,
+            commit_rx,
+        ));
+
+        self.writes.insert(
+            key,
+            CoalescedWrite {
+                index,
+                term,
+                data,
+                waiters,
+            },
+        );
+        (tx, commit_tx)
+    }
+
+    /// Forget the proposals committed, the writes can't join them.
+    pub(crate) fn compact(&mut self, committed: u64) {
+        if !self.writes.is_empty() {
+            self.writes.retain(|_, write| write.index > committed);
+        }
+    }
+}
+
+/// Resolve the writes joined the proposal by its commit and result. The
+/// proposed write receives the original error, the others receive the clone
+/// of the `ProposeError` or `ProposeError::CoalescedWriteFailed`.
+async fn fan_out<RES: ProposeResponse>(
+    group_id: u64,
+    index: u64,
+    waiters: Arc<Mutex<Waiters<RES>>>,
+    rx: oneshot::Receiver<Result<(RES, Option<Vec<u8>>), Error>>,
+    commit_rx: oneshot::Receiver<WriteCommit>,
+) {
+    // the commit is sent before the result, and dropped if the proposal failed.
+    if let Ok(commit) = commit_rx.await {
+        let mut waiters = waiters.lock().unwrap();
+        waiters.closed = true;
+        for waiter in waiters.waiters.iter_mut() {
+            if let Some(tx) = waiter.commit_tx.take() {
+                let _ = tx.send(commit);
+            }
+        }
+    }
+
+    let res = rx.await;
+    let waiters = {
+        let mut waiters = waiters.lock().unwrap();
+        waiters.closed = true;
+        std::mem::take(&mut waiters.waiters)
+    };
+    match res {
+        Ok(Ok((response, context))) => {
+            for waiter in waiters {
+                let _ = waiter.tx.send(Ok((response.clone(), context.clone())));
+            }
+        }
+        Ok(Err(err)) => {
+            let mut waiters = waiters.into_iter();
+            let proposed = waiters.next();
+            for waiter in waiters {
+                let shared = match err.without_request_id() {
+                    Error::Propose(err) => Error::Propose(err.clone()),
+                    err => Error::Propose(ProposeError::CoalescedWriteFailed {
+                        group_id,
+                        index,
+                        reason: err.to_string(),
+                    }),
+                };
+                let _ = waiter
+                    .tx
+                    .send(Err(shared.with_request_id(waiter.request_id)));
+            }
+            if let Some(waiter) = proposed {
+                let _ = waiter.tx.send(Err(err));
+            }
+        }
+        // the senders of the writes are dropped, same as the proposal.
+        Err(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::oneshot;
+    use tokio::time::Instant;
+
+    use super::CoalescedWaiter;
+    use super::WriteCoalescer;
+    use crate::id::IdGenerator;
+    use crate::id::RandomIdGenerator;
+    use crate::msg::WriteCommit;
+    use crate::Error;
+    use crate::ProposeError;
+
+    fn new_waiter(
+        commit: bool,
+    ) -> (
+        CoalescedWaiter<u64>,
+        oneshot::Receiver<Result<(u64, Option<Vec<u8>>), Error>>,
+        Option<oneshot::Receiver<WriteCommit>>,
+    ) {
+        let (tx, rx) = oneshot::channel();
+        let (commit_tx, commit_rx) = if commit {
+            let (commit_tx, commit_rx) = oneshot::channel();
+            (Some(commit_tx), Some(commit_rx))
+        } else {
+            (None, None)
+        };
+        let waiter = CoalescedWaiter {
+            request_id: RandomIdGenerator.next_uuid(),
+            tx,
+            commit_tx,
+        };
+        (waiter, rx, commit_rx)
+    }
+
+    #[tokio::test]
+    async fn test_write_coalescer() {
+        let mut coalescer = WriteCoalescer::<u64>::new(1);
+        let (waiter, rx1, commit_rx1) = new_waiter(true);
+        let (tx, commit_tx) = coalescer.track("k".to_owned(), b"v".to_vec(), 5, 2, waiter);
+
+        // the identical write joins, the others are proposed.
+        let (waiter, rx2, commit_rx2) = new_waiter(true);
+        assert!(coalescer.join("k", b"v", 2, 4, waiter).is_ok());
+        assert!(coalescer
+            .join("k", b"v2", 2, 4, new_waiter(false).0)
+            .is_err());
+        assert!(coalescer
+            .join("k", b"v", 3, 4, new_waiter(false).0)
+            .is_err());
+        assert!(coalescer
+            .join("k", b"v", 2, 5, new_waiter(false).0)
+            .is_err());
+
+        commit_tx
+            .send(WriteCommit {
+                index: 5,
+                term: 2,
+                committed_at: Instant::now(),
+            })
+            .unwrap();
+        tx.send(Ok((7, None))).unwrap();
+        for (rx, commit_rx) in [(rx1, commit_rx1), (rx2, commit_rx2)] {
+            assert_eq!(commit_rx.unwrap().await.unwrap().index, 5);
+            assert_eq!(rx.await.unwrap().unwrap().0, 7);
+        }
+
+        coalescer.compact(5);
+        assert!(coalescer.writes.is_empty());
+
+        // the error is shared with the writes joined.
+        let (waiter, rx1, _) = new_waiter(false);
+        let (tx, _) = coalescer.track("k".to_owned(), b"v".to_vec(), 6, 2, waiter);
+        let (waiter, rx2, _) = new_waiter(false);
+        assert!(coalescer.join("k", b"v", 2, 5, waiter).is_ok());
+        tx.send(Err(Error::Propose(ProposeError::Stale(2, 3))))
+            .unwrap();
+        for rx in [rx1, rx2] {
+            let err = rx.await.unwrap().unwrap_err();
+            assert!(matches!(
+                err.without_request_id(),
+                Error::Propose(ProposeError::Stale(2, 3))
+            ));
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::fmt::Debug;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+/// The raft log of a group consulted by `CompactionPolicy`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogState {
+    pub group_id: u64,
+    pub replica_id: u64,
+    pub first_index: u64,
+    pub last_index: u64,
+    pub applied_index: u64,
+    /// The approximate bytes of the entries in the log, it's counted since
+    /// the replica was created on the node.
+    pub bytes: u64,
+    /// The time elapsed since the log was last compacted or the replica was
+    /// created on the node.
+    pub since_compacted: Duration,
+}
+
+impl LogState {
+    #[inline]
+    pub fn entries(&self) -> u64 {
+        (self.last_index + 1).saturating_sub(self.first_index)
+    }
+
+    /// The index to compact the log before, keeping the last `keep_entries`
+    /// entries and the entries not applied. Returns `None` if nothing to
+    /// compact.
+    pub fn compact_index(&self, keep_entries: u64) -> Option<u64> {
+        let compact_index = self
+            .applied_index
+            .min((self.last_index + 1).saturating_sub(keep_entries));
+        (compact_index > self.first_index).then_some(compact_index)
+    }
+}
+
+/// CompactionPolicy decides when the node compacts the raft log of a group
+/// by `StorageExt::compact`, see `Config::compaction_policy`. The followers
+/// lagging behind the compacted log catch up by the snapshot of the leader.
+pub trait CompactionPolicy: Debug + Send + Sync + 'static {
+    /// Returns the index to compact the log of the group before, or `None`
+    /// if the log should not be compacted. The index is at most the applied
+    /// index.
+    fn compact_index(&self, log: &LogState) -> Option<u64>;
+}
+
+/// Never compacts the log, it's the default policy.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoCompaction;
+
+impl CompactionPolicy for NoCompaction {
+    fn compact_index(&self, _: &LogState) -> Option<u64> {
+        None
+    }
+}
+
+/// Compacts the log with more than `max_entries` entries to the last
+/// `keep_entries` entries.
+#[derive(Debug, Clone, Copy)]
+pub struct CountCompaction {
+    pub max_entries: u64,
+    pub keep_entries: u64,
+}
+
+impl CompactionPolicy for CountCompaction {
+    fn compact_index(&self, log: &LogState) -> Option<u64> {
+        if log.entries() <= self.max_entries {
+            return None;
+        }
+        log.compact_index(self.keep_entries)
+    }
+}
+
+/// Compacts the log with more than `max_bytes` bytes to the last
+/// `keep_entries` entries.
+#[derive(Debug, Clone, Copy)]
+pub struct SizeCompaction {
+    pub max_bytes: u64,
+    pub keep_entries: u64,
+}
+
+impl CompactionPolicy for SizeCompaction {
+    fn compact_index(&self, log: &LogState) -> Option<u64> {
+        if log.bytes <= self.max_bytes {
+            return None;
+        }
+        log.compact_index(self.keep_entries)
+    }
+}
+
+/// Compacts the log to the last `keep_entries` entries every `interval`.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeCompaction {
+    pub interval: Duration,
+    pub keep_entries: u64,
+}
+
+impl CompactionPolicy for TimeCompaction {
+    fn compact_index(&self, log: &LogState) -> Option<u64> {
+        if log.since_compacted < self.interval {
+            return None;
+        }
+        log.compact_index(self.keep_entries)
+    }
+}
+
+/// The usage of the raft log of a group tracked by the node.
+#[derive(Debug)]
+pub(crate) struct LogUsage {
+    pub(crate) bytes: u64,
+    pub(crate) compacted_at: Instant,
+}
+
+impl Default for LogUsage {
+    fn default() -> Self {
+        Self {
+            bytes: 0,
+            compacted_at: Instant::now(),
+        }
+    }
+}
+
+impl LogUsage {
+    /// The bytes of the compacted entries are estimated by the average size
+    /// of the entries.
+    pub(crate) fn compacted(&mut self, entries: u64, compacted_entries: u64) {
+        if entries != 0 {
+            self.bytes -= self.bytes * compacted_entries.min(entries) / entries;
+        }
+        self.compacted_at = Instant::now();
+    }
+
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::CompactionPolicy;
+    use super::CountCompaction;
+    use super::LogState;
+    use super::LogUsage;
+    use super::SizeCompaction;
+    use super::TimeCompaction;
+
+    #[test]
+    fn test_compaction_policies() {
+        let log = LogState {
+            group_id: 1,
+            replica_id: 1,
+            first_index: 11,
+            last_index: 110,
+            applied_index: 100,
+            bytes: 1000,
+            since_compacted: Duration::from_secs(10),
+        };
+        assert_eq!(log.entries(), 100);
+
+        let count = |max_entries, keep_entries| CountCompaction {
+            max_entries,
+            keep_entries,
+        };
+        assert_eq!(count(100, 10).compact_index(&log), None);
+        assert_eq!(count(50, 20).compact_index(&log), Some(91));
+        // the entries not applied are kept.
+        assert_eq!(count(50, 0).compact_index(&log), Some(100));
+        assert_eq!(count(50, 100).compact_index(&log), None);
+
+        let size = |max_bytes| SizeCompaction {
+            max_bytes,
+            keep_entries: 20,
+        };
+        assert_eq!(size(1000).compact_index(&log), None);
+        assert_eq!(size(999).compact_index(&log), Some(91));
+
+        let time = |secs| TimeCompaction {
+            interval: Duration::from_secs(secs),
+            keep_entries: 20,
+        };
+        assert_eq!(time(11).compact_index(&log), None);
+        assert_eq!(time(10).compact_index(&log), Some(91));
+    }
+
+    #[test]
+    fn test_log_usage_compacted() {
+        let mut usage = LogUsage {
+            bytes: 1000,
+            ..Default::default()
+        };
+        usage.compacted(100, 80);
+        assert_eq!(usage.bytes, 200);
+        usage.compacted(20, 40);
+        assert_eq!(usage.bytes, 0);
+    }
+}
@@ -0,0 +1,167 @@
+use std::borrow::Cow;
+
+use tracing::warn;
+
+use crate::error::DeserializationError;
+use crate::Error;
+
+/// The prefix of the envelope of the entry data. The data of the proposals
+/// starting with it is always enveloped, so the data with the prefix is never
+/// mistaken for the data of the proposal.
+const COMPRESSED_MAGIC: &[u8; 4] = b"\xffOCZ";
+
+/// The length of the header of the envelope, i.e. the magic and the codec.
+const COMPRESSED_HEADER_LEN: usize = COMPRESSED_MAGIC.len() + 1;
+
+/// The data of the proposal colliding with the magic, it's not compressed.
+const CODEC_NONE: u8 = 0;
+const CODEC_LZ4: u8 = 1;
+const CODEC_ZSTD: u8 = 2;
+
+/// The codec compressing the data of the entries before they are appended,
+/// see `Config::entry_compression`. The compressed data is self-described,
+/// so the replicas decompress the entries regardless of their config. It also
+/// compresses the snapshots of `StateMachineStore` on disk.
+///
+/// The codecs are built with the `compression` feature, the data is left
+/// uncompressed without it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EntryCompression {
+    #[default]
+    None,
+    Lz4,
+    /// Zstd with the compression level.
+    Zstd(i32),
+}
+
+impl EntryCompression {
+    /// Compress the data if it's not smaller than `threshold` bytes, the data
+    /// is returned as it is if the compressed one is not smaller. The data
+    /// starting with the magic of the envelope is enveloped regardless.
+    #[cfg_attr(not(feature = "compression"), allow(unreachable_code))]
+    pub(crate) fn compress(&self, data: Vec<u8>, threshold: usize) -> Vec<u8> {
+        if data.len() < threshold {
+            return escape(data);
+        }
+
+        let (codec, compressed): (u8, Vec<u8>) = match self {
+            EntryCompression::None => return escape(data),
+            #[cfg(feature = "compression")]
+            EntryCompression::Lz4 => (CODEC_LZ4, lz4_flex::compress_prepend_size(&data)),
+            #[cfg(feature = "compression")]
+            EntryCompression::Zstd(level) => match zstd::bulk::compress(&data, *level) {
+                Ok(compressed) => (CODEC_ZSTD, compressed),
+                Err(err) => {
+                    warn!("compress entry data error: {}, append uncompressed", err);
+                    return escape(data);
+                }
+            },
+            #[cfg(not(feature = "compression"))]
+            EntryCompression::Lz4 | EntryCompression::Zstd(_) => {
+                warn!("compress entry data without the compression feature, append uncompressed");
+                return escape(data);
+            }
+        };
+        if COMPRESSED_HEADER_LEN + compressed.len() >= data.len() {
+            return escape(data);
+        }
+        envelope(codec, &compressed)
+    }
+}
+
+fn envelope(codec: u8, data: &[u8]) -> Vec<u8> {
+    let mut envelope = Vec::with_capacity(COMPRESSED_HEADER_LEN + data.len());
+    envelope.extend_from_slice(COMPRESSED_MAGIC);
+    envelope.push(codec);
+    envelope.extend_from_slice(data);
+    envelope
+}
+
+/// Envelope the uncompressed data if it starts with the magic.
+fn escape(data: Vec<u8>) -> Vec<u8> {
+    if data.starts_with(COMPRESSED_MAGIC) {
+        envelope(CODEC_NONE, &data)
+    } else {
+        data
+    }
+}
+
+/// Decompress the data of the entry compressed by `Config::entry_compression`,
+/// e.g. the entries of `AppliedBatch`. The uncompressed data is borrowed as it
+/// is. Returns error if the envelope is corrupt or the codec is not built.
+pub fn decompress_entry_data(data: &[u8]) -> Result<Cow<'_, [u8]>, Error> {
+    if !data.starts_with(COMPRESSED_MAGIC) {
+        return Ok(Cow::Borrowed(data));
+    }
+
+    let decompressed = match data.get(COMPRESSED_MAGIC.len()) {
+        Some(&CODEC_NONE) => return Ok(Cow::Borrowed(&data[COMPRESSED_HEADER_LEN..])),
+        Some(&codec) if codec == CODEC_LZ4 || codec == CODEC_ZSTD => {
+            decompress(codec, &data[COMPRESSED_HEADER_LEN..])
+        }
+        codec => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("unknown codec {:?} of the entry data", codec),
+        )),
+    };
+    decompressed
+        .map(Cow::Owned)
+        .map_err(|err| Error::Deserialization(DeserializationError::Decompress(err)))
+}
+
+#[cfg(feature = "compression")]
+fn decompress(codec: u8, compressed: &[u8]) -> std::io::Result<Vec<u8>> {
+    if codec == CODEC_LZ4 {
+        lz4_flex::decompress_size_prepended(compressed)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err.to_string()))
+    } else {
+        zstd::stream::decode_all(compressed)
+    }
+}
+
+/// The entries compressed by the replicas with the `compression` feature
+/// can't be read.
+#[cfg(not(feature = "compression"))]
+fn decompress(codec: u8, _: &[u8]) -> std::io::Result<Vec<u8>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("codec {} requires the compression feature", codec),
+    ))
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod test {
+    use super::decompress_entry_data;
+    use super::EntryCompression;
+
+    #[test]
+    fn test_entry_compression() {
+        let data = b"oceanraft".repeat(64);
+        for compression in [EntryCompression::Lz4, EntryCompression::Zstd(3)] {
+            let compressed = compression.compress(data.clone(), 128);
+            assert!(compressed.len() < data.len());
+            assert_eq!(decompress_entry_data(&compressed).unwrap(), &data[..]);
+        }
+
+        // the data smaller than the threshold or incompressible is kept.
+        let compression = EntryCompression::Lz4;
+        assert_eq!(compression.compress(data.clone(), data.len() + 1), data);
+        assert_eq!(compression.compress(b"abc".to_vec(), 0), b"abc".to_vec());
+        assert_eq!(EntryCompression::None.compress(data.clone(), 0), data);
+
+        // the uncompressed data is left as it is.
+        assert_eq!(decompress_entry_data(b"abc").unwrap(), &b"abc"[..]);
+        assert_eq!(decompress_entry_data(b"").unwrap(), &b""[..]);
+
+        // the data colliding with the magic is enveloped.
+        let data = b"\xffOCZ\x01abc".to_vec();
+        for compression in [EntryCompression::None, EntryCompression::Lz4] {
+            let enveloped = compression.compress(data.clone(), 0);
+            assert_ne!(enveloped, data);
+            assert_eq!(decompress_entry_data(&enveloped).unwrap(), &data[..]);
+        }
+        assert!(decompress_entry_data(&data).is_err());
+        assert!(decompress_entry_data(b"\xffOCZ").is_err());
+        assert!(decompress_entry_data(b"\xffOCZ\x09abc").is_err());
+    }
+}
@@ -1,3 +1,34 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::runtime::Handle;
+
+use crate::auth::AllowAll;
+use crate::auth::Authorizer;
+use crate::background::BackgroundBudget;
+use crate::clock::MonotonicClock;
+use crate::clock::TimeSource;
+use crate::compaction::CompactionPolicy;
+use crate::compaction::NoCompaction;
+use crate::compression::EntryCompression;
+use crate::factory::GroupFactory;
+use crate::factory::NoGroupFactory;
+use crate::gate::AcceptAllGate;
+use crate::gate::EntryGate;
+use crate::id::IdGenerator;
+use crate::id::RandomIdGenerator;
+use crate::overload::OverloadShedding;
+use crate::placement::BalancedPlacement;
+use crate::placement::PlacementDriver;
+use crate::promotion::LearnerPromoter;
+use crate::promotion::NoLearnerPromotion;
+use crate::quota::LogCompactor;
+use crate::quota::NoLogCompactor;
+use crate::snapshot::NoSnapshotValidator;
+use crate::snapshot::SnapshotValidator;
+use crate::topology::NoTopologyProvider;
+use crate::topology::TopologyProvider;
 use crate::Error;
 
 /// A constant represents invalid node id of oceanraft node.
@@ -52,6 +83,317 @@ pub struct Config {
     /// > The request queue is shared among all groups on the node, which means
     /// that the value is set based on the number of consensus groups on the node.
     pub proposal_queue_size: usize,
+
+    /// The max number of the uncommitted proposals of a group, the writes
+    /// beyond it fail with `ProposeError::TooManyInflightProposals` unless
+    /// `await_inflight_proposals` is set. `0` is unlimited. default is `0`.
+    pub max_inflight_proposals: usize,
+
+    /// Hold the writes beyond `max_inflight_proposals` on the node until the
+    /// proposals of the group are committed instead of failing them, at most
+    /// `max_inflight_proposals` writes are held per group. default is `false`.
+    pub await_inflight_proposals: bool,
+
+    /// Emit `Event::ApplyBacklogHigh` when the number of committed but not
+    /// yet applied entries of a group exceeds this value, `0` means disabled.
+    /// default is `0`.
+    pub apply_backlog_threshold: u64,
+
+    /// The bytes of the committed entries of a group sent to the state
+    /// machine but not applied yet. The committed entries of the group are
+    /// held on the node once it's reached and sent again after the unapplied
+    /// bytes drop to half of it, `0` means unlimited. default is `0`.
+    pub max_apply_unapplied_size: usize,
+
+    /// The memory budget in bytes of the proposal queues and entry caches
+    /// of all groups on the node, which is allotted to groups by priority and
+    /// recent activity. The entry caches are the committed entries held on
+    /// the node until they are applied. `0` means unlimited, default is `0`.
+    pub memory_budget: usize,
+
+    /// A group is considered idle if no proposals in the number of ticks, the
+    /// allotment of idle groups is reclaimed when the memory budget is under
+    /// pressure. The allotments are rebalanced every the number of ticks, or
+    /// on the next tick once a proposal exhausted the budget. default is `100`.
+    pub memory_budget_idle_ticks: usize,
+
+    /// The max number of groups that are materialized on the node, when exceeded,
+    /// the least recently active groups which idle over `group_park_idle_ticks`
+    /// are parked until the next message or proposal. The leaders are never
+    /// parked. `0` means unlimited, default is `0`.
+    pub max_active_groups: usize,
+
+    /// A group is considered idle if no proposals and raft messages in the number
+    /// of ticks. default is `600`.
+    pub group_park_idle_ticks: usize,
+
+    /// Buffer the raft messages of the groups which do not exist on the node
+    /// until the group is created or the messages expire, so a message from a
+    /// stale or removed replica does not create the group. If disabled, the
+    /// group is created by the first message. default is `true`.
+    pub buffer_unknown_group_msgs: bool,
+
+    /// The max number of messages buffered for the groups which do not exist
+    /// on the node, further messages are dropped. default is `1024`.
+    pub unknown_group_msg_capacity: usize,
+
+    /// The buffered messages of unknown groups are dropped if the group is not
+    /// created in the number of ticks. default is `50`.
+    pub unknown_group_msg_ttl_ticks: usize,
+
+    /// The raft messages whose term is lower than the current term of the
+    /// group by more than the gap are dropped before stepped into raft, which
+    /// saves the CPU on the messages delayed by a flapping network. `0`
+    /// disables the check. default is `0`.
+    pub stale_msg_term_gap: u64,
+
+    /// The number of recent appends and snapshots remembered per group of each
+    /// peer node to drop the duplicates delivered again by the retries of the
+    /// transport, the window is cleared on every tick. `0` disables the
+    /// deduplication.
+    /// default is `0`.
+    pub msg_dedup_window: usize,
+
+    /// Record the latency of the writes proposed on the node through the
+    /// stages, enqueued, proposed, persisted, committed and applied, which is
+    /// reported by `MultiRaft::latency_report`. The reads served on the node
+    /// are recorded through queue, quorum confirmation and apply wait, see
+    /// `ReadLatency`. default is `false`.
+    pub record_latency: bool,
+
+    /// The outcomes of the successful `MultiRaft::create_group` and
+    /// `MultiRaft::remove_group` with idempotency keys are kept in the number
+    /// of ticks, the retries with the same key get the original outcome. The
+    /// outcomes are persisted by the storage and kept over the restarts of
+    /// the node until the window elapses by the wall clock. `0` disables the
+    /// idempotency keys. default is `6000`.
+    pub manage_idempotency_window_ticks: usize,
+
+    /// The max bytes of the raft log of a group. The group over the quota
+    /// escalates by `log_compactor` building the snapshot, then compacting the
+    /// log by the storage, then rejecting the writes until the log is under
+    /// the quota, see `LogQuotaStage`. `0` disables the quota. default is `0`.
+    pub group_log_quota: u64,
+
+    /// The group stays in the escalation stage for the number of ticks before
+    /// escalating to the next stage. default is `100`.
+    pub log_quota_escalation_ticks: usize,
+
+    /// Builds the snapshot of the group which exceeds `group_log_quota`, and
+    /// shrinks the raft log of the groups in the archive mode. default does
+    /// nothing.
+    pub log_compactor: Arc<dyn LogCompactor>,
+
+    /// The number of entries kept in the raft log of the groups in the archive
+    /// mode, see `CreateGroupRequest::archive`. The log is compacted by
+    /// `log_compactor` once it grows beyond, the followers lagging beyond the
+    /// log are served snapshots. default is `64`.
+    pub archive_log_entries: u64,
+
+    /// Decides the auto-promotion policy of the learners of the groups and
+    /// vetoes the promotions, `LearnerPromotion` applies the same policy to
+    /// all groups. default never promotes the learners.
+    pub learner_promoter: Arc<dyn LearnerPromoter>,
+
+    /// The leader reports the replica unreachable to raft at most once in the
+    /// number of ticks when the messages to it fail to deliver, see
+    /// `SendFailureReporter`. `0` reports every failure. default is `2`.
+    pub unreachable_debounce_ticks: usize,
+
+    /// Provides the topologies of the peer nodes for
+    /// `MultiRaft::cluster_topology`, e.g. collected by gossip or a control
+    /// plane. default provides no peer.
+    pub topology_provider: Arc<dyn TopologyProvider>,
+
+    /// Suggests the leader transfers and the replica moves which balance the
+    /// load of the nodes by the cluster topology, see
+    /// `MultiRaft::placement_plan`. default is `BalancedPlacement`.
+    pub placement_driver: Arc<dyn PlacementDriver>,
+
+    /// The source of request ids and read index contexts, tests can inject
+    /// a `SeededIdGenerator` to reproduce runs. default is random.
+    pub id_generator: Arc<dyn IdGenerator>,
+
+    /// Consulted before the normal entries are applied, it can hold, reject
+    /// or transform the entries that the node can't apply during rolling
+    /// upgrades. default accepts all entries.
+    pub entry_gate: Arc<dyn EntryGate>,
+
+    /// Pause the storage domain automatically if the groups in the domain fail
+    /// to access storage in the number of consecutive times, the unavailable
+    /// storage pauses the domain immediately instead of panic. `0` disables
+    /// the automatic pausing. default is `0`.
+    pub storage_domain_failure_threshold: usize,
+
+    /// The leader steps down if it does not hear from a quorum in the election
+    /// timeout, which makes the leader hold the lease to serve the reads of
+    /// `ConsistencyLevel::LeaderLocal` locally. default is `false`.
+    pub check_quorum: bool,
+
+    /// Serve the reads of `ConsistencyLevel::Lease` locally by the leader in
+    /// the leader lease, which is renewed by the read index confirmed by the
+    /// heartbeat quorum. The lease lasts `election_tick - heartbeat_tick`
+    /// ticks from the read index issued, it requires `check_quorum` so the
+    /// followers do not vote for another candidate in the lease. default is
+    /// `false`.
+    pub enable_lease_read: bool,
+
+    /// The clock of the leader lease, the lease is shortened by the margin of
+    /// the drift and the error of the clock, e.g. `DisciplinedClock` for the
+    /// nodes synchronized by PTP or NTP. default is `MonotonicClock` without
+    /// drift.
+    pub time_source: Arc<dyn TimeSource>,
+
+    /// Batch the read index requests of a group which arrive in a tick into
+    /// one read index round issued at the next tick, which saves the
+    /// heartbeats of the rounds at the cost of the latency up to a tick.
+    /// default is `false`.
+    pub batch_read_index: bool,
+
+    /// The max number of the outstanding read index requests of a group, the
+    /// requests beyond it fail with `ProposeError::ReadQueueFull`. `0` is
+    /// unlimited. default is `0`.
+    pub max_read_queue_len: usize,
+
+    /// Create the group by `group_factory` when a write to the group which
+    /// does not exist on the node arrives, the replica campaigns after created
+    /// and the write proceeds if it's elected immediately, e.g. the group has
+    /// a single voter, otherwise the write fails with `NotLeader` and should be
+    /// retried. default is `false`.
+    pub auto_create_groups: bool,
+
+    /// Provides the initial membership of the groups created by writes, see
+    /// `auto_create_groups`. default never creates groups.
+    pub group_factory: Arc<dyn GroupFactory>,
+
+    /// Validates the snapshots received from the leader before installed,
+    /// after the checksum of the snapshot data is verified. default accepts
+    /// all snapshots.
+    pub snapshot_validator: Arc<dyn SnapshotValidator>,
+
+    /// The max number of snapshots sent by the leaders on the node at the
+    /// same time, the snapshot is in flight until the replica installed or
+    /// failed it. The snapshots beyond it are queued and reported by
+    /// `Event::SnapshotQueued`. `0` means unlimited, default is `0`.
+    pub max_concurrent_snapshots: usize,
+
+    /// The bytes of the snapshot data sent by the leaders on the node per
+    /// second, the snapshots are queued once it's spent. `0` means unlimited,
+    /// default is `0`.
+    pub snapshot_max_bytes_per_sec: u64,
+
+    /// The snapshot counted in `max_concurrent_snapshots` is released if it's
+    /// in flight over the number of ticks, e.g. the replica never reported it
+    /// installed or failed. `0` never releases, default is `6000`.
+    pub snapshot_send_timeout_ticks: usize,
+
+    /// Stagger the first campaigns of the groups after the node started, the
+    /// election timeout of followers starts running after a delay spread over
+    /// the number of ticks by group id, which smooths the elections of many
+    /// groups after a mass restart. `0` disables the ramp, default is `0`.
+    pub election_ramp_ticks: usize,
+
+    /// Record the applied index to storage after every number of entries of
+    /// a committed batch are applied, which bounds the entries applied again
+    /// if the node crashes while applying a huge batch. The state machine must
+    /// persist the applied entries before `apply` returns. `0` disables the
+    /// checkpoints, default is `0`.
+    pub apply_checkpoint_entries: usize,
+
+    /// Consulted on creating and removing groups, and on the writes and the
+    /// membership changes if `authorize_proposals` is enabled. default permits
+    /// all operations.
+    pub authorizer: Arc<dyn Authorizer>,
+
+    /// Authorize the writes and the membership changes by `authorizer`, the
+    /// decision is cached per group until `MultiRaft::invalidate_authorization`.
+    /// default is `false`.
+    pub authorize_proposals: bool,
+
+    /// Record the proposer of the writes, i.e. the node, the replica and the
+    /// principal of `authorizer`, in the context envelope of the entries. The
+    /// proposer is surfaced in `ApplyNormal::proposer` and decoded from the
+    /// entries of `AppliedBatch` by `Proposer::decode`. default is `false`.
+    pub annotate_proposer: bool,
+
+    /// Remove the descriptors of the replicas which are no longer in the
+    /// membership of their groups every number of ticks, `0` disables the
+    /// background collection. default is `0`.
+    pub replica_desc_gc_interval_ticks: usize,
+
+    /// The replica descriptor is removed after the replica is absent from the
+    /// membership of its group in the number of ticks, which keeps the replicas
+    /// of the membership changes in progress. default is `600`.
+    pub replica_desc_gc_grace_ticks: usize,
+
+    /// The background collection only reports the replica descriptors which
+    /// would be removed. default is `false`.
+    pub replica_desc_gc_dry_run: bool,
+
+    /// Compress the data of the proposals before they are appended to the
+    /// raft log, which reduces the size of the log and the append messages of
+    /// the large proposals. The entries are decompressed before they are
+    /// applied, the entries of `AppliedBatch` are decompressed by
+    /// `decompress_entry_data`. default is `EntryCompression::None`.
+    pub entry_compression: EntryCompression,
+
+    /// The data of the proposals smaller than the number of bytes is not
+    /// compressed. default is `4096`.
+    pub entry_compression_threshold: usize,
+
+    /// Adapt the limit of the inflight appends to each peer node every number
+    /// of ticks by the round trip time and the failures reported by the
+    /// transport, see `SendFailureReporter::report_delivered`. The limit is
+    /// `max_inflight_msgs` at `adaptive_inflight_base_rtt_ms` and grows in
+    /// proportion to the rtt of the peer, the peer losing messages halves the
+    /// limit. `max_size_per_msg` is shared by the peers of a group in raft,
+    /// so it's not adapted per peer. `0` disables the adaption. default is `0`.
+    pub adaptive_inflight_interval_ticks: usize,
+
+    /// The lower bound of the adaptive inflight limit. default is `16`.
+    pub adaptive_inflight_min_msgs: usize,
+
+    /// The upper bound of the adaptive inflight limit. default is `4096`.
+    pub adaptive_inflight_max_msgs: usize,
+
+    /// The round trip time in milliseconds that `max_inflight_msgs` is sized
+    /// for. default is `1`.
+    pub adaptive_inflight_base_rtt_ms: u64,
+
+    /// Decides when the raft log of the groups are compacted by the storage,
+    /// e.g. `CountCompaction`, `SizeCompaction` and `TimeCompaction`. default
+    /// never compacts the log.
+    pub compaction_policy: Arc<dyn CompactionPolicy>,
+
+    /// Consult `compaction_policy` for the groups every number of ticks, `0`
+    /// disables the compaction. default is `10`.
+    pub compaction_check_ticks: usize,
+
+    /// The budget of the background jobs shared by the log compaction, the
+    /// replica descriptor gc and the jobs of the application, e.g. the
+    /// snapshot builds and the scrubbing. default is unlimited.
+    pub background_budget: BackgroundBudget,
+
+    /// Persist the entries and hard states of the readys of the groups in one
+    /// `MultiRaftStorage::write_batch` instead of a write per group, which
+    /// amortizes the sync of the storage over the groups. The readys with a
+    /// snapshot are still written per group. default is `false`.
+    pub batch_ready_writes: bool,
+
+    /// Shed the reads and then the non-urgent management with
+    /// `Error::Overloaded` when the node is saturated, which keeps the
+    /// capacity of the node for the writes during the spikes. `None` disables
+    /// the shedding. default is `None`.
+    pub overload_shedding: Option<OverloadShedding>,
+
+    /// The runtime which the node actor is spawned on, e.g. a dedicated runtime
+    /// whose threads are pinned to cores, which isolates the consensus from the
+    /// scheduling jitter of the application tasks. The runtime must enable the
+    /// time driver. default is the runtime which creates `MultiRaft`.
+    pub runtime: Option<Handle>,
+
+    /// The runtime which the apply actor is spawned on, default is `runtime`.
+    pub apply_runtime: Option<Handle>,
 }
 
 impl Default for Config {
@@ -70,11 +412,99 @@ impl Default for Config {
             batch_size: 0,
             replica_sync: true,
             proposal_queue_size: 1,
+            max_inflight_proposals: 0,
+            await_inflight_proposals: false,
+            apply_backlog_threshold: 0,
+            max_apply_unapplied_size: 0,
+            memory_budget: 0,
+            memory_budget_idle_ticks: 100,
+            max_active_groups: 0,
+            group_park_idle_ticks: 600,
+            buffer_unknown_group_msgs: true,
+            unknown_group_msg_capacity: 1024,
+            unknown_group_msg_ttl_ticks: 50,
+            stale_msg_term_gap: 0,
+            msg_dedup_window: 0,
+            record_latency: false,
+            manage_idempotency_window_ticks: 6000,
+            group_log_quota: 0,
+            log_quota_escalation_ticks: 100,
+            log_compactor: Arc::new(NoLogCompactor),
+            archive_log_entries: 64,
+            learner_promoter: Arc::new(NoLearnerPromotion),
+            unreachable_debounce_ticks: 2,
+            topology_provider: Arc::new(NoTopologyProvider),
+            placement_driver: Arc::new(BalancedPlacement::default()),
+            id_generator: Arc::new(RandomIdGenerator),
+            entry_gate: Arc::new(AcceptAllGate),
+            storage_domain_failure_threshold: 0,
+            check_quorum: false,
+            enable_lease_read: false,
+            time_source: Arc::new(MonotonicClock::default()),
+            batch_read_index: false,
+            max_read_queue_len: 0,
+            auto_create_groups: false,
+            group_factory: Arc::new(NoGroupFactory),
+            snapshot_validator: Arc::new(NoSnapshotValidator),
+            max_concurrent_snapshots: 0,
+            snapshot_max_bytes_per_sec: 0,
+            snapshot_send_timeout_ticks: 6000,
+            election_ramp_ticks: 0,
+            apply_checkpoint_entries: 0,
+            authorizer: Arc::new(AllowAll),
+            authorize_proposals: false,
+            annotate_proposer: false,
+            replica_desc_gc_interval_ticks: 0,
+            replica_desc_gc_grace_ticks: 600,
+            replica_desc_gc_dry_run: false,
+            entry_compression: EntryCompression::None,
+            entry_compression_threshold: 4096,
+            adaptive_inflight_interval_ticks: 0,
+            adaptive_inflight_min_msgs: 16,
+            adaptive_inflight_max_msgs: 4096,
+            adaptive_inflight_base_rtt_ms: 1,
+            compaction_policy: Arc::new(NoCompaction),
+            compaction_check_ticks: 10,
+            background_budget: BackgroundBudget::default(),
+            batch_ready_writes: false,
+            overload_shedding: None,
+            runtime: None,
+            apply_runtime: None,
         }
     }
 }
 
 impl Config {
+    /// Spawn the task of node actor on `runtime`.
+    pub(crate) fn spawn_node<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        spawn_on(self.runtime.as_ref(), future)
+    }
+
+    /// Spawn the task of apply actor on `apply_runtime`.
+    pub(crate) fn spawn_apply<F>(&self, future: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        spawn_on(
+            self.apply_runtime.as_ref().or(self.runtime.as_ref()),
+            future,
+        )
+    }
+
+    /// The duration of the leader lease, `None` if the lease read is disabled
+    /// or `check_quorum` is disabled. The lease is shorter than the election
+    /// timeout by a heartbeat to tolerate the drift of the ticks.
+    pub(crate) fn lease_duration(&self) -> Option<Duration> {
+        if !self.enable_lease_read || !self.check_quorum {
+            return None;
+        }
+        let ticks = self.election_tick.saturating_sub(self.heartbeat_tick) as u64;
+        Some(Duration::from_millis(ticks * self.tick_interval))
+    }
+
     pub fn validate(&self) -> Result<(), Error> {
         if self.node_id == INVALID_NODE_ID {
             return Err(Error::ConfigInvalid("invalid node id".to_owned()));
@@ -116,6 +546,56 @@ impl Config {
             ));
         }
 
+        if self.memory_budget != 0 && self.memory_budget_idle_ticks == 0 {
+            return Err(Error::ConfigInvalid(
+                "memory budget idle ticks must be greater than 0".to_owned(),
+            ));
+        }
+
+        if self.max_active_groups != 0 && self.group_park_idle_ticks == 0 {
+            return Err(Error::ConfigInvalid(
+                "group park idle ticks must be greater than 0".to_owned(),
+            ));
+        }
+
+        if self.buffer_unknown_group_msgs
+            && self.unknown_group_msg_capacity != 0
+            && self.unknown_group_msg_ttl_ticks == 0
+        {
+            return Err(Error::ConfigInvalid(
+                "unknown group message ttl ticks must be greater than 0".to_owned(),
+            ));
+        }
+
+        if self.adaptive_inflight_interval_ticks != 0
+            && (self.adaptive_inflight_min_msgs == 0
+                || self.adaptive_inflight_min_msgs > self.adaptive_inflight_max_msgs)
+        {
+            return Err(Error::ConfigInvalid(
+                "adaptive inflight bounds must be 0 < min <= max".to_owned(),
+            ));
+        }
+
+        if self.group_log_quota != 0 && self.log_quota_escalation_ticks == 0 {
+            return Err(Error::ConfigInvalid(
+                "log quota escalation ticks must be greater than 0".to_owned(),
+            ));
+        }
+
         Ok(())
     }
 }
+
+fn spawn_on<F>(runtime: Option<&Handle>, future: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    match runtime {
+        Some(runtime) => {
+            runtime.spawn(future);
+        }
+        None => {
+            tokio::spawn(future);
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::RwLock;
+use std::time::Duration;
+
+use futures::Future;
+use raft::GetEntriesContext;
+use tokio::sync::mpsc::unbounded_channel;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::mpsc::UnboundedSender;
+use tracing::warn;
+
+use crate::prelude::Entry;
+use crate::storage::MultiRaftStorage;
+use crate::storage::RaftStorage;
+use crate::Error;
+
+/// The interval to deliver the batch again after the consumer failed.
+const CONSUME_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The entries of the group applied by the state machine in the index range
+/// `[first_index, last_index]`, see `AppliedConsumer`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppliedBatch {
+    pub group_id: u64,
+    pub replica_id: u64,
+    pub first_index: u64,
+    pub last_index: u64,
+    /// The entries in the range, the data is transformed by the `EntryGate`
+    /// and the entries rejected by the gate are excluded. The entries caught
+    /// up from the raft log are delivered as they are in the log.
+    pub entries: Vec<Entry>,
+}
+
+/// AppliedConsumer is the secondary consumer of the applied entries, e.g. a
+/// builder of secondary indexes or a cache warmer. The batches are delivered
+/// asynchronously after they are applied by the state machine, so the slow
+/// consumer never blocks the apply.
+///
+/// The batches are delivered at least once in the order of index for each
+/// group. The consumer persists the last index consumed as its cursor, the
+/// entries after the cursor are delivered again after restart and the missing
+/// entries are caught up from the raft log if they are not compacted.
+pub trait AppliedConsumer: Send + Sync + 'static {
+    /// GAT trait for `consume`.
+    type ConsumeFuture<'life0>: Send + Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+    /// Consume the batch, the entries before the cursor are stripped. The
+    /// consumer should persist `batch.last_index` as the cursor of the group
+    /// before returning `Ok`, the batch is delivered again if it fails.
+    fn consume<'life0>(&'life0 self, batch: &'life0 AppliedBatch) -> Self::ConsumeFuture<'life0>;
+
+    /// The persisted cursor of the group, i.e. the last index consumed. It's
+    /// called once for each group since the consumer is added, `0` if the
+    /// group is never consumed.
+    fn cursor(&self, group_id: u64) -> u64;
+}
+
+/// The consumers of the applied entries on the node, the apply worker
+/// publishes the applied batches to the consumers.
+#[derive(Clone, Default)]
+pub(crate) struct AppliedConsumers {
+    senders: Arc<RwLock<Vec<UnboundedSender<AppliedBatch>>>>,
+}
+
+impl AppliedConsumers {
+    pub(crate) fn register(&self) -> UnboundedReceiver<AppliedBatch> {
+        let (tx, rx) = unbounded_channel();
+        self.senders.write().unwrap().push(tx);
+        rx
+    }
+
+    /// True if any consumer is registered, the applied entries are collected
+    /// only if it's true.
+    #[inline]
+    pub(crate) fn is_active(&self) -> bool {
+        !self.senders.read().unwrap().is_empty()
+    }
+
+    /// Publish the batch to the consumers, the stopped consumers are removed.
+    pub(crate) fn publish(&self, batch: AppliedBatch) {
+        let mut senders = self.senders.write().unwrap();
+        senders.retain(|tx| !tx.is_closed());
+        for tx in senders.iter() {
+            let _ = tx.send(batch.clone());
+        }
+    }
+}
+
+/// Deliver the applied batches to the consumer on a task spawned by
+/// `MultiRaft::add_applied_consumer`.
+pub(crate) struct AppliedConsumerRunner<C, RS, MRS>
+where
+    C: AppliedConsumer,
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+{
+    consumer: C,
+    storage: MRS,
+    rx: UnboundedReceiver<AppliedBatch>,
+    /// The cursors of the groups delivered by the runner.
+    cursors: HashMap<u64, u64>,
+    stopped: Arc<AtomicBool>,
+    _m: PhantomData<RS>,
+}
+
+impl<C, RS, MRS> AppliedConsumerRunner<C, RS, MRS>
+where
+    C: AppliedConsumer,
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+{
+    pub(crate) fn new(
+        consumer: C,
+        storage: MRS,
+        rx: UnboundedReceiver<AppliedBatch>,
+        stopped: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            consumer,
+            storage,
+            rx,
+            cursors: HashMap::new(),
+            stopped,
+            _m: PhantomData,
+        }
+    }
+
+    pub(crate) async fn run(mut self) {
+        while let Some(batch) = self.rx.recv().await {
+            if self.stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            self.deliver(batch).await;
+        }
+    }
+
+    async fn deliver(&mut self, mut batch: AppliedBatch) {
+        let group_id = batch.group_id;
+        let consumer = &self.consumer;
+        let cursor = *self
+            .cursors
+            .entry(group_id)
+            .or_insert_with(|| consumer.cursor(group_id));
+        if batch.last_index <= cursor {
+            return;
+        }
+
+        batch.entries.retain(|ent| ent.index > cursor);
+        if batch.first_index > cursor + 1 {
+            match self
+                .catch_up(group_id, batch.replica_id, cursor + 1, batch.first_index)
+                .await
+            {
+                Ok(mut entries) => {
+                    entries.append(&mut batch.entries);
+                    batch.entries = entries;
+                    batch.first_index = cursor + 1;
+                }
+                Err(err) => warn!(
+                    "group {}: catch up applied entries [{}, {}) error, skipped: {}",
+                    group_id,
+                    cursor + 1,
+                    batch.first_index,
+                    err
+                ),
+            }
+        } else {
+            batch.first_index = cursor + 1;
+        }
+
+        while let Err(err) = self.consumer.consume(&batch).await {
+            warn!(
+                "group {}: consume applied entries [{}, {}] error, retry: {}",
+                group_id, batch.first_index, batch.last_index, err
+            );
+            if self.stopped.load(Ordering::SeqCst) {
+                return;
+            }
+            tokio::time::sleep(CONSUME_RETRY_INTERVAL).await;
+        }
+        self.cursors.insert(group_id, batch.last_index);
+    }
+
+    /// Read the entries in `[low, high)` from the raft log of the group.
+    async fn catch_up(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+        low: u64,
+        high: u64,
+    ) -> Result<Vec<Entry>, Error> {
+        let gs = self.storage.group_storage(group_id, replica_id).await?;
+        let entries = gs
+            .entries(low, high, None, GetEntriesContext::empty(false))
+            .map_err(crate::storage::Error::from)?;
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicBool;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use futures::Future;
+
+    use crate::prelude::Entry;
+    use crate::storage::MultiRaftMemoryStorage;
+    use crate::Error;
+
+    use super::AppliedBatch;
+    use super::AppliedConsumer;
+    use super::AppliedConsumerRunner;
+    use super::AppliedConsumers;
+
+    /// Records the consumed indexes, the first consume fails.
+    #[derive(Clone, Default)]
+    struct RecordConsumer {
+        consumed: Arc<Mutex<Vec<u64>>>,
+        consumes: Arc<AtomicUsize>,
+    }
+
+    impl AppliedConsumer for RecordConsumer {
+        type ConsumeFuture<'life0> = impl Future<Output = Result<(), Error>> + Send + 'life0
+        where
+            Self: 'life0;
+        fn consume<'life0>(
+            &'life0 self,
+            batch: &'life0 AppliedBatch,
+        ) -> Self::ConsumeFuture<'life0> {
+            async move {
+                if self.consumes.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return Err(Error::BadParameter("unavailable".to_owned()));
+                }
+                let mut consumed = self.consumed.lock().unwrap();
+                consumed.extend(batch.entries.iter().map(|ent| ent.index));
+                Ok(())
+            }
+        }
+
+        fn cursor(&self, _: u64) -> u64 {
+            2
+        }
+    }
+
+    fn new_batch(first_index: u64, last_index: u64) -> AppliedBatch {
+        AppliedBatch {
+            group_id: 1,
+            replica_id: 1,
+            first_index,
+            last_index,
+            entries: (first_index..=last_index)
+                .map(|index| Entry {
+                    index,
+                    term: 1,
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_applied_consumer_runner() {
+        let consumers = AppliedConsumers::default();
+        assert!(!consumers.is_active());
+        let rx = consumers.register();
+        assert!(consumers.is_active());
+
+        let consumer = RecordConsumer::default();
+        let runner = AppliedConsumerRunner::new(
+            consumer.clone(),
+            MultiRaftMemoryStorage::new(1),
+            rx,
+            Arc::new(AtomicBool::new(false)),
+        );
+
+        // the entries before the cursor are stripped.
+        consumers.publish(new_batch(1, 4));
+        consumers.publish(new_batch(3, 4));
+        consumers.publish(new_batch(5, 6));
+        drop(consumers);
+        runner.run().await;
+
+        assert_eq!(*consumer.consumed.lock().unwrap(), vec![3, 4, 5, 6]);
+        // the first batch is delivered again after the failure.
+        assert_eq!(consumer.consumes.load(Ordering::SeqCst), 3);
+    }
+}
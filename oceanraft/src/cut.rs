@@ -0,0 +1,89 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+/// The commit watermark of a group in the `ConsistentCut`, the entries of
+/// the group at or below `index` are committed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommitWatermark {
+    pub group_id: u64,
+    pub replica_id: u64,
+    pub index: u64,
+    /// The term of the entry at `index`, `0` if it's compacted.
+    pub term: u64,
+    /// True if the replica is the leader, the watermark of a follower may
+    /// lag behind the leader.
+    pub leader: bool,
+}
+
+/// The manifest of a consistent cut across the groups on the node, returned
+/// by `MultiRaft::consistent_cut`. The watermarks of the groups are collected
+/// at the same time by the node, so the cut is a consistent boundary for the
+/// tooling across groups, e.g. backups and changefeeds.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ConsistentCut {
+    pub node_id: u64,
+    /// The unix time in milliseconds when the cut is taken.
+    pub taken_at_ms: u64,
+    pub watermarks: Vec<CommitWatermark>,
+    /// The groups requested which do not exist on the node.
+    pub missing: Vec<u64>,
+}
+
+impl ConsistentCut {
+    pub fn watermark(&self, group_id: u64) -> Option<&CommitWatermark> {
+        self.watermarks
+            .iter()
+            .find(|watermark| watermark.group_id == group_id)
+    }
+
+    /// Returns true if the entry of the group at `index` is below the cut.
+    pub fn covers(&self, group_id: u64, index: u64) -> bool {
+        self.watermark(group_id)
+            .map_or(false, |watermark| index <= watermark.index)
+    }
+}
+
+/// The token of `MultiRaft::read_barrier`, the state machines of the groups
+/// on the node applied at least to the indexes when the barrier returns.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReadBarrier {
+    pub node_id: u64,
+    /// The (group id, applied index) of the groups in the order of group id.
+    pub indexes: Vec<(u64, u64)>,
+}
+
+impl ReadBarrier {
+    /// The applied index of the group at the barrier.
+    pub fn index(&self, group_id: u64) -> Option<u64> {
+        self.indexes
+            .iter()
+            .find(|(id, _)| *id == group_id)
+            .map(|(_, index)| *index)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::CommitWatermark;
+    use super::ConsistentCut;
+
+    #[test]
+    fn test_consistent_cut_covers() {
+        let cut = ConsistentCut {
+            node_id: 1,
+            watermarks: vec![CommitWatermark {
+                group_id: 1,
+                replica_id: 1,
+                index: 10,
+                term: 2,
+                leader: true,
+            }],
+            missing: vec![2],
+            ..Default::default()
+        };
+        assert!(cut.covers(1, 10));
+        assert!(!cut.covers(1, 11));
+        assert!(!cut.covers(2, 1));
+        assert_eq!(cut.watermark(1).unwrap().term, 2);
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::hash::Hash;
+use std::hash::Hasher;
+
+use crate::prelude::MessageType;
+use crate::prelude::MultiRaftMessage;
+
+/// MessageDedup drops the duplicate raft messages delivered again by the
+/// retries of the transport, e.g. the `MsgAppend` and `MsgSnapshot` resent
+/// after the response was lost. The fingerprints of the recent messages are
+/// kept in a window of `Config::msg_dedup_window` per group of each peer node,
+/// so a burst of a group never evicts the fingerprints of the others.
+///
+/// The windows are cleared on every tick of the node. Raft resends the
+/// messages driven by ticks, e.g. probing a follower after heartbeats, so the
+/// retransmissions by raft itself are never dropped.
+pub(crate) struct MessageDedup {
+    window: usize,
+    /// The fingerprints of the recent messages by the peer node and the group,
+    /// the oldest first.
+    windows: HashMap<(u64, u64), VecDeque<u64>>,
+}
+
+impl MessageDedup {
+    pub(crate) fn new(window: usize) -> Self {
+        Self {
+            window,
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Returns true if the message is the duplicate of a recent message of the
+    /// group from the peer, otherwise it's remembered. Only the appends with entries and
+    /// the snapshots are checked, the other messages are cheap to step.
+    pub(crate) fn is_duplicate(&mut self, msg: &MultiRaftMessage) -> bool {
+        if self.window == 0 {
+            return false;
+        }
+        let fingerprint = match fingerprint(msg) {
+            None => return false,
+            Some(fingerprint) => fingerprint,
+        };
+
+        let recent = self
+            .windows
+            .entry((msg.from_node, msg.group_id))
+            .or_default();
+        if recent.contains(&fingerprint) {
+            return true;
+        }
+        if recent.len() >= self.window {
+            recent.pop_front();
+        }
+        recent.push_back(fingerprint);
+        false
+    }
+
+    pub(crate) fn tick(&mut self) {
+        self.windows.clear();
+    }
+}
+
+/// The fingerprint of the appends with entries and the snapshots. The entries
+/// are identified by their index and term, so the data is not hashed.
+fn fingerprint(msg: &MultiRaftMessage) -> Option<u64> {
+    let raft_msg = msg.msg.as_ref()?;
+    let mut h = DefaultHasher::new();
+    match raft_msg.msg_type() {
+        MessageType::MsgAppend if !raft_msg.entries.is_empty() => {
+            let (first, last) = (
+                &raft_msg.entries[0],
+                &raft_msg.entries[raft_msg.entries.len() - 1],
+            );
+            (first.index, first.term, last.index, last.term).hash(&mut h);
+        }
+        MessageType::MsgSnapshot => {
+            let snapshot = raft_msg.snapshot.as_ref()?;
+            let metadata = snapshot.get_metadata();
+            (metadata.index, metadata.term, snapshot.data.len()).hash(&mut h);
+        }
+        _ => return None,
+    }
+    (
+        msg.group_id,
+        raft_msg.msg_type,
+        raft_msg.from,
+        raft_msg.to,
+        raft_msg.term,
+        raft_msg.log_term,
+        raft_msg.index,
+        raft_msg.commit,
+    )
+        .hash(&mut h);
+    Some(h.finish())
+}
+
+#[cfg(test)]
+mod test {
+    use super::MessageDedup;
+    use crate::prelude::Entry;
+    use crate::prelude::Message;
+    use crate::prelude::MessageType;
+    use crate::prelude::MultiRaftMessage;
+
+    fn new_append(from_node: u64, index: u64, entries: usize) -> MultiRaftMessage {
+        let mut msg = Message::default();
+        msg.set_msg_type(MessageType::MsgAppend);
+        msg.from = from_node;
+        msg.to = 1;
+        msg.term = 1;
+        msg.index = index;
+        msg.entries = (1..=entries as u64)
+            .map(|i| Entry {
+                index: index + i,
+                term: 1,
+                ..Default::default()
+            })
+            .collect();
+        MultiRaftMessage {
+            group_id: 1,
+            from_node,
+            to_node: 1,
+            msg: Some(msg),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_message_dedup() {
+        let mut dedup = MessageDedup::new(2);
+        assert!(!dedup.is_duplicate(&new_append(2, 1, 1)));
+        assert!(dedup.is_duplicate(&new_append(2, 1, 1)));
+        // the appends without entries and the other peers are not checked.
+        assert!(!dedup.is_duplicate(&new_append(2, 1, 0)));
+        assert!(!dedup.is_duplicate(&new_append(2, 1, 0)));
+        assert!(!dedup.is_duplicate(&new_append(3, 1, 1)));
+
+        // the oldest fingerprint is evicted from the window.
+        assert!(!dedup.is_duplicate(&new_append(2, 2, 1)));
+        assert!(!dedup.is_duplicate(&new_append(2, 3, 1)));
+        assert!(!dedup.is_duplicate(&new_append(2, 1, 1)));
+        assert!(dedup.is_duplicate(&new_append(2, 3, 1)));
+
+        // the burst of another group evicts nothing of the group.
+        for index in 1..=4 {
+            let mut msg = new_append(2, index, 1);
+            msg.group_id = 2;
+            assert!(!dedup.is_duplicate(&msg));
+        }
+        assert!(dedup.is_duplicate(&new_append(2, 3, 1)));
+
+        // the windows are cleared by tick.
+        dedup.tick();
+        assert!(!dedup.is_duplicate(&new_append(2, 3, 1)));
+
+        let mut dedup = MessageDedup::new(0);
+        assert!(!dedup.is_duplicate(&new_append(2, 1, 1)));
+        assert!(!dedup.is_duplicate(&new_append(2, 1, 1)));
+    }
+}
@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+/// StorageDomains tracks the storage domain, e.g. the disk, of groups which are
+/// tagged when created, and the domains that are paused.
+///
+/// A `failure_threshold` of `0` means the domains are never paused by failures.
+pub struct StorageDomains {
+    failure_threshold: usize,
+    groups: HashMap<u64, String>,
+    paused: HashSet<String>,
+    /// The consecutive storage failures of domains.
+    failures: HashMap<String, usize>,
+}
+
+impl StorageDomains {
+    pub fn new(failure_threshold: usize) -> Self {
+        Self {
+            failure_threshold,
+            groups: HashMap::new(),
+            paused: HashSet::new(),
+            failures: HashMap::new(),
+        }
+    }
+
+    /// Tag the group with the domain, an empty domain untags the group.
+    pub fn set(&mut self, group_id: u64, domain: String) {
+        if domain.is_empty() {
+            self.groups.remove(&group_id);
+        } else {
+            self.groups.insert(group_id, domain);
+        }
+    }
+
+    pub fn remove(&mut self, group_id: u64) {
+        self.groups.remove(&group_id);
+    }
+
+    #[inline]
+    pub fn domain(&self, group_id: u64) -> Option<&str> {
+        self.groups.get(&group_id).map(|domain| domain.as_str())
+    }
+
+    /// Get the groups of the domain in the order of group id.
+    pub fn groups(&self, domain: &str) -> Vec<u64> {
+        let mut groups = self
+            .groups
+            .iter()
+            .filter(|(_, d)| d.as_str() == domain)
+            .map(|(group_id, _)| *group_id)
+            .collect::<Vec<_>>();
+        groups.sort_unstable();
+        groups
+    }
+
+    #[inline]
+    pub fn is_domain_paused(&self, domain: &str) -> bool {
+        self.paused.contains(domain)
+    }
+
+    /// Returns the domain of the group if the domain is paused.
+    #[inline]
+    pub fn paused_domain(&self, group_id: u64) -> Option<&str> {
+        self.domain(group_id)
+            .filter(|domain| self.paused.contains(*domain))
+    }
+
+    /// Mark the domain paused, returns `false` if it's already paused.
+    pub fn pause(&mut self, domain: &str) -> bool {
+        self.failures.remove(domain);
+        self.paused.insert(domain.to_owned())
+    }
+
+    /// Mark the domain resumed, returns `false` if it's not paused.
+    pub fn resume(&mut self, domain: &str) -> bool {
+        self.paused.remove(domain)
+    }
+
+    /// Record the storage failure of the group. Returns the domain of the group
+    /// if it should be paused, that is the consecutive failures reach the
+    /// threshold or the failure is `fatal`.
+    pub fn record_failure(&mut self, group_id: u64, fatal: bool) -> Option<String> {
+        if self.failure_threshold == 0 {
+            return None;
+        }
+
+        let domain = self.groups.get(&group_id)?;
+        if self.paused.contains(domain) {
+            return None;
+        }
+
+        let failures = self.failures.entry(domain.clone()).or_insert(0);
+        *failures += 1;
+        if fatal || *failures >= self.failure_threshold {
+            Some(domain.clone())
+        } else {
+            None
+        }
+    }
+
+    /// Record the storage success of the group, which resets the consecutive
+    /// failures of its domain.
+    pub fn record_success(&mut self, group_id: u64) {
+        if self.failures.is_empty() {
+            return;
+        }
+
+        if let Some(domain) = self.groups.get(&group_id) {
+            self.failures.remove(domain);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::StorageDomains;
+
+    #[test]
+    fn test_storage_domains_pause() {
+        let mut domains = StorageDomains::new(0);
+        domains.set(1, "disk1".to_owned());
+        domains.set(2, "disk2".to_owned());
+        domains.set(3, "disk1".to_owned());
+        domains.set(4, String::new());
+        assert_eq!(domains.groups("disk1"), vec![1, 3]);
+        assert_eq!(domains.domain(4), None);
+
+        assert!(domains.pause("disk1"));
+        assert!(!domains.pause("disk1"));
+        assert_eq!(domains.paused_domain(1), Some("disk1"));
+        assert_eq!(domains.paused_domain(2), None);
+
+        // the failures never pause domain if threshold is zero.
+        assert_eq!(domains.record_failure(2, true), None);
+
+        assert!(domains.resume("disk1"));
+        assert!(!domains.resume("disk1"));
+        assert_eq!(domains.paused_domain(1), None);
+
+        domains.remove(3);
+        assert_eq!(domains.groups("disk1"), vec![1]);
+    }
+
+    #[test]
+    fn test_storage_domains_failure_threshold() {
+        let mut domains = StorageDomains::new(3);
+        domains.set(1, "disk1".to_owned());
+        domains.set(2, "disk1".to_owned());
+
+        // the failures of groups in the same domain are accumulated.
+        assert_eq!(domains.record_failure(1, false), None);
+        assert_eq!(domains.record_failure(2, false), None);
+        domains.record_success(1);
+        assert_eq!(domains.record_failure(1, false), None);
+        assert_eq!(domains.record_failure(2, false), None);
+        assert_eq!(domains.record_failure(1, false), Some("disk1".to_owned()));
+
+        // the fatal failure pauses the domain immediately.
+        domains.record_success(1);
+        assert_eq!(domains.record_failure(2, true), Some("disk1".to_owned()));
+
+        // the untagged groups and paused domains are ignored.
+        assert_eq!(domains.record_failure(3, true), None);
+        domains.pause("disk1");
+        assert_eq!(domains.record_failure(1, true), None);
+    }
+}
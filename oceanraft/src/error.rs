@@ -1,3 +1,11 @@
+use uuid::Uuid;
+
+use crate::auth::Operation;
+use crate::fatal::FatalError;
+use crate::multiraft::NO_LEADER;
+use crate::state::GroupState;
+use crate::state::LeaderCandidate;
+
 // pub type Result<T> = std::result::Result<T, Error>;
 
 /// RaftCoreError is raft::Error re-exported.
@@ -43,6 +51,15 @@ pub enum RaftGroupError {
 
     #[error("group({1}) already exists in node({0})")]
     Exists(u64, u64),
+
+    #[error("group({1}) in node({0}) is paused by storage domain {2}")]
+    Paused(u64, u64, String),
+
+    #[error("the replica of group({1}) in node({0}) is a witness")]
+    Witness(u64, u64),
+
+    #[error("group({1}) in node({0}) is halted by fatal error")]
+    Halted(u64, u64),
 }
 
 #[derive(thiserror::Error, Debug, PartialEq)]
@@ -57,7 +74,7 @@ pub enum ChannelError {
     ReceiverClosed(String),
 }
 
-#[derive(thiserror::Error, Debug, PartialEq)]
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
 pub enum ProposeError {
     // TODO: more error info
     #[error("node {node_id:?} not leader: group = {group_id:?}, replica = {replica_id:?}")]
@@ -67,6 +84,16 @@ pub enum ProposeError {
         replica_id: u64,
     },
 
+    #[error("node {node_id:?} leader unknown: group = {group_id:?}, replica = {replica_id:?}, recent candidates = {candidates:?}")]
+    LeaderUnknown {
+        node_id: u64,
+        group_id: u64,
+        replica_id: u64,
+        /// The replicas which recently contacted the replica as a candidate or
+        /// leader, the most recent first.
+        candidates: Vec<LeaderCandidate>,
+    },
+
     #[error("stale write: expected is term {0}, current term is {1}")]
     Stale(u64, u64),
 
@@ -81,6 +108,85 @@ pub enum ProposeError {
 
     #[error("node {0}: has pending membership change is being processed on group {1}")]
     MembershipPending(u64 /* node_id */, u64 /* group_id */),
+
+    #[error("node {node_id}: entry {index} of group {group_id} rejected by entry gate: {reason}")]
+    GateRejected {
+        node_id: u64,
+        group_id: u64,
+        index: u64,
+        reason: String,
+    },
+
+    /// The apply of the group is held by the entry gate at `held_index`. The
+    /// entry of the proposal is committed and applied once the hold is
+    /// released, see `MultiRaft::release_gate_hold`.
+    #[error("node {node_id}: entry {index} of group {group_id} committed, but the apply is held by entry gate at {held_index}")]
+    GateHeld {
+        node_id: u64,
+        group_id: u64,
+        index: u64,
+        held_index: u64,
+    },
+
+    #[error("node {node_id}: memory budget exhausted at group {group_id}, allotted {allotted} bytes, used {used} bytes")]
+    MemoryBudgetExhausted {
+        node_id: u64,
+        group_id: u64,
+        allotted: usize,
+        used: usize,
+    },
+
+    #[error("node {node_id}: raft log of group {group_id} exceeds the quota")]
+    LogQuotaExceeded { node_id: u64, group_id: u64 },
+
+    /// The uncommitted proposals of the group reach
+    /// `Config::max_inflight_proposals`.
+    #[error("node {node_id}: group {group_id} has {limit} inflight proposals")]
+    TooManyInflightProposals {
+        node_id: u64,
+        group_id: u64,
+        limit: usize,
+    },
+
+    /// The outstanding read index requests of the group reach
+    /// `Config::max_read_queue_len`.
+    #[error("node {node_id}: read queue of group {group_id} is full, {len} reads outstanding")]
+    ReadQueueFull {
+        node_id: u64,
+        group_id: u64,
+        len: usize,
+    },
+
+    /// The proposal the write coalesced into failed, see
+    /// `MultiRaft::write_coalesced`.
+    #[error("coalesced write failed at group {group_id}, index {index}: {reason}")]
+    CoalescedWriteFailed {
+        group_id: u64,
+        index: u64,
+        reason: String,
+    },
+}
+
+impl ProposeError {
+    /// Returns `NotLeader` if the replica knows the leader of the group, otherwise
+    /// `LeaderUnknown` with the recently contacted candidates.
+    pub(crate) fn not_leader(node_id: u64, group_id: u64, state: &GroupState) -> Self {
+        let replica_id = state.get_replica_id();
+        if state.get_leader_id() != NO_LEADER {
+            return ProposeError::NotLeader {
+                node_id,
+                group_id,
+                replica_id,
+            };
+        }
+
+        ProposeError::LeaderUnknown {
+            node_id,
+            group_id,
+            replica_id,
+            candidates: state.recent_candidates(),
+        }
+    }
 }
 
 #[derive(thiserror::Error, Debug, PartialEq)]
@@ -111,6 +217,10 @@ pub enum DeserializationError {
     /// An error occurred when deserializing with flexbuffer.
     #[error("{0}")]
     Flexbuffer(#[from] flexbuffers::DeserializationError),
+
+    /// An error occurred when decompressing the raft message.
+    #[error("decompress: {0}")]
+    Decompress(#[from] std::io::Error),
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -125,6 +235,11 @@ pub enum Error {
     #[error("{0}")]
     Channel(#[from] ChannelError),
 
+    /// The work is shed since the node is overloaded, the caller should back
+    /// off and retry, see `Config::overload_shedding`.
+    #[error("node {node_id} overloaded: {reason}")]
+    Overloaded { node_id: u64, reason: String },
+
     /// An error occurred during the proposal.
     #[error("{0}")]
     Propose(#[from] ProposeError),
@@ -147,4 +262,123 @@ pub enum Error {
 
     #[error("{0}")]
     RaftGroup(#[from] RaftGroupError),
+
+    /// The index of entries to be applied is not continuous with the
+    /// applied state of the group.
+    #[error("apply entries index gap, expected {expected}, but got {got}")]
+    ApplyGap { expected: u64, got: u64 },
+
+    /// The snapshot received from the leader is corrupt or rejected by the
+    /// `SnapshotValidator`, the leader is requested to resend the snapshot.
+    #[error("snapshot rejected: group = {group_id}, index = {index}, reason = {reason}")]
+    SnapshotRejected {
+        group_id: u64,
+        index: u64,
+        reason: String,
+    },
+
+    /// The chunk of the snapshot failed to fetch or verify, the download can
+    /// be resumed from the offset, see `SnapshotDownload`.
+    #[error(
+        "fetch snapshot chunk failed: group = {group_id}, offset = {offset}, reason = {reason}"
+    )]
+    SnapshotChunk {
+        group_id: u64,
+        offset: u64,
+        reason: String,
+    },
+
+    /// The operation on the group is denied by the `Authorizer`.
+    #[error("permission denied: group = {group_id}, operation = {operation:?}, reason = {reason}")]
+    PermissionDenied {
+        group_id: u64,
+        operation: Operation,
+        reason: String,
+    },
+
+    /// The target replica of relocation did not catch up with the leader
+    /// before timeout.
+    #[error("relocation timeout: group = {group_id}, replica {replica_id} not caught up")]
+    RelocationTimeout { group_id: u64, replica_id: u64 },
+
+    /// The membership of the group did not become the target voters before
+    /// timeout, see `MultiRaft::change_membership`.
+    #[error("membership change timeout: group = {group_id}, voters = {voters:?}")]
+    MembershipChangeTimeout { group_id: u64, voters: Vec<u64> },
+
+    /// The leadership of the group was not transferred to the transferee
+    /// before timeout, see `MultiRaft::transfer_leader`.
+    #[error("leader transfer timeout: group = {group_id}, transferee = {transferee}")]
+    LeaderTransferTimeout { group_id: u64, transferee: u64 },
+
+    /// Another replica became the leader of the group during the transfer,
+    /// e.g. the transferee lost the election.
+    #[error("leader transfer to {transferee} failed: group = {group_id}, leader = {leader}")]
+    LeaderTransferFailed {
+        group_id: u64,
+        transferee: u64,
+        leader: u64,
+    },
+
+    /// The learner lags behind the committed index of the leader beyond the
+    /// threshold, see `MultiRaft::promote_learner`.
+    #[error("learner {replica_id} not caught up: group = {group_id}, matched = {matched}, committed = {committed}")]
+    LearnerNotCaughtUp {
+        group_id: u64,
+        replica_id: u64,
+        matched: u64,
+        committed: u64,
+    },
+
+    /// The participant group voted to abort the transaction when prepared,
+    /// see `TxnParticipant::prepare`.
+    #[error("txn {txn_id} prepare rejected: group = {group_id}, reason = {reason}")]
+    TxnPrepareRejected {
+        txn_id: u64,
+        group_id: u64,
+        reason: String,
+    },
+
+    /// The group is halted by the fatal error, see `MultiRaft::fatal_errors`.
+    #[error("{0}")]
+    Fatal(#[from] FatalError),
+
+    /// Wraps the error of an operation with the request id of the operation,
+    /// which is also recorded in tracing spans of the operation.
+    #[error("{source}, request_id = {request_id}")]
+    WithRequestId {
+        request_id: Uuid,
+        #[source]
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Attach the request id of the operation to the error, if the error
+    /// already has a request id, the error is returned as is.
+    pub fn with_request_id(self, request_id: Uuid) -> Self {
+        match self {
+            Error::WithRequestId { .. } => self,
+            err => Error::WithRequestId {
+                request_id,
+                source: Box::new(err),
+            },
+        }
+    }
+
+    /// Returns the request id of the operation that caused the error, if any.
+    pub fn request_id(&self) -> Option<Uuid> {
+        match self {
+            Error::WithRequestId { request_id, .. } => Some(*request_id),
+            _ => None,
+        }
+    }
+
+    /// Returns the error without the request id.
+    pub fn without_request_id(&self) -> &Error {
+        match self {
+            Error::WithRequestId { source, .. } => source.as_ref(),
+            err => err,
+        }
+    }
 }
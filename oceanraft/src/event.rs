@@ -1,3 +1,13 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use crate::gate::GateAction;
+use crate::operation::OperationProgress;
+use crate::prelude::ConfState;
+use crate::quota::LogQuotaStage;
+
 use super::error::Error;
 
 /// A LeaderElectionEvent is send when leader changed.
@@ -10,12 +20,84 @@ pub struct LeaderElectionEvent {
     pub replica_id: u64,
     /// Current leader id.
     pub leader_id: u64,
+    /// The term in which the leader is elected.
+    pub term: u64,
+}
+
+/// The health of a replica in the configuration of the group, which is gathered
+/// from the progress tracked by the leader.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicaHealth {
+    pub replica_id: u64,
+    /// The node where the replica resides, `0` if unknown.
+    pub node_id: u64,
+    /// True if the replica is a learner.
+    pub learner: bool,
+    /// True if the leader has recently received messages from the replica.
+    pub reachable: bool,
+    /// The highest log index known to be replicated to the replica.
+    pub matched: u64,
+}
+
+/// A MembershipChangeEvent is send when a membership change is applied.
+#[derive(Debug, Clone)]
+pub struct MembershipChangeEvent {
+    pub group_id: u64,
+    /// Current replica id.
+    pub replica_id: u64,
+    /// The index of the membership change entry.
+    pub index: u64,
+    /// The term of the membership change entry.
+    pub term: u64,
+    /// The resulting configuration.
+    pub conf_state: ConfState,
+    /// The health of replicas in the resulting configuration, it is only
+    /// gathered if current replica is the leader, otherwise is empty.
+    pub health: Vec<ReplicaHealth>,
+}
+
+/// The stage of the replica relocation, see `MultiRaft::relocate_replica`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RelocationStage {
+    /// The target replica is added as learner and the leader starts
+    /// pre-copying data to it.
+    LearnerAdded,
+    /// The target replica is catching up with the leader.
+    CatchingUp { matched: u64, committed: u64 },
+    /// The source replica is the leader, the leadership is transferred to
+    /// the target replica promoted to voter before the source is removed.
+    LeaderTransferred,
+    /// The target replica is promoted to voter and the source replica
+    /// is removed.
+    Promoted,
+    /// The relocation failed and the target replica has been removed.
+    Failed,
+}
+
+/// The stage of the snapshot streamed between nodes, see `SnapshotUpload`
+/// and `SnapshotStreamReceiver`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotTransferStage {
+    /// The chunk is acknowledged by the receiver.
+    Sending,
+    /// All chunks are acknowledged by the receiver.
+    Sent,
+    /// The chunk is received.
+    Receiving,
+    /// The data is received and installed by `RaftSnapshotWriter`.
+    Installed,
+    /// The chunk failed to send or the data failed to install, the stream
+    /// can be resumed from the offset.
+    Failed,
 }
 
 #[derive(Debug, Clone)]
 pub enum Event {
     LederElection(LeaderElectionEvent),
 
+    /// Sent when a membership change is applied.
+    MembershipChange(MembershipChangeEvent),
+
     /// Sent when consensus group is created.
     GroupCreate {
         group_id: u64,
@@ -25,22 +107,167 @@ pub enum Event {
         // applied_index: u64,
         // applied_term: u64,
     },
+
+    /// Sent when the idle consensus group is parked, the raft group is
+    /// released from memory until the next message or proposal arrives.
+    GroupPark { group_id: u64, replica_id: u64 },
+
+    /// Sent when the committed but not yet applied entries of the group
+    /// exceed `Config::apply_backlog_threshold`, which usually means the
+    /// state machine is slower than the raft replication.
+    ApplyBacklogHigh {
+        group_id: u64,
+        replica_id: u64,
+        /// Current leader id of the group.
+        leader_id: u64,
+        /// The number of committed but not yet applied entries.
+        backlog: u64,
+        /// The estimated bytes of the backlog entries.
+        backlog_bytes: u64,
+    },
+
+    /// Sent when the relocation of replica makes progress.
+    Relocation {
+        group_id: u64,
+        from_replica_id: u64,
+        to_replica_id: u64,
+        stage: RelocationStage,
+    },
+
+    /// Sent when the long-running operation is started, makes progress and
+    /// is finished, see `MultiRaft::operation_progress`.
+    OperationProgress(OperationProgress),
+
+    /// Sent when the index of entries to be applied is not continuous
+    /// with the applied state of the group, the apply of the group is
+    /// stopped to avoid feeding bad sequences to the state machine.
+    ApplyGap {
+        group_id: u64,
+        replica_id: u64,
+        expected: u64,
+        got: u64,
+    },
+
+    /// Sent when the entry gate holds, rejects or transforms an entry of the
+    /// group, `policy` is the policy version of the gate made the decision.
+    EntryGated {
+        group_id: u64,
+        replica_id: u64,
+        index: u64,
+        term: u64,
+        policy: u64,
+        action: GateAction,
+    },
+
+    /// Sent when the storage domain is paused, `groups` are the groups of
+    /// the domain on the node.
+    StorageDomainPaused {
+        domain: String,
+        groups: Vec<u64>,
+    },
+
+    /// Sent when the storage domain is resumed.
+    StorageDomainResumed {
+        domain: String,
+    },
+
+    /// Sent when the escalation stage of the group whose raft log exceeds
+    /// `Config::group_log_quota` changes, `LogQuotaStage::Normal` is sent
+    /// when the log is back under the quota.
+    LogQuota {
+        group_id: u64,
+        replica_id: u64,
+        /// The approximate bytes of the raft log.
+        bytes: u64,
+        quota: u64,
+        stage: LogQuotaStage,
+    },
+
+    /// Sent when the learner caught up with the leader is promoted to voter
+    /// by the auto-promotion policy of the group, see `LearnerPromoter`.
+    LearnerPromoted {
+        group_id: u64,
+        /// Current replica id, which is the leader promoted the learner.
+        replica_id: u64,
+        learner_id: u64,
+        /// The node where the learner resides.
+        node_id: u64,
+    },
+
+    /// Sent when the snapshot streamed between nodes makes progress, the
+    /// sender and the receiver report the stages of their own side.
+    SnapshotTransfer {
+        group_id: u64,
+        from_node: u64,
+        to_node: u64,
+        /// The index of the snapshot.
+        index: u64,
+        /// The number of bytes acknowledged or received.
+        offset: u64,
+        total_size: u64,
+        stage: SnapshotTransferStage,
+    },
+
+    /// Sent when the snapshot to send is queued by
+    /// `Config::max_concurrent_snapshots` or
+    /// `Config::snapshot_max_bytes_per_sec`.
+    SnapshotQueued {
+        group_id: u64,
+        /// Current replica id, which is the leader sending the snapshot.
+        replica_id: u64,
+        to_replica_id: u64,
+        /// The index of the snapshot.
+        index: u64,
+        /// The number of snapshots queued on the node.
+        queued: usize,
+    },
 }
 
 /// Shrink queue if queue capacity more than and len less than
 /// this value.
 const SHRINK_CACHE_CAPACITY: usize = 64;
 
+/// The max number of the idle buffers kept by `EventBuffers`.
+const MAX_POOLED_BUFFERS: usize = 16;
+
+/// The buffers of the events delivered by the spawned tasks, they are reused
+/// by the following deliveries of the channel and its clones.
+#[derive(Default)]
+struct EventBuffers {
+    buffers: Mutex<Vec<Vec<Arc<Event>>>>,
+    /// The number of the spawned deliveries in flight, the events are sent in
+    /// place only if it's zero, so they are not sent before the pending ones.
+    delivering: AtomicUsize,
+}
+
+impl EventBuffers {
+    fn take(&self) -> Vec<Arc<Event>> {
+        self.buffers.lock().unwrap().pop().unwrap_or_default()
+    }
+
+    fn put(&self, mut buffer: Vec<Arc<Event>>) {
+        buffer.clear();
+        buffer.shrink_to(SHRINK_CACHE_CAPACITY);
+        let mut buffers = self.buffers.lock().unwrap();
+        if buffers.len() < MAX_POOLED_BUFFERS {
+            buffers.push(buffer);
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct EventReceiver {
-    rx: flume::Receiver<Event>,
+    rx: flume::Receiver<Arc<Event>>,
 }
 
 impl EventReceiver {
     /// Wait for an incoming value from the channel associated with this receiver, returning an
     /// error if all senders have been dropped or the deadline has passed.
+    ///
+    /// The event is shared by the receivers of the emission, it's not cloned
+    /// for each of them.
     #[inline]
-    pub async fn recv(&self) -> Result<Event, Error> {
+    pub async fn recv(&self) -> Result<Arc<Event>, Error> {
         self.rx.recv_async().await.map_err(|_| {
             Error::Channel(super::error::ChannelError::SenderClosed(
                 "channel of event sender is closed".to_owned(),
@@ -50,19 +277,26 @@ impl EventReceiver {
 }
 
 pub struct EventChannel {
-    tx: flume::Sender<Event>,
-    rx: flume::Receiver<Event>,
+    tx: flume::Sender<Arc<Event>>,
+    rx: flume::Receiver<Arc<Event>>,
     cap: usize,
-    cache: Vec<Event>,
+    cache: Vec<Arc<Event>>,
+    /// Send events in `flush` without a spawned task, the events are
+    /// dropped if the channel is full.
+    inline: bool,
+    buffers: Arc<EventBuffers>,
 }
 
 impl Clone for EventChannel {
     fn clone(&self) -> Self {
+        // the clones usually push a few events, the cache grows on demand.
         Self {
             cap: self.cap,
-            cache: Vec::with_capacity(self.cap),
+            cache: Vec::new(),
             tx: self.tx.clone(),
             rx: self.rx.clone(),
+            inline: self.inline,
+            buffers: self.buffers.clone(),
         }
     }
 }
@@ -75,12 +309,19 @@ impl EventChannel {
             tx,
             rx,
             cache: Vec::with_capacity(cap),
+            inline: false,
+            buffers: Arc::new(EventBuffers::default()),
         }
     }
 
+    #[inline]
+    pub(crate) fn set_inline_flush(&mut self) {
+        self.inline = true;
+    }
+
     #[inline]
     pub fn push(&mut self, event: Event) {
-        self.cache.push(event);
+        self.cache.push(Arc::new(event));
     }
 
     #[inline]
@@ -98,21 +339,111 @@ impl EventChannel {
         }
     }
 
+    /// Send the cached events. The events are sent in place while the
+    /// channel has room, which allocates nothing, the rest are sent by a
+    /// spawned task with a pooled buffer once the channel is full.
     pub fn flush(&mut self) {
         if self.cache.is_empty() {
             return;
         }
 
-        let events = self.cache.drain(..).collect::<Vec<_>>();
+        let mut events = self.cache.drain(..);
+        if self.inline {
+            for event in events {
+                let _ = self.tx.try_send(event);
+            }
+            self.try_gc();
+            return;
+        }
+
+        let mut full = None;
+        if self.buffers.delivering.load(Ordering::Acquire) == 0 {
+            for event in events.by_ref() {
+                if let Err(err) = self.tx.try_send(event) {
+                    full = Some(err.into_inner());
+                    break;
+                }
+            }
+        }
+        if full.is_none() && events.as_slice().is_empty() {
+            drop(events);
+            self.try_gc();
+            return;
+        }
+
+        let mut buffer = self.buffers.take();
+        buffer.extend(full);
+        buffer.extend(events);
         self.try_gc();
+
         let tx = self.tx.clone();
+        let buffers = self.buffers.clone();
+        buffers.delivering.fetch_add(1, Ordering::AcqRel);
         let _ = tokio::spawn(async move {
-            for event in events {
-                match tx.send_async(event).await {
-                    Ok(_) => {}
-                    Err(_) => {}
+            for event in buffer.drain(..) {
+                if tx.send_async(event).await.is_err() {
+                    break;
                 }
             }
+            buffers.put(buffer);
+            buffers.delivering.fetch_sub(1, Ordering::AcqRel);
         });
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use super::Event;
+    use super::EventChannel;
+
+    fn new_event(group_id: u64) -> Event {
+        Event::GroupPark {
+            group_id,
+            replica_id: 1,
+        }
+    }
+
+    fn group_id_of(event: Arc<Event>) -> u64 {
+        match *event {
+            Event::GroupPark { group_id, .. } => group_id,
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_event_channel_flush() {
+        let mut chan = EventChannel::new(2);
+        let rx = chan.subscribe();
+        let delivering = |chan: &EventChannel| chan.buffers.delivering.load(Ordering::Acquire);
+        let pooled = |chan: &EventChannel| chan.buffers.buffers.lock().unwrap().len();
+
+        // the events are sent in place while the channel has room.
+        chan.push(new_event(1));
+        chan.flush();
+        assert_eq!(delivering(&chan), 0);
+
+        // the rest are delivered by the spawned task once the channel is full.
+        for group_id in 2..=4 {
+            chan.push(new_event(group_id));
+        }
+        chan.flush();
+        assert_eq!(delivering(&chan), 1);
+        for group_id in 1..=4 {
+            assert_eq!(group_id_of(rx.recv().await.unwrap()), group_id);
+        }
+        while delivering(&chan) != 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(pooled(&chan), 1);
+
+        // the clones share the pool, the buffer is not taken in place.
+        let mut cloned = chan.clone();
+        cloned.push(new_event(5));
+        cloned.flush();
+        assert_eq!(group_id_of(rx.recv().await.unwrap()), 5);
+        assert_eq!(pooled(&chan), 1);
+    }
+}
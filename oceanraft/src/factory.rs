@@ -0,0 +1,23 @@
+use std::fmt::Debug;
+
+use crate::prelude::CreateGroupRequest;
+
+/// GroupFactory provides the initial membership of the group which does not
+/// exist on the node when a write to the group arrives, it allows the
+/// applications to create shards on demand, see `Config::auto_create_groups`.
+pub trait GroupFactory: Debug + Send + Sync + 'static {
+    /// Returns the request to create the replica of the group on the node,
+    /// `None` if the group should not be created. The `group_id` of the
+    /// request must be the given group id and `lazy` is ignored.
+    fn create_group(&self, node_id: u64, group_id: u64) -> Option<CreateGroupRequest>;
+}
+
+/// Never creates groups, it's the default factory.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoGroupFactory;
+
+impl GroupFactory for NoGroupFactory {
+    fn create_group(&self, _: u64, _: u64) -> Option<CreateGroupRequest> {
+        None
+    }
+}
@@ -0,0 +1,120 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use tokio::sync::broadcast;
+use tracing::error;
+
+/// The number of fatal errors buffered for the receivers, the receiver
+/// lagging behind skips the oldest errors.
+const FATAL_ERROR_CAPACITY: usize = 128;
+
+/// The fatal error of the node, the group of the error is halted after the
+/// error is emitted to `MultiRaft::fatal_errors`. The halted group is not
+/// served by the node anymore, the application decides whether to restart
+/// the node or exit the process.
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+pub enum FatalError {
+    /// The storage of the group is unavailable and the group does not belong
+    /// to a storage domain which can be paused.
+    #[error("node {node_id}: group {group_id} storage unavailable")]
+    StorageUnavailable { node_id: u64, group_id: u64 },
+
+    /// The applied index of the group is beyond the committed or persisted
+    /// index in the storage.
+    #[error("node {node_id}: group {group_id} applied {applied} is out of range, committed = {committed}, persisted = {persisted}")]
+    AppliedOutOfRange {
+        node_id: u64,
+        group_id: u64,
+        applied: u64,
+        committed: u64,
+        persisted: u64,
+    },
+
+    /// The commit state to apply is behind the applied state of the group.
+    #[error(
+        "node {node_id}: group {group_id} commit state jump backward {applied:?} -> {commit:?}"
+    )]
+    CommitBackward {
+        node_id: u64,
+        group_id: u64,
+        /// The (index, term) applied.
+        applied: (u64, u64),
+        /// The (index, term) committed.
+        commit: (u64, u64),
+    },
+}
+
+impl FatalError {
+    pub fn node_id(&self) -> u64 {
+        match self {
+            FatalError::StorageUnavailable { node_id, .. }
+            | FatalError::AppliedOutOfRange { node_id, .. }
+            | FatalError::CommitBackward { node_id, .. } => *node_id,
+        }
+    }
+
+    pub fn group_id(&self) -> u64 {
+        match self {
+            FatalError::StorageUnavailable { group_id, .. }
+            | FatalError::AppliedOutOfRange { group_id, .. }
+            | FatalError::CommitBackward { group_id, .. } => *group_id,
+        }
+    }
+}
+
+/// The channel of the fatal errors of the node. The errors emitted before the
+/// first receiver are buffered for it, e.g. the errors of restoring groups.
+#[derive(Clone)]
+pub(crate) struct FatalErrorChannel {
+    tx: broadcast::Sender<FatalError>,
+    first_rx: Arc<Mutex<Option<broadcast::Receiver<FatalError>>>>,
+}
+
+impl FatalErrorChannel {
+    pub(crate) fn new() -> Self {
+        let (tx, rx) = broadcast::channel(FATAL_ERROR_CAPACITY);
+        Self {
+            tx,
+            first_rx: Arc::new(Mutex::new(Some(rx))),
+        }
+    }
+
+    pub(crate) fn emit(&self, err: FatalError) {
+        error!("{}", err);
+        // the error is dropped if all receivers are dropped.
+        let _ = self.tx.send(err);
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<FatalError> {
+        match self.first_rx.lock().unwrap().take() {
+            Some(rx) => rx,
+            None => self.tx.subscribe(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FatalError;
+    use super::FatalErrorChannel;
+
+    #[test]
+    fn test_fatal_error_channel() {
+        let chan = FatalErrorChannel::new();
+        let err = FatalError::StorageUnavailable {
+            node_id: 1,
+            group_id: 2,
+        };
+        chan.emit(err.clone());
+
+        // the first receiver receives the errors emitted before subscribed.
+        let mut rx1 = chan.subscribe();
+        let mut rx2 = chan.subscribe();
+        assert_eq!(rx1.try_recv().unwrap(), err);
+        assert!(rx2.try_recv().is_err());
+
+        chan.emit(err.clone());
+        assert_eq!(rx1.try_recv().unwrap().group_id(), 2);
+        assert_eq!(rx2.try_recv().unwrap().node_id(), 1);
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt::Debug;
+
+use raft::prelude::Entry;
+
+use crate::admin::AdminPayload;
+
+/// The command of the admin entry which replicates the version of the gate
+/// policy, see `MultiRaft::propose_gate_policy`.
+pub const GATE_POLICY_COMMAND: &str = "oceanraft.gate_policy";
+
+/// The decision of `EntryGate` for a committed normal entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GateDecision {
+    /// Apply the entry as usual.
+    Accept,
+    /// Pause the apply of the group at the entry. The entry and the following
+    /// entries are left unapplied until the hold is released by
+    /// `MultiRaft::release_gate_hold` or the node restarts, the entry is
+    /// consulted again then, e.g. after the node is upgraded. The proposals
+    /// of the unapplied entries receive `ProposeError::GateHeld`.
+    ///
+    /// The hold is not persisted, the applied index is not advanced so the
+    /// node consults the gate for the held entry again after restart.
+    Hold,
+    /// Skip the entry, the state machine receives it as a no-op and the
+    /// proposer receives `ProposeError::GateRejected` with the reason.
+    Reject(String),
+    /// Apply the returned data in place of the data of the entry.
+    Transform(Vec<u8>),
+}
+
+/// The action recorded by `Event::EntryGated`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateAction {
+    Hold,
+    Reject,
+    Transform,
+}
+
+/// EntryGate is consulted by the apply worker before a normal entry is decoded
+/// and fed to the state machine. It allows a node of a rolling upgrade to handle
+/// the entries proposed by newer nodes which it can't apply.
+///
+/// All replicas must make the same decision for the same entry, so `Reject` and
+/// `Transform` should only depend on the entry and the policy of the gate. The
+/// version of the policy is replicated by the group, a node whose gate policy
+/// doesn't match the replicated one holds the entries that it would reject or
+/// transform, so an old node can't reject an entry that a new node accepts or
+/// transforms. `Hold` is safe to differ between nodes since the applied index
+/// is not advanced.
+pub trait EntryGate: Debug + Send + Sync + 'static {
+    /// The version of the gate policy. `Reject` and `Transform` are only
+    /// applied if it matches the policy replicated by the group by
+    /// `MultiRaft::propose_gate_policy`, otherwise they are turned to `Hold`.
+    /// It's recorded with every non-accept decision by `Event::EntryGated`.
+    fn policy(&self) -> u64 {
+        0
+    }
+
+    fn check(&self, group_id: u64, entry: &Entry) -> GateDecision;
+}
+
+/// Accepts all entries, it's the default gate.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct AcceptAllGate;
+
+impl EntryGate for AcceptAllGate {
+    fn check(&self, _: u64, _: &Entry) -> GateDecision {
+        GateDecision::Accept
+    }
+}
+
+/// Returns the admin payload that replicates the version of the gate policy.
+pub(crate) fn gate_policy_payload(policy: u64) -> AdminPayload {
+    AdminPayload::new(GATE_POLICY_COMMAND, policy.to_be_bytes().to_vec())
+}
+
+/// Returns the version of the gate policy if the payload replicates it.
+pub(crate) fn decode_gate_policy(payload: &AdminPayload) -> Option<u64> {
+    if payload.command != GATE_POLICY_COMMAND {
+        return None;
+    }
+    payload
+        .data
+        .as_slice()
+        .try_into()
+        .ok()
+        .map(u64::from_be_bytes)
+}
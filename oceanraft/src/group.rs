@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::sync::Arc;
 
 use raft::prelude::ConfChangeTransition;
@@ -7,6 +9,7 @@ use raft::ReadState;
 use raft::Ready;
 use raft::SoftState;
 use raft::StateRole;
+use tokio::time::Instant;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
@@ -15,24 +18,43 @@ use tracing::warn;
 use tracing::Level;
 use uuid::Uuid;
 
+use crate::coalesce::CoalescedWaiter;
+use crate::coalesce::WriteCoalescer;
+use crate::compression::EntryCompression;
 use crate::msg::MembershipRequestContext;
+use crate::multiraft::ConsistencyLevel;
 use crate::multiraft::ProposeResponse;
 use crate::prelude::ConfChange;
 use crate::prelude::ConfChangeSingle;
 use crate::prelude::ConfChangeV2;
 use crate::prelude::MembershipChangeData;
+use crate::prelude::Message;
+use crate::prelude::MessageType;
 use crate::prelude::ReplicaDesc;
 use crate::prelude::Snapshot;
+use crate::promotion::LearnerCatchUp;
+use crate::quota::LogQuota;
 
+use super::apply_flow::ApplyFlow;
+use super::budget::MemoryBudget;
+use super::compaction::LogUsage;
 use super::error::Error;
 use super::error::ProposeError;
 use super::error::RaftGroupError;
 use super::event::EventChannel;
 use super::event::LeaderElectionEvent;
+use super::id::IdGenerator;
+use super::latency::LatencyRecorder;
+use super::latency::ProposalTimeline;
+use super::latency::ReadTimeline;
+use super::lease::LeaderLease;
+use super::msg::AdminRequest;
 use super::msg::ApplyData;
 use super::msg::ApplyResultMessage;
 use super::msg::MembershipRequest;
+use super::msg::ReadIndexContext;
 use super::msg::ReadIndexData;
+use super::msg::WriteCommit;
 use super::msg::WriteRequest;
 use super::multiraft::NO_NODE;
 use super::node::NodeManager;
@@ -42,12 +64,18 @@ use super::proposal::Proposal;
 use super::proposal::ProposalQueue;
 use super::proposal::ReadIndexProposal;
 use super::proposal::ReadIndexQueue;
+use super::proposer::Proposer;
 use super::replica_cache::ReplicaCache;
 use super::state::GroupState;
+use super::state::ReadMetrics;
+use super::state::StaleMessage;
+use super::storage::GroupWrite;
 use super::storage::MultiRaftStorage;
 use super::storage::RaftStorage;
 use super::transport;
+use super::transport::SendFailureReporter;
 use super::utils;
+use super::utils::encode_propose_data;
 use super::utils::flexbuffer_serialize;
 use super::Event;
 use super::ProposeData;
@@ -96,6 +124,44 @@ where
     pub status: Status,
     pub read_index_queue: ReadIndexQueue,
     pub shared_state: Arc<GroupState>,
+
+    /// The number of ticks since the last proposal or raft message, used to
+    /// park long-idle groups.
+    pub idle_ticks: usize,
+
+    /// The replica is a witness which stores only the log metadata and never
+    /// becomes leader.
+    pub witness: bool,
+
+    /// The replicas removed from the group by the membership changes applied
+    /// since the replica was created, the messages from them are dropped.
+    pub removed_replicas: HashSet<u64>,
+
+    /// The escalation of the log quota, see `Config::group_log_quota`.
+    pub log_quota: LogQuota,
+
+    /// The learners caught up with the leader, see `Config::learner_promoter`.
+    pub learner_catch_up: LearnerCatchUp,
+
+    /// The uncommitted proposals of the coalesced writes.
+    pub(crate) write_coalescer: WriteCoalescer<RES>,
+
+    /// The reads confirmed but the read index is not applied yet, they are
+    /// recorded to `latency` after applied, see `Config::record_latency`.
+    pub(crate) applying_reads: VecDeque<ReadTimeline>,
+    /// The follower reads confirmed but the read index is not applied yet,
+    /// they are responded after applied, see `ConsistencyLevel::Follower`.
+    pub(crate) applying_follower_reads: VecDeque<ReadIndexProposal>,
+    pub(crate) latency: Arc<LatencyRecorder>,
+
+    /// The usage of the raft log, see `Config::compaction_policy`.
+    pub(crate) log_usage: LogUsage,
+    /// The leader lease, see `Config::enable_lease_read`.
+    pub(crate) lease: LeaderLease,
+    /// See `Config::max_apply_unapplied_size`.
+    pub(crate) apply_flow: ApplyFlow<RES>,
+    /// The snapshots to send by the node, see `Config::max_concurrent_snapshots`.
+    pub(crate) outgoing_snapshots: Vec<Message>,
 }
 
 impl<RS, RES> RaftGroup<RS, RES>
@@ -128,6 +194,16 @@ where
         self.raft_group.raft.raft_log.last_index()
     }
 
+    /// Keep the snapshots of the messages to send by the node, returns the
+    /// others.
+    fn hold_snapshots(&mut self, msgs: Vec<Message>) -> Vec<Message> {
+        let (snapshots, msgs) = msgs
+            .into_iter()
+            .partition::<Vec<_>, _>(|msg| msg.msg_type() == MessageType::MsgSnapshot);
+        self.outgoing_snapshots.extend(snapshots);
+        msgs
+    }
+
     #[tracing::instrument(
         level = Level::TRACE,
         name = "RaftGroup::handle_ready",
@@ -138,6 +214,7 @@ where
         &mut self,
         node_id: u64,
         transport: &TR,
+        failure_reporter: &SendFailureReporter,
         storage: &MRS,
         replica_cache: &mut ReplicaCache<RS, MRS>,
         node_manager: &mut NodeManager,
@@ -159,6 +236,7 @@ where
                     group_id,
                     node_id,
                     replica_id: self.raft_group.raft.id,
+                    witness: false,
                 };
 
                 replica_cache
@@ -182,17 +260,18 @@ where
             transport::send_messages(
                 node_id,
                 transport,
+                failure_reporter,
                 replica_cache,
                 node_manager,
                 group_id,
-                rd.take_messages(),
+                self.hold_snapshots(rd.take_messages()),
             )
             .await;
         }
 
         if let Some(ss) = rd.ss() {
             self.handle_soft_state_change(node_id, storage, ss, replica_cache, event_bcast)
-                .await;
+                .await?;
         }
 
         if !rd.read_states().is_empty() {
@@ -251,6 +330,7 @@ where
         if self.commit_index != last_commit_ent.index && self.leader.replica_id != 0 {
             self.commit_index = last_commit_ent.index;
         }
+        self.write_coalescer.compact(last_commit_ent.index);
 
         self.create_apply(gs, replica_id, entries)
     }
@@ -296,7 +376,19 @@ where
                         continue;
                     }
 
-                    Some(p) => proposals.push(p),
+                    Some(mut p) => {
+                        if let Some(timeline) = p.timeline.as_mut() {
+                            timeline.committed(Instant::now());
+                        }
+                        if let Some(commit_tx) = p.commit_tx.take() {
+                            let _ = commit_tx.send(WriteCommit {
+                                index: entry.index,
+                                term: entry.term,
+                                committed_at: Instant::now(),
+                            });
+                        }
+                        proposals.push(p)
+                    }
                 };
             }
         }
@@ -325,8 +417,46 @@ where
 
     fn on_reads_ready(&mut self, rss: Vec<ReadState>) {
         self.read_index_queue.advance_reads(rss);
-        while let Some(p) = self.read_index_queue.pop_front() {
-            p.tx.map(|tx| tx.send(Ok(p.context.map_or(None, |mut ctx| ctx.context.take()))));
+        while let Some(mut p) = self.read_index_queue.pop_front() {
+            // the read index confirmed by the heartbeat quorum renews the lease
+            // from the time it's issued.
+            if self.lease.is_enabled() && self.is_leader() {
+                self.lease.renew(self.raft_group.raft.term, p.issued_at);
+            }
+            // the batched reads share the read index of the round.
+            let batched = std::mem::take(&mut p.batched);
+            let read_index = p.read_index;
+            self.respond_read(p);
+            for mut read in batched {
+                read.read_index = read_index;
+                self.respond_read(read);
+            }
+        }
+    }
+
+    fn respond_read(&mut self, mut p: ReadIndexProposal) {
+        if let (Some(mut timeline), Some(read_index)) = (p.timeline.take(), p.read_index) {
+            timeline.confirmed(read_index, Instant::now());
+            self.track_read(timeline);
+        }
+        if p.wait_applied
+            && p.read_index
+                .map_or(false, |index| index > self.raft_group.raft.raft_log.applied)
+        {
+            self.applying_follower_reads.push_back(p);
+            return;
+        }
+        p.tx.map(|tx| tx.send(Ok(p.context.map_or(None, |mut ctx| ctx.context.take()))));
+    }
+
+    /// Record the latency of the confirmed read if the read index is applied,
+    /// otherwise it waits for the apply.
+    fn track_read(&mut self, timeline: ReadTimeline) {
+        if timeline.read_index <= self.raft_group.raft.raft_log.applied {
+            self.latency
+                .record_read(self.group_id, &timeline, Instant::now());
+        } else {
+            self.applying_reads.push_back(timeline);
         }
     }
 
@@ -338,12 +468,16 @@ where
         ss: &SoftState,
         replica_cache: &mut ReplicaCache<RS, MRS>,
         event_bcast: &mut EventChannel,
-    ) {
+    ) -> Result<(), Error> {
+        // the role is updated even if the leader is unknown, e.g. the leader
+        // steps down by check quorum.
+        self.shared_state.set_role(&ss.raft_state);
         if ss.leader_id != 0 && ss.leader_id != self.leader.replica_id {
             return self
                 .handle_leader_change(node_id, storage, ss, replica_cache, event_bcast)
                 .await;
         }
+        Ok(())
     }
 
     // Process soft state changed on leader changed
@@ -359,7 +493,7 @@ where
         ss: &SoftState,
         replica_cache: &mut ReplicaCache<RS, MRS>,
         event_bcast: &mut EventChannel,
-    ) {
+    ) -> Result<(), Error> {
         let group_id = self.group_id;
 
         // cache leader replica desc
@@ -371,7 +505,7 @@ where
                 // TODO: handle storage error kind, such as try again
                 error!("node {}: group {} replica{} become leader, but got it replica description for node id error {}",
                     node_id, group_id, ss.leader_id, err);
-                return;
+                return Ok(());
             }
             Ok(op) => match op {
                 Some(desc) => desc,
@@ -389,25 +523,18 @@ where
                         group_id,
                         node_id: NO_NODE,
                         replica_id: ss.leader_id,
+                        witness: false,
                     }
                 }
             },
         };
 
-        // update group storage metadata to save current leader id
-        let mut gs_meta = storage
-            .get_group_metadata(group_id, self.replica_id)
-            .await
-            .unwrap() // TODO: handle error
-            .expect("why missing group_storage metadata");
-        if gs_meta.leader_id != ss.leader_id {
-            gs_meta.leader_id = ss.leader_id;
-            storage.set_group_metadata(gs_meta).await.unwrap(); // TODO handle error
-        }
-
         // update shared states
         self.shared_state.set_leader_id(ss.leader_id);
+        self.shared_state.set_leader_node_id(replica_desc.node_id);
         self.shared_state.set_role(&ss.raft_state);
+        self.shared_state
+            .set_pending_conf_change(self.raft_group.raft.has_pending_conf());
         let replica_id = replica_desc.replica_id;
         self.leader = replica_desc; // always set because node_id maybe NO_NODE.
         info!(
@@ -419,7 +546,27 @@ where
             group_id: self.group_id,
             leader_id: ss.leader_id,
             replica_id,
+            term: self.raft_group.raft.term,
         }));
+
+        // update group storage metadata to save current leader id, the error
+        // is handled by the caller as the storage error of the ready.
+        match storage
+            .get_group_metadata(group_id, self.replica_id)
+            .await?
+        {
+            None => warn!(
+                "node {}: group {} replica {} save leader {}, but the group metadata is missing",
+                node_id, group_id, self.replica_id, ss.leader_id
+            ),
+            Some(mut gs_meta) => {
+                if gs_meta.leader_id != ss.leader_id {
+                    gs_meta.leader_id = ss.leader_id;
+                    storage.set_group_metadata(gs_meta).await?;
+                }
+            }
+        }
+        Ok(())
     }
 
     #[tracing::instrument(
@@ -434,6 +581,7 @@ where
         write: &mut RaftGroupWriteRequest,
         gs: &RS, // TODO: cache storage in RaftGroup
         transport: &TR,
+        failure_reporter: &SendFailureReporter,
         replica_cache: &mut ReplicaCache<RS, MRS>,
         node_manager: &mut NodeManager,
     ) -> Result<Option<ApplyData<RES>>, super::storage::Error> {
@@ -443,12 +591,12 @@ where
             let snapshot = ready.snapshot().clone();
             debug!("node {}: install snapshot {:?}", node_id, snapshot);
             // FIXME: call add voters to track node, node mgr etc.
-            // TODO: consider move install_snapshot to async queues.
-            gs.install_snapshot(snapshot)?;
+            gs.install_snapshot(snapshot).await?;
+            self.log_usage.reset();
         }
 
-        if !ready.entries().is_empty() {
-            let entries = ready.take_entries();
+        let entries = ready.take_entries();
+        if !entries.is_empty() {
             debug!(
                 "node {}: append entries [{}, {}]",
                 node_id,
@@ -458,20 +606,77 @@ where
 
             // If append fails due to temporary storage unavailability,
             // we will try again later.
-            gs.append(&entries)?;
+            gs.append(&entries).await?;
         }
         if let Some(hs) = ready.hs() {
-            gs.set_hardstate(hs.clone())?
+            gs.set_hardstate(hs.clone()).await?;
+        }
+
+        write.ready = Some(ready);
+        self.handle_persisted(
+            node_id,
+            write,
+            appended_entries(&entries),
+            gs,
+            transport,
+            failure_reporter,
+            replica_cache,
+            node_manager,
+        )
+        .await
+    }
+
+    /// Takes the entries and hard state of the ready into a `GroupWrite` which
+    /// is persisted by `MultiRaftStorage::write_batch`, the ready is kept in
+    /// the request for `handle_persisted`. Returns `None` if the ready has a
+    /// snapshot to install.
+    pub(crate) fn take_group_write(&self, write: &mut RaftGroupWriteRequest) -> Option<GroupWrite> {
+        let ready = write.ready.as_mut()?;
+        if *ready.snapshot() != Snapshot::default() {
+            return None;
+        }
+
+        Some(GroupWrite {
+            group_id: self.group_id,
+            replica_id: write.replica_id,
+            entries: ready.take_entries(),
+            hard_state: ready.hs().cloned(),
+        })
+    }
+
+    /// Advance the ready of the request whose entries and hard state are
+    /// persisted, `appended` is the last index and the size of the entries
+    /// appended.
+    pub(crate) async fn handle_persisted<TR: transport::Transport, MRS: MultiRaftStorage<RS>>(
+        &mut self,
+        node_id: u64,
+        write: &mut RaftGroupWriteRequest,
+        appended: Option<(u64, u64)>,
+        gs: &RS,
+        transport: &TR,
+        failure_reporter: &SendFailureReporter,
+        replica_cache: &mut ReplicaCache<RS, MRS>,
+        node_manager: &mut NodeManager,
+    ) -> Result<Option<ApplyData<RES>>, super::storage::Error> {
+        let group_id = self.group_id;
+        let mut ready = write.ready.take().unwrap();
+        if let Some((last_index, bytes)) = appended {
+            self.log_usage.bytes += bytes;
+            self.proposals.mark_persisted(last_index);
+        }
+        if let Some(hs) = ready.hs() {
+            self.shared_state.set_term(hs.term);
         }
 
         if !ready.persisted_messages().is_empty() {
             transport::send_messages(
                 node_id,
                 transport,
+                failure_reporter,
                 replica_cache,
                 node_manager,
                 group_id,
-                ready.take_persisted_messages(),
+                self.hold_snapshots(ready.take_persisted_messages()),
             )
             .await;
         }
@@ -481,15 +686,16 @@ where
         if let Some(commit) = light_ready.commit_index() {
             debug!("node {}: set commit = {}", node_id, commit);
             self.commit_index = commit;
-            gs.set_hardstate_commit(commit)?;
+            gs.set_hardstate_commit(commit).await?;
             self.shared_state.set_commit_index(commit);
         }
 
         if !light_ready.messages().is_empty() {
-            let messages = light_ready.take_messages();
+            let messages = self.hold_snapshots(light_ready.take_messages());
             transport::send_messages(
                 node_id,
                 transport,
+                failure_reporter,
                 replica_cache,
                 node_manager,
                 group_id,
@@ -522,11 +728,11 @@ where
 
         // TODO: let forward_to_leader as configurable
         if !self.is_leader() {
-            return Err(Error::Propose(ProposeError::NotLeader {
-                node_id: self.node_id,
-                group_id: self.group_id,
-                replica_id: self.replica_id,
-            }));
+            return Err(Error::Propose(ProposeError::not_leader(
+                self.node_id,
+                self.group_id,
+                &self.shared_state,
+            )));
         }
 
         if write_data.term != 0 && self.term() > write_data.term {
@@ -536,69 +742,241 @@ where
             )));
         }
 
+        if self.shared_state.is_log_quota_exceeded() {
+            return Err(Error::Propose(ProposeError::LogQuotaExceeded {
+                node_id: self.node_id,
+                group_id: self.group_id,
+            }));
+        }
+
+        if self.is_proposals_full() {
+            return Err(Error::Propose(ProposeError::TooManyInflightProposals {
+                node_id: self.node_id,
+                group_id: self.group_id,
+                limit: self.proposals.max_inflight(),
+            }));
+        }
+
         Ok(())
     }
 
+    /// Returns true if the uncommitted proposals of the group reach
+    /// `Config::max_inflight_proposals`.
+    #[inline]
+    pub(crate) fn is_proposals_full(&self) -> bool {
+        self.proposals
+            .is_full(self.raft_group.raft.raft_log.committed)
+    }
+
+    #[tracing::instrument(
+        level = Level::TRACE,
+        name = "RaftGroup::propose_write",
+        skip_all,
+        fields(node_id=self.node_id, group_id=self.group_id, request_id=%write_request.request_id)
+    )]
     pub fn propose_write<WD: ProposeData>(
         &mut self,
         write_request: WriteRequest<WD, RES>,
+        proposer: Option<Proposer>,
+        compression: (EntryCompression, usize),
+        budget: &mut MemoryBudget,
     ) -> Option<ResponseCallback> {
+        let request_id = write_request.request_id;
         if let Err(err) = self.pre_propose_write(&write_request) {
             return Some(ResponseCallbackQueue::new_error_callback(
                 write_request.tx,
-                err,
+                err.with_request_id(request_id),
             ));
         }
 
         let term = self.term();
-        let data = match flexbuffer_serialize(&write_request.data) {
+        let data = match encode_propose_data(&write_request.data) {
             Err(err) => {
                 return Some(ResponseCallbackQueue::new_error_callback(
                     write_request.tx,
-                    err,
+                    err.with_request_id(request_id),
                 ));
             }
-            Ok(mut ser) => ser.take_buffer(),
+            Ok(data) => data,
         };
 
+        // the write queued behind the uncommitted identical write joins it
+        // instead of appending a new entry.
+        let waiter = CoalescedWaiter {
+            request_id,
+            tx: write_request.tx,
+            commit_tx: write_request.commit_tx,
+        };
+        let waiter = match write_request.coalescing_key.as_deref() {
+            Some(key) => {
+                let committed = self.raft_group.raft.raft_log.committed;
+                match self
+                    .write_coalescer
+                    .join(key, &data, term, committed, waiter)
+                {
+                    Ok(()) => return None,
+                    Err(waiter) => waiter,
+                }
+            }
+            None => waiter,
+        };
+        let coalesced = write_request.coalescing_key.map(|key| (key, data.clone()));
+        let (compression, threshold) = compression;
+        let data = compression.compress(data, threshold);
+
+        // reserve memory budget for the entry until it is applied
+        let context = write_request.context.map_or(vec![], |ctx_data| ctx_data);
+        let (context, local_context) = if write_request.local_context && !context.is_empty() {
+            (vec![], Some(context))
+        } else {
+            (context, None)
+        };
+        let context = match proposer {
+            Some(proposer) => proposer.wrap(context),
+            None => context,
+        };
+        let bytes = data.len() + context.len() + local_context.as_ref().map_or(0, Vec::len);
+        if !budget.acquire(self.group_id, bytes) {
+            let (allotted, used) = budget.allocation(self.group_id).unwrap_or((0, 0));
+            return Some(ResponseCallbackQueue::new_error_callback(
+                waiter.tx,
+                Error::Propose(ProposeError::MemoryBudgetExhausted {
+                    node_id: self.node_id,
+                    group_id: self.group_id,
+                    allotted,
+                    used,
+                })
+                .with_request_id(request_id),
+            ));
+        }
+
         // propose to raft group
         let next_index = self.last_index() + 1;
-        if let Err(err) = self.raft_group.propose(
-            write_request.context.map_or(vec![], |ctx_data| ctx_data),
-            data,
-        ) {
+        if let Err(err) = self.raft_group.propose(context, data) {
+            budget.release(self.group_id, bytes);
             return Some(ResponseCallbackQueue::new_error_callback(
-                write_request.tx,
-                Error::Raft(err),
+                waiter.tx,
+                Error::Raft(err).with_request_id(request_id),
             ));
         }
 
         let index = self.last_index() + 1;
         if next_index == index {
+            budget.release(self.group_id, bytes);
             return Some(ResponseCallbackQueue::new_error_callback(
-                write_request.tx,
+                waiter.tx,
                 Error::Propose(ProposeError::UnexpectedIndex {
                     node_id: self.node_id,
                     group_id: self.group_id,
                     replica_id: self.replica_id,
                     expected: next_index,
                     unexpected: index - 1,
-                }),
+                })
+                .with_request_id(request_id),
             ));
         }
 
+        // the result of the coalesced proposal is fanned out to the writes joined.
+        let (tx, commit_tx) = match coalesced {
+            Some((key, data)) => {
+                let (tx, commit_tx) = self
+                    .write_coalescer
+                    .track(key, data, next_index, term, waiter);
+                (tx, Some(commit_tx))
+            }
+            None => (waiter.tx, waiter.commit_tx),
+        };
         let proposal = Proposal {
             index: next_index,
             term,
             is_conf_change: false,
-            tx: Some(write_request.tx),
+            tx: Some(tx),
+            commit_tx,
+            context: local_context,
+            timeline: write_request.enqueued_at.map(ProposalTimeline::new),
         };
 
+        budget.track(self.group_id, next_index, bytes);
         self.proposals.push(proposal);
         None
     }
 
-    pub fn read_index_propose(&mut self, data: ReadIndexData) -> Option<ResponseCallback> {
+    #[tracing::instrument(
+        level = Level::TRACE,
+        name = "RaftGroup::read_index_propose",
+        skip_all,
+        fields(node_id=self.node_id, group_id=self.group_id, request_id=%Uuid::from_bytes(data.context.uuid))
+    )]
+    pub fn read_index_propose(
+        &mut self,
+        data: ReadIndexData,
+        metrics: &ReadMetrics,
+        id_generator: &dyn IdGenerator,
+    ) -> Option<ResponseCallback> {
+        // the follower without the leader drops the read index request.
+        if data.consistency == ConsistencyLevel::Follower
+            && self.raft_group.raft.leader_id == raft::INVALID_ID
+        {
+            return Some(ResponseCallbackQueue::new_error_callback(
+                data.tx,
+                Error::Propose(ProposeError::not_leader(
+                    self.node_id,
+                    self.group_id,
+                    &self.shared_state,
+                )),
+            ));
+        }
+
+        let local = self.can_read_locally(data.consistency);
+        metrics.record(data.consistency, !local);
+        let timeline = data.enqueued_at.map(ReadTimeline::new);
+        if local {
+            // renew the lease in the background before it expires, so the hot
+            // reads keep being served locally.
+            if data.consistency == ConsistencyLevel::Lease
+                && self.lease.should_renew(self.term(), self.lease.now())
+            {
+                self.renew_lease(id_generator.next_uuid());
+            }
+            // the local read is served at the commit index without confirming.
+            if let Some(mut timeline) = timeline {
+                timeline.confirmed(self.raft_group.raft.raft_log.committed, Instant::now());
+                self.track_read(timeline);
+            }
+            return Some(ResponseCallbackQueue::new_callback(
+                data.tx,
+                Ok(data.context.context),
+            ));
+        }
+
+        if self.read_index_queue.is_full() {
+            return Some(ResponseCallbackQueue::new_error_callback(
+                data.tx,
+                Error::Propose(ProposeError::ReadQueueFull {
+                    node_id: self.node_id,
+                    group_id: self.group_id,
+                    len: self.read_index_queue.max_len(),
+                }),
+            ));
+        }
+
+        let wait_applied = data.consistency == ConsistencyLevel::Follower;
+        // the batched read keeps the context locally, the round is issued by
+        // `flush_read_batch` on the tick.
+        if self.read_index_queue.is_batching() {
+            self.read_index_queue.push_batch(ReadIndexProposal {
+                uuid: Uuid::from_bytes(data.context.uuid),
+                read_index: None,
+                context: Some(data.context),
+                tx: Some(data.tx),
+                timeline,
+                issued_at: self.lease.now(),
+                wait_applied,
+                batched: vec![],
+            });
+            return None;
+        }
+
         let mut flexs = flexbuffer_serialize(&data.context).expect("invalid ReadIndexContext type");
         self.raft_group.read_index(flexs.take_buffer());
 
@@ -607,11 +985,83 @@ where
             read_index: None,
             context: None,
             tx: Some(data.tx),
+            timeline,
+            issued_at: self.lease.now(),
+            wait_applied,
+            batched: vec![],
         };
         self.read_index_queue.push_back(proposal);
         None
     }
 
+    /// Issue a read index round for the reads batched since the last tick,
+    /// returns true if a round is issued, see `Config::batch_read_index`.
+    pub(crate) fn flush_read_batch(&mut self) -> bool {
+        let mut head = match self.read_index_queue.take_batch() {
+            None => return false,
+            Some(head) => head,
+        };
+        let context = ReadIndexContext {
+            uuid: *head.uuid.as_bytes(),
+            context: None,
+        };
+        let mut flexs = flexbuffer_serialize(&context).expect("invalid ReadIndexContext type");
+        self.raft_group.read_index(flexs.take_buffer());
+        // the round is confirmed from the time it's issued.
+        head.issued_at = self.lease.now();
+        self.read_index_queue.push_back(head);
+        true
+    }
+
+    /// Issue the read index without a reader to renew the leader lease.
+    fn renew_lease(&mut self, uuid: Uuid) {
+        let context = ReadIndexContext {
+            uuid: *uuid.as_bytes(),
+            context: None,
+        };
+        let mut flexs = flexbuffer_serialize(&context).expect("invalid ReadIndexContext type");
+        self.raft_group.read_index(flexs.take_buffer());
+        self.read_index_queue.push_back(ReadIndexProposal {
+            uuid: Uuid::from_bytes(context.uuid),
+            read_index: None,
+            context: None,
+            tx: None,
+            timeline: None,
+            issued_at: self.lease.now(),
+            wait_applied: false,
+            batched: vec![],
+        });
+    }
+
+    /// Returns true if the read of the consistency level can be served by the
+    /// replica without confirming with a quorum.
+    fn can_read_locally(&self, consistency: ConsistencyLevel) -> bool {
+        let raft = &self.raft_group.raft;
+        match consistency {
+            ConsistencyLevel::Linearizable | ConsistencyLevel::Follower => false,
+            // the leader transferring the leadership loses the lease, since
+            // the transferee campaigns regardless of the lease.
+            ConsistencyLevel::Lease => {
+                self.is_leader()
+                    && raft.commit_to_current_term()
+                    && raft.lead_transferee.is_none()
+                    && self.lease.is_valid(raft.term, self.lease.now())
+            }
+            // the leader holds the lease if it steps down after losing the
+            // quorum, and it knows the latest commit after committed an entry
+            // in its term.
+            _ if self.is_leader() => raft.check_quorum && raft.commit_to_current_term(),
+            ConsistencyLevel::LeaderLocal => false,
+            ConsistencyLevel::BoundedStaleness(bound) => {
+                raft.leader_id != raft::INVALID_ID
+                    && self
+                        .shared_state
+                        .last_contact(raft.leader_id)
+                        .map_or(false, |elapsed| elapsed <= bound)
+            }
+        }
+    }
+
     fn pre_propose_membership(&mut self, request: &MembershipRequest<RES>) -> Result<(), Error> {
         if self.raft_group.raft.has_pending_conf() {
             return Err(Error::Propose(
@@ -626,11 +1076,11 @@ where
         }
 
         if !self.is_leader() {
-            return Err(Error::Propose(ProposeError::NotLeader {
-                node_id: self.node_id,
-                group_id: self.group_id,
-                replica_id: self.replica_id,
-            }));
+            return Err(Error::Propose(ProposeError::not_leader(
+                self.node_id,
+                self.group_id,
+                &self.shared_state,
+            )));
         }
 
         if !request.term.is_none() && self.term() > request.term.unwrap() {
@@ -643,13 +1093,110 @@ where
         Ok(())
     }
 
+    #[tracing::instrument(
+        level = Level::TRACE,
+        name = "RaftGroup::propose_admin",
+        skip_all,
+        fields(node_id=self.node_id, group_id=self.group_id, request_id=%request.request_id)
+    )]
+    pub fn propose_admin(
+        &mut self,
+        request: AdminRequest<RES>,
+        proposer: Option<Proposer>,
+    ) -> Option<ResponseCallback> {
+        let request_id = request.request_id;
+        if !self.is_leader() {
+            return Some(ResponseCallbackQueue::new_error_callback(
+                request.tx,
+                Error::Propose(ProposeError::not_leader(
+                    self.node_id,
+                    self.group_id,
+                    &self.shared_state,
+                ))
+                .with_request_id(request_id),
+            ));
+        }
+
+        let data = match request.payload.encode() {
+            Err(err) => {
+                return Some(ResponseCallbackQueue::new_error_callback(
+                    request.tx,
+                    err.with_request_id(request_id),
+                ))
+            }
+            Ok(data) => data,
+        };
+        let context = proposer.map_or(vec![], |proposer| proposer.wrap(vec![]));
+        let term = self.term();
+        let next_index = self.last_index() + 1;
+        if let Err(err) = self.raft_group.propose(context, data) {
+            return Some(ResponseCallbackQueue::new_error_callback(
+                request.tx,
+                Error::Raft(err).with_request_id(request_id),
+            ));
+        }
+
+        let index = self.last_index() + 1;
+        if next_index == index {
+            return Some(ResponseCallbackQueue::new_error_callback(
+                request.tx,
+                Error::Propose(ProposeError::UnexpectedIndex {
+                    node_id: self.node_id,
+                    group_id: self.group_id,
+                    replica_id: self.replica_id,
+                    expected: next_index,
+                    unexpected: index - 1,
+                })
+                .with_request_id(request_id),
+            ));
+        }
+
+        self.proposals.push(Proposal {
+            index: next_index,
+            term,
+            is_conf_change: false,
+            tx: Some(request.tx),
+            commit_tx: None,
+            context: None,
+            timeline: None,
+        });
+        None
+    }
+
+    /// Propose the membership change without the proposal tracked, which
+    /// is forwarded to the leader by raft if the replica is a follower. The
+    /// change is dropped silently by the leader if another one is pending.
+    pub(crate) fn forward_membership_change(
+        &mut self,
+        data: MembershipChangeData,
+    ) -> Result<(), Error> {
+        let res = if data.changes.len() == 1 {
+            let (ctx, cc) = to_cc(data, None);
+            self.raft_group.propose_conf_change(ctx, cc)
+        } else {
+            let (ctx, cc) = to_ccv2(data, None);
+            self.raft_group.propose_conf_change(ctx, cc)
+        };
+        res.map_err(Error::Raft)
+    }
+
+    #[tracing::instrument(
+        level = Level::TRACE,
+        name = "RaftGroup::propose_membership_change",
+        skip_all,
+        fields(node_id=self.node_id, group_id=self.group_id, request_id=%request.request_id)
+    )]
     pub fn propose_membership_change(
         &mut self,
         request: MembershipRequest<RES>,
     ) -> Option<ResponseCallback> {
+        let request_id = request.request_id;
         // TODO: add pre propose check
         if let Err(err) = self.pre_propose_membership(&request) {
-            return Some(ResponseCallbackQueue::new_error_callback(request.tx, err));
+            return Some(ResponseCallbackQueue::new_error_callback(
+                request.tx,
+                err.with_request_id(request_id),
+            ));
         }
 
         let term = self.term();
@@ -667,12 +1214,12 @@ where
 
         if let Err(err) = res {
             error!(
-                "node {}: propose membership change error: error = {}",
-                0, /* TODO: add it*/ err
+                "node {}: propose membership change error: error = {}, request_id = {}",
+                0, /* TODO: add it*/ err, request_id
             );
             return Some(ResponseCallbackQueue::new_error_callback(
                 request.tx,
-                Error::Raft(err),
+                Error::Raft(err).with_request_id(request_id),
             ));
         }
 
@@ -693,7 +1240,8 @@ where
                     replica_id: self.replica_id,
                     expected: next_index,
                     unexpected: index - 1,
-                }),
+                })
+                .with_request_id(request_id),
             ));
         }
 
@@ -702,8 +1250,12 @@ where
             term,
             is_conf_change: true,
             tx: Some(request.tx),
+            commit_tx: None,
+            context: None,
+            timeline: None,
         };
 
+        self.shared_state.set_pending_conf_change(true);
         self.proposals.push(proposal);
         None
     }
@@ -721,12 +1273,55 @@ where
         }
     }
 
+    /// Abort the election started by the witness, the witness keeps following
+    /// without leader until it hears from the new leader. The pre-vote is
+    /// always enabled, so the term and vote are not changed by the election.
+    pub(crate) fn abort_witness_campaign(&mut self) {
+        let raft = &mut self.raft_group.raft;
+        let term = raft.term;
+        raft.become_follower(term, raft::INVALID_ID);
+        raft.msgs.clear();
+    }
+
+    /// Fail the pending proposals and read index requests with the error.
+    pub(crate) fn fail_pending_requests<F: Fn() -> Error>(&mut self, err: F) {
+        for proposal in self.proposals.drain(..) {
+            proposal.tx.map(|tx| tx.send(Err(err())));
+        }
+        self.fail_pending_reads(&err);
+        for read in self.applying_follower_reads.drain(..) {
+            read.tx.map(|tx| tx.send(Err(err())));
+        }
+    }
+
+    /// Fail the read index requests not confirmed yet, the replica never
+    /// confirms them after it lost the leadership.
+    pub(crate) fn fail_pending_reads<F: Fn() -> Error>(&mut self, err: F) {
+        for read in self.read_index_queue.drain_all() {
+            read.tx.map(|tx| tx.send(Err(err())));
+        }
+    }
+
     pub(crate) fn add_track_node(&mut self, node_id: u64) {
         if self.node_ids.iter().position(|id| *id == node_id).is_none() {
             self.node_ids.push(node_id)
         }
     }
 
+    /// Returns the reason if the message should be dropped before stepped into
+    /// raft, the messages without term, e.g. the forwarded proposals and read
+    /// indexes, are never stale by term.
+    pub(crate) fn check_stale_message(&self, msg: &Message, term_gap: u64) -> Option<StaleMessage> {
+        if self.removed_replicas.contains(&msg.from) {
+            return Some(StaleMessage::RemovedReplica);
+        }
+
+        if term_gap != 0 && msg.term != 0 && msg.term + term_gap < self.raft_group.raft.term {
+            return Some(StaleMessage::Term);
+        }
+        None
+    }
+
     /// Remove the node in the group where the replica is located in the tracing nodes.
     pub(crate) fn remove_track_node(&mut self, node_id: u64) {
         let len = self.node_ids.len();
@@ -739,11 +1334,73 @@ where
             });
     }
 
+    /// Returns true if the group has no in-flight work, so the raft group can be
+    /// dropped and recreated from storage later without losing any state.
+    pub(crate) fn can_park(&self) -> bool {
+        let raft_log = &self.raft_group.raft.raft_log;
+        matches!(self.status, Status::None)
+            && self.proposals.is_empty()
+            && self.read_index_queue.is_empty()
+            && self.applying_follower_reads.is_empty()
+            && !self.raft_group.has_ready()
+            && !self.raft_group.raft.has_pending_conf()
+            && raft_log.committed == raft_log.last_index()
+            && raft_log.applied == self.commit_index
+    }
+
+    /// Returns the most up-to-date voter except self to transfer the leadership.
+    pub(crate) fn transferee(&self) -> Option<u64> {
+        let raft = &self.raft_group.raft;
+        let voters = raft.prs().conf().voters();
+        raft.prs()
+            .iter()
+            .filter(|(id, _)| **id != self.replica_id && voters.contains(**id))
+            .max_by_key(|(_, pr)| pr.matched)
+            .map(|(id, _)| *id)
+    }
+
+    /// Release the unused memory of the entry cache and queues of the group, it
+    /// is called when the memory budget of the group is reclaimed.
+    pub(crate) fn shrink_caches(&mut self) {
+        self.raft_group
+            .raft
+            .raft_log
+            .unstable
+            .entries
+            .shrink_to_fit();
+        self.proposals.shrink_to_fit();
+        self.read_index_queue.shrink_to_fit();
+    }
+
     pub(crate) fn advance_apply(&mut self, result: &ApplyResultMessage) {
         // keep  invariant
         assert!(result.applied_index <= self.commit_index);
 
         self.raft_group.advance_apply_to(result.applied_index);
+        self.shared_state
+            .set_pending_conf_change(self.raft_group.raft.has_pending_conf());
+
+        // the read indexes are confirmed in order since the commit index of
+        // the replica never goes back.
+        let now = Instant::now();
+        while let Some(timeline) = self.applying_reads.front() {
+            if timeline.read_index > result.applied_index {
+                break;
+            }
+            self.latency.record_read(self.group_id, timeline, now);
+            self.applying_reads.pop_front();
+        }
+        while let Some(read) = self.applying_follower_reads.front() {
+            if read
+                .read_index
+                .map_or(false, |index| index > result.applied_index)
+            {
+                break;
+            }
+            let read = self.applying_follower_reads.pop_front().unwrap();
+            read.tx
+                .map(|tx| tx.send(Ok(read.context.and_then(|ctx| ctx.context))));
+        }
 
         // update local apply state
         // self.applied_index = result.applied_index;
@@ -755,6 +1412,13 @@ where
     }
 }
 
+/// The last index and the size of the entries appended.
+pub(crate) fn appended_entries(entries: &[Entry]) -> Option<(u64, u64)> {
+    entries
+        .last()
+        .map(|last| (last.index, utils::compute_entries_size(entries) as u64))
+}
+
 fn to_cc(data: MembershipChangeData, user_ctx: Option<Vec<u8>>) -> (Vec<u8>, ConfChange) {
     assert_eq!(data.changes.len(), 1);
     let mut cc = ConfChange::default();
@@ -0,0 +1,84 @@
+use std::fmt::Debug;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+
+use uuid::Builder;
+use uuid::Uuid;
+
+/// IdGenerator is the source of request ids and read index contexts.
+///
+/// The default generator is random, tests can inject a `SeededIdGenerator`
+/// through `Config` to produce identical ids between runs with the same seed.
+pub trait IdGenerator: Debug + Send + Sync + 'static {
+    fn next_u64(&self) -> u64;
+
+    /// Generate a version 4 uuid from the random source.
+    fn next_uuid(&self) -> Uuid {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.next_u64().to_le_bytes());
+        bytes[8..].copy_from_slice(&self.next_u64().to_le_bytes());
+        Builder::from_random_bytes(bytes).into_uuid()
+    }
+}
+
+/// Generates ids by the random source of operating system.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RandomIdGenerator;
+
+impl IdGenerator for RandomIdGenerator {
+    fn next_u64(&self) -> u64 {
+        let bytes = Uuid::new_v4().into_bytes();
+        u64::from_le_bytes(bytes[..8].try_into().unwrap())
+    }
+
+    fn next_uuid(&self) -> Uuid {
+        Uuid::new_v4()
+    }
+}
+
+/// Generates ids by the splitmix64 sequence of the seed, the same seed
+/// generates the same sequence of ids.
+#[derive(Debug)]
+pub struct SeededIdGenerator {
+    state: AtomicU64,
+}
+
+impl SeededIdGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            state: AtomicU64::new(seed),
+        }
+    }
+}
+
+impl IdGenerator for SeededIdGenerator {
+    fn next_u64(&self) -> u64 {
+        const GAMMA: u64 = 0x9e3779b97f4a7c15;
+        let mut z = self
+            .state
+            .fetch_add(GAMMA, Ordering::Relaxed)
+            .wrapping_add(GAMMA);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IdGenerator;
+    use super::SeededIdGenerator;
+
+    #[test]
+    fn test_seeded_id_generator() {
+        let gen1 = SeededIdGenerator::new(42);
+        let gen2 = SeededIdGenerator::new(42);
+        let ids1 = (0..10).map(|_| gen1.next_uuid()).collect::<Vec<_>>();
+        let ids2 = (0..10).map(|_| gen2.next_uuid()).collect::<Vec<_>>();
+        assert_eq!(ids1, ids2);
+        assert_eq!(ids1[0].get_version_num(), 4);
+
+        let gen3 = SeededIdGenerator::new(43);
+        assert_ne!(gen3.next_uuid(), ids1[0]);
+    }
+}
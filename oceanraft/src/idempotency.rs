@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+
+use crate::auth::Operation;
+use crate::prelude::IdempotencyRecord;
+use crate::state::GroupRemoval;
+use crate::Error;
+
+/// The outcome of the management operation which is returned to the retries
+/// of the request with the same idempotency key.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ManageOutcome {
+    CreateGroup,
+    RemoveGroup(GroupRemoval),
+}
+
+impl ManageOutcome {
+    fn operation(&self) -> Operation {
+        match self {
+            ManageOutcome::CreateGroup => Operation::CreateGroup,
+            ManageOutcome::RemoveGroup(_) => Operation::RemoveGroup,
+        }
+    }
+
+    fn from_record(record: &IdempotencyRecord) -> Self {
+        if !record.remove_group {
+            return ManageOutcome::CreateGroup;
+        }
+        ManageOutcome::RemoveGroup(GroupRemoval {
+            group_id: record.group_id,
+            replica_id: record.replica_id,
+            transferee: (record.transferee != 0).then_some(record.transferee),
+            transferred: record.transferred,
+        })
+    }
+
+    fn to_record(&self, key: &str, group_id: u64, expire_at: u64) -> IdempotencyRecord {
+        let mut record = IdempotencyRecord {
+            key: key.to_owned(),
+            group_id,
+            expire_at,
+            ..Default::default()
+        };
+        if let ManageOutcome::RemoveGroup(removal) = self {
+            record.remove_group = true;
+            record.replica_id = removal.replica_id;
+            record.transferee = removal.transferee.unwrap_or_default();
+            record.transferred = removal.transferred;
+        }
+        record
+    }
+}
+
+/// The unix timestamp in milliseconds, the expiry of the persisted outcomes
+/// is measured by the wall clock since the ticks start over after restarts.
+pub(crate) fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_millis() as u64)
+}
+
+/// IdempotencyKeys records the outcomes of the successful management requests
+/// by their idempotency keys within `window_ticks`, so a request retried after
+/// a timeout gets the original outcome instead of `Exists` or `NotExist`.
+///
+/// The failed requests are not recorded and the retries execute again. The
+/// outcomes are persisted by `MultiRaftStorage::set_idempotency_record` with
+/// the wall clock expiry and restored after the node restarts.
+pub(crate) struct IdempotencyKeys {
+    window_ticks: u64,
+    tick_interval_ms: u64,
+    now: u64,
+    /// (expire tick, group id, outcome) of the keys.
+    outcomes: HashMap<String, (u64, u64, ManageOutcome)>,
+    /// The expired keys whose records are not removed from the storage yet.
+    expired: Vec<String>,
+}
+
+impl IdempotencyKeys {
+    pub(crate) fn new(window_ticks: usize, tick_interval_ms: u64) -> Self {
+        Self {
+            window_ticks: window_ticks as u64,
+            tick_interval_ms: tick_interval_ms.max(1),
+            now: 0,
+            outcomes: HashMap::new(),
+            expired: vec![],
+        }
+    }
+
+    #[inline]
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.window_ticks != 0
+    }
+
+    /// Restore the persisted outcomes at `now_ms`, the remaining window of the
+    /// outcomes is converted to ticks. The expired records are returned to be
+    /// removed from the storage.
+    pub(crate) fn restore(&mut self, records: Vec<IdempotencyRecord>, now_ms: u64) -> Vec<String> {
+        let mut expired = vec![];
+        for record in records {
+            if record.expire_at <= now_ms {
+                expired.push(record.key);
+                continue;
+            }
+            let remaining =
+                (record.expire_at - now_ms + self.tick_interval_ms - 1) / self.tick_interval_ms;
+            let outcome = ManageOutcome::from_record(&record);
+            self.outcomes
+                .insert(record.key, (self.now + remaining, record.group_id, outcome));
+        }
+        expired
+    }
+
+    /// Returns the recorded outcome of the key. The key reused by a request
+    /// of different operation or group is rejected by `Error::BadParameter`.
+    pub(crate) fn get(
+        &self,
+        key: &str,
+        op: Operation,
+        group_id: u64,
+    ) -> Result<Option<ManageOutcome>, Error> {
+        let (_, recorded_group_id, outcome) = match self.outcomes.get(key) {
+            None => return Ok(None),
+            Some(record) => record,
+        };
+        if outcome.operation() != op || *recorded_group_id != group_id {
+            return Err(Error::BadParameter(format!(
+                "idempotency key {} is used by {:?} of group {}",
+                key,
+                outcome.operation(),
+                recorded_group_id
+            )));
+        }
+        Ok(Some(outcome.clone()))
+    }
+
+    /// Record the outcome of the key at `now_ms`, the empty key is ignored.
+    /// Returns the record to be persisted.
+    pub(crate) fn record(
+        &mut self,
+        key: &str,
+        group_id: u64,
+        outcome: ManageOutcome,
+        now_ms: u64,
+    ) -> Option<IdempotencyRecord> {
+        if key.is_empty() || self.window_ticks == 0 {
+            return None;
+        }
+        let expire_at = now_ms + self.window_ticks * self.tick_interval_ms;
+        let record = outcome.to_record(key, group_id, expire_at);
+        self.outcomes.insert(
+            key.to_owned(),
+            (self.now + self.window_ticks, group_id, outcome),
+        );
+        Some(record)
+    }
+
+    /// Advance the clock and forget the expired outcomes.
+    pub(crate) fn tick(&mut self) {
+        self.now += 1;
+        if self.outcomes.is_empty() {
+            return;
+        }
+
+        let now = self.now;
+        let expired = &mut self.expired;
+        self.outcomes.retain(|key, (expire, _, _)| {
+            if *expire > now {
+                return true;
+            }
+            expired.push(key.clone());
+            false
+        });
+    }
+
+    /// Take the expired keys whose records should be removed from the storage.
+    pub(crate) fn take_expired(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.expired)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::IdempotencyKeys;
+    use super::ManageOutcome;
+    use crate::auth::Operation;
+    use crate::state::GroupRemoval;
+
+    #[test]
+    fn test_idempotency_keys() {
+        let mut keys = IdempotencyKeys::new(2, 10);
+        assert_eq!(keys.record("", 1, ManageOutcome::CreateGroup, 0), None);
+        assert_eq!(keys.get("", Operation::CreateGroup, 1).unwrap(), None);
+
+        let record = keys
+            .record("create-1", 1, ManageOutcome::CreateGroup, 100)
+            .unwrap();
+        assert_eq!(record.expire_at, 120);
+        assert!(!record.remove_group);
+        let removal = GroupRemoval {
+            group_id: 2,
+            replica_id: 3,
+            ..Default::default()
+        };
+        let record = keys
+            .record(
+                "remove-2",
+                2,
+                ManageOutcome::RemoveGroup(removal.clone()),
+                100,
+            )
+            .unwrap();
+        assert!(record.remove_group);
+        assert_eq!(record.replica_id, 3);
+        assert_eq!(
+            keys.get("create-1", Operation::CreateGroup, 1).unwrap(),
+            Some(ManageOutcome::CreateGroup)
+        );
+        assert_eq!(
+            keys.get("remove-2", Operation::RemoveGroup, 2).unwrap(),
+            Some(ManageOutcome::RemoveGroup(removal))
+        );
+
+        // the key reused by other operation or group.
+        assert!(keys.get("create-1", Operation::RemoveGroup, 1).is_err());
+        assert!(keys.get("create-1", Operation::CreateGroup, 2).is_err());
+
+        keys.tick();
+        assert!(keys
+            .get("create-1", Operation::CreateGroup, 1)
+            .unwrap()
+            .is_some());
+        keys.tick();
+        assert_eq!(
+            keys.get("create-1", Operation::CreateGroup, 1).unwrap(),
+            None
+        );
+        assert_eq!(
+            keys.get("remove-2", Operation::RemoveGroup, 2).unwrap(),
+            None
+        );
+        let mut expired = keys.take_expired();
+        expired.sort();
+        assert_eq!(expired, vec!["create-1".to_owned(), "remove-2".to_owned()]);
+        assert!(keys.take_expired().is_empty());
+
+        // disabled by the zero window.
+        let mut keys = IdempotencyKeys::new(0, 10);
+        assert_eq!(
+            keys.record("create-1", 1, ManageOutcome::CreateGroup, 0),
+            None
+        );
+        assert_eq!(
+            keys.get("create-1", Operation::CreateGroup, 1).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_idempotency_keys_restore() {
+        let removal = GroupRemoval {
+            group_id: 2,
+            replica_id: 3,
+            transferee: Some(4),
+            transferred: true,
+        };
+        let mut keys = IdempotencyKeys::new(2, 10);
+        let records = vec![
+            keys.record("create-1", 1, ManageOutcome::CreateGroup, 100)
+                .unwrap(),
+            keys.record(
+                "remove-2",
+                2,
+                ManageOutcome::RemoveGroup(removal.clone()),
+                110,
+            )
+            .unwrap(),
+        ];
+
+        // the outcome of create-1 expired at 120 while the node was down.
+        let mut keys = IdempotencyKeys::new(2, 10);
+        assert_eq!(keys.restore(records, 125), vec!["create-1".to_owned()]);
+        assert_eq!(
+            keys.get("create-1", Operation::CreateGroup, 1).unwrap(),
+            None
+        );
+        assert_eq!(
+            keys.get("remove-2", Operation::RemoveGroup, 2).unwrap(),
+            Some(ManageOutcome::RemoveGroup(removal))
+        );
+
+        // the remaining 5ms of remove-2 is rounded up to a tick.
+        keys.tick();
+        assert_eq!(
+            keys.get("remove-2", Operation::RemoveGroup, 2).unwrap(),
+            None
+        );
+        assert_eq!(keys.take_expired(), vec!["remove-2".to_owned()]);
+    }
+}
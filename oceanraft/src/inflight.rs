@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The inflight limit of a peer is halved if the ratio of the messages
+/// failed to deliver to it exceeds the threshold in an interval.
+const LOSS_RATIO_THRESHOLD: f64 = 0.01;
+
+/// The delivery samples of a peer node in an interval, the rtt is smoothed
+/// across the intervals.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct PeerSamples {
+    pub(crate) rtt: Option<Duration>,
+    pub(crate) delivered: u64,
+    pub(crate) failed: u64,
+}
+
+/// DeliveryStats collects the delivery reports of the transport by peer node,
+/// see `SendFailureReporter::report_delivered`. It's shared by the reporter
+/// and the node which samples it every `Config::adaptive_inflight_interval_ticks`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DeliveryStats {
+    peers: Arc<Mutex<HashMap<u64, PeerSamples>>>,
+}
+
+impl DeliveryStats {
+    pub(crate) fn delivered(&self, node_id: u64, rtt: Duration) {
+        let mut peers = self.peers.lock().unwrap();
+        let samples = peers.entry(node_id).or_default();
+        samples.delivered += 1;
+        // the moving average weights the new sample by 1/8, same as tcp.
+        samples.rtt = Some(match samples.rtt {
+            None => rtt,
+            Some(srtt) => (srtt * 7 + rtt) / 8,
+        });
+    }
+
+    pub(crate) fn failed(&self, node_id: u64) {
+        self.peers
+            .lock()
+            .unwrap()
+            .entry(node_id)
+            .or_default()
+            .failed += 1;
+    }
+
+    /// Take the samples of the interval, the smoothed rtt is kept.
+    pub(crate) fn sample(&self) -> HashMap<u64, PeerSamples> {
+        let mut peers = self.peers.lock().unwrap();
+        let samples = peers.clone();
+        for samples in peers.values_mut() {
+            samples.delivered = 0;
+            samples.failed = 0;
+        }
+        samples
+    }
+}
+
+/// InflightTuner adapts the limit of the inflight appends to each peer node
+/// within `[min, max]`. The limit tracks the bandwidth delay product of the
+/// peer, `Config::max_inflight_msgs` at the base rtt grows in proportion to
+/// the rtt of the peer, e.g. the peers over WAN links. The growth at most
+/// doubles the limit in an interval, and the lossy peer halves the limit.
+pub(crate) struct InflightTuner {
+    initial: usize,
+    min: usize,
+    max: usize,
+    base_rtt: Duration,
+    caps: HashMap<u64, usize>,
+}
+
+impl InflightTuner {
+    pub(crate) fn new(initial: usize, min: usize, max: usize, base_rtt: Duration) -> Self {
+        Self {
+            initial,
+            min,
+            max: max.max(min),
+            base_rtt: base_rtt.max(Duration::from_micros(1)),
+            caps: HashMap::new(),
+        }
+    }
+
+    /// Adjust the limits by the samples of an interval, the peers without
+    /// any delivery in the interval keep the limits.
+    pub(crate) fn adjust(&mut self, samples: HashMap<u64, PeerSamples>) {
+        for (node_id, samples) in samples {
+            let total = samples.delivered + samples.failed;
+            if total == 0 {
+                continue;
+            }
+            let cap = self.cap(node_id);
+            let next = if samples.failed as f64 / total as f64 > LOSS_RATIO_THRESHOLD {
+                cap / 2
+            } else {
+                match samples.rtt {
+                    None => cap,
+                    Some(rtt) => {
+                        let scale = rtt.as_secs_f64() / self.base_rtt.as_secs_f64();
+                        let target = (self.initial as f64 * scale.max(1.0)) as usize;
+                        target.min(cap.saturating_mul(2))
+                    }
+                }
+            };
+            self.caps.insert(node_id, next.clamp(self.min, self.max));
+        }
+    }
+
+    /// The limit of the inflight appends to the peer node.
+    pub(crate) fn cap(&self, node_id: u64) -> usize {
+        self.caps
+            .get(&node_id)
+            .copied()
+            .unwrap_or_else(|| self.initial.clamp(self.min, self.max))
+    }
+
+    pub(crate) fn caps(&self) -> impl Iterator<Item = (u64, usize)> + '_ {
+        self.caps.iter().map(|(node_id, cap)| (*node_id, *cap))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::DeliveryStats;
+    use super::InflightTuner;
+
+    #[test]
+    fn test_inflight_tuner() {
+        let ms = Duration::from_millis;
+        let stats = DeliveryStats::default();
+        let mut tuner = InflightTuner::new(256, 16, 4096, ms(1));
+
+        // the limit grows by the rtt and at most doubles in an interval.
+        for _ in 0..10 {
+            stats.delivered(2, ms(50));
+        }
+        stats.delivered(3, ms(1));
+        tuner.adjust(stats.sample());
+        assert_eq!(tuner.cap(2), 512);
+        assert_eq!(tuner.cap(3), 256);
+        stats.delivered(2, ms(50));
+        tuner.adjust(stats.sample());
+        assert_eq!(tuner.cap(2), 1024);
+
+        // the peer without delivery keeps the limit, the lossy peer halves it.
+        tuner.adjust(stats.sample());
+        assert_eq!(tuner.cap(2), 1024);
+        stats.delivered(2, ms(50));
+        stats.failed(2);
+        tuner.adjust(stats.sample());
+        assert_eq!(tuner.cap(2), 512);
+
+        // the limit is bounded.
+        for _ in 0..10 {
+            stats.failed(3);
+            tuner.adjust(stats.sample());
+        }
+        assert_eq!(tuner.cap(3), 16);
+        assert_eq!(tuner.cap(4), 256);
+    }
+}
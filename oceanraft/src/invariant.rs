@@ -0,0 +1,235 @@
+//! The runtime checks of the invariants of the pipeline, enabled by the
+//! `debug-assertions` feature:
+//!
+//! - the applied index of a group is monotonic.
+//! - a proposal is resolved once, the resolved indexes of a group increase.
+//! - the membership transitions are legal, see `legal_conf_transition`.
+//! - the membership apply is handed to the state machine alone.
+//! - the raft messages are sent to the known replicas of other nodes.
+//!
+//! The violation panics in the debug builds, e.g. tests, and is logged in the
+//! release builds. The checks are skipped if the feature is disabled.
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use tracing::error;
+
+use crate::prelude::ConfState;
+use crate::prelude::ReplicaDesc;
+use crate::Apply;
+use crate::ProposeData;
+use crate::ProposeResponse;
+
+const ENABLED: bool = cfg!(feature = "debug-assertions");
+
+fn violated(node_id: u64, group_id: u64, reason: String) {
+    if cfg!(debug_assertions) {
+        panic!(
+            "node {}: group {} invariant violated: {}",
+            node_id, group_id, reason
+        );
+    }
+    error!(
+        "node {}: group {} invariant violated: {}",
+        node_id, group_id, reason
+    );
+}
+
+/// Tracks the state of the apply pipeline to check the invariants.
+pub(crate) struct ApplyInvariants {
+    node_id: u64,
+    /// The index of the last proposal resolved by group.
+    resolved: HashMap<u64, u64>,
+}
+
+impl ApplyInvariants {
+    pub(crate) fn new(node_id: u64) -> Self {
+        Self {
+            node_id,
+            resolved: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn check_applied(&self, group_id: u64, prev_applied: u64, applied: u64) {
+        if ENABLED && applied < prev_applied {
+            violated(
+                self.node_id,
+                group_id,
+                format!(
+                    "applied index jump backward {} -> {}",
+                    prev_applied, applied
+                ),
+            );
+        }
+    }
+
+    pub(crate) fn check_resolved(&mut self, group_id: u64, index: u64) {
+        if !ENABLED {
+            return;
+        }
+        let last = self.resolved.entry(group_id).or_insert(0);
+        if index <= *last {
+            violated(
+                self.node_id,
+                group_id,
+                format!(
+                    "proposal at {} resolved after the proposal at {}",
+                    index, *last
+                ),
+            );
+        }
+        *last = index;
+    }
+
+    /// The applys handed to the state machine in a call are fenced by the
+    /// membership apply, which is never applied with the others.
+    pub(crate) fn check_fenced<W: ProposeData, R: ProposeResponse>(
+        &self,
+        group_id: u64,
+        applys: &[Apply<W, R>],
+    ) {
+        if !ENABLED || applys.len() < 2 {
+            return;
+        }
+        if let Some(apply) = applys
+            .iter()
+            .find(|apply| matches!(apply, Apply::Membership(_)))
+        {
+            violated(
+                self.node_id,
+                group_id,
+                format!(
+                    "membership at {} applied with {} other applys",
+                    apply.get_index(),
+                    applys.len() - 1
+                ),
+            );
+        }
+    }
+
+    /// Forget the group removed, the group may be created again.
+    pub(crate) fn forget(&mut self, group_id: u64) {
+        self.resolved.remove(&group_id);
+    }
+}
+
+pub(crate) fn check_conf_transition(
+    node_id: u64,
+    group_id: u64,
+    prev: &ConfState,
+    next: &ConfState,
+) {
+    if !ENABLED {
+        return;
+    }
+    if let Err(reason) = legal_conf_transition(prev, next) {
+        violated(
+            node_id,
+            group_id,
+            format!("conf state {:?} -> {:?}: {}", prev, next, reason),
+        );
+    }
+}
+
+pub(crate) fn check_send(node_id: u64, group_id: u64, to: u64, to_replica: &ReplicaDesc) {
+    if !ENABLED {
+        return;
+    }
+    if to_replica.node_id == node_id {
+        violated(
+            node_id,
+            group_id,
+            format!("message to replica {} sent to the node itself", to),
+        );
+    }
+    if to_replica.replica_id != to {
+        violated(
+            node_id,
+            group_id,
+            format!(
+                "message to replica {} sent to replica {}",
+                to, to_replica.replica_id
+            ),
+        );
+    }
+}
+
+/// Returns the reason if the transition of the membership is illegal:
+/// - the voters are not empty.
+/// - the learners are not voters in either configuration of joint consensus.
+/// - the learners next are the outgoing voters.
+/// - the simple change out of joint consensus changes one voter at most.
+/// - the joint consensus is entered from the voters of the previous.
+pub(crate) fn legal_conf_transition(prev: &ConfState, next: &ConfState) -> Result<(), String> {
+    if next.voters.is_empty() {
+        return Err("the voters are empty".to_owned());
+    }
+
+    let voters = next.voters.iter().collect::<HashSet<_>>();
+    let outgoing = next.voters_outgoing.iter().collect::<HashSet<_>>();
+    if let Some(learner) = next
+        .learners
+        .iter()
+        .find(|id| voters.contains(id) || outgoing.contains(id))
+    {
+        return Err(format!("learner {} is a voter", learner));
+    }
+    if let Some(learner) = next.learners_next.iter().find(|id| !outgoing.contains(id)) {
+        return Err(format!("learner next {} is not an outgoing voter", learner));
+    }
+
+    let prev_voters = prev.voters.iter().collect::<HashSet<_>>();
+    let prev_joint = !prev.voters_outgoing.is_empty();
+    // the group without any voter is initialized by the first conf state.
+    if prev_joint || prev_voters.is_empty() {
+        return Ok(());
+    }
+    if outgoing.is_empty() {
+        let changed = voters.symmetric_difference(&prev_voters).count();
+        if changed > 1 {
+            return Err(format!("{} voters changed by simple change", changed));
+        }
+    } else if outgoing != prev_voters {
+        return Err("the outgoing voters are not the previous voters".to_owned());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::legal_conf_transition;
+    use crate::prelude::ConfState;
+
+    fn conf_state(voters: Vec<u64>, outgoing: Vec<u64>, learners: Vec<u64>) -> ConfState {
+        ConfState {
+            voters,
+            voters_outgoing: outgoing,
+            learners,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_legal_conf_transition() {
+        let prev = conf_state(vec![1, 2, 3], vec![], vec![]);
+        // simple change of one voter or learner.
+        assert!(
+            legal_conf_transition(&prev, &conf_state(vec![1, 2, 3, 4], vec![], vec![])).is_ok()
+        );
+        assert!(legal_conf_transition(&prev, &conf_state(vec![1, 2, 3], vec![], vec![4])).is_ok());
+        // enter and leave joint consensus.
+        let joint = conf_state(vec![1, 4, 5], vec![1, 2, 3], vec![]);
+        assert!(legal_conf_transition(&prev, &joint).is_ok());
+        assert!(legal_conf_transition(&joint, &conf_state(vec![1, 4, 5], vec![], vec![])).is_ok());
+        // the group is initialized.
+        assert!(legal_conf_transition(&ConfState::default(), &prev).is_ok());
+
+        assert!(legal_conf_transition(&prev, &conf_state(vec![], vec![], vec![])).is_err());
+        assert!(legal_conf_transition(&prev, &conf_state(vec![1, 4, 5], vec![], vec![])).is_err());
+        assert!(legal_conf_transition(&prev, &conf_state(vec![1, 2, 3], vec![], vec![3])).is_err());
+        assert!(legal_conf_transition(&prev, &conf_state(vec![1, 4], vec![1, 2], vec![])).is_err());
+        let mut learners_next = joint.clone();
+        learners_next.learners_next = vec![4];
+        assert!(legal_conf_transition(&prev, &learners_next).is_err());
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+#[cfg(feature = "metrics")]
+type Histogram = hdrhistogram::Histogram<u64>;
+
+/// The highest latency tracked by the histograms, the higher latencies are
+/// recorded as it.
+const MAX_LATENCY_MICROS: u64 = 60 * 1000 * 1000;
+
+/// The timestamps of a write proposal through the stages on the proposer,
+/// they are taken only if `Config::record_latency` is enabled.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ProposalTimeline {
+    pub(crate) enqueued_at: Instant,
+    pub(crate) proposed_at: Instant,
+    pub(crate) persisted_at: Option<Instant>,
+    pub(crate) committed_at: Option<Instant>,
+}
+
+impl ProposalTimeline {
+    pub(crate) fn new(enqueued_at: Instant) -> Self {
+        Self {
+            enqueued_at,
+            proposed_at: Instant::now(),
+            persisted_at: None,
+            committed_at: None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn persisted(&mut self, now: Instant) {
+        self.persisted_at.get_or_insert(now);
+    }
+
+    /// The entry may be committed by the quorum of followers before it is
+    /// persisted locally, then the persist stage is counted as zero.
+    #[inline]
+    pub(crate) fn committed(&mut self, now: Instant) {
+        self.persisted_at.get_or_insert(now);
+        self.committed_at.get_or_insert(now);
+    }
+}
+
+/// The timestamps of a read through the stages on the node, they are taken
+/// only if `Config::record_latency` is enabled.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReadTimeline {
+    pub(crate) enqueued_at: Instant,
+    pub(crate) proposed_at: Instant,
+    pub(crate) confirmed_at: Option<Instant>,
+    /// The index confirmed by the quorum, the state machine reflects the
+    /// read after the index is applied.
+    pub(crate) read_index: u64,
+}
+
+impl ReadTimeline {
+    pub(crate) fn new(enqueued_at: Instant) -> Self {
+        Self {
+            enqueued_at,
+            proposed_at: Instant::now(),
+            confirmed_at: None,
+            read_index: 0,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn confirmed(&mut self, read_index: u64, now: Instant) {
+        self.read_index = read_index;
+        self.confirmed_at.get_or_insert(now);
+    }
+}
+
+/// The latency distribution of a stage, the percentiles are accurate to 3
+/// significant figures with the `metrics` feature, they are the max without
+/// it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StageLatency {
+    pub count: u64,
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+}
+
+/// The latencies of the writes of a group proposed on the node, by stage:
+///
+/// - `queue`: enqueued by `MultiRaft::write` until proposed to raft.
+/// - `persist`: proposed until the entry is persisted to the local storage.
+/// - `commit`: persisted until the entry is committed.
+/// - `apply`: committed until the entry is applied by the state machine.
+/// - `total`: enqueued until applied.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GroupLatency {
+    pub queue: StageLatency,
+    pub persist: StageLatency,
+    pub commit: StageLatency,
+    pub apply: StageLatency,
+    pub total: StageLatency,
+}
+
+/// The latencies of the reads of a group served by the node, by stage:
+///
+/// - `queue`: enqueued by `MultiRaft::read` until proposed to raft.
+/// - `confirm`: proposed until the read index is confirmed by the quorum, it
+///   is zero for the reads served locally, see `ConsistencyLevel`.
+/// - `apply`: confirmed until the read index is applied by the state machine.
+/// - `total`: enqueued until applied.
+///
+/// The slow reads waiting on `confirm` come from the round trips to the
+/// quorum, the ones waiting on `apply` come from the lag of the state machine.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReadLatency {
+    pub queue: StageLatency,
+    pub confirm: StageLatency,
+    pub apply: StageLatency,
+    pub total: StageLatency,
+}
+
+/// The latencies of writes proposed and reads served on the node by group,
+/// see `MultiRaft::latency_report`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LatencyReport {
+    pub groups: HashMap<u64, GroupLatency>,
+    pub reads: HashMap<u64, ReadLatency>,
+}
+
+struct GroupHistograms {
+    queue: Histogram,
+    persist: Histogram,
+    commit: Histogram,
+    apply: Histogram,
+    total: Histogram,
+}
+
+#[cfg(feature = "metrics")]
+fn new_histogram() -> Histogram {
+    Histogram::new_with_bounds(1, MAX_LATENCY_MICROS, 3).expect("valid histogram bounds")
+}
+
+/// The summary of the latencies without the `metrics` feature, the
+/// percentiles are not tracked and reported as the max.
+#[cfg(not(feature = "metrics"))]
+#[derive(Default)]
+struct Histogram {
+    count: u64,
+    min: u64,
+    max: u64,
+    sum: u64,
+}
+
+#[cfg(not(feature = "metrics"))]
+impl Histogram {
+    fn saturating_record(&mut self, value: u64) {
+        let value = value.min(MAX_LATENCY_MICROS);
+        self.min = if self.count == 0 {
+            value
+        } else {
+            self.min.min(value)
+        };
+        self.max = self.max.max(value);
+        self.sum = self.sum.saturating_add(value);
+        self.count += 1;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    fn len(&self) -> u64 {
+        self.count
+    }
+
+    fn min(&self) -> u64 {
+        self.min
+    }
+
+    fn max(&self) -> u64 {
+        self.max
+    }
+
+    fn mean(&self) -> f64 {
+        self.sum as f64 / self.count as f64
+    }
+
+    fn value_at_quantile(&self, _: f64) -> u64 {
+        self.max
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+fn new_histogram() -> Histogram {
+    Histogram::default()
+}
+
+fn record_stage(histogram: &mut Histogram, from: Instant, to: Instant) {
+    let micros = to.saturating_duration_since(from).as_micros() as u64;
+    histogram.saturating_record(micros);
+}
+
+fn stage_latency(histogram: &Histogram) -> StageLatency {
+    if histogram.is_empty() {
+        return StageLatency::default();
+    }
+
+    let micros = Duration::from_micros;
+    StageLatency {
+        count: histogram.len(),
+        min: micros(histogram.min()),
+        mean: micros(histogram.mean() as u64),
+        p50: micros(histogram.value_at_quantile(0.5)),
+        p90: micros(histogram.value_at_quantile(0.9)),
+        p99: micros(histogram.value_at_quantile(0.99)),
+        p999: micros(histogram.value_at_quantile(0.999)),
+        max: micros(histogram.max()),
+    }
+}
+
+impl GroupHistograms {
+    fn new() -> Self {
+        Self {
+            queue: new_histogram(),
+            persist: new_histogram(),
+            commit: new_histogram(),
+            apply: new_histogram(),
+            total: new_histogram(),
+        }
+    }
+
+    fn record(&mut self, timeline: &ProposalTimeline, applied_at: Instant) {
+        let persisted_at = timeline.persisted_at.unwrap_or(timeline.proposed_at);
+        let committed_at = timeline.committed_at.unwrap_or(persisted_at);
+        record_stage(&mut self.queue, timeline.enqueued_at, timeline.proposed_at);
+        record_stage(&mut self.persist, timeline.proposed_at, persisted_at);
+        record_stage(&mut self.commit, persisted_at, committed_at);
+        record_stage(&mut self.apply, committed_at, applied_at);
+        record_stage(&mut self.total, timeline.enqueued_at, applied_at);
+    }
+
+    fn latency(&self) -> GroupLatency {
+        GroupLatency {
+            queue: stage_latency(&self.queue),
+            persist: stage_latency(&self.persist),
+            commit: stage_latency(&self.commit),
+            apply: stage_latency(&self.apply),
+            total: stage_latency(&self.total),
+        }
+    }
+}
+
+struct ReadHistograms {
+    queue: Histogram,
+    confirm: Histogram,
+    apply: Histogram,
+    total: Histogram,
+}
+
+impl ReadHistograms {
+    fn new() -> Self {
+        Self {
+            queue: new_histogram(),
+            confirm: new_histogram(),
+            apply: new_histogram(),
+            total: new_histogram(),
+        }
+    }
+
+    fn record(&mut self, timeline: &ReadTimeline, applied_at: Instant) {
+        let confirmed_at = timeline.confirmed_at.unwrap_or(timeline.proposed_at);
+        record_stage(&mut self.queue, timeline.enqueued_at, timeline.proposed_at);
+        record_stage(&mut self.confirm, timeline.proposed_at, confirmed_at);
+        record_stage(&mut self.apply, confirmed_at, applied_at);
+        record_stage(&mut self.total, timeline.enqueued_at, applied_at);
+    }
+
+    fn latency(&self) -> ReadLatency {
+        ReadLatency {
+            queue: stage_latency(&self.queue),
+            confirm: stage_latency(&self.confirm),
+            apply: stage_latency(&self.apply),
+            total: stage_latency(&self.total),
+        }
+    }
+}
+
+/// The histograms of write and read latencies shared by the actors and
+/// `MultiRaft`.
+#[derive(Default)]
+pub(crate) struct LatencyRecorder {
+    groups: Mutex<HashMap<u64, GroupHistograms>>,
+    reads: Mutex<HashMap<u64, ReadHistograms>>,
+}
+
+impl LatencyRecorder {
+    pub(crate) fn record<'a, I>(&self, group_id: u64, timelines: I, applied_at: Instant)
+    where
+        I: IntoIterator<Item = &'a ProposalTimeline>,
+    {
+        let mut groups = self.groups.lock().unwrap();
+        let histograms = groups.entry(group_id).or_insert_with(GroupHistograms::new);
+        for timeline in timelines {
+            histograms.record(timeline, applied_at);
+        }
+    }
+
+    pub(crate) fn record_read(&self, group_id: u64, timeline: &ReadTimeline, applied_at: Instant) {
+        self.reads
+            .lock()
+            .unwrap()
+            .entry(group_id)
+            .or_insert_with(ReadHistograms::new)
+            .record(timeline, applied_at);
+    }
+
+    pub(crate) fn report(&self) -> LatencyReport {
+        LatencyReport {
+            groups: self
+                .groups
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(group_id, histograms)| (*group_id, histograms.latency()))
+                .collect(),
+            reads: self
+                .reads
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(group_id, histograms)| (*group_id, histograms.latency()))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use super::LatencyRecorder;
+    use super::ProposalTimeline;
+    use super::ReadTimeline;
+
+    #[test]
+    fn test_latency_recorder() {
+        let enqueued_at = Instant::now();
+        let ms = Duration::from_millis;
+        let timeline = ProposalTimeline {
+            enqueued_at,
+            proposed_at: enqueued_at + ms(1),
+            persisted_at: Some(enqueued_at + ms(3)),
+            committed_at: Some(enqueued_at + ms(6)),
+        };
+
+        let recorder = LatencyRecorder::default();
+        recorder.record(1, [timeline, timeline].iter(), enqueued_at + ms(10));
+        let report = recorder.report();
+        let latency = report.groups.get(&1).unwrap();
+        assert_eq!(latency.total.count, 2);
+
+        // the recorded values are rounded to 3 significant figures.
+        let close = |got: Duration, expected: Duration| {
+            let diff = got.as_micros().abs_diff(expected.as_micros());
+            assert!(diff * 100 < expected.as_micros());
+        };
+        close(latency.queue.p50, ms(1));
+        close(latency.persist.p50, ms(2));
+        close(latency.commit.max, ms(3));
+        close(latency.apply.min, ms(4));
+        close(latency.total.p99, ms(10));
+        assert!(report.groups.get(&2).is_none());
+        assert!(report.reads.is_empty());
+    }
+
+    #[test]
+    fn test_read_latency_recorder() {
+        let enqueued_at = Instant::now();
+        let ms = Duration::from_millis;
+        let mut timeline = ReadTimeline {
+            enqueued_at,
+            proposed_at: enqueued_at + ms(1),
+            confirmed_at: None,
+            read_index: 0,
+        };
+        timeline.confirmed(5, enqueued_at + ms(4));
+        assert_eq!(timeline.read_index, 5);
+
+        let recorder = LatencyRecorder::default();
+        recorder.record_read(1, &timeline, enqueued_at + ms(10));
+        let report = recorder.report();
+        let latency = report.reads.get(&1).unwrap();
+        assert_eq!(latency.total.count, 1);
+
+        let close = |got: Duration, expected: Duration| {
+            let diff = got.as_micros().abs_diff(expected.as_micros());
+            assert!(diff * 100 < expected.as_micros());
+        };
+        close(latency.queue.p50, ms(1));
+        close(latency.confirm.p50, ms(3));
+        close(latency.apply.p50, ms(6));
+        close(latency.total.max, ms(10));
+        assert!(report.groups.is_empty());
+    }
+}
@@ -0,0 +1,164 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use super::clock::lease_margin;
+use super::clock::MonotonicClock;
+use super::clock::TimeSource;
+
+/// The lease of the leader to serve the reads of `ConsistencyLevel::Lease`
+/// locally, see `Config::enable_lease_read`.
+///
+/// The lease is renewed by the read index confirmed by the heartbeat quorum,
+/// it starts at the time the read index is issued, so the followers which
+/// acknowledged the leader do not vote for another candidate until the lease
+/// expires. The lease is bound to the term of the leader, and it's shortened
+/// by the margin of the drift and the error of the time source.
+#[derive(Debug)]
+pub(crate) struct LeaderLease {
+    /// The duration of the lease, `None` if the lease read is disabled.
+    duration: Option<Duration>,
+    time_source: Arc<dyn TimeSource>,
+    term: u64,
+    expired_at: Option<Instant>,
+    /// The time the renewal read index issued, at most one renewal is in
+    /// flight within the duration.
+    renewing_since: Option<Instant>,
+}
+
+impl Default for LeaderLease {
+    fn default() -> Self {
+        Self::new(None, Arc::new(MonotonicClock::default()))
+    }
+}
+
+impl LeaderLease {
+    pub(crate) fn new(duration: Option<Duration>, time_source: Arc<dyn TimeSource>) -> Self {
+        Self {
+            duration,
+            time_source,
+            term: 0,
+            expired_at: None,
+            renewing_since: None,
+        }
+    }
+
+    /// The current time of the time source of the lease.
+    #[inline]
+    pub(crate) fn now(&self) -> Instant {
+        self.time_source.now()
+    }
+
+    #[inline]
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.duration.is_some()
+    }
+
+    /// Renew the lease of the term by the quorum confirmation requested at
+    /// `since`.
+    pub(crate) fn renew(&mut self, term: u64, since: Instant) {
+        let duration = match self.duration {
+            None => return,
+            Some(duration) => duration,
+        };
+        let expired_at = since + duration;
+        if self.term != term || self.expired_at.map_or(true, |at| at < expired_at) {
+            self.term = term;
+            self.expired_at = Some(expired_at);
+        }
+        self.renewing_since = None;
+    }
+
+    /// True if the lease of the term is held at `now`. The margin is checked
+    /// at the moment, since the error of the time source changes over time.
+    pub(crate) fn is_valid(&self, term: u64, now: Instant) -> bool {
+        let margin = match self.duration {
+            None => return false,
+            Some(duration) => lease_margin(self.time_source.as_ref(), duration),
+        };
+        self.term == term
+            && self.expired_at.map_or(false, |at| {
+                now.checked_add(margin).map_or(false, |now| now < at)
+            })
+    }
+
+    /// True if the lease of the term expires in half of the duration and no
+    /// renewal is in flight, the renewal is marked in flight if true.
+    pub(crate) fn should_renew(&mut self, term: u64, now: Instant) -> bool {
+        let duration = match self.duration {
+            None => return false,
+            Some(duration) => duration,
+        };
+        if !self.is_valid(term, now + duration / 2) {
+            if self
+                .renewing_since
+                .map_or(false, |since| now < since + duration)
+            {
+                return false;
+            }
+            self.renewing_since = Some(now);
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use std::sync::Arc;
+
+    use tokio::time::Instant;
+
+    use super::LeaderLease;
+    use crate::clock::DisciplinedClock;
+    use crate::clock::MonotonicClock;
+
+    #[test]
+    fn test_leader_lease() {
+        let now = Instant::now();
+        let mut lease = LeaderLease::new(None, Arc::new(MonotonicClock::default()));
+        lease.renew(1, now);
+        assert!(!lease.is_valid(1, now));
+        assert!(!lease.should_renew(1, now));
+
+        let duration = Duration::from_millis(100);
+        let mut lease = LeaderLease::new(Some(duration), Arc::new(MonotonicClock::default()));
+        assert!(!lease.is_valid(1, now));
+        lease.renew(1, now);
+        assert!(lease.is_valid(1, now + duration / 2));
+        assert!(!lease.is_valid(1, now + duration));
+        // the lease is bound to the term.
+        assert!(!lease.is_valid(2, now));
+
+        // the older confirmation does not shorten the lease.
+        lease.renew(1, now - duration / 2);
+        assert!(lease.is_valid(1, now + duration / 2));
+
+        assert!(!lease.should_renew(1, now));
+        assert!(lease.should_renew(1, now + duration * 3 / 4));
+        // the renewal is in flight.
+        assert!(!lease.should_renew(1, now + duration * 3 / 4));
+        lease.renew(1, now + duration * 3 / 4);
+        assert!(!lease.should_renew(1, now + duration));
+    }
+
+    #[test]
+    fn test_leader_lease_margin() {
+        let now = Instant::now();
+        let duration = Duration::from_millis(100);
+        let clock = Arc::new(DisciplinedClock::new(0, Duration::from_millis(10)));
+        let mut lease = LeaderLease::new(Some(duration), clock.clone());
+        lease.renew(1, now);
+        assert!(lease.is_valid(1, now + Duration::from_millis(89)));
+        assert!(!lease.is_valid(1, now + Duration::from_millis(90)));
+
+        // the lease is not held while the clock is not synchronized.
+        clock.set_synchronized(false);
+        assert!(!lease.is_valid(1, now));
+        clock.set_synchronized(true);
+        assert!(lease.is_valid(1, now));
+    }
+}
@@ -700,33 +700,118 @@ pub mod prelude {
     pub use raft::prelude::*;
 }
 
+mod admin;
 mod apply;
+mod apply_flow;
+mod auth;
+mod background;
+mod budget;
+mod clock;
+mod coalesce;
+mod compaction;
+mod compression;
 mod config;
+mod consumer;
+mod cut;
+mod dedup;
+mod domain;
 mod error;
 mod event;
+mod factory;
+mod fatal;
+mod gate;
 mod group;
+mod id;
+mod idempotency;
+mod inflight;
+mod invariant;
+mod latency;
+mod lease;
+mod lifecycle;
+#[cfg(feature = "log")]
 pub mod log;
 mod msg;
 mod multiraft;
 mod multiraft_handle;
 mod node;
+mod node_background;
+mod node_compaction;
+mod node_flow_control;
 mod node_handle;
 mod node_heartbeats;
+mod node_inflight;
+mod node_promotion;
+mod node_replica_gc;
+mod node_snapshot_throttle;
+mod node_storage_check;
+mod node_unreachable;
+mod operation;
+mod overload;
+mod placement;
+mod promotion;
 mod proposal;
+mod proposer;
+mod pull;
+mod quota;
 mod replica_cache;
 mod rsm;
+mod snapshot;
+mod standby;
 mod state;
 pub mod storage;
+#[cfg(any(test, feature = "testkit"))]
+pub mod testkit;
 pub mod tick;
+mod topology;
 pub mod transport;
+#[cfg(feature = "txn")]
+pub mod txn;
+mod unknown_group;
 pub mod utils;
 
+pub use admin::AdminPayload;
+pub use auth::{AllowAll, Authorizer, Operation};
+pub use background::{BackgroundBudget, BackgroundPermit, BackgroundScheduler, JobKind, JobStats};
+pub use clock::{DisciplinedClock, MonotonicClock, TimeSource};
+pub use compaction::{
+    CompactionPolicy, CountCompaction, LogState, NoCompaction, SizeCompaction, TimeCompaction,
+};
+pub use compression::{decompress_entry_data, EntryCompression};
 pub use config::Config;
+pub use consumer::{AppliedBatch, AppliedConsumer};
+pub use cut::{CommitWatermark, ConsistentCut, ReadBarrier};
 pub use error::{Error, MultiRaftStorageError, ProposeError, RaftCoreError, RaftGroupError};
-pub use event::{Event, LeaderElectionEvent};
+pub use event::{
+    Event, EventChannel, LeaderElectionEvent, MembershipChangeEvent, RelocationStage,
+    ReplicaHealth, SnapshotTransferStage,
+};
+pub use factory::{GroupFactory, NoGroupFactory};
+pub use fatal::FatalError;
+pub use gate::{AcceptAllGate, EntryGate, GateAction, GateDecision, GATE_POLICY_COMMAND};
+pub use id::{IdGenerator, RandomIdGenerator, SeededIdGenerator};
+pub use latency::{GroupLatency, LatencyReport, ReadLatency, StageLatency};
+pub use lifecycle::Lifecycle;
 pub use multiraft::{
-    MultiRaft, MultiRaftMessageSender, MultiRaftMessageSenderImpl, MultiRaftTypeSpecialization,
-    ProposeData, ProposeResponse,
+    ConsistencyLevel, MultiRaft, MultiRaftMessageSender, MultiRaftMessageSenderImpl,
+    MultiRaftTypeSpecialization, ProposeData, ProposeResponse, WriteResponse,
+};
+pub use node_handle::{IoWork, NodeHandle, Work};
+pub use operation::{OperationKind, OperationProgress, OperationState};
+pub use overload::{OverloadShedding, OverloadStats};
+pub use placement::{
+    node_loads, BalancedPlacement, NoPlacement, NodeLoad, PlacementAction, PlacementDriver,
+};
+pub use promotion::{LearnerPromoter, LearnerPromotion, NoLearnerPromotion};
+pub use proposer::Proposer;
+pub use quota::{LogCompactor, LogQuotaStage, NoLogCompactor};
+pub use rsm::{Apply, ApplyAdmin, ApplyMembership, ApplyNoOp, ApplyNormal, StateMachine};
+pub use snapshot::{NoSnapshotValidator, SnapshotValidator};
+pub use state::{
+    ConfStateMismatch, GroupPage, GroupRemoval, GroupState, GroupStates, GroupStatus, GroupSummary,
+    LeaderCandidate, LogBounds, NodeStatus, PeerStatus, ProgressStatus, ReadStats,
+    ReplicaDescGcReport, StaleMessageStats, StorageCheckReport, StorageRepair,
+};
+pub use topology::{
+    ClusterTopology, GroupTopology, NoTopologyProvider, NodeTopology, ReplicaTopology,
+    TopologyProvider,
 };
-pub use rsm::{Apply, ApplyMembership, ApplyNoOp, ApplyNormal, StateMachine};
-pub use state::{GroupState, GroupStates};
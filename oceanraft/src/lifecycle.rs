@@ -0,0 +1,33 @@
+use futures::Future;
+
+use crate::Error;
+
+/// Lifecycle of the resources which the node depends on, i.e. the transport
+/// and the storage. `MultiRaft::shutdown` flushes and stops the transport and the
+/// storage of the node, so the connections are closed and the writes are
+/// durable before the process exits.
+pub trait Lifecycle: Send + Sync + 'static {
+    /// GAT trait for `start`.
+    type StartFuture<'life0>: Send + Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+    /// Start the resource. The node is created synchronously, so it is called
+    /// by the application before the resource is passed to `MultiRaft::new`.
+    fn start(&self) -> Self::StartFuture<'_>;
+
+    /// GAT trait for `flush`.
+    type FlushFuture<'life0>: Send + Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+    /// Flush the buffered data, e.g. the messages queued to send or the writes
+    /// in the memory of storage.
+    fn flush(&self) -> Self::FlushFuture<'_>;
+
+    /// GAT trait for `stop`.
+    type StopFuture<'life0>: Send + Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+    /// Stop the resource and release its connections or files, it is called
+    /// after `flush`.
+    fn stop(&self) -> Self::StopFuture<'_>;
+}
@@ -281,7 +281,7 @@ pub fn log_panic(panic: &PanicInfo) {
     let backtrace = {
         #[cfg(feature = "bt")]
         {
-            format!("{:?}", Backtrace::force_capture())
+            format!("{:?}", std::backtrace::Backtrace::force_capture())
         }
 
         #[cfg(not(feature = "bt"))]
@@ -5,7 +5,10 @@ use std::collections::HashMap;
 use serde::Deserialize;
 use serde::Serialize;
 use tokio::sync::oneshot;
+use tokio::time::Instant;
+use uuid::Uuid;
 
+use crate::multiraft::ConsistencyLevel;
 use crate::multiraft::ProposeResponse;
 use crate::prelude::ConfChangeV2;
 use crate::prelude::ConfState;
@@ -13,9 +16,21 @@ use crate::prelude::CreateGroupRequest;
 use crate::prelude::Entry;
 use crate::prelude::MembershipChangeData;
 use crate::prelude::RemoveGroupRequest;
+use crate::prelude::SnapshotMetadata;
 
+use super::admin::AdminPayload;
+use super::cut::ConsistentCut;
 use super::error::Error;
+use super::fatal::FatalError;
 use super::proposal::Proposal;
+use super::state::GroupPage;
+use super::state::GroupRemoval;
+use super::state::GroupStatus;
+use super::state::NodeStatus;
+use super::state::ReplicaDescGcReport;
+use super::state::StorageCheckReport;
+use super::state::StorageRepair;
+use super::topology::NodeTopology;
 use super::ProposeData;
 
 pub struct WriteRequest<REQ, RES>
@@ -23,11 +38,34 @@ where
     REQ: ProposeData,
     RES: ProposeResponse,
 {
+    /// The id used to trace the request.
+    pub request_id: Uuid,
     pub group_id: u64,
     pub term: u64,
     pub data: REQ,
     pub context: Option<Vec<u8>>,
+    /// If true, the context is kept in the proposal queue of the proposer
+    /// instead of written into the entry, so it is not replicated and only
+    /// passed to the state machine of the proposer.
+    pub local_context: bool,
     pub tx: oneshot::Sender<Result<(RES, Option<Vec<u8>>), Error>>,
+    /// If some, the commit metadata of the write is sent via `commit_tx`
+    /// when the entry is committed.
+    pub commit_tx: Option<oneshot::Sender<WriteCommit>>,
+    /// The time the write is enqueued, it is some if `Config::record_latency`
+    /// is enabled.
+    pub enqueued_at: Option<Instant>,
+    /// If some, the write is coalesced with the uncommitted identical write
+    /// of the key, see `MultiRaft::write_coalesced`.
+    pub coalescing_key: Option<String>,
+}
+
+/// The commit metadata of the write proposal.
+#[derive(Debug, Clone, Copy)]
+pub struct WriteCommit {
+    pub index: u64,
+    pub term: u64,
+    pub committed_at: Instant,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -40,6 +78,8 @@ pub struct MembershipRequest<RES>
 where
     RES: ProposeResponse,
 {
+    /// The id used to trace the request.
+    pub request_id: Uuid,
     pub group_id: u64,
     pub term: Option<u64>,
     pub context: Option<Vec<u8>>,
@@ -47,8 +87,20 @@ where
     pub tx: oneshot::Sender<Result<(RES, Option<Vec<u8>>), Error>>,
 }
 
+pub struct AdminRequest<RES>
+where
+    RES: ProposeResponse,
+{
+    /// The id used to trace the request.
+    pub request_id: Uuid,
+    pub group_id: u64,
+    pub payload: AdminPayload,
+    pub tx: oneshot::Sender<Result<(RES, Option<Vec<u8>>), Error>>,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ReadIndexContext {
+    /// The id of read index request, also used to trace the request.
     pub uuid: [u8; 16],
 
     /// context for user
@@ -57,8 +109,12 @@ pub struct ReadIndexContext {
 
 pub struct ReadIndexData {
     pub group_id: u64,
+    pub consistency: ConsistencyLevel,
     pub context: ReadIndexContext,
     pub tx: oneshot::Sender<Result<Option<Vec<u8>>, Error>>,
+    /// The time the read is enqueued, it is some if `Config::record_latency`
+    /// is enabled.
+    pub enqueued_at: Option<Instant>,
 }
 
 pub enum ProposeMessage<REQ, RES>
@@ -68,11 +124,72 @@ where
 {
     Write(WriteRequest<REQ, RES>),
     Membership(MembershipRequest<RES>),
+    /// The admin entry, see `MultiRaft::propose_admin`.
+    Admin(AdminRequest<RES>),
     ReadIndexData(ReadIndexData),
+    /// The reads of multiple groups sent in one message, see
+    /// `MultiRaft::read_index_many`.
+    ReadIndexBatch(Vec<ReadIndexData>),
 }
 pub enum ManageMessage {
     CreateGroup(CreateGroupRequest, oneshot::Sender<Result<(), Error>>),
-    RemoveGroup(RemoveGroupRequest, oneshot::Sender<Result<(), Error>>),
+    /// Create groups in one batch, the results are in the order of requests.
+    CreateGroups(
+        Vec<CreateGroupRequest>,
+        oneshot::Sender<Result<Vec<Result<(), Error>>, Error>>,
+    ),
+    RemoveGroup(
+        RemoveGroupRequest,
+        oneshot::Sender<Result<GroupRemoval, Error>>,
+    ),
+    /// Pause all groups of the storage domain.
+    PauseStorageDomain(String, oneshot::Sender<Result<(), Error>>),
+    /// Resume the groups of the paused storage domain.
+    ResumeStorageDomain(String, oneshot::Sender<Result<(), Error>>),
+    /// Collect the descriptors of the replicas removed from their groups,
+    /// only reports if dry run.
+    GcReplicaDescs(bool, oneshot::Sender<Result<ReplicaDescGcReport, Error>>),
+    /// Cross-check the metadata of the groups with the group storages.
+    CheckStorage(oneshot::Sender<Result<StorageCheckReport, Error>>),
+    /// Repair the inconsistency reported by the storage check.
+    RepairStorage(StorageRepair, oneshot::Sender<Result<(), Error>>),
+    /// Set the archive mode of the group.
+    SetGroupArchive(u64, bool, oneshot::Sender<Result<(), Error>>),
+    /// Release the apply of the group held by the entry gate.
+    ReleaseGateHold(u64, oneshot::Sender<Result<(), Error>>),
+    /// Fork the new group from the snapshot of the source group, the members
+    /// are mapped from node id to replica id.
+    ForkGroup(
+        u64,
+        u64,
+        HashMap<u64, u64>,
+        oneshot::Sender<Result<u64, Error>>,
+    ),
+    /// Transfer the leadership of the group to the replica, the term in
+    /// which the transfer is started is returned.
+    TransferLeader(u64, u64, oneshot::Sender<Result<u64, Error>>),
+    /// Propose the membership change on the replica of the node, which is
+    /// forwarded to the leader by raft if the replica is a follower. The
+    /// change is not tracked, the applied membership should be observed.
+    ForwardMembership(
+        u64,
+        MembershipChangeData,
+        oneshot::Sender<Result<(), Error>>,
+    ),
+}
+
+impl ManageMessage {
+    /// The urgent management relieves the node or the group, so it's not shed
+    /// under overload, see `Config::overload_shedding`.
+    pub fn is_urgent(&self) -> bool {
+        matches!(
+            self,
+            ManageMessage::RemoveGroup(..)
+                | ManageMessage::PauseStorageDomain(..)
+                | ManageMessage::TransferLeader(..)
+                | ManageMessage::ForwardMembership(..)
+        )
+    }
 }
 
 #[allow(unused)]
@@ -123,6 +240,23 @@ where
     Apply {
         applys: HashMap<u64, ApplyData<RES>>,
     },
+    /// The replica of group is created on the node.
+    GroupCreated {
+        group_id: u64,
+        replica_id: u64,
+        conf_state: ConfState,
+    },
+    /// The replica of group is removed from the node.
+    GroupRemoved { group_id: u64, replica_id: u64 },
+    /// The snapshot is installed to the storage of replica.
+    SnapshotInstalled {
+        group_id: u64,
+        replica_id: u64,
+        metadata: SnapshotMetadata,
+    },
+    /// Release the apply of the group held by the entry gate, the held
+    /// entries are read from the storage and applied again.
+    ReleaseHold { group_id: u64, replica_id: u64 },
 }
 
 #[derive(Debug)]
@@ -159,6 +293,9 @@ pub struct CommitMembership {
 pub enum ApplyCommitMessage {
     None,
     Membership((CommitMembership, oneshot::Sender<Result<ConfState, Error>>)),
+    /// The apply of the group failed with the fatal error, the group is
+    /// halted by the node.
+    Halt(FatalError),
 }
 
 impl Default for ApplyCommitMessage {
@@ -174,4 +311,41 @@ pub enum QueryGroup {
     /// Queries if there has a pending configuration,
     /// returns true or false
     HasPendingConf(u64, oneshot::Sender<Result<bool, Error>>),
+
+    /// Queries the progress of the replica tracked by leader, returns
+    /// the matched index of the replica and the committed index of
+    /// leader, or `None` if the replica is not tracked.
+    ReplicaProgress(
+        u64, /* group_id */
+        u64, /* replica_id */
+        oneshot::Sender<Result<Option<(u64, u64)>, Error>>,
+    ),
+
+    /// Lists at most `limit` groups on the node whose group id is greater
+    /// than `cursor` in the order of group id.
+    ListGroups(
+        u64,   /* cursor */
+        usize, /* limit */
+        oneshot::Sender<GroupPage>,
+    ),
+
+    /// Queries the node-level summary of the groups and peers, the storage
+    /// usage is not filled by the node.
+    NodeStatus(oneshot::Sender<NodeStatus>),
+
+    /// Queries the status of the group on the node, the parked groups are
+    /// included.
+    GroupStatus(u64, oneshot::Sender<Result<GroupStatus, Error>>),
+
+    /// Queries the replica id of the group on the node, the parked groups
+    /// are included, which is used to access the storage of the group.
+    GroupReplica(u64, oneshot::Sender<Result<u64, Error>>),
+
+    /// Queries the membership of the groups on the node, the nodes and the
+    /// witness flags of the replicas are not filled by the node.
+    Topology(oneshot::Sender<NodeTopology>),
+
+    /// Queries the commit watermarks of the groups at the same time, all the
+    /// groups on the node if the groups are empty.
+    ConsistentCut(Vec<u64>, oneshot::Sender<ConsistentCut>),
 }
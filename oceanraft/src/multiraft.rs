@@ -1,39 +1,112 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
 use std::fmt::Debug;
 use std::marker::PhantomData;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
+use std::time::Duration;
 
+use futures::stream::FuturesUnordered;
 use futures::Future;
+use futures::Stream;
+use futures::StreamExt;
+use raft::GetEntriesContext;
+use raft::Storage;
+#[cfg(not(feature = "prost-data"))]
 use serde::Deserialize;
+#[cfg(not(feature = "prost-data"))]
 use serde::Serialize;
+use tokio::sync::broadcast;
 use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::error;
+use tracing::trace;
+use tracing::warn;
 use uuid::Uuid;
 
+use crate::prelude::ConfChangeTransition;
+use crate::prelude::ConfChangeType;
+use crate::prelude::ConfState;
 use crate::prelude::CreateGroupRequest;
+use crate::prelude::Entry;
 use crate::prelude::MembershipChangeData;
 use crate::prelude::MultiRaftMessage;
 use crate::prelude::MultiRaftMessageResponse;
+use crate::prelude::ReplicaDesc;
+use crate::prelude::SingleMembershipChange;
+use crate::prelude::Snapshot;
 use crate::protos::RemoveGroupRequest;
 
+use super::admin::AdminPayload;
+use super::auth::AuthorizationCache;
+use super::auth::Operation;
+use super::background::BackgroundScheduler;
+use super::background::JobStats;
 use super::config::Config;
+use super::consumer::AppliedConsumer;
+use super::consumer::AppliedConsumerRunner;
+use super::cut::ConsistentCut;
+use super::cut::ReadBarrier;
 use super::error::ChannelError;
 use super::error::Error;
+use super::event::Event;
 use super::event::EventChannel;
 use super::event::EventReceiver;
+use super::event::RelocationStage;
+use super::fatal::FatalError;
+use super::gate::gate_policy_payload;
+use super::id::IdGenerator;
+use super::latency::LatencyReport;
+use super::lifecycle::Lifecycle;
+use super::msg::AdminRequest;
 use super::msg::ManageMessage;
 use super::msg::MembershipRequest;
 use super::msg::ProposeMessage;
 use super::msg::QueryGroup;
 use super::msg::ReadIndexContext;
 use super::msg::ReadIndexData;
+use super::msg::WriteCommit;
 use super::msg::WriteRequest;
 use super::node::NodeActor;
+use super::node_handle::NodeHandle;
+use super::operation::OperationKind;
+use super::operation::OperationProgress;
+use super::operation::OperationState;
+use super::operation::Operations;
+use super::overload::OverloadStats;
+use super::overload::ShedClass;
+use super::placement::PlacementAction;
+use super::placement::PlacementDriver;
+use super::rsm::Apply;
+use super::standby::Standbys;
+use super::state::GroupPage;
+use super::state::GroupRemoval;
+use super::state::GroupState;
 use super::state::GroupStates;
+use super::state::GroupStatus;
+use super::state::GroupSummary;
+use super::state::LogBounds;
+use super::state::NodeStatus;
+use super::state::ReadStats;
+use super::state::ReplicaDescGcReport;
+use super::state::StaleMessageStats;
+use super::state::StorageCheckReport;
+use super::state::StorageRepair;
 use super::storage::MultiRaftStorage;
 use super::storage::RaftStorage;
+use super::storage::StorageExt;
 use super::tick::Ticker;
+use super::topology::ClusterTopology;
+use super::topology::NodeTopology;
+use super::topology::TopologyProvider;
+use super::transport::decompress_message;
+use super::transport::EndpointHealth;
+use super::transport::QueueTransport;
+use super::transport::SnapshotTransport;
+use super::transport::SnapshotUpload;
 use super::transport::Transport;
 use super::RaftGroupError;
 use super::StateMachine;
@@ -42,25 +115,85 @@ pub const NO_GORUP: u64 = 0;
 pub const NO_NODE: u64 = 0;
 pub const NO_LEADER: u64 = 0;
 
+/// The ticks between the retries of the removal of the relocated leader,
+/// which is dropped by the new leader if another membership change is pending.
+const RELOCATION_FORWARD_TICKS: u64 = 10;
+
 /// Propose request can be with custom data types
 /// for which `ProposeRequest` provides trait constraints.
+#[cfg(not(feature = "prost-data"))]
 pub trait ProposeData:
     Debug + Clone + Send + Sync + Serialize + for<'d> Deserialize<'d> + 'static
 {
 }
 
+#[cfg(not(feature = "prost-data"))]
 impl<T> ProposeData for T where
     T: Debug + Clone + Send + Sync + Serialize + for<'d> Deserialize<'d> + 'static
 {
 }
 
+/// Propose request can be with any prost message with the `prost-data`
+/// feature, the message is encoded as the data of the entry directly.
+#[cfg(feature = "prost-data")]
+pub trait ProposeData: prost::Message + Default + Clone + 'static {}
+
+#[cfg(feature = "prost-data")]
+impl<T> ProposeData for T where T: prost::Message + Default + Clone + 'static {}
+
 /// Propose and membership change requests can be responded with custom types
 /// for which `ProposePropose` provides trait constraints.
 pub trait ProposeResponse: Debug + Clone + Send + Sync + 'static {}
 
 impl<R> ProposeResponse for R where R: Debug + Clone + Send + Sync + 'static {}
 
-pub trait MultiRaftTypeSpecialization {
+/// The result of a successful write, includes the response of state machine
+/// and the commit metadata of the write.
+#[derive(Debug, Clone)]
+pub struct WriteResponse<R: ProposeResponse> {
+    /// The response created by the state machine.
+    pub data: R,
+    /// The context returned by the state machine.
+    pub context: Option<Vec<u8>>,
+    /// The index of the raft log entry of the write.
+    pub index: u64,
+    /// The term of the raft log entry of the write, the application can use
+    /// `(term, index)` for fencing.
+    pub term: u64,
+    /// The duration from the write being proposed to being committed.
+    pub commit_latency: Duration,
+    /// The duration from the write being proposed to being applied.
+    pub apply_latency: Duration,
+}
+
+/// The consistency level of the read, see `MultiRaft::read`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsistencyLevel {
+    /// The read is confirmed by a quorum with the read index algorithm.
+    #[default]
+    Linearizable,
+    /// The leader serves the read without contacting a quorum if it holds
+    /// the lease, which requires `Config::check_quorum`. Otherwise the read
+    /// falls back to `Linearizable`.
+    LeaderLocal,
+    /// The follower serves the read if it heard from the leader within the
+    /// duration, the leader serves the read as `LeaderLocal`. Otherwise the
+    /// read falls back to `Linearizable`.
+    BoundedStaleness(Duration),
+    /// The leader serves the read without contacting a quorum in the leader
+    /// lease, see `Config::enable_lease_read`. Otherwise the read falls back
+    /// to `Linearizable`, which renews the lease.
+    Lease,
+    /// The read index is confirmed by the leader, the follower forwards the
+    /// read index request to the leader over the transport. The read is
+    /// responded after the replica applied to the read index, so the
+    /// application can read the local state machine of the replica.
+    Follower,
+}
+
+/// The types specialized by the multiraft, they are `'static` so that the
+/// multiraft can be moved into the spawned tasks of the operations.
+pub trait MultiRaftTypeSpecialization: 'static {
     type D: ProposeData;
     type R: ProposeResponse;
     type M: StateMachine<Self::D, Self::R>;
@@ -95,8 +228,10 @@ impl MultiRaftMessageSender for MultiRaftMessageSenderImpl {
     where
         Self: 'life0;
 
-    fn send<'life0>(&'life0 self, msg: MultiRaftMessage) -> Self::SendFuture<'life0> {
+    fn send<'life0>(&'life0 self, mut msg: MultiRaftMessage) -> Self::SendFuture<'life0> {
         async move {
+            // the message is decompressed out of the node loop.
+            decompress_message(&mut msg)?;
             let (tx, rx) = oneshot::channel();
             match self.tx.try_send((msg, tx)) {
                 Err(TrySendError::Closed(_)) => Err(Error::Channel(ChannelError::ReceiverClosed(
@@ -105,11 +240,17 @@ impl MultiRaftMessageSender for MultiRaftMessageSenderImpl {
                 Err(TrySendError::Full(_)) => Err(Error::Channel(ChannelError::Full(
                     "channel receiver fulled for raft message".to_owned(),
                 ))),
-                Ok(_) => rx.await.map_err(|_| {
-                    Error::Channel(ChannelError::ReceiverClosed(
-                        "channel sender closed for raft message".to_owned(),
-                    ))
-                })?,
+                Ok(_) => rx
+                    .await
+                    .map_err(|_| {
+                        Error::Channel(ChannelError::ReceiverClosed(
+                            "channel sender closed for raft message".to_owned(),
+                        ))
+                    })?
+                    .map(|mut response| {
+                        response.accept_compression = cfg!(feature = "compression");
+                        response
+                    }),
             }
         }
     }
@@ -122,10 +263,25 @@ where
     TR: Transport + Clone,
 {
     node_id: u64,
+    tick_interval: u64,
     stopped: Arc<AtomicBool>,
     actor: NodeActor<T::D, T::R>,
     shared_states: GroupStates,
     event_bcast: EventChannel,
+    transport: TR,
+    storage: T::MS,
+    id_generator: Arc<dyn IdGenerator>,
+    auto_create_groups: bool,
+    /// See `Config::record_latency`.
+    record_latency: bool,
+    /// Authorizes the proposals if `Config::authorize_proposals` is enabled.
+    proposal_authorization: Option<AuthorizationCache>,
+    topology_provider: Arc<dyn TopologyProvider>,
+    placement_driver: Arc<dyn PlacementDriver>,
+    /// The standby replicas designated on the node, see `designate_standby`.
+    standbys: Standbys,
+    /// The long-running operations on the node, see `operation_progress`.
+    operations: Operations,
     _m1: PhantomData<TR>,
 }
 
@@ -155,23 +311,98 @@ where
             states.clone(),
             stopped.clone(),
         );
+        let proposal_authorization = cfg
+            .authorize_proposals
+            .then(|| AuthorizationCache::new(cfg.node_id, cfg.authorizer.clone()));
 
         Ok(Self {
             node_id: cfg.node_id,
+            tick_interval: cfg.tick_interval,
             event_bcast,
             actor,
             shared_states: states,
             stopped,
+            transport,
+            storage,
+            id_generator: cfg.id_generator,
+            auto_create_groups: cfg.auto_create_groups,
+            record_latency: cfg.record_latency,
+            proposal_authorization,
+            topology_provider: cfg.topology_provider,
+            placement_driver: cfg.placement_driver,
+            standbys: Standbys::default(),
+            operations: Operations::default(),
             _m1: PhantomData,
         })
     }
+}
+
+impl<T> MultiRaft<T, QueueTransport>
+where
+    T: MultiRaftTypeSpecialization,
+{
+    /// Create the multiraft without spawning any task, the returned `NodeHandle`
+    /// must be polled by the caller to drive the node. The outgoing messages
+    /// are returned by `NodeHandle::poll` and the incoming messages are stepped
+    /// by `NodeHandle::step`.
+    pub fn new_polled(
+        cfg: Config,
+        storage: T::MS,
+        state_machine: T::M,
+    ) -> Result<(Self, NodeHandle<T>), Error> {
+        cfg.validate()?;
+        let states = GroupStates::new();
+        let mut event_bcast = EventChannel::new(cfg.event_capacity);
+        event_bcast.set_inline_flush();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let transport = QueueTransport::new();
+        let (actor, worker, apply) = NodeActor::new_polled(
+            &cfg,
+            &transport,
+            &storage,
+            state_machine,
+            &event_bcast,
+            states.clone(),
+        );
+        let handle = NodeHandle::new(&cfg, worker, apply, transport.clone(), stopped.clone());
+        let proposal_authorization = cfg
+            .authorize_proposals
+            .then(|| AuthorizationCache::new(cfg.node_id, cfg.authorizer.clone()));
+
+        let multiraft = Self {
+            node_id: cfg.node_id,
+            tick_interval: cfg.tick_interval,
+            event_bcast,
+            actor,
+            shared_states: states,
+            stopped,
+            transport,
+            storage,
+            id_generator: cfg.id_generator,
+            auto_create_groups: cfg.auto_create_groups,
+            record_latency: cfg.record_latency,
+            proposal_authorization,
+            topology_provider: cfg.topology_provider,
+            placement_driver: cfg.placement_driver,
+            standbys: Standbys::default(),
+            operations: Operations::default(),
+            _m1: PhantomData,
+        };
+        Ok((multiraft, handle))
+    }
+}
 
+impl<T, TR> MultiRaft<T, TR>
+where
+    T: MultiRaftTypeSpecialization,
+    TR: Transport + Clone,
+{
     /// `write` the propose data to a specific group in the multiraft system.
     ///
     /// It is a blocking interface in an asynchronous environment. It waits until
     /// the proposal is successfully applied to the state machine  and the `RES and
-    /// `context` are returned through the state machine created. If the proposal
-    /// fails, an error is returned.
+    /// `context` are returned through the state machine created, along with the
+    /// commit metadata in `WriteResponse`. If the proposal fails, an error is returned.
     ///
     /// ## Parameters
     /// - `group_id`: The specific consensus group to write to.
@@ -196,13 +427,86 @@ where
         term: u64,
         context: Option<Vec<u8>>,
         propose: T::D,
-    ) -> Result<(T::R, Option<Vec<u8>>), Error> {
-        let rx = self.write_non_block(group_id, term, context, propose)?;
-        rx.await.map_err(|_| {
+    ) -> Result<WriteResponse<T::R>, Error> {
+        self.write_with_context(group_id, term, context, false, None, propose)
+            .await
+    }
+
+    /// Same as `write`, but the `context` is kept in the proposal queue of the
+    /// proposer instead of written into the raft log, which reduces the log
+    /// and network size for large contexts.
+    ///
+    /// The context is passed to the state machine only when the entry is
+    /// applied on the proposer, the other replicas apply the entry without
+    /// context, so does the proposer if the proposal is lost, e.g. the
+    /// leadership changed before the entry committed.
+    pub async fn write_local_context(
+        &self,
+        group_id: u64,
+        term: u64,
+        context: Vec<u8>,
+        propose: T::D,
+    ) -> Result<WriteResponse<T::R>, Error> {
+        self.write_with_context(group_id, term, Some(context), true, None, propose)
+            .await
+    }
+
+    /// Same as `write`, but the write is coalesced with the identical write
+    /// of the `coalescing_key` queued on the leader, e.g. the heartbeat-style
+    /// upserts of a key. If an uncommitted write of the key with the same
+    /// data is proposed in the current term, the write joins it instead of
+    /// appending a new entry, and it's resolved by the result of the single
+    /// entry committed.
+    ///
+    /// The application must only coalesce the idempotent writes, since the
+    /// joined writes are applied once. The writes of a key with different
+    /// data are never coalesced.
+    pub async fn write_coalesced(
+        &self,
+        group_id: u64,
+        term: u64,
+        coalescing_key: String,
+        propose: T::D,
+    ) -> Result<WriteResponse<T::R>, Error> {
+        self.write_with_context(group_id, term, None, false, Some(coalescing_key), propose)
+            .await
+    }
+
+    async fn write_with_context(
+        &self,
+        group_id: u64,
+        term: u64,
+        context: Option<Vec<u8>>,
+        local_context: bool,
+        coalescing_key: Option<String>,
+        propose: T::D,
+    ) -> Result<WriteResponse<T::R>, Error> {
+        let request_id = self.id_generator.next_uuid();
+        let proposed_at = Instant::now();
+        let (commit_tx, commit_rx) = oneshot::channel();
+        let rx = self.write_with_request_id(
+            request_id,
+            group_id,
+            term,
+            context,
+            local_context,
+            coalescing_key,
+            propose,
+            Some(commit_tx),
+        )?;
+        let res = rx.await.map_err(|_| {
             Error::Channel(ChannelError::SenderClosed(
                 "the sender that result the write was dropped".to_owned(),
             ))
-        })?
+        });
+        let applied_at = Instant::now();
+        let commit = commit_rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that commit the write was dropped".to_owned(),
+            ))
+        });
+        Self::to_write_response(res, commit, proposed_at, applied_at)
+            .map_err(|err| err.with_request_id(request_id))
     }
 
     pub fn write_block(
@@ -211,32 +515,100 @@ where
         term: u64,
         context: Option<Vec<u8>>,
         data: T::D,
-    ) -> Result<(T::R, Option<Vec<u8>>), Error> {
-        let rx = self.write_non_block(group_id, term, context, data)?;
-        rx.blocking_recv().map_err(|_| {
+    ) -> Result<WriteResponse<T::R>, Error> {
+        let request_id = self.id_generator.next_uuid();
+        let proposed_at = Instant::now();
+        let (commit_tx, commit_rx) = oneshot::channel();
+        let rx = self.write_with_request_id(
+            request_id,
+            group_id,
+            term,
+            context,
+            false,
+            None,
+            data,
+            Some(commit_tx),
+        )?;
+        let res = rx.blocking_recv().map_err(|_| {
             Error::Channel(ChannelError::SenderClosed(
                 "the sender that result the write was dropped".to_owned(),
             ))
-        })?
+        });
+        let applied_at = Instant::now();
+        let commit = commit_rx.blocking_recv().map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that commit the write was dropped".to_owned(),
+            ))
+        });
+        Self::to_write_response(res, commit, proposed_at, applied_at)
+            .map_err(|err| err.with_request_id(request_id))
     }
 
-    fn pre_propose_check(&self, group_id: u64) -> Result<(), Error> {
+    fn to_write_response(
+        res: Result<Result<(T::R, Option<Vec<u8>>), Error>, Error>,
+        commit: Result<WriteCommit, Error>,
+        proposed_at: Instant,
+        applied_at: Instant,
+    ) -> Result<WriteResponse<T::R>, Error> {
+        let (data, context) = res.and_then(|res| res)?;
+        // the commit metadata is always sent before the write is applied.
+        let commit = commit?;
+        Ok(WriteResponse {
+            data,
+            context,
+            index: commit.index,
+            term: commit.term,
+            commit_latency: commit.committed_at.saturating_duration_since(proposed_at),
+            apply_latency: applied_at.saturating_duration_since(proposed_at),
+        })
+    }
+
+    /// Check the proposal against the shared state of group, so the proposals
+    /// which would be rejected by the node are rejected without consuming the
+    /// capacity of the channel. `term` is the expected term of the proposal,
+    /// `0` means any term.
+    fn pre_propose_check(&self, group_id: u64, term: u64) -> Result<(), Error> {
         let state = self.shared_states.get(group_id).map_or(
             Err(Error::RaftGroup(RaftGroupError::Deleted(0, group_id))),
             |state| Ok(state),
         )?;
 
-        if !state.is_leader() {
-            return Err(Error::Propose(super::ProposeError::NotLeader {
+        // the parked group is materialized by the node when the proposal arrives.
+        if !state.is_leader() && !state.is_parked() {
+            return Err(Error::Propose(super::ProposeError::not_leader(
+                self.node_id,
+                group_id,
+                &state,
+            )));
+        }
+
+        if state.is_log_quota_exceeded() {
+            return Err(Error::Propose(super::ProposeError::LogQuotaExceeded {
                 node_id: self.node_id,
                 group_id,
-                replica_id: state.get_replica_id(),
             }));
         }
 
+        // the term of shared state never goes ahead of the group, so the
+        // proposal stale here is also stale on the node.
+        let current_term = state.get_term();
+        if term != 0 && current_term > term {
+            return Err(Error::Propose(super::ProposeError::Stale(
+                term,
+                current_term,
+            )));
+        }
+
         Ok(())
     }
 
+    fn authorize_proposal(&self, group_id: u64, op: Operation) -> Result<(), Error> {
+        match self.proposal_authorization.as_ref() {
+            Some(authorization) => authorization.authorize(group_id, op),
+            None => Ok(()),
+        }
+    }
+
     pub fn write_non_block(
         &self,
         group_id: u64,
@@ -244,25 +616,61 @@ where
         context: Option<Vec<u8>>,
         data: T::D,
     ) -> Result<oneshot::Receiver<Result<(T::R, Option<Vec<u8>>), Error>>, Error> {
-        let _ = self.pre_propose_check(group_id)?;
+        let request_id = self.id_generator.next_uuid();
+        self.write_with_request_id(request_id, group_id, term, context, false, None, data, None)
+    }
+
+    fn write_with_request_id(
+        &self,
+        request_id: Uuid,
+        group_id: u64,
+        term: u64,
+        context: Option<Vec<u8>>,
+        local_context: bool,
+        coalescing_key: Option<String>,
+        data: T::D,
+        commit_tx: Option<oneshot::Sender<WriteCommit>>,
+    ) -> Result<oneshot::Receiver<Result<(T::R, Option<Vec<u8>>), Error>>, Error> {
+        self.authorize_proposal(group_id, Operation::Propose)
+            .map_err(|err| err.with_request_id(request_id))?;
+
+        // the unknown group is created by the node when the write arrives.
+        if !self.auto_create_groups || self.shared_states.get(group_id).is_some() {
+            let _ = self
+                .pre_propose_check(group_id, term)
+                .map_err(|err| err.with_request_id(request_id))?;
+        }
 
+        trace!(
+            "node {}: write to group {}, request_id = {}",
+            self.node_id,
+            group_id,
+            request_id
+        );
         let (tx, rx) = oneshot::channel();
         match self
             .actor
             .propose_tx
             .try_send(ProposeMessage::Write(WriteRequest {
+                request_id,
                 group_id,
                 term,
                 data,
                 context,
+                local_context,
                 tx,
+                commit_tx,
+                enqueued_at: self.record_latency.then(Instant::now),
+                coalescing_key,
             })) {
             Err(TrySendError::Full(_)) => Err(Error::Channel(ChannelError::Full(
                 "channel no avaiable capacity for write".to_owned(),
-            ))),
+            ))
+            .with_request_id(request_id)),
             Err(TrySendError::Closed(_)) => Err(Error::Channel(ChannelError::ReceiverClosed(
                 "channel receiver closed for write".to_owned(),
-            ))),
+            ))
+            .with_request_id(request_id)),
             Ok(_) => Ok(rx),
         }
     }
@@ -274,148 +682,1867 @@ where
         context: Option<Vec<u8>>,
         data: MembershipChangeData,
     ) -> Result<(T::R, Option<Vec<u8>>), Error> {
-        let rx = self.membership_non_block(group_id, term, context, data)?;
-        rx.await.map_err(|_| {
-            Error::Channel(ChannelError::SenderClosed(
-                "the sender that result the membership change was dropped".to_owned(),
-            ))
-        })?
+        let request_id = self.id_generator.next_uuid();
+        let rx = self.membership_with_request_id(request_id, group_id, term, context, data)?;
+        rx.await
+            .map_err(|_| {
+                Error::Channel(ChannelError::SenderClosed(
+                    "the sender that result the membership change was dropped".to_owned(),
+                ))
+            })
+            .and_then(|res| res)
+            .map_err(|err| err.with_request_id(request_id))
     }
 
-    pub fn membership_block(
+    /// Propose the admin entry to the group, which replicates the control
+    /// command of the application through the log, e.g. "reconfigure
+    /// compaction". The entry is applied as `Apply::Admin` in the order of the
+    /// log on each replica, the payload is not decoded by the codec of the
+    /// proposals. Returns the response of the state machine of the leader.
+    ///
+    /// The admin entries are authorized by `Operation::Admin` if
+    /// `Config::authorize_proposals` is enabled.
+    pub async fn propose_admin(&self, group_id: u64, payload: AdminPayload) -> Result<T::R, Error> {
+        let request_id = self.id_generator.next_uuid();
+        self.authorize_proposal(group_id, Operation::Admin)
+            .and_then(|_| self.pre_propose_check(group_id, 0))
+            .map_err(|err| err.with_request_id(request_id))?;
+
+        trace!(
+            "node {}: propose admin {} to group {}, request_id = {}",
+            self.node_id,
+            payload.command,
+            group_id,
+            request_id
+        );
+        let (tx, rx) = oneshot::channel();
+        match self
+            .actor
+            .propose_tx
+            .try_send(ProposeMessage::Admin(AdminRequest {
+                request_id,
+                group_id,
+                payload,
+                tx,
+            })) {
+            Err(TrySendError::Full(_)) => {
+                return Err(Error::Channel(ChannelError::Full(
+                    "channel no available capacity for admin".to_owned(),
+                ))
+                .with_request_id(request_id))
+            }
+            Err(TrySendError::Closed(_)) => {
+                return Err(Error::Channel(ChannelError::ReceiverClosed(
+                    "channel receiver closed for admin".to_owned(),
+                ))
+                .with_request_id(request_id))
+            }
+            Ok(_) => {}
+        }
+
+        rx.await
+            .map_err(|_| {
+                Error::Channel(ChannelError::SenderClosed(
+                    "the sender that result the admin was dropped".to_owned(),
+                ))
+            })
+            .and_then(|res| res)
+            .map(|(res, _)| res)
+            .map_err(|err| err.with_request_id(request_id))
+    }
+
+    /// Allocate a new replica id for the group from storage and propose an
+    /// `AddNode` membership change to add the replica on `node_id`.
+    ///
+    /// Returns the allocated replica id along with the result of the membership
+    /// change.
+    pub async fn add_replica(
         &self,
         group_id: u64,
+        node_id: u64,
         term: Option<u64>,
         context: Option<Vec<u8>>,
-        data: MembershipChangeData,
-    ) -> Result<(T::R, Option<Vec<u8>>), Error> {
-        let rx = self.membership_non_block(group_id, term, context, data)?;
-        rx.blocking_recv().map_err(|_| {
-            Error::Channel(ChannelError::SenderClosed(
-                "the sender that result the membership change was dropped".to_owned(),
-            ))
-        })?
+    ) -> Result<(u64, T::R, Option<Vec<u8>>), Error> {
+        let replica_id = self.storage.next_replica_id(group_id).await?;
+        let data = MembershipChangeData {
+            changes: vec![SingleMembershipChange {
+                node_id,
+                replica_id,
+                change_type: ConfChangeType::AddNode as i32,
+                witness: false,
+            }],
+            replicas: vec![ReplicaDesc {
+                node_id,
+                group_id,
+                replica_id,
+                witness: false,
+            }],
+            ..Default::default()
+        };
+        let (res, ctx) = self.membership(group_id, term, context, data).await?;
+        Ok((replica_id, res, ctx))
     }
 
-    pub fn membership_non_block(
+    /// Allocate a new replica id for the group from storage and propose an
+    /// `AddLearnerNode` membership change to add the learner on `node_id`,
+    /// returns the allocated replica id. The node of the learner creates the
+    /// group with the replica id, e.g. by `create_group` or by the messages
+    /// from the leader unless `Config::buffer_unknown_group_msgs`.
+    pub async fn add_learner(&self, group_id: u64, node_id: u64) -> Result<u64, Error> {
+        let replica_id = self.storage.next_replica_id(group_id).await?;
+        let data = MembershipChangeData {
+            changes: vec![SingleMembershipChange {
+                node_id,
+                replica_id,
+                change_type: ConfChangeType::AddLearnerNode as i32,
+                witness: false,
+            }],
+            replicas: vec![ReplicaDesc {
+                node_id,
+                group_id,
+                replica_id,
+                witness: false,
+            }],
+            ..Default::default()
+        };
+        self.membership(group_id, None, None, data).await?;
+        Ok(replica_id)
+    }
+
+    /// Promote the learner of the group on `node_id` to voter by an `AddNode`
+    /// membership change. The learner must have caught up with the leader,
+    /// that is the matched index of the learner is within `max_lag` entries of
+    /// the committed index of the leader, otherwise
+    /// `Error::LearnerNotCaughtUp` is returned and the learner is kept.
+    ///
+    /// ## Notes
+    /// Must be called on the node of the leader of the group, otherwise
+    /// `ProposeError::NotLeader` is returned. Returns `Error::BadParameter` if
+    /// the replica is not a learner of the group.
+    pub async fn promote_learner(
         &self,
         group_id: u64,
-        term: Option<u64>,
-        context: Option<Vec<u8>>,
-        data: MembershipChangeData,
-    ) -> Result<oneshot::Receiver<Result<(T::R, Option<Vec<u8>>), Error>>, Error> {
-        let _ = self.pre_propose_check(group_id)?;
+        node_id: u64,
+        replica_id: u64,
+        max_lag: u64,
+    ) -> Result<(T::R, Option<Vec<u8>>), Error> {
+        let is_learner = self.group_state(group_id).map_or(false, |state| {
+            state.get_conf_state().learners.contains(&replica_id)
+        });
+        if !is_learner {
+            return Err(Error::BadParameter(format!(
+                "replica {} is not a learner of group {}",
+                replica_id, group_id
+            )));
+        }
 
-        let (tx, rx) = oneshot::channel();
+        let (matched, committed) = self
+            .replica_progress(group_id, replica_id)
+            .await?
+            .ok_or_else(|| {
+                Error::BadParameter(format!(
+                    "replica {} is not a learner of group {}",
+                    replica_id, group_id
+                ))
+            })?;
+        if matched + max_lag < committed {
+            return Err(Error::LearnerNotCaughtUp {
+                group_id,
+                replica_id,
+                matched,
+                committed,
+            });
+        }
 
-        let request = MembershipRequest {
-            group_id,
-            term,
-            context,
-            data,
-            tx,
+        let data = MembershipChangeData {
+            changes: vec![SingleMembershipChange {
+                node_id,
+                replica_id,
+                change_type: ConfChangeType::AddNode as i32,
+                witness: false,
+            }],
+            replicas: vec![ReplicaDesc {
+                node_id,
+                group_id,
+                replica_id,
+                witness: false,
+            }],
+            ..Default::default()
         };
+        self.membership(group_id, None, None, data).await
+    }
 
-        match self
-            .actor
-            .propose_tx
-            .try_send(ProposeMessage::Membership(request))
-        {
-            Err(TrySendError::Full(_)) => Err(Error::Channel(ChannelError::Full(
-                "channel no available capacity for memberhsip".to_owned(),
-            ))),
-            Err(TrySendError::Closed(_)) => Err(Error::Channel(ChannelError::ReceiverClosed(
-                "channel receiver closed for membership".to_owned(),
-            ))),
-            Ok(_) => Ok(rx),
+    /// Designate the standby replica of the group on `node_id`, returns the
+    /// replica id allocated for it. The standby is not a member of the group,
+    /// the node of the standby tails the committed log of the group by
+    /// `standby_entries` and `tail_standby` until it's promoted to voter by
+    /// `promote_standby`, so the promotion needs near-zero catch-up.
+    ///
+    /// ## Notes
+    /// The designations are kept in memory on the node, they are lost after
+    /// the node restarts.
+    pub async fn designate_standby(&self, group_id: u64, node_id: u64) -> Result<u64, Error> {
+        if let Some(standby) = self.standbys.get(group_id, node_id) {
+            return Ok(standby.replica_id);
+        }
+
+        let replica_id = self.storage.next_replica_id(group_id).await?;
+        let standby = ReplicaDesc {
+            node_id,
+            group_id,
+            replica_id,
+            witness: false,
+        };
+        if !self.standbys.designate(standby) {
+            return Err(Error::BadParameter(format!(
+                "group {} already has a standby on node {}",
+                group_id, node_id
+            )));
         }
+        Ok(replica_id)
     }
 
-    /// `read_index` is use **read_index algorithm** to read data
-    /// from a specific group.
-    ///
-    /// `read_index` is a blocking interface in an asynchronous environment,
-    /// and the user should use `.await` to wait for it to complete. If executed
-    /// successfully, it returns the associated `context`, and at this point, the
-    /// caller can **safely** read data from the state machine. If it fails, an
-    /// error is returned.
+    /// The standby replicas of the group designated on the node.
+    pub fn standbys(&self, group_id: u64) -> Vec<ReplicaDesc> {
+        self.standbys.list(group_id)
+    }
 
-    /// ## Parameters
-    /// - `group_id`: The specific group to read from.
-    /// - `context`: The context associated with the read. The context goes through
-    /// the context pipeline during the read.
+    /// Promote the standby replica of the group on `node_id` to voter by an
+    /// `AddNode` membership change, returns the replica id of the standby
+    /// along with the result of the membership change. The node of the
+    /// standby creates the group with the replica id, e.g. by `create_group`
+    /// or by the messages from the leader unless
+    /// `Config::buffer_unknown_group_msgs`, whose raft log is already tailed.
     ///
     /// ## Notes
-    /// read_index implements the approach described in the Raft paper where read
-    /// requests do not need to be written to the Raft log, avoiding the cost of
-    /// writing to disk.
-    ///
-    /// ## Errors
-    /// Most errors require retries. The following error requires a different
-    /// handling approach:
-    /// - `ProposeError::NotLeader`: The application can refresh the leader and
-    /// retry based on the error information using the route table.
-    ///
-    /// ## Panics
-    pub async fn read_index(
+    /// Must be called on the node of the leader of the group, otherwise
+    /// `ProposeError::NotLeader` is returned. Returns `Error::BadParameter` if
+    /// the standby is not designated on the node.
+    pub async fn promote_standby(
         &self,
         group_id: u64,
-        context: Option<Vec<u8>>,
-    ) -> Result<Option<Vec<u8>>, Error> {
-        let rx = self.read_index_non_block(group_id, context)?;
-        rx.await.map_err(|_| {
-            Error::Channel(ChannelError::SenderClosed(
-                "the sender that result the read_index change was dropped".to_owned(),
+        node_id: u64,
+    ) -> Result<(u64, T::R, Option<Vec<u8>>), Error> {
+        let standby = self.standbys.get(group_id, node_id).ok_or_else(|| {
+            Error::BadParameter(format!(
+                "group {} has no standby on node {}",
+                group_id, node_id
             ))
-        })?
+        })?;
+        let data = MembershipChangeData {
+            changes: vec![SingleMembershipChange {
+                node_id,
+                replica_id: standby.replica_id,
+                change_type: ConfChangeType::AddNode as i32,
+                witness: false,
+            }],
+            replicas: vec![standby.clone()],
+            ..Default::default()
+        };
+        let (res, ctx) = self.membership(group_id, None, None, data).await?;
+        self.standbys.remove(group_id, node_id);
+        Ok((standby.replica_id, res, ctx))
     }
 
-    pub fn read_index_block(
+    /// Returns the committed entries of the local replica of the group after
+    /// the index `after` in the order of index, at most `max_size` bytes but
+    /// at least one entry. The entries are tailed by the standby replicas by
+    /// `tail_standby`. Returns `storage::Error::LogCompacted` if the entries
+    /// are compacted, the standby installs the snapshot of `standby_snapshot`
+    /// instead.
+    pub async fn standby_entries(
         &self,
         group_id: u64,
-        context: Option<Vec<u8>>,
-    ) -> Result<Option<Vec<u8>>, Error> {
-        let rx = self.read_index_non_block(group_id, context)?;
-        rx.blocking_recv().map_err(|_| {
-            Error::Channel(ChannelError::SenderClosed(
-                "the sender that result the read_index was dropped".to_owned(),
-            ))
-        })?
+        after: u64,
+        max_size: Option<u64>,
+    ) -> Result<Vec<Entry>, Error> {
+        let commit_index = self
+            .group_state(group_id)
+            .map_or(0, |state| state.get_commit_index());
+        if commit_index <= after {
+            return Ok(vec![]);
+        }
+
+        let replica_id = self.group_replica(group_id).await?;
+        let gs = self.storage.group_storage(group_id, replica_id).await?;
+        let entries = gs
+            .entries(
+                after + 1,
+                commit_index + 1,
+                max_size,
+                GetEntriesContext::empty(false),
+            )
+            .map_err(super::storage::Error::from)?;
+        Ok(entries)
     }
 
-    pub fn read_index_non_block(
+    /// Returns the snapshot of the local replica of the group, which is
+    /// installed by the standby replicas whose tailed log is compacted.
+    pub async fn standby_snapshot(&self, group_id: u64) -> Result<Snapshot, Error> {
+        let replica_id = self.group_replica(group_id).await?;
+        let gs = self.storage.group_storage(group_id, replica_id).await?;
+        let snapshot = gs
+            .snapshot(0, replica_id)
+            .map_err(super::storage::Error::from)?;
+        Ok(snapshot)
+    }
+
+    /// Append the committed entries of the group, e.g. by `standby_entries`
+    /// of the leader, to the raft log of the standby replica on the node and
+    /// commit them, returns the last index tailed. The entries at or before
+    /// the last index of the log are skipped, the group is not created on the
+    /// node until the standby is promoted.
+    pub async fn tail_standby(
         &self,
         group_id: u64,
-        context: Option<Vec<u8>>,
-    ) -> Result<oneshot::Receiver<Result<Option<Vec<u8>>, Error>>, Error> {
-        let (tx, rx) = oneshot::channel();
-        match self
-            .actor
-            .propose_tx
-            .try_send(ProposeMessage::ReadIndexData(ReadIndexData {
-                group_id,
-                context: ReadIndexContext {
-                    uuid: Uuid::new_v4().into_bytes(),
-                    context,
-                },
-                tx,
-            })) {
-            Err(TrySendError::Full(_)) => Err(Error::Channel(ChannelError::Full(
-                "channel no available capacity for read_index".to_owned(),
-            ))),
-            Err(TrySendError::Closed(_)) => Err(Error::Channel(ChannelError::ReceiverClosed(
-                "channel receiver closed for read_index".to_owned(),
-            ))),
-            Ok(_) => Ok(rx),
+        replica_id: u64,
+        entries: Vec<Entry>,
+    ) -> Result<u64, Error> {
+        let gs = self.storage.group_storage(group_id, replica_id).await?;
+        let last_index = gs.last_index().map_err(super::storage::Error::from)?;
+        let entries = entries
+            .into_iter()
+            .filter(|ent| ent.index > last_index)
+            .collect::<Vec<_>>();
+        let first_index = match entries.first() {
+            None => return Ok(last_index),
+            Some(ent) => ent.index,
+        };
+        if first_index != last_index + 1 {
+            return Err(Error::BadParameter(format!(
+                "group {} standby tails entries from {}, but last index is {}",
+                group_id, first_index, last_index
+            )));
         }
+
+        let last_index = entries[entries.len() - 1].index;
+        gs.append(&entries).await?;
+        gs.set_hardstate_commit(last_index).await?;
+        Ok(last_index)
     }
 
-    /// Campaign and wait raft group by given `group_id`.
+    /// Install the snapshot of the group, e.g. by `standby_snapshot` of the
+    /// leader, to the standby replica on the node, the standby tails the
+    /// entries after the snapshot.
+    pub async fn install_standby_snapshot(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+        snapshot: Snapshot,
+    ) -> Result<(), Error> {
+        let gs = self.storage.group_storage(group_id, replica_id).await?;
+        gs.install_snapshot(snapshot).await?;
+        Ok(())
+    }
+
+    /// Change the voters of the group to the replicas of `targets` in joint
+    /// consensus, returns the membership applied on the node.
     ///
-    /// `campaign` is synchronous and waits for the campaign to submitted a
-    /// result to raft.
-    pub async fn campaign_group(&self, group_id: u64) -> Result<(), Error> {
-        let rx = self.campaign_group_non_block(group_id);
+    /// The changes are computed against the membership applied on the node,
+    /// the replicas of `targets` which are not voters are added (the learners
+    /// are promoted) and the voters not in `targets` are removed by one
+    /// `ConfChangeV2`, which enters the joint state and leaves it automatically.
+    /// The learners not in `targets` are kept. The membership change pending
+    /// and the storage temporarily unavailable are retried every tick until
+    /// `timeout`, the changes are computed again by each retry.
+    ///
+    /// ## Notes
+    /// Must be called on the node of the leader of the group, otherwise
+    /// `ProposeError::NotLeader` is returned. The replica ids of `targets`
+    /// should be allocated by `MultiRaftStorage::next_replica_id`.
+    pub async fn change_membership(
+        &self,
+        group_id: u64,
+        targets: Vec<ReplicaDesc>,
+        timeout: Duration,
+    ) -> Result<ConfState, Error> {
+        let state = match self.group_state(group_id) {
+            Some(state) => state,
+            None => {
+                return Err(Error::RaftGroup(RaftGroupError::NotExist(
+                    self.node_id,
+                    group_id,
+                )))
+            }
+        };
+        let deadline = Instant::now() + timeout;
+        loop {
+            // the joint state is left by the leader once it's applied.
+            let conf_state = state.get_conf_state();
+            if conf_state.voters_outgoing.is_empty() {
+                let data = self
+                    .membership_changes(group_id, &conf_state, &targets)
+                    .await?;
+                if data.changes.is_empty() {
+                    return Ok(conf_state);
+                }
+
+                match self.membership(group_id, None, None, data).await {
+                    Ok(_) => continue,
+                    Err(err) if Self::is_transient_membership_error(&err) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(Error::MembershipChangeTimeout {
+                    group_id,
+                    voters: targets.iter().map(|target| target.replica_id).collect(),
+                });
+            }
+            tokio::time::sleep(Duration::from_millis(self.tick_interval)).await;
+        }
+    }
+
+    /// Compute the membership changes from the voters of `conf_state` to the
+    /// replicas of `targets`.
+    async fn membership_changes(
+        &self,
+        group_id: u64,
+        conf_state: &ConfState,
+        targets: &[ReplicaDesc],
+    ) -> Result<MembershipChangeData, Error> {
+        let mut data = MembershipChangeData {
+            transition: ConfChangeTransition::Auto as i32,
+            ..Default::default()
+        };
+        for target in targets {
+            if conf_state.voters.contains(&target.replica_id) {
+                continue;
+            }
+            data.changes.push(SingleMembershipChange {
+                node_id: target.node_id,
+                replica_id: target.replica_id,
+                change_type: ConfChangeType::AddNode as i32,
+                witness: target.witness,
+            });
+            data.replicas.push(ReplicaDesc {
+                group_id,
+                ..target.clone()
+            });
+        }
+
+        let target_ids = targets
+            .iter()
+            .map(|target| target.replica_id)
+            .collect::<Vec<_>>();
+        for replica_id in conf_state.voters.iter() {
+            if target_ids.contains(replica_id) {
+                continue;
+            }
+            let replica = self
+                .storage
+                .get_replica_desc(group_id, *replica_id)
+                .await?
+                .ok_or_else(|| {
+                    Error::BadParameter(format!(
+                        "group {} has no descriptor of voter {}",
+                        group_id, replica_id
+                    ))
+                })?;
+            data.changes.push(SingleMembershipChange {
+                node_id: replica.node_id,
+                replica_id: replica.replica_id,
+                change_type: ConfChangeType::RemoveNode as i32,
+                witness: replica.witness,
+            });
+            data.replicas.push(replica);
+        }
+        Ok(data)
+    }
+
+    fn is_transient_membership_error(err: &Error) -> bool {
+        matches!(
+            err.without_request_id(),
+            Error::Propose(super::ProposeError::MembershipPending(..))
+                | Error::Storage(super::storage::Error::StorageTemporarilyUnavailable)
+                | Error::Channel(ChannelError::Full(_))
+        )
+    }
+
+    /// Transfer the leadership of the group to the voter `transferee_replica_id`
+    /// and wait for the transferee to be elected, returns the replica of the new
+    /// leader. `Error::LeaderTransferFailed` is returned if another replica is
+    /// elected, `Error::LeaderTransferTimeout` if the transferee is not elected
+    /// in `timeout`, e.g. it doesn't catch up with the leader in the election
+    /// timeout so the transfer is aborted by raft.
+    ///
+    /// ## Notes
+    /// Must be called on the node of the leader of the group, otherwise
+    /// `ProposeError::NotLeader` is returned.
+    pub async fn transfer_leader(
+        &self,
+        group_id: u64,
+        transferee_replica_id: u64,
+        timeout: Duration,
+    ) -> Result<ReplicaDesc, Error> {
+        let state = match self.group_state(group_id) {
+            Some(state) => state,
+            None => {
+                return Err(Error::RaftGroup(RaftGroupError::NotExist(
+                    self.node_id,
+                    group_id,
+                )))
+            }
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.management_request(ManageMessage::TransferLeader(
+            group_id,
+            transferee_replica_id,
+            tx,
+        ))?;
+        let term = rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the leader transfer was dropped".to_owned(),
+            ))
+        })??;
+
+        // the leader change is observed by the group state, which is updated
+        // when `Event::LederElection` is sent, so the events of the
+        // application are not consumed.
+        let deadline = Instant::now() + timeout;
+        loop {
+            let leader = state.get_leader_id();
+            if leader == transferee_replica_id {
+                break;
+            }
+            if state.get_term() > term && leader != NO_LEADER && leader != state.get_replica_id() {
+                return Err(Error::LeaderTransferFailed {
+                    group_id,
+                    transferee: transferee_replica_id,
+                    leader,
+                });
+            }
+
+            if Instant::now() >= deadline {
+                return Err(Error::LeaderTransferTimeout {
+                    group_id,
+                    transferee: transferee_replica_id,
+                });
+            }
+            tokio::time::sleep(Duration::from_millis(self.tick_interval)).await;
+        }
+
+        self.storage
+            .get_replica_desc(group_id, transferee_replica_id)
+            .await?
+            .ok_or_else(|| {
+                Error::BadParameter(format!(
+                    "group {} has no descriptor of replica {}",
+                    group_id, transferee_replica_id
+                ))
+            })
+    }
+
+    /// Relocate the replica `from_replica_id` on `from_node_id` of the group to
+    /// `to_node_id`, returns the replica id allocated for the target replica.
+    ///
+    /// The relocation is driven by membership changes and keeps the group at full
+    /// replication throughout:
+    /// 1. The target replica is added as learner, the leader pre-copies data
+    ///    (snapshot and logs) to it.
+    /// 2. Wait for the target replica to catch up with the leader in `timeout`.
+    /// 3. Promote the target replica to voter and remove the source replica with
+    ///    a `ConfChangeV2` in joint consensus.
+    ///
+    /// If the source replica is the leader, the target replica is promoted
+    /// alone and the leadership is transferred to it in `timeout`, then the
+    /// removal of the source replica is forwarded to the new leader by raft
+    /// and waited to be applied in `timeout`, so the leader never removes
+    /// itself.
+    ///
+    /// The progress is reported by `Event::Relocation` and tracked as the
+    /// operation, see `operation_progress`. If any step after the learner is
+    /// added fails before the target replica is promoted, the target replica
+    /// is removed from the group.
+    ///
+    /// ## Notes
+    /// Must be called on the node of the leader of the group, otherwise
+    /// `ProposeError::NotLeader` is returned.
+    pub async fn relocate_replica(
+        &self,
+        group_id: u64,
+        from_node_id: u64,
+        from_replica_id: u64,
+        to_node_id: u64,
+        timeout: Duration,
+    ) -> Result<u64, Error> {
+        let op_id = self.start_operation(OperationKind::RelocateReplica, group_id);
+        self.relocate_operation(
+            op_id,
+            group_id,
+            from_node_id,
+            from_replica_id,
+            to_node_id,
+            timeout,
+        )
+        .await
+    }
+
+    /// Start `relocate_replica` on a spawned task, returns the id of the
+    /// operation along with the handle of the task, so the progress is
+    /// queried by `operation_progress` before the relocation finishes.
+    pub fn start_relocate_replica(
+        self: &Arc<Self>,
+        group_id: u64,
+        from_node_id: u64,
+        from_replica_id: u64,
+        to_node_id: u64,
+        timeout: Duration,
+    ) -> (u64, JoinHandle<Result<u64, Error>>) {
+        let op_id = self.start_operation(OperationKind::RelocateReplica, group_id);
+        let this = self.clone();
+        let handle = tokio::spawn(async move {
+            this.relocate_operation(
+                op_id,
+                group_id,
+                from_node_id,
+                from_replica_id,
+                to_node_id,
+                timeout,
+            )
+            .await
+        });
+        (op_id, handle)
+    }
+
+    async fn relocate_operation(
+        &self,
+        op_id: u64,
+        group_id: u64,
+        from_node_id: u64,
+        from_replica_id: u64,
+        to_node_id: u64,
+        timeout: Duration,
+    ) -> Result<u64, Error> {
+        let res = self
+            .relocate(
+                op_id,
+                group_id,
+                from_node_id,
+                from_replica_id,
+                to_node_id,
+                timeout,
+            )
+            .await;
+        self.finish_operation(op_id, &res);
+        res
+    }
+
+    async fn relocate(
+        &self,
+        op_id: u64,
+        group_id: u64,
+        from_node_id: u64,
+        from_replica_id: u64,
+        to_node_id: u64,
+        timeout: Duration,
+    ) -> Result<u64, Error> {
+        let to_replica_id = self.storage.next_replica_id(group_id).await?;
+        let leader_source = self.group_state(group_id).map_or(false, |state| {
+            state.is_leader() && state.get_replica_id() == from_replica_id
+        });
+        let data = MembershipChangeData {
+            changes: vec![SingleMembershipChange {
+                node_id: to_node_id,
+                replica_id: to_replica_id,
+                change_type: ConfChangeType::AddLearnerNode as i32,
+                witness: false,
+            }],
+            replicas: vec![ReplicaDesc {
+                node_id: to_node_id,
+                group_id,
+                replica_id: to_replica_id,
+                witness: false,
+            }],
+            ..Default::default()
+        };
+        self.membership(group_id, None, None, data).await?;
+        self.emit_relocation(
+            op_id,
+            group_id,
+            from_replica_id,
+            to_replica_id,
+            RelocationStage::LearnerAdded,
+        );
+
+        if let Err(err) = self
+            .relocate_promote(
+                op_id,
+                group_id,
+                from_node_id,
+                from_replica_id,
+                to_node_id,
+                to_replica_id,
+                leader_source,
+                timeout,
+            )
+            .await
+        {
+            self.relocate_cleanup(op_id, group_id, from_replica_id, to_node_id, to_replica_id)
+                .await;
+            return Err(err);
+        }
+
+        if leader_source {
+            self.relocate_leader(
+                op_id,
+                group_id,
+                from_node_id,
+                from_replica_id,
+                to_replica_id,
+                timeout,
+            )
+            .await?;
+        }
+
+        self.emit_relocation(
+            op_id,
+            group_id,
+            from_replica_id,
+            to_replica_id,
+            RelocationStage::Promoted,
+        );
+        Ok(to_replica_id)
+    }
+
+    async fn relocate_promote(
+        &self,
+        op_id: u64,
+        group_id: u64,
+        from_node_id: u64,
+        from_replica_id: u64,
+        to_node_id: u64,
+        to_replica_id: u64,
+        leader_source: bool,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        // wait for the learner to catch up with the leader.
+        let deadline = Instant::now() + timeout;
+        let mut last_matched = None;
+        loop {
+            if let Some((matched, committed)) =
+                self.replica_progress(group_id, to_replica_id).await?
+            {
+                if last_matched != Some(matched) {
+                    last_matched = Some(matched);
+                    self.emit_relocation(
+                        op_id,
+                        group_id,
+                        from_replica_id,
+                        to_replica_id,
+                        RelocationStage::CatchingUp { matched, committed },
+                    );
+                }
+
+                if matched >= committed {
+                    break;
+                }
+            }
+
+            if Instant::now() >= deadline {
+                return Err(Error::RelocationTimeout {
+                    group_id,
+                    replica_id: to_replica_id,
+                });
+            }
+            tokio::time::sleep(Duration::from_millis(self.tick_interval)).await;
+        }
+
+        // the leader source is removed after the leadership is transferred.
+        if leader_source {
+            let data = MembershipChangeData {
+                changes: vec![SingleMembershipChange {
+                    node_id: to_node_id,
+                    replica_id: to_replica_id,
+                    change_type: ConfChangeType::AddNode as i32,
+                    witness: false,
+                }],
+                replicas: vec![ReplicaDesc {
+                    node_id: to_node_id,
+                    group_id,
+                    replica_id: to_replica_id,
+                    witness: false,
+                }],
+                ..Default::default()
+            };
+            self.membership(group_id, None, None, data).await?;
+            return Ok(());
+        }
+
+        // promote the learner and remove the source replica in joint consensus.
+        let data = MembershipChangeData {
+            transition: ConfChangeTransition::Auto as i32,
+            changes: vec![
+                SingleMembershipChange {
+                    node_id: to_node_id,
+                    replica_id: to_replica_id,
+                    change_type: ConfChangeType::AddNode as i32,
+                    witness: false,
+                },
+                SingleMembershipChange {
+                    node_id: from_node_id,
+                    replica_id: from_replica_id,
+                    change_type: ConfChangeType::RemoveNode as i32,
+                    witness: false,
+                },
+            ],
+            replicas: vec![
+                ReplicaDesc {
+                    node_id: to_node_id,
+                    group_id,
+                    replica_id: to_replica_id,
+                    witness: false,
+                },
+                ReplicaDesc {
+                    node_id: from_node_id,
+                    group_id,
+                    replica_id: from_replica_id,
+                    witness: false,
+                },
+            ],
+        };
+        self.membership(group_id, None, None, data).await?;
+        Ok(())
+    }
+
+    /// Transfer the leadership of the source replica to the promoted target
+    /// replica, then remove the source replica by the new leader.
+    async fn relocate_leader(
+        &self,
+        op_id: u64,
+        group_id: u64,
+        from_node_id: u64,
+        from_replica_id: u64,
+        to_replica_id: u64,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        self.transfer_leader(group_id, to_replica_id, timeout)
+            .await?;
+        self.emit_relocation(
+            op_id,
+            group_id,
+            from_replica_id,
+            to_replica_id,
+            RelocationStage::LeaderTransferred,
+        );
+
+        let data = MembershipChangeData {
+            changes: vec![SingleMembershipChange {
+                node_id: from_node_id,
+                replica_id: from_replica_id,
+                change_type: ConfChangeType::RemoveNode as i32,
+                witness: false,
+            }],
+            replicas: vec![ReplicaDesc {
+                node_id: from_node_id,
+                group_id,
+                replica_id: from_replica_id,
+                witness: false,
+            }],
+            ..Default::default()
+        };
+        let deadline = Instant::now() + timeout;
+        let mut ticks = 0;
+        loop {
+            // the removal is observed by the membership applied on the node.
+            let voters = match self.group_state(group_id) {
+                Some(state) => state.get_conf_state().voters,
+                None => return Ok(()),
+            };
+            if !voters.contains(&from_replica_id) {
+                return Ok(());
+            }
+
+            if Instant::now() >= deadline {
+                return Err(Error::MembershipChangeTimeout {
+                    group_id,
+                    voters: voters
+                        .into_iter()
+                        .filter(|voter| *voter != from_replica_id)
+                        .collect(),
+                });
+            }
+
+            if ticks % RELOCATION_FORWARD_TICKS == 0 {
+                if let Err(err) = self.forward_membership(group_id, data.clone()).await {
+                    warn!(
+                        "node {}: forward removal of relocated replica {} of group {} error: {}",
+                        self.node_id, from_replica_id, group_id, err
+                    );
+                }
+            }
+            ticks += 1;
+            tokio::time::sleep(Duration::from_millis(self.tick_interval)).await;
+        }
+    }
+
+    /// Propose the membership change on the replica of the node without
+    /// waiting for it, the follower forwards it to the leader by raft.
+    async fn forward_membership(
+        &self,
+        group_id: u64,
+        data: MembershipChangeData,
+    ) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.management_request(ManageMessage::ForwardMembership(group_id, data, tx))?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the forwarded membership change was dropped".to_owned(),
+            ))
+        })?
+    }
+
+    /// Remove the target replica of the failed relocation.
+    async fn relocate_cleanup(
+        &self,
+        op_id: u64,
+        group_id: u64,
+        from_replica_id: u64,
+        to_node_id: u64,
+        to_replica_id: u64,
+    ) {
+        let data = MembershipChangeData {
+            changes: vec![SingleMembershipChange {
+                node_id: to_node_id,
+                replica_id: to_replica_id,
+                change_type: ConfChangeType::RemoveNode as i32,
+                witness: false,
+            }],
+            replicas: vec![ReplicaDesc {
+                node_id: to_node_id,
+                group_id,
+                replica_id: to_replica_id,
+                witness: false,
+            }],
+            ..Default::default()
+        };
+        if let Err(err) = self.membership(group_id, None, None, data).await {
+            error!(
+                "node {}: cleanup relocation target replica {} of group {} error: {}",
+                self.node_id, to_replica_id, group_id, err
+            );
+        }
+
+        self.emit_relocation(
+            op_id,
+            group_id,
+            from_replica_id,
+            to_replica_id,
+            RelocationStage::Failed,
+        );
+    }
+
+    fn emit_relocation(
+        &self,
+        op_id: u64,
+        group_id: u64,
+        from_replica_id: u64,
+        to_replica_id: u64,
+        stage: RelocationStage,
+    ) {
+        let (step, done, total) = match stage {
+            RelocationStage::LearnerAdded => ("learner added", 0, 0),
+            RelocationStage::CatchingUp { matched, committed } => {
+                ("catching up", matched, committed)
+            }
+            RelocationStage::LeaderTransferred => ("leader transferred", 0, 0),
+            RelocationStage::Promoted => ("promoted", 0, 0),
+            RelocationStage::Failed => ("target removed", 0, 0),
+        };
+        let mut event_chan = self.event_bcast.clone();
+        event_chan.push(Event::Relocation {
+            group_id,
+            from_replica_id,
+            to_replica_id,
+            stage,
+        });
+        if let Some(progress) = self.operations.update(op_id, step, done, total) {
+            event_chan.push(Event::OperationProgress(progress));
+        }
+        event_chan.flush();
+    }
+
+    /// Track the long-running operation, the id of the operation is sent by
+    /// `Event::OperationProgress` when it's started.
+    fn start_operation(&self, kind: OperationKind, group_id: u64) -> u64 {
+        let op_id = self.id_generator.next_u64();
+        let progress = self.operations.start(op_id, kind, group_id);
+        let mut event_chan = self.event_bcast.clone();
+        event_chan.push(Event::OperationProgress(progress));
+        event_chan.flush();
+        op_id
+    }
+
+    fn update_operation(&self, op_id: u64, step: &str, done: u64, total: u64) {
+        if let Some(progress) = self.operations.update(op_id, step, done, total) {
+            let mut event_chan = self.event_bcast.clone();
+            event_chan.push(Event::OperationProgress(progress));
+            event_chan.flush();
+        }
+    }
+
+    fn finish_operation<V>(&self, op_id: u64, res: &Result<V, Error>) {
+        let state = match res {
+            Ok(_) => OperationState::Succeeded,
+            Err(err) => OperationState::Failed(err.to_string()),
+        };
+        if let Some(progress) = self.operations.finish(op_id, state) {
+            let mut event_chan = self.event_bcast.clone();
+            event_chan.push(Event::OperationProgress(progress));
+            event_chan.flush();
+        }
+    }
+
+    /// Returns the progress of the operation on the node, e.g.
+    /// `relocate_replica`, `fork_group`, `balance` and `upload_snapshot`. The
+    /// id of the operation is returned by their `start_*` variants. The
+    /// running operations and the recently finished operations are kept,
+    /// `None` if the operation is unknown or evicted.
+    pub fn operation_progress(&self, op_id: u64) -> Option<OperationProgress> {
+        self.operations.get(op_id)
+    }
+
+    /// Returns the running and the recently finished operations on the node
+    /// in the order of start, so the control plane finds the operations to
+    /// monitor after it restarts.
+    pub fn operations(&self) -> Vec<OperationProgress> {
+        self.operations.list()
+    }
+
+    /// Resume the snapshot upload by `SnapshotUpload::resume` and track it as
+    /// the operation, the step is measured by the acknowledged bytes. The
+    /// failed upload can be resumed again, which is tracked as another
+    /// operation.
+    pub async fn upload_snapshot<ST: SnapshotTransport>(
+        &self,
+        upload: &mut SnapshotUpload,
+        transport: &ST,
+    ) -> Result<(), Error> {
+        let op_id = self.start_operation(OperationKind::SnapshotUpload, upload.group_id());
+        self.upload_snapshot_operation(op_id, upload, transport)
+            .await
+    }
+
+    /// Start `upload_snapshot` on a spawned task, returns the id of the
+    /// operation along with the handle of the task, which returns the upload
+    /// back to be resumed if it failed.
+    pub fn start_upload_snapshot<ST: SnapshotTransport>(
+        self: &Arc<Self>,
+        mut upload: SnapshotUpload,
+        transport: Arc<ST>,
+    ) -> (u64, JoinHandle<(SnapshotUpload, Result<(), Error>)>) {
+        let op_id = self.start_operation(OperationKind::SnapshotUpload, upload.group_id());
+        let this = self.clone();
+        let handle = tokio::spawn(async move {
+            let res = this
+                .upload_snapshot_operation(op_id, &mut upload, transport.as_ref())
+                .await;
+            (upload, res)
+        });
+        (op_id, handle)
+    }
+
+    async fn upload_snapshot_operation<ST: SnapshotTransport>(
+        &self,
+        op_id: u64,
+        upload: &mut SnapshotUpload,
+        transport: &ST,
+    ) -> Result<(), Error> {
+        let mut events = self.event_bcast.clone();
+        let res = upload
+            .resume_with(transport, &mut events, |acked, total_size| {
+                self.update_operation(op_id, "sending", acked, total_size)
+            })
+            .await;
+        self.finish_operation(op_id, &res);
+        res
+    }
+
+    /// Query the matched index of the replica and the committed index of leader.
+    async fn replica_progress(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+    ) -> Result<Option<(u64, u64)>, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.actor
+            .query_group_tx
+            .send(QueryGroup::ReplicaProgress(group_id, replica_id, tx))
+            .map_err(|_| {
+                Error::Channel(ChannelError::ReceiverClosed(
+                    "channel receiver closed for query replica progress".to_owned(),
+                ))
+            })?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the replica progress was dropped".to_owned(),
+            ))
+        })?
+    }
+
+    /// List at most `limit` groups on the node whose group id is greater than
+    /// `cursor` in the order of group id. Pass `0` as the cursor to list from
+    /// the first group, and `next_cursor` of the returned page to list the
+    /// next page.
+    pub async fn list_groups_paged(&self, cursor: u64, limit: usize) -> Result<GroupPage, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.actor
+            .query_group_tx
+            .send(QueryGroup::ListGroups(cursor, limit, tx))
+            .map_err(|_| {
+                Error::Channel(ChannelError::ReceiverClosed(
+                    "channel receiver closed for query list groups".to_owned(),
+                ))
+            })?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the list groups was dropped".to_owned(),
+            ))
+        })
+    }
+
+    /// Returns the node-level summary of the groups, storage and peers on the
+    /// node, it is gathered by a single query to the node instead of querying
+    /// each group, which is suitable for a health endpoint.
+    pub async fn node_status(&self) -> Result<NodeStatus, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.actor
+            .query_group_tx
+            .send(QueryGroup::NodeStatus(tx))
+            .map_err(|_| {
+                Error::Channel(ChannelError::ReceiverClosed(
+                    "channel receiver closed for query node status".to_owned(),
+                ))
+            })?;
+        let mut status = rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the node status was dropped".to_owned(),
+            ))
+        })?;
+        status.storage_bytes = self.storage.approximate_size().await.ok();
+        Ok(status)
+    }
+
+    /// Returns the health of the endpoints of the peer node reported by the
+    /// transport, it's read without querying the node, see `PeerEndpoints`.
+    /// The health of all the peers is reported by `node_status` as well.
+    pub fn node_health(&self, node_id: u64) -> Vec<EndpointHealth> {
+        self.transport.endpoint_health(node_id)
+    }
+
+    /// Returns the status of the replica of the group on the node, the leader
+    /// reports the progress of the replicas as well, see `node_status` for the
+    /// summary of the node.
+    pub async fn group_status(&self, group_id: u64) -> Result<GroupStatus, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.actor
+            .query_group_tx
+            .send(QueryGroup::GroupStatus(group_id, tx))
+            .map_err(|_| {
+                Error::Channel(ChannelError::ReceiverClosed(
+                    "channel receiver closed for query group status".to_owned(),
+                ))
+            })?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the group status was dropped".to_owned(),
+            ))
+        })?
+    }
+
+    /// Collect the commit watermarks of the groups on the node at the same
+    /// time, all the entries at or below the watermarks are committed. It's a
+    /// consistent boundary across the groups for the tooling, e.g. backups
+    /// and changefeeds. All the groups on the node are collected if `groups`
+    /// is empty, the groups which do not exist are reported as missing.
+    ///
+    /// The watermark of a follower may lag behind the leader, so the cut
+    /// taken on the node holding the leaders is more recent.
+    pub async fn consistent_cut(&self, groups: &[u64]) -> Result<ConsistentCut, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.actor
+            .query_group_tx
+            .send(QueryGroup::ConsistentCut(groups.to_vec(), tx))
+            .map_err(|_| {
+                Error::Channel(ChannelError::ReceiverClosed(
+                    "channel receiver closed for query consistent cut".to_owned(),
+                ))
+            })?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the consistent cut was dropped".to_owned(),
+            ))
+        })
+    }
+
+    /// Returns the groups on the node with their member replicas, nodes,
+    /// leaders and the health of the replicas tracked by the leaders on the
+    /// node, the parked groups are included.
+    pub async fn topology(&self) -> Result<NodeTopology, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.actor
+            .query_group_tx
+            .send(QueryGroup::Topology(tx))
+            .map_err(|_| {
+                Error::Channel(ChannelError::ReceiverClosed(
+                    "channel receiver closed for query topology".to_owned(),
+                ))
+            })?;
+        let mut topology = rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the topology was dropped".to_owned(),
+            ))
+        })?;
+
+        for group in topology.groups.iter_mut() {
+            let replica_descs = self.storage.scan_group_replica_desc(group.group_id).await?;
+            for replica in group.replicas.iter_mut() {
+                if let Some(desc) = replica_descs
+                    .iter()
+                    .find(|desc| desc.replica_id == replica.replica_id)
+                {
+                    replica.node_id = desc.node_id;
+                    replica.witness = desc.witness;
+                }
+            }
+            group.leader_node_id = replica_descs
+                .iter()
+                .find(|desc| desc.replica_id == group.leader_id)
+                .map_or(NO_NODE, |desc| desc.node_id);
+        }
+        Ok(topology)
+    }
+
+    /// Returns the topology of the node merged with the topologies of the
+    /// peer nodes provided by `Config::topology_provider`, so the operators
+    /// can tell where the groups live from any node.
+    pub async fn cluster_topology(&self) -> Result<ClusterTopology, Error> {
+        let topology = self.topology().await?;
+        let mut nodes = self
+            .topology_provider
+            .peer_topologies()
+            .into_iter()
+            .filter(|node| node.node_id != self.node_id)
+            .collect::<Vec<_>>();
+        nodes.push(topology);
+        nodes.sort_unstable_by_key(|node| node.node_id);
+        Ok(ClusterTopology { nodes })
+    }
+
+    /// Returns the actions suggested by `Config::placement_driver` to balance
+    /// the load of the nodes by the cluster topology of the node.
+    pub async fn placement_plan(&self) -> Result<Vec<PlacementAction>, Error> {
+        let topology = self.cluster_topology().await?;
+        Ok(self.placement_driver.plan(&topology))
+    }
+
+    /// Execute the placement action by `transfer_leader` or
+    /// `relocate_replica`, each step of which waits at most `timeout`.
+    ///
+    /// ## Notes
+    /// Must be called on the node of the leader of the group, otherwise
+    /// `ProposeError::NotLeader` is returned.
+    pub async fn execute_placement(
+        &self,
+        action: PlacementAction,
+        timeout: Duration,
+    ) -> Result<(), Error> {
+        match action {
+            PlacementAction::TransferLeader {
+                group_id,
+                transferee,
+                ..
+            } => self
+                .transfer_leader(group_id, transferee, timeout)
+                .await
+                .map(|_| ()),
+            PlacementAction::MoveReplica {
+                group_id,
+                from_node_id,
+                from_replica_id,
+                to_node_id,
+            } => self
+                .relocate_replica(group_id, from_node_id, from_replica_id, to_node_id, timeout)
+                .await
+                .map(|_| ()),
+        }
+    }
+
+    /// Plan the placement and execute the actions of the groups led by the
+    /// node in order, the actions of the other groups are left to the nodes
+    /// of their leaders. Returns the executed actions along with the results.
+    /// The application balances the cluster automatically by calling it
+    /// periodically on every node.
+    pub async fn balance(
+        &self,
+        timeout: Duration,
+    ) -> Result<Vec<(PlacementAction, Result<(), Error>)>, Error> {
+        let op_id = self.start_operation(OperationKind::Balance, 0);
+        self.balance_operation(op_id, timeout).await
+    }
+
+    /// Start `balance` on a spawned task, returns the id of the operation
+    /// along with the handle of the task.
+    pub fn start_balance(
+        self: &Arc<Self>,
+        timeout: Duration,
+    ) -> (
+        u64,
+        JoinHandle<Result<Vec<(PlacementAction, Result<(), Error>)>, Error>>,
+    ) {
+        let op_id = self.start_operation(OperationKind::Balance, 0);
+        let this = self.clone();
+        let handle = tokio::spawn(async move { this.balance_operation(op_id, timeout).await });
+        (op_id, handle)
+    }
+
+    async fn balance_operation(
+        &self,
+        op_id: u64,
+        timeout: Duration,
+    ) -> Result<Vec<(PlacementAction, Result<(), Error>)>, Error> {
+        let res = self.balance_actions(op_id, timeout).await;
+        self.finish_operation(op_id, &res);
+        res
+    }
+
+    async fn balance_actions(
+        &self,
+        op_id: u64,
+        timeout: Duration,
+    ) -> Result<Vec<(PlacementAction, Result<(), Error>)>, Error> {
+        let mut results = vec![];
+        let actions = self.placement_plan().await?;
+        let total = actions.len() as u64;
+        for (done, action) in actions.into_iter().enumerate() {
+            self.update_operation(op_id, "executing placement", done as u64, total);
+            let is_leader = self
+                .group_state(action.group_id())
+                .map_or(false, |state| state.is_leader());
+            if !is_leader {
+                continue;
+            }
+            let res = self.execute_placement(action, timeout).await;
+            if let Err(err) = res.as_ref() {
+                warn!(
+                    "node {}: execute placement {:?} error: {}",
+                    self.node_id, action, err
+                );
+            }
+            results.push((action, res));
+        }
+        Ok(results)
+    }
+
+    /// Returns the bounds of the raft log of the replica of the group on the
+    /// node, which are read from the storage, so the compaction policies and
+    /// backup tools don't need to know the storage implementation.
+    pub async fn log_bounds(&self, group_id: u64) -> Result<LogBounds, Error> {
+        let replica_id = self.group_replica(group_id).await?;
+        let gs = self.storage.group_storage(group_id, replica_id).await?;
+        Ok(LogBounds::from_storage(group_id, replica_id, &gs).await?)
+    }
+
+    /// The replica id of the group on the node.
+    async fn group_replica(&self, group_id: u64) -> Result<u64, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.actor
+            .query_group_tx
+            .send(QueryGroup::GroupReplica(group_id, tx))
+            .map_err(|_| {
+                Error::Channel(ChannelError::ReceiverClosed(
+                    "channel receiver closed for query group replica".to_owned(),
+                ))
+            })?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the group replica was dropped".to_owned(),
+            ))
+        })?
+    }
+
+    /// Returns the number of reads on the node by consistency level.
+    pub fn read_stats(&self) -> ReadStats {
+        self.actor.read_metrics.stats()
+    }
+
+    /// Returns the number of raft messages dropped before stepped into raft
+    /// since they are stale or duplicate, see `Config::stale_msg_term_gap`
+    /// and `Config::msg_dedup_window`.
+    pub fn stale_message_stats(&self) -> StaleMessageStats {
+        self.actor.stale_msg_metrics.stats()
+    }
+
+    /// Returns the loop latency of the node and the number of the work shed
+    /// under overload, see `Config::overload_shedding`.
+    pub fn overload_stats(&self) -> OverloadStats {
+        self.actor.overload.stats()
+    }
+
+    /// Returns the scheduler of the background jobs of the node, the jobs of
+    /// the application such as the snapshot builds and the scrubbing run
+    /// with its permits, so they share `Config::background_budget` with the
+    /// maintenance of the node.
+    pub fn background_scheduler(&self) -> Arc<BackgroundScheduler> {
+        self.actor.background.clone()
+    }
+
+    /// Returns the runs, the deferrals and the cost of the background jobs of
+    /// the node by kind in the order of priority.
+    pub fn background_stats(&self) -> Vec<JobStats> {
+        self.actor.background.stats()
+    }
+
+    /// Shed the work of the class with `Error::Overloaded` if the node is
+    /// overloaded, the occupancy is of the proposal queue shared with the
+    /// writes.
+    fn admit(&self, class: ShedClass) -> Result<(), Error> {
+        let propose_tx = &self.actor.propose_tx;
+        let capacity = propose_tx.max_capacity();
+        self.actor
+            .overload
+            .admit(class, capacity - propose_tx.capacity(), capacity)
+    }
+
+    /// Add the secondary consumer of the applied entries, the batches applied
+    /// by the state machine are delivered to the consumer on a spawned task
+    /// from its persisted cursors, see `AppliedConsumer`.
+    pub fn add_applied_consumer<C: AppliedConsumer>(&self, consumer: C) {
+        let rx = self.actor.applied_consumers.register();
+        let runner = AppliedConsumerRunner::<C, T::S, T::MS>::new(
+            consumer,
+            self.storage.clone(),
+            rx,
+            self.stopped.clone(),
+        );
+        tokio::spawn(runner.run());
+    }
+
+    /// Apply the group in pull mode, the committed entries of the group are
+    /// buffered for `committed_entries` instead of applied by the
+    /// `StateMachine`, the other groups on the node are not affected. The
+    /// group is applied in pull mode until it's removed from the node.
+    pub fn enable_pull_apply(&self, group_id: u64) {
+        self.actor.pull_applys.enable(group_id)
+    }
+
+    /// Pull at most `max` committed entries of the group in pull mode after
+    /// the cursor, which is the last index pulled by the caller, in the order
+    /// of index. The entries at or before the cursor are skipped. The caller
+    /// replies the proposals by the `tx` of the entries as the `StateMachine`
+    /// does, then acknowledges them by `acknowledge_applied`.
+    ///
+    /// The membership changes in the entries are applied to the group before
+    /// they are pulled, and the `StateMachine` hooks, e.g. `on_snapshot_installed`,
+    /// are still called for the group, the entries before the snapshot installed
+    /// are dropped. The membership change is pulled alone once the entries
+    /// pulled before it are acknowledged, as `StateMachine::apply` is fenced.
+    pub fn committed_entries(
+        &self,
+        group_id: u64,
+        cursor: u64,
+        max: usize,
+    ) -> Result<Vec<Apply<T::D, T::R>>, Error> {
+        self.actor.pull_applys.pull(group_id, cursor, max)
+    }
+
+    /// Acknowledge the entries of the group in pull mode pulled up to the
+    /// index are applied, the applied index of the group advances to it, so
+    /// the reads waiting for the index are served and the log before it can
+    /// be compacted. Returns `Error::BadParameter` if the index is not pulled.
+    pub fn acknowledge_applied(&self, group_id: u64, index: u64) -> Result<(), Error> {
+        self.actor.pull_applys.acknowledge(group_id, index)
+    }
+
+    /// Returns the latency histograms of the writes proposed and the reads
+    /// served on the node by group and stage, it is empty unless
+    /// `Config::record_latency` is enabled.
+    pub fn latency_report(&self) -> LatencyReport {
+        self.actor.latency.report()
+    }
+
+    /// Returns a stream of all groups on the node in the order of group id,
+    /// the groups are fetched by pages of `page_size`.
+    pub fn list_groups_stream(
+        &self,
+        page_size: usize,
+    ) -> impl Stream<Item = Result<GroupSummary, Error>> + '_ {
+        futures::stream::unfold(
+            (Some(0), VecDeque::new()),
+            move |(mut cursor, mut buffered)| async move {
+                loop {
+                    if let Some(summary) = buffered.pop_front() {
+                        return Some((Ok(summary), (cursor, buffered)));
+                    }
+
+                    match self.list_groups_paged(cursor?, page_size).await {
+                        Err(err) => return Some((Err(err), (None, buffered))),
+                        Ok(page) => {
+                            cursor = page.next_cursor;
+                            buffered.extend(page.groups);
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    pub fn membership_block(
+        &self,
+        group_id: u64,
+        term: Option<u64>,
+        context: Option<Vec<u8>>,
+        data: MembershipChangeData,
+    ) -> Result<(T::R, Option<Vec<u8>>), Error> {
+        let request_id = self.id_generator.next_uuid();
+        let rx = self.membership_with_request_id(request_id, group_id, term, context, data)?;
+        rx.blocking_recv()
+            .map_err(|_| {
+                Error::Channel(ChannelError::SenderClosed(
+                    "the sender that result the membership change was dropped".to_owned(),
+                ))
+            })
+            .and_then(|res| res)
+            .map_err(|err| err.with_request_id(request_id))
+    }
+
+    pub fn membership_non_block(
+        &self,
+        group_id: u64,
+        term: Option<u64>,
+        context: Option<Vec<u8>>,
+        data: MembershipChangeData,
+    ) -> Result<oneshot::Receiver<Result<(T::R, Option<Vec<u8>>), Error>>, Error> {
+        let request_id = self.id_generator.next_uuid();
+        self.membership_with_request_id(request_id, group_id, term, context, data)
+    }
+
+    fn membership_with_request_id(
+        &self,
+        request_id: Uuid,
+        group_id: u64,
+        term: Option<u64>,
+        context: Option<Vec<u8>>,
+        data: MembershipChangeData,
+    ) -> Result<oneshot::Receiver<Result<(T::R, Option<Vec<u8>>), Error>>, Error> {
+        let _ = self
+            .authorize_proposal(group_id, Operation::Membership)
+            .and_then(|_| self.pre_propose_check(group_id, term.unwrap_or(0)))
+            .map_err(|err| err.with_request_id(request_id))?;
+
+        trace!(
+            "node {}: membership change to group {}, request_id = {}",
+            self.node_id,
+            group_id,
+            request_id
+        );
+        let (tx, rx) = oneshot::channel();
+
+        let request = MembershipRequest {
+            request_id,
+            group_id,
+            term,
+            context,
+            data,
+            tx,
+        };
+
+        match self
+            .actor
+            .propose_tx
+            .try_send(ProposeMessage::Membership(request))
+        {
+            Err(TrySendError::Full(_)) => Err(Error::Channel(ChannelError::Full(
+                "channel no available capacity for memberhsip".to_owned(),
+            ))
+            .with_request_id(request_id)),
+            Err(TrySendError::Closed(_)) => Err(Error::Channel(ChannelError::ReceiverClosed(
+                "channel receiver closed for membership".to_owned(),
+            ))
+            .with_request_id(request_id)),
+            Ok(_) => Ok(rx),
+        }
+    }
+
+    /// `read_index` is use **read_index algorithm** to read data
+    /// from a specific group.
+    ///
+    /// `read_index` is a blocking interface in an asynchronous environment,
+    /// and the user should use `.await` to wait for it to complete. If executed
+    /// successfully, it returns the associated `context`, and at this point, the
+    /// caller can **safely** read data from the state machine. If it fails, an
+    /// error is returned.
+    ///
+    /// ## Parameters
+    /// - `group_id`: The specific group to read from.
+    /// - `context`: The context associated with the read. The context goes through
+    /// the context pipeline during the read.
+    ///
+    /// ## Notes
+    /// read_index implements the approach described in the Raft paper where read
+    /// requests do not need to be written to the Raft log, avoiding the cost of
+    /// writing to disk.
+    ///
+    /// ## Errors
+    /// Most errors require retries. The following error requires a different
+    /// handling approach:
+    /// - `ProposeError::NotLeader`: The application can refresh the leader and
+    /// retry based on the error information using the route table.
+    ///
+    /// ## Panics
+    pub async fn read_index(
+        &self,
+        group_id: u64,
+        context: Option<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.read(group_id, ConsistencyLevel::Linearizable, context)
+            .await
+    }
+
+    /// Read from the group by the leader lease, the hot reads on the leader
+    /// skip the round trip of `read_index` in the lease. It's same as
+    /// `read_index` if `Config::enable_lease_read` is disabled or the lease
+    /// expires, see `ConsistencyLevel::Lease`.
+    pub async fn lease_read(
+        &self,
+        group_id: u64,
+        context: Option<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.read(group_id, ConsistencyLevel::Lease, context).await
+    }
+
+    /// Read from the local replica of the group, which may be a follower,
+    /// without reading the data from the leader. The read index is confirmed
+    /// by the leader and the returned future is completed after the local
+    /// state machine applied to it, see `ConsistencyLevel::Follower`.
+    ///
+    /// ## Errors
+    /// - `ProposeError::LeaderUnknown`: The replica does not know the leader
+    /// to forward the read index request, the application can retry later.
+    pub async fn follower_read(
+        &self,
+        group_id: u64,
+        context: Option<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        self.read(group_id, ConsistencyLevel::Follower, context)
+            .await
+    }
+
+    /// Read from the group with the given consistency level, it returns the
+    /// `context` when the caller can read data from the state machine at the
+    /// level. The `Linearizable` read is same as `read_index`.
+    ///
+    /// The weaker levels are served by the replica without contacting a
+    /// quorum, they fall back to `Linearizable` if the lease of the leader
+    /// or the staleness bound does not hold, see `ConsistencyLevel`. Note
+    /// that the staleness of follower reads is bounded by the last contact
+    /// of the leader, the caller should also wait the state machine to
+    /// apply the entries the follower has committed.
+    pub async fn read(
+        &self,
+        group_id: u64,
+        consistency: ConsistencyLevel,
+        context: Option<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let request_id = self.id_generator.next_uuid();
+        let rx = self.read_index_with_request_id(request_id, group_id, consistency, context)?;
+        rx.await
+            .map_err(|_| {
+                Error::Channel(ChannelError::SenderClosed(
+                    "the sender that result the read_index change was dropped".to_owned(),
+                ))
+            })
+            .and_then(|res| res)
+            .map_err(|err| err.with_request_id(request_id))
+    }
+
+    pub fn read_index_block(
+        &self,
+        group_id: u64,
+        context: Option<Vec<u8>>,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let request_id = self.id_generator.next_uuid();
+        let rx = self.read_index_with_request_id(
+            request_id,
+            group_id,
+            ConsistencyLevel::Linearizable,
+            context,
+        )?;
+        rx.blocking_recv()
+            .map_err(|_| {
+                Error::Channel(ChannelError::SenderClosed(
+                    "the sender that result the read_index was dropped".to_owned(),
+                ))
+            })
+            .and_then(|res| res)
+            .map_err(|err| err.with_request_id(request_id))
+    }
+
+    pub fn read_index_non_block(
+        &self,
+        group_id: u64,
+        context: Option<Vec<u8>>,
+    ) -> Result<oneshot::Receiver<Result<Option<Vec<u8>>, Error>>, Error> {
+        self.read_index_with_request_id(
+            self.id_generator.next_uuid(),
+            group_id,
+            ConsistencyLevel::Linearizable,
+            context,
+        )
+    }
+
+    fn read_index_with_request_id(
+        &self,
+        request_id: Uuid,
+        group_id: u64,
+        consistency: ConsistencyLevel,
+        context: Option<Vec<u8>>,
+    ) -> Result<oneshot::Receiver<Result<Option<Vec<u8>>, Error>>, Error> {
+        trace!(
+            "node {}: read_index from group {}, request_id = {}",
+            self.node_id,
+            group_id,
+            request_id
+        );
+        self.admit(ShedClass::Read)
+            .map_err(|err| err.with_request_id(request_id))?;
+        let (tx, rx) = oneshot::channel();
+        match self
+            .actor
+            .propose_tx
+            .try_send(ProposeMessage::ReadIndexData(ReadIndexData {
+                group_id,
+                consistency,
+                context: ReadIndexContext {
+                    uuid: request_id.into_bytes(),
+                    context,
+                },
+                tx,
+                enqueued_at: self.record_latency.then(Instant::now),
+            })) {
+            Err(TrySendError::Full(_)) => Err(Error::Channel(ChannelError::Full(
+                "channel no available capacity for read_index".to_owned(),
+            ))
+            .with_request_id(request_id)),
+            Err(TrySendError::Closed(_)) => Err(Error::Channel(ChannelError::ReceiverClosed(
+                "channel receiver closed for read_index".to_owned(),
+            ))
+            .with_request_id(request_id)),
+            Ok(_) => Ok(rx),
+        }
+    }
+
+    /// Read from multiple groups with the read index algorithm, the reads are
+    /// sent to the node in one message and the results of the groups are
+    /// yielded by the returned stream as they resolve, in no particular order.
+    /// Each item is the group id with the result same as `read_index`.
+    pub fn read_index_many(
+        &self,
+        reads: Vec<(u64, Option<Vec<u8>>)>,
+    ) -> Result<impl Stream<Item = (u64, Result<Option<Vec<u8>>, Error>)>, Error> {
+        self.read_many(reads, ConsistencyLevel::Linearizable)
+    }
+
+    /// Wait until the state machines of the groups on the node applied to
+    /// the read indexes of the groups, the read indexes are confirmed by the
+    /// leaders at the same time. The reads of the local state machines of the
+    /// groups after the barrier observe all the writes committed before it,
+    /// so it's a linearizable point across the groups, e.g. for the
+    /// scatter-gather queries over the shards.
+    ///
+    /// The barrier fails if any group fails, e.g. the group does not exist on
+    /// the node or its replica does not know the leader.
+    pub async fn read_barrier(&self, groups: &[u64]) -> Result<ReadBarrier, Error> {
+        let mut groups = groups.to_vec();
+        groups.sort_unstable();
+        groups.dedup();
+        let mut reads = self.read_many(
+            groups.iter().map(|group_id| (*group_id, None)).collect(),
+            ConsistencyLevel::Follower,
+        )?;
+        while let Some((_, res)) = reads.next().await {
+            res?;
+        }
+
+        let indexes = groups
+            .into_iter()
+            .map(|group_id| {
+                let applied = self
+                    .shared_states
+                    .get(group_id)
+                    .map_or(0, |state| state.get_applied_index());
+                (group_id, applied)
+            })
+            .collect();
+        Ok(ReadBarrier {
+            node_id: self.node_id,
+            indexes,
+        })
+    }
+
+    fn read_many(
+        &self,
+        reads: Vec<(u64, Option<Vec<u8>>)>,
+        consistency: ConsistencyLevel,
+    ) -> Result<impl Stream<Item = (u64, Result<Option<Vec<u8>>, Error>)> + Unpin, Error> {
+        self.admit(ShedClass::Read)?;
+        let mut batch = Vec::with_capacity(reads.len());
+        let results = FuturesUnordered::new();
+        for (group_id, context) in reads {
+            let request_id = self.id_generator.next_uuid();
+            let (tx, rx) = oneshot::channel();
+            batch.push(ReadIndexData {
+                group_id,
+                consistency,
+                context: ReadIndexContext {
+                    uuid: request_id.into_bytes(),
+                    context,
+                },
+                tx,
+                enqueued_at: self.record_latency.then(Instant::now),
+            });
+            results.push(async move {
+                let res = rx
+                    .await
+                    .map_err(|_| {
+                        Error::Channel(ChannelError::SenderClosed(
+                            "the sender that result the read_index was dropped".to_owned(),
+                        ))
+                    })
+                    .and_then(|res| res)
+                    .map_err(|err| err.with_request_id(request_id));
+                (group_id, res)
+            });
+        }
+
+        trace!(
+            "node {}: read_index from {} groups",
+            self.node_id,
+            batch.len()
+        );
+        match self
+            .actor
+            .propose_tx
+            .try_send(ProposeMessage::ReadIndexBatch(batch))
+        {
+            Err(TrySendError::Full(_)) => Err(Error::Channel(ChannelError::Full(
+                "channel no available capacity for read_index".to_owned(),
+            ))),
+            Err(TrySendError::Closed(_)) => Err(Error::Channel(ChannelError::ReceiverClosed(
+                "channel receiver closed for read_index".to_owned(),
+            ))),
+            Ok(_) => Ok(results),
+        }
+    }
+
+    /// Campaign and wait raft group by given `group_id`.
+    ///
+    /// `campaign` is synchronous and waits for the campaign to submitted a
+    /// result to raft.
+    pub async fn campaign_group(&self, group_id: u64) -> Result<(), Error> {
+        let rx = self.campaign_group_non_block(group_id);
         rx.await.map_err(|_| {
             Error::Channel(ChannelError::SenderClosed(
                 "the sender that result the campaign group change was dropped".to_owned(),
@@ -432,13 +2559,26 @@ where
     /// campaign receiver stop, `Error` is returned.
     pub fn campaign_group_non_block(&self, group_id: u64) -> oneshot::Receiver<Result<(), Error>> {
         let (tx, rx) = oneshot::channel();
-        if let Err(_) = self.actor.campaign_tx.try_send((group_id, tx)) {
-            panic!("MultiRaftActor stopped")
+        if let Err(err) = self.actor.campaign_tx.try_send((group_id, tx)) {
+            let (tx, err) = match err {
+                TrySendError::Full((_, tx)) => (
+                    tx,
+                    ChannelError::Full("channel no available capacity for campaign".to_owned()),
+                ),
+                TrySendError::Closed((_, tx)) => (
+                    tx,
+                    ChannelError::ReceiverClosed("channel receiver closed for campaign".to_owned()),
+                ),
+            };
+            let _ = tx.send(Err(Error::Channel(err)));
         }
 
         rx
     }
 
+    /// Create the group on the node. The retry of the request with the same
+    /// `idempotency_key` gets the original outcome, see
+    /// `Config::manage_idempotency_window_ticks`.
     pub async fn create_group(&self, request: CreateGroupRequest) -> Result<(), Error> {
         let (tx, rx) = oneshot::channel();
         self.management_request(ManageMessage::CreateGroup(request, tx))?;
@@ -449,7 +2589,33 @@ where
         })?
     }
 
-    pub async fn remove_group(&self, request: RemoveGroupRequest) -> Result<(), Error> {
+    /// Create groups in one batch, used to provision many groups at bootstrap.
+    ///
+    /// The storages of the groups are initialized by one call of storage and
+    /// the raft groups are built in parallel. Returns the result of each group
+    /// in the order of `requests`, the error of the batch is returned if the
+    /// node can't accept the request.
+    pub async fn create_groups(
+        &self,
+        requests: Vec<CreateGroupRequest>,
+    ) -> Result<Vec<Result<(), Error>>, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.management_request(ManageMessage::CreateGroups(requests, tx))?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the group_manager change was dropped".to_owned(),
+            ))
+        })?
+    }
+
+    /// Remove the replica of group from the node. If the replica is the
+    /// leader, the leadership is transferred to the most up-to-date voter
+    /// first and the replica is removed after the new leader is established
+    /// or `election_tick` ticks, so the group avoids an election.
+    ///
+    /// The retry of the request with the same `idempotency_key` gets the
+    /// original outcome, or waits for the removal in flight.
+    pub async fn remove_group(&self, request: RemoveGroupRequest) -> Result<GroupRemoval, Error> {
         let (tx, rx) = oneshot::channel();
         self.management_request(ManageMessage::RemoveGroup(request, tx))?;
         rx.await.map_err(|_| {
@@ -459,7 +2625,180 @@ where
         })?
     }
 
+    /// Pause all groups of the storage domain on the node, e.g. the disk of
+    /// the domain is degraded. The leaders of the domain transfer the leadership
+    /// away before paused, the proposals to the paused groups fail with
+    /// `RaftGroupError::Paused` until the domain is resumed.
+    pub async fn pause_storage_domain(&self, domain: &str) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.management_request(ManageMessage::PauseStorageDomain(domain.to_owned(), tx))?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the storage domain pause was dropped".to_owned(),
+            ))
+        })?
+    }
+
+    /// Remove the descriptors of the replicas which are absent from the current
+    /// membership of their groups over `Config::replica_desc_gc_grace_ticks`,
+    /// the removed descriptors are returned. If `dry_run`, the descriptors are
+    /// only reported. The background collection is enabled by
+    /// `Config::replica_desc_gc_interval_ticks`.
+    pub async fn gc_replica_descs(&self, dry_run: bool) -> Result<ReplicaDescGcReport, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.management_request(ManageMessage::GcReplicaDescs(dry_run, tx))?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the replica desc gc was dropped".to_owned(),
+            ))
+        })?
+    }
+
+    /// Cross-check the metadata of the groups on the node with the group
+    /// storages and the replica descriptors, the orphan storages, the missing
+    /// storages and the mismatched conf states are reported. The check is also
+    /// run when the node starts, the groups of the missing storages are not
+    /// restored.
+    pub async fn check_storage(&self) -> Result<StorageCheckReport, Error> {
+        let (tx, rx) = oneshot::channel();
+        self.management_request(ManageMessage::CheckStorage(tx))?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the storage check was dropped".to_owned(),
+            ))
+        })?
+    }
+
+    /// Repair the inconsistency reported by `check_storage`, the repair takes
+    /// effect on the next start of the node. Returns `Error::BadParameter` if
+    /// the group is started on the node.
+    pub async fn repair_storage(&self, repair: StorageRepair) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.management_request(ManageMessage::RepairStorage(repair, tx))?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the storage repair was dropped".to_owned(),
+            ))
+        })?
+    }
+
+    /// Set the archive mode of the group on the node, see
+    /// `CreateGroupRequest::archive`. The mode is set on each replica of the
+    /// group, the log of each replica is truncated to the last
+    /// `Config::archive_log_entries` entries by `Config::log_compactor`.
+    pub async fn set_group_archive(&self, group_id: u64, archive: bool) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.management_request(ManageMessage::SetGroupArchive(group_id, archive, tx))?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the group archive was dropped".to_owned(),
+            ))
+        })?
+    }
+
+    /// Release the apply of the group on the node held by `Config::entry_gate`,
+    /// e.g. after the gate is upgraded to accept the held entry. The held
+    /// entries are applied again and consulted by the gate again, so they
+    /// may be held again.
+    pub async fn release_gate_hold(&self, group_id: u64) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.management_request(ManageMessage::ReleaseGateHold(group_id, tx))?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the release of gate hold was dropped".to_owned(),
+            ))
+        })?
+    }
+
+    /// Replicate the version of the gate policy to the group, it's proposed as
+    /// the admin entry of `GATE_POLICY_COMMAND` and persisted to the group
+    /// metadata when applied. The entries after it are only rejected or
+    /// transformed by the replicas whose `EntryGate::policy` matches, the
+    /// others hold them until they are upgraded. The entry is applied as
+    /// `Apply::Admin`, returns the response of the state machine of the leader.
+    pub async fn propose_gate_policy(&self, group_id: u64, policy: u64) -> Result<T::R, Error> {
+        self.propose_admin(group_id, gate_policy_payload(policy)).await
+    }
+
+    /// Fork the new group from the snapshot of the source group on the node,
+    /// e.g. templating the groups of a tenant or a staging copy of the group.
+    /// The members of the new group are mapped from node id to replica id,
+    /// the node must be a member. The new group is independent of the source
+    /// group after the fork.
+    ///
+    /// The replicas on the other nodes are created by the raft messages unless
+    /// `Config::buffer_unknown_group_msgs`, otherwise by `create_group`, and they
+    /// catch up by the snapshot sent by the leader. Returns the index of the
+    /// snapshot of the source group.
+    pub async fn fork_group(
+        &self,
+        src_group_id: u64,
+        new_group_id: u64,
+        member_mapping: HashMap<u64, u64>,
+    ) -> Result<u64, Error> {
+        let op_id = self.start_operation(OperationKind::ForkGroup, new_group_id);
+        self.fork_group_operation(op_id, src_group_id, new_group_id, member_mapping)
+            .await
+    }
+
+    /// Start `fork_group` on a spawned task, returns the id of the operation
+    /// along with the handle of the task.
+    pub fn start_fork_group(
+        self: &Arc<Self>,
+        src_group_id: u64,
+        new_group_id: u64,
+        member_mapping: HashMap<u64, u64>,
+    ) -> (u64, JoinHandle<Result<u64, Error>>) {
+        let op_id = self.start_operation(OperationKind::ForkGroup, new_group_id);
+        let this = self.clone();
+        let handle = tokio::spawn(async move {
+            this.fork_group_operation(op_id, src_group_id, new_group_id, member_mapping)
+                .await
+        });
+        (op_id, handle)
+    }
+
+    async fn fork_group_operation(
+        &self,
+        op_id: u64,
+        src_group_id: u64,
+        new_group_id: u64,
+        member_mapping: HashMap<u64, u64>,
+    ) -> Result<u64, Error> {
+        let (tx, rx) = oneshot::channel();
+        let res = match self.management_request(ManageMessage::ForkGroup(
+            src_group_id,
+            new_group_id,
+            member_mapping,
+            tx,
+        )) {
+            Err(err) => Err(err),
+            Ok(_) => rx.await.unwrap_or_else(|_| {
+                Err(Error::Channel(ChannelError::SenderClosed(
+                    "the sender that result the group fork was dropped".to_owned(),
+                )))
+            }),
+        };
+        self.finish_operation(op_id, &res);
+        res
+    }
+
+    /// Resume the paused storage domain, the groups of the domain are
+    /// materialized by the next message or proposal.
+    pub async fn resume_storage_domain(&self, domain: &str) -> Result<(), Error> {
+        let (tx, rx) = oneshot::channel();
+        self.management_request(ManageMessage::ResumeStorageDomain(domain.to_owned(), tx))?;
+        rx.await.map_err(|_| {
+            Error::Channel(ChannelError::SenderClosed(
+                "the sender that result the storage domain resume was dropped".to_owned(),
+            ))
+        })?
+    }
+
     fn management_request(&self, msg: ManageMessage) -> Result<(), Error> {
+        if !msg.is_urgent() {
+            self.admit(ShedClass::Manage)?;
+        }
         match self.actor.manage_tx.try_send(msg) {
             Err(TrySendError::Full(_)) => Err(Error::Channel(ChannelError::Full(
                 "channel no available capacity for group management".to_owned(),
@@ -472,6 +2811,9 @@ where
     }
 
     /// Return true if it is can to submit membership change to givend group_id.
+    ///
+    /// The node is queried for the strict result, `GroupState::has_pending_conf_change`
+    /// of `group_state` is the cheap check which is updated at apply time.
     pub async fn can_submmit_membership_change(&self, group_id: u64) -> Result<bool, Error> {
         let (tx, rx) = oneshot::channel();
         self.actor
@@ -482,6 +2824,22 @@ where
         Ok(!res)
     }
 
+    /// Returns the shared state of the replica of the group on the node, the
+    /// followers expose the leader learned from appends and heartbeats.
+    #[inline]
+    pub fn group_state(&self, group_id: u64) -> Option<Arc<GroupState>> {
+        self.shared_states.get(group_id)
+    }
+
+    /// Drop the cached authorization decisions of the proposals to the group,
+    /// the `Authorizer` is consulted again on the next proposal, e.g. after
+    /// the permissions of the tenant changed.
+    pub fn invalidate_authorization(&self, group_id: u64) {
+        if let Some(authorization) = self.proposal_authorization.as_ref() {
+            authorization.invalidate(group_id);
+        }
+    }
+
     #[inline]
     pub fn message_sender(&self) -> MultiRaftMessageSenderImpl {
         MultiRaftMessageSenderImpl {
@@ -489,6 +2847,31 @@ where
         }
     }
 
+    /// Returns the number of raft messages dropped because their groups did
+    /// not exist on the node and were not created before the messages expired
+    /// or the buffer was full.
+    #[inline]
+    pub fn dropped_unknown_group_messages(&self) -> u64 {
+        self.actor
+            .unknown_group_msgs_dropped
+            .load(std::sync::atomic::Ordering::Relaxed)
+    }
+
+    /// Returns the receiver of the fatal errors of the node, the group of the
+    /// error is halted and not served by the node until it restarts. The
+    /// first receiver also receives the errors emitted before, e.g. when
+    /// restoring the groups.
+    pub fn fatal_errors(&self) -> broadcast::Receiver<FatalError> {
+        self.actor.fatal_errors.subscribe()
+    }
+
+    /// Returns the event channel of the node, the components of the
+    /// application, e.g. `SnapshotUpload`, report their events by it.
+    #[inline]
+    pub fn event_channel(&self) -> EventChannel {
+        self.event_bcast.clone()
+    }
+
     #[inline]
     /// Creates a new Receiver connected to event channel Sender.
     /// Note: The Receiver **does not** turn this channel into a broadcast channel.
@@ -496,8 +2879,25 @@ where
         self.event_bcast.subscribe()
     }
 
+    /// Stop the node and wait until the node actor and the apply actor exited.
     pub async fn stop(&self) {
         self.stopped
             .store(true, std::sync::atomic::Ordering::SeqCst);
+        self.actor.join().await;
+    }
+
+    /// Stop the node like `stop`, then flush and stop the transport and the
+    /// storage of the node, see `Lifecycle`. The storage is closed after the
+    /// actors exited, so no writes are in flight.
+    pub async fn shutdown(&self) -> Result<(), Error>
+    where
+        TR: Lifecycle,
+        T::MS: Lifecycle,
+    {
+        self.stop().await;
+        self.transport.flush().await?;
+        self.transport.stop().await?;
+        self.storage.flush().await?;
+        self.storage.stop().await
     }
 }
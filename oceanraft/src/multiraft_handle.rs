@@ -3,17 +3,19 @@ use std::sync::Arc;
 
 use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::oneshot;
-use uuid::Uuid;
 
 use crate::prelude::CreateGroupRequest;
 use crate::prelude::MembershipChangeData;
 use crate::protos::RemoveGroupRequest;
+use crate::ConsistencyLevel;
+use crate::GroupRemoval;
 use crate::MultiRaftMessageSenderImpl;
 use crate::MultiRaftTypeSpecialization;
 
 use super::error::*;
 use super::event::EventChannel;
 use super::event::EventReceiver;
+use super::id::IdGenerator;
 use super::msg::ManageMessage;
 use super::msg::MembershipRequest;
 use super::msg::ProposeMessage;
@@ -21,7 +23,7 @@ use super::msg::QueryGroup;
 use super::msg::ReadIndexContext;
 use super::msg::ReadIndexData;
 use super::msg::WriteRequest;
-use super::node_handle::NodeHandle;
+use super::node_handle::NodeChannels;
 use super::state::GroupStates;
 use super::RaftGroupError;
 
@@ -29,11 +31,12 @@ pub struct MultiRaftHandle<T>
 where
     T: MultiRaftTypeSpecialization,
 {
-    node_handle: NodeHandle<T::D, T::R>,
+    node_handle: NodeChannels<T::D, T::R>,
     shared_states: GroupStates,
     event_bcast: EventChannel,
     node_id: u64,
     stopped: Arc<AtomicBool>,
+    id_generator: Arc<dyn IdGenerator>,
 }
 
 impl<T> MultiRaftHandle<T>
@@ -48,11 +51,11 @@ where
 
         // TODO: make configurable: enter following case if don't allow forward to leader propose
         if !state.is_leader() {
-            return Err(Error::Propose(super::ProposeError::NotLeader {
-                node_id: self.node_id,
+            return Err(Error::Propose(super::ProposeError::not_leader(
+                self.node_id,
                 group_id,
-                replica_id: state.get_replica_id(),
-            }));
+                &state,
+            )));
         }
 
         Ok(())
@@ -126,11 +129,16 @@ where
             .node_handle
             .propose_tx
             .try_send(ProposeMessage::Write(WriteRequest {
+                request_id: self.id_generator.next_uuid(),
                 group_id,
                 term,
                 data,
                 context,
+                local_context: false,
                 tx,
+                commit_tx: None,
+                enqueued_at: None,
+                coalescing_key: None,
             })) {
             Err(TrySendError::Full(_)) => Err(Error::Channel(ChannelError::Full(
                 "channel no avaiable capacity for write".to_owned(),
@@ -184,6 +192,7 @@ where
         let (tx, rx) = oneshot::channel();
 
         let request = MembershipRequest {
+            request_id: self.id_generator.next_uuid(),
             group_id,
             term,
             context,
@@ -214,7 +223,7 @@ where
     /// successfully, it returns the associated `context`, and at this point, the
     /// caller can **safely** read data from the state machine. If it fails, an
     /// error is returned.
-
+    ///
     /// ## Parameters
     /// - `group_id`: The specific group to read from.
     /// - `context`: The context associated with the read. The context goes through
@@ -269,11 +278,13 @@ where
             .propose_tx
             .try_send(ProposeMessage::ReadIndexData(ReadIndexData {
                 group_id,
+                consistency: ConsistencyLevel::Linearizable,
                 context: ReadIndexContext {
-                    uuid: Uuid::new_v4().into_bytes(),
+                    uuid: self.id_generator.next_uuid().into_bytes(),
                     context,
                 },
                 tx,
+                enqueued_at: None,
             })) {
             Err(TrySendError::Full(_)) => Err(Error::Channel(ChannelError::Full(
                 "channel no available capacity for read_index".to_owned(),
@@ -307,8 +318,18 @@ where
     /// campaign receiver stop, `Error` is returned.
     pub fn campaign_group(&self, group_id: u64) -> oneshot::Receiver<Result<(), Error>> {
         let (tx, rx) = oneshot::channel();
-        if let Err(_) = self.node_handle.campaign_tx.try_send((group_id, tx)) {
-            panic!("MultiRaftActor stopped")
+        if let Err(err) = self.node_handle.campaign_tx.try_send((group_id, tx)) {
+            let (tx, err) = match err {
+                TrySendError::Full((_, tx)) => (
+                    tx,
+                    ChannelError::Full("channel no available capacity for campaign".to_owned()),
+                ),
+                TrySendError::Closed((_, tx)) => (
+                    tx,
+                    ChannelError::ReceiverClosed("channel receiver closed for campaign".to_owned()),
+                ),
+            };
+            let _ = tx.send(Err(Error::Channel(err)));
         }
 
         rx
@@ -324,7 +345,10 @@ where
         })?
     }
 
-    pub async fn async_remove_group(&self, request: RemoveGroupRequest) -> Result<(), Error> {
+    pub async fn async_remove_group(
+        &self,
+        request: RemoveGroupRequest,
+    ) -> Result<GroupRemoval, Error> {
         let (tx, rx) = oneshot::channel();
         self.management_request(ManageMessage::RemoveGroup(request, tx))?;
         rx.await.map_err(|_| {
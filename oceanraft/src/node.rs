@@ -4,10 +4,16 @@ use std::collections::hash_map::Iter;
 use std::collections::HashSet;
 use std::collections::VecDeque;
 use std::sync::atomic::AtomicBool;
+use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use raft::prelude::ConfState;
+use raft::RawNode;
+use raft::SnapshotStatus;
 use raft::StateRole;
 use tokio::sync::mpsc::channel;
 use tokio::sync::mpsc::unbounded_channel;
@@ -16,6 +22,7 @@ use tokio::sync::mpsc::Sender;
 use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot;
+use tokio::sync::watch;
 use tracing::debug;
 use tracing::error;
 use tracing::info;
@@ -23,27 +30,60 @@ use tracing::trace;
 use tracing::warn;
 use tracing::Level;
 use tracing::Span;
+use uuid::Uuid;
 
 use crate::multiraft::ProposeResponse;
 use crate::multiraft::NO_LEADER;
 use crate::prelude::ConfChangeType;
+use crate::prelude::CreateGroupRequest;
 use crate::prelude::GroupMetadata;
+use crate::prelude::MembershipChangeData;
 use crate::prelude::Message;
 use crate::prelude::MessageType;
 use crate::prelude::MultiRaftMessage;
 use crate::prelude::MultiRaftMessageResponse;
 use crate::prelude::ReplicaDesc;
+use crate::prelude::Snapshot;
+use crate::prelude::SnapshotChecksum;
 
 use super::apply::ApplyActor;
+use super::apply::ApplyWorker;
+use super::apply_flow::ApplyFlow;
+use super::auth::authorize;
+use super::auth::Operation;
+use super::background::BackgroundScheduler;
+use super::budget::MemoryBudget;
+use super::budget::DEFAULT_PRIORITY;
+use super::coalesce::WriteCoalescer;
+use super::compaction::LogUsage;
 use super::config::Config;
+use super::consumer::AppliedConsumers;
+use super::cut::CommitWatermark;
+use super::cut::ConsistentCut;
+use super::dedup::MessageDedup;
+use super::domain::StorageDomains;
 use super::error::ChannelError;
 use super::error::Error;
+use super::error::ProposeError;
 use super::error::RaftGroupError;
 use super::event::Event;
 use super::event::EventChannel;
+use super::event::MembershipChangeEvent;
+use super::event::ReplicaHealth;
+use super::fatal::FatalError;
+use super::fatal::FatalErrorChannel;
+use super::group::appended_entries;
 use super::group::RaftGroup;
 use super::group::RaftGroupWriteRequest;
 use super::group::Status;
+use super::idempotency::unix_millis;
+use super::idempotency::IdempotencyKeys;
+use super::idempotency::ManageOutcome;
+use super::inflight::DeliveryStats;
+use super::inflight::InflightTuner;
+use super::invariant;
+use super::latency::LatencyRecorder;
+use super::lease::LeaderLease;
 use super::msg::ApplyCommitMessage;
 use super::msg::ApplyData;
 use super::msg::ApplyMessage;
@@ -52,36 +92,77 @@ use super::msg::CommitMembership;
 use super::msg::ManageMessage;
 use super::msg::ProposeMessage;
 use super::msg::QueryGroup;
+use super::msg::ReadIndexData;
+use super::msg::WriteRequest;
 use super::multiraft::NO_GORUP;
 use super::multiraft::NO_NODE;
+use super::node_promotion::PromotingLearner;
+use super::overload::OverloadDetector;
+use super::promotion::LearnerCatchUp;
 use super::proposal::ProposalQueue;
 use super::proposal::ReadIndexQueue;
+use super::proposer::Proposer;
+use super::pull::PullApplys;
+use super::quota::LogQuota;
+use super::quota::LogQuotaStage;
 use super::replica_cache::ReplicaCache;
 use super::rsm::StateMachine;
+use super::snapshot::verify_snapshot_checksum;
+use super::snapshot::SnapshotThrottle;
+use super::state::GroupPage;
+use super::state::GroupRemoval;
 use super::state::GroupState;
 use super::state::GroupStates;
+use super::state::GroupStatus;
+use super::state::GroupSummary;
+use super::state::NodeStatus;
+use super::state::PeerStatus;
+use super::state::ProgressStatus;
+use super::state::ReadMetrics;
+use super::state::StaleMessage;
+use super::state::StaleMessageMetrics;
 use super::storage::MultiRaftStorage;
 use super::storage::RaftStorage;
 use super::tick::Ticker;
+use super::topology::conf_members;
+use super::topology::GroupTopology;
+use super::topology::NodeTopology;
+use super::topology::ReplicaTopology;
+use super::transport::SendFailure;
+use super::transport::SendFailureReporter;
 use super::transport::Transport;
+use super::unknown_group::UnknownGroupMessages;
 use super::ProposeData;
 /// Shrink queue if queue capacity more than and len less than
 /// this value.
 const SHRINK_CACHE_CAPACITY: usize = 64;
 
+/// The raw nodes of groups are built in parallel when creating more than
+/// this number of groups in one batch.
+const PARALLEL_BUILD_MIN_GROUPS: usize = 64;
+
 pub(crate) type ResponseCallback = Box<dyn FnOnce() -> Result<(), Error> + Send + Sync + 'static>;
 
 pub(crate) struct ResponseCallbackQueue {
     cbs: VecDeque<ResponseCallback>,
+    /// Run callbacks in `flush` instead of a spawned task, used when
+    /// the node is driven by `NodeHandle::poll` of the caller.
+    inline: bool,
 }
 
 impl ResponseCallbackQueue {
     pub(crate) fn new() -> Self {
         Self {
             cbs: VecDeque::new(),
+            inline: false,
         }
     }
 
+    #[inline]
+    pub(crate) fn set_inline_flush(&mut self) {
+        self.inline = true;
+    }
+
     pub(crate) fn new_callback<T: Send + Sync + 'static>(
         tx: oneshot::Sender<Result<T, Error>>,
         res: Result<T, Error>,
@@ -123,13 +204,18 @@ impl ResponseCallbackQueue {
     pub(crate) fn flush(&mut self) {
         let cbs = self.cbs.drain(..).collect::<Vec<_>>();
         self.try_gc();
-        tokio::spawn(async move {
+        let run = move || {
             for cb in cbs {
                 if let Err(err) = cb() {
                     warn!("{}", err)
                 }
             }
-        });
+        };
+        if self.inline {
+            run();
+        } else {
+            tokio::spawn(async move { run() });
+        }
     }
 }
 
@@ -215,8 +301,19 @@ where
     )>,
     pub manage_tx: Sender<ManageMessage>,
     pub query_group_tx: UnboundedSender<QueryGroup>,
-    #[allow(unused)]
+    /// Number of dropped messages of the groups which do not exist on the node.
+    pub unknown_group_msgs_dropped: Arc<AtomicU64>,
+    pub read_metrics: Arc<ReadMetrics>,
+    pub stale_msg_metrics: Arc<StaleMessageMetrics>,
+    pub latency: Arc<LatencyRecorder>,
+    pub overload: Arc<OverloadDetector>,
+    pub background: Arc<BackgroundScheduler>,
+    pub applied_consumers: AppliedConsumers,
+    pub pull_applys: PullApplys<W, R>,
+    pub fatal_errors: FatalErrorChannel,
     apply: ApplyActor,
+    /// Closed once the node worker exited.
+    exited: watch::Receiver<()>,
 }
 
 impl<W, R> NodeActor<W, R>
@@ -250,6 +347,15 @@ where
         let (apply_request_tx, apply_request_rx) = unbounded_channel();
         let (apply_response_tx, apply_response_rx) = unbounded_channel();
         let (group_query_tx, group_query_rx) = unbounded_channel();
+        let unknown_group_msgs_dropped = Arc::new(AtomicU64::new(0));
+        let read_metrics = Arc::new(ReadMetrics::default());
+        let stale_msg_metrics = Arc::new(StaleMessageMetrics::default());
+        let latency = Arc::new(LatencyRecorder::default());
+        let overload = Arc::new(OverloadDetector::new(cfg.node_id, cfg.overload_shedding));
+        let background = Arc::new(BackgroundScheduler::new(cfg.background_budget.clone()));
+        let applied_consumers = AppliedConsumers::default();
+        let pull_applys = PullApplys::new(apply_response_tx.clone());
+        let fatal_errors = FatalErrorChannel::new();
         let apply = ApplyActor::spawn(
             cfg,
             rsm,
@@ -258,6 +364,10 @@ where
             apply_request_rx,
             apply_response_tx,
             commit_tx,
+            event_bcast,
+            latency.clone(),
+            applied_consumers.clone(),
+            pull_applys.clone(),
             stopped.clone(),
         );
 
@@ -275,11 +385,20 @@ where
             commit_rx,
             group_query_rx,
             states,
+            unknown_group_msgs_dropped.clone(),
+            read_metrics.clone(),
+            stale_msg_metrics.clone(),
+            latency.clone(),
+            overload.clone(),
+            background.clone(),
+            fatal_errors.clone(),
         );
 
-        tokio::spawn(async move {
+        let (exited_tx, exited) = watch::channel(());
+        cfg.spawn_node(async move {
             worker.restore().await;
             worker.main_loop(ticker, stopped).await;
+            drop(exited_tx);
         });
 
         Self {
@@ -288,9 +407,150 @@ where
             propose_tx,
             campaign_tx,
             manage_tx,
+            unknown_group_msgs_dropped,
+            read_metrics,
+            stale_msg_metrics,
+            latency,
+            overload,
+            background,
+            applied_consumers,
+            pull_applys,
+            fatal_errors,
             apply,
+            exited,
         }
     }
+
+    /// Wait until the node worker and the apply worker exited after the node
+    /// is stopped. The workers of the polled node are driven by the caller of
+    /// `NodeHandle::poll`, so it returns immediately for them.
+    pub(crate) async fn join(&self) {
+        let mut exited = self.exited.clone();
+        while exited.changed().await.is_ok() {}
+        self.apply.join().await;
+    }
+
+    /// Create the actor and its workers without spawning them, the workers
+    /// are driven by `NodeHandle::poll`.
+    pub(crate) fn new_polled<TR, RS, MRS, RSM>(
+        cfg: &Config,
+        transport: &TR,
+        storage: &MRS,
+        rsm: RSM,
+        event_bcast: &EventChannel,
+        states: GroupStates,
+    ) -> (
+        Self,
+        NodeWorker<TR, RS, MRS, W, R>,
+        ApplyWorker<W, R, RSM, RS, MRS>,
+    )
+    where
+        TR: Transport + Clone,
+        RS: RaftStorage,
+        MRS: MultiRaftStorage<RS>,
+        RSM: StateMachine<W, R>,
+    {
+        let (propose_tx, propose_rx) = channel(cfg.proposal_queue_size);
+        let (manage_tx, manage_rx) = channel(1);
+        let (campaign_tx, campaign_rx) = channel(1);
+        let (raft_message_tx, raft_message_rx) = channel(10);
+
+        let (commit_tx, commit_rx) = unbounded_channel();
+
+        let (apply_request_tx, apply_request_rx) = unbounded_channel();
+        let (apply_response_tx, apply_response_rx) = unbounded_channel();
+        let (group_query_tx, group_query_rx) = unbounded_channel();
+        let unknown_group_msgs_dropped = Arc::new(AtomicU64::new(0));
+        let read_metrics = Arc::new(ReadMetrics::default());
+        let stale_msg_metrics = Arc::new(StaleMessageMetrics::default());
+        let latency = Arc::new(LatencyRecorder::default());
+        let overload = Arc::new(OverloadDetector::new(cfg.node_id, cfg.overload_shedding));
+        let background = Arc::new(BackgroundScheduler::new(cfg.background_budget.clone()));
+        let applied_consumers = AppliedConsumers::default();
+        let pull_applys = PullApplys::new(apply_response_tx.clone());
+        let fatal_errors = FatalErrorChannel::new();
+        let apply_worker = ApplyWorker::new(
+            cfg,
+            rsm,
+            storage.clone(),
+            states.clone(),
+            apply_request_rx,
+            apply_response_tx,
+            commit_tx,
+            event_bcast,
+            latency.clone(),
+            applied_consumers.clone(),
+            pull_applys.clone(),
+        );
+
+        let mut worker = NodeWorker::<TR, RS, MRS, W, R>::new(
+            cfg,
+            transport,
+            storage,
+            propose_rx,
+            campaign_rx,
+            raft_message_rx,
+            apply_request_tx,
+            apply_response_rx,
+            manage_rx,
+            event_bcast,
+            commit_rx,
+            group_query_rx,
+            states,
+            unknown_group_msgs_dropped.clone(),
+            read_metrics.clone(),
+            stale_msg_metrics.clone(),
+            latency.clone(),
+            overload.clone(),
+            background.clone(),
+            fatal_errors.clone(),
+        );
+        worker.pending_responses.set_inline_flush();
+
+        let actor = Self {
+            query_group_tx: group_query_tx,
+            raft_message_tx,
+            propose_tx,
+            campaign_tx,
+            manage_tx,
+            unknown_group_msgs_dropped,
+            read_metrics,
+            stale_msg_metrics,
+            latency,
+            overload,
+            background,
+            applied_consumers,
+            pull_applys,
+            fatal_errors,
+            apply: ApplyActor::polled(),
+            exited: watch::channel(()).1,
+        };
+        (actor, worker, apply_worker)
+    }
+}
+
+/// The group that is registered on the node but the raft group is not
+/// materialized, it is created by the next message or proposal.
+pub(crate) struct ParkedGroup {
+    pub(crate) replica_id: u64,
+    pub(crate) replicas: Vec<ReplicaDesc>,
+    pub(crate) applied_hint: Option<u64>,
+    pub(crate) priority: u32,
+    /// Campaign after materialized if the replica was leader when parked.
+    pub(crate) campaign: bool,
+    /// True if the raft group was materialized before parked.
+    pub(crate) materialized: bool,
+}
+
+/// The leader which is removed after the leadership is transferred, see
+/// `ManageMessage::RemoveGroup`.
+pub(crate) struct RemovingGroup {
+    pub(crate) transferee: u64,
+    pub(crate) ticks: usize,
+    pub(crate) tx: oneshot::Sender<Result<GroupRemoval, Error>>,
+    pub(crate) idempotency_key: String,
+    /// The retries of the request with the same idempotency key.
+    pub(crate) waiters: Vec<oneshot::Sender<Result<GroupRemoval, Error>>>,
 }
 
 pub struct NodeWorker<TR, RS, MRS, W, R>
@@ -308,6 +568,7 @@ where
     pub(crate) node_manager: NodeManager,
     pub(crate) replica_cache: ReplicaCache<RS, MRS>,
     pub(crate) groups: HashMap<u64, RaftGroup<RS, R>>,
+    pub(crate) parked_groups: HashMap<u64, ParkedGroup>,
     pub(crate) active_groups: HashSet<u64>,
     pub(crate) pending_responses: ResponseCallbackQueue,
     pub(crate) event_chan: EventChannel,
@@ -323,6 +584,64 @@ where
     pub(crate) apply_result_rx: UnboundedReceiver<ApplyResultMessage>,
     pub(crate) query_group_rx: UnboundedReceiver<QueryGroup>,
     pub(crate) shared_states: GroupStates,
+    pub(crate) memory_budget: MemoryBudget,
+    pub(crate) unknown_group_msgs: UnknownGroupMessages,
+    pub(crate) msg_dedup: MessageDedup,
+    pub(crate) started_at: Instant,
+    /// The time of the last message received from the peer nodes.
+    pub(crate) peer_contacts: HashMap<u64, Instant>,
+    pub(crate) storage_domains: StorageDomains,
+    /// The groups of paused storage domains which are transferring the
+    /// leadership away before parked, with the ticks waited.
+    pub(crate) pausing_groups: HashMap<u64, usize>,
+    /// The groups in the archive mode with the index before which the log was
+    /// last requested to compact, see `Config::archive_log_entries`.
+    pub(crate) archive_groups: HashMap<u64, u64>,
+    /// The leaders which are transferring the leadership away before removed.
+    pub(crate) removing_groups: HashMap<u64, RemovingGroup>,
+    pub(crate) idempotency_keys: IdempotencyKeys,
+    /// The (group id, replica id) of the learners to promote, see
+    /// `Config::learner_promoter`.
+    pub(crate) ready_learners: Vec<(u64, u64)>,
+    /// The learner promotions in flight by group.
+    pub(crate) learner_promotions: HashMap<u64, PromotingLearner<R>>,
+    /// The ticks elapsed since the node started, see `Config::election_ramp_ticks`.
+    pub(crate) elapsed_ticks: usize,
+    /// The replicas absent from the membership of their groups with the
+    /// tick first observed, see `Config::replica_desc_gc_grace_ticks`.
+    pub(crate) replica_desc_gc_absent: HashMap<(u64, u64), usize>,
+    pub(crate) last_replica_desc_gc_tick: usize,
+    pub(crate) read_metrics: Arc<ReadMetrics>,
+    pub(crate) stale_msg_metrics: Arc<StaleMessageMetrics>,
+    /// The latency of the reads, see `Config::record_latency`.
+    pub(crate) latency: Arc<LatencyRecorder>,
+    /// The loop latency is recorded for shedding, see `Config::overload_shedding`.
+    pub(crate) overload: Arc<OverloadDetector>,
+    /// The budget of the maintenance jobs, see `Config::background_budget`.
+    pub(crate) background: Arc<BackgroundScheduler>,
+    pub(crate) send_failure_reporter: SendFailureReporter,
+    pub(crate) send_failure_rx: UnboundedReceiver<SendFailure>,
+    /// The tick that the replicas were last reported unreachable, see
+    /// `Config::unreachable_debounce_ticks`.
+    pub(crate) unreachable_reports: HashMap<(u64, u64), usize>,
+    /// See `Config::max_concurrent_snapshots`.
+    pub(crate) snapshot_throttle: SnapshotThrottle,
+    /// The delivery reports of the transport by peer node, see
+    /// `Config::adaptive_inflight_interval_ticks`.
+    pub(crate) delivery_stats: DeliveryStats,
+    pub(crate) inflight_tuner: InflightTuner,
+    pub(crate) last_inflight_adjust_tick: usize,
+    pub(crate) last_compaction_tick: usize,
+    /// The groups escalated to `LogQuotaStage::Compact` or beyond, their log
+    /// is compacted at the applied index with the next compaction.
+    pub(crate) quota_compactions: HashSet<u64>,
+    pub(crate) fatal_errors: FatalErrorChannel,
+    /// The groups halted by the fatal errors, they are not created again
+    /// until the node restarts.
+    pub(crate) halted_groups: HashSet<u64>,
+    /// The writes held for the inflight proposals of their groups in the
+    /// order of arrival, see `Config::await_inflight_proposals`.
+    pub(crate) waiting_writes: HashMap<u64, VecDeque<WriteRequest<W, R>>>,
 }
 
 impl<TR, RS, MRS, WD, RES> NodeWorker<TR, RS, MRS, WD, RES>
@@ -350,12 +669,25 @@ where
         commit_rx: UnboundedReceiver<ApplyCommitMessage>,
         group_query_rx: UnboundedReceiver<QueryGroup>,
         shared_states: GroupStates,
+        unknown_group_msgs_dropped: Arc<AtomicU64>,
+        read_metrics: Arc<ReadMetrics>,
+        stale_msg_metrics: Arc<StaleMessageMetrics>,
+        latency: Arc<LatencyRecorder>,
+        overload: Arc<OverloadDetector>,
+        background: Arc<BackgroundScheduler>,
+        fatal_errors: FatalErrorChannel,
     ) -> Self {
+        let (send_failure_tx, send_failure_rx) = unbounded_channel();
+        let delivery_stats = DeliveryStats::default();
+        let send_failure_reporter =
+            SendFailureReporter::new(cfg.node_id, send_failure_tx, delivery_stats.clone());
+        transport.register_failure_reporter(send_failure_reporter.clone());
         NodeWorker::<TR, RS, MRS, WD, RES> {
             cfg: cfg.clone(),
             node_id: cfg.node_id,
             node_manager: NodeManager::new(),
             groups: HashMap::new(),
+            parked_groups: HashMap::new(),
             propose_rx,
             campaign_rx,
             multiraft_message_rx: raft_message_rx,
@@ -371,15 +703,93 @@ where
             pending_responses: ResponseCallbackQueue::new(),
             shared_states,
             query_group_rx: group_query_rx,
+            memory_budget: MemoryBudget::new(cfg.memory_budget, cfg.memory_budget_idle_ticks),
+            unknown_group_msgs: UnknownGroupMessages::new(
+                cfg.unknown_group_msg_capacity,
+                cfg.unknown_group_msg_ttl_ticks,
+                unknown_group_msgs_dropped,
+            ),
+            msg_dedup: MessageDedup::new(cfg.msg_dedup_window),
+            started_at: Instant::now(),
+            peer_contacts: HashMap::new(),
+            storage_domains: StorageDomains::new(cfg.storage_domain_failure_threshold),
+            pausing_groups: HashMap::new(),
+            archive_groups: HashMap::new(),
+            removing_groups: HashMap::new(),
+            idempotency_keys: IdempotencyKeys::new(
+                cfg.manage_idempotency_window_ticks,
+                cfg.tick_interval,
+            ),
+            ready_learners: vec![],
+            learner_promotions: HashMap::new(),
+            elapsed_ticks: 0,
+            replica_desc_gc_absent: HashMap::new(),
+            last_replica_desc_gc_tick: 0,
+            read_metrics,
+            stale_msg_metrics,
+            latency,
+            overload,
+            background,
+            send_failure_reporter,
+            send_failure_rx,
+            unreachable_reports: HashMap::new(),
+            snapshot_throttle: SnapshotThrottle::new(
+                cfg.max_concurrent_snapshots,
+                cfg.snapshot_max_bytes_per_sec,
+                cfg.tick_interval,
+                cfg.snapshot_send_timeout_ticks,
+            ),
+            delivery_stats,
+            inflight_tuner: InflightTuner::new(
+                cfg.max_inflight_msgs,
+                cfg.adaptive_inflight_min_msgs,
+                cfg.adaptive_inflight_max_msgs,
+                Duration::from_millis(cfg.adaptive_inflight_base_rtt_ms),
+            ),
+            last_inflight_adjust_tick: 0,
+            last_compaction_tick: 0,
+            quota_compactions: HashSet::new(),
+            fatal_errors,
+            halted_groups: HashSet::new(),
+            waiting_writes: HashMap::new(),
         }
     }
 
     /// Restore the node from storage.
     /// TODO: add unit test
-    async fn restore(&mut self) {
+    pub(crate) async fn restore(&mut self) {
+        self.restore_idempotency_keys().await;
+
         // TODO: load all replica desc to recreate node manager.
         // TODO: use group_iter
-        let gs_metas = self.storage.scan_group_metadata().await.unwrap();
+        let gs_metas = match self.storage.scan_group_metadata().await {
+            Ok(gs_metas) => gs_metas,
+            Err(err) => {
+                error!(
+                    "node {}: restore groups error, scan group metadata: {}",
+                    self.node_id, err
+                );
+                return;
+            }
+        };
+
+        // the inconsistent groups are reported instead of failing at the first
+        // message, see `MultiRaft::check_storage`.
+        let missing = match self.check_storage().await {
+            Ok(report) => {
+                if !report.is_consistent() {
+                    warn!(
+                        "node {}: the storage is inconsistent with the metadata of groups: {:?}",
+                        self.node_id, report
+                    );
+                }
+                report.missing
+            }
+            Err(err) => {
+                warn!("node {}: check storage error: {}", self.node_id, err);
+                vec![]
+            }
+        };
 
         for gs_meta in gs_metas.iter() {
             // TODO: check group metadta status to detect whether deleted.
@@ -387,35 +797,67 @@ where
                 continue;
             }
 
+            // the lost storage is not recreated empty.
+            if missing.contains(&(gs_meta.group_id, gs_meta.replica_id)) {
+                continue;
+            }
+
             // TODO: cache optimize
-            let gs = self
+            let restored = match self
                 .storage
                 .group_storage(gs_meta.group_id, gs_meta.replica_id)
                 .await
-                .unwrap();
-            let rs = gs.initial_state().unwrap();
-            if !rs.initialized() {
-                continue;
-            }
+            {
+                Ok(gs) => match gs.initial_state() {
+                    Ok(rs) if !rs.initialized() => continue,
+                    Ok(_) => self.storage.scan_group_replica_desc(gs_meta.group_id).await,
+                    Err(err) => Err(err.into()),
+                },
+                Err(err) => Err(err),
+            };
+            // the group is halted instead of restored from the storage which
+            // can't be read.
+            let replica_descs: Vec<ReplicaDesc> = match restored {
+                Ok(replica_descs) => replica_descs,
+                Err(err) => {
+                    error!(
+                        "node {}: restore group {} error {}",
+                        self.node_id, gs_meta.group_id, err
+                    );
+                    self.halt_group(FatalError::StorageUnavailable {
+                        node_id: self.node_id,
+                        group_id: gs_meta.group_id,
+                    });
+                    continue;
+                }
+            };
 
             self.node_manager
                 .add_group(gs_meta.node_id, gs_meta.group_id);
-
-            let replica_descs: Vec<ReplicaDesc> = self
-                .storage
-                .scan_group_replica_desc(gs_meta.group_id)
-                .await
-                .unwrap();
             // if empty voters and conf state uninitialized, don't restore
-            self.create_raft_group(
-                gs_meta.group_id,
-                gs_meta.replica_id,
-                replica_descs,
-                None,
-                None,
-            )
-            .await
-            .unwrap();
+            if let Err(err) = self
+                .create_raft_group(
+                    gs_meta.group_id,
+                    gs_meta.replica_id,
+                    replica_descs,
+                    None,
+                    None,
+                    DEFAULT_PRIORITY,
+                )
+                .await
+            {
+                // the group is halted if the error is fatal.
+                error!(
+                    "node {}: restore group {} error {}",
+                    self.node_id, gs_meta.group_id, err
+                );
+                continue;
+            }
+            self.storage_domains
+                .set(gs_meta.group_id, gs_meta.storage_domain.clone());
+            if gs_meta.archive {
+                self.archive_groups.insert(gs_meta.group_id, 0);
+            }
             // TODO: move track group node here.
         }
     }
@@ -451,27 +893,11 @@ where
                 // Note: see https://github.com/tokio-rs/tokio/discussions/4019 for more
                 // information about why mut here.
 
-                Some((req, tx)) = self.multiraft_message_rx.recv() => {
-                    let res = self.handle_multiraft_message(req).await ;
-                    self.pending_responses.push_back(ResponseCallbackQueue::new_callback(tx, res));
-                },
+                Some((req, tx)) = self.multiraft_message_rx.recv() => self.handle_multiraft_request(req, tx).await,
 
-                _ = ticker.recv() => {
-                    self.groups.iter_mut().for_each(|(id, group)| {
-                        if group.raft_group.tick() {
-                            self.active_groups.insert(*id);
-                        }
-                    });
-                    ticks += 1;
-                    if ticks >= self.cfg.heartbeat_tick {
-                        ticks = 0;
-                        self.merge_heartbeats();
-                    }
-                },
+                _ = ticker.recv() => self.handle_tick(&mut ticks),
 
-                Some(req) = self.propose_rx.recv() => if let Some(cb) = self.handle_propose(req) {
-                    self.pending_responses.push_back(cb);
-                },
+                Some(req) = self.propose_rx.recv() => self.handle_propose_request(req).await,
 
                 Some(res) = self.apply_result_rx.recv() =>  self.handle_apply_result(res).await,
 
@@ -479,184 +905,822 @@ where
                     self.pending_responses.push_back(cb);
                 },
 
-                Some((group_id, tx)) = self.campaign_rx.recv() => {
-                    self.campaign_raft(group_id, tx);
-                    self.active_groups.insert(group_id);
-                }
+                Some((group_id, tx)) = self.campaign_rx.recv() => self.handle_campaign(group_id, tx).await,
 
                 Some(msg) = self.commit_rx.recv() => self.handle_apply_commit(msg).await,
 
                 Some(msg) = self.query_group_rx.recv() => self.handle_query_group(msg),
 
+                Some(failure) = self.send_failure_rx.recv() => self.handle_send_failure(failure).await,
+
                 else => {},
             }
 
+            let started = Instant::now();
+            self.resume_waiting_writes();
             if !self.active_groups.is_empty() {
                 self.handle_readys().await;
                 /* here is active groups already drained */
             }
+            self.send_snapshots().await;
+
+            if !self.removing_groups.is_empty() {
+                self.handle_removing_groups().await;
+            }
+
+            if !self.ready_learners.is_empty() || !self.learner_promotions.is_empty() {
+                self.promote_learners().await;
+            }
+            self.tick_adaptive_inflight().await;
+            self.run_background_jobs().await;
+            self.overload.record_loop_latency(started.elapsed());
 
             self.pending_responses.flush();
         }
     }
 
-    async fn handle_multiraft_message(
-        &mut self,
-        msg: MultiRaftMessage,
-    ) -> Result<MultiRaftMessageResponse, Error> {
-        let rmsg = msg.msg.as_ref().expect("invalid msg");
-        // for a heartbeat message, fanout is executed only if context in
-        // the heartbeat message is empty.
-        match rmsg.msg_type() {
-            MessageType::MsgHeartbeat if rmsg.context.is_empty() => {
-                self.fanout_heartbeat(msg).await
-            }
-            MessageType::MsgHeartbeatResponse if rmsg.context.is_empty() => {
-                self.fanout_heartbeat_response(msg).await
+    /// Run one iteration of the main loop without waiting, all pending
+    /// requests in the channels are handled. Returns the number of handled
+    /// requests, ticks are not counted.
+    pub(crate) async fn poll_once(&mut self, tick: bool, ticks: &mut usize) -> usize {
+        let mut handled = 0;
+        if tick {
+            self.handle_tick(ticks);
+        }
+
+        while let Ok((req, tx)) = self.multiraft_message_rx.try_recv() {
+            self.handle_multiraft_request(req, tx).await;
+            handled += 1;
+        }
+
+        while let Ok(req) = self.propose_rx.try_recv() {
+            self.handle_propose_request(req).await;
+            handled += 1;
+        }
+
+        while let Ok(res) = self.apply_result_rx.try_recv() {
+            self.handle_apply_result(res).await;
+            handled += 1;
+        }
+
+        while let Ok(msg) = self.manage_rx.try_recv() {
+            if let Some(cb) = self.handle_manage_message(msg).await {
+                self.pending_responses.push_back(cb);
             }
-            _ => self.handle_raft_message(msg).await,
+            handled += 1;
         }
-    }
 
-    #[tracing::instrument(
-        name = "NodeActor::handle_raft_message",
-        level = Level::TRACE,
-        skip_all,
-    )]
-    async fn handle_raft_message(
-        &mut self,
-        mut msg: MultiRaftMessage,
-    ) -> Result<MultiRaftMessageResponse, Error> {
-        if !self.groups.contains_key(&msg.group_id) {
-            let msg = msg.clone();
-            let raft_msg = msg.msg.as_ref().expect("why message missing raft msg");
-            // TODO: if group mark deleted, we need return error
-            let _ = self
-                .create_raft_group(
-                    msg.group_id,
-                    raft_msg.to,
-                    msg.replicas.clone(),
-                    None,
-                    Some(msg.clone()),
-                )
-                .await
-                .map_err(|err| {
-                    error!(
-                        "node {}: create group for replica {} error {}",
-                        self.node_id, raft_msg.to, err
-                    );
-                    err
-                })?;
+        while let Ok((group_id, tx)) = self.campaign_rx.try_recv() {
+            self.handle_campaign(group_id, tx).await;
+            handled += 1;
         }
 
-        let raft_msg = msg
-            .msg
-            .take()
-            .expect("invalid message, raft Message should not be none.");
+        while let Ok(msg) = self.commit_rx.try_recv() {
+            self.handle_apply_commit(msg).await;
+            handled += 1;
+        }
 
-        let group_id = msg.group_id;
-        let from_replica = ReplicaDesc {
-            group_id,
-            node_id: msg.from_node,
-            replica_id: raft_msg.from,
-        };
-        let to_replica = ReplicaDesc {
-            group_id,
-            node_id: msg.to_node,
-            replica_id: raft_msg.to,
-        };
+        while let Ok(msg) = self.query_group_rx.try_recv() {
+            self.handle_query_group(msg);
+            handled += 1;
+        }
 
-        // processing messages between replicas from other nodes to self node.
-        trace!(
-            "node {}: recv msg {:?} from {}: group = {}, from_replica = {}, to_replica = {}",
-            self.node_id,
-            raft_msg.msg_type(),
-            msg.from_node,
-            group_id,
-            from_replica.replica_id,
-            to_replica.replica_id,
-        );
+        while let Ok(failure) = self.send_failure_rx.try_recv() {
+            self.handle_send_failure(failure).await;
+            handled += 1;
+        }
 
-        let _ = self
-            .replica_cache
-            .cache_replica_desc(group_id, from_replica.clone(), self.cfg.replica_sync)
-            .await?;
+        let started = Instant::now();
+        self.resume_waiting_writes();
+        if !self.active_groups.is_empty() {
+            self.handle_readys().await;
+        }
+        self.send_snapshots().await;
 
-        let _ = self
-            .replica_cache
-            .cache_replica_desc(group_id, to_replica.clone(), self.cfg.replica_sync)
-            .await?;
+        if !self.removing_groups.is_empty() {
+            self.handle_removing_groups().await;
+        }
 
-        if !self.node_manager.contains_node(&from_replica.node_id) {
-            self.node_manager.add_group(from_replica.node_id, group_id);
+        if !self.ready_learners.is_empty() || !self.learner_promotions.is_empty() {
+            self.promote_learners().await;
         }
+        self.tick_adaptive_inflight().await;
+        self.run_background_jobs().await;
+        self.overload.record_loop_latency(started.elapsed());
 
-        // if a group exists, try to maintain groups on the node
-        // if self.groups.contains_key(&group_id)
-        //     && !self.node_manager.contains_node(&to_replica.node_id)
-        // {
-        //     self.node_manager.add_group(to_replica.node_id, group_id);
-        // }
+        self.pending_responses.flush();
+        self.event_chan.flush();
+        handled
+    }
 
-        let group = self
-            .groups
-            .get_mut(&group_id)
-            .expect("unreachable: group always initialize or return error in the previouse code");
+    /// Handle the membership commit received from apply, the apply worker
+    /// waits for the commits when it is polled inline.
+    pub(crate) async fn serve_apply_commit(&mut self, msg: ApplyCommitMessage) {
+        self.handle_apply_commit(msg).await;
+        self.pending_responses.flush();
+    }
 
-        if let Err(err) = group.raft_group.step(raft_msg) {
-            warn!("node {}: step raf message error: {}", self.node_id, err);
+    fn handle_tick(&mut self, ticks: &mut usize) {
+        let elapsed_ticks = self.elapsed_ticks;
+        let ramp_ticks = self.cfg.election_ramp_ticks;
+        self.groups.iter_mut().for_each(|(id, group)| {
+            group.idle_ticks += 1;
+            // the reads batched since the last tick share a read index round.
+            if group.flush_read_batch() {
+                self.active_groups.insert(*id);
+            }
+            // the follower holds the election timeout until the ramp delay of
+            // the group elapsed, the leader and candidates tick as usual.
+            if elapsed_ticks < election_ramp_delay(*id, ramp_ticks)
+                && group.raft_group.raft.state == StateRole::Follower
+            {
+                return;
+            }
+            if group.raft_group.tick() {
+                self.active_groups.insert(*id);
+            }
+            // the witness never campaigns after the election timeout.
+            if group.witness && group.is_pre_candidate() {
+                group.abort_witness_campaign();
+            }
+        });
+        self.elapsed_ticks = self.elapsed_ticks.saturating_add(1);
+        *ticks += 1;
+        if *ticks >= self.cfg.heartbeat_tick {
+            *ticks = 0;
+            self.merge_heartbeats();
         }
-        self.active_groups.insert(group_id);
-        Ok(MultiRaftMessageResponse {})
+        self.background.tick();
+        self.tick_memory_budget();
+        self.tick_log_quotas();
+        self.tick_archive_groups();
+        self.tick_learner_promotions();
+        self.tick_unreachable_reports();
+        self.snapshot_throttle.tick();
+        self.expire_snapshots();
+        self.park_idle_groups();
+        self.tick_pausing_groups();
+        self.removing_groups
+            .values_mut()
+            .for_each(|removing| removing.ticks += 1);
+        self.idempotency_keys.tick();
+        self.tick_unknown_group_msgs();
+        self.msg_dedup.tick();
     }
 
-    /// if `None` is returned, the write request is successfully committed
-    /// to raft, otherwise the callback closure of the error response is
-    /// returned.
-    ///
-    /// Note: Must be called to respond to the client when the loop ends.
-    #[tracing::instrument(
-        level = Level::TRACE,
-        name = "NodeActor::handle_propose",
-        skip_all
+    pub(crate) async fn handle_multiraft_request(
+        &mut self,
+        req: MultiRaftMessage,
+        tx: oneshot::Sender<Result<MultiRaftMessageResponse, Error>>,
+    ) {
+        let res = self.handle_multiraft_message(req).await;
+        self.pending_responses
+            .push_back(ResponseCallbackQueue::new_callback(tx, res));
+    }
+
+    async fn handle_propose_request(&mut self, req: ProposeMessage<WD, RES>) {
+        let req = match req {
+            ProposeMessage::ReadIndexBatch(reads) => {
+                return self.handle_read_index_batch(reads).await
+            }
+            req => req,
+        };
+        let group_id = match &req {
+            ProposeMessage::Write(data) => data.group_id,
+            ProposeMessage::Membership(request) => request.group_id,
+            ProposeMessage::Admin(request) => request.group_id,
+            ProposeMessage::ReadIndexData(read_data) => read_data.group_id,
+            ProposeMessage::ReadIndexBatch(_) => unreachable!(),
+        };
+        self.try_materialize_group(group_id).await;
+        let req = match req {
+            ProposeMessage::Write(data) => match self.try_auto_create_group(group_id).await {
+                Err(err) => {
+                    self.pending_responses
+                        .push_back(ResponseCallbackQueue::new_error_callback(
+                            data.tx,
+                            err.with_request_id(data.request_id),
+                        ));
+                    return;
+                }
+                Ok(()) => ProposeMessage::Write(data),
+            },
+            req => req,
+        };
+        if let Some(cb) = self.handle_propose(req) {
+            self.pending_responses.push_back(cb);
+        }
+    }
+
+    async fn handle_read_index_batch(&mut self, reads: Vec<ReadIndexData>) {
+        // the responses of the batch are flushed together.
+        for read_data in reads {
+            self.try_materialize_group(read_data.group_id).await;
+            if let Some(cb) = self.handle_propose(ProposeMessage::ReadIndexData(read_data)) {
+                self.pending_responses.push_back(cb);
+            }
+        }
+    }
+
+    /// Create the group of the write by `Config::group_factory` if the group
+    /// does not exist on the node, the replica campaigns after created so the
+    /// write can proceed if it's elected immediately.
+    async fn try_auto_create_group(&mut self, group_id: u64) -> Result<(), Error> {
+        if !self.cfg.auto_create_groups
+            || self.groups.contains_key(&group_id)
+            || self.parked_groups.contains_key(&group_id)
+        {
+            return Ok(());
+        }
+
+        let request = match self.cfg.group_factory.create_group(self.node_id, group_id) {
+            None => return Ok(()),
+            Some(request) => request,
+        };
+        if request.group_id != group_id {
+            return Err(Error::BadParameter(format!(
+                "the group factory created group {} for group {}",
+                request.group_id, group_id
+            )));
+        }
+
+        info!(
+            "node {}: auto create group {} by write, replica = {}",
+            self.node_id, group_id, request.replica_id
+        );
+
+        // initialize the membership of the new group, the storage of the
+        // group which was created before is kept.
+        let gs = self
+            .storage
+            .group_storage(group_id, request.replica_id)
+            .await?;
+        if gs
+            .initial_state()
+            .map_err(|err| Error::Raft(err))?
+            .conf_state
+            == ConfState::default()
+        {
+            let mut voters = request
+                .replicas
+                .iter()
+                .map(|replica| replica.replica_id)
+                .collect::<Vec<_>>();
+            if voters.is_empty() {
+                voters.push(request.replica_id);
+            }
+            gs.set_confstate(ConfState {
+                voters,
+                learners: request
+                    .learners
+                    .iter()
+                    .map(|learner| learner.replica_id)
+                    .collect(),
+                ..Default::default()
+            })
+            .await?;
+        }
+        self.create_group(request).await?;
+        if let Some(group) = self.groups.get_mut(&group_id) {
+            if !group.witness {
+                group
+                    .raft_group
+                    .campaign()
+                    .map_err(|err| Error::Raft(err))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Create the raft group of the request, the lazy flag is ignored.
+    async fn create_group(&mut self, mut request: CreateGroupRequest) -> Result<(), Error> {
+        let group_id = request.group_id;
+        let replica_id = request.replica_id;
+        self.check_storage_domain(group_id, &request.storage_domain)?;
+        self.init_learners(&mut request).await?;
+        self.active_groups.insert(group_id);
+        self.create_raft_group(
+            group_id,
+            replica_id,
+            request.replicas,
+            Some(request.applied_hint),
+            None,
+            request.priority,
+        )
+        .await?;
+        let mut res = self
+            .set_storage_domain(group_id, replica_id, request.storage_domain)
+            .await;
+        if res.is_ok() {
+            res = self
+                .set_group_archive(group_id, replica_id, request.archive)
+                .await;
+        }
+        self.deliver_unknown_group_msgs(group_id).await;
+        res
+    }
+
+    /// Initialize the learners of the request in the membership of the group
+    /// if the membership is not initialized in storage, the replicas of the
+    /// request are the voters. The learners are moved to the replicas of the
+    /// request, so that they are cached as the other members.
+    async fn init_learners(&self, request: &mut CreateGroupRequest) -> Result<(), Error> {
+        if request.learners.is_empty() {
+            return Ok(());
+        }
+
+        let gs = self
+            .storage
+            .group_storage(request.group_id, request.replica_id)
+            .await?;
+        if gs
+            .initial_state()
+            .map_err(|err| Error::Raft(err))?
+            .conf_state
+            == ConfState::default()
+        {
+            let voters = request
+                .replicas
+                .iter()
+                .map(|replica| replica.replica_id)
+                .collect::<Vec<_>>();
+            if voters.is_empty() {
+                return Err(Error::BadParameter(format!(
+                    "group {} is created with learners but no voters",
+                    request.group_id
+                )));
+            }
+            gs.set_confstate(ConfState {
+                voters,
+                learners: request
+                    .learners
+                    .iter()
+                    .map(|learner| learner.replica_id)
+                    .collect(),
+                ..Default::default()
+            })
+            .await?;
+        }
+        request.replicas.extend(request.learners.drain(..));
+        Ok(())
+    }
+
+    /// Create the group seeded from the snapshot of the source group, the
+    /// members are mapped from node id to replica id. Returns the index of
+    /// the snapshot.
+    async fn fork_group(
+        &mut self,
+        src_group_id: u64,
+        group_id: u64,
+        member_mapping: HashMap<u64, u64>,
+    ) -> Result<u64, Error> {
+        let replica_id = match member_mapping.get(&self.node_id) {
+            None => {
+                return Err(Error::BadParameter(format!(
+                    "node {} is not a member of the forked group {}",
+                    self.node_id, group_id
+                )))
+            }
+            Some(replica_id) => *replica_id,
+        };
+        self.authorize(group_id, Operation::CreateGroup)?;
+        self.check_create_group(group_id, replica_id)?;
+
+        self.materialize_group(src_group_id).await?;
+        let src_replica_id = match self.groups.get(&src_group_id) {
+            None => return Err(self.missing_group_error(src_group_id)),
+            Some(group) => group.replica_id,
+        };
+        let mut snapshot = self
+            .storage
+            .group_storage(src_group_id, src_replica_id)
+            .await?
+            .snapshot(0, replica_id)
+            .map_err(|err| Error::Raft(err))?;
+        let index = snapshot.get_metadata().index;
+        if index == 0 {
+            return Err(Error::BadParameter(format!(
+                "group {} has no snapshot to fork",
+                src_group_id
+            )));
+        }
+
+        let mut replicas = member_mapping
+            .iter()
+            .map(|(node_id, replica_id)| ReplicaDesc {
+                node_id: *node_id,
+                group_id,
+                replica_id: *replica_id,
+                witness: false,
+            })
+            .collect::<Vec<_>>();
+        replicas.sort_by_key(|replica| replica.replica_id);
+        snapshot.mut_metadata().set_conf_state(ConfState {
+            voters: replicas.iter().map(|replica| replica.replica_id).collect(),
+            ..Default::default()
+        });
+        let metadata = snapshot.get_metadata().clone();
+        self.storage
+            .group_storage(group_id, replica_id)
+            .await?
+            .install_snapshot(snapshot)
+            .await?;
+
+        self.active_groups.insert(group_id);
+        self.create_raft_group(group_id, replica_id, replicas, None, None, DEFAULT_PRIORITY)
+            .await?;
+        self.send_apply_msg(ApplyMessage::SnapshotInstalled {
+            group_id,
+            replica_id,
+            metadata,
+        });
+        self.deliver_unknown_group_msgs(group_id).await;
+        info!(
+            "node {}: fork group {} from group {} at index {}",
+            self.node_id, group_id, src_group_id, index
+        );
+        Ok(index)
+    }
+
+    async fn handle_campaign(&mut self, group_id: u64, tx: oneshot::Sender<Result<(), Error>>) {
+        self.try_materialize_group(group_id).await;
+        self.campaign_raft(group_id, tx);
+        self.active_groups.insert(group_id);
+    }
+
+    pub(crate) async fn handle_multiraft_message(
+        &mut self,
+        msg: MultiRaftMessage,
+    ) -> Result<MultiRaftMessageResponse, Error> {
+        self.peer_contacts.insert(msg.from_node, Instant::now());
+        let rmsg = msg.msg.as_ref().expect("invalid msg");
+        // for a heartbeat message, fanout is executed only if context in
+        // the heartbeat message is empty.
+        match rmsg.msg_type() {
+            MessageType::MsgHeartbeat if rmsg.context.is_empty() => {
+                self.fanout_heartbeat(msg).await
+            }
+            MessageType::MsgHeartbeatResponse if rmsg.context.is_empty() => {
+                self.fanout_heartbeat_response(msg).await
+            }
+            _ => self.handle_raft_message(msg).await,
+        }
+    }
+
+    #[tracing::instrument(
+        name = "NodeActor::handle_raft_message",
+        level = Level::TRACE,
+        skip_all,
+    )]
+    async fn handle_raft_message(
+        &mut self,
+        mut msg: MultiRaftMessage,
+    ) -> Result<MultiRaftMessageResponse, Error> {
+        if self.parked_groups.contains_key(&msg.group_id) {
+            self.materialize_group(msg.group_id).await?;
+        }
+
+        if !self.groups.contains_key(&msg.group_id) && self.cfg.buffer_unknown_group_msgs {
+            // buffer the message until the group is created, so that a message
+            // from a stale or removed replica does not create the group.
+            let group_id = msg.group_id;
+            if !self.unknown_group_msgs.push(msg) {
+                warn!(
+                    "node {}: drop message of unknown group {}, {} messages are buffered",
+                    self.node_id,
+                    group_id,
+                    self.unknown_group_msgs.len()
+                );
+            }
+            return Ok(MultiRaftMessageResponse::default());
+        }
+
+        if !self.groups.contains_key(&msg.group_id) {
+            let msg = msg.clone();
+            let raft_msg = msg.msg.as_ref().expect("why message missing raft msg");
+            // TODO: if group mark deleted, we need return error
+            let _ = self
+                .create_raft_group(
+                    msg.group_id,
+                    raft_msg.to,
+                    msg.replicas.clone(),
+                    None,
+                    Some(msg.clone()),
+                    DEFAULT_PRIORITY,
+                )
+                .await
+                .map_err(|err| {
+                    error!(
+                        "node {}: create group for replica {} error {}",
+                        self.node_id, raft_msg.to, err
+                    );
+                    err
+                })?;
+        }
+
+        // drop the stale messages before learning the replicas of them, so
+        // the removed replicas are not cached again.
+        let stale = self
+            .groups
+            .get(&msg.group_id)
+            .and_then(|group| {
+                group.check_stale_message(
+                    msg.msg.as_ref().expect("invalid msg"),
+                    self.cfg.stale_msg_term_gap,
+                )
+            })
+            .or_else(|| {
+                self.msg_dedup
+                    .is_duplicate(&msg)
+                    .then_some(StaleMessage::Duplicate)
+            });
+        if let Some(reason) = stale {
+            trace!(
+                "node {}: drop {:?} message of group {} from node {}",
+                self.node_id,
+                reason,
+                msg.group_id,
+                msg.from_node
+            );
+            self.stale_msg_metrics.record(reason);
+            return Ok(MultiRaftMessageResponse::default());
+        }
+
+        let raft_msg = msg
+            .msg
+            .take()
+            .expect("invalid message, raft Message should not be none.");
+
+        let group_id = msg.group_id;
+        let from_replica = ReplicaDesc {
+            group_id,
+            node_id: msg.from_node,
+            replica_id: raft_msg.from,
+            witness: false,
+        };
+        let to_replica = ReplicaDesc {
+            group_id,
+            node_id: msg.to_node,
+            replica_id: raft_msg.to,
+            witness: false,
+        };
+
+        // processing messages between replicas from other nodes to self node.
+        trace!(
+            "node {}: recv msg {:?} from {}: group = {}, from_replica = {}, to_replica = {}",
+            self.node_id,
+            raft_msg.msg_type(),
+            msg.from_node,
+            group_id,
+            from_replica.replica_id,
+            to_replica.replica_id,
+        );
+
+        let _ = self
+            .replica_cache
+            .learn_replica_desc(group_id, from_replica.clone(), self.cfg.replica_sync)
+            .await?;
+
+        let _ = self
+            .replica_cache
+            .learn_replica_desc(group_id, to_replica.clone(), self.cfg.replica_sync)
+            .await?;
+
+        if !self.node_manager.contains_node(&from_replica.node_id) {
+            self.node_manager.add_group(from_replica.node_id, group_id);
+        }
+
+        // if a group exists, try to maintain groups on the node
+        // if self.groups.contains_key(&group_id)
+        //     && !self.node_manager.contains_node(&to_replica.node_id)
+        // {
+        //     self.node_manager.add_group(to_replica.node_id, group_id);
+        // }
+
+        let group = self
+            .groups
+            .get_mut(&group_id)
+            .expect("unreachable: group always initialize or return error in the previouse code");
+
+        Self::learn_leader_hint(group, &from_replica, &raft_msg);
+        if group.witness && raft_msg.msg_type() == MessageType::MsgTimeoutNow {
+            warn!(
+                "node {}: witness replica({}) of group {} ignores leadership transfer from {}",
+                self.node_id, group.replica_id, group_id, from_replica.replica_id
+            );
+            return Ok(MultiRaftMessageResponse::default());
+        }
+        if raft_msg.msg_type() == MessageType::MsgSnapStatus {
+            // the follower rejected the snapshot, the snapshot is sent again
+            // after the leader probes the follower.
+            if group.is_leader() {
+                group
+                    .raft_group
+                    .report_snapshot(raft_msg.from, SnapshotStatus::Failure);
+                self.active_groups.insert(group_id);
+            }
+            return Ok(MultiRaftMessageResponse::default());
+        }
+        if let Some(snapshot) = raft_msg.snapshot.as_ref() {
+            if let Err(reason) = Self::verify_snapshot(
+                &self.cfg,
+                group_id,
+                group.witness,
+                msg.snapshot_checksum.as_ref(),
+                snapshot,
+            ) {
+                let index = snapshot.get_metadata().index;
+                warn!(
+                    "node {}: reject snapshot {} of group {} from {}: {}",
+                    self.node_id, index, group_id, from_replica.replica_id, reason
+                );
+
+                // request the leader to resend the snapshot.
+                let mut status = Message::default();
+                status.set_msg_type(MessageType::MsgSnapStatus);
+                status.from = raft_msg.to;
+                status.to = raft_msg.from;
+                status.reject = true;
+                if let Err(err) = self.transport.send(MultiRaftMessage {
+                    group_id,
+                    from_node: self.node_id,
+                    to_node: msg.from_node,
+                    msg: Some(status),
+                    ..Default::default()
+                }) {
+                    warn!(
+                        "node {}: send snapshot status of group {} to node {} error: {}",
+                        self.node_id, group_id, msg.from_node, err
+                    );
+                }
+                return Err(Error::SnapshotRejected {
+                    group_id,
+                    index,
+                    reason,
+                });
+            }
+        }
+        if let Err(err) = group.raft_group.step(raft_msg) {
+            warn!("node {}: step raf message error: {}", self.node_id, err);
+        }
+        group.idle_ticks = 0;
+        self.active_groups.insert(group_id);
+        Ok(MultiRaftMessageResponse::default())
+    }
+
+    /// Learn the leader from the source of appends and heartbeats and expose it
+    /// via the shared state before the raft group processes the message, and
+    /// track the replicas that recently requested votes or sent appends.
+    fn learn_leader_hint(group: &RaftGroup<RS, RES>, from: &ReplicaDesc, msg: &Message) {
+        let term = group.raft_group.raft.term;
+        match msg.msg_type() {
+            MessageType::MsgAppend | MessageType::MsgHeartbeat | MessageType::MsgSnapshot
+                if msg.term >= term =>
+            {
+                group
+                    .shared_state
+                    .record_contact(from.replica_id, from.node_id, msg.term);
+                if group.shared_state.get_leader_id() != from.replica_id {
+                    group.shared_state.set_leader_id(from.replica_id);
+                    group.shared_state.set_leader_node_id(from.node_id);
+                }
+            }
+            MessageType::MsgRequestVote | MessageType::MsgRequestPreVote if msg.term >= term => {
+                group
+                    .shared_state
+                    .record_contact(from.replica_id, from.node_id, msg.term)
+            }
+            _ => {}
+        }
+    }
+
+    /// Verify the snapshot received from the leader before installed, returns
+    /// the reason if the snapshot is rejected.
+    fn verify_snapshot(
+        cfg: &Config,
+        group_id: u64,
+        witness: bool,
+        checksum: Option<&SnapshotChecksum>,
+        snapshot: &Snapshot,
+    ) -> Result<(), String> {
+        // the checksum is missing if the leader is of the old version.
+        if let Some(checksum) = checksum {
+            verify_snapshot_checksum(checksum, &snapshot.data)?;
+        }
+
+        // the witness has no state machine and the data of snapshot is empty.
+        if witness {
+            return Ok(());
+        }
+        cfg.snapshot_validator.validate(
+            group_id,
+            snapshot.get_metadata(),
+            &mut snapshot.data.as_slice(),
+        )
+    }
+
+    /// Propose the write to the group, the write is held if the group has
+    /// too many inflight proposals, see `Config::await_inflight_proposals`.
+    /// The `resumed` write is the held one and is proposed as it is.
+    pub(crate) fn handle_write(
+        &mut self,
+        data: WriteRequest<WD, RES>,
+        resumed: bool,
+    ) -> Option<ResponseCallback> {
+        let group_id = data.group_id;
+        match self.groups.get_mut(&group_id) {
+            None => {
+                warn!(
+                    "node {}: proposal failed, group {} does not exists",
+                    self.node_id, group_id,
+                );
+                Some(ResponseCallbackQueue::new_error_callback(
+                    data.tx,
+                    self.missing_group_error(group_id)
+                        .with_request_id(data.request_id),
+                ))
+            }
+            Some(group) => {
+                // the writes behind the held writes are held to keep the order.
+                if !resumed
+                    && self.cfg.await_inflight_proposals
+                    && (group.is_proposals_full() || self.waiting_writes.contains_key(&group_id))
+                {
+                    return self.hold_write(data);
+                }
+                self.active_groups.insert(group_id);
+                group.idle_ticks = 0;
+                let proposer = self.cfg.annotate_proposer.then(|| Proposer {
+                    node_id: self.node_id,
+                    replica_id: group.replica_id,
+                    principal: self.cfg.authorizer.principal(self.node_id, group_id),
+                });
+                let compression = (
+                    self.cfg.entry_compression,
+                    self.cfg.entry_compression_threshold,
+                );
+                group.propose_write(data, proposer, compression, &mut self.memory_budget)
+            }
+        }
+    }
+
+    /// if `None` is returned, the write request is successfully committed
+    /// to raft, otherwise the callback closure of the error response is
+    /// returned.
+    ///
+    /// Note: Must be called to respond to the client when the loop ends.
+    #[tracing::instrument(
+        level = Level::TRACE,
+        name = "NodeActor::handle_propose",
+        skip_all
     )]
     fn handle_propose(&mut self, msg: ProposeMessage<WD, RES>) -> Option<ResponseCallback> {
         match msg {
-            ProposeMessage::Write(data) => {
-                let group_id = data.group_id;
+            ProposeMessage::Write(data) => self.handle_write(data, false),
+            ProposeMessage::Membership(request) => {
+                let group_id = request.group_id;
                 match self.groups.get_mut(&group_id) {
                     None => {
                         warn!(
-                            "node {}: proposal failed, group {} does not exists",
+                            "node {}: proposal membership failed, group {} does not exists",
                             self.node_id, group_id,
                         );
                         return Some(ResponseCallbackQueue::new_error_callback(
-                            data.tx,
-                            Error::RaftGroup(RaftGroupError::Deleted(self.node_id, group_id)),
+                            request.tx,
+                            self.missing_group_error(group_id)
+                                .with_request_id(request.request_id),
                         ));
                     }
                     Some(group) => {
                         self.active_groups.insert(group_id);
-                        group.propose_write(data)
+                        group.idle_ticks = 0;
+                        group.propose_membership_change(request)
                     }
                 }
             }
-            ProposeMessage::Membership(request) => {
+            ProposeMessage::Admin(request) => {
                 let group_id = request.group_id;
                 match self.groups.get_mut(&group_id) {
                     None => {
                         warn!(
-                            "node {}: proposal membership failed, group {} does not exists",
+                            "node {}: proposal admin failed, group {} does not exists",
                             self.node_id, group_id,
                         );
                         return Some(ResponseCallbackQueue::new_error_callback(
                             request.tx,
-                            Error::RaftGroup(RaftGroupError::Deleted(self.node_id, group_id)),
+                            self.missing_group_error(group_id)
+                                .with_request_id(request.request_id),
                         ));
                     }
                     Some(group) => {
                         self.active_groups.insert(group_id);
-                        group.propose_membership_change(request)
+                        group.idle_ticks = 0;
+                        let proposer = self.cfg.annotate_proposer.then(|| Proposer {
+                            node_id: self.node_id,
+                            replica_id: group.replica_id,
+                            principal: self.cfg.authorizer.principal(self.node_id, group_id),
+                        });
+                        group.propose_admin(request, proposer)
                     }
                 }
             }
@@ -670,15 +1734,44 @@ where
                         );
                         return Some(ResponseCallbackQueue::new_error_callback(
                             read_data.tx,
-                            Error::RaftGroup(RaftGroupError::Deleted(self.node_id, group_id)),
+                            self.missing_group_error(group_id)
+                                .with_request_id(Uuid::from_bytes(read_data.context.uuid)),
+                        ));
+                    }
+                    // the witness has no state machine to read.
+                    Some(group) if group.witness => {
+                        return Some(ResponseCallbackQueue::new_error_callback(
+                            read_data.tx,
+                            Error::RaftGroup(RaftGroupError::Witness(self.node_id, group_id))
+                                .with_request_id(Uuid::from_bytes(read_data.context.uuid)),
                         ));
                     }
                     Some(group) => {
                         self.active_groups.insert(group_id);
-                        group.read_index_propose(read_data)
+                        group.idle_ticks = 0;
+                        group.read_index_propose(
+                            read_data,
+                            &self.read_metrics,
+                            self.cfg.id_generator.as_ref(),
+                        )
                     }
                 }
             }
+            // the batch is split by `handle_propose_request`.
+            ProposeMessage::ReadIndexBatch(_) => unreachable!(),
+        }
+    }
+
+    /// The error of the proposal to the group that is not materialized on
+    /// the node, the group is either paused or deleted.
+    fn missing_group_error(&self, group_id: u64) -> Error {
+        match self.storage_domains.paused_domain(group_id) {
+            Some(domain) => Error::RaftGroup(RaftGroupError::Paused(
+                self.node_id,
+                group_id,
+                domain.to_owned(),
+            )),
+            None => Error::RaftGroup(RaftGroupError::Deleted(self.node_id, group_id)),
         }
     }
 
@@ -690,7 +1783,15 @@ where
     fn campaign_raft(&mut self, group_id: u64, tx: oneshot::Sender<Result<(), Error>>) {
         let res = if let Some(group) = self.groups.get_mut(&group_id) {
             //            self.activity_groups.insert(group_id);
-            group.raft_group.campaign().map_err(|err| Error::Raft(err))
+            group.idle_ticks = 0;
+            if group.witness {
+                Err(Error::RaftGroup(RaftGroupError::Witness(
+                    self.node_id,
+                    group_id,
+                )))
+            } else {
+                group.raft_group.campaign().map_err(|err| Error::Raft(err))
+            }
         } else {
             warn!(
                 "the node({}) campaign group({}) is removed",
@@ -713,73 +1814,256 @@ where
         skip_all,
     )]
     async fn handle_manage_message(&mut self, msg: ManageMessage) -> Option<ResponseCallback> {
+        let msg = match self.replay_manage_message(msg) {
+            Ok(msg) => msg,
+            Err(cb) => return cb,
+        };
         match msg {
             // handle raft group management request
             // ManageMessage::GroupData(data) => self.handle_group_manage(data).await,
+            ManageMessage::CreateGroup(request, tx) if request.lazy => {
+                let group_id = request.group_id;
+                let replica_id = request.replica_id;
+                let domain = request.storage_domain.clone();
+                let archive = request.archive;
+                let idempotency_key = request.idempotency_key.clone();
+                if let Err(err) = self
+                    .authorize(group_id, Operation::CreateGroup)
+                    .and_then(|_| self.check_storage_domain(group_id, &domain))
+                {
+                    return Some(ResponseCallbackQueue::new_callback(tx, Err(err)));
+                }
+                let mut res = self.register_parked_group(request).await;
+                if res.is_ok() {
+                    res = self.set_storage_domain(group_id, replica_id, domain).await;
+                }
+                if res.is_ok() {
+                    res = self.set_group_archive(group_id, replica_id, archive).await;
+                    self.deliver_unknown_group_msgs(group_id).await;
+                }
+                if res.is_ok() {
+                    self.record_manage_outcome(
+                        &idempotency_key,
+                        group_id,
+                        ManageOutcome::CreateGroup,
+                    )
+                    .await;
+                }
+                return Some(ResponseCallbackQueue::new_callback(tx, res));
+            }
             ManageMessage::CreateGroup(request, tx) => {
-                self.active_groups.insert(request.group_id);
-                let res = self
-                    .create_raft_group(
-                        request.group_id,
-                        request.replica_id,
-                        request.replicas,
-                        Some(request.applied_hint),
-                        None,
+                let group_id = request.group_id;
+                let idempotency_key = request.idempotency_key.clone();
+                let res = match self.authorize(group_id, Operation::CreateGroup) {
+                    Ok(_) => self.create_group(request).await,
+                    Err(err) => Err(err),
+                };
+                if res.is_ok() {
+                    self.record_manage_outcome(
+                        &idempotency_key,
+                        group_id,
+                        ManageOutcome::CreateGroup,
                     )
                     .await;
+                }
                 return Some(ResponseCallbackQueue::new_callback(tx, res));
             }
+            ManageMessage::CreateGroups(requests, tx) => {
+                let res = self.create_raft_groups(requests).await;
+                return Some(ResponseCallbackQueue::new_callback(tx, Ok(res)));
+            }
             ManageMessage::RemoveGroup(request, tx) => {
-                // marke delete
                 let group_id = request.group_id;
-                let group = match self.groups.get_mut(&group_id) {
-                    None => return Some(ResponseCallbackQueue::new_callback(tx, Ok(()))),
-                    Some(group) => group,
-                };
-
-                for proposal in group.proposals.drain(..) {
-                    proposal.tx.map(|tx| {
-                        tx.send(Err(Error::RaftGroup(RaftGroupError::Deleted(
-                            self.node_id,
-                            group_id,
-                        ))))
-                    });
+                if let Err(err) = self.authorize(group_id, Operation::RemoveGroup) {
+                    return Some(ResponseCallbackQueue::new_callback(tx, Err(err)));
+                }
+                if let Err(err) = self.materialize_group(group_id).await {
+                    return Some(ResponseCallbackQueue::new_callback(tx, Err(err)));
+                }
+                if self.removing_groups.contains_key(&group_id) {
+                    return Some(ResponseCallbackQueue::new_callback(
+                        tx,
+                        Err(Error::BadParameter(format!(
+                            "group {} is being removed",
+                            group_id
+                        ))),
+                    ));
                 }
 
-                group.status = Status::Delete;
+                // the leader transfers the leadership before removed, so the
+                // group avoids an election.
+                let transferee = match self.groups.get_mut(&group_id) {
+                    Some(group) if group.is_leader() => group.transferee().map(|transferee| {
+                        group.raft_group.transfer_leader(transferee);
+                        transferee
+                    }),
+                    _ => None,
+                };
+                if let Some(transferee) = transferee {
+                    info!(
+                        "node {}: transfer leadership of group {} to {} before removed",
+                        self.node_id, group_id, transferee
+                    );
+                    self.active_groups.insert(group_id);
+                    self.removing_groups.insert(
+                        group_id,
+                        RemovingGroup {
+                            transferee,
+                            ticks: 0,
+                            tx,
+                            idempotency_key: request.idempotency_key,
+                            waiters: vec![],
+                        },
+                    );
+                    return None;
+                }
 
-                let replica_id = group.replica_id;
-                match self
-                    .storage
-                    .get_group_metadata(group_id, replica_id)
+                let res = self
+                    .do_remove_group(group_id)
                     .await
-                    .unwrap()
-                {
-                    None => {
-                        self.storage
-                            .set_group_metadata(GroupMetadata {
-                                group_id,
-                                replica_id,
-                                node_id: self.node_id,
-                                create_timestamp: 0,
-                                leader_id: group.leader.replica_id,
-                                deleted: true,
-                            })
-                            .await
-                            .unwrap();
-                    }
-                    Some(mut meta) => {
-                        if !meta.deleted {
-                            meta.deleted = true;
-                            self.storage.set_group_metadata(meta).await.unwrap();
-                        }
-                    }
+                    .map(|replica_id| GroupRemoval {
+                        group_id,
+                        replica_id: replica_id.unwrap_or(request.replica_id),
+                        ..Default::default()
+                    });
+                if let Ok(removal) = &res {
+                    self.record_manage_outcome(
+                        &request.idempotency_key,
+                        group_id,
+                        ManageOutcome::RemoveGroup(removal.clone()),
+                    )
+                    .await;
                 }
-
                 // TODO: impl broadcast
+                return Some(ResponseCallbackQueue::new_callback(tx, res));
+            }
+            ManageMessage::PauseStorageDomain(domain, tx) => {
+                let res = if domain.is_empty() {
+                    Err(Error::BadParameter(
+                        "storage domain must not be empty".to_owned(),
+                    ))
+                } else {
+                    self.pause_storage_domain(&domain);
+                    Ok(())
+                };
+                return Some(ResponseCallbackQueue::new_callback(tx, res));
+            }
+            ManageMessage::ResumeStorageDomain(domain, tx) => {
+                self.resume_storage_domain(&domain);
                 return Some(ResponseCallbackQueue::new_callback(tx, Ok(())));
             }
+            ManageMessage::GcReplicaDescs(dry_run, tx) => {
+                let res = self.gc_replica_descs(dry_run).await;
+                return Some(ResponseCallbackQueue::new_callback(tx, res));
+            }
+            ManageMessage::CheckStorage(tx) => {
+                let res = self.check_storage().await;
+                return Some(ResponseCallbackQueue::new_callback(tx, res));
+            }
+            ManageMessage::RepairStorage(repair, tx) => {
+                let res = self.repair_storage(repair).await;
+                return Some(ResponseCallbackQueue::new_callback(tx, res));
+            }
+            ManageMessage::SetGroupArchive(group_id, archive, tx) => {
+                let replica_id = match self.groups.get(&group_id) {
+                    Some(group) => Some(group.replica_id),
+                    None => self
+                        .parked_groups
+                        .get(&group_id)
+                        .map(|parked| parked.replica_id),
+                };
+                let res = match replica_id {
+                    None => Err(self.missing_group_error(group_id)),
+                    Some(replica_id) => self.set_group_archive(group_id, replica_id, archive).await,
+                };
+                return Some(ResponseCallbackQueue::new_callback(tx, res));
+            }
+            ManageMessage::ReleaseGateHold(group_id, tx) => {
+                let res = match self.groups.get(&group_id) {
+                    None => Err(self.missing_group_error(group_id)),
+                    Some(group) => {
+                        self.send_apply_msg(ApplyMessage::ReleaseHold {
+                            group_id,
+                            replica_id: group.replica_id,
+                        });
+                        Ok(())
+                    }
+                };
+                return Some(ResponseCallbackQueue::new_callback(tx, res));
+            }
+            ManageMessage::ForkGroup(src_group_id, group_id, member_mapping, tx) => {
+                let res = self
+                    .fork_group(src_group_id, group_id, member_mapping)
+                    .await;
+                return Some(ResponseCallbackQueue::new_callback(tx, res));
+            }
+            ManageMessage::TransferLeader(group_id, transferee, tx) => {
+                let res = self.transfer_leader(group_id, transferee);
+                return Some(ResponseCallbackQueue::new_callback(tx, res));
+            }
+            ManageMessage::ForwardMembership(group_id, data, tx) => {
+                let res = self.forward_membership(group_id, data).await;
+                return Some(ResponseCallbackQueue::new_callback(tx, res));
+            }
+        }
+    }
+
+    /// Propose the membership change without tracking it, the follower
+    /// forwards the change to the leader by raft.
+    async fn forward_membership(
+        &mut self,
+        group_id: u64,
+        data: MembershipChangeData,
+    ) -> Result<(), Error> {
+        self.materialize_group(group_id).await?;
+        let group = match self.groups.get_mut(&group_id) {
+            Some(group) => group,
+            None => return Err(self.missing_group_error(group_id)),
+        };
+        info!(
+            "node {}: forward membership change {:?} of group {}",
+            self.node_id, data.changes, group_id
+        );
+        group.forward_membership_change(data)?;
+        self.active_groups.insert(group_id);
+        Ok(())
+    }
+
+    /// Transfer the leadership of the group to the voter `transferee`,
+    /// returns the term in which the transfer is started.
+    fn transfer_leader(&mut self, group_id: u64, transferee: u64) -> Result<u64, Error> {
+        let group = match self.groups.get_mut(&group_id) {
+            Some(group) => group,
+            None => return Err(self.missing_group_error(group_id)),
+        };
+        if !group.is_leader() {
+            return Err(Error::Propose(ProposeError::not_leader(
+                self.node_id,
+                group_id,
+                &group.shared_state,
+            )));
         }
+        if !group
+            .raft_group
+            .raft
+            .prs()
+            .conf()
+            .voters()
+            .contains(transferee)
+        {
+            return Err(Error::BadParameter(format!(
+                "replica {} is not a voter of group {}",
+                transferee, group_id
+            )));
+        }
+
+        info!(
+            "node {}: transfer leadership of group {} to {}",
+            self.node_id, group_id, transferee
+        );
+        group.raft_group.transfer_leader(transferee);
+        self.active_groups.insert(group_id);
+        Ok(group.term())
     }
 
     // #[tracing::instrument(
@@ -849,8 +2133,151 @@ where
         replicas_desc: Vec<ReplicaDesc>,
         applied_hint: Option<u64>,
         init_msg: Option<MultiRaftMessage>,
+        priority: u32,
     ) -> Result<(), Error> {
-        if self.groups.contains_key(&group_id) {
+        self.check_create_group(group_id, replica_id)?;
+        let group_storage = self.storage.group_storage(group_id, replica_id).await?;
+        let applied = Self::select_applied(&group_storage, applied_hint).await;
+        let (raft_group, rs) = self.halt_on_fatal(Self::build_raw_node(
+            &self.cfg,
+            group_id,
+            replica_id,
+            group_storage,
+            applied,
+        ))?;
+        self.register_raft_group(
+            group_id,
+            replica_id,
+            replicas_desc,
+            raft_group,
+            rs,
+            init_msg,
+            priority,
+        )
+        .await
+    }
+
+    /// Create the raft groups of the requests in one batch, the storages of the
+    /// groups are initialized by one call of storage and the raw nodes are built
+    /// in parallel. Returns the results in the order of requests.
+    fn authorize(&self, group_id: u64, op: Operation) -> Result<(), Error> {
+        authorize(self.cfg.authorizer.as_ref(), self.node_id, group_id, op)
+    }
+
+    async fn create_raft_groups(
+        &mut self,
+        requests: Vec<CreateGroupRequest>,
+    ) -> Vec<Result<(), Error>> {
+        let mut results = (0..requests.len()).map(|_| None).collect::<Vec<_>>();
+        let mut group_ids = HashSet::new();
+        let mut pending = vec![];
+        for (i, mut request) in requests.into_iter().enumerate() {
+            if !group_ids.insert(request.group_id) {
+                results[i] = Some(Err(Error::RaftGroup(RaftGroupError::Exists(
+                    self.node_id,
+                    request.group_id,
+                ))));
+                continue;
+            }
+
+            if let Err(err) = self
+                .authorize(request.group_id, Operation::CreateGroup)
+                .and_then(|_| self.check_create_group(request.group_id, request.replica_id))
+                .and_then(|_| self.check_storage_domain(request.group_id, &request.storage_domain))
+            {
+                results[i] = Some(Err(err));
+                continue;
+            }
+
+            if request.lazy {
+                let group_id = request.group_id;
+                let replica_id = request.replica_id;
+                let domain = request.storage_domain.clone();
+                let archive = request.archive;
+                let mut res = self.register_parked_group(request).await;
+                if res.is_ok() {
+                    res = self.set_storage_domain(group_id, replica_id, domain).await;
+                }
+                if res.is_ok() {
+                    res = self.set_group_archive(group_id, replica_id, archive).await;
+                    self.deliver_unknown_group_msgs(group_id).await;
+                }
+                results[i] = Some(res);
+                continue;
+            }
+
+            if let Err(err) = self.init_learners(&mut request).await {
+                results[i] = Some(Err(err));
+                continue;
+            }
+            pending.push((i, request));
+        }
+
+        let storages = self
+            .storage
+            .group_storages(
+                pending
+                    .iter()
+                    .map(|(_, request)| (request.group_id, request.replica_id))
+                    .collect(),
+            )
+            .await;
+        let mut builds = Vec::with_capacity(pending.len());
+        for ((_, request), storage) in pending.iter().zip(storages) {
+            let applied = match storage.as_ref() {
+                Ok(storage) => Self::select_applied(storage, Some(request.applied_hint)).await,
+                Err(_) => 0,
+            };
+            builds.push((request.group_id, request.replica_id, storage, applied));
+        }
+        let raw_nodes = Self::build_raw_nodes(&self.cfg, builds);
+
+        for ((i, request), raw_node) in pending.into_iter().zip(raw_nodes) {
+            let group_id = request.group_id;
+            let mut res = match self.halt_on_fatal(raw_node) {
+                Err(err) => Err(err),
+                Ok((raft_group, rs)) => {
+                    self.active_groups.insert(group_id);
+                    self.register_raft_group(
+                        group_id,
+                        request.replica_id,
+                        request.replicas,
+                        raft_group,
+                        rs,
+                        None,
+                        request.priority,
+                    )
+                    .await
+                }
+            };
+            if res.is_ok() {
+                res = self
+                    .set_storage_domain(group_id, request.replica_id, request.storage_domain)
+                    .await;
+            }
+            if res.is_ok() {
+                res = self
+                    .set_group_archive(group_id, request.replica_id, request.archive)
+                    .await;
+                self.deliver_unknown_group_msgs(group_id).await;
+            }
+            results[i] = Some(res);
+        }
+
+        results
+            .into_iter()
+            .map(|res| res.expect("unreachable"))
+            .collect()
+    }
+
+    fn check_create_group(&self, group_id: u64, replica_id: u64) -> Result<(), Error> {
+        if self.halted_groups.contains(&group_id) {
+            return Err(Error::RaftGroup(RaftGroupError::Halted(
+                self.node_id,
+                group_id,
+            )));
+        }
+        if self.groups.contains_key(&group_id) || self.parked_groups.contains_key(&group_id) {
             return Err(Error::RaftGroup(RaftGroupError::Exists(
                 self.node_id,
                 group_id,
@@ -868,46 +2295,191 @@ where
                 "replica id must be more than 0".to_owned(),
             ));
         }
+        Ok(())
+    }
 
-        let group_storage = self.storage.group_storage(group_id, replica_id).await?;
+    /// The groups can't be created in the paused storage domain.
+    fn check_storage_domain(&self, group_id: u64, domain: &str) -> Result<(), Error> {
+        if self.storage_domains.is_domain_paused(domain) {
+            return Err(Error::RaftGroup(RaftGroupError::Paused(
+                self.node_id,
+                group_id,
+                domain.to_owned(),
+            )));
+        }
+        Ok(())
+    }
+
+    /// Tag the group with the storage domain. The domain is persisted to the
+    /// group metadata if the storage of the group is created, so it's
+    /// restored when the node restarts.
+    async fn set_storage_domain(
+        &mut self,
+        group_id: u64,
+        replica_id: u64,
+        domain: String,
+    ) -> Result<(), Error> {
+        if domain.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(mut meta) = self
+            .storage
+            .get_group_metadata(group_id, replica_id)
+            .await?
+        {
+            if meta.storage_domain != domain {
+                meta.storage_domain = domain.clone();
+                self.storage.set_group_metadata(meta).await?;
+            }
+        }
+        self.storage_domains.set(group_id, domain);
+        Ok(())
+    }
+
+    /// Set the archive mode of the group. The mode is persisted to the group
+    /// metadata if the storage of the group is created, so it's restored when
+    /// the node restarts.
+    async fn set_group_archive(
+        &mut self,
+        group_id: u64,
+        replica_id: u64,
+        archive: bool,
+    ) -> Result<(), Error> {
+        if archive == self.archive_groups.contains_key(&group_id) {
+            return Ok(());
+        }
+
+        if let Some(mut meta) = self
+            .storage
+            .get_group_metadata(group_id, replica_id)
+            .await?
+        {
+            if meta.archive != archive {
+                meta.archive = archive;
+                self.storage.set_group_metadata(meta).await?;
+            }
+        }
+        if archive {
+            self.archive_groups.insert(group_id, 0);
+        } else {
+            self.archive_groups.remove(&group_id);
+        }
+        info!(
+            "node {}: set archive mode of group {} to {}",
+            self.node_id, group_id, archive
+        );
+        Ok(())
+    }
+
+    /// Build the raw nodes of groups, the groups are split into chunks which are
+    /// built by scoped threads if there are many groups.
+    fn build_raw_nodes(
+        cfg: &Config,
+        groups: Vec<(u64, u64, Result<RS, super::storage::Error>, u64)>,
+    ) -> Vec<Result<(RawNode<RS>, raft::RaftState), Error>> {
+        let build = |(group_id, replica_id, storage, applied): (
+            u64,
+            u64,
+            Result<RS, super::storage::Error>,
+            u64,
+        )| {
+            storage.map_err(Error::from).and_then(|storage| {
+                Self::build_raw_node(cfg, group_id, replica_id, storage, applied)
+            })
+        };
+
+        let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        if threads == 1 || groups.len() < PARALLEL_BUILD_MIN_GROUPS {
+            return groups.into_iter().map(build).collect();
+        }
+
+        let chunk_size = (groups.len() + threads - 1) / threads;
+        let mut chunks = vec![];
+        let mut groups = groups.into_iter();
+        loop {
+            let chunk = groups.by_ref().take(chunk_size).collect::<Vec<_>>();
+            if chunk.is_empty() {
+                break;
+            }
+            chunks.push(chunk);
+        }
+
+        std::thread::scope(|scope| {
+            let handles = chunks
+                .into_iter()
+                .map(|chunk| scope.spawn(move || chunk.into_iter().map(build).collect::<Vec<_>>()))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .flat_map(|handle| handle.join().expect("build raw node panicked"))
+                .collect()
+        })
+    }
+
+    /// Select a suitable applied index from both storage and initial provided.
+    async fn select_applied(group_storage: &RS, applied_hint: Option<u64>) -> u64 {
+        cmp::max(
+            group_storage.get_applied().await.unwrap_or(0),
+            applied_hint.unwrap_or(0),
+        )
+    }
+
+    fn build_raw_node(
+        cfg: &Config,
+        group_id: u64,
+        replica_id: u64,
+        group_storage: RS,
+        applied: u64,
+    ) -> Result<(RawNode<RS>, raft::RaftState), Error> {
         let rs: raft::RaftState = group_storage
             .initial_state()
             .map_err(|err| Error::Raft(err))?;
 
-        // select a suitable applied index from both storage and initial provided.
-        let applied = cmp::max(
-            group_storage.get_applied().unwrap_or(0),
-            applied_hint.unwrap_or(0),
-        );
         let committed_index = rs.hard_state.commit;
-        let persisted_index = group_storage.last_index().unwrap();
+        let persisted_index = group_storage.last_index().map_err(|err| Error::Raft(err))?;
         if applied > cmp::min(committed_index, persisted_index) {
-            panic!(
-                "provide hit applied is out of range [applied({}), min (committed({}), persisted({}))]",
-                applied, committed_index, persisted_index
-            );
+            return Err(Error::Fatal(FatalError::AppliedOutOfRange {
+                node_id: cfg.node_id,
+                group_id,
+                applied,
+                committed: committed_index,
+                persisted: persisted_index,
+            }));
         }
 
         let raft_cfg = raft::Config {
             id: replica_id,
             applied, // TODO: support hint skip
-            election_tick: self.cfg.election_tick,
-            heartbeat_tick: self.cfg.heartbeat_tick,
-            max_size_per_msg: self.cfg.max_size_per_msg,
-            max_inflight_msgs: self.cfg.max_inflight_msgs,
-            batch_append: self.cfg.batch_append,
+            election_tick: cfg.election_tick,
+            heartbeat_tick: cfg.heartbeat_tick,
+            max_size_per_msg: cfg.max_size_per_msg,
+            max_inflight_msgs: cfg.max_inflight_msgs,
+            batch_append: cfg.batch_append,
+            check_quorum: cfg.check_quorum,
             pre_vote: true,
             ..Default::default()
         };
-        let raft_store = group_storage.clone();
-        let raft_group = raft::RawNode::with_default_logger(&raft_cfg, raft_store)
+        let raft_group = raft::RawNode::with_default_logger(&raft_cfg, group_storage)
             .map_err(|err| Error::Raft(err))?;
 
         info!(
             "node {}: replica({}) of raft group({}) is created",
-            self.node_id, group_id, replica_id
+            cfg.node_id, group_id, replica_id
         );
+        Ok((raft_group, rs))
+    }
 
+    async fn register_raft_group(
+        &mut self,
+        group_id: u64,
+        replica_id: u64,
+        replicas_desc: Vec<ReplicaDesc>,
+        raft_group: RawNode<RS>,
+        rs: raft::RaftState,
+        init_msg: Option<MultiRaftMessage>,
+        priority: u32,
+    ) -> Result<(), Error> {
         let mut leader: ReplicaDesc = ReplicaDesc::default();
 
         if let Some(init_msg) = init_msg {
@@ -957,16 +2529,32 @@ where
             replica_id,
             raft_group,
             node_ids: Vec::new(),
-            proposals: ProposalQueue::new(replica_id),
+            proposals: ProposalQueue::new(replica_id)
+                .with_max_inflight(self.cfg.max_inflight_proposals),
             leader,
             status: Status::None,
-            read_index_queue: ReadIndexQueue::new(),
+            read_index_queue: ReadIndexQueue::new()
+                .with_limits(self.cfg.batch_read_index, self.cfg.max_read_queue_len),
             shared_state: shared_state.clone(),
+            idle_ticks: 0,
+            witness: false,
+            removed_replicas: HashSet::new(),
+            log_quota: LogQuota::default(),
+            learner_catch_up: LearnerCatchUp::default(),
+            write_coalescer: WriteCoalescer::new(group_id),
+            applying_reads: VecDeque::new(),
+            applying_follower_reads: VecDeque::new(),
+            latency: self.latency.clone(),
+            log_usage: LogUsage::default(),
+            lease: LeaderLease::new(self.cfg.lease_duration(), self.cfg.time_source.clone()),
+            apply_flow: ApplyFlow::default(),
+            outgoing_snapshots: vec![],
             // applied_index: 0,
             // applied_term: 0,
             commit_index: rs.hard_state.commit,
             commit_term: rs.hard_state.term,
         };
+        shared_state.set_conf_state(group.raft_group.raft.prs().conf().to_conf_state());
 
         for replica_desc in replicas_desc.iter() {
             self.replica_cache
@@ -976,6 +2564,11 @@ where
             group.add_track_node(replica_desc.node_id);
             self.node_manager.add_group(replica_desc.node_id, group_id);
         }
+        group.witness = self
+            .replica_cache
+            .replica_desc(group_id, replica_id)
+            .await?
+            .map_or(false, |replica_desc| replica_desc.witness);
 
         // TODO: check voters and replica_descs consistent
 
@@ -1000,6 +2593,7 @@ where
             }
         }
         self.groups.insert(group_id, group);
+        self.memory_budget.register(group_id, priority);
 
         self.event_chan.push(Event::GroupCreate {
             group_id,
@@ -1007,9 +2601,14 @@ where
         });
 
         let prev_shard_state = self.shared_states.insert(group_id, shared_state);
+        // the parked group is notified by `materialize_group`.
+        if prev_shard_state.is_none() {
+            self.notify_group_created(group_id);
+        }
 
+        // the shared state of parked group is replaced when materialized.
         assert_eq!(
-            prev_shard_state.is_none(),
+            prev_shard_state.map_or(true, |state| state.is_parked()),
             true,
             "expect group {} shared state is empty, but goted",
             group_id
@@ -1037,8 +2636,616 @@ where
         for node_id in group.node_ids {
             self.node_manager.remove_group(node_id, group_id);
         }
+        self.memory_budget.unregister(group_id);
+        self.storage_domains.remove(group_id);
+        self.archive_groups.remove(&group_id);
+        self.pausing_groups.remove(&group_id);
+
+        Ok(())
+    }
+
+    /// Mark the group deleted, returns the replica id of the group or `None`
+    /// if the group does not exist.
+    async fn do_remove_group(&mut self, group_id: u64) -> Result<Option<u64>, Error> {
+        let group = match self.groups.get_mut(&group_id) {
+            None => return Ok(None),
+            Some(group) => group,
+        };
+
+        for proposal in group.proposals.drain(..) {
+            proposal.tx.map(|tx| {
+                tx.send(Err(Error::RaftGroup(RaftGroupError::Deleted(
+                    self.node_id,
+                    group_id,
+                ))))
+            });
+        }
+
+        group.status = Status::Delete;
+        self.memory_budget.unregister(group_id);
+
+        let replica_id = group.replica_id;
+        match self
+            .storage
+            .get_group_metadata(group_id, replica_id)
+            .await?
+        {
+            None => {
+                self.storage
+                    .set_group_metadata(GroupMetadata {
+                        group_id,
+                        replica_id,
+                        node_id: self.node_id,
+                        create_timestamp: 0,
+                        leader_id: group.leader.replica_id,
+                        deleted: true,
+                        storage_domain: String::new(),
+                        archive: false,
+                        gate_policy: 0,
+                    })
+                    .await?;
+            }
+            Some(mut meta) => {
+                if !meta.deleted {
+                    meta.deleted = true;
+                    self.storage.set_group_metadata(meta).await?;
+                }
+            }
+        }
+
+        self.send_apply_msg(ApplyMessage::GroupRemoved {
+            group_id,
+            replica_id,
+        });
+
+        Ok(Some(replica_id))
+    }
+
+    /// Restore the outcomes of the idempotency keys persisted before the node
+    /// restarts, the expired records are removed.
+    async fn restore_idempotency_keys(&mut self) {
+        if !self.idempotency_keys.is_enabled() {
+            return;
+        }
+
+        let records = match self.storage.scan_idempotency_records().await {
+            Ok(records) => records,
+            Err(err) => {
+                warn!(
+                    "node {}: restore idempotency keys error, scan idempotency records: {}",
+                    self.node_id, err
+                );
+                return;
+            }
+        };
+        for key in self.idempotency_keys.restore(records, unix_millis()) {
+            if let Err(err) = self.storage.remove_idempotency_record(key).await {
+                warn!(
+                    "node {}: remove expired idempotency record error: {}",
+                    self.node_id, err
+                );
+            }
+        }
+    }
+
+    /// Record the outcome of the management request by its idempotency key
+    /// and persist it, the records of the expired keys are removed as well.
+    /// The outcome is kept in memory if the storage fails, it's lost only if
+    /// the node restarts.
+    async fn record_manage_outcome(&mut self, key: &str, group_id: u64, outcome: ManageOutcome) {
+        let record = match self
+            .idempotency_keys
+            .record(key, group_id, outcome, unix_millis())
+        {
+            None => return,
+            Some(record) => record,
+        };
+
+        for key in self.idempotency_keys.take_expired() {
+            if let Err(err) = self.storage.remove_idempotency_record(key).await {
+                warn!(
+                    "node {}: remove expired idempotency record error: {}",
+                    self.node_id, err
+                );
+            }
+        }
+        if let Err(err) = self.storage.set_idempotency_record(record).await {
+            warn!(
+                "node {}: persist the outcome of idempotency key {} of group {} error: {}",
+                self.node_id, key, group_id, err
+            );
+        }
+    }
+
+    /// Respond the management request by the outcome recorded by its
+    /// idempotency key, the retry of the group removal in flight waits for
+    /// the removal. Returns the message if there is no outcome to replay.
+    fn replay_manage_message(
+        &mut self,
+        msg: ManageMessage,
+    ) -> Result<ManageMessage, Option<ResponseCallback>> {
+        match msg {
+            ManageMessage::CreateGroup(request, tx) if !request.idempotency_key.is_empty() => {
+                match self.idempotency_keys.get(
+                    &request.idempotency_key,
+                    Operation::CreateGroup,
+                    request.group_id,
+                ) {
+                    Ok(None) => Ok(ManageMessage::CreateGroup(request, tx)),
+                    Ok(Some(_)) => Err(Some(ResponseCallbackQueue::new_callback(tx, Ok(())))),
+                    Err(err) => Err(Some(ResponseCallbackQueue::new_callback(tx, Err(err)))),
+                }
+            }
+            ManageMessage::RemoveGroup(request, tx) if !request.idempotency_key.is_empty() => {
+                if let Some(removing) = self.removing_groups.get_mut(&request.group_id) {
+                    if removing.idempotency_key == request.idempotency_key {
+                        removing.waiters.push(tx);
+                        return Err(None);
+                    }
+                }
+                match self.idempotency_keys.get(
+                    &request.idempotency_key,
+                    Operation::RemoveGroup,
+                    request.group_id,
+                ) {
+                    Ok(Some(ManageOutcome::RemoveGroup(removal))) => {
+                        Err(Some(ResponseCallbackQueue::new_callback(tx, Ok(removal))))
+                    }
+                    Ok(_) => Ok(ManageMessage::RemoveGroup(request, tx)),
+                    Err(err) => Err(Some(ResponseCallbackQueue::new_callback(tx, Err(err)))),
+                }
+            }
+            msg => Ok(msg),
+        }
+    }
+
+    /// Remove the leaders which the new leader is established or waiting
+    /// for the transfer timeout.
+    async fn handle_removing_groups(&mut self) {
+        let removes = self
+            .removing_groups
+            .iter()
+            .filter(|(group_id, removing)| {
+                removing.ticks >= self.cfg.election_tick
+                    || self.groups.get(group_id).map_or(true, |group| {
+                        let leader_id = group.raft_group.raft.leader_id;
+                        leader_id != 0 && leader_id != group.replica_id
+                    })
+            })
+            .map(|(group_id, _)| *group_id)
+            .collect::<Vec<_>>();
+
+        for group_id in removes {
+            let removing = self
+                .removing_groups
+                .remove(&group_id)
+                .expect("unreachable: removing group collected in the previous code");
+            let transferred = self.groups.get(&group_id).map_or(false, |group| {
+                let leader_id = group.raft_group.raft.leader_id;
+                leader_id != 0 && leader_id != group.replica_id
+            });
+            if !transferred {
+                warn!(
+                    "node {}: transfer leadership of group {} to {} timeout, remove the leader",
+                    self.node_id, group_id, removing.transferee
+                );
+            }
+
+            let res = self
+                .do_remove_group(group_id)
+                .await
+                .map(|replica_id| GroupRemoval {
+                    group_id,
+                    replica_id: replica_id.unwrap_or_default(),
+                    transferee: Some(removing.transferee),
+                    transferred,
+                });
+            if let Ok(removal) = &res {
+                self.record_manage_outcome(
+                    &removing.idempotency_key,
+                    group_id,
+                    ManageOutcome::RemoveGroup(removal.clone()),
+                )
+                .await;
+            }
+            for waiter in removing.waiters {
+                let res = match &res {
+                    Ok(removal) => Ok(removal.clone()),
+                    Err(err) => Err(Error::BadParameter(format!(
+                        "remove group {} error: {}",
+                        group_id, err
+                    ))),
+                };
+                self.pending_responses
+                    .push_back(ResponseCallbackQueue::new_callback(waiter, res));
+            }
+            self.pending_responses
+                .push_back(ResponseCallbackQueue::new_callback(removing.tx, res));
+        }
+    }
+
+    /// Register the metadata of the group without creating raft group, the
+    /// group is parked until the first message or proposal arrives.
+    async fn register_parked_group(
+        &mut self,
+        mut request: CreateGroupRequest,
+    ) -> Result<(), Error> {
+        let group_id = request.group_id;
+        let replica_id = request.replica_id;
+        if self.groups.contains_key(&group_id) || self.parked_groups.contains_key(&group_id) {
+            return Err(Error::RaftGroup(RaftGroupError::Exists(
+                self.node_id,
+                group_id,
+            )));
+        }
+
+        if group_id == 0 {
+            return Err(Error::BadParameter(
+                "group id must be more than 0".to_owned(),
+            ));
+        }
+
+        if replica_id == 0 {
+            return Err(Error::BadParameter(
+                "replica id must be more than 0".to_owned(),
+            ));
+        }
+
+        self.init_learners(&mut request).await?;
+        for replica_desc in request.replicas.iter() {
+            self.replica_cache
+                .cache_replica_desc(group_id, replica_desc.clone(), true)
+                .await?;
+            self.node_manager.add_group(replica_desc.node_id, group_id);
+        }
+
+        let shared_state = Arc::new(GroupState::from((
+            replica_id,
+            0, /* commit_index */
+            0, /* commit_term */
+            NO_LEADER,
+            StateRole::Follower,
+        )));
+        shared_state.set_parked(true);
+        self.shared_states.insert(group_id, shared_state);
+
+        self.parked_groups.insert(
+            group_id,
+            ParkedGroup {
+                replica_id,
+                replicas: request.replicas,
+                applied_hint: Some(request.applied_hint),
+                priority: request.priority,
+                campaign: false,
+                materialized: false,
+            },
+        );
+        info!(
+            "node {}: replica({}) of raft group({}) is registered and parked",
+            self.node_id, replica_id, group_id
+        );
+        Ok(())
+    }
+
+    /// Create the raft group of the parked group from storage, it's a no-op
+    /// if the group is not parked.
+    async fn materialize_group(&mut self, group_id: u64) -> Result<(), Error> {
+        if !self.parked_groups.contains_key(&group_id) {
+            return Ok(());
+        }
+
+        // the groups of paused storage domain are kept parked until resumed.
+        if let Some(domain) = self.storage_domains.paused_domain(group_id) {
+            return Err(Error::RaftGroup(RaftGroupError::Paused(
+                self.node_id,
+                group_id,
+                domain.to_owned(),
+            )));
+        }
+
+        let parked = match self.parked_groups.remove(&group_id) {
+            None => return Ok(()),
+            Some(parked) => parked,
+        };
+
+        if let Err(err) = self
+            .create_raft_group(
+                group_id,
+                parked.replica_id,
+                parked.replicas.clone(),
+                parked.applied_hint,
+                None,
+                parked.priority,
+            )
+            .await
+        {
+            error!(
+                "node {}: materialize replica({}) of raft group({}) error {}",
+                self.node_id, parked.replica_id, group_id, err
+            );
+            self.parked_groups.insert(group_id, parked);
+            return Err(err);
+        }
+
+        // the group created lazily is created when first materialized.
+        if !parked.materialized {
+            self.notify_group_created(group_id);
+        }
+
+        // persist the domain of the group created lazily.
+        if let Some(domain) = self.storage_domains.domain(group_id) {
+            let domain = domain.to_owned();
+            if let Err(err) = self
+                .set_storage_domain(group_id, parked.replica_id, domain)
+                .await
+            {
+                warn!(
+                    "node {}: persist storage domain of replica({}) of raft group({}) error {}",
+                    self.node_id, parked.replica_id, group_id, err
+                );
+            }
+        }
+
+        let group = self
+            .groups
+            .get_mut(&group_id)
+            .expect("unreachable: group created in the previous code");
+        if parked.campaign {
+            if let Err(err) = group.raft_group.campaign() {
+                warn!(
+                    "node {}: campaign materialized replica({}) of raft group({}) error {}",
+                    self.node_id, parked.replica_id, group_id, err
+                );
+            }
+        }
+        self.active_groups.insert(group_id);
+        info!(
+            "node {}: replica({}) of raft group({}) is materialized",
+            self.node_id, parked.replica_id, group_id
+        );
+        Ok(())
+    }
+
+    #[inline]
+    async fn try_materialize_group(&mut self, group_id: u64) {
+        if self.parked_groups.contains_key(&group_id) {
+            // the error is logged and responded by the following handler
+            // because of the group does not exists.
+            let _ = self.materialize_group(group_id).await;
+        }
+    }
+
+    /// Park the least recently active groups which idle over `group_park_idle_ticks`
+    /// until the number of materialized groups does not exceed `max_active_groups`.
+    /// The leaders are never parked, otherwise the followers campaign after the
+    /// heartbeats stopped.
+    fn park_idle_groups(&mut self) {
+        if self.cfg.max_active_groups == 0 || self.groups.len() <= self.cfg.max_active_groups {
+            return;
+        }
+
+        let mut idle_groups = self
+            .groups
+            .iter()
+            .filter(|(_, group)| {
+                group.idle_ticks >= self.cfg.group_park_idle_ticks && !group.is_leader()
+            })
+            .map(|(group_id, group)| (*group_id, group.idle_ticks))
+            .collect::<Vec<_>>();
+        // least recently active first
+        idle_groups.sort_by(|a, b| b.1.cmp(&a.1));
+
+        let excess = self.groups.len() - self.cfg.max_active_groups;
+        for (group_id, _) in idle_groups.into_iter().take(excess) {
+            self.park_group(group_id);
+        }
+    }
+
+    fn park_group(&mut self, group_id: u64) {
+        match self.groups.get(&group_id) {
+            Some(group) if group.can_park() && !group.is_leader() => {}
+            _ => return,
+        };
+        self.do_park_group(group_id, false);
+    }
+
+    fn do_park_group(&mut self, group_id: u64, campaign: bool) {
+        let group = match self.groups.remove(&group_id) {
+            None => return,
+            Some(group) => group,
+        };
+        self.active_groups.remove(&group_id);
+        let priority = self
+            .memory_budget
+            .priority(group_id)
+            .unwrap_or(DEFAULT_PRIORITY);
+        self.memory_budget.unregister(group_id);
+        group.shared_state.set_parked(true);
+
+        self.parked_groups.insert(
+            group_id,
+            ParkedGroup {
+                replica_id: group.replica_id,
+                replicas: vec![],
+                applied_hint: None,
+                priority,
+                campaign,
+                materialized: true,
+            },
+        );
+        self.event_chan.push(Event::GroupPark {
+            group_id,
+            replica_id: group.replica_id,
+        });
+        debug!(
+            "node {}: replica({}) of raft group({}) is parked",
+            self.node_id, group.replica_id, group_id
+        );
+    }
+
+    /// Pause all groups of the storage domain on the node. The leaders of the
+    /// domain transfer the leadership to the most up-to-date voter first and
+    /// are parked after transferred or `election_tick` ticks, other groups are
+    /// parked immediately. The paused groups are not materialized until the
+    /// domain is resumed.
+    fn pause_storage_domain(&mut self, domain: &str) {
+        if !self.storage_domains.pause(domain) {
+            return;
+        }
+
+        let groups = self.storage_domains.groups(domain);
+        for group_id in groups.iter() {
+            if let Some(parked) = self.parked_groups.get_mut(group_id) {
+                // the leadership is taken by others when paused.
+                parked.campaign = false;
+                continue;
+            }
+
+            let group = match self.groups.get_mut(group_id) {
+                None => continue,
+                Some(group) => group,
+            };
+
+            if group.is_leader() {
+                if let Some(transferee) = group.transferee() {
+                    group.raft_group.transfer_leader(transferee);
+                    self.active_groups.insert(*group_id);
+                    self.pausing_groups.insert(*group_id, 0);
+                    continue;
+                }
+            }
+            self.force_park_group(*group_id);
+        }
+
+        warn!(
+            "node {}: storage domain {} is paused, groups = {:?}",
+            self.node_id, domain, groups
+        );
+        self.event_chan.push(Event::StorageDomainPaused {
+            domain: domain.to_owned(),
+            groups,
+        });
+    }
+
+    /// Resume the groups of the paused storage domain, the groups are
+    /// materialized by the next message or proposal.
+    fn resume_storage_domain(&mut self, domain: &str) {
+        if !self.storage_domains.resume(domain) {
+            return;
+        }
+
+        for group_id in self.storage_domains.groups(domain) {
+            self.pausing_groups.remove(&group_id);
+        }
+        info!(
+            "node {}: storage domain {} is resumed",
+            self.node_id, domain
+        );
+        self.event_chan.push(Event::StorageDomainResumed {
+            domain: domain.to_owned(),
+        });
+    }
+
+    /// Park the pausing groups which the leadership is transferred or waiting
+    /// for the transfer timeout.
+    fn tick_pausing_groups(&mut self) {
+        if self.pausing_groups.is_empty() {
+            return;
+        }
+
+        let mut parks = vec![];
+        for (group_id, ticks) in self.pausing_groups.iter_mut() {
+            *ticks += 1;
+            let transferred = self
+                .groups
+                .get(group_id)
+                .map_or(true, |group| !group.is_leader());
+            if transferred || *ticks >= self.cfg.election_tick {
+                parks.push(*group_id);
+            }
+        }
+
+        for group_id in parks {
+            self.pausing_groups.remove(&group_id);
+            self.force_park_group(group_id);
+        }
+    }
+
+    /// Park the group of the paused storage domain regardless of the in-flight
+    /// requests, which fail with `RaftGroupError::Paused`.
+    fn force_park_group(&mut self, group_id: u64) {
+        let domain = match self.storage_domains.paused_domain(group_id) {
+            None => return,
+            Some(domain) => domain.to_owned(),
+        };
+
+        if let Some(group) = self.groups.get_mut(&group_id) {
+            let node_id = self.node_id;
+            group.fail_pending_requests(|| {
+                Error::RaftGroup(RaftGroupError::Paused(node_id, group_id, domain.clone()))
+            });
+        }
+        self.do_park_group(group_id, false);
+    }
+
+    /// Record the storage failure of the group, the domain of the group is paused
+    /// if the failures reach the threshold. Returns true if the domain of the group
+    /// is paused, so the failure of the group is not fatal to the node.
+    fn record_storage_failure(&mut self, group_id: u64, fatal: bool) -> bool {
+        if self.storage_domains.paused_domain(group_id).is_some() {
+            return true;
+        }
+
+        match self.storage_domains.record_failure(group_id, fatal) {
+            None => false,
+            Some(domain) => {
+                error!(
+                    "node {}: group {} storage failed, pause storage domain {}",
+                    self.node_id, group_id, domain
+                );
+                self.pause_storage_domain(&domain);
+                true
+            }
+        }
+    }
+
+    /// Halt the group of the fatal error after the error is emitted, the
+    /// in-flight requests of the group fail with `RaftGroupError::Halted`.
+    fn halt_group(&mut self, err: FatalError) {
+        let group_id = err.group_id();
+        self.fatal_errors.emit(err);
+        self.halted_groups.insert(group_id);
+        self.active_groups.remove(&group_id);
+        self.parked_groups.remove(&group_id);
+
+        let mut group = match self.groups.remove(&group_id) {
+            None => return,
+            Some(group) => group,
+        };
+        let node_id = self.node_id;
+        group.fail_pending_requests(|| Error::RaftGroup(RaftGroupError::Halted(node_id, group_id)));
+        for node_id in group.node_ids.iter() {
+            self.node_manager.remove_group(*node_id, group_id);
+        }
+        self.memory_budget.unregister(group_id);
+        self.archive_groups.remove(&group_id);
+        self.pausing_groups.remove(&group_id);
+    }
+
+    /// Fail the in-flight proposals and read indexes of the group with the
+    /// storage error of the ready, the writes of them are not retried.
+    fn fail_on_storage_error(&mut self, group_id: u64, err: &super::storage::Error) {
+        if let Some(group) = self.groups.get_mut(&group_id) {
+            group.fail_pending_requests(|| Error::Storage(err.duplicate()));
+        }
+    }
 
-        Ok(())
+    /// Halt the group if the result is the fatal error.
+    fn halt_on_fatal<T>(&mut self, res: Result<T, Error>) -> Result<T, Error> {
+        if let Err(Error::Fatal(err)) = res.as_ref() {
+            self.halt_group(err.clone());
+        }
+        res
     }
 
     #[tracing::instrument(
@@ -1056,23 +3263,235 @@ where
         };
 
         group.advance_apply(&result);
+        // the held applies are sent once the state machine catches up.
+        let resumed = group
+            .apply_flow
+            .on_applied(result.applied_index, self.cfg.max_apply_unapplied_size);
+        self.memory_budget
+            .release_to(result.group_id, result.applied_index);
+        self.memory_budget
+            .set_cached(result.group_id, group.apply_flow.cached_size());
+        if let Some(apply) = resumed {
+            debug!(
+                "node {}: resume applies of group {} from index {}, {} bytes unapplied",
+                self.node_id,
+                result.group_id,
+                apply.entries.first().map_or(0, |entry| entry.index),
+                group.apply_flow.unapplied_size()
+            );
+            self.send_applys(HashMap::from([(result.group_id, apply)]));
+        }
         debug!(
             "node {}: group = {} apply state change = {:?}",
             self.node_id, result.group_id, result
         );
     }
 
+    /// Deliver the buffered messages which arrived before the group was created.
+    async fn deliver_unknown_group_msgs(&mut self, group_id: u64) {
+        for msg in self.unknown_group_msgs.take(group_id) {
+            if let Err(err) = self.handle_raft_message(msg).await {
+                warn!(
+                    "node {}: deliver buffered message to group {} error {}",
+                    self.node_id, group_id, err
+                );
+            }
+        }
+    }
+
+    fn tick_unknown_group_msgs(&mut self) {
+        let expired = self.unknown_group_msgs.tick();
+        if expired != 0 {
+            debug!(
+                "node {}: drop {} expired messages of unknown groups",
+                self.node_id, expired
+            );
+        }
+    }
+
+    /// Escalate the groups whose raft log exceeds `Config::group_log_quota`
+    /// bytes, see `LogQuotaStage`.
+    fn tick_log_quotas(&mut self) {
+        let quota = self.cfg.group_log_quota;
+        if quota == 0 {
+            return;
+        }
+
+        let escalation_ticks = self.cfg.log_quota_escalation_ticks;
+        let compactor = &self.cfg.log_compactor;
+        for group in self.groups.values_mut() {
+            let bytes = group.log_usage.bytes;
+            let stage = group.log_quota.tick(bytes, quota, escalation_ticks);
+            // the log is compacted as the entries are applied until it's under
+            // the quota.
+            if group.log_quota.stage() >= LogQuotaStage::Compact {
+                self.quota_compactions.insert(group.group_id);
+            }
+            let stage = match stage {
+                Some(stage) => stage,
+                None => continue,
+            };
+
+            let (group_id, replica_id) = (group.group_id, group.replica_id);
+            if stage == LogQuotaStage::BuildSnapshot {
+                let applied = group.raft_group.raft.raft_log.applied;
+                compactor.build_snapshot(group_id, replica_id, applied);
+            }
+            group
+                .shared_state
+                .set_log_quota_exceeded(stage == LogQuotaStage::Backpressure);
+            warn!(
+                "node {}: raft log of group {} has {} bytes, quota {}, log quota stage {:?}",
+                self.node_id, group_id, bytes, quota, stage
+            );
+            self.event_chan.push(Event::LogQuota {
+                group_id,
+                replica_id,
+                bytes,
+                quota,
+                stage,
+            });
+        }
+    }
+
+    /// Truncate the raft log of the groups in the archive mode to the last
+    /// `Config::archive_log_entries` entries by `Config::log_compactor`, the
+    /// followers lagging beyond the log are served snapshots.
+    fn tick_archive_groups(&mut self) {
+        if self.archive_groups.is_empty() {
+            return;
+        }
+
+        let keep = self.cfg.archive_log_entries;
+        let compactor = &self.cfg.log_compactor;
+        for (group_id, requested) in self.archive_groups.iter_mut() {
+            // the parked groups are compacted after materialized.
+            let group = match self.groups.get(group_id) {
+                None => continue,
+                Some(group) => group,
+            };
+            let raft_log = &group.raft_group.raft.raft_log;
+            let applied = raft_log.applied;
+            let compact_index = cmp::min(applied, (raft_log.last_index() + 1).saturating_sub(keep));
+            if compact_index <= raft_log.first_index() || compact_index <= *requested {
+                continue;
+            }
+
+            debug!(
+                "node {}: compact raft log of archive group {} before {}, applied = {}",
+                self.node_id, group_id, compact_index, applied
+            );
+            compactor.build_snapshot(*group_id, group.replica_id, applied);
+            compactor.compact(*group_id, group.replica_id, compact_index);
+            *requested = compact_index;
+        }
+    }
+
+    /// Rebalance the memory budget of groups, shrink the caches of groups whose
+    /// allotment was reclaimed and update the allocation to shared states.
+    fn tick_memory_budget(&mut self) {
+        if !self.memory_budget.is_enabled() {
+            return;
+        }
+
+        for group_id in self.memory_budget.tick() {
+            if let Some(group) = self.groups.get_mut(&group_id) {
+                debug!(
+                    "node {}: memory budget of group {} is reclaimed",
+                    self.node_id, group_id
+                );
+                group.shrink_caches();
+            }
+        }
+
+        for group_id in self.memory_budget.take_changed() {
+            if let (Some(group), Some((allotted, used))) = (
+                self.groups.get(&group_id),
+                self.memory_budget.allocation(group_id),
+            ) {
+                group.shared_state.set_memory_allotted(allotted as u64);
+                group.shared_state.set_memory_used(used as u64);
+            }
+        }
+    }
+
     async fn handle_apply_commit(&mut self, commit: ApplyCommitMessage) {
         match commit {
             ApplyCommitMessage::None => return,
+            ApplyCommitMessage::Halt(err) => self.halt_group(err),
             ApplyCommitMessage::Membership((commit, tx)) => {
+                let (group_id, index, term) = (commit.group_id, commit.index, commit.term);
                 let res = self.commit_membership_change(commit).await;
+                if let Ok(conf_state) = res.as_ref() {
+                    self.emit_membership_change(group_id, index, term, conf_state.clone())
+                        .await;
+                }
                 self.pending_responses
                     .push_back(ResponseCallbackQueue::new_callback(tx, res))
             }
         }
     }
 
+    /// Emit `Event::MembershipChange` with the health of replicas in the resulting
+    /// configuration, the health is gathered only if the replica is leader.
+    async fn emit_membership_change(
+        &mut self,
+        group_id: u64,
+        index: u64,
+        term: u64,
+        conf_state: ConfState,
+    ) {
+        let group = match self.groups.get(&group_id) {
+            None => return,
+            Some(group) => group,
+        };
+        let replica_id = group.replica_id;
+
+        let mut progresses = vec![];
+        if group.is_leader() {
+            let prs = group.raft_group.raft.prs();
+            let learners = conf_state.learners.iter().map(|id| (*id, true));
+            for (id, learner) in conf_state
+                .voters
+                .iter()
+                .map(|id| (*id, false))
+                .chain(learners)
+            {
+                let (reachable, matched) = match prs.get(id) {
+                    None => (false, 0),
+                    // the leader is always reachable for itself.
+                    Some(pr) => (id == replica_id || pr.recent_active, pr.matched),
+                };
+                progresses.push((id, learner, reachable, matched));
+            }
+        }
+
+        let mut health = Vec::with_capacity(progresses.len());
+        for (id, learner, reachable, matched) in progresses {
+            let node_id = match self.replica_cache.replica_desc(group_id, id).await {
+                Ok(Some(replica_desc)) => replica_desc.node_id,
+                _ => NO_NODE,
+            };
+            health.push(ReplicaHealth {
+                replica_id: id,
+                node_id,
+                learner,
+                reachable,
+                matched,
+            });
+        }
+
+        self.event_chan
+            .push(Event::MembershipChange(MembershipChangeEvent {
+                group_id,
+                replica_id,
+                index,
+                term,
+                conf_state,
+                health,
+            }));
+    }
+
     fn handle_query_group(&self, msg: QueryGroup) {
         match msg {
             QueryGroup::HasPendingConf(group_id, tx) => match self.get_group(group_id) {
@@ -1094,6 +3513,319 @@ where
                     }
                 }
             },
+            QueryGroup::ReplicaProgress(group_id, replica_id, tx) => {
+                let res = self.get_group(group_id).and_then(|group| {
+                    if !group.is_leader() {
+                        return Err(Error::Propose(ProposeError::NotLeader {
+                            node_id: self.node_id,
+                            group_id,
+                            replica_id: group.replica_id,
+                        }));
+                    }
+                    let committed = group.raft_group.raft.raft_log.committed;
+                    Ok(group
+                        .raft_group
+                        .raft
+                        .prs()
+                        .get(replica_id)
+                        .map(|pr| (pr.matched, committed)))
+                });
+                if let Err(_) = tx.send(res) {
+                    error!("send query ReplicaProgress result error, receiver dropped");
+                }
+            }
+            QueryGroup::ListGroups(cursor, limit, tx) => {
+                if let Err(_) = tx.send(self.list_groups(cursor, limit)) {
+                    error!("send query ListGroups result error, receiver dropped");
+                }
+            }
+            QueryGroup::NodeStatus(tx) => {
+                if let Err(_) = tx.send(self.node_status()) {
+                    error!("send query NodeStatus result error, receiver dropped");
+                }
+            }
+            QueryGroup::GroupStatus(group_id, tx) => {
+                if let Err(_) = tx.send(self.group_status(group_id)) {
+                    error!("send query GroupStatus result error, receiver dropped");
+                }
+            }
+            QueryGroup::GroupReplica(group_id, tx) => {
+                let res = match self.parked_groups.get(&group_id) {
+                    Some(parked) => Ok(parked.replica_id),
+                    None => self.get_group(group_id).map(|group| group.replica_id),
+                };
+                if let Err(_) = tx.send(res) {
+                    error!("send query GroupReplica result error, receiver dropped");
+                }
+            }
+            QueryGroup::Topology(tx) => {
+                if let Err(_) = tx.send(self.topology()) {
+                    error!("send query Topology result error, receiver dropped");
+                }
+            }
+            QueryGroup::ConsistentCut(groups, tx) => {
+                if let Err(_) = tx.send(self.consistent_cut(groups)) {
+                    error!("send query ConsistentCut result error, receiver dropped");
+                }
+            }
+        }
+    }
+
+    /// The membership of the groups on the node including the parked groups,
+    /// the health of the replicas is filled if the group is led by the node.
+    fn topology(&self) -> NodeTopology {
+        let mut groups = Vec::with_capacity(self.groups.len() + self.parked_groups.len());
+        for (group_id, group) in self.groups.iter() {
+            let raft = &group.raft_group.raft;
+            let prs = group.is_leader().then(|| raft.prs());
+            let replicas = conf_members(&raft.prs().conf().to_conf_state())
+                .into_iter()
+                .map(|(replica_id, learner)| {
+                    let pr = prs.and_then(|prs| prs.get(replica_id));
+                    ReplicaTopology {
+                        replica_id,
+                        node_id: NO_NODE,
+                        learner,
+                        witness: false,
+                        reachable: pr.map(|pr| replica_id == group.replica_id || pr.recent_active),
+                        matched: pr.map(|pr| pr.matched),
+                    }
+                })
+                .collect();
+            groups.push(GroupTopology {
+                group_id: *group_id,
+                replica_id: group.replica_id,
+                role: raft.state,
+                leader_id: raft.leader_id,
+                leader_node_id: NO_NODE,
+                replicas,
+                parked: false,
+            });
+        }
+
+        for (group_id, parked) in self.parked_groups.iter() {
+            let state = match self.shared_states.get(*group_id) {
+                None => continue,
+                Some(state) => state,
+            };
+            let replicas = conf_members(&state.get_conf_state())
+                .into_iter()
+                .map(|(replica_id, learner)| ReplicaTopology {
+                    replica_id,
+                    node_id: NO_NODE,
+                    learner,
+                    witness: false,
+                    reachable: None,
+                    matched: None,
+                })
+                .collect();
+            groups.push(GroupTopology {
+                group_id: *group_id,
+                replica_id: parked.replica_id,
+                role: state.get_role(),
+                leader_id: state.get_leader_id(),
+                leader_node_id: NO_NODE,
+                replicas,
+                parked: true,
+            });
+        }
+
+        groups.sort_unstable_by_key(|group| group.group_id);
+        NodeTopology {
+            node_id: self.node_id,
+            groups,
+        }
+    }
+
+    /// Collect the commit watermarks of the groups, the live groups report
+    /// the commit index of the raft log and the parked groups report the
+    /// commit index of the shared state.
+    fn consistent_cut(&self, mut groups: Vec<u64>) -> ConsistentCut {
+        if groups.is_empty() {
+            groups = self
+                .groups
+                .keys()
+                .chain(self.parked_groups.keys())
+                .copied()
+                .collect();
+            groups.sort_unstable();
+        }
+
+        let mut cut = ConsistentCut {
+            node_id: self.node_id,
+            taken_at_ms: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+            ..Default::default()
+        };
+        for group_id in groups {
+            if let Some(group) = self.groups.get(&group_id) {
+                let raft_log = &group.raft_group.raft.raft_log;
+                cut.watermarks.push(CommitWatermark {
+                    group_id,
+                    replica_id: group.replica_id,
+                    index: raft_log.committed,
+                    term: raft_log.term(raft_log.committed).unwrap_or(0),
+                    leader: group.is_leader(),
+                });
+                continue;
+            }
+
+            match (
+                self.parked_groups.get(&group_id),
+                self.shared_states.get(group_id),
+            ) {
+                (Some(parked), Some(state)) => cut.watermarks.push(CommitWatermark {
+                    group_id,
+                    replica_id: parked.replica_id,
+                    index: state.get_commit_index(),
+                    term: state.get_commit_term(),
+                    leader: false,
+                }),
+                _ => cut.missing.push(group_id),
+            }
+        }
+        cut
+    }
+
+    /// Summarize the groups and peers of the node, the storage usage is left
+    /// to the caller.
+    fn node_status(&self) -> NodeStatus {
+        let mut status = NodeStatus {
+            node_id: self.node_id,
+            parked: self.parked_groups.len(),
+            uptime: self.started_at.elapsed(),
+            ..Default::default()
+        };
+
+        for group in self.groups.values() {
+            match group.raft_group.raft.state {
+                StateRole::Leader => status.leaders += 1,
+                StateRole::Follower => status.followers += 1,
+                StateRole::Candidate | StateRole::PreCandidate => status.candidates += 1,
+            }
+            status.pending_proposals += group.proposals.queue.len();
+            status.apply_backlog += group.shared_state.get_apply_backlog();
+            status.apply_backlog_bytes += group.shared_state.get_apply_backlog_bytes();
+        }
+
+        status.peers = self
+            .node_manager
+            .iter()
+            .filter(|(node_id, _)| **node_id != self.node_id)
+            .map(|(node_id, node)| PeerStatus {
+                node_id: *node_id,
+                groups: node.group_map.len(),
+                last_contact: self.peer_contacts.get(node_id).map(|at| at.elapsed()),
+                endpoints: self.transport.endpoint_health(*node_id),
+            })
+            .collect();
+        status.peers.sort_unstable_by_key(|peer| peer.node_id);
+        status
+    }
+
+    fn group_status(&self, group_id: u64) -> Result<GroupStatus, Error> {
+        if self.parked_groups.contains_key(&group_id) {
+            let state = self.shared_states.get(group_id).ok_or(Error::RaftGroup(
+                RaftGroupError::Deleted(self.node_id, group_id),
+            ))?;
+            return Ok(GroupStatus {
+                group_id,
+                replica_id: state.get_replica_id(),
+                role: state.get_role(),
+                leader_id: state.get_leader_id(),
+                leader_node_id: state.get_leader_node_id(),
+                term: state.get_term(),
+                commit_index: state.get_commit_index(),
+                applied_index: state.get_applied_index(),
+                last_index: state.get_commit_index(),
+                pending_proposals: 0,
+                pending_conf_change: false,
+                progress: vec![],
+                parked: true,
+            });
+        }
+
+        let group = self.get_group(group_id)?;
+        let raft = &group.raft_group.raft;
+        let mut progress = vec![];
+        if group.is_leader() {
+            let learners = raft.prs().conf().learners();
+            progress = raft
+                .prs()
+                .iter()
+                .map(|(replica_id, pr)| ProgressStatus {
+                    replica_id: *replica_id,
+                    matched: pr.matched,
+                    next_index: pr.next_idx,
+                    state: pr.state,
+                    recent_active: *replica_id == group.replica_id || pr.recent_active,
+                    paused: pr.is_paused(),
+                    learner: learners.contains(replica_id),
+                })
+                .collect::<Vec<_>>();
+            progress.sort_unstable_by_key(|pr| pr.replica_id);
+        }
+        Ok(GroupStatus {
+            group_id,
+            replica_id: group.replica_id,
+            role: raft.state,
+            leader_id: raft.leader_id,
+            leader_node_id: group.shared_state.get_leader_node_id(),
+            term: raft.term,
+            commit_index: raft.raft_log.committed,
+            applied_index: group.shared_state.get_applied_index(),
+            last_index: raft.raft_log.last_index(),
+            pending_proposals: group.proposals.queue.len(),
+            pending_conf_change: raft.has_pending_conf(),
+            progress,
+            parked: false,
+        })
+    }
+
+    /// List at most `limit` groups whose group id is greater than `cursor`,
+    /// including the parked groups.
+    fn list_groups(&self, cursor: u64, limit: usize) -> GroupPage {
+        let limit = cmp::max(limit, 1);
+        let mut group_ids = self
+            .groups
+            .keys()
+            .chain(self.parked_groups.keys())
+            .filter(|group_id| **group_id > cursor)
+            .copied()
+            .collect::<Vec<_>>();
+        group_ids.sort_unstable();
+
+        let next_cursor = if group_ids.len() > limit {
+            Some(group_ids[limit - 1])
+        } else {
+            None
+        };
+        group_ids.truncate(limit);
+
+        let groups = group_ids
+            .into_iter()
+            .filter_map(|group_id| match self.groups.get(&group_id) {
+                Some(group) => Some(GroupSummary {
+                    group_id,
+                    replica_id: group.replica_id,
+                    role: group.raft_group.raft.state,
+                    leader_id: group.raft_group.raft.leader_id,
+                    term: group.raft_group.raft.term,
+                    commit_index: group.raft_group.raft.raft_log.committed,
+                    applied_index: group.raft_group.raft.raft_log.applied,
+                    parked: false,
+                }),
+                None => self
+                    .shared_states
+                    .get(group_id)
+                    .map(|state| GroupSummary::from_state(group_id, &state)),
+            })
+            .collect();
+
+        GroupPage {
+            groups,
+            next_cursor,
         }
     }
 
@@ -1121,6 +3853,14 @@ where
         assert_eq!(changes.len(), view.conf_change.changes.len());
 
         let group_id = view.group_id;
+        // the replica ids of the committed changes are the high-water mark of
+        // the allocator, every replica advances it so that the ids are never
+        // reused after the leadership moves to this node.
+        if let Some(max_replica_id) = changes.iter().map(|change| change.replica_id).max() {
+            self.storage
+                .advance_replica_id(group_id, max_replica_id)
+                .await?;
+        }
         let group = match self.groups.get_mut(&group_id) {
             Some(group) => group,
             None => {
@@ -1146,6 +3886,7 @@ where
                         &mut self.replica_cache,
                         change_request.node_id,
                         change_request.replica_id,
+                        change_request.witness,
                     )
                     .await
                 }
@@ -1161,7 +3902,20 @@ where
                     )
                     .await
                 }
-                ConfChangeType::AddLearnerNode => unimplemented!(),
+                // the learner is tracked as same as voter, so that the leader
+                // can replicate to it.
+                ConfChangeType::AddLearnerNode => {
+                    Self::add_replica(
+                        self.node_id,
+                        group,
+                        &mut self.node_manager,
+                        &mut self.replica_cache,
+                        change_request.node_id,
+                        change_request.replica_id,
+                        change_request.witness,
+                    )
+                    .await
+                }
             }
         }
 
@@ -1228,6 +3982,7 @@ where
                 )));
             }
         };
+        let prev_conf_state = group.raft_group.raft.prs().conf().to_conf_state();
         let conf_state = match group.raft_group.apply_conf_change(&view.conf_change) {
             Err(err) => {
                 error!(
@@ -1238,12 +3993,14 @@ where
             }
             Ok(conf_state) => conf_state,
         };
+        invariant::check_conf_transition(self.node_id, group_id, &prev_conf_state, &conf_state);
 
         let gs = &self
             .storage
             .group_storage(group_id, group.replica_id)
             .await?;
-        gs.set_confstate(conf_state.clone())?;
+        gs.set_confstate(conf_state.clone()).await?;
+        group.shared_state.set_conf_state(conf_state.clone());
         debug!(
             "node {}: applied conf_state {:?} for group {} replica{}",
             self.node_id, conf_state, group_id, group.replica_id
@@ -1258,9 +4015,11 @@ where
         replica_cache: &mut ReplicaCache<RS, MRS>,
         change_node_id: u64,
         change_replica_id: u64,
+        witness: bool,
     ) {
         let group_id = group.group_id;
         node_manager.add_group(change_node_id, group_id);
+        group.removed_replicas.remove(&change_replica_id);
 
         // TODO: this call need transfer to user call, and if user call return errored,
         // the membership change should failed and user need to retry.
@@ -1274,6 +4033,7 @@ where
                     group_id,
                     node_id: change_node_id,
                     replica_id: change_replica_id,
+                    witness,
                 },
                 true,
             )
@@ -1299,6 +4059,7 @@ where
         let group_id = group.group_id;
         let _ = group.remove_pending_proposals();
         group.remove_track_node(changed_node_id);
+        group.removed_replicas.insert(changed_replica_id);
         // TODO: think remove if node has empty group_map.
         let _ = node_manager.remove_group(changed_node_id, group_id);
 
@@ -1309,6 +4070,7 @@ where
                     group_id,
                     node_id: changed_node_id,
                     replica_id: changed_replica_id,
+                    witness: false,
                 },
                 true,
             )
@@ -1324,6 +4086,8 @@ where
     async fn handle_readys(&mut self) {
         let mut writes = HashMap::new();
         let mut applys = HashMap::new();
+        let mut stepped_down = HashSet::new();
+        let max_unapplied = self.cfg.max_apply_unapplied_size;
         let ready_groups = self.active_groups.drain().collect::<Vec<u64>>();
         for group_id in ready_groups {
             if group_id == NO_GORUP {
@@ -1344,21 +4108,36 @@ where
                 continue;
             }
 
+            // the role observed by the last ready.
+            let was_leader = group.shared_state.is_leader();
             let res = group
                 .handle_ready(
                     self.node_id,
                     &self.transport,
+                    &self.send_failure_reporter,
                     &self.storage,
                     &mut self.replica_cache,
                     &mut self.node_manager,
                     &mut self.event_chan,
                 )
                 .await;
+            for msg in group.outgoing_snapshots.drain(..) {
+                self.snapshot_throttle.enqueue(group_id, msg);
+            }
 
             let err = match res {
                 Ok((gwr, apply)) => {
+                    if was_leader && !group.is_leader() {
+                        stepped_down.insert(group_id);
+                    }
                     writes.insert(group_id, gwr);
-                    apply.map(|apply| applys.insert(group_id, apply));
+                    if let Some(apply) =
+                        apply.and_then(|apply| group.apply_flow.admit(apply, max_unapplied))
+                    {
+                        applys.insert(group_id, apply);
+                    }
+                    self.memory_budget
+                        .set_cached(group_id, group.apply_flow.cached_size());
                     continue;
                 }
                 Err(err) => err,
@@ -1371,11 +4150,20 @@ where
                             "node {}: group {} storage temporarily unavailable",
                             self.node_id, group_id
                         );
-                        self.active_groups.insert(group_id);
+                        if !self.record_storage_failure(group_id, false) {
+                            self.active_groups.insert(group_id);
+                        }
                         continue;
                     }
-                    _ => {
-                        panic!("node {}: storage unavailable", self.node_id)
+                    storage_err => {
+                        if !self.record_storage_failure(group_id, true) {
+                            self.fail_on_storage_error(group_id, &storage_err);
+                            self.halt_group(FatalError::StorageUnavailable {
+                                node_id: self.node_id,
+                                group_id,
+                            });
+                        }
+                        continue;
                     }
                 },
                 _ => {
@@ -1390,10 +4178,138 @@ where
         }
 
         self.handle_writes(writes).await;
+
+        if !stepped_down.is_empty() {
+            self.fail_stepped_down_requests(stepped_down).await;
+        }
+    }
+
+    /// Fail the requests of the groups which lost the leadership immediately
+    /// with the hint of the new leader, instead of failing them one by one
+    /// after the requests queued ahead of them are handled. The pending reads
+    /// of the groups are never confirmed by the replica either. The queued
+    /// requests of the other groups are handled in order.
+    async fn fail_stepped_down_requests(&mut self, group_ids: HashSet<u64>) {
+        let node_id = self.node_id;
+        for group_id in group_ids.iter() {
+            if let Some(group) = self.groups.get_mut(group_id) {
+                info!(
+                    "node {}: group {} replica {} lost the leadership, fail the pending reads",
+                    node_id, group_id, group.replica_id
+                );
+                let shared_state = group.shared_state.clone();
+                group.fail_pending_reads(|| {
+                    Error::Propose(ProposeError::not_leader(node_id, *group_id, &shared_state))
+                });
+            }
+        }
+
+        let mut queued = vec![];
+        while let Ok(req) = self.propose_rx.try_recv() {
+            queued.push(req);
+        }
+        for req in queued {
+            let req = match req {
+                ProposeMessage::ReadIndexBatch(reads) => {
+                    let (failed, reads): (Vec<_>, Vec<_>) = reads
+                        .into_iter()
+                        .partition(|read| group_ids.contains(&read.group_id));
+                    for read in failed {
+                        self.fail_not_leader(ProposeMessage::ReadIndexData(read));
+                    }
+                    if reads.is_empty() {
+                        continue;
+                    }
+                    ProposeMessage::ReadIndexBatch(reads)
+                }
+                req => req,
+            };
+            let group_id = match &req {
+                ProposeMessage::Write(data) => data.group_id,
+                ProposeMessage::Membership(request) => request.group_id,
+                ProposeMessage::Admin(request) => request.group_id,
+                ProposeMessage::ReadIndexData(read_data) => read_data.group_id,
+                ProposeMessage::ReadIndexBatch(_) => NO_GORUP,
+            };
+            if group_ids.contains(&group_id) {
+                self.fail_not_leader(req);
+            } else {
+                self.handle_propose_request(req).await;
+            }
+        }
+    }
+
+    fn fail_not_leader(&mut self, req: ProposeMessage<WD, RES>) {
+        let not_leader = |group_id| match self.groups.get(&group_id) {
+            Some(group) => Error::Propose(ProposeError::not_leader(
+                self.node_id,
+                group_id,
+                &group.shared_state,
+            )),
+            None => self.missing_group_error(group_id),
+        };
+        let cb = match req {
+            ProposeMessage::Write(data) => ResponseCallbackQueue::new_error_callback(
+                data.tx,
+                not_leader(data.group_id).with_request_id(data.request_id),
+            ),
+            ProposeMessage::Membership(request) => ResponseCallbackQueue::new_error_callback(
+                request.tx,
+                not_leader(request.group_id).with_request_id(request.request_id),
+            ),
+            ProposeMessage::Admin(request) => ResponseCallbackQueue::new_error_callback(
+                request.tx,
+                not_leader(request.group_id).with_request_id(request.request_id),
+            ),
+            ProposeMessage::ReadIndexData(read_data) => ResponseCallbackQueue::new_error_callback(
+                read_data.tx,
+                not_leader(read_data.group_id)
+                    .with_request_id(Uuid::from_bytes(read_data.context.uuid)),
+            ),
+            ProposeMessage::ReadIndexBatch(_) => unreachable!(),
+        };
+        self.pending_responses.push_back(cb);
+    }
+
+    /// Persist the entries and hard states of the readys without a snapshot in
+    /// one storage write batch. Returns the results of the groups batched, the
+    /// ok result is the last index and the size of the entries appended.
+    async fn write_ready_batch(
+        &self,
+        writes: &mut HashMap<u64, RaftGroupWriteRequest>,
+    ) -> HashMap<u64, Result<Option<(u64, u64)>, super::storage::Error>> {
+        let mut group_writes = vec![];
+        let mut appended = vec![];
+        for (group_id, gwr) in writes.iter_mut() {
+            let group_write = match self.groups.get(group_id) {
+                Some(group) => group.take_group_write(gwr),
+                None => None,
+            };
+            if let Some(group_write) = group_write {
+                appended.push((*group_id, appended_entries(&group_write.entries)));
+                group_writes.push(group_write);
+            }
+        }
+        if group_writes.is_empty() {
+            return HashMap::new();
+        }
+
+        let results = self.storage.write_batch(group_writes).await;
+        appended
+            .into_iter()
+            .zip(results)
+            .map(|((group_id, appended), res)| (group_id, res.map(|_| appended)))
+            .collect()
     }
 
     async fn handle_writes(&mut self, mut writes: HashMap<u64, RaftGroupWriteRequest>) {
         let mut applys = HashMap::new();
+        let mut installed_snapshots = vec![];
+        let mut batched = if self.cfg.batch_ready_writes {
+            self.write_ready_batch(&mut writes).await
+        } else {
+            HashMap::new()
+        };
 
         // TODO(yuanchang.xu) Disk write flow control
         for (group_id, gwr) in writes.iter_mut() {
@@ -1405,17 +4321,27 @@ where
                         super::storage::Error::StorageTemporarilyUnavailable => {
                             warn!("node {}: group {} handle_write but storage temporarily unavailable ", self.node_id, group_id);
 
-                            self.active_groups.insert(*group_id);
+                            if !self.record_storage_failure(*group_id, false) {
+                                self.active_groups.insert(*group_id);
+                            }
                             continue;
                         }
                         super::storage::Error::StorageUnavailable => {
-                            panic!("node {}: storage unavailable", self.node_id)
+                            if !self.record_storage_failure(*group_id, true) {
+                                self.fail_on_storage_error(*group_id, &err);
+                                self.halt_group(FatalError::StorageUnavailable {
+                                    node_id: self.node_id,
+                                    group_id: *group_id,
+                                });
+                            }
+                            continue;
                         }
                         _ => {
                             warn!(
                                 "node {}: get raft storage for group {} to handle_writes error: {}",
                                 self.node_id, *group_id, err
                             );
+                            self.fail_on_storage_error(*group_id, &err);
                             continue;
                         }
                     }
@@ -1442,20 +4368,91 @@ where
                 }
             };
 
-            let res = group
-                .handle_write(
-                    self.node_id,
-                    gwr,
-                    &gs,
-                    &self.transport,
-                    &mut self.replica_cache,
-                    &mut self.node_manager,
-                )
-                .await;
+            let snapshot_metadata = gwr
+                .ready
+                .as_ref()
+                .filter(|ready| *ready.snapshot() != Snapshot::default())
+                .map(|ready| ready.snapshot().get_metadata().clone());
+            let replica_id = group.replica_id;
+            let res = match batched.remove(group_id) {
+                Some(Ok(appended)) => {
+                    group
+                        .handle_persisted(
+                            self.node_id,
+                            gwr,
+                            appended,
+                            &gs,
+                            &self.transport,
+                            &self.send_failure_reporter,
+                            &mut self.replica_cache,
+                            &mut self.node_manager,
+                        )
+                        .await
+                }
+                Some(Err(err)) => Err(err),
+                None => {
+                    group
+                        .handle_write(
+                            self.node_id,
+                            gwr,
+                            &gs,
+                            &self.transport,
+                            &self.send_failure_reporter,
+                            &mut self.replica_cache,
+                            &mut self.node_manager,
+                        )
+                        .await
+                }
+            };
+            for msg in group.outgoing_snapshots.drain(..) {
+                self.snapshot_throttle.enqueue(*group_id, msg);
+            }
 
             let write_err = match res {
                 Ok(apply) => {
-                    apply.map(|apply| applys.insert(*group_id, apply));
+                    if let Some(metadata) = snapshot_metadata {
+                        // the membership changes before the snapshot are never
+                        // applied here, observe the replica ids from its conf state.
+                        let cs = metadata.get_conf_state();
+                        let max_replica_id = cs
+                            .voters
+                            .iter()
+                            .chain(cs.learners.iter())
+                            .chain(cs.voters_outgoing.iter())
+                            .chain(cs.learners_next.iter())
+                            .max();
+                        if let Some(max_replica_id) = max_replica_id {
+                            if let Err(err) = self
+                                .storage
+                                .advance_replica_id(*group_id, *max_replica_id)
+                                .await
+                            {
+                                warn!(
+                                    "node {}: group {} advance replica id to {} got error: {}",
+                                    self.node_id, *group_id, max_replica_id, err
+                                );
+                            }
+                        }
+                        group
+                            .shared_state
+                            .set_conf_state(metadata.get_conf_state().clone());
+                        installed_snapshots.push(ApplyMessage::SnapshotInstalled {
+                            group_id: *group_id,
+                            replica_id,
+                            metadata,
+                        });
+                        group.apply_flow.reset();
+                    }
+                    if let Some(apply) = apply.and_then(|apply| {
+                        group
+                            .apply_flow
+                            .admit(apply, self.cfg.max_apply_unapplied_size)
+                    }) {
+                        applys.insert(*group_id, apply);
+                    }
+                    self.memory_budget
+                        .set_cached(*group_id, group.apply_flow.cached_size());
+                    self.storage_domains.record_success(*group_id);
                     continue;
                 }
 
@@ -1469,40 +4466,61 @@ where
                 super::storage::Error::LogTemporarilyUnavailable
                 | super::storage::Error::SnapshotTemporarilyUnavailable
                 | super::storage::Error::StorageTemporarilyUnavailable => {
-                    self.active_groups.insert(*group_id);
+                    if !self.record_storage_failure(*group_id, false) {
+                        self.active_groups.insert(*group_id);
+                    }
                     continue;
                 }
 
                 super::storage::Error::LogUnavailable
                 | super::storage::Error::SnapshotUnavailable => {
-                    panic!(
-                        "node {}: group {} storage unavailable",
-                        self.node_id, *group_id
-                    );
-
-                    // TODO: consider response and panic here.
+                    if !self.record_storage_failure(*group_id, true) {
+                        self.fail_on_storage_error(*group_id, &write_err);
+                        self.halt_group(FatalError::StorageUnavailable {
+                            node_id: self.node_id,
+                            group_id: *group_id,
+                        });
+                    }
+                    continue;
                 }
                 _ => {
                     warn!(
                         "node {}: group {} raft storage to handle_write got error: {}",
                         self.node_id, *group_id, write_err
                     );
+                    self.fail_on_storage_error(*group_id, &write_err);
                     continue;
                 }
             }
         }
 
+        // the snapshots are installed before the entries of the applys.
+        for msg in installed_snapshots {
+            self.send_apply_msg(msg);
+        }
         if !applys.is_empty() {
             self.send_applys(applys);
         }
     }
 
     fn send_applys(&self, applys: HashMap<u64, ApplyData<RES>>) {
+        self.send_apply_msg(ApplyMessage::Apply { applys })
+    }
+
+    /// Notify the state machine that the replica of group is created.
+    fn notify_group_created(&self, group_id: u64) {
+        if let Some(group) = self.groups.get(&group_id) {
+            self.send_apply_msg(ApplyMessage::GroupCreated {
+                group_id,
+                replica_id: group.replica_id,
+                conf_state: group.raft_group.raft.prs().conf().to_conf_state(),
+            });
+        }
+    }
+
+    fn send_apply_msg(&self, msg: ApplyMessage<RES>) {
         let span = tracing::span::Span::current();
-        if let Err(_err) = self
-            .apply_tx
-            .send((span.clone(), ApplyMessage::Apply { applys }))
-        {
+        if let Err(_err) = self.apply_tx.send((span.clone(), msg)) {
             // FIXME: this should unreachable, because the lifetime of apply actor is bound to us.
             warn!("apply actor stopped");
         }
@@ -1518,21 +4536,42 @@ where
     }
 }
 
+/// The ticks the election timeout of the group is held after the node started,
+/// the groups are spread over `ramp_ticks` deterministically by group id.
+fn election_ramp_delay(group_id: u64, ramp_ticks: usize) -> usize {
+    if ramp_ticks == 0 {
+        return 0;
+    }
+    (group_id % ramp_ticks as u64) as usize
+}
+
 #[cfg(test)]
 mod tests {
+    use std::collections::HashSet;
+    use std::collections::VecDeque;
     use std::sync::Arc;
 
+    use super::election_ramp_delay;
+    use super::ApplyFlow;
     use super::NodeWorker;
+    use crate::coalesce::WriteCoalescer;
+    use crate::compaction::LogUsage;
+    use crate::latency::LatencyRecorder;
+    use crate::lease::LeaderLease;
+    use crate::promotion::LearnerCatchUp;
     use crate::proposal::ProposalQueue;
     use crate::proposal::ReadIndexQueue;
+    use crate::quota::LogQuota;
     use crate::storage::MemStorage;
     use crate::storage::MultiRaftMemoryStorage;
 
     use crate::group::RaftGroup;
     use crate::group::Status;
 
+    use crate::prelude::Message;
     use crate::prelude::ReplicaDesc;
     use crate::replica_cache::ReplicaCache;
+    use crate::state::StaleMessage;
     use crate::transport::LocalTransport;
     use crate::Error;
     use crate::MultiRaftMessageSenderImpl;
@@ -1571,6 +4610,19 @@ mod tests {
             status: Status::None,
             shared_state: Arc::new(GroupState::default()),
             read_index_queue: ReadIndexQueue::new(),
+            idle_ticks: 0,
+            witness: false,
+            removed_replicas: HashSet::new(),
+            log_quota: LogQuota::default(),
+            learner_catch_up: LearnerCatchUp::default(),
+            write_coalescer: WriteCoalescer::new(group_id),
+            applying_reads: VecDeque::new(),
+            applying_follower_reads: VecDeque::new(),
+            latency: Arc::new(LatencyRecorder::default()),
+            log_usage: LogUsage::default(),
+            lease: LeaderLease::default(),
+            apply_flow: ApplyFlow::default(),
+            outgoing_snapshots: vec![],
 
             commit_term: 0, // TODO: init committed term from storage
             commit_index: 0,
@@ -1599,6 +4651,7 @@ mod tests {
                 &mut replica_cache,
                 node_id,
                 replica_id,
+                false,
             )
             .await;
         }
@@ -1621,6 +4674,7 @@ mod tests {
                     group_id,
                     node_id,
                     replica_id,
+                    witness: false,
                 }
             );
         }
@@ -1682,6 +4736,7 @@ mod tests {
                     &mut replica_cache,
                     node_id,
                     replica_id,
+                    false,
                 )
                 .await;
             }
@@ -1706,6 +4761,7 @@ mod tests {
                     group_id,
                     node_id,
                     replica_id,
+                    witness: false,
                 }
             );
         }
@@ -1747,4 +4803,71 @@ mod tests {
 
         assert_eq!(raft_group.node_ids, vec![1]);
     }
+
+    #[tokio::test]
+    async fn test_check_stale_message() {
+        let raft_store = MemStorage::new();
+        let mut node_manager = NodeManager::new();
+        let storage = MultiRaftMemoryStorage::new(1);
+        let mut replica_cache = ReplicaCache::new(storage);
+        let mut raft_group = new_raft_group(1, 1, 1, &raft_store).unwrap();
+        raft_group.raft_group.raft.term = 10;
+
+        let msg = |from: u64, term: u64| Message {
+            from,
+            to: 1,
+            term,
+            ..Default::default()
+        };
+        assert_eq!(raft_group.check_stale_message(&msg(2, 1), 0), None);
+        assert_eq!(raft_group.check_stale_message(&msg(2, 5), 5), None);
+        assert_eq!(
+            raft_group.check_stale_message(&msg(2, 4), 5),
+            Some(StaleMessage::Term)
+        );
+        // the forwarded proposals have no term.
+        assert_eq!(raft_group.check_stale_message(&msg(2, 0), 5), None);
+
+        TestMultiRaftActorRuntime::remove_replica(
+            1,
+            &mut raft_group,
+            &mut node_manager,
+            &mut replica_cache,
+            2,
+            2,
+        )
+        .await;
+        assert_eq!(
+            raft_group.check_stale_message(&msg(2, 10), 0),
+            Some(StaleMessage::RemovedReplica)
+        );
+
+        // the replica is added back.
+        TestMultiRaftActorRuntime::add_replica(
+            1,
+            &mut raft_group,
+            &mut node_manager,
+            &mut replica_cache,
+            2,
+            2,
+            false,
+        )
+        .await;
+        assert_eq!(raft_group.check_stale_message(&msg(2, 10), 0), None);
+    }
+
+    #[test]
+    fn test_election_ramp_delay() {
+        assert_eq!(election_ramp_delay(7, 0), 0);
+
+        let ramp_ticks = 10;
+        let mut spread = vec![0; ramp_ticks];
+        for group_id in 1..=1000 {
+            let delay = election_ramp_delay(group_id, ramp_ticks);
+            assert!(delay < ramp_ticks);
+            assert_eq!(delay, election_ramp_delay(group_id, ramp_ticks));
+            spread[delay] += 1;
+        }
+        assert!(spread.iter().all(|n| *n == 100), "{:?}", spread);
+    }
 }
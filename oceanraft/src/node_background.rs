@@ -0,0 +1,57 @@
+use tracing::trace;
+
+use crate::multiraft::ProposeResponse;
+
+use super::background::JobKind;
+use super::node::NodeWorker;
+use super::storage::MultiRaftStorage;
+use super::storage::RaftStorage;
+use super::transport::Transport;
+use super::ProposeData;
+
+impl<TR, RS, MRS, WD, RES> NodeWorker<TR, RS, MRS, WD, RES>
+where
+    TR: Transport + Clone,
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+    WD: ProposeData,
+    RES: ProposeResponse,
+{
+    /// Run the due maintenance jobs of the node in the order of priority, see
+    /// `Config::background_budget`. The jobs deferred for the budget are
+    /// still due, so they are run once the budget is refilled.
+    pub(crate) async fn run_background_jobs(&mut self) {
+        let background = self.background.clone();
+        for kind in background.priorities().iter().cloned() {
+            let due = match kind {
+                JobKind::Compaction => self.log_compaction_due(),
+                JobKind::ReplicaDescGc => self.replica_desc_gc_due(),
+                // the snapshots and the scrubbing are run by the application.
+                JobKind::Snapshot | JobKind::Scrub => false,
+            };
+            if !due {
+                continue;
+            }
+
+            let mut permit = match background.try_acquire(kind) {
+                None => {
+                    trace!(
+                        "node {}: background job {:?} deferred for the budget",
+                        self.node_id,
+                        kind
+                    );
+                    continue;
+                }
+                Some(permit) => permit,
+            };
+            match kind {
+                JobKind::Compaction => {
+                    let bytes = self.compact_logs().await;
+                    permit.record_io(bytes);
+                }
+                JobKind::ReplicaDescGc => self.run_replica_desc_gc().await,
+                JobKind::Snapshot | JobKind::Scrub => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use std::mem::take;
+
+use tracing::debug;
+use tracing::warn;
+
+use crate::multiraft::ProposeResponse;
+
+use super::compaction::LogState;
+use super::node::NodeWorker;
+use super::storage::MultiRaftStorage;
+use super::storage::RaftStorage;
+use super::transport::Transport;
+use super::ProposeData;
+
+impl<TR, RS, MRS, WD, RES> NodeWorker<TR, RS, MRS, WD, RES>
+where
+    TR: Transport + Clone,
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+    WD: ProposeData,
+    RES: ProposeResponse,
+{
+    /// The compaction is due every `Config::compaction_check_ticks` ticks, or
+    /// once a group escalated to `LogQuotaStage::Compact`.
+    pub(crate) fn log_compaction_due(&self) -> bool {
+        !self.quota_compactions.is_empty() || self.policy_compaction_due()
+    }
+
+    fn policy_compaction_due(&self) -> bool {
+        let interval = self.cfg.compaction_check_ticks;
+        interval != 0 && self.elapsed_ticks.saturating_sub(self.last_compaction_tick) >= interval
+    }
+
+    /// Compact the raft log of the groups as decided by
+    /// `Config::compaction_policy`, the groups over `Config::group_log_quota`
+    /// are compacted at the applied index. Returns the estimated bytes
+    /// compacted.
+    pub(crate) async fn compact_logs(&mut self) -> u64 {
+        let policy_due = self.policy_compaction_due();
+        if policy_due {
+            self.last_compaction_tick = self.elapsed_ticks;
+        }
+
+        let policy = self.cfg.compaction_policy.clone();
+        let quota_compactions = take(&mut self.quota_compactions);
+        let compactions = self
+            .groups
+            .values()
+            .filter(|group| policy_due || quota_compactions.contains(&group.group_id))
+            .filter_map(|group| {
+                let raft_log = &group.raft_group.raft.raft_log;
+                let log = LogState {
+                    group_id: group.group_id,
+                    replica_id: group.replica_id,
+                    first_index: raft_log.first_index(),
+                    last_index: raft_log.last_index(),
+                    applied_index: raft_log.applied,
+                    bytes: group.log_usage.bytes,
+                    since_compacted: group.log_usage.compacted_at.elapsed(),
+                };
+                let compact_index = if quota_compactions.contains(&group.group_id) {
+                    log.applied_index
+                } else {
+                    policy.compact_index(&log)?.min(log.applied_index)
+                };
+                (compact_index > log.first_index).then_some((log, compact_index))
+            })
+            .collect::<Vec<_>>();
+
+        let mut compacted_bytes = 0;
+        for (log, compact_index) in compactions {
+            let res = match self
+                .storage
+                .group_storage(log.group_id, log.replica_id)
+                .await
+            {
+                Ok(gs) => gs.compact(compact_index).await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = res {
+                warn!(
+                    "node {}: compact raft log of group {} before {} error: {}",
+                    self.node_id, log.group_id, compact_index, err
+                );
+                continue;
+            }
+
+            debug!(
+                "node {}: compact raft log of group {} before {}, {} entries",
+                self.node_id,
+                log.group_id,
+                compact_index,
+                log.entries()
+            );
+            if let Some(group) = self.groups.get_mut(&log.group_id) {
+                let bytes = group.log_usage.bytes;
+                group
+                    .log_usage
+                    .compacted(log.entries(), compact_index - log.first_index);
+                compacted_bytes += bytes - group.log_usage.bytes;
+            }
+        }
+        compacted_bytes
+    }
+}
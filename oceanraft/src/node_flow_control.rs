@@ -0,0 +1,71 @@
+use crate::multiraft::ProposeResponse;
+
+use super::error::Error;
+use super::error::ProposeError;
+use super::msg::WriteRequest;
+use super::node::NodeWorker;
+use super::node::ResponseCallback;
+use super::node::ResponseCallbackQueue;
+use super::storage::MultiRaftStorage;
+use super::storage::RaftStorage;
+use super::transport::Transport;
+use super::ProposeData;
+
+impl<TR, RS, MRS, WD, RES> NodeWorker<TR, RS, MRS, WD, RES>
+where
+    TR: Transport + Clone,
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+    WD: ProposeData,
+    RES: ProposeResponse,
+{
+    /// Hold the write until the inflight proposals of the group are committed,
+    /// the write fails if the group already holds `max_inflight_proposals`
+    /// writes.
+    pub(crate) fn hold_write(&mut self, data: WriteRequest<WD, RES>) -> Option<ResponseCallback> {
+        let limit = self.cfg.max_inflight_proposals;
+        let waiting = self.waiting_writes.entry(data.group_id).or_default();
+        if waiting.len() >= limit {
+            let err = Error::Propose(ProposeError::TooManyInflightProposals {
+                node_id: self.node_id,
+                group_id: data.group_id,
+                limit,
+            });
+            return Some(ResponseCallbackQueue::new_error_callback(
+                data.tx,
+                err.with_request_id(data.request_id),
+            ));
+        }
+        waiting.push_back(data);
+        None
+    }
+
+    /// Propose the held writes of the groups whose proposals are committed,
+    /// the writes of the removed groups fail.
+    pub(crate) fn resume_waiting_writes(&mut self) {
+        if self.waiting_writes.is_empty() {
+            return;
+        }
+
+        let group_ids = self.waiting_writes.keys().cloned().collect::<Vec<_>>();
+        for group_id in group_ids {
+            let mut waiting = self.waiting_writes.remove(&group_id).unwrap_or_default();
+            while !self
+                .groups
+                .get(&group_id)
+                .map_or(false, |group| group.is_proposals_full())
+            {
+                let data = match waiting.pop_front() {
+                    None => break,
+                    Some(data) => data,
+                };
+                if let Some(cb) = self.handle_write(data, true) {
+                    self.pending_responses.push_back(cb);
+                }
+            }
+            if !waiting.is_empty() {
+                self.waiting_writes.insert(group_id, waiting);
+            }
+        }
+    }
+}
@@ -1,52 +1,41 @@
-use super::ProposeData;
+use std::fmt;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::task::Context;
+use std::task::Poll;
+use std::time::Duration;
+use std::time::Instant;
+
+use futures::future::select;
+use futures::future::BoxFuture;
+use futures::future::Either;
+use futures::pin_mut;
+use futures::FutureExt;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::oneshot;
 
+use crate::multiraft::MultiRaftTypeSpecialization;
 use crate::multiraft::ProposeResponse;
-use crate::multiraft::NO_LEADER;
-use crate::prelude::ConfChangeType;
-use crate::prelude::GroupMetadata;
-use crate::prelude::Message;
-use crate::prelude::MessageType;
 use crate::prelude::MultiRaftMessage;
 use crate::prelude::MultiRaftMessageResponse;
-use crate::prelude::ReplicaDesc;
+use crate::transport::QueueTransport;
 
-use super::apply::ApplyActor;
+use super::apply::ApplyWorker;
 use super::config::Config;
-use super::error::ChannelError;
 use super::error::Error;
-use super::error::RaftGroupError;
-use super::event::Event;
-use super::event::EventChannel;
-use super::group::RaftGroup;
-use super::group::RaftGroupWriteRequest;
-use super::group::Status;
-use super::msg::ApplyCommitMessage;
-use super::msg::ApplyData;
-use super::msg::ApplyMessage;
-use super::msg::ApplyResultMessage;
-use super::msg::CommitMembership;
 use super::msg::ManageMessage;
 use super::msg::ProposeMessage;
 use super::msg::QueryGroup;
-use super::multiraft::NO_GORUP;
-use super::multiraft::NO_NODE;
-use super::proposal::ProposalQueue;
-use super::proposal::ReadIndexQueue;
-use super::replica_cache::ReplicaCache;
-use super::rsm::StateMachine;
-use super::state::GroupState;
-use super::state::GroupStates;
-use super::storage::MultiRaftStorage;
-use super::storage::RaftStorage;
-use super::tick::Ticker;
-use super::transport::Transport;
-
-use tokio::sync::mpsc::Receiver;
-use tokio::sync::mpsc::Sender;
-use tokio::sync::mpsc::UnboundedSender;
-use tokio::sync::oneshot;
+use super::node::NodeWorker;
+use super::ProposeData;
 
-pub(crate) struct NodeHandle<W, R>
+pub(crate) struct NodeChannels<W, R>
 where
     W: ProposeData,
     R: ProposeResponse,
@@ -61,3 +50,231 @@ where
     pub manage_tx: Sender<ManageMessage>,
     pub query_group_tx: UnboundedSender<QueryGroup>,
 }
+
+/// The work that the caller of `NodeHandle::poll` should perform.
+#[derive(Debug)]
+pub enum Work {
+    /// Send the message to the node `to_node` over the network of the caller.
+    Send(MultiRaftMessage),
+    /// The storage or state machine IO of the iteration didn't complete
+    /// inline. The caller should drive it to completion, e.g. on its own
+    /// executor or thread pool, and poll again. The node is not advanced
+    /// until the IO completes.
+    Io(IoWork),
+    /// Requests or applies were handled, the caller should poll again
+    /// immediately to make progress.
+    Poll,
+    /// Poll again no later than the instant to drive the tick of raft.
+    WakeAt(Instant),
+}
+
+/// The pending IO of an iteration of the node, see `Work::Io`.
+pub struct IoWork(BoxFuture<'static, ()>);
+
+impl Future for IoWork {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        self.0.as_mut().poll(cx)
+    }
+}
+
+impl fmt::Debug for IoWork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("IoWork").finish_non_exhaustive()
+    }
+}
+
+type StepResponse = oneshot::Sender<Result<MultiRaftMessageResponse, Error>>;
+
+/// The workers moved into the iteration, they are owned by the `IoWork` while
+/// the IO of the iteration is pending.
+struct Workers<T>
+where
+    T: MultiRaftTypeSpecialization,
+{
+    node: NodeWorker<QueueTransport, T::S, T::MS, T::D, T::R>,
+    apply: ApplyWorker<T::D, T::R, T::M, T::S, T::MS>,
+    ticks: usize,
+    restored: bool,
+}
+
+impl<T> Workers<T>
+where
+    T: MultiRaftTypeSpecialization,
+{
+    /// Run one iteration of the node and apply loops, returns the number of
+    /// handled requests and applies.
+    async fn run(&mut self, tick: bool, steps: Vec<(MultiRaftMessage, StepResponse)>) -> usize {
+        if !self.restored {
+            self.node.restore().await;
+            self.restored = true;
+        }
+
+        let mut handled = steps.len();
+        for (msg, tx) in steps {
+            self.node.handle_multiraft_request(msg, tx).await;
+        }
+        handled += self.node.poll_once(tick, &mut self.ticks).await;
+
+        // the apply of membership change waits the commit of the node. Only
+        // the receive of the commit is raced with the apply, the commit
+        // received is always handled to the end.
+        let apply = self.apply.poll_once();
+        pin_mut!(apply);
+        loop {
+            let received = {
+                let recv = self.node.commit_rx.recv();
+                pin_mut!(recv);
+                match select(apply.as_mut(), recv).await {
+                    Either::Left((n, _)) => {
+                        handled += n;
+                        None
+                    }
+                    Either::Right((msg, _)) => Some(msg),
+                }
+            };
+            match received {
+                None => break,
+                Some(Some(msg)) => {
+                    self.node.serve_apply_commit(msg).await;
+                    handled += 1;
+                }
+                Some(None) => {
+                    handled += apply.await;
+                    break;
+                }
+            }
+        }
+        // the commits sent by the last applies.
+        while let Ok(msg) = self.node.commit_rx.try_recv() {
+            self.node.serve_apply_commit(msg).await;
+            handled += 1;
+        }
+        handled
+    }
+}
+
+/// NodeHandle drives the node by the caller instead of the spawned actors.
+///
+/// `poll` and `step` never block and need no runtime. Each `poll` runs one
+/// iteration of the node and apply loops inline, the network IO is returned
+/// as `Work::Send`. If the storage or state machine doesn't complete inline,
+/// the rest of the iteration is returned as `Work::Io` for the caller to
+/// drive.
+pub struct NodeHandle<T>
+where
+    T: MultiRaftTypeSpecialization,
+{
+    workers: Option<Box<Workers<T>>>,
+    io_done: Arc<Mutex<Option<(Box<Workers<T>>, usize)>>>,
+    steps: Vec<(MultiRaftMessage, StepResponse)>,
+    transport: QueueTransport,
+    stopped: Arc<AtomicBool>,
+    tick_interval: Duration,
+    next_tick: Option<Instant>,
+}
+
+impl<T> NodeHandle<T>
+where
+    T: MultiRaftTypeSpecialization,
+{
+    pub(crate) fn new(
+        cfg: &Config,
+        worker: NodeWorker<QueueTransport, T::S, T::MS, T::D, T::R>,
+        apply: ApplyWorker<T::D, T::R, T::M, T::S, T::MS>,
+        transport: QueueTransport,
+        stopped: Arc<AtomicBool>,
+    ) -> Self {
+        Self {
+            workers: Some(Box::new(Workers {
+                node: worker,
+                apply,
+                ticks: 0,
+                restored: false,
+            })),
+            io_done: Arc::new(Mutex::new(None)),
+            steps: vec![],
+            transport,
+            stopped,
+            tick_interval: Duration::from_millis(cfg.tick_interval),
+            next_tick: None,
+        }
+    }
+
+    /// Step the message received from other nodes. The message is handled by
+    /// the next `poll`, the returned receiver gets the response to reply to
+    /// the sender after that.
+    pub fn step(
+        &mut self,
+        msg: MultiRaftMessage,
+    ) -> oneshot::Receiver<Result<MultiRaftMessageResponse, Error>> {
+        let (tx, rx) = oneshot::channel();
+        self.steps.push((msg, tx));
+        rx
+    }
+
+    /// Advance the node one iteration at `now`. The node ticks if the tick
+    /// interval elapsed since the last tick. Returns the work that the caller
+    /// should perform, the last item is always `Work::WakeAt`.
+    ///
+    /// While the `Work::Io` returned by the previous poll is pending, the node
+    /// is not advanced and only `Work::WakeAt` is returned.
+    pub fn poll(&mut self, now: Instant) -> Vec<Work> {
+        if self.stopped.load(Ordering::SeqCst) {
+            return vec![];
+        }
+
+        let next_tick = *self.next_tick.get_or_insert(now + self.tick_interval);
+        let mut handled = 0;
+        if self.workers.is_none() {
+            match self.io_done.lock().unwrap().take() {
+                Some((workers, n)) => {
+                    self.workers = Some(workers);
+                    handled += n;
+                }
+                None => return vec![Work::WakeAt(next_tick)],
+            }
+        }
+
+        let tick = now >= next_tick;
+        if tick {
+            self.next_tick = Some(now + self.tick_interval);
+        }
+
+        let mut workers = self.workers.take().unwrap();
+        let steps = mem::take(&mut self.steps);
+        let mut iteration: BoxFuture<'static, _> = Box::pin(async move {
+            let n = workers.run(tick, steps).await;
+            (workers, n)
+        });
+        let io = match (&mut iteration).now_or_never() {
+            Some((workers, n)) => {
+                self.workers = Some(workers);
+                handled += n;
+                None
+            }
+            None => {
+                let io_done = self.io_done.clone();
+                Some(IoWork(Box::pin(async move {
+                    let done = iteration.await;
+                    *io_done.lock().unwrap() = Some(done);
+                })))
+            }
+        };
+
+        let mut works = self
+            .transport
+            .drain()
+            .into_iter()
+            .map(Work::Send)
+            .collect::<Vec<_>>();
+        if let Some(io) = io {
+            works.push(Work::Io(io));
+        } else if handled != 0 {
+            works.push(Work::Poll);
+        }
+        works.push(Work::WakeAt(self.next_tick.unwrap()));
+        works
+    }
+}
@@ -94,13 +94,15 @@ where
                 to_node: *to_node,
                 replicas: vec![],
                 msg: Some(raft_msg),
+                ..Default::default()
             }) {
                 tracing::error!(
                     "node {}: send heartbeat to {} error: {}",
                     self.node_id,
                     *to_node,
                     err
-                )
+                );
+                self.send_failure_reporter.report_node(*to_node);
             }
         }
     }
@@ -117,6 +119,8 @@ where
         if let Some(from_node) = self.node_manager.get_node(&from_node_id) {
             for (group_id, _) in from_node.group_map.iter() {
                 let group = match self.groups.get_mut(group_id) {
+                    // the parked group is not waked up by heartbeats.
+                    None if self.parked_groups.contains_key(group_id) => continue,
                     None => {
                         warn!("node {}: from node {} failed to fanout to group {} because does not exists", self.node_id, from_node_id, *group_id);
                         continue;
@@ -158,6 +162,12 @@ where
                     },
                 };
 
+                group.shared_state.record_contact(
+                    from_replica.replica_id,
+                    from_node_id,
+                    group.raft_group.raft.term,
+                );
+
                 // FIXME: t30_membership single_step
                 let to_replica = match self
                     .replica_cache
@@ -239,11 +249,12 @@ where
                 to_node: from_node_id,
                 replicas: vec![],
                 msg: Some(raft_msg),
+                ..Default::default()
             }
         };
 
         let _ = self.transport.send(response_msg)?;
-        Ok(MultiRaftMessageResponse {})
+        Ok(MultiRaftMessageResponse::default())
     }
 
     /// Fanout heartbeats response from other nodes to all raft groups on this node.
@@ -254,6 +265,8 @@ where
         if let Some(node) = self.node_manager.get_node(&msg.from_node) {
             for (group_id, _) in node.group_map.iter() {
                 let group = match self.groups.get_mut(group_id) {
+                    // the parked group is not waked up by heartbeats.
+                    None if self.parked_groups.contains_key(group_id) => continue,
                     None => {
                         warn!("node {}: from node {} failed to fanout response to group {} because does not exists", self.node_id, msg.from_node, *group_id);
                         continue;
@@ -325,6 +338,6 @@ where
             );
             self.node_manager.add_node(msg.from_node);
         }
-        Ok(MultiRaftMessageResponse {})
+        Ok(MultiRaftMessageResponse::default())
     }
 }
@@ -0,0 +1,69 @@
+use tracing::debug;
+
+use crate::multiraft::ProposeResponse;
+
+use super::node::NodeWorker;
+use super::storage::MultiRaftStorage;
+use super::storage::RaftStorage;
+use super::transport::Transport;
+use super::ProposeData;
+
+impl<TR, RS, MRS, WD, RES> NodeWorker<TR, RS, MRS, WD, RES>
+where
+    TR: Transport + Clone,
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+    WD: ProposeData,
+    RES: ProposeResponse,
+{
+    /// Adapt the inflight limits of the peer nodes every
+    /// `Config::adaptive_inflight_interval_ticks` ticks by the delivery
+    /// reports of the interval.
+    pub(crate) async fn tick_adaptive_inflight(&mut self) {
+        let interval = self.cfg.adaptive_inflight_interval_ticks;
+        if interval == 0
+            || self
+                .elapsed_ticks
+                .saturating_sub(self.last_inflight_adjust_tick)
+                < interval
+        {
+            return;
+        }
+        self.last_inflight_adjust_tick = self.elapsed_ticks;
+        self.inflight_tuner.adjust(self.delivery_stats.sample());
+
+        // the limits are set to the leaders every interval since raft resets
+        // the progress of the replicas when a new leader is elected.
+        let caps = self.inflight_tuner.caps().collect::<Vec<_>>();
+        for (node_id, cap) in caps {
+            let group_ids = match self.node_manager.get_node(&node_id) {
+                None => continue,
+                Some(node) => node.group_map.keys().cloned().collect::<Vec<_>>(),
+            };
+            for group_id in group_ids {
+                if !self
+                    .groups
+                    .get(&group_id)
+                    .map_or(false, |group| group.is_leader())
+                {
+                    continue;
+                }
+                let replica_id = match self.replica_cache.replica_for_node(group_id, node_id).await
+                {
+                    Ok(Some(replica_desc)) => replica_desc.replica_id,
+                    _ => continue,
+                };
+                if let Some(group) = self.groups.get_mut(&group_id) {
+                    group
+                        .raft_group
+                        .raft
+                        .adjust_max_inflight_msgs(replica_id, cap);
+                }
+            }
+            debug!(
+                "node {}: adapt inflight appends to node {} to {}",
+                self.node_id, node_id, cap
+            );
+        }
+    }
+}
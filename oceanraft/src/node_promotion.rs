@@ -0,0 +1,201 @@
+use tokio::sync::oneshot;
+use tokio::sync::oneshot::error::TryRecvError;
+use tracing::info;
+use tracing::warn;
+
+use crate::multiraft::ProposeResponse;
+use crate::prelude::ConfChangeType;
+use crate::prelude::MembershipChangeData;
+use crate::prelude::SingleMembershipChange;
+
+use super::error::ChannelError;
+use super::error::Error;
+use super::event::Event;
+use super::msg::MembershipRequest;
+use super::node::NodeWorker;
+use super::storage::MultiRaftStorage;
+use super::storage::RaftStorage;
+use super::transport::Transport;
+use super::ProposeData;
+
+/// The learner promotion proposed by the leader, see `Config::learner_promoter`.
+pub(crate) struct PromotingLearner<RES: ProposeResponse> {
+    pub(crate) replica_id: u64,
+    pub(crate) node_id: u64,
+    pub(crate) rx: oneshot::Receiver<Result<(RES, Option<Vec<u8>>), Error>>,
+}
+
+impl<TR, RS, MRS, WD, RES> NodeWorker<TR, RS, MRS, WD, RES>
+where
+    TR: Transport + Clone,
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+    WD: ProposeData,
+    RES: ProposeResponse,
+{
+    /// Track the learners catching up with the leaders on the node, the
+    /// learners which have caught up for the ticks of the policy of their
+    /// groups are promoted by `promote_learners`.
+    pub(crate) fn tick_learner_promotions(&mut self) {
+        let promoter = &self.cfg.learner_promoter;
+        for (group_id, group) in self.groups.iter_mut() {
+            let policy = match promoter.policy(*group_id) {
+                Some(policy) if group.is_leader() => policy,
+                _ => {
+                    group.learner_catch_up.clear();
+                    continue;
+                }
+            };
+
+            let raft = &group.raft_group.raft;
+            let last_index = raft.raft_log.last_index();
+            let prs = raft.prs();
+            let lags = prs
+                .conf()
+                .to_conf_state()
+                .learners
+                .into_iter()
+                .filter_map(|id| {
+                    prs.get(id)
+                        .map(|pr| (id, last_index.saturating_sub(pr.matched)))
+                })
+                .collect::<Vec<_>>();
+            // one promotion of the group is in flight at a time.
+            let ready = group.learner_catch_up.tick(lags, &policy);
+            if let Some(replica_id) = ready.first() {
+                if !self.learner_promotions.contains_key(group_id) {
+                    self.ready_learners.push((*group_id, *replica_id));
+                }
+            }
+        }
+    }
+
+    /// Propose the promotions of the learners which have caught up, and emit
+    /// `Event::LearnerPromoted` after the promotions are applied.
+    pub(crate) async fn promote_learners(&mut self) {
+        let node_id = self.node_id;
+        let event_chan = &mut self.event_chan;
+        let groups = &mut self.groups;
+        self.learner_promotions.retain(|group_id, promoting| {
+            let res = match promoting.rx.try_recv() {
+                Err(TryRecvError::Empty) => return true,
+                Err(TryRecvError::Closed) => Err(Error::Channel(ChannelError::SenderClosed(
+                    "channel of membership response is closed".to_owned(),
+                ))),
+                Ok(res) => res,
+            };
+
+            let group = match groups.get_mut(group_id) {
+                None => return false,
+                Some(group) => group,
+            };
+            match res {
+                Ok(_) => {
+                    info!(
+                        "node {}: learner {} on node {} of group {} is promoted",
+                        node_id, promoting.replica_id, promoting.node_id, group_id
+                    );
+                    event_chan.push(Event::LearnerPromoted {
+                        group_id: *group_id,
+                        replica_id: group.replica_id,
+                        learner_id: promoting.replica_id,
+                        node_id: promoting.node_id,
+                    });
+                }
+                Err(err) => {
+                    warn!(
+                        "node {}: promote learner {} of group {} error: {}",
+                        node_id, promoting.replica_id, group_id, err
+                    );
+                    group.learner_catch_up.reset(promoting.replica_id);
+                }
+            }
+            false
+        });
+
+        for (group_id, replica_id) in std::mem::take(&mut self.ready_learners) {
+            if let Err(err) = self.promote_learner(group_id, replica_id).await {
+                warn!(
+                    "node {}: promote learner {} of group {} error: {}",
+                    self.node_id, replica_id, group_id, err
+                );
+                if let Some(group) = self.groups.get_mut(&group_id) {
+                    group.learner_catch_up.reset(replica_id);
+                }
+            }
+        }
+    }
+
+    async fn promote_learner(&mut self, group_id: u64, replica_id: u64) -> Result<(), Error> {
+        let replica_desc = self
+            .replica_cache
+            .replica_desc(group_id, replica_id)
+            .await?
+            .ok_or_else(|| {
+                Error::BadParameter(format!(
+                    "replica desc of learner {} of group {} is not found",
+                    replica_id, group_id
+                ))
+            })?;
+
+        let group = match self.groups.get_mut(&group_id) {
+            Some(group) if group.is_leader() => group,
+            _ => return Ok(()),
+        };
+        // retry on the next tick after the pending membership change applied.
+        if group.raft_group.raft.has_pending_conf() {
+            return Ok(());
+        }
+
+        let node_id = replica_desc.node_id;
+        if let Err(reason) = self
+            .cfg
+            .learner_promoter
+            .approve(group_id, replica_id, node_id)
+        {
+            info!(
+                "node {}: promotion of learner {} of group {} is vetoed: {}",
+                self.node_id, replica_id, group_id, reason
+            );
+            group.learner_catch_up.reset(replica_id);
+            return Ok(());
+        }
+
+        let data = MembershipChangeData {
+            changes: vec![SingleMembershipChange {
+                node_id,
+                replica_id,
+                change_type: ConfChangeType::AddNode as i32,
+                witness: replica_desc.witness,
+            }],
+            replicas: vec![replica_desc],
+            ..Default::default()
+        };
+        let (tx, rx) = oneshot::channel();
+        let request = MembershipRequest {
+            request_id: self.cfg.id_generator.next_uuid(),
+            group_id,
+            term: Some(group.term()),
+            context: None,
+            data,
+            tx,
+        };
+        info!(
+            "node {}: propose to promote learner {} on node {} of group {}",
+            self.node_id, replica_id, node_id, group_id
+        );
+        if let Some(cb) = group.propose_membership_change(request) {
+            self.pending_responses.push_back(cb);
+        }
+        self.active_groups.insert(group_id);
+        self.learner_promotions.insert(
+            group_id,
+            PromotingLearner {
+                replica_id,
+                node_id,
+                rx,
+            },
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use tracing::info;
+use tracing::warn;
+
+use crate::multiraft::ProposeResponse;
+
+use super::error::Error;
+use super::node::NodeWorker;
+use super::state::ReplicaDescGcReport;
+use super::storage::MultiRaftStorage;
+use super::storage::RaftStorage;
+use super::transport::Transport;
+use super::ProposeData;
+
+impl<TR, RS, MRS, WD, RES> NodeWorker<TR, RS, MRS, WD, RES>
+where
+    TR: Transport + Clone,
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+    WD: ProposeData,
+    RES: ProposeResponse,
+{
+    /// The collection is due every `Config::replica_desc_gc_interval_ticks`
+    /// ticks.
+    pub(crate) fn replica_desc_gc_due(&self) -> bool {
+        let interval = self.cfg.replica_desc_gc_interval_ticks;
+        interval != 0
+            && self
+                .elapsed_ticks
+                .saturating_sub(self.last_replica_desc_gc_tick)
+                >= interval
+    }
+
+    /// Collect the replica descriptors in the background.
+    pub(crate) async fn run_replica_desc_gc(&mut self) {
+        self.last_replica_desc_gc_tick = self.elapsed_ticks;
+
+        match self
+            .gc_replica_descs(self.cfg.replica_desc_gc_dry_run)
+            .await
+        {
+            Ok(report) if !report.removed.is_empty() => info!(
+                "node {}: replica desc gc collected {} replicas, dry_run = {}: {:?}",
+                self.node_id,
+                report.removed.len(),
+                report.dry_run,
+                report.removed
+            ),
+            Ok(_) => {}
+            Err(err) => warn!("node {}: replica desc gc error: {}", self.node_id, err),
+        }
+    }
+
+    /// Remove the descriptors of the replicas which are absent from the current
+    /// membership of their groups over `Config::replica_desc_gc_grace_ticks`,
+    /// the absence is observed by the collections, so the first collection
+    /// that observes the replica starts the window. The descriptors are only
+    /// reported if `dry_run`. The parked groups are skipped.
+    pub(crate) async fn gc_replica_descs(
+        &mut self,
+        dry_run: bool,
+    ) -> Result<ReplicaDescGcReport, Error> {
+        let now = self.elapsed_ticks;
+        let grace_ticks = self.cfg.replica_desc_gc_grace_ticks;
+        let mut absent = HashMap::new();
+        let mut report = ReplicaDescGcReport {
+            removed: vec![],
+            dry_run,
+        };
+
+        let group_ids = self.groups.keys().cloned().collect::<Vec<_>>();
+        for group_id in group_ids {
+            let (replica_id, members) = match self.groups.get(&group_id) {
+                None => continue,
+                Some(group) => {
+                    let conf_state = group.raft_group.raft.prs().conf().to_conf_state();
+                    let members = conf_state
+                        .voters
+                        .iter()
+                        .chain(conf_state.learners.iter())
+                        .chain(conf_state.voters_outgoing.iter())
+                        .chain(conf_state.learners_next.iter())
+                        .cloned()
+                        .collect::<HashSet<_>>();
+                    (group.replica_id, members)
+                }
+            };
+
+            let descs = self.storage.scan_group_replica_desc(group_id).await?;
+            let member_nodes = descs
+                .iter()
+                .filter(|desc| members.contains(&desc.replica_id))
+                .map(|desc| desc.node_id)
+                .collect::<HashSet<_>>();
+            for desc in descs {
+                if desc.replica_id == replica_id || members.contains(&desc.replica_id) {
+                    continue;
+                }
+
+                let key = (group_id, desc.replica_id);
+                let since = self
+                    .replica_desc_gc_absent
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or(now);
+                if now.saturating_sub(since) < grace_ticks {
+                    absent.insert(key, since);
+                    continue;
+                }
+
+                if dry_run {
+                    absent.insert(key, since);
+                    report.removed.push(desc);
+                    continue;
+                }
+
+                self.storage
+                    .remove_replica_desc(group_id, desc.replica_id)
+                    .await?;
+                self.replica_cache
+                    .remove_replica_desc(group_id, desc.clone(), false)
+                    .await?;
+                // the node is still tracked if other members are on the node.
+                if !member_nodes.contains(&desc.node_id) {
+                    self.node_manager.remove_group(desc.node_id, group_id);
+                    if let Some(group) = self.groups.get_mut(&group_id) {
+                        group.remove_track_node(desc.node_id);
+                    }
+                }
+                report.removed.push(desc);
+            }
+        }
+
+        self.replica_desc_gc_absent = absent;
+        Ok(report)
+    }
+}
@@ -0,0 +1,142 @@
+use raft::ProgressState;
+use raft::SnapshotStatus;
+use tracing::debug;
+use tracing::warn;
+
+use crate::event::Event;
+use crate::multiraft::ProposeResponse;
+
+use super::node::NodeWorker;
+use super::storage::MultiRaftStorage;
+use super::storage::RaftStorage;
+use super::transport;
+use super::transport::Transport;
+use super::ProposeData;
+
+impl<TR, RS, MRS, WD, RES> NodeWorker<TR, RS, MRS, WD, RES>
+where
+    TR: Transport + Clone,
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+    WD: ProposeData,
+    RES: ProposeResponse,
+{
+    /// Returns true if the leader of the group is sending the snapshot to the
+    /// replica, the progress of the replica leaves the snapshot state once it
+    /// installed or failed the snapshot.
+    fn is_sending_snapshot(&self, group_id: u64, replica_id: u64, index: Option<u64>) -> bool {
+        let group = match self.groups.get(&group_id) {
+            Some(group) if group.is_leader() => group,
+            _ => return false,
+        };
+        group
+            .raft_group
+            .raft
+            .prs()
+            .get(replica_id)
+            .map_or(false, |pr| {
+                pr.state == ProgressState::Snapshot
+                    && index.map_or(true, |index| pr.pending_snapshot == index)
+            })
+    }
+
+    /// Report the snapshot to the replica failed, the leader probes the
+    /// replica again and the slot of the snapshot is released.
+    pub(crate) fn report_snapshot_failure(&mut self, group_id: u64, replica_id: u64) {
+        self.snapshot_throttle.finish(group_id, replica_id);
+        if !self.is_sending_snapshot(group_id, replica_id, None) {
+            return;
+        }
+
+        debug!(
+            "node {}: report snapshot of group {} to replica {} failed",
+            self.node_id, group_id, replica_id
+        );
+        if let Some(group) = self.groups.get_mut(&group_id) {
+            group
+                .raft_group
+                .report_snapshot(replica_id, SnapshotStatus::Failure);
+        }
+    }
+
+    /// Release the snapshots in flight over `Config::snapshot_send_timeout_ticks`
+    /// and report them failed.
+    pub(crate) fn expire_snapshots(&mut self) {
+        for (group_id, replica_id) in self.snapshot_throttle.expire() {
+            warn!(
+                "node {}: snapshot of group {} to replica {} timeout",
+                self.node_id, group_id, replica_id
+            );
+            self.report_snapshot_failure(group_id, replica_id);
+        }
+    }
+
+    /// Send the snapshots built by the leaders within the limits, see
+    /// `Config::max_concurrent_snapshots` and
+    /// `Config::snapshot_max_bytes_per_sec`. The snapshots left are reported
+    /// queued once.
+    pub(crate) async fn send_snapshots(&mut self) {
+        if self.snapshot_throttle.queued_len() == 0 {
+            return;
+        }
+
+        let sending = |group_id, replica_id| self.is_sending_snapshot(group_id, replica_id, None);
+        let finished = self
+            .snapshot_throttle
+            .sending()
+            .filter(|(group_id, replica_id)| !sending(*group_id, *replica_id))
+            .collect::<Vec<_>>();
+        for (group_id, replica_id) in finished {
+            self.snapshot_throttle.finish(group_id, replica_id);
+        }
+
+        while let Some(queued) = self.snapshot_throttle.pop() {
+            let index = queued
+                .msg
+                .snapshot
+                .as_ref()
+                .map_or(0, |snapshot| snapshot.get_metadata().index);
+            // the leader stepped down or the replica is probed again.
+            if !self.is_sending_snapshot(queued.group_id, queued.msg.to, Some(index)) {
+                debug!(
+                    "node {}: drop stale snapshot {} of group {} to replica {}",
+                    self.node_id, index, queued.group_id, queued.msg.to
+                );
+                self.snapshot_throttle
+                    .finish(queued.group_id, queued.msg.to);
+                continue;
+            }
+
+            transport::send_messages(
+                self.node_id,
+                &self.transport,
+                &self.send_failure_reporter,
+                &mut self.replica_cache,
+                &mut self.node_manager,
+                queued.group_id,
+                vec![queued.msg],
+            )
+            .await;
+        }
+
+        let queued_len = self.snapshot_throttle.queued_len();
+        for queued in self.snapshot_throttle.unreported() {
+            queued.reported = true;
+            let replica_id = self
+                .groups
+                .get(&queued.group_id)
+                .map_or(0, |group| group.replica_id);
+            self.event_chan.push(Event::SnapshotQueued {
+                group_id: queued.group_id,
+                replica_id,
+                to_replica_id: queued.msg.to,
+                index: queued
+                    .msg
+                    .snapshot
+                    .as_ref()
+                    .map_or(0, |snapshot| snapshot.get_metadata().index),
+                queued: queued_len,
+            });
+        }
+    }
+}
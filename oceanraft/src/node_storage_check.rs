@@ -0,0 +1,158 @@
+use std::collections::HashSet;
+
+use tracing::info;
+
+use crate::multiraft::ProposeResponse;
+use crate::prelude::GroupMetadata;
+use crate::prelude::ReplicaDesc;
+use crate::topology::conf_members;
+
+use super::error::Error;
+use super::node::NodeWorker;
+use super::state::ConfStateMismatch;
+use super::state::StorageCheckReport;
+use super::state::StorageRepair;
+use super::storage::MultiRaftStorage;
+use super::storage::RaftStorage;
+use super::transport::Transport;
+use super::ProposeData;
+
+impl<TR, RS, MRS, WD, RES> NodeWorker<TR, RS, MRS, WD, RES>
+where
+    TR: Transport + Clone,
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+    WD: ProposeData,
+    RES: ProposeResponse,
+{
+    /// Cross-check the metadata of the groups on the node with the group
+    /// storages and the replica descriptors, it's run before the groups are
+    /// restored. The check only reads the storage.
+    pub(crate) async fn check_storage(&self) -> Result<StorageCheckReport, Error> {
+        let metas = self.storage.scan_group_metadata().await?;
+        let described = metas
+            .iter()
+            .map(|meta| (meta.group_id, meta.replica_id))
+            .collect::<HashSet<_>>();
+        let storages = self.storage.scan_group_storages().await?;
+        let stored = storages.iter().cloned().collect::<HashSet<_>>();
+
+        let mut report = StorageCheckReport::default();
+        for (group_id, replica_id) in storages {
+            if !described.contains(&(group_id, replica_id)) {
+                report.orphans.push((group_id, replica_id));
+            }
+        }
+
+        for meta in metas.iter() {
+            if meta.deleted || meta.node_id != self.node_id {
+                continue;
+            }
+            if !stored.contains(&(meta.group_id, meta.replica_id)) {
+                report.missing.push((meta.group_id, meta.replica_id));
+                continue;
+            }
+
+            let gs = self
+                .storage
+                .group_storage(meta.group_id, meta.replica_id)
+                .await?;
+            let rs = gs.initial_state().map_err(|err| Error::Raft(err))?;
+            let replica_desc = self
+                .storage
+                .get_replica_desc(meta.group_id, meta.replica_id)
+                .await?;
+            let members = conf_members(&rs.conf_state)
+                .into_iter()
+                .map(|(replica_id, _)| replica_id)
+                .collect::<Vec<_>>();
+            // the uninitialized group learns the membership from the leader.
+            let absent = rs.initialized() && !members.contains(&meta.replica_id);
+            let misplaced = replica_desc
+                .as_ref()
+                .map_or(false, |desc| desc.node_id != self.node_id);
+            if absent || misplaced {
+                report.mismatched.push(ConfStateMismatch {
+                    group_id: meta.group_id,
+                    replica_id: meta.replica_id,
+                    members,
+                    replica_desc,
+                });
+            }
+        }
+
+        report.orphans.sort_unstable();
+        report.missing.sort_unstable();
+        report.mismatched.sort_by_key(|mismatch| mismatch.group_id);
+        Ok(report)
+    }
+
+    pub(crate) async fn repair_storage(&mut self, repair: StorageRepair) -> Result<(), Error> {
+        let (group_id, replica_id) = match repair {
+            StorageRepair::ForgetGroup {
+                group_id,
+                replica_id,
+            }
+            | StorageRepair::AdoptGroup {
+                group_id,
+                replica_id,
+            }
+            | StorageRepair::DescribeReplica {
+                group_id,
+                replica_id,
+            } => (group_id, replica_id),
+        };
+        if self.groups.contains_key(&group_id) || self.parked_groups.contains_key(&group_id) {
+            return Err(Error::BadParameter(format!(
+                "group {} is started on node {}, it can't be repaired",
+                group_id, self.node_id
+            )));
+        }
+        // the orphan whose replica is unknown by the storage can be forgotten.
+        let forget = matches!(repair, StorageRepair::ForgetGroup { .. });
+        if group_id == 0 || (replica_id == 0 && !forget) {
+            return Err(Error::BadParameter(format!(
+                "invalid replica {} of group {} to repair",
+                replica_id, group_id
+            )));
+        }
+
+        info!(
+            "node {}: repair storage of group {} replica {}: {:?}",
+            self.node_id, group_id, replica_id, repair
+        );
+        match repair {
+            StorageRepair::ForgetGroup { .. } | StorageRepair::AdoptGroup { .. } => {
+                let mut meta = self
+                    .storage
+                    .get_group_metadata(group_id, replica_id)
+                    .await?
+                    .unwrap_or(GroupMetadata {
+                        group_id,
+                        replica_id,
+                        node_id: self.node_id,
+                        ..Default::default()
+                    });
+                meta.deleted = forget;
+                self.storage.set_group_metadata(meta).await?;
+            }
+            StorageRepair::DescribeReplica { .. } => {
+                self.storage
+                    .remove_replica_desc(group_id, replica_id)
+                    .await?;
+                self.storage
+                    .set_replica_desc(
+                        group_id,
+                        ReplicaDesc {
+                            node_id: self.node_id,
+                            group_id,
+                            replica_id,
+                            witness: false,
+                        },
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,86 @@
+use tracing::debug;
+
+use crate::multiraft::ProposeResponse;
+
+use super::node::NodeWorker;
+use super::storage::MultiRaftStorage;
+use super::storage::RaftStorage;
+use super::transport::SendFailure;
+use super::transport::Transport;
+use super::ProposeData;
+
+impl<TR, RS, MRS, WD, RES> NodeWorker<TR, RS, MRS, WD, RES>
+where
+    TR: Transport + Clone,
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+    WD: ProposeData,
+    RES: ProposeResponse,
+{
+    /// Report the target replicas of the message failed to deliver unreachable
+    /// to the leaders on the node.
+    pub(crate) async fn handle_send_failure(&mut self, failure: SendFailure) {
+        match failure {
+            SendFailure::Replica {
+                group_id,
+                replica_id,
+            } => self.report_unreachable(group_id, replica_id),
+            SendFailure::Snapshot {
+                group_id,
+                replica_id,
+            } => self.report_snapshot_failure(group_id, replica_id),
+            SendFailure::Node(node_id) => {
+                let group_ids = match self.node_manager.get_node(&node_id) {
+                    None => return,
+                    Some(node) => node.group_map.keys().cloned().collect::<Vec<_>>(),
+                };
+                for group_id in group_ids {
+                    if let Ok(Some(replica_desc)) =
+                        self.replica_cache.replica_for_node(group_id, node_id).await
+                    {
+                        self.report_unreachable(group_id, replica_desc.replica_id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The replica is reported at most once in
+    /// `Config::unreachable_debounce_ticks`, only the leader tracks the
+    /// progress of replicas so the reports to others are ignored. raft ignores
+    /// the unreachable replica in the snapshot state, whose snapshot failures
+    /// are reported by `report_snapshot_failure` instead.
+    fn report_unreachable(&mut self, group_id: u64, replica_id: u64) {
+        let group = match self.groups.get_mut(&group_id) {
+            Some(group) if group.is_leader() && group.replica_id != replica_id => group,
+            _ => return,
+        };
+
+        let now = self.elapsed_ticks;
+        let key = (group_id, replica_id);
+        if let Some(reported) = self.unreachable_reports.get(&key) {
+            if now.saturating_sub(*reported) < self.cfg.unreachable_debounce_ticks {
+                return;
+            }
+        }
+        self.unreachable_reports.insert(key, now);
+
+        debug!(
+            "node {}: report replica {} of group {} unreachable",
+            self.node_id, replica_id, group_id
+        );
+        group.raft_group.report_unreachable(replica_id);
+    }
+
+    /// Forget the reports out of the debounce window.
+    pub(crate) fn tick_unreachable_reports(&mut self) {
+        if self.unreachable_reports.is_empty() {
+            return;
+        }
+
+        let now = self.elapsed_ticks;
+        let debounce_ticks = self.cfg.unreachable_debounce_ticks;
+        self.unreachable_reports
+            .retain(|_, reported| now.saturating_sub(*reported) < debounce_ticks);
+    }
+}
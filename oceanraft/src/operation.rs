@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The max number of finished operations kept for `MultiRaft::operation_progress`.
+const MAX_FINISHED_OPERATIONS: usize = 1024;
+
+/// The long-running management operations tracked by the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+    /// See `MultiRaft::relocate_replica`.
+    RelocateReplica,
+    /// See `MultiRaft::fork_group`.
+    ForkGroup,
+    /// See `MultiRaft::balance`.
+    Balance,
+    /// See `MultiRaft::upload_snapshot`.
+    SnapshotUpload,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum OperationState {
+    Running,
+    Succeeded,
+    /// The operation failed with the error.
+    Failed(String),
+}
+
+/// The progress of the operation, it's sent by `Event::OperationProgress`
+/// whenever it changes and queried by `MultiRaft::operation_progress`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperationProgress {
+    /// The id of the operation, it's unique on the node across restarts as
+    /// it's generated by `Config::id_generator`.
+    pub op_id: u64,
+    pub kind: OperationKind,
+    /// The group of the operation, `0` if the operation is across groups.
+    pub group_id: u64,
+    pub state: OperationState,
+    /// The step in progress, e.g. `catching up`.
+    pub step: String,
+    /// The work done of the step out of `total`, e.g. the matched index of
+    /// the learner out of the committed index of the leader. Both are `0` if
+    /// the step is not measured.
+    pub done: u64,
+    pub total: u64,
+    /// The time since the operation started.
+    pub elapsed: Duration,
+}
+
+impl OperationProgress {
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.state != OperationState::Running
+    }
+}
+
+struct TrackedOperation {
+    progress: OperationProgress,
+    started_at: Instant,
+    /// The order of start.
+    seq: u64,
+}
+
+impl TrackedOperation {
+    fn snapshot(&self) -> OperationProgress {
+        let mut progress = self.progress.clone();
+        if !progress.is_finished() {
+            progress.elapsed = self.started_at.elapsed();
+        }
+        progress
+    }
+}
+
+#[derive(Default)]
+struct OperationsInner {
+    operations: HashMap<u64, TrackedOperation>,
+    /// The finished operations in the order of finish, the oldest is evicted
+    /// beyond `MAX_FINISHED_OPERATIONS`.
+    finished: VecDeque<u64>,
+    next_seq: u64,
+}
+
+/// The operations of `MultiRaft` on the node, the running operations and the
+/// recently finished operations are kept, so the control planes resume the
+/// monitoring after their restarts.
+#[derive(Default)]
+pub(crate) struct Operations {
+    inner: Mutex<OperationsInner>,
+}
+
+impl Operations {
+    pub(crate) fn start(
+        &self,
+        op_id: u64,
+        kind: OperationKind,
+        group_id: u64,
+    ) -> OperationProgress {
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_seq += 1;
+        let operation = TrackedOperation {
+            progress: OperationProgress {
+                op_id,
+                kind,
+                group_id,
+                state: OperationState::Running,
+                step: "started".to_owned(),
+                done: 0,
+                total: 0,
+                elapsed: Duration::ZERO,
+            },
+            started_at: Instant::now(),
+            seq: inner.next_seq,
+        };
+        let progress = operation.snapshot();
+        inner.operations.insert(op_id, operation);
+        progress
+    }
+
+    /// Update the step of the running operation, returns `None` if the
+    /// operation is not running.
+    pub(crate) fn update(
+        &self,
+        op_id: u64,
+        step: &str,
+        done: u64,
+        total: u64,
+    ) -> Option<OperationProgress> {
+        let mut inner = self.inner.lock().unwrap();
+        let operation = inner.operations.get_mut(&op_id)?;
+        if operation.progress.is_finished() {
+            return None;
+        }
+        operation.progress.step = step.to_owned();
+        operation.progress.done = done;
+        operation.progress.total = total;
+        Some(operation.snapshot())
+    }
+
+    pub(crate) fn finish(&self, op_id: u64, state: OperationState) -> Option<OperationProgress> {
+        let mut inner = self.inner.lock().unwrap();
+        let operation = inner.operations.get_mut(&op_id)?;
+        if operation.progress.is_finished() {
+            return None;
+        }
+        operation.progress.elapsed = operation.started_at.elapsed();
+        operation.progress.state = state;
+        let progress = operation.snapshot();
+
+        inner.finished.push_back(op_id);
+        while inner.finished.len() > MAX_FINISHED_OPERATIONS {
+            if let Some(evicted) = inner.finished.pop_front() {
+                inner.operations.remove(&evicted);
+            }
+        }
+        Some(progress)
+    }
+
+    pub(crate) fn get(&self, op_id: u64) -> Option<OperationProgress> {
+        self.inner
+            .lock()
+            .unwrap()
+            .operations
+            .get(&op_id)
+            .map(|operation| operation.snapshot())
+    }
+
+    /// The running and the recently finished operations in the order of
+    /// start.
+    pub(crate) fn list(&self) -> Vec<OperationProgress> {
+        let inner = self.inner.lock().unwrap();
+        let mut operations = inner
+            .operations
+            .values()
+            .map(|operation| (operation.seq, operation.snapshot()))
+            .collect::<Vec<_>>();
+        operations.sort_by_key(|(seq, _)| *seq);
+        operations
+            .into_iter()
+            .map(|(_, progress)| progress)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::OperationKind;
+    use super::OperationState;
+    use super::Operations;
+    use super::MAX_FINISHED_OPERATIONS;
+
+    #[test]
+    fn test_operations() {
+        let operations = Operations::default();
+        let progress = operations.start(1, OperationKind::RelocateReplica, 10);
+        assert_eq!(progress.state, OperationState::Running);
+        assert!(operations.update(2, "catching up", 1, 2).is_none());
+
+        let progress = operations.update(1, "catching up", 5, 8).unwrap();
+        assert_eq!(
+            (progress.step.as_str(), progress.done, progress.total),
+            ("catching up", 5, 8)
+        );
+        let progress = operations
+            .finish(1, OperationState::Failed("timeout".to_owned()))
+            .unwrap();
+        assert!(progress.is_finished());
+        // the finished operation is not updated again.
+        assert!(operations.update(1, "promoted", 0, 0).is_none());
+        assert!(operations.finish(1, OperationState::Succeeded).is_none());
+        assert_eq!(operations.get(1), Some(progress));
+
+        // the oldest finished operations are evicted.
+        for op_id in 2..=MAX_FINISHED_OPERATIONS as u64 + 1 {
+            operations.start(op_id, OperationKind::Balance, 0);
+            operations.finish(op_id, OperationState::Succeeded);
+        }
+        operations.start(0, OperationKind::ForkGroup, 11);
+        assert!(operations.get(1).is_none());
+        assert!(operations.get(2).is_some());
+        let listed = operations.list();
+        assert_eq!(listed.len(), MAX_FINISHED_OPERATIONS + 1);
+        assert_eq!(listed.last().map(|progress| progress.op_id), Some(0));
+    }
+}
@@ -0,0 +1,178 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use crate::Error;
+
+/// The thresholds of shedding the work of the node under overload, see
+/// `Config::overload_shedding`. The occupancy is the percent of the proposal
+/// queue in use, the loop latency is the time the node takes to handle the
+/// readys of an iteration of the main loop. The reads are shed first, then
+/// the non-urgent management, the writes are never shed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverloadShedding {
+    /// Shed `read_index` once the occupancy reaches the percent, default is
+    /// `80`.
+    pub read_occupancy_percent: u8,
+    /// Shed `read_index` once the loop latency reaches it, default is `50ms`.
+    pub read_loop_latency: Duration,
+    /// Shed the non-urgent management once the occupancy reaches the
+    /// percent, default is `95`.
+    pub manage_occupancy_percent: u8,
+    /// Shed the non-urgent management once the loop latency reaches it,
+    /// default is `200ms`.
+    pub manage_loop_latency: Duration,
+}
+
+impl Default for OverloadShedding {
+    fn default() -> Self {
+        Self {
+            read_occupancy_percent: 80,
+            read_loop_latency: Duration::from_millis(50),
+            manage_occupancy_percent: 95,
+            manage_loop_latency: Duration::from_millis(200),
+        }
+    }
+}
+
+/// The class of the work shed under overload, the lower class is shed first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ShedClass {
+    Read,
+    Manage,
+}
+
+/// The overload of the node, see `MultiRaft::overload_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct OverloadStats {
+    /// The smoothed time of handling the readys of an iteration of the main
+    /// loop, it follows the spikes immediately and decays slowly.
+    pub loop_latency: Duration,
+    pub shed_reads: u64,
+    pub shed_manages: u64,
+}
+
+/// OverloadDetector is shared by the node which records the loop latency and
+/// `MultiRaft` which sheds the work before it's queued.
+pub(crate) struct OverloadDetector {
+    node_id: u64,
+    shedding: Option<OverloadShedding>,
+    loop_latency_us: AtomicU64,
+    shed_reads: AtomicU64,
+    shed_manages: AtomicU64,
+}
+
+impl OverloadDetector {
+    pub(crate) fn new(node_id: u64, shedding: Option<OverloadShedding>) -> Self {
+        Self {
+            node_id,
+            shedding,
+            loop_latency_us: AtomicU64::new(0),
+            shed_reads: AtomicU64::new(0),
+            shed_manages: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn record_loop_latency(&self, elapsed: Duration) {
+        let sample = elapsed.as_micros() as u64;
+        let latency = self.loop_latency_us.load(Ordering::Relaxed);
+        // the decay weights the new sample by 1/8, so a single fast iteration
+        // does not end the shedding.
+        let latency = if sample >= latency {
+            sample
+        } else {
+            latency - (latency - sample) / 8
+        };
+        self.loop_latency_us.store(latency, Ordering::Relaxed);
+    }
+
+    /// Check the work of the class is admitted with `queued` of `capacity`
+    /// slots of the proposal queue in use, returns `Error::Overloaded` if
+    /// it's shed.
+    pub(crate) fn admit(
+        &self,
+        class: ShedClass,
+        queued: usize,
+        capacity: usize,
+    ) -> Result<(), Error> {
+        let shedding = match self.shedding.as_ref() {
+            None => return Ok(()),
+            Some(shedding) => shedding,
+        };
+        let (occupancy_threshold, latency_threshold, shed) = match class {
+            ShedClass::Read => (
+                shedding.read_occupancy_percent,
+                shedding.read_loop_latency,
+                &self.shed_reads,
+            ),
+            ShedClass::Manage => (
+                shedding.manage_occupancy_percent,
+                shedding.manage_loop_latency,
+                &self.shed_manages,
+            ),
+        };
+
+        let occupancy = queued * 100 / capacity.max(1);
+        let latency = Duration::from_micros(self.loop_latency_us.load(Ordering::Relaxed));
+        let reason = if occupancy >= occupancy_threshold as usize {
+            format!("proposal queue occupancy {}%", occupancy)
+        } else if latency >= latency_threshold {
+            format!("loop latency {:?}", latency)
+        } else {
+            return Ok(());
+        };
+        shed.fetch_add(1, Ordering::Relaxed);
+        Err(Error::Overloaded {
+            node_id: self.node_id,
+            reason: format!("{:?} shed by {}", class, reason),
+        })
+    }
+
+    pub(crate) fn stats(&self) -> OverloadStats {
+        OverloadStats {
+            loop_latency: Duration::from_micros(self.loop_latency_us.load(Ordering::Relaxed)),
+            shed_reads: self.shed_reads.load(Ordering::Relaxed),
+            shed_manages: self.shed_manages.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::OverloadDetector;
+    use super::OverloadShedding;
+    use super::ShedClass;
+
+    #[test]
+    fn test_overload_shedding() {
+        let ms = Duration::from_millis;
+        let detector = OverloadDetector::new(1, None);
+        detector.record_loop_latency(ms(1000));
+        assert!(detector.admit(ShedClass::Read, 100, 100).is_ok());
+
+        // the reads are shed before the management.
+        let detector = OverloadDetector::new(1, Some(OverloadShedding::default()));
+        assert!(detector.admit(ShedClass::Read, 79, 100).is_ok());
+        assert!(detector.admit(ShedClass::Read, 80, 100).is_err());
+        assert!(detector.admit(ShedClass::Manage, 80, 100).is_ok());
+        assert!(detector.admit(ShedClass::Manage, 95, 100).is_err());
+
+        // the loop latency follows the spike and decays slowly.
+        detector.record_loop_latency(ms(80));
+        assert!(detector.admit(ShedClass::Read, 0, 100).is_err());
+        assert!(detector.admit(ShedClass::Manage, 0, 100).is_ok());
+        detector.record_loop_latency(ms(0));
+        assert_eq!(detector.stats().loop_latency, ms(70));
+        assert!(detector.admit(ShedClass::Read, 0, 100).is_err());
+        for _ in 0..16 {
+            detector.record_loop_latency(ms(0));
+        }
+        assert!(detector.admit(ShedClass::Read, 0, 100).is_ok());
+
+        let stats = detector.stats();
+        assert_eq!(stats.shed_reads, 3);
+        assert_eq!(stats.shed_manages, 1);
+    }
+}
@@ -0,0 +1,417 @@
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+
+use raft::StateRole;
+
+use crate::multiraft::NO_NODE;
+use crate::topology::ClusterTopology;
+use crate::topology::GroupTopology;
+
+/// The load of a node, see `node_loads`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeLoad {
+    pub node_id: u64,
+    /// The groups which have a replica on the node.
+    pub groups: usize,
+    /// The groups whose replica on the node is the leader.
+    pub leaders: usize,
+}
+
+/// The action suggested by `PlacementDriver` to balance the load of the
+/// nodes, it's executed by `MultiRaft::execute_placement` on the node of the
+/// leader of the group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlacementAction {
+    /// Transfer the leadership of the group from the node of the leader to
+    /// the voter on `to_node_id`, see `MultiRaft::transfer_leader`.
+    TransferLeader {
+        group_id: u64,
+        from_node_id: u64,
+        to_node_id: u64,
+        transferee: u64,
+    },
+    /// Move the replica of the group from `from_node_id` to `to_node_id`, see
+    /// `MultiRaft::relocate_replica`.
+    MoveReplica {
+        group_id: u64,
+        from_node_id: u64,
+        from_replica_id: u64,
+        to_node_id: u64,
+    },
+}
+
+impl PlacementAction {
+    #[inline]
+    pub fn group_id(&self) -> u64 {
+        match self {
+            PlacementAction::TransferLeader { group_id, .. } => *group_id,
+            PlacementAction::MoveReplica { group_id, .. } => *group_id,
+        }
+    }
+}
+
+/// PlacementDriver suggests the actions which balance the replicas and the
+/// leaders of the groups across the nodes, see `MultiRaft::placement_plan`.
+/// The driver is called on any node with the cluster topology of the node,
+/// so the quality of the plan depends on `Config::topology_provider`.
+pub trait PlacementDriver: Debug + Send + Sync + 'static {
+    fn plan(&self, topology: &ClusterTopology) -> Vec<PlacementAction>;
+}
+
+/// Suggests nothing.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoPlacement;
+
+impl PlacementDriver for NoPlacement {
+    fn plan(&self, _: &ClusterTopology) -> Vec<PlacementAction> {
+        vec![]
+    }
+}
+
+/// Balances the leader counts and then the group counts of the nodes, until
+/// the difference between the most and the least loaded nodes is within the
+/// tolerance. It's the default driver.
+///
+/// The leaders are transferred to the voters which are reachable from the
+/// leader, and the replicas are moved only if they are not the leader, so
+/// the leadership is balanced before the replica of the leader is moved.
+#[derive(Debug, Clone, Copy)]
+pub struct BalancedPlacement {
+    /// The tolerated difference of the leader counts, default is `1`.
+    pub leader_tolerance: usize,
+    /// The tolerated difference of the group counts, default is `1`.
+    pub group_tolerance: usize,
+    /// The maximum actions suggested by a plan, default is `4`.
+    pub max_actions: usize,
+}
+
+impl Default for BalancedPlacement {
+    fn default() -> Self {
+        Self {
+            leader_tolerance: 1,
+            group_tolerance: 1,
+            max_actions: 4,
+        }
+    }
+}
+
+impl PlacementDriver for BalancedPlacement {
+    fn plan(&self, topology: &ClusterTopology) -> Vec<PlacementAction> {
+        let mut loads = node_loads(topology)
+            .into_iter()
+            .map(|load| (load.node_id, load))
+            .collect::<BTreeMap<_, _>>();
+        let groups = leader_views(topology);
+        let mut actions = vec![];
+        let mut planned = vec![];
+
+        while actions.len() < self.max_actions {
+            match self.plan_leader_transfer(&groups, &loads, &planned) {
+                None => break,
+                Some(action) => {
+                    if let PlacementAction::TransferLeader {
+                        from_node_id,
+                        to_node_id,
+                        ..
+                    } = action
+                    {
+                        if let Some(load) = loads.get_mut(&from_node_id) {
+                            load.leaders -= 1;
+                        }
+                        if let Some(load) = loads.get_mut(&to_node_id) {
+                            load.leaders += 1;
+                        }
+                    }
+                    planned.push(action.group_id());
+                    actions.push(action);
+                }
+            }
+        }
+
+        while actions.len() < self.max_actions {
+            match self.plan_replica_move(&groups, &loads, &planned) {
+                None => break,
+                Some(action) => {
+                    if let PlacementAction::MoveReplica {
+                        from_node_id,
+                        to_node_id,
+                        ..
+                    } = action
+                    {
+                        if let Some(load) = loads.get_mut(&from_node_id) {
+                            load.groups -= 1;
+                        }
+                        if let Some(load) = loads.get_mut(&to_node_id) {
+                            load.groups += 1;
+                        }
+                    }
+                    planned.push(action.group_id());
+                    actions.push(action);
+                }
+            }
+        }
+        actions
+    }
+}
+
+impl BalancedPlacement {
+    fn plan_leader_transfer(
+        &self,
+        groups: &[&GroupTopology],
+        loads: &BTreeMap<u64, NodeLoad>,
+        planned: &[u64],
+    ) -> Option<PlacementAction> {
+        let from = loads.values().max_by_key(|load| load.leaders)?;
+        let mut best: Option<(usize, PlacementAction)> = None;
+        for group in groups.iter() {
+            if group.leader_node_id != from.node_id || planned.contains(&group.group_id) {
+                continue;
+            }
+            for replica in group.replicas.iter() {
+                if replica.learner
+                    || replica.witness
+                    || replica.replica_id == group.leader_id
+                    || replica.reachable == Some(false)
+                {
+                    continue;
+                }
+                let to = match loads.get(&replica.node_id) {
+                    None => continue,
+                    Some(to) => to,
+                };
+                if from.leaders <= to.leaders + self.leader_tolerance
+                    || best
+                        .as_ref()
+                        .map_or(false, |(leaders, _)| *leaders <= to.leaders)
+                {
+                    continue;
+                }
+                best = Some((
+                    to.leaders,
+                    PlacementAction::TransferLeader {
+                        group_id: group.group_id,
+                        from_node_id: from.node_id,
+                        to_node_id: to.node_id,
+                        transferee: replica.replica_id,
+                    },
+                ));
+            }
+        }
+        best.map(|(_, action)| action)
+    }
+
+    fn plan_replica_move(
+        &self,
+        groups: &[&GroupTopology],
+        loads: &BTreeMap<u64, NodeLoad>,
+        planned: &[u64],
+    ) -> Option<PlacementAction> {
+        let from = loads.values().max_by_key(|load| load.groups)?;
+        let to = loads.values().min_by_key(|load| load.groups)?;
+        if from.groups <= to.groups + self.group_tolerance {
+            return None;
+        }
+
+        groups
+            .iter()
+            .filter(|group| !planned.contains(&group.group_id))
+            .filter(|group| {
+                group
+                    .replicas
+                    .iter()
+                    .all(|replica| replica.node_id != to.node_id)
+            })
+            .find_map(|group| {
+                group
+                    .replicas
+                    .iter()
+                    .find(|replica| {
+                        replica.node_id == from.node_id
+                            && replica.replica_id != group.leader_id
+                            && !replica.learner
+                            && !replica.witness
+                    })
+                    .map(|replica| PlacementAction::MoveReplica {
+                        group_id: group.group_id,
+                        from_node_id: from.node_id,
+                        from_replica_id: replica.replica_id,
+                        to_node_id: to.node_id,
+                    })
+            })
+    }
+}
+
+/// The group counts and the leader counts of the nodes of the topology in the
+/// order of node id. The counts are reported by the nodes themselves from the
+/// group states on them.
+pub fn node_loads(topology: &ClusterTopology) -> Vec<NodeLoad> {
+    topology
+        .nodes
+        .iter()
+        .map(|node| NodeLoad {
+            node_id: node.node_id,
+            groups: node.groups.iter().filter(|group| !group.parked).count(),
+            leaders: node
+                .groups
+                .iter()
+                .filter(|group| group.role == StateRole::Leader)
+                .count(),
+        })
+        .collect()
+}
+
+/// The views of the groups reported by the leaders in the order of group id,
+/// only the leader knows the progress of the replicas.
+fn leader_views(topology: &ClusterTopology) -> Vec<&GroupTopology> {
+    let mut groups = topology
+        .nodes
+        .iter()
+        .flat_map(|node| node.groups.iter())
+        .filter(|group| group.role == StateRole::Leader && group.leader_node_id != NO_NODE)
+        .collect::<Vec<_>>();
+    groups.sort_by_key(|group| group.group_id);
+    groups
+}
+
+#[cfg(test)]
+mod test {
+    use raft::StateRole;
+
+    use super::node_loads;
+    use super::BalancedPlacement;
+    use super::NodeLoad;
+    use super::PlacementAction;
+    use super::PlacementDriver;
+    use crate::topology::ClusterTopology;
+    use crate::topology::GroupTopology;
+    use crate::topology::NodeTopology;
+    use crate::topology::ReplicaTopology;
+
+    /// The groups are placed on the nodes of `members`, the first member is
+    /// the leader. The replica id is the node id.
+    fn make_topology(nodes: u64, groups: &[&[u64]]) -> ClusterTopology {
+        let mut topology = ClusterTopology {
+            nodes: (1..=nodes)
+                .map(|node_id| NodeTopology {
+                    node_id,
+                    groups: vec![],
+                })
+                .collect(),
+        };
+        for (i, members) in groups.iter().enumerate() {
+            let leader = members[0];
+            for node_id in members.iter() {
+                topology.nodes[*node_id as usize - 1]
+                    .groups
+                    .push(GroupTopology {
+                        group_id: i as u64 + 1,
+                        replica_id: *node_id,
+                        role: if *node_id == leader {
+                            StateRole::Leader
+                        } else {
+                            StateRole::Follower
+                        },
+                        leader_id: leader,
+                        leader_node_id: leader,
+                        replicas: members
+                            .iter()
+                            .map(|node_id| ReplicaTopology {
+                                replica_id: *node_id,
+                                node_id: *node_id,
+                                learner: false,
+                                witness: false,
+                                reachable: Some(true),
+                                matched: Some(1),
+                            })
+                            .collect(),
+                        parked: false,
+                    });
+            }
+        }
+        topology
+    }
+
+    #[test]
+    fn test_node_loads() {
+        let topology = make_topology(3, &[&[1, 2], &[1, 2], &[2, 3]]);
+        assert_eq!(
+            node_loads(&topology),
+            vec![
+                NodeLoad {
+                    node_id: 1,
+                    groups: 2,
+                    leaders: 2
+                },
+                NodeLoad {
+                    node_id: 2,
+                    groups: 3,
+                    leaders: 1
+                },
+                NodeLoad {
+                    node_id: 3,
+                    groups: 1,
+                    leaders: 0
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_balanced_placement() {
+        let driver = BalancedPlacement::default();
+
+        // the balanced topology is kept.
+        let topology = make_topology(3, &[&[1, 2, 3], &[2, 3, 1], &[3, 1, 2]]);
+        assert_eq!(driver.plan(&topology), vec![]);
+
+        // the leaders are transferred to the least loaded voters.
+        let topology = make_topology(3, &[&[1, 2, 3], &[1, 2, 3], &[1, 2, 3]]);
+        assert_eq!(
+            driver.plan(&topology),
+            vec![
+                PlacementAction::TransferLeader {
+                    group_id: 1,
+                    from_node_id: 1,
+                    to_node_id: 2,
+                    transferee: 2,
+                },
+                PlacementAction::TransferLeader {
+                    group_id: 2,
+                    from_node_id: 1,
+                    to_node_id: 3,
+                    transferee: 3,
+                },
+            ]
+        );
+
+        // the follower replicas are moved to the empty node, the replicas of
+        // the leader are kept.
+        let topology = make_topology(4, &[&[1, 2], &[2, 1], &[1, 3], &[3, 1]]);
+        assert_eq!(
+            driver.plan(&topology),
+            vec![
+                PlacementAction::MoveReplica {
+                    group_id: 2,
+                    from_node_id: 1,
+                    from_replica_id: 1,
+                    to_node_id: 4,
+                },
+                PlacementAction::MoveReplica {
+                    group_id: 4,
+                    from_node_id: 1,
+                    from_replica_id: 1,
+                    to_node_id: 4,
+                },
+            ]
+        );
+
+        // the plan is bounded by the maximum actions.
+        let driver = BalancedPlacement {
+            leader_tolerance: 0,
+            max_actions: 1,
+            ..Default::default()
+        };
+        let topology = make_topology(2, &[&[1, 2], &[1, 2], &[1, 2], &[1, 2]]);
+        assert_eq!(driver.plan(&topology).len(), 1);
+    }
+}
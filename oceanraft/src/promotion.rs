@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+/// The policy to promote the learners of a group to voters automatically,
+/// see `LearnerPromoter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LearnerPromotion {
+    /// The learner has caught up if its matched index is within the number of
+    /// entries of the last index of the leader.
+    pub max_lag: u64,
+    /// The learner is promoted after it has caught up for the number of
+    /// consecutive ticks.
+    pub ticks: usize,
+}
+
+/// LearnerPromoter decides the auto-promotion policy of the groups and vetoes
+/// the promotions. The leader of the group with a policy watches the progress
+/// of the learners on every tick, and proposes to promote the learner which
+/// has caught up for the ticks of the policy. It is called on the node actor,
+/// so the implementations should not block.
+pub trait LearnerPromoter: Debug + Send + Sync + 'static {
+    /// Returns the policy of the group, `None` if the learners of the group
+    /// are not promoted automatically.
+    fn policy(&self, group_id: u64) -> Option<LearnerPromotion>;
+
+    /// Called before the learner is promoted. Returns the reason if the
+    /// promotion is vetoed, the learner must catch up for the ticks of the
+    /// policy again before the next attempt.
+    fn approve(&self, group_id: u64, replica_id: u64, node_id: u64) -> Result<(), String>;
+}
+
+/// Never promotes the learners, it's the default promoter.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoLearnerPromotion;
+
+impl LearnerPromoter for NoLearnerPromotion {
+    fn policy(&self, _: u64) -> Option<LearnerPromotion> {
+        None
+    }
+
+    fn approve(&self, _: u64, _: u64, _: u64) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Applies the policy to all groups and approves all promotions.
+impl LearnerPromoter for LearnerPromotion {
+    fn policy(&self, _: u64) -> Option<LearnerPromotion> {
+        Some(*self)
+    }
+
+    fn approve(&self, _: u64, _: u64, _: u64) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// The consecutive ticks that the learners of a group have caught up with
+/// the leader.
+#[derive(Debug, Default)]
+pub(crate) struct LearnerCatchUp {
+    ticks: HashMap<u64, usize>,
+}
+
+impl LearnerCatchUp {
+    /// Advance by the (replica id, lag) of the learners, the absent learners
+    /// are forgotten. Returns the learners which have caught up for the ticks
+    /// of the policy in the order of replica id.
+    pub(crate) fn tick<I>(&mut self, lags: I, policy: &LearnerPromotion) -> Vec<u64>
+    where
+        I: IntoIterator<Item = (u64, u64)>,
+    {
+        let mut ticks = HashMap::new();
+        let mut ready = vec![];
+        for (replica_id, lag) in lags {
+            if lag > policy.max_lag {
+                continue;
+            }
+            let caught_up = self.ticks.get(&replica_id).map_or(1, |ticks| ticks + 1);
+            if caught_up >= policy.ticks {
+                ready.push(replica_id);
+            }
+            ticks.insert(replica_id, caught_up);
+        }
+        self.ticks = ticks;
+        ready.sort_unstable();
+        ready
+    }
+
+    /// Start over the learner, e.g. the promotion is vetoed or failed.
+    pub(crate) fn reset(&mut self, replica_id: u64) {
+        self.ticks.remove(&replica_id);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.ticks.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LearnerCatchUp;
+    use super::LearnerPromotion;
+
+    #[test]
+    fn test_learner_catch_up() {
+        let policy = LearnerPromotion {
+            max_lag: 10,
+            ticks: 3,
+        };
+        let mut catch_up = LearnerCatchUp::default();
+        assert!(catch_up.tick([(2, 5), (3, 20)], &policy).is_empty());
+        assert!(catch_up.tick([(2, 10), (3, 5)], &policy).is_empty());
+        assert_eq!(catch_up.tick([(2, 0), (3, 5)], &policy), vec![2]);
+        assert_eq!(catch_up.tick([(3, 5), (2, 0)], &policy), vec![2, 3]);
+
+        // falling behind or absent starts over.
+        assert_eq!(catch_up.tick([(2, 11), (3, 5)], &policy), vec![3]);
+        assert!(catch_up.tick([(2, 0)], &policy).is_empty());
+        assert!(catch_up.tick([(2, 0), (3, 0)], &policy).is_empty());
+        assert_eq!(catch_up.tick([(2, 0)], &policy), vec![2]);
+
+        // the vetoed learner starts over.
+        catch_up.reset(2);
+        assert!(catch_up.tick([(2, 0)], &policy).is_empty());
+    }
+}
@@ -4,6 +4,7 @@ use std::fmt::Debug;
 
 use raft::ReadState;
 use tokio::sync::oneshot;
+use tokio::time::Instant;
 use tracing::debug;
 use tracing::error;
 use uuid::Uuid;
@@ -12,7 +13,10 @@ use crate::multiraft::ProposeResponse;
 
 use super::error::Error;
 use super::error::ProposeError;
+use super::latency::ProposalTimeline;
+use super::latency::ReadTimeline;
 use super::msg::ReadIndexContext;
+use super::msg::WriteCommit;
 use super::utils::flexbuffer_deserialize;
 
 /// Shrink queue if queue capacity more than and len less than
@@ -25,12 +29,28 @@ pub struct ReadIndexProposal {
     pub context: Option<ReadIndexContext>,
     // if some, the R is sent to client via tx.
     pub tx: Option<oneshot::Sender<Result<Option<Vec<u8>>, Error>>>,
+    // if some, the latency of stages is recorded, see `Config::record_latency`.
+    pub(crate) timeline: Option<ReadTimeline>,
+    // the time the read index is issued, the lease of the leader is renewed
+    // from it, see `Config::enable_lease_read`.
+    pub(crate) issued_at: Instant,
+    // if true, the read is responded after the read index is applied, see
+    // `ConsistencyLevel::Follower`.
+    pub(crate) wait_applied: bool,
+    // the reads which share the read index round of the read, see
+    // `Config::batch_read_index`.
+    pub(crate) batched: Vec<ReadIndexProposal>,
 }
 
 pub struct ReadIndexQueue {
     ready_cnt: usize,
     handle_cnt: usize,
     queue: VecDeque<ReadIndexProposal>,
+    /// The reads waiting for the next round, see `Config::batch_read_index`.
+    batch: Vec<ReadIndexProposal>,
+    batching: bool,
+    /// See `Config::max_read_queue_len`.
+    max_len: usize,
 }
 
 impl ReadIndexQueue {
@@ -39,9 +59,59 @@ impl ReadIndexQueue {
             ready_cnt: 0,
             handle_cnt: 0,
             queue: VecDeque::new(),
+            batch: Vec::new(),
+            batching: false,
+            max_len: 0,
         }
     }
 
+    /// Batch the reads into rounds and bound the outstanding reads, see
+    /// `Config::batch_read_index` and `Config::max_read_queue_len`.
+    pub(crate) fn with_limits(mut self, batching: bool, max_len: usize) -> Self {
+        self.batching = batching;
+        self.max_len = max_len;
+        self
+    }
+
+    #[inline]
+    pub(crate) fn is_batching(&self) -> bool {
+        self.batching
+    }
+
+    #[inline]
+    pub(crate) fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// The number of the outstanding reads, including the batched reads.
+    pub(crate) fn len(&self) -> usize {
+        self.queue
+            .iter()
+            .map(|read| 1 + read.batched.len())
+            .sum::<usize>()
+            + self.batch.len()
+    }
+
+    #[inline]
+    pub(crate) fn is_full(&self) -> bool {
+        self.max_len != 0 && self.len() >= self.max_len
+    }
+
+    /// Add the read to the next round.
+    #[inline]
+    pub(crate) fn push_batch(&mut self, proposal: ReadIndexProposal) {
+        self.batch.push(proposal);
+    }
+
+    /// Take the reads of the next round, the first read carries the others
+    /// and is issued as the read index of the round.
+    pub(crate) fn take_batch(&mut self) -> Option<ReadIndexProposal> {
+        let mut reads = std::mem::take(&mut self.batch).into_iter();
+        let mut head = reads.next()?;
+        head.batched = reads.collect();
+        Some(head)
+    }
+
     #[inline]
     #[allow(unused)]
     pub fn push_front(&mut self, proposal: ReadIndexProposal) {
@@ -55,6 +125,32 @@ impl ReadIndexQueue {
         self.queue.push_back(proposal)
     }
 
+    /// Release the unused capacity of the queue.
+    #[inline]
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.queue.shrink_to_fit();
+    }
+
+    #[inline]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.queue.is_empty() && self.batch.is_empty()
+    }
+
+    /// Remove all proposals of the queue, including the proposals that
+    /// are not ready and the batched reads.
+    pub(crate) fn drain_all(&mut self) -> Vec<ReadIndexProposal> {
+        self.ready_cnt = 0;
+        self.handle_cnt = 0;
+        self.queue
+            .drain(..)
+            .chain(self.batch.drain(..))
+            .flat_map(|mut read| {
+                let batched = std::mem::take(&mut read.batched);
+                std::iter::once(read).chain(batched)
+            })
+            .collect()
+    }
+
     fn try_gc(&mut self) {
         // TODO: think move the shrink_to_fit operation  to background task?
         if self.queue.capacity() > SHRINK_CACHE_CAPACITY && self.queue.len() < SHRINK_CACHE_CAPACITY
@@ -85,7 +181,10 @@ impl ReadIndexQueue {
             match self.queue.get_mut(self.ready_cnt) {
                 Some(read) if read.uuid == Uuid::from_bytes(read_ctx.uuid) => {
                     read.read_index = Some(rs.index);
-                    read.context = Some(read_ctx.clone());
+                    // the context of the batched read is kept locally.
+                    if read.context.is_none() {
+                        read.context = Some(read_ctx.clone());
+                    }
                     self.ready_cnt += 1;
                 }
                 Some(read) => error!("unexpected uuid {} detected", read.uuid),
@@ -105,12 +204,21 @@ pub struct Proposal<R: ProposeResponse> {
     pub is_conf_change: bool,
     // if some, the R is sent to client via tx.
     pub tx: Option<oneshot::Sender<Result<(R, Option<Vec<u8>>), Error>>>,
+    // if some, the commit metadata is sent to client via commit_tx when committed.
+    pub commit_tx: Option<oneshot::Sender<WriteCommit>>,
+    // if some, the context is kept locally and passed to the state machine
+    // instead of the context of entry.
+    pub context: Option<Vec<u8>>,
+    // if some, the latency of stages is recorded, see `Config::record_latency`.
+    pub(crate) timeline: Option<ProposalTimeline>,
 }
 
 #[derive(Debug)]
 pub struct ProposalQueue<RES: ProposeResponse> {
     pub replica_id: u64,
     pub queue: VecDeque<Proposal<RES>>,
+    /// See `Config::max_inflight_proposals`.
+    max_inflight: usize,
 }
 
 impl<RES: ProposeResponse> ProposalQueue<RES> {
@@ -118,9 +226,31 @@ impl<RES: ProposeResponse> ProposalQueue<RES> {
         ProposalQueue {
             replica_id,
             queue: VecDeque::new(),
+            max_inflight: 0,
         }
     }
 
+    pub(crate) fn with_max_inflight(mut self, max_inflight: usize) -> Self {
+        self.max_inflight = max_inflight;
+        self
+    }
+
+    /// The number of the proposals not committed up to `committed`.
+    pub(crate) fn inflight(&self, committed: u64) -> usize {
+        self.queue.len() - self.queue.partition_point(|p| p.index <= committed)
+    }
+
+    /// Returns true if the uncommitted proposals reach the max inflight.
+    #[inline]
+    pub(crate) fn is_full(&self, committed: u64) -> bool {
+        self.max_inflight != 0 && self.inflight(committed) >= self.max_inflight
+    }
+
+    #[inline]
+    pub(crate) fn max_inflight(&self) -> usize {
+        self.max_inflight
+    }
+
     pub fn push(&mut self, proposal: Proposal<RES>) {
         if let Some(last) = self.queue.back() {
             // The term must be increasing among all log entries and the index
@@ -216,6 +346,28 @@ impl<RES: ProposeResponse> ProposalQueue<RES> {
     pub fn is_empty(&self) -> bool {
         self.queue.is_empty()
     }
+
+    /// Stamp the proposals whose entries are persisted up to `index` for the
+    /// latency recorder, the proposals before the first stamped one from the
+    /// back were stamped by the previous persists.
+    pub(crate) fn mark_persisted(&mut self, index: u64) {
+        let now = Instant::now();
+        for proposal in self.queue.iter_mut().rev() {
+            if proposal.index > index {
+                continue;
+            }
+            match proposal.timeline.as_mut() {
+                Some(timeline) if timeline.persisted_at.is_none() => timeline.persisted(now),
+                _ => break,
+            }
+        }
+    }
+
+    /// Release the unused capacity of the queue.
+    #[inline]
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.queue.shrink_to_fit();
+    }
 }
 
 // #[test]
@@ -0,0 +1,85 @@
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::utils::flexbuffer_serialize;
+
+/// The prefix of the context envelope, the context of the entries proposed
+/// without the envelope is left as it is.
+const ENVELOPE_MAGIC: &[u8; 4] = b"\xffOPE";
+
+/// The length of the envelope header, i.e. the magic and the length of the
+/// serialized proposer.
+const ENVELOPE_HEADER_LEN: usize = ENVELOPE_MAGIC.len() + 4;
+
+/// The identity of the proposer of the entry. It's recorded in the context
+/// envelope of the entry if `Config::annotate_proposer` is enabled, so the
+/// applied entries can be audited by the node which originated them.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Proposer {
+    pub node_id: u64,
+    pub replica_id: u64,
+    /// The authenticated principal of the proposal, see `Authorizer::principal`.
+    pub principal: Option<String>,
+}
+
+impl Proposer {
+    /// Wrap the context of the entry in the envelope of the proposer.
+    pub(crate) fn wrap(&self, context: Vec<u8>) -> Vec<u8> {
+        let proposer = flexbuffer_serialize(self)
+            .expect("invalid Proposer type")
+            .take_buffer();
+        let mut envelope = Vec::with_capacity(ENVELOPE_HEADER_LEN + proposer.len() + context.len());
+        envelope.extend_from_slice(ENVELOPE_MAGIC);
+        envelope.extend_from_slice(&(proposer.len() as u32).to_be_bytes());
+        envelope.extend_from_slice(&proposer);
+        envelope.extend_from_slice(&context);
+        envelope
+    }
+
+    /// Decode the proposer from the context of the entry, e.g. the entries of
+    /// `AppliedBatch`. Returns the proposer if the context is enveloped and
+    /// the context of the proposal.
+    pub fn decode(context: &[u8]) -> (Option<Proposer>, &[u8]) {
+        if context.len() < ENVELOPE_HEADER_LEN || !context.starts_with(ENVELOPE_MAGIC) {
+            return (None, context);
+        }
+
+        let mut len = [0; 4];
+        len.copy_from_slice(&context[ENVELOPE_MAGIC.len()..ENVELOPE_HEADER_LEN]);
+        let end = ENVELOPE_HEADER_LEN + u32::from_be_bytes(len) as usize;
+        if context.len() < end {
+            return (None, context);
+        }
+        // the malformed envelope is the context of the proposal.
+        match flexbuffers::from_slice(&context[ENVELOPE_HEADER_LEN..end]) {
+            Ok(proposer) => (Some(proposer), &context[end..]),
+            Err(_) => (None, context),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Proposer;
+
+    #[test]
+    fn test_proposer_envelope() {
+        let proposer = Proposer {
+            node_id: 1,
+            replica_id: 3,
+            principal: Some("tenant-a".to_owned()),
+        };
+        let envelope = proposer.wrap(b"ctx".to_vec());
+        assert_eq!(
+            Proposer::decode(&envelope),
+            (Some(proposer.clone()), &b"ctx"[..])
+        );
+
+        let envelope = proposer.wrap(vec![]);
+        assert_eq!(Proposer::decode(&envelope), (Some(proposer), &b""[..]));
+
+        // the context without the envelope is left as it is.
+        assert_eq!(Proposer::decode(b"ctx"), (None, &b"ctx"[..]));
+        assert_eq!(Proposer::decode(b""), (None, &b""[..]));
+    }
+}
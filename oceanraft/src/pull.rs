@@ -0,0 +1,283 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::Apply;
+use crate::Error;
+use crate::ProposeData;
+use crate::ProposeResponse;
+
+use super::msg::ApplyResultMessage;
+
+/// The applys of the group in pull mode.
+struct PullGroup<W, R>
+where
+    W: ProposeData,
+    R: ProposeResponse,
+{
+    /// The applys committed and not pulled yet.
+    applys: VecDeque<Apply<W, R>>,
+    /// The index and term of the applys pulled and not acknowledged yet.
+    pulled: VecDeque<(u64, u64)>,
+    acknowledged: u64,
+}
+
+impl<W, R> Default for PullGroup<W, R>
+where
+    W: ProposeData,
+    R: ProposeResponse,
+{
+    fn default() -> Self {
+        Self {
+            applys: VecDeque::new(),
+            pulled: VecDeque::new(),
+            acknowledged: 0,
+        }
+    }
+}
+
+/// The groups applied in pull mode on the node, see `MultiRaft::committed_entries`.
+/// The apply worker buffers the applys of the groups instead of feeding them
+/// to the `StateMachine`, and the applied index of the group is reported to
+/// the node when the applys are acknowledged.
+pub(crate) struct PullApplys<W, R>
+where
+    W: ProposeData,
+    R: ProposeResponse,
+{
+    groups: Arc<Mutex<HashMap<u64, PullGroup<W, R>>>>,
+    tx: UnboundedSender<ApplyResultMessage>,
+}
+
+impl<W, R> Clone for PullApplys<W, R>
+where
+    W: ProposeData,
+    R: ProposeResponse,
+{
+    fn clone(&self) -> Self {
+        Self {
+            groups: self.groups.clone(),
+            tx: self.tx.clone(),
+        }
+    }
+}
+
+impl<W, R> PullApplys<W, R>
+where
+    W: ProposeData,
+    R: ProposeResponse,
+{
+    pub(crate) fn new(tx: UnboundedSender<ApplyResultMessage>) -> Self {
+        Self {
+            groups: Arc::new(Mutex::new(HashMap::new())),
+            tx,
+        }
+    }
+
+    pub(crate) fn enable(&self, group_id: u64) {
+        self.groups.lock().unwrap().entry(group_id).or_default();
+    }
+
+    #[inline]
+    pub(crate) fn is_pull(&self, group_id: u64) -> bool {
+        self.groups.lock().unwrap().contains_key(&group_id)
+    }
+
+    /// Buffer the applys of the group, returns the applys back if the group
+    /// is not in pull mode.
+    pub(crate) fn push(&self, group_id: u64, applys: Vec<Apply<W, R>>) -> Option<Vec<Apply<W, R>>> {
+        match self.groups.lock().unwrap().get_mut(&group_id) {
+            None => Some(applys),
+            Some(group) => {
+                group.applys.extend(applys);
+                None
+            }
+        }
+    }
+
+    /// Pull at most `max` applys after the cursor, the applys at or before
+    /// the cursor are skipped as pulled. The membership apply is fenced as
+    /// it's applied by the `StateMachine`, it's pulled alone once the applys
+    /// pulled before it are acknowledged.
+    pub(crate) fn pull(
+        &self,
+        group_id: u64,
+        cursor: u64,
+        max: usize,
+    ) -> Result<Vec<Apply<W, R>>, Error> {
+        let mut groups = self.groups.lock().unwrap();
+        let group = groups
+            .get_mut(&group_id)
+            .ok_or_else(|| not_pull_mode(group_id))?;
+
+        let mut applys = vec![];
+        while let Some(apply) = group.applys.pop_front() {
+            let pending = apply.get_index() > cursor;
+            let membership = matches!(apply, Apply::Membership(_));
+            let in_flight = !group.pulled.is_empty();
+            if pending && (applys.len() == max || (membership && in_flight)) {
+                group.applys.push_front(apply);
+                break;
+            }
+            group
+                .pulled
+                .push_back((apply.get_index(), apply.get_term()));
+            if pending {
+                applys.push(apply);
+                if membership {
+                    break;
+                }
+            }
+        }
+        Ok(applys)
+    }
+
+    /// Acknowledge the applys pulled up to the index applied, the applied
+    /// index of the group is reported to the node.
+    pub(crate) fn acknowledge(&self, group_id: u64, index: u64) -> Result<(), Error> {
+        let mut groups = self.groups.lock().unwrap();
+        let group = groups
+            .get_mut(&group_id)
+            .ok_or_else(|| not_pull_mode(group_id))?;
+        if index <= group.acknowledged {
+            return Ok(());
+        }
+        if group.pulled.back().map_or(true, |(last, _)| *last < index) {
+            return Err(Error::BadParameter(format!(
+                "group {} acknowledge index {} not pulled",
+                group_id, index
+            )));
+        }
+
+        let mut applied = None;
+        while let Some((pulled_index, term)) = group.pulled.front().copied() {
+            if pulled_index > index {
+                break;
+            }
+            group.pulled.pop_front();
+            applied = Some((pulled_index, term));
+        }
+        if let Some((applied_index, applied_term)) = applied {
+            group.acknowledged = applied_index;
+            // the node dropped if the send failed.
+            let _ = self.tx.send(ApplyResultMessage {
+                group_id,
+                applied_index,
+                applied_term,
+            });
+        }
+        Ok(())
+    }
+
+    /// The applys before the snapshot installed are dropped.
+    pub(crate) fn snapshot_installed(&self, group_id: u64, index: u64) {
+        if let Some(group) = self.groups.lock().unwrap().get_mut(&group_id) {
+            group.applys.retain(|apply| apply.get_index() > index);
+            group
+                .pulled
+                .retain(|(pulled_index, _)| *pulled_index > index);
+            group.acknowledged = group.acknowledged.max(index);
+        }
+    }
+
+    pub(crate) fn remove(&self, group_id: u64) {
+        self.groups.lock().unwrap().remove(&group_id);
+    }
+}
+
+fn not_pull_mode(group_id: u64) -> Error {
+    Error::BadParameter(format!("group {} is not applied in pull mode", group_id))
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::PullApplys;
+    use crate::Apply;
+    use crate::ApplyMembership;
+    use crate::ApplyNoOp;
+
+    fn noop(index: u64) -> Apply<(), ()> {
+        Apply::NoOp(ApplyNoOp {
+            group_id: 1,
+            index,
+            term: 2,
+        })
+    }
+
+    #[test]
+    fn test_pull_applys() {
+        let (tx, mut rx) = unbounded_channel();
+        let pulls = PullApplys::<(), ()>::new(tx);
+        assert!(pulls.push(1, vec![noop(1)]).is_some());
+        assert!(pulls.pull(1, 0, 10).is_err());
+
+        pulls.enable(1);
+        assert!(pulls.push(1, (1..=5).map(noop).collect()).is_none());
+        // the applys at or before the cursor are skipped.
+        let applys = pulls.pull(1, 1, 2).unwrap();
+        assert_eq!(
+            applys
+                .iter()
+                .map(|apply| apply.get_index())
+                .collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+        assert!(pulls.acknowledge(1, 4).is_err());
+        pulls.acknowledge(1, 3).unwrap();
+        let res = rx.try_recv().unwrap();
+        assert_eq!((res.applied_index, res.applied_term), (3, 2));
+        // acknowledged again is no-op.
+        pulls.acknowledge(1, 2).unwrap();
+        assert!(rx.try_recv().is_err());
+
+        pulls.snapshot_installed(1, 4);
+        let applys = pulls.pull(1, 3, 10).unwrap();
+        assert_eq!(
+            applys
+                .iter()
+                .map(|apply| apply.get_index())
+                .collect::<Vec<_>>(),
+            vec![5]
+        );
+        pulls.remove(1);
+        assert!(!pulls.is_pull(1));
+    }
+
+    #[test]
+    fn test_pull_membership_fence() {
+        let (tx, _rx) = unbounded_channel();
+        let pulls = PullApplys::<(), ()>::new(tx);
+        pulls.enable(1);
+        let membership = Apply::Membership(ApplyMembership {
+            group_id: 1,
+            index: 3,
+            term: 2,
+            change_data: None,
+            ctx: None,
+            conf_state: Default::default(),
+            tx: None,
+        });
+        assert!(pulls
+            .push(1, vec![noop(1), noop(2), membership, noop(4)])
+            .is_none());
+        let indexes = |applys: Vec<Apply<(), ()>>| {
+            applys
+                .iter()
+                .map(|apply| apply.get_index())
+                .collect::<Vec<_>>()
+        };
+
+        // the membership waits for the applys before it acknowledged.
+        assert_eq!(indexes(pulls.pull(1, 0, 10).unwrap()), vec![1, 2]);
+        assert!(pulls.pull(1, 2, 10).unwrap().is_empty());
+        pulls.acknowledge(1, 2).unwrap();
+        assert_eq!(indexes(pulls.pull(1, 2, 10).unwrap()), vec![3]);
+        pulls.acknowledge(1, 3).unwrap();
+        assert_eq!(indexes(pulls.pull(1, 3, 10).unwrap()), vec![4]);
+    }
+}
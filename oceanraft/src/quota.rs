@@ -0,0 +1,122 @@
+use std::fmt::Debug;
+
+/// The escalation stage of the group whose raft log exceeds
+/// `Config::group_log_quota` bytes, the stages escalate in order if the log is
+/// still over the quota after `Config::log_quota_escalation_ticks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogQuotaStage {
+    /// The log is under the quota.
+    Normal,
+    /// `LogCompactor::build_snapshot` is requested at the applied index.
+    BuildSnapshot,
+    /// The log is compacted at the applied index by `StorageExt::compact`
+    /// along with the compaction of `Config::compaction_policy`, regardless
+    /// of the followers which have not replicated the entries, they catch up
+    /// by the snapshot. The log is compacted again as the entries are applied
+    /// until it's under the quota.
+    Compact,
+    /// The writes to the group are rejected by
+    /// `ProposeError::LogQuotaExceeded` until the log is under the quota.
+    Backpressure,
+}
+
+/// LogCompactor shrinks the raft log of the group which exceeds the quota,
+/// see `Config::group_log_quota`. It is called on the node actor, so the
+/// implementations should not block, e.g. spawn the work.
+pub trait LogCompactor: Debug + Send + Sync + 'static {
+    /// Build the snapshot of the state machine of the group at `applied_index`,
+    /// so the entries before can be truncated.
+    fn build_snapshot(&self, group_id: u64, replica_id: u64, applied_index: u64);
+
+    /// Truncate the entries of the group before `compact_index`.
+    fn compact(&self, group_id: u64, replica_id: u64, compact_index: u64);
+}
+
+/// Does nothing, the groups over the quota escalate to backpressure until the
+/// application compacts the log. It's the default compactor.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoLogCompactor;
+
+impl LogCompactor for NoLogCompactor {
+    fn build_snapshot(&self, _: u64, _: u64, _: u64) {}
+
+    fn compact(&self, _: u64, _: u64, _: u64) {}
+}
+
+/// The escalation of the log quota of a group.
+#[derive(Debug)]
+pub(crate) struct LogQuota {
+    stage: LogQuotaStage,
+    /// The ticks elapsed in the current stage.
+    ticks: usize,
+}
+
+impl Default for LogQuota {
+    fn default() -> Self {
+        Self {
+            stage: LogQuotaStage::Normal,
+            ticks: 0,
+        }
+    }
+}
+
+impl LogQuota {
+    #[inline]
+    pub(crate) fn stage(&self) -> LogQuotaStage {
+        self.stage
+    }
+
+    /// Advance the escalation by the bytes of the log. Returns the new stage
+    /// if the stage changed.
+    pub(crate) fn tick(
+        &mut self,
+        bytes: u64,
+        quota: u64,
+        escalation_ticks: usize,
+    ) -> Option<LogQuotaStage> {
+        if bytes <= quota {
+            if self.stage == LogQuotaStage::Normal {
+                return None;
+            }
+            *self = Self::default();
+            return Some(LogQuotaStage::Normal);
+        }
+
+        self.ticks += 1;
+        let next = match self.stage {
+            LogQuotaStage::Normal => LogQuotaStage::BuildSnapshot,
+            _ if self.ticks < escalation_ticks => return None,
+            LogQuotaStage::BuildSnapshot => LogQuotaStage::Compact,
+            LogQuotaStage::Compact => LogQuotaStage::Backpressure,
+            LogQuotaStage::Backpressure => return None,
+        };
+        self.stage = next;
+        self.ticks = 0;
+        Some(next)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LogQuota;
+    use super::LogQuotaStage;
+
+    #[test]
+    fn test_log_quota_escalation() {
+        let mut quota = LogQuota::default();
+        assert_eq!(quota.tick(10, 10, 2), None);
+        assert_eq!(quota.tick(11, 10, 2), Some(LogQuotaStage::BuildSnapshot));
+        assert_eq!(quota.tick(11, 10, 2), None);
+        assert_eq!(quota.tick(11, 10, 2), Some(LogQuotaStage::Compact));
+        assert_eq!(quota.tick(11, 10, 2), None);
+        assert_eq!(quota.tick(11, 10, 2), Some(LogQuotaStage::Backpressure));
+        assert_eq!(quota.tick(11, 10, 2), None);
+        assert_eq!(quota.tick(11, 10, 2), None);
+        assert_eq!(quota.stage(), LogQuotaStage::Backpressure);
+
+        // back under the quota, the escalation starts over.
+        assert_eq!(quota.tick(5, 10, 2), Some(LogQuotaStage::Normal));
+        assert_eq!(quota.tick(5, 10, 2), None);
+        assert_eq!(quota.tick(11, 10, 2), Some(LogQuotaStage::BuildSnapshot));
+    }
+}
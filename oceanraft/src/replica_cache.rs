@@ -130,6 +130,25 @@ where
         None
     }
 
+    /// Cache the replica learned from the raft messages if it's unknown. The
+    /// messages don't carry whether the replica is a witness, so the known
+    /// replica is not overridden.
+    pub async fn learn_replica_desc(
+        &mut self,
+        group_id: u64,
+        replica_desc: ReplicaDesc,
+        sync: bool,
+    ) -> Result<(), Error> {
+        if self
+            .replica_desc(group_id, replica_desc.replica_id)
+            .await?
+            .is_some()
+        {
+            return Ok(());
+        }
+        self.cache_replica_desc(group_id, replica_desc, sync).await
+    }
+
     /// Cache given replica and `sync` indicates whether syn to storage.
     pub async fn cache_replica_desc(
         &mut self,
@@ -153,7 +172,15 @@ where
                     .await?;
             }
 
-            rds.push(replica_desc);
+            // the replica may be changed, e.g. it's learned from messages
+            // before known as a witness.
+            match rds
+                .iter()
+                .position(|replica| replica.replica_id == replica_desc.replica_id)
+            {
+                Some(index) => rds[index] = replica_desc,
+                None => rds.push(replica_desc),
+            }
             return Ok(());
         }
 
@@ -174,7 +201,10 @@ where
         sync: bool,
     ) -> Result<(), Error> {
         if let Some(rds) = self.cache.get_mut(&group_id) {
-            if let Some(index) = rds.iter().position(|replica| *replica == replica_desc) {
+            if let Some(index) = rds
+                .iter()
+                .position(|replica| replica.replica_id == replica_desc.replica_id)
+            {
                 let _ = rds.remove(index);
             }
 
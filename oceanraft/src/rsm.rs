@@ -6,8 +6,11 @@ use tokio::sync::oneshot;
 use crate::multiraft::ProposeResponse;
 use crate::prelude::ConfState;
 use crate::prelude::MembershipChangeData;
+use crate::prelude::SnapshotMetadata;
 
+use super::admin::AdminPayload;
 use super::error::Error;
+use super::proposer::Proposer;
 use super::GroupState;
 use super::ProposeData;
 
@@ -30,6 +33,9 @@ where
     pub term: u64,
     pub data: REQ,
     pub context: Option<Vec<u8>>,
+    /// The proposer of the entry, it's some if `Config::annotate_proposer`
+    /// is enabled when the entry is proposed.
+    pub proposer: Option<Proposer>,
     pub is_conf_change: bool,
     pub tx: Option<oneshot::Sender<Result<(RES, Option<Vec<u8>>), Error>>>, // TODO: consider the tx and apply data separation.
 }
@@ -46,6 +52,20 @@ pub struct ApplyMembership<RES: ProposeResponse> {
     pub tx: Option<oneshot::Sender<Result<(RES, Option<Vec<u8>>), Error>>>,
 }
 
+/// The admin entry proposed by `MultiRaft::propose_admin`, its payload is not
+/// decoded by the codec of the proposals.
+#[derive(Debug)]
+pub struct ApplyAdmin<RES: ProposeResponse> {
+    pub group_id: u64,
+    pub index: u64,
+    pub term: u64,
+    pub payload: AdminPayload,
+    /// The proposer of the entry, it's some if `Config::annotate_proposer`
+    /// is enabled when the entry is proposed.
+    pub proposer: Option<Proposer>,
+    pub tx: Option<oneshot::Sender<Result<(RES, Option<Vec<u8>>), Error>>>,
+}
+
 #[derive(Debug)]
 pub enum Apply<W, R>
 where
@@ -55,6 +75,7 @@ where
     NoOp(ApplyNoOp),
     Normal(ApplyNormal<W, R>),
     Membership(ApplyMembership<R>),
+    Admin(ApplyAdmin<R>),
 }
 
 impl<W, R> Apply<W, R>
@@ -67,6 +88,7 @@ where
             Self::NoOp(noop) => noop.index,
             Self::Normal(normal) => normal.index,
             Self::Membership(membership) => membership.index,
+            Self::Admin(admin) => admin.index,
         }
     }
 
@@ -76,6 +98,7 @@ where
             Self::NoOp(noop) => noop.term,
             Self::Normal(normal) => normal.term,
             Self::Membership(membership) => membership.term,
+            Self::Admin(admin) => admin.term,
         }
     }
 }
@@ -89,6 +112,13 @@ where
     where
         Self: 'life0;
 
+    /// Apply the entries of the group. The applys of a group are in the order
+    /// of index without gaps and each entry is applied once, the ordering is
+    /// checked at runtime by the `debug-assertions` feature.
+    ///
+    /// The `Apply::Membership` is applied alone after the applys before it
+    /// are applied, so the state machine may apply the other applys of a
+    /// call concurrently without reading a membership in change.
     fn apply<'life0>(
         &'life0 self,
         group_id: u64,
@@ -96,4 +126,21 @@ where
         state: &GroupState,
         applys: Vec<Apply<W, R>>,
     ) -> Self::ApplyFuture<'life0>;
+
+    /// Called when the replica of group is created on the node, before any
+    /// entry of the replica is applied. The `conf_state` is the membership
+    /// in the storage when created. The default implementation does nothing.
+    #[allow(unused_variables)]
+    fn on_group_created(&self, group_id: u64, replica_id: u64, conf_state: &ConfState) {}
+
+    /// Called when the replica of group is removed from the node, no entry of
+    /// the replica is applied after. The default implementation does nothing.
+    #[allow(unused_variables)]
+    fn on_group_removed(&self, group_id: u64, replica_id: u64) {}
+
+    /// Called when the snapshot is installed to the storage of replica, the
+    /// entries after the snapshot are applied after. The default implementation
+    /// does nothing.
+    #[allow(unused_variables)]
+    fn on_snapshot_installed(&self, group_id: u64, replica_id: u64, metadata: &SnapshotMetadata) {}
 }
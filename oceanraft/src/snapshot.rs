@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::io::Read;
+
+use crate::prelude::Message;
+use crate::prelude::SnapshotChecksum;
+use crate::prelude::SnapshotMetadata;
+
+/// The size of the chunks of the snapshot data checksummed.
+const SNAPSHOT_CHECKSUM_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// SnapshotValidator validates the snapshot received from the leader before
+/// it is installed, e.g. checks the format of the data, the snapshot which
+/// fails validation is rejected and the leader is requested to resend.
+pub trait SnapshotValidator: Debug + Send + Sync + 'static {
+    /// Validate the snapshot of the group, `reader` reads the data of the
+    /// snapshot. Returns the reason if the snapshot is rejected.
+    fn validate(
+        &self,
+        group_id: u64,
+        metadata: &SnapshotMetadata,
+        reader: &mut dyn Read,
+    ) -> Result<(), String>;
+}
+
+/// Accepts all snapshots, it's the default validator.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoSnapshotValidator;
+
+impl SnapshotValidator for NoSnapshotValidator {
+    fn validate(&self, _: u64, _: &SnapshotMetadata, _: &mut dyn Read) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Compute the checksums of the chunks of the snapshot data.
+pub(crate) fn snapshot_checksum(data: &[u8]) -> SnapshotChecksum {
+    SnapshotChecksum {
+        chunk_size: SNAPSHOT_CHECKSUM_CHUNK_SIZE as u64,
+        chunk_crc32s: data
+            .chunks(SNAPSHOT_CHECKSUM_CHUNK_SIZE)
+            .map(crc32fast::hash)
+            .collect(),
+    }
+}
+
+/// Verify the snapshot data with the checksums, returns the reason if the
+/// data is corrupt.
+pub(crate) fn verify_snapshot_checksum(
+    checksum: &SnapshotChecksum,
+    data: &[u8],
+) -> Result<(), String> {
+    if checksum.chunk_size == 0 {
+        return Err("invalid checksum chunk size 0".to_owned());
+    }
+
+    let chunks = data.chunks(checksum.chunk_size as usize);
+    if chunks.len() != checksum.chunk_crc32s.len() {
+        return Err(format!(
+            "expected {} chunks, got {}",
+            checksum.chunk_crc32s.len(),
+            chunks.len()
+        ));
+    }
+
+    for (i, (chunk, expected)) in chunks.zip(checksum.chunk_crc32s.iter()).enumerate() {
+        let got = crc32fast::hash(chunk);
+        if got != *expected {
+            return Err(format!(
+                "chunk {} crc32 mismatch, expected {}, got {}",
+                i, expected, got
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The snapshot waiting to be sent by `SnapshotThrottle`.
+pub(crate) struct QueuedSnapshot {
+    pub(crate) group_id: u64,
+    pub(crate) msg: Message,
+    /// True if `Event::SnapshotQueued` is sent for it.
+    pub(crate) reported: bool,
+}
+
+/// SnapshotThrottle limits the snapshots sent by the leaders on the node, see
+/// `Config::max_concurrent_snapshots` and `Config::snapshot_max_bytes_per_sec`.
+/// The snapshots beyond the limits are queued in the order they are built.
+pub(crate) struct SnapshotThrottle {
+    max_concurrent: usize,
+    /// The snapshot in flight over the ticks is released, `0` never expires.
+    timeout_ticks: u64,
+    ticks: u64,
+    /// The bytes refilled per tick, `0` is unlimited.
+    bytes_per_tick: u64,
+    /// The bytes left of the tick, the snapshot is sent while it's positive
+    /// and the overdraft is paid by the following ticks.
+    budget: i64,
+    /// The (group, replica) of the snapshots in flight and the tick sent.
+    sending: HashMap<(u64, u64), u64>,
+    queued: VecDeque<QueuedSnapshot>,
+}
+
+impl SnapshotThrottle {
+    pub(crate) fn new(
+        max_concurrent: usize,
+        bytes_per_sec: u64,
+        tick_interval_ms: u64,
+        timeout_ticks: usize,
+    ) -> Self {
+        let bytes_per_tick = match bytes_per_sec {
+            0 => 0,
+            _ => (bytes_per_sec.saturating_mul(tick_interval_ms) / 1000).max(1),
+        };
+        Self {
+            max_concurrent,
+            timeout_ticks: timeout_ticks as u64,
+            ticks: 0,
+            bytes_per_tick,
+            budget: bytes_per_tick as i64,
+            sending: HashMap::new(),
+            queued: VecDeque::new(),
+        }
+    }
+
+    /// Refill the budget of a tick.
+    pub(crate) fn tick(&mut self) {
+        self.ticks += 1;
+        let bytes = self.bytes_per_tick as i64;
+        self.budget = (self.budget + bytes).min(bytes);
+    }
+
+    /// Release the snapshots in flight over the timeout, returns the
+    /// (group, replica) of them.
+    pub(crate) fn expire(&mut self) -> Vec<(u64, u64)> {
+        if self.timeout_ticks == 0 || self.sending.is_empty() {
+            return vec![];
+        }
+
+        let (ticks, timeout_ticks) = (self.ticks, self.timeout_ticks);
+        let expired = self
+            .sending
+            .iter()
+            .filter(|(_, sent)| ticks.saturating_sub(**sent) >= timeout_ticks)
+            .map(|(key, _)| *key)
+            .collect::<Vec<_>>();
+        for key in expired.iter() {
+            self.sending.remove(key);
+        }
+        expired
+    }
+
+    /// Queue the snapshot of the group, the snapshot queued to the same
+    /// replica before is replaced.
+    pub(crate) fn enqueue(&mut self, group_id: u64, msg: Message) {
+        let to = msg.to;
+        self.queued
+            .retain(|queued| queued.group_id != group_id || queued.msg.to != to);
+        self.queued.push_back(QueuedSnapshot {
+            group_id,
+            msg,
+            reported: false,
+        });
+    }
+
+    /// The (group, replica) of the snapshots in flight.
+    pub(crate) fn sending(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.sending.keys().cloned()
+    }
+
+    /// Pop the snapshot to send if the limits allow, it's counted in flight
+    /// until it's finished or released.
+    pub(crate) fn pop(&mut self) -> Option<QueuedSnapshot> {
+        if self.max_concurrent != 0 && self.sending.len() >= self.max_concurrent {
+            return None;
+        }
+        if self.bytes_per_tick != 0 && self.budget <= 0 {
+            return None;
+        }
+
+        let queued = self.queued.pop_front()?;
+        if self.max_concurrent != 0 {
+            self.sending
+                .insert((queued.group_id, queued.msg.to), self.ticks);
+        }
+        if self.bytes_per_tick != 0 {
+            let bytes = queued
+                .msg
+                .snapshot
+                .as_ref()
+                .map_or(0, |snapshot| snapshot.data.len());
+            self.budget = self.budget.saturating_sub(bytes as i64);
+        }
+        Some(queued)
+    }
+
+    /// Stop counting the snapshot in flight, e.g. it's stale.
+    pub(crate) fn finish(&mut self, group_id: u64, replica_id: u64) {
+        self.sending.remove(&(group_id, replica_id));
+    }
+
+    /// The snapshots waiting to send, which are not reported yet.
+    pub(crate) fn unreported(&mut self) -> impl Iterator<Item = &mut QueuedSnapshot> {
+        self.queued.iter_mut().filter(|queued| !queued.reported)
+    }
+
+    #[inline]
+    pub(crate) fn queued_len(&self) -> usize {
+        self.queued.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::snapshot_checksum;
+    use super::verify_snapshot_checksum;
+    use super::SnapshotThrottle;
+    use super::SNAPSHOT_CHECKSUM_CHUNK_SIZE;
+    use crate::prelude::Message;
+    use crate::prelude::MessageType;
+    use crate::prelude::Snapshot;
+
+    #[test]
+    fn test_snapshot_checksum() {
+        let mut data = vec![7; SNAPSHOT_CHECKSUM_CHUNK_SIZE * 2 + 1];
+        let checksum = snapshot_checksum(&data);
+        assert_eq!(checksum.chunk_crc32s.len(), 3);
+        assert!(verify_snapshot_checksum(&checksum, &data).is_ok());
+
+        data[SNAPSHOT_CHECKSUM_CHUNK_SIZE + 1] = 8;
+        let err = verify_snapshot_checksum(&checksum, &data).unwrap_err();
+        assert!(err.starts_with("chunk 1"), "{}", err);
+
+        data.truncate(SNAPSHOT_CHECKSUM_CHUNK_SIZE);
+        assert!(verify_snapshot_checksum(&checksum, &data).is_err());
+
+        let checksum = snapshot_checksum(&[]);
+        assert!(verify_snapshot_checksum(&checksum, &[]).is_ok());
+    }
+
+    fn new_snapshot_msg(to: u64, size: usize) -> Message {
+        let mut msg = Message::default();
+        msg.set_msg_type(MessageType::MsgSnapshot);
+        msg.to = to;
+        msg.snapshot = Some(Snapshot {
+            data: vec![0; size],
+            ..Default::default()
+        });
+        msg
+    }
+
+    #[test]
+    fn test_snapshot_throttle() {
+        // the unlimited throttle sends all snapshots.
+        let mut throttle = SnapshotThrottle::new(0, 0, 10, 0);
+        for to in 2..=4 {
+            throttle.enqueue(1, new_snapshot_msg(to, 1024));
+        }
+        assert_eq!(std::iter::from_fn(|| throttle.pop()).count(), 3);
+
+        // 1000 bytes per tick.
+        let mut throttle = SnapshotThrottle::new(2, 100 * 1000, 10, 0);
+        throttle.enqueue(1, new_snapshot_msg(2, 10));
+        throttle.enqueue(2, new_snapshot_msg(2, 10));
+        throttle.enqueue(3, new_snapshot_msg(2, 10));
+        // the snapshot to the same replica is replaced.
+        throttle.enqueue(1, new_snapshot_msg(2, 2500));
+        assert_eq!(throttle.unreported().count(), 3);
+        throttle
+            .unreported()
+            .for_each(|queued| queued.reported = true);
+        assert_eq!(throttle.unreported().count(), 0);
+
+        // at most 2 snapshots are in flight.
+        assert_eq!(throttle.pop().map(|queued| queued.group_id), Some(2));
+        assert_eq!(throttle.pop().map(|queued| queued.group_id), Some(3));
+        assert!(throttle.pop().is_none());
+        assert_eq!(throttle.sending().count(), 2);
+        throttle.finish(2, 2);
+        let queued = throttle.pop().unwrap();
+        assert_eq!((queued.group_id, queued.msg.to), (1, 2));
+        assert_eq!(throttle.queued_len(), 0);
+
+        // the overdraft of the large snapshot is paid by the next tick.
+        throttle.finish(1, 2);
+        throttle.finish(3, 2);
+        throttle.enqueue(4, new_snapshot_msg(2, 10));
+        assert!(throttle.pop().is_none());
+        throttle.tick();
+        assert!(throttle.pop().is_none());
+        throttle.tick();
+        assert_eq!(throttle.pop().map(|queued| queued.group_id), Some(4));
+    }
+
+    #[test]
+    fn test_snapshot_throttle_expire() {
+        let mut throttle = SnapshotThrottle::new(1, 0, 10, 3);
+        throttle.enqueue(1, new_snapshot_msg(2, 10));
+        throttle.enqueue(2, new_snapshot_msg(2, 10));
+        assert_eq!(throttle.pop().map(|queued| queued.group_id), Some(1));
+        assert!(throttle.pop().is_none());
+
+        throttle.tick();
+        throttle.tick();
+        assert!(throttle.expire().is_empty());
+        throttle.tick();
+        // the slot of the snapshot never reported is released.
+        assert_eq!(throttle.expire(), vec![(1, 2)]);
+        assert_eq!(throttle.sending().count(), 0);
+        assert_eq!(throttle.pop().map(|queued| queued.group_id), Some(2));
+    }
+}
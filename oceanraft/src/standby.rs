@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::RwLock;
+
+use crate::prelude::ReplicaDesc;
+
+/// The standby replicas designated for the groups on the node, see
+/// `MultiRaft::designate_standby`. The standby replica is not a member of
+/// the group, it tails the committed log of the group out of raft until it's
+/// promoted to voter by `MultiRaft::promote_standby`.
+#[derive(Clone, Default)]
+pub(crate) struct Standbys {
+    replicas: Arc<RwLock<HashMap<u64, Vec<ReplicaDesc>>>>,
+}
+
+impl Standbys {
+    /// Designate the standby replica, returns false if the group already has
+    /// a standby on the node of the replica.
+    pub(crate) fn designate(&self, replica: ReplicaDesc) -> bool {
+        let mut replicas = self.replicas.write().unwrap();
+        let standbys = replicas.entry(replica.group_id).or_default();
+        if standbys
+            .iter()
+            .any(|standby| standby.node_id == replica.node_id)
+        {
+            return false;
+        }
+        standbys.push(replica);
+        true
+    }
+
+    /// The standby replica of the group on the node.
+    pub(crate) fn get(&self, group_id: u64, node_id: u64) -> Option<ReplicaDesc> {
+        self.replicas
+            .read()
+            .unwrap()
+            .get(&group_id)
+            .and_then(|standbys| {
+                standbys
+                    .iter()
+                    .find(|standby| standby.node_id == node_id)
+                    .cloned()
+            })
+    }
+
+    /// The standby replicas of the group.
+    pub(crate) fn list(&self, group_id: u64) -> Vec<ReplicaDesc> {
+        self.replicas
+            .read()
+            .unwrap()
+            .get(&group_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn remove(&self, group_id: u64, node_id: u64) -> Option<ReplicaDesc> {
+        let mut replicas = self.replicas.write().unwrap();
+        let standbys = replicas.get_mut(&group_id)?;
+        let pos = standbys
+            .iter()
+            .position(|standby| standby.node_id == node_id)?;
+        let standby = standbys.remove(pos);
+        if standbys.is_empty() {
+            replicas.remove(&group_id);
+        }
+        Some(standby)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prelude::ReplicaDesc;
+
+    use super::Standbys;
+
+    fn replica(group_id: u64, node_id: u64, replica_id: u64) -> ReplicaDesc {
+        ReplicaDesc {
+            node_id,
+            group_id,
+            replica_id,
+            witness: false,
+        }
+    }
+
+    #[test]
+    fn test_standbys() {
+        let standbys = Standbys::default();
+        assert!(standbys.designate(replica(1, 4, 4)));
+        assert!(!standbys.designate(replica(1, 4, 5)));
+        assert!(standbys.designate(replica(1, 5, 5)));
+        assert!(standbys.designate(replica(2, 4, 4)));
+
+        assert_eq!(standbys.get(1, 4), Some(replica(1, 4, 4)));
+        assert_eq!(standbys.get(1, 6), None);
+        assert_eq!(standbys.list(1), vec![replica(1, 4, 4), replica(1, 5, 5)]);
+
+        assert_eq!(standbys.remove(1, 4), Some(replica(1, 4, 4)));
+        assert_eq!(standbys.remove(1, 4), None);
+        assert_eq!(standbys.list(1), vec![replica(1, 5, 5)]);
+        assert_eq!(standbys.remove(1, 5), Some(replica(1, 5, 5)));
+        assert!(standbys.list(1).is_empty());
+    }
+}
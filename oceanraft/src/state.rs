@@ -1,12 +1,46 @@
 use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicBool;
 use std::sync::atomic::AtomicU64;
 use std::sync::atomic::AtomicUsize;
 use std::sync::atomic::Ordering;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
 
+use raft::prelude::ConfState;
+use raft::ProgressState;
 use raft::StateRole;
 
+use crate::multiraft::ConsistencyLevel;
+use crate::prelude::ReplicaDesc;
+use crate::storage::RaftStorage;
+use crate::storage::Result as StorageResult;
+use crate::transport::EndpointHealth;
+
+/// The max number of recently contacted replicas tracked by a group.
+const MAX_RECENT_CONTACTS: usize = 3;
+
+/// A replica which recently contacted the group as a candidate or leader,
+/// it hints the client where the leader may be when the leader is unknown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LeaderCandidate {
+    pub replica_id: u64,
+    pub node_id: u64,
+    pub term: u64,
+    /// The elapsed time since the last contact.
+    pub last_contact: Duration,
+}
+
+struct Contact {
+    replica_id: u64,
+    node_id: u64,
+    term: u64,
+    at: Instant,
+}
+
 struct WrapStateRole(usize);
 
 impl From<&StateRole> for WrapStateRole {
@@ -35,8 +69,21 @@ pub struct GroupState {
     replica_id: AtomicU64,
     commit_index: AtomicU64,
     commit_term: AtomicU64,
+    /// The term of the replica persisted, it may lag behind the term of the
+    /// raft group but never goes ahead of it.
+    term: AtomicU64,
     leader_id: AtomicU64,
+    leader_node_id: AtomicU64,
     role: AtomicUsize,
+    applied_index: AtomicU64,
+    apply_backlog_bytes: AtomicU64,
+    memory_allotted: AtomicU64,
+    memory_used: AtomicU64,
+    parked: AtomicBool,
+    log_quota_exceeded: AtomicBool,
+    recent_contacts: Mutex<VecDeque<Contact>>,
+    conf_state: RwLock<ConfState>,
+    pending_conf_change: AtomicBool,
 }
 
 impl Default for GroupState {
@@ -51,8 +98,19 @@ impl From<(u64, u64, u64, u64, StateRole)> for GroupState {
             replica_id: AtomicU64::new(value.0),
             commit_index: AtomicU64::new(value.1),
             commit_term: AtomicU64::new(value.2),
+            term: AtomicU64::new(value.2),
             leader_id: AtomicU64::new(value.3),
+            leader_node_id: AtomicU64::new(0),
             role: AtomicUsize::new(WrapStateRole::from(&value.4).0),
+            applied_index: AtomicU64::new(0),
+            apply_backlog_bytes: AtomicU64::new(0),
+            memory_allotted: AtomicU64::new(0),
+            memory_used: AtomicU64::new(0),
+            parked: AtomicBool::new(false),
+            log_quota_exceeded: AtomicBool::new(false),
+            recent_contacts: Mutex::new(VecDeque::new()),
+            conf_state: RwLock::new(ConfState::default()),
+            pending_conf_change: AtomicBool::new(false),
         }
     }
 }
@@ -63,8 +121,19 @@ impl GroupState {
             replica_id: AtomicU64::new(0),
             commit_index: AtomicU64::new(0),
             commit_term: AtomicU64::new(0),
+            term: AtomicU64::new(0),
             leader_id: AtomicU64::new(0),
+            leader_node_id: AtomicU64::new(0),
             role: AtomicUsize::new(0),
+            applied_index: AtomicU64::new(0),
+            apply_backlog_bytes: AtomicU64::new(0),
+            memory_allotted: AtomicU64::new(0),
+            memory_used: AtomicU64::new(0),
+            parked: AtomicBool::new(false),
+            log_quota_exceeded: AtomicBool::new(false),
+            recent_contacts: Mutex::new(VecDeque::new()),
+            conf_state: RwLock::new(ConfState::default()),
+            pending_conf_change: AtomicBool::new(false),
         }
     }
 
@@ -102,6 +171,16 @@ impl GroupState {
         self.commit_term.store(val, Ordering::SeqCst)
     }
 
+    #[inline]
+    pub fn get_term(&self) -> u64 {
+        self.term.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub fn set_term(&self, val: u64) {
+        self.term.store(val, Ordering::SeqCst)
+    }
+
     #[inline]
     #[allow(unused)]
     pub fn get_leader_id(&self) -> u64 {
@@ -113,6 +192,60 @@ impl GroupState {
         self.leader_id.store(val, Ordering::SeqCst)
     }
 
+    /// Get the node id of the leader, `0` if the leader is unknown or the node
+    /// of the leader is not known.
+    #[inline]
+    #[allow(unused)]
+    pub fn get_leader_node_id(&self) -> u64 {
+        self.leader_node_id.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub fn set_leader_node_id(&self, val: u64) {
+        self.leader_node_id.store(val, Ordering::SeqCst)
+    }
+
+    /// Record the replica which sent vote requests, appends or heartbeats to
+    /// the group, only the most recent contacts are kept.
+    pub fn record_contact(&self, replica_id: u64, node_id: u64, term: u64) {
+        let mut contacts = self.recent_contacts.lock().unwrap();
+        contacts.retain(|contact| contact.replica_id != replica_id);
+        if contacts.len() >= MAX_RECENT_CONTACTS {
+            contacts.pop_front();
+        }
+        contacts.push_back(Contact {
+            replica_id,
+            node_id,
+            term,
+            at: Instant::now(),
+        });
+    }
+
+    /// Get the elapsed time since the last contact of the replica, `None` if
+    /// the replica is not contacted recently.
+    pub fn last_contact(&self, replica_id: u64) -> Option<Duration> {
+        let contacts = self.recent_contacts.lock().unwrap();
+        contacts
+            .iter()
+            .find(|contact| contact.replica_id == replica_id)
+            .map(|contact| contact.at.elapsed())
+    }
+
+    /// Get the recently contacted replicas, the most recent first.
+    pub fn recent_candidates(&self) -> Vec<LeaderCandidate> {
+        let contacts = self.recent_contacts.lock().unwrap();
+        contacts
+            .iter()
+            .rev()
+            .map(|contact| LeaderCandidate {
+                replica_id: contact.replica_id,
+                node_id: contact.node_id,
+                term: contact.term,
+                last_contact: contact.at.elapsed(),
+            })
+            .collect()
+    }
+
     #[inline]
     pub fn set_role(&self, role: &StateRole) {
         self.role
@@ -130,6 +263,471 @@ impl GroupState {
     pub fn is_leader(&self) -> bool {
         self.get_role() == StateRole::Leader
     }
+
+    #[inline]
+    pub fn get_applied_index(&self) -> u64 {
+        self.applied_index.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub fn set_applied_index(&self, val: u64) {
+        self.applied_index.store(val, Ordering::SeqCst)
+    }
+
+    /// Get the estimated bytes of the committed entries that are being
+    /// applied by the apply actor.
+    #[inline]
+    pub fn get_apply_backlog_bytes(&self) -> u64 {
+        self.apply_backlog_bytes.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub fn set_apply_backlog_bytes(&self, val: u64) {
+        self.apply_backlog_bytes.store(val, Ordering::SeqCst)
+    }
+
+    /// Get the number of entries that are committed but not yet applied.
+    #[inline]
+    pub fn get_apply_backlog(&self) -> u64 {
+        self.get_commit_index()
+            .saturating_sub(self.get_applied_index())
+    }
+
+    /// Get the bytes of memory budget allotted to the group.
+    #[inline]
+    #[allow(unused)]
+    pub fn get_memory_allotted(&self) -> u64 {
+        self.memory_allotted.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub fn set_memory_allotted(&self, val: u64) {
+        self.memory_allotted.store(val, Ordering::SeqCst)
+    }
+
+    /// Get the bytes of memory budget used by the pending proposals of the group.
+    #[inline]
+    #[allow(unused)]
+    pub fn get_memory_used(&self) -> u64 {
+        self.memory_used.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub fn set_memory_used(&self, val: u64) {
+        self.memory_used.store(val, Ordering::SeqCst)
+    }
+
+    /// Returns true if the raft group of the replica is not materialized on the node,
+    /// it is created when the next message or proposal arrives.
+    #[inline]
+    #[allow(unused)]
+    pub fn is_parked(&self) -> bool {
+        self.parked.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub fn set_parked(&self, val: bool) {
+        self.parked.store(val, Ordering::SeqCst)
+    }
+
+    /// Returns true if the writes to the group are rejected since its raft
+    /// log exceeds `Config::group_log_quota`, see `LogQuotaStage::Backpressure`.
+    #[inline]
+    pub fn is_log_quota_exceeded(&self) -> bool {
+        self.log_quota_exceeded.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub fn set_log_quota_exceeded(&self, val: bool) {
+        self.log_quota_exceeded.store(val, Ordering::SeqCst)
+    }
+
+    /// Get the membership of the group applied on the replica, it's updated
+    /// when the membership change is applied or the snapshot is installed.
+    #[inline]
+    #[allow(unused)]
+    pub fn get_conf_state(&self) -> ConfState {
+        self.conf_state.read().unwrap().clone()
+    }
+
+    #[inline]
+    pub fn set_conf_state(&self, val: ConfState) {
+        *self.conf_state.write().unwrap() = val
+    }
+
+    /// Returns true if the leader has a membership change that is proposed
+    /// but not yet applied, the new membership change can't be proposed until
+    /// it's applied. It's updated at apply time, so it may lag behind the raft
+    /// group, see `MultiRaft::can_submmit_membership_change` for the strict check.
+    #[inline]
+    #[allow(unused)]
+    pub fn has_pending_conf_change(&self) -> bool {
+        self.pending_conf_change.load(Ordering::SeqCst)
+    }
+
+    #[inline]
+    pub fn set_pending_conf_change(&self, val: bool) {
+        self.pending_conf_change.store(val, Ordering::SeqCst)
+    }
+}
+
+/// A lightweight summary of the replica of a group on the node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupSummary {
+    pub group_id: u64,
+    pub replica_id: u64,
+    pub role: StateRole,
+    pub leader_id: u64,
+    pub term: u64,
+    pub commit_index: u64,
+    pub applied_index: u64,
+    /// The raft group is not materialized, the other fields are the
+    /// state when the group was parked.
+    pub parked: bool,
+}
+
+impl GroupSummary {
+    pub(crate) fn from_state(group_id: u64, state: &GroupState) -> Self {
+        Self {
+            group_id,
+            replica_id: state.get_replica_id(),
+            role: state.get_role(),
+            leader_id: state.get_leader_id(),
+            term: state.get_commit_term(),
+            commit_index: state.get_commit_index(),
+            applied_index: state.get_applied_index(),
+            parked: state.is_parked(),
+        }
+    }
+}
+
+/// The progress of a replica tracked by the leader, see `GroupStatus`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProgressStatus {
+    pub replica_id: u64,
+    /// The index of the last entry known to be replicated to the replica.
+    pub matched: u64,
+    /// The index of the next entry to send to the replica.
+    pub next_index: u64,
+    pub state: ProgressState,
+    /// The replica was contacted within the last election timeout.
+    pub recent_active: bool,
+    /// The sending to the replica is paused, e.g. the inflight messages are
+    /// full or the replica is probed.
+    pub paused: bool,
+    pub learner: bool,
+}
+
+/// The status of the replica of a group on the node, see
+/// `MultiRaft::group_status`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroupStatus {
+    pub group_id: u64,
+    pub replica_id: u64,
+    pub role: StateRole,
+    pub leader_id: u64,
+    pub leader_node_id: u64,
+    pub term: u64,
+    pub commit_index: u64,
+    /// The index applied by the state machine.
+    pub applied_index: u64,
+    /// The index of the last entry of the raft log, it's not persisted yet
+    /// if it's greater than the stable index of the storage.
+    pub last_index: u64,
+    /// The number of proposals that are proposed but not yet applied.
+    pub pending_proposals: usize,
+    pub pending_conf_change: bool,
+    /// The progress of the replicas in the order of replica id, it's only
+    /// tracked by the leader.
+    pub progress: Vec<ProgressStatus>,
+    /// The raft group is not materialized, the status is the state when the
+    /// group was parked and nothing is pending.
+    pub parked: bool,
+}
+
+/// A page of the groups on the node ordered by group id.
+#[derive(Debug, Clone, Default)]
+pub struct GroupPage {
+    pub groups: Vec<GroupSummary>,
+    /// The cursor to list the next page, `None` if there are no more groups.
+    pub next_cursor: Option<u64>,
+}
+
+/// The result of `MultiRaft::remove_group`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GroupRemoval {
+    pub group_id: u64,
+    pub replica_id: u64,
+    /// The replica which the leadership is transferred to before the leader
+    /// is removed, `None` if the removed replica is not the leader.
+    pub transferee: Option<u64>,
+    /// False if the new leader is not established before the timeout, the
+    /// leader is removed anyway.
+    pub transferred: bool,
+}
+
+/// The result of `MultiRaft::gc_replica_descs`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReplicaDescGcReport {
+    /// The descriptors of the replicas which are absent from the membership of
+    /// their groups over the grace ticks, they are kept if `dry_run`.
+    pub removed: Vec<ReplicaDesc>,
+    pub dry_run: bool,
+}
+
+/// The replica of the group whose membership in storage does not match its
+/// metadata, see `StorageCheckReport`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConfStateMismatch {
+    pub group_id: u64,
+    pub replica_id: u64,
+    /// The voters and the learners of the conf state in the group storage.
+    pub members: Vec<u64>,
+    /// The descriptor of the replica, `None` if it's missing.
+    pub replica_desc: Option<ReplicaDesc>,
+}
+
+/// The result of `MultiRaft::check_storage`, the metadata of the groups is
+/// cross-checked with the group storages.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StorageCheckReport {
+    /// The `(group_id, replica_id)` of the group storages without metadata,
+    /// e.g. left by a partial delete. They are never restored.
+    pub orphans: Vec<(u64, u64)>,
+    /// The `(group_id, replica_id)` of the live metadata whose group storage
+    /// does not exist. They are not restored, since an empty storage would be
+    /// created in place of the lost one.
+    pub missing: Vec<(u64, u64)>,
+    /// The replicas which are not members of the initialized conf state in
+    /// storage, or whose descriptors are on another node.
+    pub mismatched: Vec<ConfStateMismatch>,
+}
+
+impl StorageCheckReport {
+    #[inline]
+    pub fn is_consistent(&self) -> bool {
+        self.orphans.is_empty() && self.missing.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// The repair of the inconsistency reported by `StorageCheckReport`, see
+/// `MultiRaft::repair_storage`. The repairs are refused for the groups which
+/// are started on the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageRepair {
+    /// Mark the metadata of the group deleted, so the group is neither
+    /// restored nor reported. It's the repair of the missing storages and
+    /// the orphans.
+    ForgetGroup { group_id: u64, replica_id: u64 },
+    /// Write the metadata of the orphan, so the group is restored by the next
+    /// start of the node.
+    AdoptGroup { group_id: u64, replica_id: u64 },
+    /// Write the descriptor of the replica on the node.
+    DescribeReplica { group_id: u64, replica_id: u64 },
+}
+
+/// The peer node observed by the node, see `NodeStatus`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerStatus {
+    pub node_id: u64,
+    /// The number of groups that have replicas on the peer.
+    pub groups: usize,
+    /// The elapsed time since the last message received from the peer, `None`
+    /// if no message has been received since the node started.
+    pub last_contact: Option<Duration>,
+    /// The health of the endpoints of the peer reported by the transport,
+    /// see `Transport::endpoint_health`.
+    pub endpoints: Vec<EndpointHealth>,
+}
+
+/// A node-level summary of the node, see `MultiRaft::node_status`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NodeStatus {
+    pub node_id: u64,
+    /// The number of groups whose replica on the node is leader.
+    pub leaders: usize,
+    /// The number of groups whose replica on the node is follower.
+    pub followers: usize,
+    /// The number of groups whose replica on the node is candidate or
+    /// pre-candidate.
+    pub candidates: usize,
+    /// The number of groups that are parked and not counted in the roles.
+    pub parked: usize,
+    /// The number of proposals that are proposed but not yet applied.
+    pub pending_proposals: usize,
+    /// The number of entries that are committed but not yet applied.
+    pub apply_backlog: u64,
+    /// The estimated bytes of the committed but not yet applied entries.
+    pub apply_backlog_bytes: u64,
+    /// The approximate bytes used by the storage, `None` if the storage
+    /// failed to report it.
+    pub storage_bytes: Option<u64>,
+    /// The peer nodes in the order of node id.
+    pub peers: Vec<PeerStatus>,
+    pub uptime: Duration,
+}
+
+/// The bounds of the raft log of the replica in the storage, see
+/// `MultiRaft::log_bounds`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogBounds {
+    pub group_id: u64,
+    pub replica_id: u64,
+    /// The index of the first entry in the storage.
+    pub first_index: u64,
+    /// The index of the last entry in the storage, the entries that are not
+    /// yet persisted are not included.
+    pub last_index: u64,
+    /// The index of the last entry that was truncated by compaction or
+    /// snapshot, it is `first_index - 1`.
+    pub truncated_index: u64,
+    /// The term of the truncated entry, `None` if the term is no longer
+    /// available in the storage.
+    pub truncated_term: Option<u64>,
+    /// The index of the last installed snapshot.
+    pub snapshot_index: u64,
+}
+
+impl LogBounds {
+    pub(crate) async fn from_storage<S: RaftStorage>(
+        group_id: u64,
+        replica_id: u64,
+        storage: &S,
+    ) -> StorageResult<Self> {
+        let first_index = storage.first_index()?;
+        let last_index = storage.last_index()?;
+        let truncated_index = first_index - 1;
+        let truncated_term = storage.term(truncated_index).ok();
+        let snapshot_index = storage.snapshot_metadata().await?.index;
+        Ok(Self {
+            group_id,
+            replica_id,
+            first_index,
+            last_index,
+            truncated_index,
+            truncated_term,
+            snapshot_index,
+        })
+    }
+}
+
+/// The number of reads on the node by consistency level, see
+/// `MultiRaft::read_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ReadStats {
+    pub linearizable: u64,
+    pub leader_local: u64,
+    /// The `LeaderLocal` reads that fell back to read index since the leader
+    /// did not hold the lease.
+    pub leader_local_fallbacks: u64,
+    pub bounded_staleness: u64,
+    /// The `BoundedStaleness` reads that fell back to read index since the
+    /// staleness bound was exceeded.
+    pub bounded_staleness_fallbacks: u64,
+    pub lease: u64,
+    /// The `Lease` reads that fell back to read index since the leader did
+    /// not hold the leader lease.
+    pub lease_fallbacks: u64,
+    pub follower: u64,
+}
+
+/// The counters of reads shared by the node and `MultiRaft`.
+#[derive(Default)]
+pub(crate) struct ReadMetrics {
+    linearizable: AtomicU64,
+    leader_local: AtomicU64,
+    leader_local_fallbacks: AtomicU64,
+    bounded_staleness: AtomicU64,
+    bounded_staleness_fallbacks: AtomicU64,
+    lease: AtomicU64,
+    lease_fallbacks: AtomicU64,
+    follower: AtomicU64,
+}
+
+impl ReadMetrics {
+    /// Record the read of the level, `fallback` is true if the read is served
+    /// by read index instead of the level.
+    pub(crate) fn record(&self, level: ConsistencyLevel, fallback: bool) {
+        let (count, fallbacks) = match level {
+            ConsistencyLevel::Linearizable => (&self.linearizable, None),
+            ConsistencyLevel::LeaderLocal => {
+                (&self.leader_local, Some(&self.leader_local_fallbacks))
+            }
+            ConsistencyLevel::BoundedStaleness(_) => (
+                &self.bounded_staleness,
+                Some(&self.bounded_staleness_fallbacks),
+            ),
+            ConsistencyLevel::Lease => (&self.lease, Some(&self.lease_fallbacks)),
+            ConsistencyLevel::Follower => (&self.follower, None),
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+        if let (true, Some(fallbacks)) = (fallback, fallbacks) {
+            fallbacks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn stats(&self) -> ReadStats {
+        ReadStats {
+            linearizable: self.linearizable.load(Ordering::Relaxed),
+            leader_local: self.leader_local.load(Ordering::Relaxed),
+            leader_local_fallbacks: self.leader_local_fallbacks.load(Ordering::Relaxed),
+            bounded_staleness: self.bounded_staleness.load(Ordering::Relaxed),
+            bounded_staleness_fallbacks: self.bounded_staleness_fallbacks.load(Ordering::Relaxed),
+            lease: self.lease.load(Ordering::Relaxed),
+            lease_fallbacks: self.lease_fallbacks.load(Ordering::Relaxed),
+            follower: self.follower.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// The reason why a raft message is dropped before stepped into raft.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum StaleMessage {
+    /// The term of message is far below the current term of the group.
+    Term,
+    /// The message is from a replica removed from the group.
+    RemovedReplica,
+    /// The message is the duplicate of a recent message from the peer node.
+    Duplicate,
+}
+
+/// The number of stale raft messages dropped on the node, see
+/// `MultiRaft::stale_message_stats`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StaleMessageStats {
+    /// The messages whose term is lower than the group by more than
+    /// `Config::stale_msg_term_gap`.
+    pub stale_term: u64,
+    pub removed_replica: u64,
+    /// The duplicate appends and snapshots, see `Config::msg_dedup_window`.
+    pub duplicate: u64,
+}
+
+/// The counters of stale messages shared by the node and `MultiRaft`.
+#[derive(Default)]
+pub(crate) struct StaleMessageMetrics {
+    stale_term: AtomicU64,
+    removed_replica: AtomicU64,
+    duplicate: AtomicU64,
+}
+
+impl StaleMessageMetrics {
+    pub(crate) fn record(&self, reason: StaleMessage) {
+        let count = match reason {
+            StaleMessage::Term => &self.stale_term,
+            StaleMessage::RemovedReplica => &self.removed_replica,
+            StaleMessage::Duplicate => &self.duplicate,
+        };
+        count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stats(&self) -> StaleMessageStats {
+        StaleMessageStats {
+            stale_term: self.stale_term.load(Ordering::Relaxed),
+            removed_replica: self.removed_replica.load(Ordering::Relaxed),
+            duplicate: self.duplicate.load(Ordering::Relaxed),
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -0,0 +1,620 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use futures::Future;
+use raft::GetEntriesContext;
+use raft::Result as RaftResult;
+
+use crate::lifecycle::Lifecycle;
+use crate::prelude::ConfState;
+use crate::prelude::Entry;
+use crate::prelude::GroupMetadata;
+use crate::prelude::HardState;
+use crate::prelude::IdempotencyRecord;
+use crate::prelude::RaftState;
+use crate::prelude::ReplicaDesc;
+use crate::prelude::Snapshot;
+
+use super::Error;
+use super::GroupWrite;
+use super::MultiRaftStorage;
+use super::RaftStorage;
+use super::Result;
+use super::Storage;
+use super::StorageExt;
+
+/// Each record is `group_id`, `replica_id`, `term` and `vote` encoded in
+/// little endian, followed by the crc32 of them.
+const RECORD_SIZE: usize = 4 * 8 + 4;
+
+struct VoteAuditLogCore {
+    file: File,
+    /// The last audited `(term, vote)` of `(group_id, replica_id)`.
+    tails: HashMap<(u64, u64), (u64, u64)>,
+}
+
+/// VoteAuditLog is an append-only file which records the term and vote
+/// transitions of the replicas on the node, it is written after the storage
+/// of group persisted the `HardState`, so the recovered `HardState` must not
+/// be older than the tail of the audit log, otherwise the storage lost the
+/// persisted vote, e.g. by a bug of fsync.
+///
+/// The log is synced for each record, it is intended for testing the custom
+/// storage implementations rather than production.
+#[derive(Clone)]
+pub struct VoteAuditLog {
+    core: Arc<Mutex<VoteAuditLogCore>>,
+}
+
+impl VoteAuditLog {
+    /// Open the audit log at `path`, create it if missing. The torn record at
+    /// the end of log, which is written by an interrupted append, is dropped.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|err| Error::Other(Box::new(err)))?;
+        let mut buf = vec![];
+        file.read_to_end(&mut buf)
+            .map_err(|err| Error::Other(Box::new(err)))?;
+
+        let mut tails = HashMap::new();
+        let mut valid_len = 0;
+        for record in buf.chunks_exact(RECORD_SIZE) {
+            let Some((group_id, replica_id, term, vote)) = decode_record(record) else {
+                break;
+            };
+            tails.insert((group_id, replica_id), (term, vote));
+            valid_len += RECORD_SIZE;
+        }
+        if valid_len != buf.len() {
+            file.set_len(valid_len as u64)
+                .map_err(|err| Error::Other(Box::new(err)))?;
+        }
+
+        Ok(Self {
+            core: Arc::new(Mutex::new(VoteAuditLogCore { file, tails })),
+        })
+    }
+
+    /// Returns the last audited `(term, vote)` of the replica.
+    pub fn tail(&self, group_id: u64, replica_id: u64) -> Option<(u64, u64)> {
+        self.core
+            .lock()
+            .unwrap()
+            .tails
+            .get(&(group_id, replica_id))
+            .copied()
+    }
+
+    /// Append the record if the term or vote is changed since the tail.
+    fn record(&self, group_id: u64, replica_id: u64, term: u64, vote: u64) -> Result<()> {
+        let mut core = self.core.lock().unwrap();
+        if core.tails.get(&(group_id, replica_id)) == Some(&(term, vote)) {
+            return Ok(());
+        }
+
+        let record = encode_record(group_id, replica_id, term, vote);
+        core.file
+            .write_all(&record)
+            .and_then(|_| core.file.sync_data())
+            .map_err(|err| Error::Other(Box::new(err)))?;
+        core.tails.insert((group_id, replica_id), (term, vote));
+        Ok(())
+    }
+
+    /// Check the recovered `HardState` of the replica is not older than the
+    /// tail of the audit log, returns `Error::VoteRegression` if the term is
+    /// rolled back or the vote in the same term is lost.
+    pub fn check(&self, group_id: u64, replica_id: u64, hs: &HardState) -> Result<()> {
+        let Some((audit_term, audit_vote)) = self.tail(group_id, replica_id) else {
+            return Ok(());
+        };
+
+        if hs.term < audit_term
+            || (hs.term == audit_term && audit_vote != 0 && hs.vote != audit_vote)
+        {
+            return Err(Error::VoteRegression {
+                group_id,
+                replica_id,
+                term: hs.term,
+                vote: hs.vote,
+                audit_term,
+                audit_vote,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn encode_record(group_id: u64, replica_id: u64, term: u64, vote: u64) -> [u8; RECORD_SIZE] {
+    let mut record = [0; RECORD_SIZE];
+    for (i, val) in [group_id, replica_id, term, vote].into_iter().enumerate() {
+        record[i * 8..(i + 1) * 8].copy_from_slice(&val.to_le_bytes());
+    }
+    let crc = crc32fast::hash(&record[..RECORD_SIZE - 4]);
+    record[RECORD_SIZE - 4..].copy_from_slice(&crc.to_le_bytes());
+    record
+}
+
+fn decode_record(record: &[u8]) -> Option<(u64, u64, u64, u64)> {
+    let crc = u32::from_le_bytes(record[RECORD_SIZE - 4..].try_into().unwrap());
+    if crc32fast::hash(&record[..RECORD_SIZE - 4]) != crc {
+        return None;
+    }
+
+    let val = |i: usize| u64::from_le_bytes(record[i * 8..(i + 1) * 8].try_into().unwrap());
+    Some((val(0), val(1), val(2), val(3)))
+}
+
+/// VoteAuditStorage records the term and vote transitions of the replica to
+/// `VoteAuditLog` after they are persisted by the inner storage, the other
+/// methods are delegated to the inner storage.
+#[derive(Clone)]
+pub struct VoteAuditStorage<S: RaftStorage> {
+    group_id: u64,
+    replica_id: u64,
+    inner: S,
+    log: VoteAuditLog,
+}
+
+impl<S: RaftStorage> VoteAuditStorage<S> {
+    pub fn new(group_id: u64, replica_id: u64, inner: S, log: VoteAuditLog) -> Self {
+        Self {
+            group_id,
+            replica_id,
+            inner,
+            log,
+        }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Check the `HardState` recovered by the inner storage against the audit log.
+    pub fn check(&self) -> Result<()> {
+        let rs = self.inner.initial_state()?;
+        self.log
+            .check(self.group_id, self.replica_id, &rs.hard_state)
+    }
+}
+
+impl<S: RaftStorage> Storage for VoteAuditStorage<S> {
+    fn initial_state(&self) -> RaftResult<RaftState> {
+        self.inner.initial_state()
+    }
+
+    fn entries(
+        &self,
+        low: u64,
+        high: u64,
+        max_size: impl Into<Option<u64>>,
+        context: GetEntriesContext,
+    ) -> RaftResult<Vec<Entry>> {
+        self.inner.entries(low, high, max_size, context)
+    }
+
+    fn term(&self, idx: u64) -> RaftResult<u64> {
+        self.inner.term(idx)
+    }
+
+    fn first_index(&self) -> RaftResult<u64> {
+        self.inner.first_index()
+    }
+
+    fn last_index(&self) -> RaftResult<u64> {
+        self.inner.last_index()
+    }
+
+    fn snapshot(&self, request_index: u64, to: u64) -> RaftResult<Snapshot> {
+        self.inner.snapshot(request_index, to)
+    }
+}
+
+impl<S: RaftStorage> StorageExt for VoteAuditStorage<S> {
+    type AppendFuture<'life0> = S::AppendFuture<'life0>
+    where
+        Self: 'life0;
+    fn append<'life0>(&'life0 self, ents: &'life0 [Entry]) -> Self::AppendFuture<'life0> {
+        self.inner.append(ents)
+    }
+
+    type SetHardStateFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn set_hardstate(&self, hs: HardState) -> Self::SetHardStateFuture<'_> {
+        async move {
+            let (term, vote) = (hs.term, hs.vote);
+            self.inner.set_hardstate(hs).await?;
+
+            let log = self.log.clone();
+            let (group_id, replica_id) = (self.group_id, self.replica_id);
+            tokio::task::spawn_blocking(move || log.record(group_id, replica_id, term, vote))
+                .await
+                .map_err(|err| Error::Other(Box::new(err)))?
+        }
+    }
+
+    type SetConfStateFuture<'life0> = S::SetConfStateFuture<'life0>
+    where
+        Self: 'life0;
+    fn set_confstate(&self, cs: ConfState) -> Self::SetConfStateFuture<'_> {
+        self.inner.set_confstate(cs)
+    }
+
+    type SetHardStateCommitFuture<'life0> = S::SetHardStateCommitFuture<'life0>
+    where
+        Self: 'life0;
+    fn set_hardstate_commit(&self, commit: u64) -> Self::SetHardStateCommitFuture<'_> {
+        self.inner.set_hardstate_commit(commit)
+    }
+
+    type InstallSnapshotFuture<'life0> = S::InstallSnapshotFuture<'life0>
+    where
+        Self: 'life0;
+    fn install_snapshot(&self, snapshot: Snapshot) -> Self::InstallSnapshotFuture<'_> {
+        self.inner.install_snapshot(snapshot)
+    }
+
+    type SnapshotMetadataFuture<'life0> = S::SnapshotMetadataFuture<'life0>
+    where
+        Self: 'life0;
+    fn snapshot_metadata(&self) -> Self::SnapshotMetadataFuture<'_> {
+        self.inner.snapshot_metadata()
+    }
+
+    type GetAppliedFuture<'life0> = S::GetAppliedFuture<'life0>
+    where
+        Self: 'life0;
+    fn get_applied(&self) -> Self::GetAppliedFuture<'_> {
+        self.inner.get_applied()
+    }
+
+    type SetAppliedFuture<'life0> = S::SetAppliedFuture<'life0>
+    where
+        Self: 'life0;
+    fn set_applied(&self, index: u64) -> Self::SetAppliedFuture<'_> {
+        self.inner.set_applied(index)
+    }
+
+    type CompactFuture<'life0> = S::CompactFuture<'life0>
+    where
+        Self: 'life0;
+    fn compact(&self, compact_index: u64) -> Self::CompactFuture<'_> {
+        self.inner.compact(compact_index)
+    }
+}
+
+impl<S: RaftStorage> RaftStorage for VoteAuditStorage<S> {
+    type SnapshotReader = S::SnapshotReader;
+    type SnapshotWriter = S::SnapshotWriter;
+}
+
+/// MultiRaftVoteAuditStorage wraps the group storages of `M` by
+/// `VoteAuditStorage`, the recovered `HardState` of each group storage is
+/// checked against the audit log before it is returned, so a replica which
+/// lost the persisted vote fails to start instead of violating the election
+/// safety.
+#[derive(Clone)]
+pub struct MultiRaftVoteAuditStorage<S: RaftStorage, M: MultiRaftStorage<S>> {
+    inner: M,
+    log: VoteAuditLog,
+    _m: PhantomData<S>,
+}
+
+impl<S: RaftStorage, M: MultiRaftStorage<S>> MultiRaftVoteAuditStorage<S, M> {
+    pub fn new(inner: M, log: VoteAuditLog) -> Self {
+        Self {
+            inner,
+            log,
+            _m: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    fn wrap(&self, group_id: u64, replica_id: u64, inner: S) -> Result<VoteAuditStorage<S>> {
+        let storage = VoteAuditStorage::new(group_id, replica_id, inner, self.log.clone());
+        storage.check()?;
+        Ok(storage)
+    }
+}
+
+impl<S, M> MultiRaftStorage<VoteAuditStorage<S>> for MultiRaftVoteAuditStorage<S, M>
+where
+    S: RaftStorage,
+    M: MultiRaftStorage<S>,
+{
+    type GroupStorageFuture<'life0> = impl Future<Output = Result<VoteAuditStorage<S>>> + 'life0
+        where
+            Self: 'life0;
+    fn group_storage(&self, group_id: u64, replica_id: u64) -> Self::GroupStorageFuture<'_> {
+        async move {
+            let inner = self.inner.group_storage(group_id, replica_id).await?;
+            self.wrap(group_id, replica_id, inner)
+        }
+    }
+
+    type GroupStoragesFuture<'life0> = impl Future<Output = Vec<Result<VoteAuditStorage<S>>>> + 'life0
+        where
+            Self: 'life0;
+    fn group_storages(&self, groups: Vec<(u64, u64)>) -> Self::GroupStoragesFuture<'_> {
+        async move {
+            self.inner
+                .group_storages(groups.clone())
+                .await
+                .into_iter()
+                .zip(groups)
+                .map(|(res, (group_id, replica_id))| {
+                    res.and_then(|inner| self.wrap(group_id, replica_id, inner))
+                })
+                .collect()
+        }
+    }
+
+    type WriteBatchFuture<'life0> = impl Future<Output = Vec<Result<()>>> + 'life0
+        where
+            Self: 'life0;
+    fn write_batch(&self, writes: Vec<GroupWrite>) -> Self::WriteBatchFuture<'_> {
+        async move {
+            let votes = writes
+                .iter()
+                .map(|write| {
+                    write
+                        .hard_state
+                        .as_ref()
+                        .map(|hs| (write.group_id, write.replica_id, hs.term, hs.vote))
+                })
+                .collect::<Vec<_>>();
+            let mut results = self.inner.write_batch(writes).await;
+
+            // the votes persisted are recorded as `VoteAuditStorage::set_hardstate`.
+            for (res, vote) in results.iter_mut().zip(votes) {
+                let (group_id, replica_id, term, vote) = match vote {
+                    Some(vote) if res.is_ok() => vote,
+                    _ => continue,
+                };
+                let log = self.log.clone();
+                *res = tokio::task::spawn_blocking(move || {
+                    log.record(group_id, replica_id, term, vote)
+                })
+                .await
+                .map_err(|err| Error::Other(Box::new(err)))
+                .and_then(|res| res);
+            }
+            results
+        }
+    }
+
+    type ScanGroupMetadataFuture<'life0> = M::ScanGroupMetadataFuture<'life0>
+        where
+            Self: 'life0;
+    fn scan_group_metadata(&self) -> Self::ScanGroupMetadataFuture<'_> {
+        self.inner.scan_group_metadata()
+    }
+
+    type ScanGroupStoragesFuture<'life0> = M::ScanGroupStoragesFuture<'life0>
+        where
+            Self: 'life0;
+    fn scan_group_storages(&self) -> Self::ScanGroupStoragesFuture<'_> {
+        self.inner.scan_group_storages()
+    }
+
+    type GetGroupMetadataFuture<'life0> = M::GetGroupMetadataFuture<'life0>
+        where
+            Self: 'life0;
+    fn get_group_metadata(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+    ) -> Self::GetGroupMetadataFuture<'_> {
+        self.inner.get_group_metadata(group_id, replica_id)
+    }
+
+    type SetGroupMetadataFuture<'life0> = M::SetGroupMetadataFuture<'life0>
+        where
+            Self: 'life0;
+    fn set_group_metadata(&self, meta: GroupMetadata) -> Self::SetGroupMetadataFuture<'_> {
+        self.inner.set_group_metadata(meta)
+    }
+
+    type ReplicaDescFuture<'life0> = M::ReplicaDescFuture<'life0>
+        where
+            Self: 'life0;
+    fn get_replica_desc(&self, group_id: u64, replica_id: u64) -> Self::ReplicaDescFuture<'_> {
+        self.inner.get_replica_desc(group_id, replica_id)
+    }
+
+    type SetReplicaDescFuture<'life0> = M::SetReplicaDescFuture<'life0>
+        where
+            Self: 'life0;
+    fn set_replica_desc(
+        &self,
+        group_id: u64,
+        replica_desc: ReplicaDesc,
+    ) -> Self::SetReplicaDescFuture<'_> {
+        self.inner.set_replica_desc(group_id, replica_desc)
+    }
+
+    type RemoveReplicaDescFuture<'life0> = M::RemoveReplicaDescFuture<'life0>
+        where
+            Self: 'life0;
+    fn remove_replica_desc(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+    ) -> Self::RemoveReplicaDescFuture<'_> {
+        self.inner.remove_replica_desc(group_id, replica_id)
+    }
+
+    type ScanGroupReplicaDescFuture<'life0> = M::ScanGroupReplicaDescFuture<'life0>
+        where
+            Self: 'life0;
+    fn scan_group_replica_desc(&self, group_id: u64) -> Self::ScanGroupReplicaDescFuture<'_> {
+        self.inner.scan_group_replica_desc(group_id)
+    }
+
+    type ReplicaForNodeFuture<'life0> = M::ReplicaForNodeFuture<'life0>
+        where
+            Self: 'life0;
+    fn replica_for_node(&self, group_id: u64, node_id: u64) -> Self::ReplicaForNodeFuture<'_> {
+        self.inner.replica_for_node(group_id, node_id)
+    }
+
+    type NextReplicaIdFuture<'life0> = M::NextReplicaIdFuture<'life0>
+        where
+            Self: 'life0;
+    fn next_replica_id(&self, group_id: u64) -> Self::NextReplicaIdFuture<'_> {
+        self.inner.next_replica_id(group_id)
+    }
+
+    type AdvanceReplicaIdFuture<'life0> = M::AdvanceReplicaIdFuture<'life0>
+        where
+            Self: 'life0;
+    fn advance_replica_id(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+    ) -> Self::AdvanceReplicaIdFuture<'_> {
+        self.inner.advance_replica_id(group_id, replica_id)
+    }
+
+    type ApproximateSizeFuture<'life0> = M::ApproximateSizeFuture<'life0>
+        where
+            Self: 'life0;
+    fn approximate_size(&self) -> Self::ApproximateSizeFuture<'_> {
+        self.inner.approximate_size()
+    }
+
+    type SetIdempotencyRecordFuture<'life0> = M::SetIdempotencyRecordFuture<'life0>
+        where
+            Self: 'life0;
+    fn set_idempotency_record(
+        &self,
+        record: IdempotencyRecord,
+    ) -> Self::SetIdempotencyRecordFuture<'_> {
+        self.inner.set_idempotency_record(record)
+    }
+
+    type RemoveIdempotencyRecordFuture<'life0> = M::RemoveIdempotencyRecordFuture<'life0>
+        where
+            Self: 'life0;
+    fn remove_idempotency_record(&self, key: String) -> Self::RemoveIdempotencyRecordFuture<'_> {
+        self.inner.remove_idempotency_record(key)
+    }
+
+    type ScanIdempotencyRecordsFuture<'life0> = M::ScanIdempotencyRecordsFuture<'life0>
+        where
+            Self: 'life0;
+    fn scan_idempotency_records(&self) -> Self::ScanIdempotencyRecordsFuture<'_> {
+        self.inner.scan_idempotency_records()
+    }
+}
+
+impl<S, M> Lifecycle for MultiRaftVoteAuditStorage<S, M>
+where
+    S: RaftStorage,
+    M: MultiRaftStorage<S> + Lifecycle,
+{
+    type StartFuture<'life0> = M::StartFuture<'life0>
+        where
+            Self: 'life0;
+    fn start(&self) -> Self::StartFuture<'_> {
+        self.inner.start()
+    }
+
+    type FlushFuture<'life0> = M::FlushFuture<'life0>
+        where
+            Self: 'life0;
+    fn flush(&self) -> Self::FlushFuture<'_> {
+        self.inner.flush()
+    }
+
+    type StopFuture<'life0> = M::StopFuture<'life0>
+        where
+            Self: 'life0;
+    fn stop(&self) -> Self::StopFuture<'_> {
+        self.inner.stop()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs::OpenOptions;
+    use std::io::Write;
+
+    use tempdir::TempDir;
+
+    use crate::prelude::HardState;
+    use crate::storage::Error;
+    use crate::storage::MemStorage;
+    use crate::storage::StorageExt;
+
+    use super::VoteAuditLog;
+    use super::VoteAuditStorage;
+
+    fn hard_state(term: u64, vote: u64) -> HardState {
+        HardState {
+            term,
+            vote,
+            commit: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_vote_audit_detect_lost_vote() {
+        let dir = TempDir::new("vote_audit").unwrap();
+        let path = dir.path().join("vote.audit");
+        let log = VoteAuditLog::open(&path).unwrap();
+        let storage = VoteAuditStorage::new(1, 1, MemStorage::new(), log);
+        storage.set_hardstate(hard_state(1, 0)).await.unwrap();
+        storage.set_hardstate(hard_state(2, 3)).await.unwrap();
+        storage.check().unwrap();
+
+        // append a torn record which is dropped when reopen.
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(&[1, 2, 3])
+            .unwrap();
+        let log = VoteAuditLog::open(&path).unwrap();
+        assert_eq!(log.tail(1, 1), Some((2, 3)));
+        assert_eq!(log.tail(1, 2), None);
+
+        // the storage which lost the vote is rejected.
+        let lost = MemStorage::new();
+        lost.set_hardstate(hard_state(2, 0)).await.unwrap();
+        assert_eq!(
+            VoteAuditStorage::new(1, 1, lost, log.clone())
+                .check()
+                .unwrap_err(),
+            Error::VoteRegression {
+                group_id: 1,
+                replica_id: 1,
+                term: 2,
+                vote: 0,
+                audit_term: 2,
+                audit_vote: 3,
+            }
+        );
+
+        let newer = MemStorage::new();
+        newer.set_hardstate(hard_state(3, 0)).await.unwrap();
+        VoteAuditStorage::new(1, 1, newer, log).check().unwrap();
+    }
+}
@@ -1,5 +1,15 @@
 use std::cmp;
 use std::collections::HashMap;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
 use std::sync::RwLock;
 use std::sync::RwLockReadGuard;
@@ -15,17 +25,21 @@ use raft::Result as RaftResult;
 use raft::StorageError;
 use tokio::sync::RwLock as AsyncRwLock;
 
+use crate::lifecycle::Lifecycle;
 use crate::multiraft::NO_LEADER;
 use crate::prelude::ConfState;
 use crate::prelude::Entry;
 use crate::prelude::GroupMetadata;
 use crate::prelude::HardState;
+use crate::prelude::IdempotencyRecord;
 use crate::prelude::RaftState;
 use crate::prelude::ReplicaDesc;
 use crate::prelude::Snapshot;
 use crate::prelude::SnapshotMetadata;
+use crate::utils::compute_entries_size;
 
 use super::Error;
+use super::GroupWrite;
 use super::MultiRaftStorage;
 use super::RaftSnapshotReader;
 use super::RaftSnapshotWriter;
@@ -40,6 +54,74 @@ struct TriggerSlow {
     block: Duration,
 }
 
+/// The location of the data spilled to `SpillFile`.
+#[derive(Clone, Copy)]
+struct SpillRef {
+    offset: u64,
+    len: usize,
+}
+
+/// The temporary file holding the large data of `MemStorage`, the file is
+/// removed when the storage is dropped.
+struct SpillFile {
+    path: PathBuf,
+    file: File,
+    len: u64,
+}
+
+impl SpillFile {
+    fn create() -> io::Result<Self> {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "oceanraft-mem-{}-{}.spill",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok(Self { path, file, len: 0 })
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<SpillRef> {
+        self.file.seek(SeekFrom::Start(self.len))?;
+        self.file.write_all(data)?;
+        let spilled = SpillRef {
+            offset: self.len,
+            len: data.len(),
+        };
+        self.len += data.len() as u64;
+        Ok(spilled)
+    }
+
+    fn read(&mut self, spilled: SpillRef) -> io::Result<Vec<u8>> {
+        let mut data = vec![0; spilled.len];
+        self.file.seek(SeekFrom::Start(spilled.offset))?;
+        self.file.read_exact(&mut data)?;
+        Ok(data)
+    }
+
+    fn reset(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.len = 0;
+        Ok(())
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// The data of the snapshot installed to `MemStorage`.
+enum SnapshotData {
+    Memory(Vec<u8>),
+    Spilled(SpillRef),
+}
+
 /// The Memory Storage Core instance holds the actual state of the storage struct. To access this
 /// value, use the `rl` and `wl` functions on the main MemStorage implementation.
 #[derive(Default)]
@@ -68,6 +150,16 @@ pub struct MemStorageCore {
     trigger_log_read_slow: TriggerSlow,
     // Stores get entries context.
     get_entries_context: Option<GetEntriesContext>,
+    // The data of the entries and the snapshots not smaller than the
+    // threshold is spilled to the temporary file, `None` keeps all of the
+    // data in memory.
+    spill_threshold: Option<usize>,
+    spill_file: Option<SpillFile>,
+    // The spilled data of the entries by index, the entries in `entries`
+    // have the data empty.
+    spilled_entries: HashMap<u64, SpillRef>,
+    // The data of the last snapshot installed.
+    snapshot_data: Option<SnapshotData>,
 }
 
 impl MemStorageCore {
@@ -172,6 +264,10 @@ impl MemStorageCore {
         self.raft_state.hard_state.term = cmp::max(self.raft_state.hard_state.term, meta.term);
         self.raft_state.hard_state.commit = index;
         self.entries.clear();
+        self.spilled_entries.clear();
+        if let Err(err) = self.reclaim_spill() {
+            return Err(RaftError::Store(StorageError::Other(Box::new(err))));
+        }
 
         // Update conf states.
         self.raft_state.conf_state = meta.take_conf_state();
@@ -229,7 +325,10 @@ impl MemStorageCore {
             let offset = compact_index - entry.index;
             self.entries.drain(..offset as usize);
         }
-        Ok(())
+        self.spilled_entries
+            .retain(|index, _| *index >= compact_index);
+        self.reclaim_spill()
+            .map_err(|err| Error::Other(Box::new(err)))
     }
 
     /// Append the new entries to storage.
@@ -272,10 +371,92 @@ impl MemStorageCore {
         // Remove all entries overwritten by `ents`.
         let diff = ents[0].index - self.first_index();
         self.entries.drain(diff as usize..);
-        self.entries.extend_from_slice(ents);
+        self.spilled_entries
+            .retain(|index, _| *index < ents[0].index);
+        self.reclaim_spill()
+            .map_err(|err| Error::Other(Box::new(err)))?;
+        for ent in ents {
+            match self.spill(&ent.data) {
+                Err(err) => return Err(Error::Other(Box::new(err))),
+                Ok(None) => self.entries.push(ent.clone()),
+                Ok(Some(spilled)) => {
+                    self.spilled_entries.insert(ent.index, spilled);
+                    let mut ent = ent.clone();
+                    ent.data = vec![];
+                    self.entries.push(ent);
+                }
+            }
+        }
         Ok(())
     }
 
+    /// Spill the data to the temporary file if it's not smaller than the spill
+    /// threshold, returns `None` if the data is kept in memory.
+    fn spill(&mut self, data: &[u8]) -> io::Result<Option<SpillRef>> {
+        match self.spill_threshold {
+            Some(threshold) if data.len() >= threshold => {}
+            _ => return Ok(None),
+        }
+
+        if self.spill_file.is_none() {
+            self.spill_file = Some(SpillFile::create()?);
+        }
+        self.spill_file.as_mut().unwrap().write(data).map(Some)
+    }
+
+    fn load_spilled(&mut self, spilled: SpillRef) -> io::Result<Vec<u8>> {
+        self.spill_file
+            .as_mut()
+            .expect("the data is spilled without spill file")
+            .read(spilled)
+    }
+
+    /// Truncate the spill file once none of the data is spilled, the space of
+    /// the overwritten and compacted data is reclaimed.
+    fn reclaim_spill(&mut self) -> io::Result<()> {
+        if !self.spilled_entries.is_empty()
+            || matches!(self.snapshot_data, Some(SnapshotData::Spilled(_)))
+        {
+            return Ok(());
+        }
+
+        match self.spill_file.as_mut() {
+            Some(file) if file.len != 0 => file.reset(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Keep the data of the installed snapshot, which is spilled if it's not
+    /// smaller than the spill threshold.
+    fn set_snapshot_data(&mut self, data: Vec<u8>) -> Result<()> {
+        // the data of the previous snapshot is not referenced anymore.
+        self.snapshot_data = None;
+        self.reclaim_spill()
+            .map_err(|err| Error::Other(Box::new(err)))?;
+        self.snapshot_data = match self.spill(&data) {
+            Err(err) => return Err(Error::Other(Box::new(err))),
+            Ok(None) => Some(SnapshotData::Memory(data)),
+            Ok(Some(spilled)) => Some(SnapshotData::Spilled(spilled)),
+        };
+        Ok(())
+    }
+
+    fn snapshot_data(&mut self) -> Result<Vec<u8>> {
+        match self.snapshot_data {
+            None => Err(Error::SnapshotUnavailable),
+            Some(SnapshotData::Memory(ref data)) => Ok(data.clone()),
+            Some(SnapshotData::Spilled(spilled)) => self
+                .load_spilled(spilled)
+                .map_err(|err| Error::Other(Box::new(err))),
+        }
+    }
+
+    /// The size of the entries including the data spilled.
+    fn approximate_size(&self) -> u64 {
+        let spilled: usize = self.spilled_entries.values().map(|s| s.len).sum();
+        (compute_entries_size(&self.entries) + spilled) as u64
+    }
+
     /// Commit to `idx` and set configuration to the given states. Only used for tests.
     pub fn commit_to_and_set_conf_states(&mut self, idx: u64, cs: Option<ConfState>) -> Result<()> {
         self.commit_to(idx)?;
@@ -299,6 +480,11 @@ impl MemStorageCore {
         self.trigger_log_temp_unavailable = enable;
     }
 
+    /// Set a LogUnavailable error, the storage is unavailable for a long time.
+    pub fn trigger_log_permanently_unavailable(&mut self, enable: bool) {
+        self.trigger_log_unavailable = enable;
+    }
+
     /// Enable log to write slowly.
     pub fn enable_log_write_slow(&mut self, block: Duration) {
         self.trigger_log_write_slow.enable = true;
@@ -334,6 +520,9 @@ impl MemStorageCore {
 /// logs and then access them with `Storage` APIs. The only exception is `Storage::snapshot`. There
 /// is no data in `Snapshot` returned by `MemStorage::snapshot` because applied data is not stored
 /// in `MemStorage`.
+///
+/// The large data of the entries and the installed snapshots can be spilled to a temporary file,
+/// see `MemStorage::new_with_spill_threshold`.
 #[derive(Clone, Default)]
 pub struct MemStorage {
     core: Arc<RwLock<MemStorageCore>>,
@@ -347,6 +536,15 @@ impl MemStorage {
         }
     }
 
+    /// Returns a new memory storage which spills the data of the entries and
+    /// the snapshots not smaller than `threshold` bytes to a temporary file,
+    /// so the storage is usable for the large state.
+    pub fn new_with_spill_threshold(threshold: usize) -> MemStorage {
+        let store = MemStorage::new();
+        store.wl().spill_threshold = Some(threshold);
+        store
+    }
+
     /// Create a new `MemStorage` with a given `Config`. The given `Config` will be used to
     /// initialize the storage.
     ///
@@ -389,6 +587,16 @@ impl MemStorage {
     pub fn wl(&self) -> RwLockWriteGuard<'_, MemStorageCore> {
         self.core.write().unwrap()
     }
+
+    /// Persist the write of `MultiRaftMemoryStorage::write_batch`.
+    fn write(&self, write: GroupWrite) -> Result<()> {
+        let mut core = self.wl();
+        core.append(&write.entries)?;
+        if let Some(hs) = write.hard_state {
+            core.set_hardstate(hs)?;
+        }
+        Ok(())
+    }
 }
 
 impl Storage for MemStorage {
@@ -442,6 +650,13 @@ impl Storage for MemStorage {
         let lo = (low - offset) as usize;
         let hi = (high - offset) as usize;
         let mut ents = core.entries[lo..hi].to_vec();
+        for ent in ents.iter_mut() {
+            if let Some(spilled) = core.spilled_entries.get(&ent.index).copied() {
+                ent.data = core
+                    .load_spilled(spilled)
+                    .map_err(|err| RaftError::Store(StorageError::Other(Box::new(err))))?;
+            }
+        }
         raft::util::limit_size(&mut ents, max_size);
         Ok(ents)
     }
@@ -499,38 +714,84 @@ impl Storage for MemStorage {
 }
 
 impl StorageExt for MemStorage {
-    fn append(&self, ents: &[Entry]) -> Result<()> {
-        self.wl().append(ents).map_err(|err| err.into())
+    type AppendFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn append<'life0>(&'life0 self, ents: &'life0 [Entry]) -> Self::AppendFuture<'life0> {
+        async move { self.wl().append(ents).map_err(|err| err.into()) }
     }
 
-    fn install_snapshot(&self, snapshot: Snapshot) -> Result<()> {
-        self.wl().apply_snapshot(snapshot).map_err(|err| err.into())
+    type InstallSnapshotFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn install_snapshot(&self, snapshot: Snapshot) -> Self::InstallSnapshotFuture<'_> {
+        async move { self.wl().apply_snapshot(snapshot).map_err(|err| err.into()) }
     }
 
-    fn set_hardstate(&self, hs: HardState) -> Result<()> {
-        self.wl().set_hardstate(hs)
+    type SnapshotMetadataFuture<'life0> = impl Future<Output = Result<SnapshotMetadata>> + 'life0
+    where
+        Self: 'life0;
+    fn snapshot_metadata(&self) -> Self::SnapshotMetadataFuture<'_> {
+        async move { Ok(self.rl().snapshot_metadata.clone()) }
     }
 
-    fn set_confstate(&self, cs: ConfState) -> Result<()> {
-        self.wl().set_conf_state(cs)
+    type SetHardStateFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn set_hardstate(&self, hs: HardState) -> Self::SetHardStateFuture<'_> {
+        async move { self.wl().set_hardstate(hs) }
     }
 
-    fn set_hardstate_commit(&self, commit: u64) -> Result<()> {
-        self.wl().set_commit(commit);
-        Ok(())
+    type SetConfStateFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn set_confstate(&self, cs: ConfState) -> Self::SetConfStateFuture<'_> {
+        async move { self.wl().set_conf_state(cs) }
     }
 
-    fn get_applied(&self) -> Result<u64> {
-        Ok(self.rl().applied_index)
+    type SetHardStateCommitFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn set_hardstate_commit(&self, commit: u64) -> Self::SetHardStateCommitFuture<'_> {
+        async move {
+            self.wl().set_commit(commit);
+            Ok(())
+        }
     }
 
-    fn set_applied(&self, index: u64) -> Result<()> {
-        self.wl().applied_index = index;
-        Ok(())
+    type GetAppliedFuture<'life0> = impl Future<Output = Result<u64>> + 'life0
+    where
+        Self: 'life0;
+    fn get_applied(&self) -> Self::GetAppliedFuture<'_> {
+        async move { Ok(self.rl().applied_index) }
+    }
+
+    type SetAppliedFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn set_applied(&self, index: u64) -> Self::SetAppliedFuture<'_> {
+        async move {
+            self.wl().applied_index = index;
+            Ok(())
+        }
+    }
+
+    type CompactFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn compact(&self, compact_index: u64) -> Self::CompactFuture<'_> {
+        async move {
+            let mut core = self.wl();
+            let compact_index = cmp::min(compact_index, core.last_index());
+            core.compact(compact_index)
+        }
     }
 }
 
 impl RaftSnapshotWriter for MemStorage {
+    type BuildSnapshotFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
     fn build_snapshot(
         &self,
         group_id: u64,
@@ -538,12 +799,20 @@ impl RaftSnapshotWriter for MemStorage {
         applied_index: u64,
         applied_term: u64,
         last_conf_state: ConfState,
-    ) -> Result<()> {
-        unimplemented!()
+    ) -> Self::BuildSnapshotFuture<'_> {
+        async move { unimplemented!() }
     }
 
-    fn install_snapshot(&self, group_id: u64, replica_id: u64, data: Vec<u8>) -> Result<()> {
-        unimplemented!()
+    type InstallSnapshotDataFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn install_snapshot(
+        &self,
+        _group_id: u64,
+        _replica_id: u64,
+        data: Vec<u8>,
+    ) -> Self::InstallSnapshotDataFuture<'_> {
+        async move { self.wl().set_snapshot_data(data) }
     }
 
     // fn save_snapshot(&self, _group_id: u64, _replica_id: u64, snapshot: Snapshot) -> Result<()> {
@@ -555,8 +824,8 @@ impl RaftSnapshotWriter for MemStorage {
 }
 
 impl RaftSnapshotReader for MemStorage {
-    fn load_snapshot(&self, group_id: u64, replica_id: u64) -> Result<Vec<u8>> {
-        unimplemented!()
+    fn load_snapshot(&self, _group_id: u64, _replica_id: u64) -> Result<Vec<u8>> {
+        self.wl().snapshot_data()
     }
 }
 
@@ -573,6 +842,9 @@ pub struct MultiRaftMemoryStorage {
     group_storages: Arc<AsyncRwLock<HashMap<u64, MemStorage>>>,
     group_metadatas: Arc<AsyncRwLock<HashMap<u64, GroupMetadata>>>,
     replicas: Arc<AsyncRwLock<HashMap<u64, Vec<ReplicaDesc>>>>,
+    replica_id_counters: Arc<AsyncRwLock<HashMap<u64, u64>>>,
+    idempotency_records: Arc<AsyncRwLock<HashMap<String, IdempotencyRecord>>>,
+    spill_threshold: Option<usize>,
 }
 
 impl MultiRaftMemoryStorage {
@@ -583,6 +855,19 @@ impl MultiRaftMemoryStorage {
             group_storages: Default::default(),
             group_metadatas: Default::default(),
             replicas: Default::default(),
+            replica_id_counters: Default::default(),
+            idempotency_records: Default::default(),
+            spill_threshold: None,
+        }
+    }
+
+    /// The group storages spill the data of the entries and the snapshots not
+    /// smaller than `threshold` bytes to the temporary files, see
+    /// `MemStorage::new_with_spill_threshold`.
+    pub fn new_with_spill_threshold(node_id: u64, threshold: usize) -> Self {
+        Self {
+            spill_threshold: Some(threshold),
+            ..Self::new(node_id)
         }
     }
 
@@ -609,7 +894,10 @@ impl MultiRaftStorage<MemStorage> for MultiRaftMemoryStorage {
             let mut wl = self.group_storages.write().await;
             match wl.get_mut(&group_id) {
                 None => {
-                    let storage = MemStorage::new();
+                    let storage = match self.spill_threshold {
+                        Some(threshold) => MemStorage::new_with_spill_threshold(threshold),
+                        None => MemStorage::new(),
+                    };
                     wl.insert(group_id, storage.clone());
                     let mut group_metadatas = self.group_metadatas.write().await;
                     let group_metadata = GroupMetadata {
@@ -622,6 +910,9 @@ impl MultiRaftStorage<MemStorage> for MultiRaftMemoryStorage {
                             .expect("Time went backwards")
                             .as_secs(),
                         deleted: false,
+                        storage_domain: String::new(),
+                        archive: false,
+                        gate_policy: 0,
                     };
                     group_metadatas.insert(group_id, group_metadata);
                     Ok(storage)
@@ -631,6 +922,36 @@ impl MultiRaftStorage<MemStorage> for MultiRaftMemoryStorage {
         }
     }
 
+    type GroupStoragesFuture<'life0> = impl Future<Output = Vec<Result<MemStorage>>> + 'life0
+        where
+            Self: 'life0;
+    fn group_storages(&self, groups: Vec<(u64, u64)>) -> Self::GroupStoragesFuture<'_> {
+        async move {
+            let mut storages = Vec::with_capacity(groups.len());
+            for (group_id, replica_id) in groups {
+                storages.push(self.group_storage(group_id, replica_id).await);
+            }
+            storages
+        }
+    }
+
+    type WriteBatchFuture<'life0> = impl Future<Output = Vec<Result<()>>> + 'life0
+        where
+            Self: 'life0;
+    fn write_batch(&self, writes: Vec<GroupWrite>) -> Self::WriteBatchFuture<'_> {
+        async move {
+            let mut results = Vec::with_capacity(writes.len());
+            for write in writes {
+                let res = match self.group_storage(write.group_id, write.replica_id).await {
+                    Err(err) => Err(err),
+                    Ok(gs) => gs.write(write),
+                };
+                results.push(res);
+            }
+            results
+        }
+    }
+
     type ScanGroupMetadataFuture<'life0> = impl Future<Output = Result<Vec<GroupMetadata>>> + 'life0
         where
             Self: 'life0;
@@ -641,6 +962,27 @@ impl MultiRaftStorage<MemStorage> for MultiRaftMemoryStorage {
         }
     }
 
+    type ScanGroupStoragesFuture<'life0> = impl Future<Output = Result<Vec<(u64, u64)>>> + 'life0
+        where
+            Self: 'life0;
+    fn scan_group_storages(&self) -> Self::ScanGroupStoragesFuture<'_> {
+        async move {
+            // the storages are keyed by group, the replica is known by the
+            // metadata of the group.
+            let storages = self.group_storages.read().await;
+            let group_metadatas = self.group_metadatas.read().await;
+            Ok(storages
+                .keys()
+                .map(|group_id| {
+                    let replica_id = group_metadatas
+                        .get(group_id)
+                        .map_or(0, |meta| meta.replica_id);
+                    (*group_id, replica_id)
+                })
+                .collect())
+        }
+    }
+
     type GetGroupMetadataFuture<'life0> = impl Future<Output = Result<Option<GroupMetadata>>> + 'life0
         where
             Self: 'life0;
@@ -797,6 +1139,123 @@ impl MultiRaftStorage<MemStorage> for MultiRaftMemoryStorage {
             };
         }
     }
+
+    type NextReplicaIdFuture<'life0> = impl Future<Output = Result<u64>> + 'life0
+    where
+        Self: 'life0;
+
+    fn next_replica_id(&self, group_id: u64) -> Self::NextReplicaIdFuture<'_> {
+        async move {
+            let trigger_storage_temp_unavailable =
+                self.trigger_storage_temp_unavailable.read().await;
+            if *trigger_storage_temp_unavailable {
+                return Err(Error::StorageTemporarilyUnavailable);
+            }
+
+            // the allocated id must be greater than the known replicas, because
+            // the replicas maybe learned from messages instead of allocator.
+            let max_known = {
+                let rl = self.replicas.read().await;
+                rl.get(&group_id).map_or(0, |replicas| {
+                    replicas.iter().map(|r| r.replica_id).max().unwrap_or(0)
+                })
+            };
+
+            let mut wl = self.replica_id_counters.write().await;
+            let counter = wl.entry(group_id).or_insert(0);
+            *counter = cmp::max(*counter, max_known) + 1;
+            Ok(*counter)
+        }
+    }
+
+    type AdvanceReplicaIdFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+
+    fn advance_replica_id(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+    ) -> Self::AdvanceReplicaIdFuture<'_> {
+        async move {
+            let mut wl = self.replica_id_counters.write().await;
+            let counter = wl.entry(group_id).or_insert(0);
+            *counter = cmp::max(*counter, replica_id);
+            Ok(())
+        }
+    }
+
+    type ApproximateSizeFuture<'life0> = impl Future<Output = Result<u64>> + 'life0
+    where
+        Self: 'life0;
+    fn approximate_size(&self) -> Self::ApproximateSizeFuture<'_> {
+        async move {
+            let rl = self.group_storages.read().await;
+            Ok(rl
+                .values()
+                .map(|storage| storage.rl().approximate_size())
+                .sum())
+        }
+    }
+
+    type SetIdempotencyRecordFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn set_idempotency_record(
+        &self,
+        record: IdempotencyRecord,
+    ) -> Self::SetIdempotencyRecordFuture<'_> {
+        async move {
+            let mut wl = self.idempotency_records.write().await;
+            wl.insert(record.key.clone(), record);
+            Ok(())
+        }
+    }
+
+    type RemoveIdempotencyRecordFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn remove_idempotency_record(&self, key: String) -> Self::RemoveIdempotencyRecordFuture<'_> {
+        async move {
+            let mut wl = self.idempotency_records.write().await;
+            wl.remove(&key);
+            Ok(())
+        }
+    }
+
+    type ScanIdempotencyRecordsFuture<'life0> = impl Future<Output = Result<Vec<IdempotencyRecord>>> + 'life0
+    where
+        Self: 'life0;
+    fn scan_idempotency_records(&self) -> Self::ScanIdempotencyRecordsFuture<'_> {
+        async move {
+            let rl = self.idempotency_records.read().await;
+            Ok(rl.values().cloned().collect())
+        }
+    }
+}
+
+/// The memory storage has nothing to flush or release.
+impl Lifecycle for MultiRaftMemoryStorage {
+    type StartFuture<'life0> = impl Future<Output = std::result::Result<(), crate::Error>> + 'life0
+    where
+        Self: 'life0;
+    fn start(&self) -> Self::StartFuture<'_> {
+        async move { Ok(()) }
+    }
+
+    type FlushFuture<'life0> = impl Future<Output = std::result::Result<(), crate::Error>> + 'life0
+    where
+        Self: 'life0;
+    fn flush(&self) -> Self::FlushFuture<'_> {
+        async move { Ok(()) }
+    }
+
+    type StopFuture<'life0> = impl Future<Output = std::result::Result<(), crate::Error>> + 'life0
+    where
+        Self: 'life0;
+    fn stop(&self) -> Self::StopFuture<'_> {
+        async move { Ok(()) }
+    }
 }
 
 #[cfg(test)]
@@ -812,8 +1271,14 @@ mod test {
     use crate::prelude::Entry;
     use crate::prelude::Snapshot;
 
+    use crate::prelude::ReplicaDesc;
+    use crate::storage::MultiRaftStorage;
+    use crate::storage::RaftSnapshotReader;
+    use crate::storage::RaftSnapshotWriter;
+
     use super::GetEntriesContext;
     use super::MemStorage;
+    use super::MultiRaftMemoryStorage;
 
     fn new_entry(index: u64, term: u64) -> Entry {
         let mut e = Entry::default();
@@ -1095,4 +1560,84 @@ mod test {
         let snap = new_snapshot(3, 3, nodes);
         storage.wl().apply_snapshot(snap).unwrap_err();
     }
+
+    #[tokio::test]
+    async fn test_storage_spill() {
+        let new_entry_with_data = |index: u64, data: &[u8]| {
+            let mut e = new_entry(index, 1);
+            e.data = data.to_vec();
+            e
+        };
+        let storage = MemStorage::new_with_spill_threshold(8);
+        let ents = vec![
+            new_entry_with_data(1, b"small"),
+            new_entry_with_data(2, b"spilled data 2"),
+            new_entry_with_data(3, b"spilled data 3"),
+        ];
+        storage.wl().append(&ents).unwrap();
+        assert_eq!(storage.rl().spilled_entries.len(), 2);
+        assert!(storage.rl().entries[1].data.is_empty());
+        let res = storage.entries(1, 4, None, GetEntriesContext::empty(false));
+        assert_eq!(res.unwrap(), ents);
+
+        // the spilled entries overwritten are reloaded from the new data.
+        let ent = new_entry_with_data(3, b"spilled data 3'");
+        storage.wl().append(&[ent.clone()]).unwrap();
+        let res = storage.entries(3, 4, None, GetEntriesContext::empty(false));
+        assert_eq!(res.unwrap(), vec![ent]);
+
+        // the spill file is truncated once none of the data is spilled.
+        storage.wl().compact(4).unwrap();
+        assert!(storage.rl().spilled_entries.is_empty());
+        assert_eq!(storage.rl().spill_file.as_ref().unwrap().len, 0);
+
+        let snapshot_data = b"spilled snapshot".to_vec();
+        let res = RaftSnapshotWriter::install_snapshot(&storage, 1, 1, snapshot_data.clone());
+        res.await.unwrap();
+        assert_eq!(storage.load_snapshot(1, 1).unwrap(), snapshot_data);
+        assert_ne!(storage.rl().spill_file.as_ref().unwrap().len, 0);
+        let res = RaftSnapshotWriter::install_snapshot(&storage, 1, 1, b"small".to_vec());
+        res.await.unwrap();
+        assert_eq!(storage.load_snapshot(1, 1).unwrap(), b"small".to_vec());
+        assert_eq!(storage.rl().spill_file.as_ref().unwrap().len, 0);
+
+        // the spill file is removed with the storage.
+        let path = storage.rl().spill_file.as_ref().unwrap().path.clone();
+        assert!(path.exists());
+        drop(storage);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_next_replica_id() {
+        let storage = MultiRaftMemoryStorage::new(1);
+        assert_eq!(storage.next_replica_id(1).await.unwrap(), 1);
+        assert_eq!(storage.next_replica_id(1).await.unwrap(), 2);
+        // other groups have their own counter
+        assert_eq!(storage.next_replica_id(2).await.unwrap(), 1);
+
+        // the allocated id must be greater than known replicas.
+        storage
+            .set_replica_desc(
+                1,
+                ReplicaDesc {
+                    node_id: 1,
+                    group_id: 1,
+                    replica_id: 5,
+                    witness: false,
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(storage.next_replica_id(1).await.unwrap(), 6);
+
+        // removed replica ids are never reused.
+        storage.remove_replica_desc(1, 5).await.unwrap();
+        assert_eq!(storage.next_replica_id(1).await.unwrap(), 7);
+
+        // the ids allocated by other nodes are skipped once observed.
+        storage.advance_replica_id(1, 10).await.unwrap();
+        storage.advance_replica_id(1, 9).await.unwrap();
+        assert_eq!(storage.next_replica_id(1).await.unwrap(), 11);
+    }
 }
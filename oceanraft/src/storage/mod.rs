@@ -1,4 +1,5 @@
 use futures::Future;
+use prost::Message;
 use raft::Error as RaftError;
 use raft::StorageError as RaftStorageError;
 use raft::StorageError;
@@ -7,8 +8,12 @@ use crate::prelude::ConfState;
 use crate::prelude::Entry;
 use crate::prelude::GroupMetadata;
 use crate::prelude::HardState;
+use crate::prelude::IdempotencyRecord;
 use crate::prelude::ReplicaDesc;
 use crate::prelude::Snapshot;
+use crate::prelude::SnapshotAppMetadata;
+use crate::prelude::SnapshotMetadata;
+use crate::prelude::SnapshotPayload;
 
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
@@ -44,6 +49,37 @@ pub enum Error {
     #[error("snapshot is temporarily unavailable")]
     SnapshotTemporarilyUnavailable,
 
+    /// The on-disk format version of the group storage is not supported, e.g.
+    /// it's written by a newer version or no migration is registered for it.
+    #[error("incompatible storage format version {found}, supported version is {supported}")]
+    IncompatibleStorageVersion { found: u32, supported: u32 },
+
+    /// The snapshot is rejected by the application before it is installed,
+    /// e.g. it was built by a newer schema version.
+    #[error("incompatible snapshot: {0}")]
+    IncompatibleSnapshot(String),
+
+    /// The recovered `HardState` of the replica is older than the tail of the
+    /// vote audit log, the storage lost the persisted term or vote.
+    #[error("replica {replica_id} of group {group_id} recovered term {term} vote {vote}, older than audited term {audit_term} vote {audit_vote}")]
+    VoteRegression {
+        group_id: u64,
+        replica_id: u64,
+        term: u64,
+        vote: u64,
+        audit_term: u64,
+        audit_vote: u64,
+    },
+
+    /// The log of the replica is torn by a crash and can't be repaired without
+    /// losing the committed entries, see `RockStore`.
+    #[error("replica {replica_id} of group {group_id} has an unrepairable log: {reason}")]
+    UnrepairableLog {
+        group_id: u64,
+        replica_id: u64,
+        reason: String,
+    },
+
     /// Some other error occurred.
     #[error("unknown error {0}")]
     Other(#[from] Box<dyn std::error::Error + Sync + Send>),
@@ -65,10 +101,80 @@ impl PartialEq for Error {
                     Error::SnapshotTemporarilyUnavailable,
                     Error::SnapshotTemporarilyUnavailable,
                 )
+        ) || matches!(
+            (self, other),
+            (
+                Error::IncompatibleStorageVersion { found: f1, supported: s1 },
+                Error::IncompatibleStorageVersion { found: f2, supported: s2 },
+            ) if f1 == f2 && s1 == s2
+        ) || matches!(
+            (self, other),
+            (Error::IncompatibleSnapshot(r1), Error::IncompatibleSnapshot(r2)) if r1 == r2
+        ) || matches!(
+            (self, other),
+            (
+                Error::VoteRegression { group_id: g1, replica_id: r1, term: t1, vote: v1, audit_term: at1, audit_vote: av1 },
+                Error::VoteRegression { group_id: g2, replica_id: r2, term: t2, vote: v2, audit_term: at2, audit_vote: av2 },
+            ) if (g1, r1, t1, v1, at1, av1) == (g2, r2, t2, v2, at2, av2)
+        ) || matches!(
+            (self, other),
+            (
+                Error::UnrepairableLog { group_id: g1, replica_id: r1, reason: s1 },
+                Error::UnrepairableLog { group_id: g2, replica_id: r2, reason: s2 },
+            ) if (g1, r1, s1) == (g2, r2, s2)
         )
     }
 }
 
+impl Error {
+    /// Duplicate the error to fail each of the requests affected by it, the
+    /// source of `Error::Other` is kept as the message.
+    pub(crate) fn duplicate(&self) -> Self {
+        match self {
+            Error::StorageUnavailable => Error::StorageUnavailable,
+            Error::StorageTemporarilyUnavailable => Error::StorageTemporarilyUnavailable,
+            Error::LogCompacted => Error::LogCompacted,
+            Error::LogUnavailable => Error::LogUnavailable,
+            Error::LogTemporarilyUnavailable => Error::LogTemporarilyUnavailable,
+            Error::SnapshotOutOfDate => Error::SnapshotOutOfDate,
+            Error::SnapshotUnavailable => Error::SnapshotUnavailable,
+            Error::SnapshotTemporarilyUnavailable => Error::SnapshotTemporarilyUnavailable,
+            Error::IncompatibleStorageVersion { found, supported } => {
+                Error::IncompatibleStorageVersion {
+                    found: *found,
+                    supported: *supported,
+                }
+            }
+            Error::IncompatibleSnapshot(reason) => Error::IncompatibleSnapshot(reason.clone()),
+            Error::VoteRegression {
+                group_id,
+                replica_id,
+                term,
+                vote,
+                audit_term,
+                audit_vote,
+            } => Error::VoteRegression {
+                group_id: *group_id,
+                replica_id: *replica_id,
+                term: *term,
+                vote: *vote,
+                audit_term: *audit_term,
+                audit_vote: *audit_vote,
+            },
+            Error::UnrepairableLog {
+                group_id,
+                replica_id,
+                reason,
+            } => Error::UnrepairableLog {
+                group_id: *group_id,
+                replica_id: *replica_id,
+                reason: reason.clone(),
+            },
+            Error::Other(err) => Error::Other(err.to_string().into()),
+        }
+    }
+}
+
 impl From<StorageError> for Error {
     fn from(that: raft::StorageError) -> Self {
         match that {
@@ -105,6 +211,10 @@ impl From<Error> for RaftStorageError {
             Error::LogTemporarilyUnavailable => Self::LogTemporarilyUnavailable,
             Error::SnapshotOutOfDate => Self::SnapshotOutOfDate,
             Error::SnapshotTemporarilyUnavailable => Self::SnapshotTemporarilyUnavailable,
+            err @ Error::IncompatibleStorageVersion { .. } => Self::Other(Box::new(err)),
+            err @ Error::IncompatibleSnapshot(_) => Self::Other(Box::new(err)),
+            err @ Error::VoteRegression { .. } => Self::Other(Box::new(err)),
+            err @ Error::UnrepairableLog { .. } => Self::Other(Box::new(err)),
             Error::Other(err) => Self::Other(err),
         }
     }
@@ -130,6 +240,18 @@ impl From<Error> for RaftError {
             Error::SnapshotTemporarilyUnavailable => {
                 RaftError::Store(RaftStorageError::SnapshotTemporarilyUnavailable)
             }
+            err @ Error::IncompatibleStorageVersion { .. } => {
+                RaftError::Store(RaftStorageError::Other(Box::new(err)))
+            }
+            err @ Error::IncompatibleSnapshot(_) => {
+                RaftError::Store(RaftStorageError::Other(Box::new(err)))
+            }
+            err @ Error::VoteRegression { .. } => {
+                RaftError::Store(RaftStorageError::Other(Box::new(err)))
+            }
+            err @ Error::UnrepairableLog { .. } => {
+                RaftError::Store(RaftStorageError::Other(Box::new(err)))
+            }
             Error::Other(err) => RaftError::Store(RaftStorageError::Other(err)),
         }
     }
@@ -144,46 +266,133 @@ pub use raft::Storage;
 /// RaftStorageWriter provides writes all the information about the current Raft implementation,
 /// including Raft Log, commit index, the leader to vote for, etc.
 ///
+/// The methods are asynchronous, so the implementations backed by network or
+/// blocking IO should not block the executor threads, e.g. by `spawn_blocking`.
+///
 /// If any Storage method returns an error, the raft instance will become inoperable and refuse to participate in elections; the application is responsible for cleanup and recovery in this case.
 pub trait StorageExt {
+    /// GAT trait for `append`.
+    type AppendFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
     /// Append the new entries to storage.
     ///
     /// # Panics
     ///
     /// Panics if `ents` contains compacted entries, or there's a gap between `ents` and the last
     /// received entry in the storage.
-    fn append(&self, ents: &[Entry]) -> Result<()>;
+    fn append<'life0>(&'life0 self, ents: &'life0 [Entry]) -> Self::AppendFuture<'life0>;
 
+    /// GAT trait for `set_hardstate`.
+    type SetHardStateFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
     /// Saves the current HardState.
-    fn set_hardstate(&self, hs: HardState) -> Result<()>;
+    fn set_hardstate(&self, hs: HardState) -> Self::SetHardStateFuture<'_>;
 
+    /// GAT trait for `set_confstate`.
+    type SetConfStateFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
     /// Saves the current ConfState
-    fn set_confstate(&self, cs: ConfState) -> Result<()>;
+    fn set_confstate(&self, cs: ConfState) -> Self::SetConfStateFuture<'_>;
 
+    /// GAT trait for `set_hardstate_commit`.
+    type SetHardStateCommitFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
     /// Saves the commit index to hardstate.
-    fn set_hardstate_commit(&self, commit: u64) -> Result<()>;
+    fn set_hardstate_commit(&self, commit: u64) -> Self::SetHardStateCommitFuture<'_>;
 
+    /// GAT trait for `install_snapshot`.
+    type InstallSnapshotFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
     /// Overwrites the contents of this Storage object with those of the given snapshot.
     ///
     /// # Panics
     ///
     /// Panics if the snapshot index is less than the storage’s first index.
-    fn install_snapshot(&self, snapshot: Snapshot) -> Result<()>;
+    fn install_snapshot(&self, snapshot: Snapshot) -> Self::InstallSnapshotFuture<'_>;
 
-    fn get_applied(&self) -> Result<u64>;
+    /// GAT trait for `snapshot_metadata`.
+    type SnapshotMetadataFuture<'life0>: Send
+        + Future<Output = Result<SnapshotMetadata>>
+        + 'life0
+    where
+        Self: 'life0;
+    /// Get the metadata of the last installed snapshot, it is default if no
+    /// snapshot was installed.
+    fn snapshot_metadata(&self) -> Self::SnapshotMetadataFuture<'_>;
+
+    /// GAT trait for `get_applied`.
+    type GetAppliedFuture<'life0>: Send + Future<Output = Result<u64>> + 'life0
+    where
+        Self: 'life0;
+    fn get_applied(&self) -> Self::GetAppliedFuture<'_>;
 
-    fn set_applied(&self, index: u64) -> Result<()>;
+    /// GAT trait for `set_applied`.
+    type SetAppliedFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn set_applied(&self, index: u64) -> Self::SetAppliedFuture<'_>;
+
+    /// GAT trait for `compact`.
+    type CompactFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    /// Discard the entries before `compact_index`, the index must not be
+    /// greater than the applied index. It's a no-op if the entries were
+    /// compacted, and the last entry is kept.
+    fn compact(&self, compact_index: u64) -> Self::CompactFuture<'_>;
 }
 
+/// RaftSnapshotReader loads the snapshot of the application, it's called by
+/// `Storage::snapshot` which `raft-rs` calls synchronously, so the reader is
+/// synchronous unlike `RaftSnapshotWriter`.
 pub trait RaftSnapshotReader: Clone + Send + Sync + 'static {
     // TODO: using serializer trait for adta
     fn load_snapshot(&self, group_id: u64, replica_id: u64) -> Result<Vec<u8>>;
+
+    /// Load the metadata attached to the snapshot by the application, it is
+    /// sent with the snapshot data and persisted by the receiver.
+    fn load_snapshot_metadata(
+        &self,
+        _group_id: u64,
+        _replica_id: u64,
+    ) -> Result<SnapshotAppMetadata> {
+        Ok(SnapshotAppMetadata::default())
+    }
 }
 
 pub trait RaftSnapshotWriter: Clone + Send + Sync + 'static {
+    /// GAT trait for `install_snapshot`.
+    type InstallSnapshotDataFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
     // TODO: using serializer trait for adta
-    fn install_snapshot(&self, group_id: u64, replica_id: u64, data: Vec<u8>) -> Result<()>;
+    fn install_snapshot(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+        data: Vec<u8>,
+    ) -> Self::InstallSnapshotDataFuture<'_>;
 
+    /// Check the metadata of the received snapshot before it is installed,
+    /// returns `Error::IncompatibleSnapshot` to reject the snapshot.
+    fn check_snapshot(
+        &self,
+        _group_id: u64,
+        _replica_id: u64,
+        _meta: &SnapshotAppMetadata,
+    ) -> Result<()> {
+        Ok(())
+    }
+
+    /// GAT trait for `build_snapshot`.
+    type BuildSnapshotFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
     fn build_snapshot(
         &self,
         group_id: u64,
@@ -191,7 +400,40 @@ pub trait RaftSnapshotWriter: Clone + Send + Sync + 'static {
         applied_index: u64,
         applied_term: u64,
         last_conf_state: ConfState,
-    ) -> Result<()>;
+    ) -> Self::BuildSnapshotFuture<'_>;
+}
+
+/// Prefix of the snapshot data which carries `SnapshotAppMetadata`.
+const SNAPSHOT_PAYLOAD_MAGIC: &[u8] = b"\0oceanraft_snap_v1";
+
+/// Wrap the application data of the snapshot with its metadata. The data is
+/// returned as is if the metadata is default, so the snapshots without
+/// metadata keep the format of older versions.
+pub fn encode_snapshot_payload(meta: SnapshotAppMetadata, data: Vec<u8>) -> Vec<u8> {
+    if meta == SnapshotAppMetadata::default() {
+        return data;
+    }
+
+    let payload = SnapshotPayload {
+        metadata: Some(meta),
+        data,
+    };
+    let mut buf = Vec::with_capacity(SNAPSHOT_PAYLOAD_MAGIC.len() + payload.encoded_len());
+    buf.extend_from_slice(SNAPSHOT_PAYLOAD_MAGIC);
+    payload.encode(&mut buf).expect("unreachable");
+    buf
+}
+
+/// Split the snapshot data into the metadata and the application data, the
+/// metadata is default if the data is not wrapped by `encode_snapshot_payload`.
+pub fn decode_snapshot_payload(data: Vec<u8>) -> Result<(SnapshotAppMetadata, Vec<u8>)> {
+    if !data.starts_with(SNAPSHOT_PAYLOAD_MAGIC) {
+        return Ok((SnapshotAppMetadata::default(), data));
+    }
+
+    let payload = SnapshotPayload::decode(&data[SNAPSHOT_PAYLOAD_MAGIC.len()..])
+        .map_err(|err| Error::Other(Box::new(err)))?;
+    Ok((payload.metadata.unwrap_or_default(), payload.data))
 }
 
 /// RaftStorage provides read and writes all the information about the current Raft implementation,
@@ -207,6 +449,16 @@ pub trait RaftStorage: Storage + StorageExt + Clone + Send + Sync + 'static {
 // MultiRaft storage trait
 //----------------------------------------------------------------------
 
+/// The entries and the hard state of the ready of a group, persisted by
+/// `MultiRaftStorage::write_batch`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GroupWrite {
+    pub group_id: u64,
+    pub replica_id: u64,
+    pub entries: Vec<Entry>,
+    pub hard_state: Option<HardState>,
+}
+
 /// MultiRaftStorage per raft group.
 pub trait MultiRaftStorage<S: RaftStorage>: Clone + Send + Sync + 'static {
     /// GAT trait for `group_storage`.
@@ -217,6 +469,24 @@ pub trait MultiRaftStorage<S: RaftStorage>: Clone + Send + Sync + 'static {
     /// new one.
     fn group_storage(&self, group_id: u64, replica_id: u64) -> Self::GroupStorageFuture<'_>;
 
+    /// GAT trait for `group_storages`.
+    type GroupStoragesFuture<'life0>: Send + Future<Output = Vec<Result<S>>>
+    where
+        Self: 'life0;
+    /// Get the `RaftStorage` impls of `(group_id, replica_id)` pairs, the missing
+    /// ones are created in one batch if the storage supports it. Returns the
+    /// results in the order of `groups`.
+    fn group_storages(&self, groups: Vec<(u64, u64)>) -> Self::GroupStoragesFuture<'_>;
+
+    /// GAT trait for `write_batch`.
+    type WriteBatchFuture<'life0>: Send + Future<Output = Vec<Result<()>>>
+    where
+        Self: 'life0;
+    /// Persist the writes of the groups, the storage supporting it persists
+    /// them in one batch, e.g. a RocksDB `WriteBatch` synced once. Returns the
+    /// results in the order of `writes`, see `Config::batch_ready_writes`.
+    fn write_batch(&self, writes: Vec<GroupWrite>) -> Self::WriteBatchFuture<'_>;
+
     /// GAT trait for `groups`.
     type ScanGroupMetadataFuture<'life0>: Send + Future<Output = Result<Vec<GroupMetadata>>>
     where
@@ -228,6 +498,15 @@ pub trait MultiRaftStorage<S: RaftStorage>: Clone + Send + Sync + 'static {
     /// should consider using group_metadata_iter (todo).
     fn scan_group_metadata(&self) -> Self::ScanGroupMetadataFuture<'_>;
 
+    /// GAT trait for `scan_group_storages`.
+    type ScanGroupStoragesFuture<'life0>: Send + Future<Output = Result<Vec<(u64, u64)>>>
+    where
+        Self: 'life0;
+    /// Scan the `(group_id, replica_id)` of the group storages which exist
+    /// regardless of the metadata of the groups, it never creates a storage.
+    /// The replica id is `0` if the storage does not know it.
+    fn scan_group_storages(&self) -> Self::ScanGroupStoragesFuture<'_>;
+
     /// GAT trait for `get_group_metadata`.
     type GetGroupMetadataFuture<'life0>: Send + Future<Output = Result<Option<GroupMetadata>>>
     where
@@ -291,11 +570,85 @@ pub trait MultiRaftStorage<S: RaftStorage>: Clone + Send + Sync + 'static {
         Self: 'life0;
     // Get the `ReplicaDesc` by `group_id` and `node_id`.
     fn replica_for_node(&self, group_id: u64, node_id: u64) -> Self::ReplicaForNodeFuture<'_>;
+
+    /// GAT trait for `next_replica_id`.
+    type NextReplicaIdFuture<'life0>: Send + Future<Output = Result<u64>> + 'life0
+    where
+        Self: 'life0;
+    /// Allocate a new replica id for the group by `group_id`.
+    ///
+    /// # Notes
+    /// The allocator is a monotonic counter persisted per group, the allocated
+    /// id is always greater than the counter and the ids of the known replicas
+    /// of the group. The allocations must be serialized by the implementation.
+    ///
+    /// The counter is a high-water mark replicated with the membership: every
+    /// replica advances it by `advance_replica_id` when a membership change is
+    /// applied, so the node which becomes the leader later never reuses the ids
+    /// allocated by the previous leaders, even if their descriptors were removed.
+    fn next_replica_id(&self, group_id: u64) -> Self::NextReplicaIdFuture<'_>;
+
+    /// GAT trait for `advance_replica_id`.
+    type AdvanceReplicaIdFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    /// Advance the replica id counter of the group by `group_id` to `replica_id`
+    /// if it is behind, the ids not greater than it are never allocated.
+    fn advance_replica_id(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+    ) -> Self::AdvanceReplicaIdFuture<'_>;
+
+    /// GAT trait for `approximate_size`.
+    type ApproximateSizeFuture<'life0>: Send + Future<Output = Result<u64>> + 'life0
+    where
+        Self: 'life0;
+    /// Get the approximate bytes used by the storage of all groups.
+    fn approximate_size(&self) -> Self::ApproximateSizeFuture<'_>;
+
+    /// GAT trait for `set_idempotency_record`.
+    type SetIdempotencyRecordFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    /// Set the outcome of the management request by its idempotency key, the
+    /// record of the same key is replaced.
+    fn set_idempotency_record(
+        &self,
+        record: IdempotencyRecord,
+    ) -> Self::SetIdempotencyRecordFuture<'_>;
+
+    /// GAT trait for `remove_idempotency_record`.
+    type RemoveIdempotencyRecordFuture<'life0>: Send + Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    /// Remove the outcome of the idempotency key, it's called after the
+    /// record expires.
+    fn remove_idempotency_record(&self, key: String) -> Self::RemoveIdempotencyRecordFuture<'_>;
+
+    /// GAT trait for `scan_idempotency_records`.
+    type ScanIdempotencyRecordsFuture<'life0>: Send
+        + Future<Output = Result<Vec<IdempotencyRecord>>>
+        + 'life0
+    where
+        Self: 'life0;
+    /// Scan the outcomes of all idempotency keys, including the expired ones
+    /// which are not removed yet.
+    fn scan_idempotency_records(&self) -> Self::ScanIdempotencyRecordsFuture<'_>;
 }
 
+mod audit;
 mod mem;
+mod witness;
 
 #[cfg(feature = "store-rocksdb")]
 mod rocks;
+pub use audit::{MultiRaftVoteAuditStorage, VoteAuditLog, VoteAuditStorage};
 pub use mem::{MemStorage, MultiRaftMemoryStorage};
-pub use rocks::{ApplyWriteBatch, RockStore, RockStoreCore, StateMachineStore};
+#[cfg(feature = "store-rocksdb")]
+pub use rocks::{
+    upgrade, ApplyWriteBatch, RockStorage, RockStore, RockStoreCore, SnapshotStorageStats,
+    StateMachineStore, STORAGE_FORMAT_VERSION,
+};
+pub(crate) use witness::strip_entries_payload;
+pub use witness::{MultiRaftWitnessStorage, WitnessSnapshot, WitnessStorage};
@@ -1,5 +1,7 @@
 mod storage {
+    use std::collections::HashSet;
     use std::sync::Arc;
+    use std::sync::Mutex;
     use std::time::Duration;
     use std::time::SystemTime;
     use std::time::UNIX_EPOCH;
@@ -21,16 +23,24 @@ mod storage {
     use rocksdb::WriteBatch;
     use rocksdb::WriteOptions;
     use tracing::error;
+    use tracing::info;
+    use tracing::warn;
 
+    use crate::lifecycle::Lifecycle;
     use crate::multiraft::NO_LEADER;
     use crate::prelude::ConfState;
     use crate::prelude::Entry;
     use crate::prelude::GroupMetadata;
     use crate::prelude::HardState;
+    use crate::prelude::IdempotencyRecord;
     use crate::prelude::ReplicaDesc;
     use crate::prelude::Snapshot;
+    use crate::prelude::SnapshotAppMetadata;
     use crate::prelude::SnapshotMetadata;
+    use crate::storage::decode_snapshot_payload;
+    use crate::storage::encode_snapshot_payload;
     use crate::storage::Error;
+    use crate::storage::GroupWrite;
     use crate::storage::MultiRaftStorage;
     use crate::storage::RaftSnapshotReader;
     use crate::storage::RaftSnapshotWriter;
@@ -251,6 +261,49 @@ mod storage {
     /// Constant prerfix for snapshot metadata and store in meta column family.
     const LOG_SNAP_META_PREFIX: &'static str = "snap_meta";
 
+    /// Constant prerfix for snapshot metadata of application and store in meta column family.
+    const LOG_SNAP_APP_META_PREFIX: &'static str = "snap_app_meta";
+
+    /// Constant prerfix for replica id allocator and store in meta column family.
+    const REPLICA_ID_COUNTER_PREFIX: &'static str = "rid";
+
+    /// Constant prerfix for format version of group and store in meta column family.
+    const FORMAT_VERSION_PREFIX: &'static str = "ver";
+
+    /// Constant prerfix for idempotency records and store in meta column family.
+    const IDEMPOTENCY_PREFIX: &'static str = "idem";
+
+    /// The on-disk format version of group storage written by this crate. The
+    /// version is recorded per group when the group storage is created and is
+    /// checked when the group storage is opened.
+    ///
+    /// Bump the version and register a migration in `MIGRATIONS` when the
+    /// layout of the group storage changes.
+    pub const STORAGE_FORMAT_VERSION: u32 = 1;
+
+    /// A migration upgrades the group storage from format version `from`
+    /// to `from + 1`.
+    struct Migration {
+        from: u32,
+        migrate: fn(&MDB, u64, u64) -> std::result::Result<(), RocksdbError>,
+    }
+
+    /// The registered migrations in order of `from`.
+    const MIGRATIONS: &[Migration] = &[Migration {
+        from: 0,
+        migrate: migrate_v0_to_v1,
+    }];
+
+    /// Version `0` is the format before the version record was introduced, the
+    /// layout is the same as version `1` so that only the version is recorded.
+    fn migrate_v0_to_v1(
+        _db: &MDB,
+        _group_id: u64,
+        _replica_id: u64,
+    ) -> std::result::Result<(), RocksdbError> {
+        Ok(())
+    }
+
     /// Constant prerfix for log empty flag and store in log column family.
     const LOG_EMPTY_PREFIX: &'static str = "log_empty";
 
@@ -335,6 +388,13 @@ mod storage {
             format!("{}_{}_{}", LOG_SNAP_META_PREFIX, group_id, replica_id)
         }
 
+        /// Format snapshot metadata of application key with mode
+        /// `snap_app_meta_{group_id}_{replica_id}`
+        #[inline]
+        fn format_snapshot_app_metadata_key(group_id: u64, replica_id: u64) -> String {
+            format!("{}_{}_{}", LOG_SNAP_APP_META_PREFIX, group_id, replica_id)
+        }
+
         /// Format replica description key with mode `rd_{group_id}_{replica_id}` and
         /// stored in metadata cf.
         #[inline]
@@ -356,6 +416,25 @@ mod storage {
         fn format_group_replica_desc_seek_key(group_id: u64) -> String {
             format!("{}_{}_", REPLICA_DESC_PREFIX, group_id)
         }
+
+        /// Format replica id allocator key with mode `rid_{group_id}` and stored in metadata cf.
+        #[inline]
+        fn format_replica_id_counter_key(group_id: u64) -> String {
+            format!("{}_{}", REPLICA_ID_COUNTER_PREFIX, group_id)
+        }
+
+        /// Format group format version key with mode `ver_{group_id}_{replica_id}` and
+        /// stored in metadata cf.
+        #[inline]
+        fn format_version_key(group_id: u64, replica_id: u64) -> String {
+            format!("{}_{}_{}", FORMAT_VERSION_PREFIX, group_id, replica_id)
+        }
+
+        /// Format idempotency record key with mode `idem_{key}` and stored in metadata cf.
+        #[inline]
+        fn format_idempotency_key(key: &str) -> String {
+            format!("{}_{}", IDEMPOTENCY_PREFIX, key)
+        }
     }
 
     #[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
@@ -372,9 +451,40 @@ mod storage {
         empty: bool,
     }
 
+    /// The report of the log repaired on open, see `RockStoreCore::repair_log`.
+    #[derive(Debug, PartialEq)]
+    struct LogRepair {
+        recorded_last_index: u64,
+        /// The last index of the repaired log.
+        last_index: u64,
+        /// The term and commit of the `HardState`.
+        term: u64,
+        commit: u64,
+        /// The indexes of the removed entries.
+        removed: Vec<u64>,
+    }
+
+    /// Run the blocking io of `f` on the blocking threads of tokio, so the
+    /// synced writes of rocksdb don't block the executor threads. The panic
+    /// of `f` is resumed on the caller.
+    pub(super) async fn spawn_blocking<T, F>(f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> Result<T> + Send + 'static,
+    {
+        match tokio::task::spawn_blocking(f).await {
+            Ok(res) => res,
+            Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+            Err(err) => Err(Error::Other(Box::new(err))),
+        }
+    }
+
     /*****************************************************************************
      * ROCKSTORE CORE
      *****************************************************************************/
+    /// RockStoreCore is the `RaftStorage` of a replica, it shares the rocksdb
+    /// of `RockStore` and is created by `MultiRaftStorage::group_storage`, see
+    /// `RockStore` for the format.
     #[derive(Clone)]
     pub struct RockStoreCore<SR: RaftSnapshotReader, SW: RaftSnapshotWriter> {
         node_id: u64,
@@ -386,31 +496,19 @@ mod storage {
     }
 
     impl<SR: RaftSnapshotReader, SW: RaftSnapshotWriter> RockStoreCore<SR, SW> {
-        /// New and initialze RockStoreCore from db.
-        ///
-        /// # Panics
-        /// if RockStoreCore has been initialized
-        fn new(
-            node_id: u64,
+        /// Put the initial state of the group to the batch, includes the empty
+        /// flag of log, the default `HardState`, `ConfState` and `SnapshotMetadata`.
+        fn put_initial_state(
+            batch: &mut WriteBatch,
+            db: &Arc<MDB>,
             group_id: u64,
             replica_id: u64,
-            db: &Arc<MDB>,
-            rsnap: &SR,
-            wsnap: &SW,
-        ) -> std::result::Result<Self, RocksdbError> {
-            let core = RockStoreCore {
-                node_id,
-                group_id,
-                replica_id,
-                db: db.clone(),
-                rsnap: rsnap.clone(),
-                wsnap: wsnap.clone(),
-            };
-
-            core.set_empty_flag(true)?;
+        ) {
+            let log_cf = DBEnv::get_log_cf(db);
+            let key = DBEnv::format_empty_key(group_id, replica_id);
+            batch.put_cf(&log_cf, key, true.to_string());
 
             let meta_cf = DBEnv::get_metadata_cf(db);
-            let mut batch = WriteBatch::default();
             // put default hard_state
             let hs = HardState::default();
             let key = DBEnv::format_hardstate_key(group_id, replica_id);
@@ -428,12 +526,6 @@ mod storage {
             let key = DBEnv::format_snapshot_metadata_key(group_id, replica_id);
             let value = meta.encode_to_vec();
             batch.put_cf(&meta_cf, key, value);
-
-            let mut writeopts = WriteOptions::default();
-            writeopts.set_sync(true);
-            core.db.write_opt(batch, &writeopts)?;
-
-            Ok(core)
         }
 
         /// Handling rocksdb write related error and returned Error.
@@ -585,6 +677,98 @@ mod storage {
                 })
         }
 
+        /// Validate the log recovered after a crash and repair the torn tail.
+        /// The entries which are not continuous with the log, beyond the
+        /// recorded last index, or of a term newer than the `HardState` are
+        /// removed, since they were never acknowledged. The repair is refused
+        /// if it loses the committed entries.
+        ///
+        /// Returns `None` if the log is consistent.
+        fn repair_log(&self) -> Result<Option<LogRepair>> {
+            let to_err = |err| self.to_write_err(err, true, false, "repair_log".into());
+            let ent_meta = self.get_entry_meta().map_err(to_err)?;
+            let hs = self.get_hard_state().map_err(to_err)?;
+            let snap_meta = self.get_snapshot_metadata().map_err(to_err)?;
+
+            let log_cf = DBEnv::get_log_cf(&self.db);
+            let prefix = DBEnv::format_entry_key_prefix(self.group_id);
+            let start_key = DBEnv::format_entry_key(self.group_id, ent_meta.first_index);
+            let iter_mode = IteratorMode::From(start_key.as_bytes(), rocksdb::Direction::Forward);
+            let iter = self
+                .db
+                .iterator_cf_opt(&log_cf, ReadOptions::default(), iter_mode);
+
+            let mut last_index = ent_meta.first_index - 1;
+            let mut torn = vec![];
+            for item in iter {
+                let (key, value) = item.map_err(to_err)?;
+                if !key.starts_with(prefix.as_bytes()) {
+                    break;
+                }
+                let index = std::str::from_utf8(&key[prefix.len()..])
+                    .ok()
+                    .and_then(|index| index.parse::<u64>().ok())
+                    .expect("invalid entry key");
+                if torn.is_empty() && index == last_index + 1 && index <= ent_meta.last_index {
+                    let ent = Entry::decode(value.as_ref()).unwrap(); // TODO: use difference serializer
+                    if ent.term <= hs.term {
+                        last_index = index;
+                        continue;
+                    }
+                }
+                torn.push(index);
+            }
+
+            if torn.is_empty() && (ent_meta.empty || last_index == ent_meta.last_index) {
+                return Ok(None);
+            }
+
+            let unrepairable = |reason| Error::UnrepairableLog {
+                group_id: self.group_id,
+                replica_id: self.replica_id,
+                reason,
+            };
+            // the compacted entries are covered by the snapshot only if the log
+            // starts after it.
+            let empty = ent_meta.empty || last_index < ent_meta.first_index;
+            if empty && ent_meta.first_index != snap_meta.index + 1 {
+                return Err(unrepairable(format!(
+                    "the first entry {} is missing and the snapshot index is {}",
+                    ent_meta.first_index, snap_meta.index
+                )));
+            }
+            let last_index = std::cmp::max(last_index, snap_meta.index);
+            if hs.commit > last_index {
+                return Err(unrepairable(format!(
+                    "the committed entries ({}, {}] are missing",
+                    last_index, hs.commit
+                )));
+            }
+
+            let mut batch = WriteBatch::default();
+            for index in torn.iter() {
+                batch.delete_cf(&log_cf, DBEnv::format_entry_key(self.group_id, *index));
+            }
+            if empty {
+                let key = DBEnv::format_empty_key(self.group_id, self.replica_id);
+                batch.put_cf(&log_cf, key, true.to_string());
+            } else {
+                let key = DBEnv::format_last_index_key(self.group_id, self.replica_id);
+                batch.put_cf(&log_cf, key, last_index.to_be_bytes());
+            }
+            let mut writeopts = WriteOptions::default();
+            writeopts.set_sync(true);
+            self.db.write_opt(batch, &writeopts).map_err(to_err)?;
+
+            Ok(Some(LogRepair {
+                recorded_last_index: ent_meta.last_index,
+                last_index,
+                term: hs.term,
+                commit: hs.commit,
+                removed: torn,
+            }))
+        }
+
         fn get_entry(&self, index: u64) -> std::result::Result<Entry, RocksdbError> {
             let logcf = DBEnv::get_log_cf(&self.db);
             let key = DBEnv::format_entry_key(self.group_id, index);
@@ -607,28 +791,38 @@ mod storage {
             )
         }
 
+        /// Save the snapshot metadata with the metadata of application in one batch.
         fn set_snapshot_metadata(
             &self,
             meta: &SnapshotMetadata,
+            app_meta: &SnapshotAppMetadata,
         ) -> std::result::Result<(), RocksdbError> {
             let cf = DBEnv::get_metadata_cf(&self.db);
+            let mut batch = WriteBatch::default();
             let key = DBEnv::format_snapshot_metadata_key(self.group_id, self.replica_id);
-            let value = meta.encode_to_vec(); // TODO: use difference serializer
+            batch.put_cf(&cf, key, meta.encode_to_vec()); // TODO: use difference serializer
+            let key = DBEnv::format_snapshot_app_metadata_key(self.group_id, self.replica_id);
+            batch.put_cf(&cf, key, app_meta.encode_to_vec());
             let mut writeopts = WriteOptions::default();
             writeopts.set_sync(true);
-            self.db.put_cf_opt(&cf, key, value, &writeopts)
+            self.db.write_opt(batch, &writeopts)
         }
 
-        fn set_empty_flag(&self, flag: bool) -> std::result::Result<(), RocksdbError> {
-            let logcf = DBEnv::get_log_cf(&self.db);
-            let mut writeopts = WriteOptions::default();
-            writeopts.set_sync(true);
-            self.db.put_cf_opt(
-                &logcf,
-                DBEnv::format_empty_key(self.group_id, self.replica_id),
-                flag.to_string(),
-                &writeopts,
-            )
+        /// Get the metadata of application of the last installed snapshot, it is
+        /// default if no snapshot with metadata was installed.
+        pub fn snapshot_app_metadata(&self) -> Result<SnapshotAppMetadata> {
+            let cf = DBEnv::get_metadata_cf(&self.db);
+            let key = DBEnv::format_snapshot_app_metadata_key(self.group_id, self.replica_id);
+            let readopts = ReadOptions::default();
+            match self
+                .db
+                .get_cf_opt(&cf, &key, &readopts)
+                .map_err(|err| self.to_read_err(err, false, true, "snapshot_app_metadata".into()))?
+            {
+                None => Ok(SnapshotAppMetadata::default()),
+                Some(data) => SnapshotAppMetadata::decode(data.as_ref())
+                    .map_err(|err| Error::Other(Box::new(err))),
+            }
         }
     }
 
@@ -894,7 +1088,14 @@ mod storage {
             let mut snap = Snapshot::default();
             // get snapshot data from user state machine.
             let data = self.rsnap.load_snapshot(self.group_id, self.replica_id)?;
-            snap.set_data(data);
+            let mut app_meta = self
+                .rsnap
+                .load_snapshot_metadata(self.group_id, self.replica_id)?;
+            if app_meta != SnapshotAppMetadata::default() && app_meta.source_replica_id == 0 {
+                app_meta.source_node_id = self.node_id;
+                app_meta.source_replica_id = self.replica_id;
+            }
+            snap.set_data(encode_snapshot_payload(app_meta, data));
 
             // constructor snapshot metadata from store.
             let snap_meta = self
@@ -936,8 +1137,8 @@ mod storage {
         }
     }
 
-    impl<SR: RaftSnapshotReader, SW: RaftSnapshotWriter> StorageExt for RockStoreCore<SR, SW> {
-        fn set_hardstate(&self, hs: HardState) -> Result<()> {
+    impl<SR: RaftSnapshotReader, SW: RaftSnapshotWriter> RockStoreCore<SR, SW> {
+        fn write_hard_state(&self, hs: HardState) -> Result<()> {
             let metacf = DBEnv::get_metadata_cf(&self.db);
             let key = DBEnv::format_hardstate_key(self.group_id, self.replica_id);
             let value = hs.encode_to_vec(); // TODO: add feature for difference serializers.
@@ -955,13 +1156,13 @@ mod storage {
                 })
         }
 
-        fn set_hardstate_commit(&self, commit: u64) -> Result<()> {
+        fn write_hard_state_commit(&self, commit: u64) -> Result<()> {
             let mut hs = self.get_hard_state().unwrap();
             hs.commit = commit;
-            self.set_hardstate(hs)
+            self.write_hard_state(hs)
         }
 
-        fn set_confstate(&self, cs: ConfState) -> Result<()> {
+        fn write_conf_state(&self, cs: ConfState) -> Result<()> {
             let metacf = DBEnv::get_metadata_cf(&self.db);
             let key = DBEnv::format_confstate_key(self.group_id, self.replica_id);
             let value = cs.encode_to_vec(); // TODO: add feature for difference serializers.
@@ -979,7 +1180,7 @@ mod storage {
                 })
         }
 
-        fn set_applied(&self, index: u64) -> Result<()> {
+        fn write_applied(&self, index: u64) -> Result<()> {
             let metacf = DBEnv::get_metadata_cf(&self.db);
             let key = DBEnv::format_applied_key(self.group_id);
             let mut writeopts = WriteOptions::default();
@@ -996,7 +1197,7 @@ mod storage {
                 })
         }
 
-        fn get_applied(&self) -> Result<u64> {
+        fn read_applied(&self) -> Result<u64> {
             let metacf = DBEnv::get_metadata_cf(&self.db);
             let key = DBEnv::format_applied_key(self.group_id);
             let readopts = ReadOptions::default();
@@ -1009,7 +1210,23 @@ mod storage {
                 })
         }
 
-        fn append(&self, ents: &[Entry]) -> Result<()> {
+        fn write_entries(&self, ents: &[Entry]) -> Result<()> {
+            if ents.is_empty() {
+                return Ok(());
+            }
+
+            let mut batch = WriteBatch::default();
+            self.batch_entries(&mut batch, ents)?;
+            let mut writeopts = WriteOptions::default();
+            writeopts.set_sync(true);
+            self.db
+                .write_opt(batch, &writeopts)
+                .map_err(|err| self.to_write_err(err, true, false, "append".into()))
+        }
+
+        /// Put the entries to the batch, the entries overwritten by them are
+        /// removed before.
+        pub(crate) fn batch_entries(&self, batch: &mut WriteBatch, ents: &[Entry]) -> Result<()> {
             if ents.is_empty() {
                 return Ok(());
             }
@@ -1060,7 +1277,6 @@ mod storage {
 
             // batch writes empty_flag (if need), first_index(if need), last_index and
             // entries to log column family.
-            let mut batch = WriteBatch::default();
             if ent_meta.empty {
                 // set first index
                 let key = DBEnv::format_first_index_key(self.group_id, self.replica_id);
@@ -1083,16 +1299,56 @@ mod storage {
             let key = DBEnv::format_last_index_key(self.group_id, self.replica_id);
             let value = ents.last().expect("unreachable").index.to_be_bytes();
             batch.put_cf(&log_cf, key, value);
+            Ok(())
+        }
+
+        pub(crate) fn batch_hard_state(&self, batch: &mut WriteBatch, hs: &HardState) {
+            let metacf = DBEnv::get_metadata_cf(&self.db);
+            let key = DBEnv::format_hardstate_key(self.group_id, self.replica_id);
+            batch.put_cf(&metacf, key, hs.encode_to_vec());
+        }
+
+        /// Delete the entries before `compact_index`, the last entry is kept so
+        /// the log is not empty.
+        fn compact_entries(&self, compact_index: u64) -> Result<()> {
+            let ent_meta = self
+                .get_entry_meta()
+                .map_err(|err| self.to_write_err(err, true, false, "compact".into()))?;
+            let compact_index = std::cmp::min(compact_index, ent_meta.last_index);
+            if ent_meta.empty || compact_index <= ent_meta.first_index {
+                return Ok(());
+            }
+
+            // the entries are deleted one by one instead of delete range, see
+            // the FIXME of `write_entries`.
+            let log_cf = DBEnv::get_log_cf(&self.db);
+            let mut batch = WriteBatch::default();
+            for index in ent_meta.first_index..compact_index {
+                batch.delete_cf(&log_cf, DBEnv::format_entry_key(self.group_id, index));
+            }
+            let key = DBEnv::format_first_index_key(self.group_id, self.replica_id);
+            batch.put_cf(&log_cf, key, compact_index.to_be_bytes());
 
             let mut writeopts = WriteOptions::default();
             writeopts.set_sync(true);
-            self.db
-                .write_opt(batch, &writeopts)
-                .map_err(|err| self.to_write_err(err, true, false, "append".into()))
+            self.db.write_opt(batch, &writeopts).map_err(|err| {
+                self.to_write_err(
+                    err,
+                    true,
+                    false,
+                    format!("compact: compact_index = {}", compact_index),
+                )
+            })
         }
 
-        fn install_snapshot(&self, mut snapshot: Snapshot) -> Result<()> {
-            let mut snap_meta = snapshot.metadata.as_ref().expect("unreachable").clone();
+        /// Check the snapshot and save its metadata, returns the data of the
+        /// snapshot to be installed to the state machine, or `None` if the
+        /// snapshot is empty.
+        fn begin_install_snapshot(
+            &self,
+            mut snapshot: Snapshot,
+        ) -> Result<Option<(SnapshotMetadata, EntryMetadata, Vec<u8>)>> {
+            let snap_meta = snapshot.metadata.as_ref().expect("unreachable").clone();
             let ent_meta = self
                 .get_entry_meta()
                 .map_err(|err| self.to_write_err(err, false, true, "install_snapshot".into()))?;
@@ -1102,31 +1358,41 @@ mod storage {
             }
 
             if snap_meta == SnapshotMetadata::default() {
-                return Ok(());
+                return Ok(None);
             }
 
-            // save snapshot metadata
-            self.set_snapshot_metadata(&snap_meta).map_err(|err| {
-                self.to_write_err(
-                    err,
-                    false,
-                    true,
-                    format!("install_snapshot: meta = {:?}", snap_meta),
-                )
-            })?;
-            // save snapshot data to user statemachine
-            // TODO: consider save snapshot metadata to user statemachine.
-            // TODO: consider use async method and add scheduler api
+            // let the application reject the snapshot before anything is changed.
+            let (app_meta, data) = decode_snapshot_payload(snapshot.take_data())?;
             self.wsnap
-                .install_snapshot(self.group_id, self.replica_id, snapshot.take_data())?;
+                .check_snapshot(self.group_id, self.replica_id, &app_meta)?;
 
+            // save snapshot metadata
+            self.set_snapshot_metadata(&snap_meta, &app_meta)
+                .map_err(|err| {
+                    self.to_write_err(
+                        err,
+                        false,
+                        true,
+                        format!("install_snapshot: meta = {:?}", snap_meta),
+                    )
+                })?;
+            Ok(Some((snap_meta, ent_meta, data)))
+        }
+
+        /// Update the hardstate, clear the entries and update the confstate
+        /// after the data of snapshot is installed to the state machine.
+        fn finish_install_snapshot(
+            &self,
+            mut snap_meta: SnapshotMetadata,
+            ent_meta: EntryMetadata,
+        ) -> Result<()> {
             // update hardstate
             let mut hs = self
                 .get_hard_state()
                 .map_err(|err| self.to_write_err(err, false, true, "install_snapshot".into()))?;
             hs.term = std::cmp::max(snap_meta.term, hs.term);
             hs.commit = snap_meta.index;
-            self.set_hardstate(hs)?;
+            self.write_hard_state(hs)?;
 
             // clear entries
             if !ent_meta.empty {
@@ -1154,12 +1420,103 @@ mod storage {
             }
 
             // update confstate
-            self.set_confstate(snap_meta.take_conf_state())?;
+            self.write_conf_state(snap_meta.take_conf_state())?;
 
             Ok(())
         }
     }
 
+    impl<SR: RaftSnapshotReader, SW: RaftSnapshotWriter> StorageExt for RockStoreCore<SR, SW> {
+        type SetHardStateFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+        where
+            Self: 'life0;
+        fn set_hardstate(&self, hs: HardState) -> Self::SetHardStateFuture<'_> {
+            let core = self.clone();
+            spawn_blocking(move || core.write_hard_state(hs))
+        }
+
+        type SetHardStateCommitFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+        where
+            Self: 'life0;
+        fn set_hardstate_commit(&self, commit: u64) -> Self::SetHardStateCommitFuture<'_> {
+            let core = self.clone();
+            spawn_blocking(move || core.write_hard_state_commit(commit))
+        }
+
+        type SetConfStateFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+        where
+            Self: 'life0;
+        fn set_confstate(&self, cs: ConfState) -> Self::SetConfStateFuture<'_> {
+            let core = self.clone();
+            spawn_blocking(move || core.write_conf_state(cs))
+        }
+
+        type SetAppliedFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+        where
+            Self: 'life0;
+        fn set_applied(&self, index: u64) -> Self::SetAppliedFuture<'_> {
+            let core = self.clone();
+            spawn_blocking(move || core.write_applied(index))
+        }
+
+        type SnapshotMetadataFuture<'life0> = impl Future<Output = Result<SnapshotMetadata>> + 'life0
+        where
+            Self: 'life0;
+        fn snapshot_metadata(&self) -> Self::SnapshotMetadataFuture<'_> {
+            async move {
+                self.get_snapshot_metadata()
+                    .map_err(|err| self.to_write_err(err, false, true, "snapshot_metadata".into()))
+            }
+        }
+
+        type GetAppliedFuture<'life0> = impl Future<Output = Result<u64>> + 'life0
+        where
+            Self: 'life0;
+        fn get_applied(&self) -> Self::GetAppliedFuture<'_> {
+            async move { self.read_applied() }
+        }
+
+        type AppendFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+        where
+            Self: 'life0;
+        fn append<'life0>(&'life0 self, ents: &'life0 [Entry]) -> Self::AppendFuture<'life0> {
+            let (core, ents) = (self.clone(), ents.to_vec());
+            spawn_blocking(move || core.write_entries(&ents))
+        }
+
+        type CompactFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+        where
+            Self: 'life0;
+        fn compact(&self, compact_index: u64) -> Self::CompactFuture<'_> {
+            let core = self.clone();
+            spawn_blocking(move || core.compact_entries(compact_index))
+        }
+
+        type InstallSnapshotFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+        where
+            Self: 'life0;
+        fn install_snapshot(&self, snapshot: Snapshot) -> Self::InstallSnapshotFuture<'_> {
+            async move {
+                let core = self.clone();
+                let prepared =
+                    spawn_blocking(move || core.begin_install_snapshot(snapshot)).await?;
+                let (snap_meta, ent_meta, data) = match prepared {
+                    None => return Ok(()),
+                    Some(prepared) => prepared,
+                };
+
+                // save snapshot data to user statemachine
+                // TODO: consider save snapshot metadata to user statemachine.
+                self.wsnap
+                    .install_snapshot(self.group_id, self.replica_id, data)
+                    .await?;
+
+                let core = self.clone();
+                spawn_blocking(move || core.finish_install_snapshot(snap_meta, ent_meta)).await
+            }
+        }
+    }
+
     impl<SR: RaftSnapshotReader, SW: RaftSnapshotWriter> RaftStorage for RockStoreCore<SR, SW> {
         type SnapshotWriter = SW;
         type SnapshotReader = SR;
@@ -1168,6 +1525,37 @@ mod storage {
     /*****************************************************************************
      * RockStore
      *****************************************************************************/
+    /// RockStore stores the raft state of all groups on the node in a rocksdb.
+    ///
+    /// # Format
+    /// The metadata column family `metadta_cf` stores:
+    /// - `gs_{group_id}_{replica_id}`: the `GroupMetadata` encoded by protobuf.
+    /// - `ver_{group_id}_{replica_id}`: the format version of the group, u32 in big endian.
+    /// - `{group_id}_{replica_id}_hs`: the `HardState` encoded by protobuf.
+    /// - `{group_id}_{replica_id}_cs`: the `ConfState` encoded by protobuf.
+    /// - `rd_{group_id:020}_{replica_id:020}`: the `ReplicaDesc` encoded by protobuf.
+    /// - `rid_{group_id}`: the replica id allocator, u64 in big endian.
+    /// - `snap_meta_{group_id}_{replica_id}`: the `SnapshotMetadata` encoded by protobuf.
+    /// - `snap_app_meta_{group_id}_{replica_id}`: the `SnapshotAppMetadata` of the
+    /// installed snapshot encoded by protobuf.
+    ///
+    /// The log column family `raft_log_cf` stores:
+    /// - `ent_{group_id}_{index:020}`: the `Entry` encoded by protobuf.
+    /// - `fidx_{group_id}_{replica_id}` and `lidx_{group_id}_{replica_id}`: the first
+    /// and last index of entries.
+    /// - `log_empty_{group_id}_{replica_id}`: the flag whether the entries are empty.
+    ///
+    /// # Versioning
+    /// A group storage written by an older format version is migrated by the
+    /// registered migrations when it's opened, `upgrade` migrates all groups ahead.
+    /// A group storage written by a newer format version fails to open with
+    /// `Error::IncompatibleStorageVersion`.
+    ///
+    /// # Recovery
+    /// The log of a group is validated when the group storage is opened the first
+    /// time, the torn tail left by a crash is removed and the repair is logged. A
+    /// group storage whose committed entries are lost fails to open with
+    /// `Error::UnrepairableLog`.
     #[derive(Clone)]
     pub struct RockStore<SR, SW>
     where
@@ -1179,6 +1567,10 @@ mod storage {
         db: Arc<MDB>,
         rsnap: SR,
         wsnap: SW,
+        /// The groups whose log was validated since the store is opened.
+        validated: Arc<Mutex<HashSet<(u64, u64)>>>,
+        /// Serializes the updates of the replica id counters.
+        replica_id_lock: Arc<Mutex<()>>,
     }
 
     impl<SR, SW> RockStore<SR, SW>
@@ -1211,6 +1603,8 @@ mod storage {
                 db: Arc::new(db),
                 rsnap: snapshot_reader,
                 wsnap: snapshot_writer,
+                validated: Arc::default(),
+                replica_id_lock: Arc::default(),
             }
         }
 
@@ -1236,34 +1630,244 @@ mod storage {
             })
         }
 
+        /// Get the format version of the group, `0` if the version is not recorded.
+        fn get_format_version(&self, group_id: u64, replica_id: u64) -> Result<u32> {
+            let metacf = DBEnv::get_metadata_cf(&self.db);
+            let key = DBEnv::format_version_key(group_id, replica_id);
+            let readopts = ReadOptions::default();
+            let data = self
+                .db
+                .get_pinned_cf_opt(&metacf, &key, &readopts)
+                .map_err(|err| {
+                    self.to_storage_err(group_id, replica_id, err, "get_format_version".into())
+                })?;
+            match data {
+                Some(data) => {
+                    let buf: [u8; 4] = data.as_ref().try_into().map_err(|_| {
+                        Error::Other(
+                            format!(
+                                "format version of group {} replica {} is corrupted, expect 4 bytes but got {}",
+                                group_id,
+                                replica_id,
+                                data.len()
+                            )
+                            .into(),
+                        )
+                    })?;
+                    Ok(u32::from_be_bytes(buf))
+                }
+                None => Ok(0),
+            }
+        }
+
+        fn set_format_version(
+            &self,
+            group_id: u64,
+            replica_id: u64,
+            version: u32,
+        ) -> std::result::Result<(), RocksdbError> {
+            let metacf = DBEnv::get_metadata_cf(&self.db);
+            let key = DBEnv::format_version_key(group_id, replica_id);
+            let mut writeopts = WriteOptions::default();
+            writeopts.set_sync(true);
+            self.db
+                .put_cf_opt(&metacf, &key, version.to_be_bytes(), &writeopts)
+        }
+
+        /// Run the registered migrations to upgrade all groups to `STORAGE_FORMAT_VERSION`.
+        /// Returns the number of upgraded groups.
+        pub fn upgrade(&self) -> Result<usize> {
+            let groups = self
+                .scan_groups()
+                .map_err(|err| self.to_storage_err(0, 0, err, "upgrade".into()))?;
+
+            let mut upgraded = 0;
+            for meta in groups.iter() {
+                let (group_id, replica_id) = (meta.group_id, meta.replica_id);
+                let version = self.get_format_version(group_id, replica_id)?;
+                if version == STORAGE_FORMAT_VERSION {
+                    continue;
+                }
+                self.migrate_group(group_id, replica_id, version)?;
+                upgraded += 1;
+            }
+            Ok(upgraded)
+        }
+
+        /// Run the registered migrations of the group from the format version
+        /// `version` to `STORAGE_FORMAT_VERSION`. The group written by a newer
+        /// format fails with `Error::IncompatibleStorageVersion`.
+        fn migrate_group(&self, group_id: u64, replica_id: u64, mut version: u32) -> Result<()> {
+            let to_err = |err| self.to_storage_err(group_id, replica_id, err, "upgrade".into());
+            while version < STORAGE_FORMAT_VERSION {
+                let migration = match MIGRATIONS.iter().find(|m| m.from == version) {
+                    None => break,
+                    Some(migration) => migration,
+                };
+                (migration.migrate)(&self.db, group_id, replica_id).map_err(to_err)?;
+                version += 1;
+                self.set_format_version(group_id, replica_id, version)
+                    .map_err(to_err)?;
+            }
+
+            if version != STORAGE_FORMAT_VERSION {
+                return Err(Error::IncompatibleStorageVersion {
+                    found: version,
+                    supported: STORAGE_FORMAT_VERSION,
+                });
+            }
+            Ok(())
+        }
+
         pub(crate) fn create_group_store_if_missing(
             &self,
             group_id: u64,
             replica_id: u64,
-        ) -> std::result::Result<RockStoreCore<SR, SW>, RocksdbError> {
-            let meta_cf = DBEnv::get_metadata_cf(&self.db);
-            let key = self.group_store_key(group_id, replica_id);
-            let readopts = ReadOptions::default();
-            match self.db.get_cf_opt(&meta_cf, &key, &readopts)? {
-                Some(_) => {
-                    return Ok(RockStoreCore {
-                        node_id: self.node_id,
-                        group_id,
-                        replica_id,
-                        db: self.db.clone(),
-                        rsnap: self.rsnap.clone(),
-                        wsnap: self.wsnap.clone(),
-                    })
+        ) -> Result<RockStoreCore<SR, SW>> {
+            self.create_group_stores_if_missing(&[(group_id, replica_id)])
+                .pop()
+                .expect("unreachable")
+        }
+
+        /// Repair the log of the group torn by a crash when it's opened the
+        /// first time since the store is opened, see `RockStoreCore::repair_log`.
+        fn validate_group_log(&self, core: &RockStoreCore<SR, SW>) -> Result<()> {
+            let group = (core.group_id, core.replica_id);
+            if self.validated.lock().unwrap().contains(&group) {
+                return Ok(());
+            }
+
+            match core.repair_log() {
+                Err(err) => {
+                    error!(
+                        "node {}: refuse to open group {} replica {} storage, {}",
+                        self.node_id, core.group_id, core.replica_id, err
+                    );
+                    return Err(err);
                 }
-                None => RockStoreCore::<SR, SW>::new(
+                Ok(Some(repair)) => warn!(
+                    "node {}: repaired the torn log of group {} replica {}, recorded last index {}, repaired last index {}, hard state term {} commit {}, removed entries {:?}",
                     self.node_id,
+                    core.group_id,
+                    core.replica_id,
+                    repair.recorded_last_index,
+                    repair.last_index,
+                    repair.term,
+                    repair.commit,
+                    repair.removed
+                ),
+                Ok(None) => {}
+            }
+            self.validated.lock().unwrap().insert(group);
+            Ok(())
+        }
+
+        /// Persist the writes of the groups in one batch synced once, the
+        /// writes of the groups failed to open are excluded from the batch.
+        pub(crate) fn write_groups(&self, writes: Vec<GroupWrite>) -> Vec<Result<()>> {
+            let groups = writes
+                .iter()
+                .map(|write| (write.group_id, write.replica_id))
+                .collect::<Vec<_>>();
+            let cores = self.create_group_stores_if_missing(&groups);
+
+            let mut batch = WriteBatch::default();
+            let mut batched = vec![];
+            let mut results = Vec::with_capacity(writes.len());
+            for (i, (write, core)) in writes.iter().zip(cores).enumerate() {
+                let res = core.and_then(|core| {
+                    core.batch_entries(&mut batch, &write.entries)?;
+                    if let Some(hs) = write.hard_state.as_ref() {
+                        core.batch_hard_state(&mut batch, hs);
+                    }
+                    Ok(())
+                });
+                if res.is_ok() {
+                    batched.push(i);
+                }
+                results.push(res);
+            }
+            if batched.is_empty() {
+                return results;
+            }
+
+            let mut writeopts = WriteOptions::default();
+            writeopts.set_sync(true);
+            if let Err(err) = self.db.write_opt(batch, &writeopts) {
+                let err = self.to_storage_err(0, 0, err, "write_batch".into());
+                for i in batched {
+                    results[i] = Err(err.duplicate());
+                }
+            }
+            results
+        }
+
+        /// Open the group storages of `(group_id, replica_id)`, the missing groups
+        /// are initialized in a single write batch. Returns results in the order of
+        /// `groups`.
+        pub(crate) fn create_group_stores_if_missing(
+            &self,
+            groups: &[(u64, u64)],
+        ) -> Vec<Result<RockStoreCore<SR, SW>>> {
+            let meta_cf = DBEnv::get_metadata_cf(&self.db);
+            let readopts = ReadOptions::default();
+            let mut batch = WriteBatch::default();
+            let mut missing = vec![];
+            let mut results = Vec::with_capacity(groups.len());
+            for (i, (group_id, replica_id)) in groups.iter().cloned().enumerate() {
+                let to_err =
+                    |err| self.to_storage_err(group_id, replica_id, err, "group_storage".into());
+                let key = self.group_store_key(group_id, replica_id);
+                let exists = match self.db.get_cf_opt(&meta_cf, &key, &readopts) {
+                    Err(err) => {
+                        results.push(Err(to_err(err)));
+                        continue;
+                    }
+                    Ok(val) => val.is_some(),
+                };
+                let core = RockStoreCore {
+                    node_id: self.node_id,
                     group_id,
                     replica_id,
-                    &self.db,
-                    &self.rsnap,
-                    &self.wsnap,
-                )
-                .and_then(|core| {
+                    db: self.db.clone(),
+                    rsnap: self.rsnap.clone(),
+                    wsnap: self.wsnap.clone(),
+                };
+
+                if exists {
+                    let found = match self.get_format_version(group_id, replica_id) {
+                        Err(err) => {
+                            results.push(Err(err));
+                            continue;
+                        }
+                        Ok(found) => found,
+                    };
+                    // the group written by an older format is migrated on open.
+                    if found != STORAGE_FORMAT_VERSION {
+                        if let Err(err) = self.migrate_group(group_id, replica_id, found) {
+                            error!(
+                                "node {}: open group {} replica {} storage with format version {} failed, supported version is {}: {}",
+                                self.node_id, group_id, replica_id, found, STORAGE_FORMAT_VERSION, err
+                            );
+                            results.push(Err(err));
+                            continue;
+                        }
+                        info!(
+                            "node {}: group {} replica {} storage is upgraded from format version {} to {}",
+                            self.node_id, group_id, replica_id, found, STORAGE_FORMAT_VERSION
+                        );
+                    }
+                    if let Err(err) = self.validate_group_log(&core) {
+                        results.push(Err(err));
+                        continue;
+                    }
+                } else {
+                    RockStoreCore::<SR, SW>::put_initial_state(
+                        &mut batch,
+                        &self.db,
+                        group_id,
+                        replica_id,
+                    );
                     let metadata = GroupMetadata {
                         group_id,
                         replica_id,
@@ -1274,16 +1878,42 @@ mod storage {
                             .unwrap_or(Duration::default())
                             .as_secs(),
                         deleted: false,
+                        storage_domain: String::new(),
+                        archive: false,
+                        gate_policy: 0,
                     };
+                    let version_key = DBEnv::format_version_key(group_id, replica_id);
+                    batch.put_cf(&meta_cf, version_key, STORAGE_FORMAT_VERSION.to_be_bytes());
+                    batch.put_cf(&meta_cf, key, metadata.encode_to_vec());
+                    missing.push(i);
+                }
 
-                    let mut writeopts = WriteOptions::default();
-                    writeopts.set_sync(true);
-                    let val = metadata.encode_to_vec();
-                    self.db
-                        .put_cf_opt(&meta_cf, key, val.to_vec(), &writeopts)?;
-                    Ok(core)
-                }),
+                results.push(Ok(core));
+            }
+
+            if !missing.is_empty() {
+                let mut writeopts = WriteOptions::default();
+                writeopts.set_sync(true);
+                match self.db.write_opt(batch, &writeopts) {
+                    Err(err) => {
+                        for i in missing {
+                            let (group_id, replica_id) = groups[i];
+                            results[i] = Err(self.to_storage_err(
+                                group_id,
+                                replica_id,
+                                err.clone(),
+                                "group_storage".into(),
+                            ));
+                        }
+                    }
+                    // the log created by the store is never torn by a crash before.
+                    Ok(()) => {
+                        let mut validated = self.validated.lock().unwrap();
+                        validated.extend(missing.into_iter().map(|i| groups[i]));
+                    }
+                }
             }
+            results
         }
 
         /// Scan groups by using `group_` prefix.
@@ -1314,6 +1944,36 @@ mod storage {
             Ok(groups)
         }
 
+        /// Scan the group stores by the format version keys, which are written
+        /// along with the initial state of the stores.
+        fn scan_group_stores(&self) -> std::result::Result<Vec<(u64, u64)>, RocksdbError> {
+            let metacf = DBEnv::get_metadata_cf(&self.db);
+            let prefix = format!("{}_", FORMAT_VERSION_PREFIX);
+
+            let mut stores = vec![];
+            let iter_mode = IteratorMode::From(prefix.as_bytes(), rocksdb::Direction::Forward);
+            let readopts = ReadOptions::default();
+            let iter = self.db.iterator_cf_opt(&metacf, readopts, iter_mode);
+
+            for item in iter {
+                let (key, _) = item?;
+                let key = match std::str::from_utf8(&key) {
+                    Ok(key) => key,
+                    Err(_) => break, /* cross the boundary of the seek prefix */
+                };
+
+                let ids = match key.strip_prefix(&prefix) {
+                    Some(ids) => ids,
+                    None => break, /* prefix is no longer matched */
+                };
+                let mut ids = ids.split('_').map(|id| id.parse::<u64>());
+                if let (Some(Ok(group_id)), Some(Ok(replica_id))) = (ids.next(), ids.next()) {
+                    stores.push((group_id, replica_id));
+                }
+            }
+            Ok(stores)
+        }
+
         fn get_group_metadata(
             &self,
             group_id: u64,
@@ -1382,6 +2042,45 @@ mod storage {
             self.db.delete_cf_opt(&metacf, &key, &writeopts)
         }
 
+        fn set_idempotency_record(
+            &self,
+            record: &IdempotencyRecord,
+        ) -> std::result::Result<(), RocksdbError> {
+            let metacf = DBEnv::get_metadata_cf(&self.db);
+            let key = DBEnv::format_idempotency_key(&record.key);
+            let mut writeopts = WriteOptions::default();
+            writeopts.set_sync(true);
+            self.db
+                .put_cf_opt(&metacf, &key, record.encode_to_vec(), &writeopts)
+        }
+
+        fn remove_idempotency_record(&self, key: &str) -> std::result::Result<(), RocksdbError> {
+            let metacf = DBEnv::get_metadata_cf(&self.db);
+            let key = DBEnv::format_idempotency_key(key);
+            let writeopts = WriteOptions::default();
+            self.db.delete_cf_opt(&metacf, &key, &writeopts)
+        }
+
+        fn scan_idempotency_records(
+            &self,
+        ) -> std::result::Result<Vec<IdempotencyRecord>, RocksdbError> {
+            let metacf = DBEnv::get_metadata_cf(&self.db);
+            let prefix = format!("{}_", IDEMPOTENCY_PREFIX);
+            let iter_mode = IteratorMode::From(prefix.as_bytes(), rocksdb::Direction::Forward);
+            let readopts = ReadOptions::default();
+            let iter = self.db.iterator_cf_opt(&metacf, readopts, iter_mode);
+
+            let mut records = vec![];
+            for item in iter {
+                let (key, value) = item?;
+                if !key.starts_with(prefix.as_bytes()) {
+                    break; /* prefix is no longer matched */
+                }
+                records.push(IdempotencyRecord::decode(value.as_ref()).unwrap());
+            }
+            Ok(records)
+        }
+
         // scan saved all replica descs from storage.
         fn scan_replica_desc(&self) -> std::result::Result<Vec<ReplicaDesc>, RocksdbError> {
             let metacf = DBEnv::get_metadata_cf(&self.db);
@@ -1471,29 +2170,377 @@ mod storage {
                 }
             }
 
-            return Ok(None);
+            return Ok(None);
+        }
+
+        fn get_replica_id_counter(&self, group_id: u64) -> Result<u64> {
+            let metacf = DBEnv::get_metadata_cf(&self.db);
+            let key = DBEnv::format_replica_id_counter_key(group_id);
+            let readopts = ReadOptions::default();
+            let to_err = |err| self.to_storage_err(group_id, 0, err, "replica_id_counter".into());
+            match self
+                .db
+                .get_pinned_cf_opt(&metacf, &key, &readopts)
+                .map_err(to_err)?
+            {
+                Some(data) => {
+                    let buf: [u8; 8] = data.as_ref().try_into().map_err(|_| {
+                        Error::Other(
+                            format!(
+                                "replica id counter of group {} is corrupted, expect 8 bytes but got {}",
+                                group_id,
+                                data.len()
+                            )
+                            .into(),
+                        )
+                    })?;
+                    Ok(u64::from_be_bytes(buf))
+                }
+                None => Ok(0),
+            }
+        }
+
+        fn set_replica_id_counter(&self, group_id: u64, counter: u64) -> Result<()> {
+            let metacf = DBEnv::get_metadata_cf(&self.db);
+            let key = DBEnv::format_replica_id_counter_key(group_id);
+            let mut writeopts = WriteOptions::default();
+            writeopts.set_sync(true);
+            self.db
+                .put_cf_opt(&metacf, &key, counter.to_be_bytes(), &writeopts)
+                .map_err(|err| self.to_storage_err(group_id, 0, err, "replica_id_counter".into()))
+        }
+
+        fn next_replica_id(&self, group_id: u64) -> Result<u64> {
+            // serialize the read-modify-write of the counter, otherwise the
+            // concurrent allocations may read the same value.
+            let _guard = self.replica_id_lock.lock().unwrap();
+            let counter = self.get_replica_id_counter(group_id)?;
+
+            // the allocated id must be greater than the known replicas, because
+            // the replicas maybe learned from messages instead of allocator.
+            let max_known = self
+                .scan_group_replica_desc(group_id)
+                .map_err(|err| self.to_storage_err(group_id, 0, err, "next_replica_id".into()))?
+                .iter()
+                .map(|rd| rd.replica_id)
+                .max()
+                .unwrap_or(0);
+
+            let next = std::cmp::max(counter, max_known) + 1;
+            self.set_replica_id_counter(group_id, next)?;
+            Ok(next)
+        }
+
+        fn advance_replica_id(&self, group_id: u64, replica_id: u64) -> Result<()> {
+            let _guard = self.replica_id_lock.lock().unwrap();
+            if self.get_replica_id_counter(group_id)? >= replica_id {
+                return Ok(());
+            }
+            self.set_replica_id_counter(group_id, replica_id)
+        }
+
+        /// Get the approximate bytes of raft logs and metadata, which is the
+        /// sum of sst files and memtables of the column families.
+        fn approximate_size(&self) -> std::result::Result<u64, RocksdbError> {
+            let mut size = 0;
+            for cf in [
+                DBEnv::get_metadata_cf(&self.db),
+                DBEnv::get_log_cf(&self.db),
+            ] {
+                for name in [
+                    "rocksdb.total-sst-files-size",
+                    "rocksdb.cur-size-all-mem-tables",
+                ] {
+                    size += self.db.property_int_value_cf(&cf, name)?.unwrap_or(0);
+                }
+            }
+            Ok(size)
+        }
+
+        /// Flush the memtables of the column families and sync the wal.
+        fn flush_db(&self) -> std::result::Result<(), RocksdbError> {
+            for cf in [
+                DBEnv::get_metadata_cf(&self.db),
+                DBEnv::get_log_cf(&self.db),
+            ] {
+                self.db.flush_cf(&cf)?;
+            }
+            self.db.flush_wal(true)
+        }
+    }
+
+    mod rock_store_test {
+        use futures::Future;
+
+        use super::STORAGE_FORMAT_VERSION;
+        use crate::prelude::*;
+        use crate::storage::upgrade;
+        use crate::storage::Error;
+        use crate::storage::RaftSnapshotReader;
+        use crate::storage::RaftSnapshotWriter;
+        use crate::storage::RockStore;
+
+        #[derive(Clone, Default)]
+        struct NoopSnap;
+        impl RaftSnapshotReader for NoopSnap {
+            fn load_snapshot(
+                &self,
+                _group_id: u64,
+                _replica_id: u64,
+            ) -> crate::storage::Result<Vec<u8>> {
+                unimplemented!()
+            }
+        }
+
+        impl RaftSnapshotWriter for NoopSnap {
+            type BuildSnapshotFuture<'life0> = impl Future<Output = crate::storage::Result<()>> + 'life0
+            where
+                Self: 'life0;
+            fn build_snapshot(
+                &self,
+                _group_id: u64,
+                _replica_id: u64,
+                _applied_index: u64,
+                _applied_term: u64,
+                _last_conf_state: ConfState,
+            ) -> Self::BuildSnapshotFuture<'_> {
+                async move { unimplemented!() }
+            }
+
+            type InstallSnapshotDataFuture<'life0> = impl Future<Output = crate::storage::Result<()>> + 'life0
+            where
+                Self: 'life0;
+            fn install_snapshot(
+                &self,
+                _group_id: u64,
+                _replica_id: u64,
+                _data: Vec<u8>,
+            ) -> Self::InstallSnapshotDataFuture<'_> {
+                async move { unimplemented!() }
+            }
+        }
+
+        #[test]
+        fn test_scan_replica_desc() {
+            use tempdir;
+            let tmp_dir = tempdir::TempDir::new("oceanraft").unwrap();
+
+            let node_id = 1;
+            let snap = NoopSnap::default();
+            let rock_store = RockStore::new(node_id, tmp_dir.path(), snap.clone(), snap.clone());
+
+            let replica_descs = (1..=10000)
+                .map(|i| ReplicaDesc {
+                    node_id,
+                    group_id: i,
+                    replica_id: i,
+                    witness: false,
+                })
+                .collect::<Vec<_>>();
+
+            for replica_desc in replica_descs.iter() {
+                rock_store
+                    .set_replica_desc(replica_desc.group_id, replica_desc)
+                    .unwrap();
+            }
+
+            let scan_replica_descs = rock_store.scan_replica_desc().unwrap();
+            assert_eq!(scan_replica_descs, replica_descs);
+            tmp_dir.close().unwrap();
+        }
+
+        #[test]
+        fn test_next_replica_id() {
+            use super::DBEnv;
+
+            let tmp_dir = tempdir::TempDir::new("oceanraft").unwrap();
+            let snap = NoopSnap::default();
+            let rock_store = RockStore::new(1, tmp_dir.path(), snap.clone(), snap.clone());
+
+            let handles = (0..8)
+                .map(|_| {
+                    let rock_store = rock_store.clone();
+                    std::thread::spawn(move || {
+                        (0..16)
+                            .map(|_| rock_store.next_replica_id(1).unwrap())
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect::<Vec<_>>();
+            let mut ids = handles
+                .into_iter()
+                .flat_map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>();
+            ids.sort();
+            assert_eq!(ids, (1..=128).collect::<Vec<_>>());
+
+            // the ids observed from the applied membership are never allocated.
+            rock_store.advance_replica_id(1, 200).unwrap();
+            rock_store.advance_replica_id(1, 150).unwrap();
+            assert_eq!(rock_store.next_replica_id(1).unwrap(), 201);
+
+            // the corrupted counter is reported instead of panicking.
+            let metacf = DBEnv::get_metadata_cf(&rock_store.db);
+            rock_store
+                .db
+                .put_cf(&metacf, DBEnv::format_replica_id_counter_key(1), [1u8])
+                .unwrap();
+            assert!(rock_store.next_replica_id(1).is_err());
+            tmp_dir.close().unwrap();
+        }
+
+        #[test]
+        fn test_group_format_version() {
+            let tmp_dir = tempdir::TempDir::new("oceanraft").unwrap();
+            let snap = NoopSnap::default();
+            let rock_store = RockStore::new(1, tmp_dir.path(), snap.clone(), snap.clone());
+            rock_store.create_group_store_if_missing(1, 1).unwrap();
+            assert_eq!(
+                rock_store.get_format_version(1, 1).unwrap(),
+                STORAGE_FORMAT_VERSION
+            );
+
+            // the group created before the version record is introduced is
+            // migrated ahead or on open.
+            rock_store.set_format_version(1, 1, 0).unwrap();
+            assert_eq!(upgrade(&rock_store).unwrap(), 1);
+            assert_eq!(upgrade(&rock_store).unwrap(), 0);
+            rock_store.set_format_version(1, 1, 0).unwrap();
+            rock_store.create_group_store_if_missing(1, 1).unwrap();
+            assert_eq!(
+                rock_store.get_format_version(1, 1).unwrap(),
+                STORAGE_FORMAT_VERSION
+            );
+
+            // the group written by a newer format can't be downgraded.
+            rock_store
+                .set_format_version(1, 1, STORAGE_FORMAT_VERSION + 1)
+                .unwrap();
+            assert!(rock_store.create_group_store_if_missing(1, 1).is_err());
+            assert!(upgrade(&rock_store).is_err());
+
+            // the corrupted version is reported instead of panicking.
+            let metacf = super::DBEnv::get_metadata_cf(&rock_store.db);
+            rock_store
+                .db
+                .put_cf(&metacf, super::DBEnv::format_version_key(1, 1), [1u8])
+                .unwrap();
+            assert!(rock_store.get_format_version(1, 1).is_err());
+            assert!(rock_store.create_group_store_if_missing(1, 1).is_err());
+            tmp_dir.close().unwrap();
+        }
+
+        #[test]
+        fn test_repair_torn_log() {
+            use prost::Message as _;
+
+            use super::DBEnv;
+            use super::LogRepair;
+
+            let new_entry = |index, term| Entry {
+                index,
+                term,
+                ..Default::default()
+            };
+            let tmp_dir = tempdir::TempDir::new("oceanraft").unwrap();
+            let snap = NoopSnap::default();
+            let rock_store = RockStore::new(1, tmp_dir.path(), snap.clone(), snap.clone());
+            let core = rock_store.create_group_store_if_missing(1, 1).unwrap();
+            let ents = (1..=5).map(|index| new_entry(index, 1)).collect::<Vec<_>>();
+            core.write_entries(&ents).unwrap();
+            core.write_hard_state(HardState {
+                term: 1,
+                vote: 1,
+                commit: 3,
+            })
+            .unwrap();
+            assert_eq!(core.repair_log().unwrap(), None);
+
+            {
+                // the entries beyond the recorded last index and of a newer term.
+                let log_cf = DBEnv::get_log_cf(&core.db);
+                let put_entry = |ent: Entry| {
+                    let key = DBEnv::format_entry_key(1, ent.index);
+                    core.db.put_cf(&log_cf, key, ent.encode_to_vec()).unwrap();
+                };
+                put_entry(new_entry(6, 1));
+                assert_eq!(
+                    core.repair_log().unwrap(),
+                    Some(LogRepair {
+                        recorded_last_index: 5,
+                        last_index: 5,
+                        term: 1,
+                        commit: 3,
+                        removed: vec![6],
+                    })
+                );
+                put_entry(new_entry(5, 2));
+                assert_eq!(core.repair_log().unwrap().unwrap().removed, vec![5]);
+                assert_eq!(core.entries_unchecked(), ents[..4]);
+
+                // the tail of the recorded entries is missing.
+                core.db
+                    .delete_cf(&log_cf, DBEnv::format_entry_key(1, 4))
+                    .unwrap();
+                assert_eq!(core.repair_log().unwrap().unwrap().last_index, 3);
+                assert_eq!(core.repair_log().unwrap(), None);
+
+                // the committed entries are missing.
+                core.db
+                    .delete_cf(&log_cf, DBEnv::format_entry_key(1, 3))
+                    .unwrap();
+            }
+
+            // the group refuses to open after restarted.
+            drop(core);
+            drop(rock_store);
+            let rock_store = RockStore::new(1, tmp_dir.path(), snap.clone(), snap.clone());
+            match rock_store.create_group_store_if_missing(1, 1) {
+                Err(Error::UnrepairableLog {
+                    group_id: 1,
+                    replica_id: 1,
+                    ..
+                }) => {}
+                Err(err) => panic!("expected unrepairable log, got {:?}", err),
+                Ok(_) => panic!("expected unrepairable log"),
+            }
+            tmp_dir.close().unwrap();
         }
-    }
 
-    mod rock_store_test {
-        use crate::prelude::*;
-        use crate::storage::RaftSnapshotReader;
-        use crate::storage::RaftSnapshotWriter;
-        use crate::storage::RockStore;
+        /// Snapshot of the application with `version` schema, it accepts the
+        /// snapshots of schema versions up to `version`.
+        #[derive(Clone)]
+        struct VersionedSnap {
+            version: u32,
+        }
 
-        #[derive(Clone, Default)]
-        struct NoopSnap;
-        impl RaftSnapshotReader for NoopSnap {
+        impl RaftSnapshotReader for VersionedSnap {
             fn load_snapshot(
                 &self,
                 _group_id: u64,
                 _replica_id: u64,
             ) -> crate::storage::Result<Vec<u8>> {
-                unimplemented!()
+                Ok(b"data".to_vec())
+            }
+
+            fn load_snapshot_metadata(
+                &self,
+                _group_id: u64,
+                _replica_id: u64,
+            ) -> crate::storage::Result<SnapshotAppMetadata> {
+                Ok(SnapshotAppMetadata {
+                    schema_version: self.version,
+                    start_key: b"a".to_vec(),
+                    end_key: b"z".to_vec(),
+                    ..Default::default()
+                })
             }
         }
 
-        impl RaftSnapshotWriter for NoopSnap {
+        impl RaftSnapshotWriter for VersionedSnap {
+            type BuildSnapshotFuture<'life0> = impl Future<Output = crate::storage::Result<()>> + 'life0
+            where
+                Self: 'life0;
             fn build_snapshot(
                 &self,
                 _group_id: u64,
@@ -1501,45 +2548,78 @@ mod storage {
                 _applied_index: u64,
                 _applied_term: u64,
                 _last_conf_state: ConfState,
-            ) -> crate::storage::Result<()> {
-                unimplemented!()
+            ) -> Self::BuildSnapshotFuture<'_> {
+                async move { unimplemented!() }
             }
 
+            type InstallSnapshotDataFuture<'life0> = impl Future<Output = crate::storage::Result<()>> + 'life0
+            where
+                Self: 'life0;
             fn install_snapshot(
                 &self,
                 _group_id: u64,
                 _replica_id: u64,
-                _data: Vec<u8>,
+                data: Vec<u8>,
+            ) -> Self::InstallSnapshotDataFuture<'_> {
+                async move {
+                    assert_eq!(data, b"data".to_vec());
+                    Ok(())
+                }
+            }
+
+            fn check_snapshot(
+                &self,
+                _group_id: u64,
+                _replica_id: u64,
+                meta: &SnapshotAppMetadata,
             ) -> crate::storage::Result<()> {
-                unimplemented!()
+                if meta.schema_version > self.version {
+                    return Err(Error::IncompatibleSnapshot(format!(
+                        "schema version {} > {}",
+                        meta.schema_version, self.version
+                    )));
+                }
+                Ok(())
             }
         }
 
-        #[test]
-        fn test_scan_replica_desc() {
-            use tempdir;
-            let tmp_dir = tempdir::TempDir::new("oceanraft").unwrap();
-
-            let node_id = 1;
-            let snap = NoopSnap::default();
-            let rock_store = RockStore::new(node_id, tmp_dir.path(), snap.clone(), snap.clone());
-
-            let replica_descs = (1..=10000)
-                .map(|i| ReplicaDesc {
-                    node_id,
-                    group_id: i,
-                    replica_id: i,
-                })
-                .collect::<Vec<_>>();
+        #[tokio::test]
+        async fn test_snapshot_app_metadata() {
+            use crate::storage::Storage;
+            use crate::storage::StorageExt;
 
-            for replica_desc in replica_descs.iter() {
-                rock_store
-                    .set_replica_desc(replica_desc.group_id, replica_desc)
-                    .unwrap();
+            let tmp_dir = tempdir::TempDir::new("oceanraft").unwrap();
+            let new_snap = VersionedSnap { version: 2 };
+            let leader_store =
+                RockStore::new(1, tmp_dir.path().join("1"), new_snap.clone(), new_snap);
+            let leader = leader_store.create_group_store_if_missing(1, 1).unwrap();
+            let mut snapshot = leader.snapshot(4, 0).unwrap();
+            snapshot.mut_metadata().term = 4;
+            snapshot.mut_metadata().mut_conf_state().voters = vec![1, 2];
+
+            // the follower with older schema rejects the snapshot and keeps its state.
+            let old_snap = VersionedSnap { version: 1 };
+            let old_store = RockStore::new(2, tmp_dir.path().join("2"), old_snap.clone(), old_snap);
+            let old = old_store.create_group_store_if_missing(1, 2).unwrap();
+            match old.install_snapshot(snapshot.clone()).await {
+                Err(Error::IncompatibleSnapshot(_)) => {}
+                res => panic!("expected incompatible snapshot error, got {:?}", res),
             }
-
-            let scan_replica_descs = rock_store.scan_replica_desc().unwrap();
-            assert_eq!(scan_replica_descs, replica_descs);
+            assert_eq!(
+                old.snapshot_app_metadata().unwrap(),
+                SnapshotAppMetadata::default()
+            );
+            assert_eq!(old.initial_state().unwrap().hard_state.commit, 0);
+
+            let new_snap = VersionedSnap { version: 2 };
+            let new_store = RockStore::new(3, tmp_dir.path().join("3"), new_snap.clone(), new_snap);
+            let new = new_store.create_group_store_if_missing(1, 3).unwrap();
+            new.install_snapshot(snapshot).await.unwrap();
+            let app_meta = new.snapshot_app_metadata().unwrap();
+            assert_eq!(app_meta.schema_version, 2);
+            assert_eq!(app_meta.start_key, b"a".to_vec());
+            assert_eq!(app_meta.source_node_id, 1);
+            assert_eq!(app_meta.source_replica_id, 1);
             tmp_dir.close().unwrap();
         }
     }
@@ -1554,11 +2634,28 @@ mod storage {
         Self: 'life0;
 
         fn group_storage(&self, group_id: u64, replica_id: u64) -> Self::GroupStorageFuture<'_> {
+            async move { self.create_group_store_if_missing(group_id, replica_id) }
+        }
+
+        type GroupStoragesFuture<'life0> = impl Future<Output = Vec<Result<RockStoreCore<SR, SW>>>> + 'life0
+        where
+            Self: 'life0;
+
+        fn group_storages(&self, groups: Vec<(u64, u64)>) -> Self::GroupStoragesFuture<'_> {
+            async move { self.create_group_stores_if_missing(&groups) }
+        }
+
+        type WriteBatchFuture<'life0> = impl Future<Output = Vec<Result<()>>> + 'life0
+        where
+            Self: 'life0;
+
+        fn write_batch(&self, writes: Vec<GroupWrite>) -> Self::WriteBatchFuture<'_> {
+            let (store, len) = (self.clone(), writes.len());
             async move {
-                self.create_group_store_if_missing(group_id, replica_id)
-                    .map_err(|err| {
-                        self.to_storage_err(group_id, replica_id, err, "group_storage".into())
-                    })
+                match spawn_blocking(move || Ok(store.write_groups(writes))).await {
+                    Ok(results) => results,
+                    Err(err) => (0..len).map(|_| Err(err.duplicate())).collect(),
+                }
             }
         }
 
@@ -1572,6 +2669,16 @@ mod storage {
             }
         }
 
+        type ScanGroupStoragesFuture<'life0> = impl Future<Output = Result<Vec<(u64, u64)>>> + 'life0
+        where
+            Self: 'life0;
+        fn scan_group_storages(&self) -> Self::ScanGroupStoragesFuture<'_> {
+            async move {
+                self.scan_group_stores()
+                    .map_err(|err| self.to_storage_err(0, 0, err, "scan_group_storages".into()))
+            }
+        }
+
         type GetGroupMetadataFuture<'life0> = impl Future<Output = Result<Option<GroupMetadata>>> + 'life0
         where
             Self: 'life0;
@@ -1676,15 +2783,131 @@ mod storage {
                     })
             }
         }
+
+        type NextReplicaIdFuture<'life0> = impl Future<Output = Result<u64>> + 'life0
+        where
+            Self: 'life0;
+        fn next_replica_id(&self, group_id: u64) -> Self::NextReplicaIdFuture<'_> {
+            async move { self.next_replica_id(group_id) }
+        }
+
+        type AdvanceReplicaIdFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+        where
+            Self: 'life0;
+        fn advance_replica_id(
+            &self,
+            group_id: u64,
+            replica_id: u64,
+        ) -> Self::AdvanceReplicaIdFuture<'_> {
+            async move { self.advance_replica_id(group_id, replica_id) }
+        }
+
+        type ApproximateSizeFuture<'life0> = impl Future<Output = Result<u64>> + 'life0
+        where
+            Self: 'life0;
+        fn approximate_size(&self) -> Self::ApproximateSizeFuture<'_> {
+            async move {
+                self.approximate_size()
+                    .map_err(|err| self.to_storage_err(0, 0, err, "approximate_size".into()))
+            }
+        }
+
+        type SetIdempotencyRecordFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+        where
+            Self: 'life0;
+        fn set_idempotency_record(
+            &self,
+            record: IdempotencyRecord,
+        ) -> Self::SetIdempotencyRecordFuture<'_> {
+            async move {
+                self.set_idempotency_record(&record).map_err(|err| {
+                    self.to_storage_err(record.group_id, 0, err, "set_idempotency_record".into())
+                })
+            }
+        }
+
+        type RemoveIdempotencyRecordFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+        where
+            Self: 'life0;
+        fn remove_idempotency_record(
+            &self,
+            key: String,
+        ) -> Self::RemoveIdempotencyRecordFuture<'_> {
+            async move {
+                self.remove_idempotency_record(&key).map_err(|err| {
+                    self.to_storage_err(0, 0, err, "remove_idempotency_record".into())
+                })
+            }
+        }
+
+        type ScanIdempotencyRecordsFuture<'life0> = impl Future<Output = Result<Vec<IdempotencyRecord>>> + 'life0
+        where
+            Self: 'life0;
+        fn scan_idempotency_records(&self) -> Self::ScanIdempotencyRecordsFuture<'_> {
+            async move {
+                self.scan_idempotency_records().map_err(|err| {
+                    self.to_storage_err(0, 0, err, "scan_idempotency_records".into())
+                })
+            }
+        }
+    }
+
+    impl<SR, SW> Lifecycle for RockStore<SR, SW>
+    where
+        SR: RaftSnapshotReader,
+        SW: RaftSnapshotWriter,
+    {
+        type StartFuture<'life0> = impl Future<Output = std::result::Result<(), crate::Error>> + 'life0
+        where
+            Self: 'life0;
+        fn start(&self) -> Self::StartFuture<'_> {
+            async move { Ok(()) }
+        }
+
+        type FlushFuture<'life0> = impl Future<Output = std::result::Result<(), crate::Error>> + 'life0
+        where
+            Self: 'life0;
+        fn flush(&self) -> Self::FlushFuture<'_> {
+            let store = self.clone();
+            async move {
+                spawn_blocking(move || {
+                    store
+                        .flush_db()
+                        .map_err(|err| store.to_storage_err(0, 0, err, "flush".into()))
+                })
+                .await?;
+                Ok(())
+            }
+        }
+
+        type StopFuture<'life0> = impl Future<Output = std::result::Result<(), crate::Error>> + 'life0
+        where
+            Self: 'life0;
+        /// Wait for the background compactions and flushes of rocksdb to stop,
+        /// the db is closed when the last reference of the store is dropped.
+        fn stop(&self) -> Self::StopFuture<'_> {
+            let db = self.db.clone();
+            async move {
+                spawn_blocking(move || {
+                    db.cancel_all_background_work(true);
+                    Ok(())
+                })
+                .await?;
+                Ok(())
+            }
+        }
     }
 }
 
 mod state_machine {
     use std::collections::BTreeMap;
+    use std::collections::HashMap;
     use std::marker::PhantomData;
     use std::path::Path;
     use std::sync::Arc;
+    use std::sync::Mutex;
 
+    use futures::Future;
     use rocksdb::BoundColumnFamily;
     use rocksdb::ColumnFamilyDescriptor;
     use rocksdb::DBWithThreadMode;
@@ -1696,6 +2919,8 @@ mod state_machine {
     use rocksdb::WriteBatch;
     use rocksdb::WriteOptions;
 
+    use crate::compression::decompress_entry_data;
+    use crate::compression::EntryCompression;
     use crate::prelude::ConfState;
     use crate::prelude::StoreData;
     use crate::storage::Error;
@@ -1704,6 +2929,8 @@ mod state_machine {
     use crate::storage::Result as StorageResult;
     use crate::ProposeResponse;
 
+    use super::storage::spawn_blocking;
+
     type Result<T> = std::result::Result<T, StateMachineStoreError>;
 
     #[derive(thiserror::Error, Debug)]
@@ -1900,6 +3127,9 @@ mod state_machine {
     where
         R: ProposeResponse,
     {
+        type BuildSnapshotFuture<'life0> = impl Future<Output = StorageResult<()>> + 'life0
+        where
+            Self: 'life0;
         fn build_snapshot(
             &self,
             group_id: u64,
@@ -1907,37 +3137,48 @@ mod state_machine {
             applied_index: u64,
             applied_term: u64,
             conf_state: ConfState,
-        ) -> StorageResult<()> {
-            let serializer = SnapshotSerializer {
-                meta: SnapshotMetaSerializer {
-                    applied_index,
-                    applied_term,
-                    last_membership: SnapshotMembership::from(conf_state),
-                },
-                data: SnapshotDataSerializer::try_from((group_id, self))
-                    .map_err(|err| Error::Other(Box::new(err)))?,
-            };
+        ) -> Self::BuildSnapshotFuture<'_> {
+            let store = self.clone();
+            spawn_blocking(move || {
+                let serializer = SnapshotSerializer {
+                    meta: SnapshotMetaSerializer {
+                        applied_index,
+                        applied_term,
+                        last_membership: SnapshotMembership::from(conf_state),
+                    },
+                    data: SnapshotDataSerializer::try_from((group_id, &store))
+                        .map_err(|err| Error::Other(Box::new(err)))?,
+                };
 
-            let data = serializer
-                .serialize()
-                .map_err(|err| Error::Other(Box::new(err)))?;
+                let data = serializer
+                    .serialize()
+                    .map_err(|err| Error::Other(Box::new(err)))?;
 
-            self.set_snapshot(group_id, &data)
-                .map_err(|err| Error::Other(Box::new(err)))
+                store
+                    .set_snapshot(group_id, data)
+                    .map_err(|err| Error::Other(Box::new(err)))
+            })
         }
 
+        type InstallSnapshotDataFuture<'life0> = impl Future<Output = StorageResult<()>> + 'life0
+        where
+            Self: 'life0;
         fn install_snapshot(
             &self,
             group_id: u64,
             _replica_id: u64,
             data: Vec<u8>,
-        ) -> StorageResult<()> {
-            if data.is_empty() {
-                return Ok(());
-            }
+        ) -> Self::InstallSnapshotDataFuture<'_> {
+            let store = self.clone();
+            spawn_blocking(move || {
+                if data.is_empty() {
+                    return Ok(());
+                }
 
-            self.restore(group_id, data)
-                .map_err(|err| Error::Other(Box::new(err)))
+                store
+                    .restore(group_id, data)
+                    .map_err(|err| Error::Other(Box::new(err)))
+            })
         }
     }
 
@@ -2092,9 +3333,34 @@ mod state_machine {
     pub struct StateMachineStore<R: ProposeResponse> {
         _node_id: u64,
         db: Arc<DBWithThreadMode<MultiThreaded>>,
+        snapshot_compression: EntryCompression,
+        /// The raw and stored bytes of the snapshot of the groups.
+        snapshot_sizes: Arc<Mutex<HashMap<u64, (u64, u64)>>>,
         _m: PhantomData<R>,
     }
 
+    /// The sizes of the snapshots saved or loaded by `StateMachineStore` since
+    /// it's opened, see `StateMachineStore::snapshot_stats`.
+    #[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+    pub struct SnapshotStorageStats {
+        /// The groups whose snapshot is counted.
+        pub snapshots: usize,
+        /// The bytes of the snapshots before compressed.
+        pub raw_bytes: u64,
+        /// The bytes of the snapshots on disk.
+        pub stored_bytes: u64,
+    }
+
+    impl SnapshotStorageStats {
+        /// The stored bytes out of the raw bytes, `1.0` if nothing is counted.
+        pub fn compression_ratio(&self) -> f64 {
+            if self.raw_bytes == 0 {
+                return 1.0;
+            }
+            self.stored_bytes as f64 / self.raw_bytes as f64
+        }
+    }
+
     // impl<R: WriteResponse> MultiStateMachine<StoreData, R> for KVStore<R> {
     //     type E = RockStateMachineError;
     //     type S = KVStateMachine<R>;
@@ -2136,10 +3402,30 @@ mod state_machine {
             Self {
                 _node_id: node_id,
                 db: Arc::new(db),
+                snapshot_compression: EntryCompression::None,
+                snapshot_sizes: Arc::new(Mutex::new(HashMap::new())),
                 _m: PhantomData,
             }
         }
 
+        /// Compress the snapshots on disk with the codec, they are decompressed
+        /// by `load_snapshot` transparently. The snapshots stored before are
+        /// loaded regardless of the codec. default is `EntryCompression::None`.
+        pub fn with_snapshot_compression(mut self, compression: EntryCompression) -> Self {
+            self.snapshot_compression = compression;
+            self
+        }
+
+        /// The sizes of the snapshots saved or loaded since the store is opened.
+        pub fn snapshot_stats(&self) -> SnapshotStorageStats {
+            let sizes = self.snapshot_sizes.lock().unwrap();
+            SnapshotStorageStats {
+                snapshots: sizes.len(),
+                raw_bytes: sizes.values().map(|(raw, _)| raw).sum(),
+                stored_bytes: sizes.values().map(|(_, stored)| stored).sum(),
+            }
+        }
+
         /// Batch apply to `StateMachineStore`, which provides a convenient
         /// method for state machine apply.
         // pub fn apply(&self, group_id: u64, applys: &mut Vec<Apply<StoreData, R>>) -> Result<()> {
@@ -2326,15 +3612,23 @@ mod state_machine {
             )
         }
 
-        /// Save current snapshot raw datas to snapshot column of rocksdb.
-        fn set_snapshot(&self, group_id: u64, data: &[u8]) -> Result<()> {
+        /// Save current snapshot raw datas to snapshot column of rocksdb, the
+        /// datas are compressed by the snapshot compression of the store.
+        fn set_snapshot(&self, group_id: u64, data: Vec<u8>) -> Result<()> {
             let cf = self.get_snapshot_cf()?;
             let key = format_snapshot_key(group_id);
+            let raw_len = data.len() as u64;
+            let data = self.snapshot_compression.compress(data, 0);
             let mut writeopts = WriteOptions::default();
             writeopts.set_sync(true);
             self.db
-                .put_cf_opt(&cf, key, data, &writeopts)
-                .map_err(|err| StateMachineStoreError::Other(Box::new(err)))
+                .put_cf_opt(&cf, key, &data, &writeopts)
+                .map_err(|err| StateMachineStoreError::Other(Box::new(err)))?;
+            self.snapshot_sizes
+                .lock()
+                .unwrap()
+                .insert(group_id, (raw_len, data.len() as u64));
+            Ok(())
         }
 
         // Get current snapshot raw datas from snapshot column of rocksdb.
@@ -2342,10 +3636,22 @@ mod state_machine {
             let cf = self.get_snapshot_cf()?;
             let readopts = ReadOptions::default();
             let key = format_snapshot_key(group_id);
-            self.db
+            let stored = match self
+                .db
                 .get_pinned_cf_opt(&cf, &key, &readopts)
                 .map_err(|err| StateMachineStoreError::Other(Box::new(err)))?
-                .map_or(Ok(vec![]), |data| Ok(data.to_vec()))
+            {
+                None => return Ok(vec![]),
+                Some(stored) => stored,
+            };
+            let data = decompress_entry_data(stored.as_ref())
+                .map_err(|err| StateMachineStoreError::Other(Box::new(err)))?
+                .into_owned();
+            self.snapshot_sizes
+                .lock()
+                .unwrap()
+                .insert(group_id, (data.len() as u64, stored.len() as u64));
+            Ok(data)
         }
 
         /// Get current conf_state from rocksdb.s
@@ -2453,6 +3759,7 @@ mod tests {
     use std::path::PathBuf;
 
     use futures::future::BoxFuture;
+    use futures::Future;
     use futures::FutureExt;
     use protobuf::Message as PbMessage;
     use raft::prelude::HardState;
@@ -2478,11 +3785,15 @@ mod tests {
     use crate::prelude::ReplicaDesc;
     use crate::prelude::Snapshot;
     use crate::protos::StoreData;
+    use crate::storage::GroupWrite;
     use crate::storage::MultiRaftStorage;
+    use crate::storage::RaftSnapshotReader;
     use crate::storage::RaftSnapshotWriter;
     use crate::storage::StorageExt;
     use crate::Apply;
     use crate::ApplyNormal;
+    #[cfg(feature = "compression")]
+    use crate::EntryCompression;
 
     fn rand_temp_dir() -> PathBuf {
         let rand_str: String = rand::thread_rng()
@@ -2531,6 +3842,7 @@ mod tests {
             data,
             is_conf_change: false,
             context: None,
+            proposer: None,
             tx: None,
         })
     }
@@ -2628,6 +3940,16 @@ mod tests {
         destroy_db(&state_machine_temp_dir);
     }
 
+    /// Run the future of storage in the sync tests, the writes of storage
+    /// need a runtime for the blocking io.
+    fn block_on<F: Future>(fut: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(fut)
+    }
+
     /// Provide async env for tests
     ///
     /// **Note:** a little of trick to see [Reference parameter to async fn does not live long enough](https://users.rust-lang.org/t/reference-parameter-to-async-fn-does-not-live-long-enough/65230) get more informactions.
@@ -2707,14 +4029,13 @@ mod tests {
             db_test_env::<_, ()>(|rock_store, _state_machine| {
                 let rock_store_core = rock_store.create_group_store_if_missing(1, 1).unwrap();
                 rock_store_core.append_unchecked(&ents);
-                rock_store_core
-                    .set_hardstate(HardState {
-                        term: idx,
-                        vote: 0,
-                        commit: idx,
-                    })
-                    .unwrap();
-                rock_store_core.set_confstate(conf_state.clone()).unwrap();
+                block_on(rock_store_core.set_hardstate(HardState {
+                    term: idx,
+                    vote: 0,
+                    commit: idx,
+                }))
+                .unwrap();
+                block_on(rock_store_core.set_confstate(conf_state.clone())).unwrap();
 
                 let result = rock_store_core.snapshot(windex, 0);
                 if result != wresult {
@@ -2833,8 +4154,12 @@ mod tests {
                             vote: 0,
                             commit: apply_idx,
                         })
+                        .await
+                        .unwrap();
+                    rock_store_core
+                        .set_confstate(conf_state.clone())
+                        .await
                         .unwrap();
-                    rock_store_core.set_confstate(conf_state.clone()).unwrap();
 
                     let mut batch = state_machine.write_batch_for_apply(group_id);
                     for apply in applys.iter_mut() {
@@ -2852,6 +4177,10 @@ mod tests {
                                 batch.set_applied_index(membership.index);
                                 batch.set_applied_term(membership.term);
                             }
+                            Apply::Admin(admin) => {
+                                batch.set_applied_index(admin.index);
+                                batch.set_applied_term(admin.term);
+                            }
                         }
                     }
                     state_machine.write_apply_bath(group_id, batch).unwrap();
@@ -2859,6 +4188,7 @@ mod tests {
                     // .await
                     state_machine
                         .build_snapshot(group_id, 1, apply_idx, apply_idx, conf_state.clone())
+                        .await
                         .unwrap();
 
                     let result = rock_store_core.snapshot(windex, 0);
@@ -2926,8 +4256,9 @@ mod tests {
                 let rock_store_core = rock_store.create_group_store_if_missing(1, 1).unwrap();
 
                 rock_store_core.append_unchecked(&ents);
-                let res =
-                    panic::catch_unwind(AssertUnwindSafe(|| rock_store_core.append(&entries)));
+                let res = panic::catch_unwind(AssertUnwindSafe(|| {
+                    block_on(rock_store_core.append(&entries))
+                }));
                 if let Some(wentries) = wentries {
                     let _ = res.unwrap();
                     let e = &rock_store_core.entries_unchecked();
@@ -3035,6 +4366,147 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_rock_storage_compact() {
+        db_test_env::<_, ()>(|rock_store, _state_machine| {
+            let ents = vec![new_entry(3, 3), new_entry(4, 4), new_entry(5, 5)];
+
+            let rock_store_core = rock_store.create_group_store_if_missing(1, 1).unwrap();
+            rock_store_core.append_unchecked(&ents);
+
+            block_on(rock_store_core.compact(4)).unwrap();
+            assert_eq!(rock_store_core.first_index(), Ok(4));
+            assert_eq!(
+                rock_store_core.term(3),
+                Err(RaftError::Store(RaftStorageError::Compacted))
+            );
+            assert_eq!(rock_store_core.term(4), Ok(4));
+
+            // the compacted entries and the last entry are kept.
+            block_on(rock_store_core.compact(2)).unwrap();
+            assert_eq!(rock_store_core.first_index(), Ok(4));
+            block_on(rock_store_core.compact(10)).unwrap();
+            assert_eq!(rock_store_core.first_index(), Ok(5));
+            assert_eq!(rock_store_core.last_index(), Ok(5));
+        });
+    }
+
+    #[test]
+    fn test_rock_store_write_groups() {
+        db_test_env::<_, ()>(|rock_store, _state_machine| {
+            let hs = HardState {
+                term: 4,
+                vote: 1,
+                commit: 3,
+            };
+            let writes = vec![
+                GroupWrite {
+                    group_id: 1,
+                    replica_id: 1,
+                    entries: vec![new_entry(1, 1), new_entry(2, 2)],
+                    hard_state: None,
+                },
+                GroupWrite {
+                    group_id: 2,
+                    replica_id: 1,
+                    entries: vec![new_entry(1, 1), new_entry(2, 2), new_entry(3, 4)],
+                    hard_state: Some(hs.clone()),
+                },
+            ];
+            let results = rock_store.write_groups(writes);
+            assert!(results.iter().all(|res| res.is_ok()));
+
+            let core1 = rock_store.create_group_store_if_missing(1, 1).unwrap();
+            assert_eq!(core1.last_index(), Ok(2));
+            let core2 = rock_store.create_group_store_if_missing(2, 1).unwrap();
+            assert_eq!(core2.last_index(), Ok(3));
+            assert_eq!(core2.initial_state().unwrap().hard_state, hs);
+        });
+    }
+
+    /// The state of the groups is recovered after the store is closed without
+    /// flushing and reopened on the same path.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_rock_store_recovery() {
+        let state_machine_temp_dir = rand_temp_dir().join("oceanraft_state_machine");
+        let rock_store_temp_dir = rand_temp_dir().join("oceanraft_rock_store");
+        let node_id = 1;
+        let state_machine = new_state_machine::<()>(&state_machine_temp_dir, node_id);
+
+        let hs = HardState {
+            term: 5,
+            vote: 1,
+            commit: 5,
+        };
+        let cs = ConfState {
+            voters: vec![1, 2, 3],
+            learners: vec![4],
+            ..Default::default()
+        };
+        let replica = ReplicaDesc {
+            node_id,
+            group_id: 1,
+            replica_id: 1,
+            witness: false,
+        };
+        {
+            let rock_store = new_rockstore(&rock_store_temp_dir, node_id, &state_machine);
+            let core = rock_store.group_storage(1, 1).await.unwrap();
+            core.install_snapshot(new_snapshot(3, 3, vec![1, 2, 3]))
+                .await
+                .unwrap();
+            core.append(&[new_entry(4, 4), new_entry(5, 5), new_entry(6, 5)])
+                .await
+                .unwrap();
+            core.set_hardstate(hs.clone()).await.unwrap();
+            core.set_confstate(cs.clone()).await.unwrap();
+            rock_store
+                .set_replica_desc(1, replica.clone())
+                .await
+                .unwrap();
+
+            let results = rock_store.write_groups(vec![GroupWrite {
+                group_id: 2,
+                replica_id: 1,
+                entries: vec![new_entry(1, 1), new_entry(2, 1)],
+                hard_state: Some(HardState {
+                    term: 1,
+                    vote: 1,
+                    commit: 2,
+                }),
+            }]);
+            assert!(results.iter().all(|res| res.is_ok()));
+        }
+
+        let rock_store = new_rockstore(&rock_store_temp_dir, node_id, &state_machine);
+        let core = rock_store.group_storage(1, 1).await.unwrap();
+        let rs = core.initial_state().unwrap();
+        assert_eq!(rs.hard_state, hs);
+        assert_eq!(rs.conf_state, cs);
+        assert_eq!(core.first_index(), Ok(4));
+        assert_eq!(core.last_index(), Ok(6));
+        assert_eq!(
+            core.entries(4, 7, None, GetEntriesContext::empty(false)),
+            Ok(vec![new_entry(4, 4), new_entry(5, 5), new_entry(6, 5)])
+        );
+        let snap_meta = core.snapshot_metadata().await.unwrap();
+        assert_eq!((snap_meta.index, snap_meta.term), (3, 3));
+        assert_eq!(
+            rock_store.get_replica_desc(1, 1).await.unwrap(),
+            Some(replica)
+        );
+
+        let core = rock_store.group_storage(2, 1).await.unwrap();
+        assert_eq!(core.last_index(), Ok(2));
+        assert_eq!(core.initial_state().unwrap().hard_state.commit, 2);
+
+        drop(core);
+        drop(rock_store);
+        drop(state_machine);
+        destroy_db(&rock_store_temp_dir);
+        destroy_db(&state_machine_temp_dir);
+    }
+
     #[test]
     fn test_rock_storage_last_index() {
         db_test_env::<_, ()>(|rock_store, _state_machine| {
@@ -3049,7 +4521,7 @@ mod tests {
                 panic!("want {:?}, got {:?}", wresult, result);
             }
 
-            rock_store_core.append(&[new_entry(6, 5)]).unwrap();
+            block_on(rock_store_core.append(&[new_entry(6, 5)])).unwrap();
             let wresult = Ok(6);
             let result = rock_store_core.last_index();
             if result != wresult {
@@ -3058,6 +4530,46 @@ mod tests {
         });
     }
 
+    #[cfg(feature = "compression")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_state_machine_snapshot_compression() {
+        let group_id = 1;
+        let path = rand_temp_dir().join("oceanraft_state_machine");
+        let state_machine =
+            new_state_machine::<()>(&path, 1).with_snapshot_compression(EntryCompression::Zstd(3));
+        let mut batch = state_machine.write_batch_for_apply(group_id);
+        for i in 0..64 {
+            batch.put_data(&StoreData {
+                key: format!("key_{}", i),
+                value: vec![b'v'; 256],
+            });
+        }
+        batch.set_applied_index(5);
+        batch.set_applied_term(5);
+        state_machine.write_apply_bath(group_id, batch).unwrap();
+
+        let mut conf_state = ConfState::default();
+        conf_state.voters = vec![1, 2, 3];
+        state_machine
+            .build_snapshot(group_id, 1, 5, 5, conf_state)
+            .await
+            .unwrap();
+        let stats = state_machine.snapshot_stats();
+        assert_eq!(stats.snapshots, 1);
+        assert!(stats.compression_ratio() < 0.5);
+
+        // the snapshot is decompressed transparently.
+        let data = state_machine.load_snapshot(group_id, 1).unwrap();
+        assert_eq!(data.len() as u64, stats.raw_bytes);
+        let serializer = SnapshotSerializer::deserialize(&data).unwrap();
+        assert_eq!(serializer.meta.applied_index, 5);
+        assert_eq!(serializer.data.bt_map.len(), 64);
+        assert!(state_machine.load_snapshot(2, 1).unwrap().is_empty());
+
+        drop(state_machine);
+        destroy_db(&path);
+    }
+
     #[test]
     fn test_rock_storage_apply_snapshot() {
         let nodes = vec![1, 2, 3];
@@ -3066,11 +4578,11 @@ mod tests {
             let rock_store_core = rock_store.create_group_store_if_missing(1, 1).unwrap();
             // Apply snapshot successfully
             let snap = new_snapshot(4, 4, nodes.clone());
-            rock_store_core.install_snapshot(snap).unwrap();
+            block_on(rock_store_core.install_snapshot(snap)).unwrap();
 
             // Apply snapshot fails due to StorageError::SnapshotOutOfDate
             let snap = new_snapshot(3, 3, nodes);
-            rock_store_core.install_snapshot(snap).unwrap_err();
+            block_on(rock_store_core.install_snapshot(snap)).unwrap_err();
         });
     }
 
@@ -3088,17 +4600,20 @@ mod tests {
                         node_id: 1,
                         group_id,
                         replica_id: 1,
+                        witness: false,
                     },
                     ReplicaDesc {
                         node_id: 2,
                         group_id,
 
                         replica_id: 2,
+                        witness: false,
                     },
                     ReplicaDesc {
                         node_id: 3,
                         group_id,
                         replica_id: 3,
+                        witness: false,
                     },
                 ];
 
@@ -3163,6 +4678,22 @@ mod tests {
     }
 }
 
-pub use storage::{RockStore, RockStoreCore};
+pub use storage::{RockStore, RockStoreCore, STORAGE_FORMAT_VERSION};
+
+/// The `RaftStorage` of the replicas stored in `RockStore`.
+pub type RockStorage<SR, SW> = RockStoreCore<SR, SW>;
+
+/// Run the registered migrations to upgrade all groups of the store to
+/// `STORAGE_FORMAT_VERSION` ahead, otherwise the groups are upgraded when they
+/// are opened. Returns the number of upgraded groups.
+pub fn upgrade<SR, SW>(store: &RockStore<SR, SW>) -> crate::storage::Result<usize>
+where
+    SR: crate::storage::RaftSnapshotReader,
+    SW: crate::storage::RaftSnapshotWriter,
+{
+    store.upgrade()
+}
 
-pub use state_machine::{ApplyWriteBatch, StateMachineStore, StateMachineStoreError};
+pub use state_machine::{
+    ApplyWriteBatch, SnapshotStorageStats, StateMachineStore, StateMachineStoreError,
+};
@@ -0,0 +1,459 @@
+use std::marker::PhantomData;
+
+use futures::Future;
+use raft::GetEntriesContext;
+use raft::Result as RaftResult;
+
+use crate::lifecycle::Lifecycle;
+use crate::prelude::ConfState;
+use crate::prelude::Entry;
+use crate::prelude::EntryType;
+use crate::prelude::GroupMetadata;
+use crate::prelude::HardState;
+use crate::prelude::IdempotencyRecord;
+use crate::prelude::RaftState;
+use crate::prelude::ReplicaDesc;
+use crate::prelude::Snapshot;
+
+use super::GroupWrite;
+use super::MultiRaftStorage;
+use super::RaftSnapshotReader;
+use super::RaftSnapshotWriter;
+use super::RaftStorage;
+use super::Result;
+use super::Storage;
+use super::StorageExt;
+
+/// Clear the payload of the normal entries, the membership entries are kept
+/// since the witness needs them to track the configuration of the group.
+pub(crate) fn strip_entries_payload(ents: &mut [Entry]) {
+    for ent in ents.iter_mut() {
+        if ent.entry_type() == EntryType::EntryNormal {
+            ent.data.clear();
+            ent.context.clear();
+        }
+    }
+}
+
+/// WitnessSnapshot is the snapshot reader and writer of witness replicas,
+/// the witness has no state machine, so the snapshot data is ignored.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WitnessSnapshot;
+
+impl RaftSnapshotReader for WitnessSnapshot {
+    fn load_snapshot(&self, _: u64, _: u64) -> Result<Vec<u8>> {
+        Ok(vec![])
+    }
+}
+
+impl RaftSnapshotWriter for WitnessSnapshot {
+    type InstallSnapshotDataFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn install_snapshot(&self, _: u64, _: u64, _: Vec<u8>) -> Self::InstallSnapshotDataFuture<'_> {
+        async move { Ok(()) }
+    }
+
+    type BuildSnapshotFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn build_snapshot(
+        &self,
+        _: u64,
+        _: u64,
+        _: u64,
+        _: u64,
+        _: ConfState,
+    ) -> Self::BuildSnapshotFuture<'_> {
+        async move { Ok(()) }
+    }
+}
+
+/// WitnessStorage stores only the log metadata of the replica, e.g. term,
+/// index and membership entries, the payload of normal entries and the data
+/// of snapshots are dropped before written to the inner storage.
+#[derive(Clone)]
+pub struct WitnessStorage<S: RaftStorage> {
+    inner: S,
+}
+
+impl<S: RaftStorage> WitnessStorage<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: RaftStorage> Storage for WitnessStorage<S> {
+    fn initial_state(&self) -> RaftResult<RaftState> {
+        self.inner.initial_state()
+    }
+
+    fn entries(
+        &self,
+        low: u64,
+        high: u64,
+        max_size: impl Into<Option<u64>>,
+        context: GetEntriesContext,
+    ) -> RaftResult<Vec<Entry>> {
+        self.inner.entries(low, high, max_size, context)
+    }
+
+    fn term(&self, idx: u64) -> RaftResult<u64> {
+        self.inner.term(idx)
+    }
+
+    fn first_index(&self) -> RaftResult<u64> {
+        self.inner.first_index()
+    }
+
+    fn last_index(&self) -> RaftResult<u64> {
+        self.inner.last_index()
+    }
+
+    fn snapshot(&self, request_index: u64, to: u64) -> RaftResult<Snapshot> {
+        self.inner.snapshot(request_index, to)
+    }
+}
+
+impl<S: RaftStorage> StorageExt for WitnessStorage<S> {
+    type AppendFuture<'life0> = impl Future<Output = Result<()>> + 'life0
+    where
+        Self: 'life0;
+    fn append<'life0>(&'life0 self, ents: &'life0 [Entry]) -> Self::AppendFuture<'life0> {
+        async move {
+            // the leader strips the payload before sending to witness, but the
+            // entries may come from the leader of older version.
+            if ents
+                .iter()
+                .all(|ent| ent.entry_type() != EntryType::EntryNormal || ent.data.is_empty())
+            {
+                return self.inner.append(ents).await;
+            }
+
+            let mut ents = ents.to_vec();
+            strip_entries_payload(&mut ents);
+            self.inner.append(&ents).await
+        }
+    }
+
+    type SetHardStateFuture<'life0> = S::SetHardStateFuture<'life0>
+    where
+        Self: 'life0;
+    fn set_hardstate(&self, hs: HardState) -> Self::SetHardStateFuture<'_> {
+        self.inner.set_hardstate(hs)
+    }
+
+    type SetConfStateFuture<'life0> = S::SetConfStateFuture<'life0>
+    where
+        Self: 'life0;
+    fn set_confstate(&self, cs: ConfState) -> Self::SetConfStateFuture<'_> {
+        self.inner.set_confstate(cs)
+    }
+
+    type SetHardStateCommitFuture<'life0> = S::SetHardStateCommitFuture<'life0>
+    where
+        Self: 'life0;
+    fn set_hardstate_commit(&self, commit: u64) -> Self::SetHardStateCommitFuture<'_> {
+        self.inner.set_hardstate_commit(commit)
+    }
+
+    type InstallSnapshotFuture<'life0> = S::InstallSnapshotFuture<'life0>
+    where
+        Self: 'life0;
+    fn install_snapshot(&self, mut snapshot: Snapshot) -> Self::InstallSnapshotFuture<'_> {
+        snapshot.data.clear();
+        self.inner.install_snapshot(snapshot)
+    }
+
+    type SnapshotMetadataFuture<'life0> = S::SnapshotMetadataFuture<'life0>
+    where
+        Self: 'life0;
+    fn snapshot_metadata(&self) -> Self::SnapshotMetadataFuture<'_> {
+        self.inner.snapshot_metadata()
+    }
+
+    type GetAppliedFuture<'life0> = S::GetAppliedFuture<'life0>
+    where
+        Self: 'life0;
+    fn get_applied(&self) -> Self::GetAppliedFuture<'_> {
+        self.inner.get_applied()
+    }
+
+    type SetAppliedFuture<'life0> = S::SetAppliedFuture<'life0>
+    where
+        Self: 'life0;
+    fn set_applied(&self, index: u64) -> Self::SetAppliedFuture<'_> {
+        self.inner.set_applied(index)
+    }
+
+    type CompactFuture<'life0> = S::CompactFuture<'life0>
+    where
+        Self: 'life0;
+    fn compact(&self, compact_index: u64) -> Self::CompactFuture<'_> {
+        self.inner.compact(compact_index)
+    }
+}
+
+impl<S: RaftStorage> RaftStorage for WitnessStorage<S> {
+    type SnapshotReader = WitnessSnapshot;
+    type SnapshotWriter = WitnessSnapshot;
+}
+
+/// MultiRaftWitnessStorage wraps the group storages of `M` by `WitnessStorage`,
+/// it's used by the node which hosts only witness replicas.
+#[derive(Clone)]
+pub struct MultiRaftWitnessStorage<S: RaftStorage, M: MultiRaftStorage<S>> {
+    inner: M,
+    _m: PhantomData<S>,
+}
+
+impl<S: RaftStorage, M: MultiRaftStorage<S>> MultiRaftWitnessStorage<S, M> {
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            _m: PhantomData,
+        }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &M {
+        &self.inner
+    }
+}
+
+impl<S, M> MultiRaftStorage<WitnessStorage<S>> for MultiRaftWitnessStorage<S, M>
+where
+    S: RaftStorage,
+    M: MultiRaftStorage<S>,
+{
+    type GroupStorageFuture<'life0> = impl Future<Output = Result<WitnessStorage<S>>> + 'life0
+        where
+            Self: 'life0;
+    fn group_storage(&self, group_id: u64, replica_id: u64) -> Self::GroupStorageFuture<'_> {
+        async move {
+            self.inner
+                .group_storage(group_id, replica_id)
+                .await
+                .map(WitnessStorage::new)
+        }
+    }
+
+    type GroupStoragesFuture<'life0> = impl Future<Output = Vec<Result<WitnessStorage<S>>>> + 'life0
+        where
+            Self: 'life0;
+    fn group_storages(&self, groups: Vec<(u64, u64)>) -> Self::GroupStoragesFuture<'_> {
+        async move {
+            self.inner
+                .group_storages(groups)
+                .await
+                .into_iter()
+                .map(|res| res.map(WitnessStorage::new))
+                .collect()
+        }
+    }
+
+    type WriteBatchFuture<'life0> = M::WriteBatchFuture<'life0>
+        where
+            Self: 'life0;
+    fn write_batch(&self, mut writes: Vec<GroupWrite>) -> Self::WriteBatchFuture<'_> {
+        for write in writes.iter_mut() {
+            strip_entries_payload(&mut write.entries);
+        }
+        self.inner.write_batch(writes)
+    }
+
+    type ScanGroupMetadataFuture<'life0> = M::ScanGroupMetadataFuture<'life0>
+        where
+            Self: 'life0;
+    fn scan_group_metadata(&self) -> Self::ScanGroupMetadataFuture<'_> {
+        self.inner.scan_group_metadata()
+    }
+
+    type ScanGroupStoragesFuture<'life0> = M::ScanGroupStoragesFuture<'life0>
+        where
+            Self: 'life0;
+    fn scan_group_storages(&self) -> Self::ScanGroupStoragesFuture<'_> {
+        self.inner.scan_group_storages()
+    }
+
+    type GetGroupMetadataFuture<'life0> = M::GetGroupMetadataFuture<'life0>
+        where
+            Self: 'life0;
+    fn get_group_metadata(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+    ) -> Self::GetGroupMetadataFuture<'_> {
+        self.inner.get_group_metadata(group_id, replica_id)
+    }
+
+    type SetGroupMetadataFuture<'life0> = M::SetGroupMetadataFuture<'life0>
+        where
+            Self: 'life0;
+    fn set_group_metadata(&self, meta: GroupMetadata) -> Self::SetGroupMetadataFuture<'_> {
+        self.inner.set_group_metadata(meta)
+    }
+
+    type ReplicaDescFuture<'life0> = M::ReplicaDescFuture<'life0>
+        where
+            Self: 'life0;
+    fn get_replica_desc(&self, group_id: u64, replica_id: u64) -> Self::ReplicaDescFuture<'_> {
+        self.inner.get_replica_desc(group_id, replica_id)
+    }
+
+    type SetReplicaDescFuture<'life0> = M::SetReplicaDescFuture<'life0>
+        where
+            Self: 'life0;
+    fn set_replica_desc(
+        &self,
+        group_id: u64,
+        replica_desc: ReplicaDesc,
+    ) -> Self::SetReplicaDescFuture<'_> {
+        self.inner.set_replica_desc(group_id, replica_desc)
+    }
+
+    type RemoveReplicaDescFuture<'life0> = M::RemoveReplicaDescFuture<'life0>
+        where
+            Self: 'life0;
+    fn remove_replica_desc(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+    ) -> Self::RemoveReplicaDescFuture<'_> {
+        self.inner.remove_replica_desc(group_id, replica_id)
+    }
+
+    type ScanGroupReplicaDescFuture<'life0> = M::ScanGroupReplicaDescFuture<'life0>
+        where
+            Self: 'life0;
+    fn scan_group_replica_desc(&self, group_id: u64) -> Self::ScanGroupReplicaDescFuture<'_> {
+        self.inner.scan_group_replica_desc(group_id)
+    }
+
+    type ReplicaForNodeFuture<'life0> = M::ReplicaForNodeFuture<'life0>
+        where
+            Self: 'life0;
+    fn replica_for_node(&self, group_id: u64, node_id: u64) -> Self::ReplicaForNodeFuture<'_> {
+        self.inner.replica_for_node(group_id, node_id)
+    }
+
+    type NextReplicaIdFuture<'life0> = M::NextReplicaIdFuture<'life0>
+        where
+            Self: 'life0;
+    fn next_replica_id(&self, group_id: u64) -> Self::NextReplicaIdFuture<'_> {
+        self.inner.next_replica_id(group_id)
+    }
+
+    type AdvanceReplicaIdFuture<'life0> = M::AdvanceReplicaIdFuture<'life0>
+        where
+            Self: 'life0;
+    fn advance_replica_id(
+        &self,
+        group_id: u64,
+        replica_id: u64,
+    ) -> Self::AdvanceReplicaIdFuture<'_> {
+        self.inner.advance_replica_id(group_id, replica_id)
+    }
+
+    type ApproximateSizeFuture<'life0> = M::ApproximateSizeFuture<'life0>
+        where
+            Self: 'life0;
+    fn approximate_size(&self) -> Self::ApproximateSizeFuture<'_> {
+        self.inner.approximate_size()
+    }
+
+    type SetIdempotencyRecordFuture<'life0> = M::SetIdempotencyRecordFuture<'life0>
+        where
+            Self: 'life0;
+    fn set_idempotency_record(
+        &self,
+        record: IdempotencyRecord,
+    ) -> Self::SetIdempotencyRecordFuture<'_> {
+        self.inner.set_idempotency_record(record)
+    }
+
+    type RemoveIdempotencyRecordFuture<'life0> = M::RemoveIdempotencyRecordFuture<'life0>
+        where
+            Self: 'life0;
+    fn remove_idempotency_record(&self, key: String) -> Self::RemoveIdempotencyRecordFuture<'_> {
+        self.inner.remove_idempotency_record(key)
+    }
+
+    type ScanIdempotencyRecordsFuture<'life0> = M::ScanIdempotencyRecordsFuture<'life0>
+        where
+            Self: 'life0;
+    fn scan_idempotency_records(&self) -> Self::ScanIdempotencyRecordsFuture<'_> {
+        self.inner.scan_idempotency_records()
+    }
+}
+
+impl<S, M> Lifecycle for MultiRaftWitnessStorage<S, M>
+where
+    S: RaftStorage,
+    M: MultiRaftStorage<S> + Lifecycle,
+{
+    type StartFuture<'life0> = M::StartFuture<'life0>
+        where
+            Self: 'life0;
+    fn start(&self) -> Self::StartFuture<'_> {
+        self.inner.start()
+    }
+
+    type FlushFuture<'life0> = M::FlushFuture<'life0>
+        where
+            Self: 'life0;
+    fn flush(&self) -> Self::FlushFuture<'_> {
+        self.inner.flush()
+    }
+
+    type StopFuture<'life0> = M::StopFuture<'life0>
+        where
+            Self: 'life0;
+    fn stop(&self) -> Self::StopFuture<'_> {
+        self.inner.stop()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::prelude::Entry;
+    use crate::prelude::EntryType;
+    use crate::prelude::Snapshot;
+    use crate::storage::MemStorage;
+    use crate::storage::Storage;
+    use crate::storage::StorageExt;
+
+    use super::WitnessStorage;
+
+    #[tokio::test]
+    async fn test_witness_storage_strip_payload() {
+        let storage = WitnessStorage::new(MemStorage::new());
+        let mut ss = Snapshot::default();
+        ss.mut_metadata().index = 1;
+        ss.mut_metadata().term = 1;
+        storage.install_snapshot(ss).await.unwrap();
+
+        let mut ents = vec![];
+        for (index, entry_type) in [(2, EntryType::EntryNormal), (3, EntryType::EntryConfChange)] {
+            let mut ent = Entry::default();
+            ent.index = index;
+            ent.term = 1;
+            ent.set_entry_type(entry_type);
+            ent.data = b"data".to_vec();
+            ents.push(ent);
+        }
+        storage.append(&ents).await.unwrap();
+
+        let stored = storage
+            .entries(2, 4, None, raft::GetEntriesContext::empty(false))
+            .unwrap();
+        assert_eq!(stored[0].term, 1);
+        assert!(stored[0].data.is_empty());
+        assert_eq!(stored[1].data, b"data".to_vec());
+    }
+}
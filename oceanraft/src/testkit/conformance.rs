@@ -0,0 +1,384 @@
+//! The conformance suite of the storages and the state machines implemented
+//! by the applications. Each check drives the implementation through the
+//! contract the node relies on and panics with the violated invariant, so the
+//! checks are called from the tests of the implementation, e.g.
+//!
+//! ```ignore
+//! #[tokio::test]
+//! async fn test_conformance() {
+//!     let storage = MyStorage::open(path);
+//!     let gs = storage.group_storage(1, 1).await.unwrap();
+//!     conformance::check_raft_storage(&gs).await;
+//!     conformance::check_storage_recovery(storage, 2, 1, |storage| {
+//!         drop(storage);
+//!         MyStorage::open(path)
+//!     })
+//!     .await;
+//! }
+//! ```
+use std::fmt::Debug;
+
+use raft::GetEntriesContext;
+use raft::StorageError;
+
+use crate::multiraft::ProposeResponse;
+use crate::prelude::ConfState;
+use crate::prelude::Entry;
+use crate::prelude::HardState;
+use crate::rsm::Apply;
+use crate::rsm::ApplyNormal;
+use crate::rsm::StateMachine;
+use crate::storage::GroupWrite;
+use crate::storage::MultiRaftStorage;
+use crate::storage::RaftSnapshotReader;
+use crate::storage::RaftSnapshotWriter;
+use crate::storage::RaftStorage;
+use crate::GroupState;
+use crate::ProposeData;
+
+fn new_entry(index: u64, term: u64) -> Entry {
+    Entry {
+        index,
+        term,
+        data: format!("data-{}-{}", index, term).into_bytes(),
+        ..Default::default()
+    }
+}
+
+fn entries<RS: RaftStorage>(gs: &RS, low: u64, high: u64, max_size: Option<u64>) -> Vec<Entry> {
+    gs.entries(low, high, max_size, GetEntriesContext::empty(false))
+        .unwrap_or_else(|err| panic!("storage: read entries [{}, {}) error: {}", low, high, err))
+}
+
+/// Check the raft log, the hard state, the conf state and the applied index
+/// of the group storage, which must be created without any state.
+pub async fn check_raft_storage<RS: RaftStorage>(gs: &RS) {
+    assert_eq!(
+        gs.first_index().unwrap(),
+        1,
+        "storage: first index of new log"
+    );
+    assert_eq!(
+        gs.last_index().unwrap(),
+        0,
+        "storage: last index of new log"
+    );
+
+    // the appended entries are read back.
+    let ents = (1..=5).map(|index| new_entry(index, 1)).collect::<Vec<_>>();
+    gs.append(&ents).await.unwrap();
+    assert_eq!(
+        gs.last_index().unwrap(),
+        5,
+        "storage: last index after append"
+    );
+    assert_eq!(entries(gs, 1, 6, None), ents, "storage: appended entries");
+    assert_eq!(gs.term(3).unwrap(), 1, "storage: term of appended entry");
+    assert_eq!(
+        entries(gs, 1, 6, Some(0)).len(),
+        1,
+        "storage: entries limited by size must return at least one entry"
+    );
+
+    // the conflicting entries and all entries after them are replaced.
+    let conflicts = vec![new_entry(4, 2)];
+    gs.append(&conflicts).await.unwrap();
+    assert_eq!(
+        gs.last_index().unwrap(),
+        4,
+        "storage: last index after conflict"
+    );
+    assert_eq!(
+        entries(gs, 3, 5, None),
+        vec![ents[2].clone(), conflicts[0].clone()],
+        "storage: entries after conflict"
+    );
+
+    let hs = HardState {
+        term: 2,
+        vote: 1,
+        commit: 3,
+    };
+    gs.set_hardstate(hs.clone()).await.unwrap();
+    assert_eq!(
+        gs.initial_state().unwrap().hard_state,
+        hs,
+        "storage: hard state"
+    );
+    gs.set_hardstate_commit(4).await.unwrap();
+    assert_eq!(
+        gs.initial_state().unwrap().hard_state.commit,
+        4,
+        "storage: commit of hard state"
+    );
+
+    let cs = ConfState {
+        voters: vec![1, 2, 3],
+        ..Default::default()
+    };
+    gs.set_confstate(cs.clone()).await.unwrap();
+    assert_eq!(
+        gs.initial_state().unwrap().conf_state,
+        cs,
+        "storage: conf state"
+    );
+
+    gs.set_applied(3).await.unwrap();
+    assert_eq!(gs.get_applied().await.unwrap(), 3, "storage: applied index");
+
+    // the entries before the compact index are not readable.
+    gs.compact(3).await.unwrap();
+    assert_eq!(
+        gs.first_index().unwrap(),
+        3,
+        "storage: first index after compact"
+    );
+    assert_eq!(
+        gs.last_index().unwrap(),
+        4,
+        "storage: last index after compact"
+    );
+    assert_eq!(
+        gs.entries(1, 5, None, GetEntriesContext::empty(false)),
+        Err(raft::Error::Store(StorageError::Compacted)),
+        "storage: compacted entries"
+    );
+    assert_eq!(
+        entries(gs, 3, 5, None).len(),
+        2,
+        "storage: entries after compact"
+    );
+}
+
+/// Check the state of the group persisted by `storage` is recovered after it's
+/// reopened by `reopen`, which drops the storage and opens it again on the
+/// same data, e.g. the same path. The group must not exist in the storage.
+pub async fn check_storage_recovery<RS, MRS, F>(
+    storage: MRS,
+    group_id: u64,
+    replica_id: u64,
+    reopen: F,
+) where
+    RS: RaftStorage,
+    MRS: MultiRaftStorage<RS>,
+    F: FnOnce(MRS) -> MRS,
+{
+    let ents = (1..=3).map(|index| new_entry(index, 1)).collect::<Vec<_>>();
+    let hs = HardState {
+        term: 1,
+        vote: replica_id,
+        commit: 2,
+    };
+    let cs = ConfState {
+        voters: vec![replica_id],
+        ..Default::default()
+    };
+    {
+        let gs = storage.group_storage(group_id, replica_id).await.unwrap();
+        let results = storage
+            .write_batch(vec![GroupWrite {
+                group_id,
+                replica_id,
+                entries: ents.clone(),
+                hard_state: Some(hs.clone()),
+            }])
+            .await;
+        for res in results {
+            res.unwrap();
+        }
+        gs.set_confstate(cs.clone()).await.unwrap();
+        gs.set_applied(2).await.unwrap();
+    }
+
+    // the state acknowledged before crash is recovered.
+    let storage = reopen(storage);
+    let gs = storage.group_storage(group_id, replica_id).await.unwrap();
+    let state = gs.initial_state().unwrap();
+    assert_eq!(state.hard_state, hs, "recovery: hard state");
+    assert_eq!(state.conf_state, cs, "recovery: conf state");
+    assert_eq!(gs.last_index().unwrap(), 3, "recovery: last index");
+    assert_eq!(entries(&gs, 1, 4, None), ents, "recovery: entries");
+    assert_eq!(
+        gs.get_applied().await.unwrap(),
+        2,
+        "recovery: applied index"
+    );
+    let storages = storage.scan_group_storages().await.unwrap();
+    assert!(
+        storages.contains(&(group_id, replica_id)),
+        "recovery: group storages"
+    );
+}
+
+/// Check the snapshot built by `source` is restored by `target`, i.e. the
+/// snapshot built by `target` after the snapshot of `source` is installed
+/// has the same data. `source` has the state of the group applied at
+/// `applied_index`, the snapshot data must be encoded deterministically.
+pub async fn check_snapshot_round_trip<S>(
+    source: &S,
+    target: &S,
+    group_id: u64,
+    replica_id: u64,
+    applied_index: u64,
+    applied_term: u64,
+    conf_state: ConfState,
+) where
+    S: RaftSnapshotWriter + RaftSnapshotReader,
+{
+    source
+        .build_snapshot(
+            group_id,
+            replica_id,
+            applied_index,
+            applied_term,
+            conf_state.clone(),
+        )
+        .await
+        .unwrap();
+    let data = source.load_snapshot(group_id, replica_id).unwrap();
+
+    RaftSnapshotWriter::install_snapshot(target, group_id, replica_id, data.clone())
+        .await
+        .unwrap();
+    target
+        .build_snapshot(
+            group_id,
+            replica_id,
+            applied_index,
+            applied_term,
+            conf_state,
+        )
+        .await
+        .unwrap();
+    let restored = target.load_snapshot(group_id, replica_id).unwrap();
+    assert_eq!(
+        restored, data,
+        "snapshot: restored state differs from source"
+    );
+}
+
+/// Check the state machine applies the entries idempotently. After a crash,
+/// the entries after the applied index persisted are applied again, so the
+/// state must not change by applying the entries applied. `observe` returns
+/// the state of the group in the state machine.
+pub async fn check_state_machine<W, R, M, S, F>(
+    state_machine: &M,
+    group_id: u64,
+    replica_id: u64,
+    datas: Vec<W>,
+    observe: F,
+) where
+    W: ProposeData,
+    R: ProposeResponse,
+    M: StateMachine<W, R>,
+    S: PartialEq + Debug,
+    F: Fn() -> S,
+{
+    let state = GroupState::new();
+    let applys = |from: usize| {
+        datas[from..]
+            .iter()
+            .enumerate()
+            .map(|(i, data)| {
+                Apply::Normal(ApplyNormal {
+                    group_id,
+                    index: (from + i + 1) as u64,
+                    term: 1,
+                    data: data.clone(),
+                    context: None,
+                    proposer: None,
+                    is_conf_change: false,
+                    tx: None,
+                })
+            })
+            .collect::<Vec<_>>()
+    };
+
+    state_machine
+        .apply(group_id, replica_id, &state, applys(0))
+        .await;
+    let applied = observe();
+
+    // the whole batch is applied again.
+    state_machine
+        .apply(group_id, replica_id, &state, applys(0))
+        .await;
+    assert_eq!(observe(), applied, "state machine: entries applied again");
+
+    // the suffix of the batch is applied again.
+    state_machine
+        .apply(group_id, replica_id, &state, applys(datas.len() / 2))
+        .await;
+    assert_eq!(observe(), applied, "state machine: suffix applied again");
+}
+
+#[cfg(all(test, not(feature = "prost-data")))]
+mod test {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use futures::Future;
+
+    use crate::rsm::Apply;
+    use crate::rsm::StateMachine;
+    use crate::storage::MultiRaftMemoryStorage;
+    use crate::storage::MultiRaftStorage;
+    use crate::GroupState;
+
+    use super::check_raft_storage;
+    use super::check_state_machine;
+    use super::check_storage_recovery;
+
+    /// The key value state machine which skips the entries applied.
+    #[derive(Clone, Default)]
+    struct KvStateMachine {
+        kvs: Arc<Mutex<(u64, HashMap<String, String>)>>,
+    }
+
+    impl StateMachine<(String, String), ()> for KvStateMachine {
+        type ApplyFuture<'life0> = impl Future<Output = ()> + 'life0
+        where
+            Self: 'life0;
+        fn apply(
+            &self,
+            _: u64,
+            _: u64,
+            _: &GroupState,
+            applys: Vec<Apply<(String, String), ()>>,
+        ) -> Self::ApplyFuture<'_> {
+            async move {
+                let mut kvs = self.kvs.lock().unwrap();
+                for apply in applys {
+                    if let Apply::Normal(normal) = apply {
+                        if normal.index > kvs.0 {
+                            kvs.0 = normal.index;
+                            kvs.1.insert(normal.data.0, normal.data.1);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conformance_memory_storage() {
+        let storage = MultiRaftMemoryStorage::new(1);
+        let gs = storage.group_storage(1, 1).await.unwrap();
+        check_raft_storage(&gs).await;
+        // the memory storage is recovered by itself.
+        check_storage_recovery(storage, 2, 1, |storage| storage).await;
+    }
+
+    #[tokio::test]
+    async fn test_conformance_state_machine() {
+        let state_machine = KvStateMachine::default();
+        let datas = (0..4)
+            .map(|i| (format!("key-{}", i % 2), format!("value-{}", i)))
+            .collect::<Vec<_>>();
+        check_state_machine(&state_machine, 1, 1, datas, || {
+            state_machine.kvs.lock().unwrap().clone()
+        })
+        .await;
+    }
+}
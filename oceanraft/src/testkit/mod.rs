@@ -0,0 +1,4 @@
+//! The kits to test the implementations of the traits of the crate, enabled
+//! by the `testkit` feature.
+pub mod conformance;
+pub mod workload;
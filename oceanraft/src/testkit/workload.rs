@@ -0,0 +1,666 @@
+//! The randomized workload to soak-test the clusters and the state machines
+//! of the applications. The workload issues writes, reads, membership
+//! changes, leader transfers and faults against the nodes connected by the
+//! `LocalTransport`, checks the invariants of raft while running and checks
+//! the state machines of the replicas converge after the faults are healed,
+//! e.g.
+//!
+//! ```ignore
+//! let mut workload = Workload::<MyType, _>::new(WorkloadConfig::default(), |rng| MyData {
+//!     key: rng.gen(),
+//! });
+//! let stats = workload
+//!     .run(WorkloadCluster {
+//!         nodes: &nodes,
+//!         storages: &storages,
+//!         transport: &transport,
+//!         tickers: &tickers,
+//!         apply_rxs: &mut apply_rxs,
+//!         groups: &mut groups,
+//!     })
+//!     .await;
+//! ```
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+use raft::GetEntriesContext;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand::SeedableRng;
+use tokio::sync::mpsc::Receiver;
+use tokio::time::timeout;
+use tracing::debug;
+use tracing::info;
+
+use crate::multiraft::MultiRaftMessageSenderImpl;
+use crate::prelude::ConfChangeType;
+use crate::prelude::Entry;
+use crate::prelude::MembershipChangeData;
+use crate::prelude::ReplicaDesc;
+use crate::prelude::SingleMembershipChange;
+use crate::rsm::Apply;
+use crate::storage::MultiRaftStorage;
+use crate::storage::Storage;
+use crate::tick::ManualTick;
+use crate::transport::LocalTransport;
+use crate::Event;
+use crate::MultiRaft;
+use crate::MultiRaftTypeSpecialization;
+
+/// The kind of operation issued by `Workload`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WorkloadOp {
+    Write,
+    Read,
+    Membership,
+    TransferLeader,
+    Fault,
+}
+
+/// Configuration of `Workload`.
+///
+/// The weights decide the probability of each kind of operation,
+/// `0` disables the operation.
+#[derive(Debug, Clone)]
+pub struct WorkloadConfig {
+    /// Seed of the random generator, the same seed issues the same
+    /// sequence of operations.
+    pub seed: u64,
+    /// The number of operations to issue.
+    pub ops: usize,
+    /// The groups that operations issued to, the groups must be created
+    /// in the cluster before running.
+    pub groups: Vec<u64>,
+    pub write_weight: u32,
+    pub read_weight: u32,
+    pub membership_weight: u32,
+    pub transfer_leader_weight: u32,
+    pub fault_weight: u32,
+    /// Timeout of each operation.
+    pub op_timeout: Duration,
+    /// Interval to tick all nodes of the cluster in background.
+    pub tick_interval: Duration,
+    /// Minimum replicas of each group, membership changes never remove
+    /// replica below it.
+    pub min_replicas: usize,
+    /// Timeout for the state machines of the replicas to converge after
+    /// the faults are healed.
+    pub converge_timeout: Duration,
+}
+
+impl Default for WorkloadConfig {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            ops: 100,
+            groups: vec![1],
+            write_weight: 60,
+            read_weight: 20,
+            membership_weight: 5,
+            transfer_leader_weight: 5,
+            fault_weight: 10,
+            op_timeout: Duration::from_millis(500),
+            tick_interval: Duration::from_millis(10),
+            min_replicas: 3,
+            converge_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Counters of issued operations, failed operations are expected when
+/// faults are injected or leader is changed.
+#[derive(Debug, Default, Clone)]
+pub struct WorkloadStats {
+    pub writes: usize,
+    pub write_errors: usize,
+    pub reads: usize,
+    pub read_errors: usize,
+    pub memberships: usize,
+    pub membership_errors: usize,
+    pub transfer_leaders: usize,
+    pub faults: usize,
+    pub heals: usize,
+}
+
+/// The cluster the workload issued against, the node id of `nodes[i]`,
+/// `storages[i]`, `tickers[i]` and `apply_rxs[i]` is `i + 1`.
+pub struct WorkloadCluster<'a, T>
+where
+    T: MultiRaftTypeSpecialization,
+{
+    pub nodes: &'a [Arc<MultiRaft<T, LocalTransport<MultiRaftMessageSenderImpl>>>],
+    pub storages: &'a [T::MS],
+    /// The transport connects the nodes, the faults are injected by
+    /// disconnecting the nodes.
+    pub transport: &'a LocalTransport<MultiRaftMessageSenderImpl>,
+    pub tickers: &'a [ManualTick],
+    /// The receivers of the applys forwarded by the state machines of the
+    /// nodes, the workload responds the normal applys.
+    pub apply_rxs: &'a mut [Option<Receiver<Vec<Apply<T::D, T::R>>>>],
+    /// The nodes of the groups, updated by the membership changes of the
+    /// workload.
+    pub groups: &'a mut HashMap<u64, Vec<u64>>,
+}
+
+/// Checks invariants of the cluster while workload running.
+///
+/// - election safety: at most one leader is observed by events per term.
+/// - log matching: if two logs contain an entry with the same index and term,
+///   then the logs are identical in all entries up through the given index.
+/// - applied monotonicity: the index of applys of each replica is strictly increasing.
+/// - state machine convergence: the replicas apply the same entry at the same
+///   index and apply up to the same index once the faults are healed.
+#[derive(Debug, Default)]
+pub struct InvariantChecker {
+    // (group_id, term) -> leader_id
+    leaders: HashMap<(u64, u64), u64>,
+    // (node_id, group_id) -> index -> (term, applied data)
+    applied: HashMap<(u64, u64), BTreeMap<u64, (u64, String)>>,
+}
+
+impl InvariantChecker {
+    pub fn observe_event(&mut self, node_id: u64, event: &Event) {
+        if let Event::LederElection(elect) = event {
+            if elect.leader_id == 0 {
+                return;
+            }
+            match self.leaders.get(&(elect.group_id, elect.term)) {
+                Some(leader_id) => assert_eq!(
+                    *leader_id, elect.leader_id,
+                    "election safety violated: node {} observed leader {} of group {} at term {}, but leader {} observed before",
+                    node_id, elect.leader_id, elect.group_id, elect.term, leader_id
+                ),
+                None => {
+                    self.leaders
+                        .insert((elect.group_id, elect.term), elect.leader_id);
+                }
+            }
+        }
+    }
+
+    pub fn observe_apply<D, R>(&mut self, node_id: u64, apply: &Apply<D, R>)
+    where
+        D: crate::ProposeData,
+        R: crate::ProposeResponse,
+    {
+        let (group_id, data) = match apply {
+            Apply::NoOp(noop) => (noop.group_id, String::new()),
+            Apply::Normal(normal) => (normal.group_id, format!("{:?}", normal.data)),
+            Apply::Membership(membership) => {
+                (membership.group_id, format!("{:?}", membership.change_data))
+            }
+            Apply::Admin(admin) => (admin.group_id, String::new()),
+        };
+        let index = apply.get_index();
+        let applied = self.applied.entry((node_id, group_id)).or_default();
+        if let Some((applied_index, _)) = applied.last_key_value() {
+            assert!(
+                index > *applied_index,
+                "applied monotonicity violated: node {} group {} applied {} after {}",
+                node_id,
+                group_id,
+                index,
+                applied_index
+            );
+        }
+        applied.insert(index, (apply.get_term(), data));
+    }
+
+    /// Returns the last index applied by the replica of the group on the node.
+    pub fn applied_index(&self, node_id: u64, group_id: u64) -> u64 {
+        self.applied
+            .get(&(node_id, group_id))
+            .and_then(|applied| applied.last_key_value())
+            .map_or(0, |(index, _)| *index)
+    }
+
+    /// Check the replicas of the group on `nodes` applied the same entries
+    /// at the same indexes and applied up to the same index.
+    pub fn check_convergence(&self, group_id: u64, nodes: &[u64]) {
+        let empty = BTreeMap::new();
+        for (i, a_node) in nodes.iter().enumerate() {
+            let a = self.applied.get(&(*a_node, group_id)).unwrap_or(&empty);
+            for b_node in nodes[i + 1..].iter() {
+                let b = self.applied.get(&(*b_node, group_id)).unwrap_or(&empty);
+                assert_eq!(
+                    self.applied_index(*a_node, group_id),
+                    self.applied_index(*b_node, group_id),
+                    "state machine convergence violated: group {} applied index differs between node {} and node {}",
+                    group_id,
+                    a_node,
+                    b_node
+                );
+                for (index, a_apply) in a.iter() {
+                    if let Some(b_apply) = b.get(index) {
+                        assert_eq!(
+                            a_apply, b_apply,
+                            "state machine convergence violated: group {} applied {} differs between node {} and node {}",
+                            group_id, index, a_node, b_node
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Check log matching of the group between replicas of `nodes` by inspecting storage.
+    pub async fn check_log_matching<T>(storages: &[T::MS], group_id: u64, nodes: &[u64])
+    where
+        T: MultiRaftTypeSpecialization,
+    {
+        let mut logs = vec![];
+        for node_id in nodes.iter() {
+            let storage = &storages[*node_id as usize - 1];
+            let replica_id = match storage.replica_for_node(group_id, *node_id).await.unwrap() {
+                Some(rd) => rd.replica_id,
+                None => continue,
+            };
+            let gs = storage.group_storage(group_id, replica_id).await.unwrap();
+            let first_index = gs.first_index().unwrap();
+            let last_index = gs.last_index().unwrap();
+            let ents = match first_index <= last_index {
+                true => gs
+                    .entries(
+                        first_index,
+                        last_index + 1,
+                        u64::MAX,
+                        GetEntriesContext::empty(false),
+                    )
+                    .unwrap(),
+                false => vec![],
+            };
+            logs.push((*node_id, ents));
+        }
+
+        for i in 0..logs.len() {
+            for j in i + 1..logs.len() {
+                Self::check_log_pair(group_id, &logs[i], &logs[j]);
+            }
+        }
+    }
+
+    fn check_log_pair(group_id: u64, a: &(u64, Vec<Entry>), b: &(u64, Vec<Entry>)) {
+        let (a_node, a_ents) = a;
+        let (b_node, b_ents) = b;
+        let (a_first, b_first) = match (a_ents.first(), b_ents.first()) {
+            (Some(a), Some(b)) => (a.index, b.index),
+            _ => return,
+        };
+        let lo = std::cmp::max(a_first, b_first);
+        let hi = std::cmp::min(a_ents.last().unwrap().index, b_ents.last().unwrap().index);
+        if lo > hi {
+            return;
+        }
+
+        // find the last index that has the same term, then all entries
+        // before it must be identical.
+        let mut matched = false;
+        for index in (lo..=hi).rev() {
+            let a_ent = &a_ents[(index - a_first) as usize];
+            let b_ent = &b_ents[(index - b_first) as usize];
+            if !matched && a_ent.term == b_ent.term {
+                matched = true;
+            }
+
+            if matched {
+                assert!(
+                    a_ent.term == b_ent.term && a_ent.data == b_ent.data,
+                    "log matching violated: group {} entry {} differs between node {} and node {}",
+                    group_id,
+                    index,
+                    a_node,
+                    b_node
+                );
+            }
+        }
+    }
+}
+
+/// A randomized workload generator issues writes, reads, membership changes,
+/// leader transfers and faults against a cluster while checking invariants.
+///
+/// The data of write is generated by `gen_write`, so users can soak-test their
+/// state machines with their own data. The normal applys are responded with
+/// the default response.
+pub struct Workload<T, F>
+where
+    T: MultiRaftTypeSpecialization,
+    F: FnMut(&mut StdRng) -> T::D,
+{
+    cfg: WorkloadConfig,
+    rng: StdRng,
+    gen_write: F,
+    checker: InvariantChecker,
+    stats: WorkloadStats,
+    // disconnected node pairs.
+    partitions: Vec<(u64, u64)>,
+    _m: PhantomData<T>,
+}
+
+impl<T, F> Workload<T, F>
+where
+    T: MultiRaftTypeSpecialization,
+    T::R: Default,
+    F: FnMut(&mut StdRng) -> T::D,
+{
+    pub fn new(cfg: WorkloadConfig, gen_write: F) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(cfg.seed),
+            cfg,
+            gen_write,
+            checker: InvariantChecker::default(),
+            stats: WorkloadStats::default(),
+            partitions: vec![],
+            _m: PhantomData,
+        }
+    }
+
+    /// Run the workload against the cluster, panics if any invariant is violated.
+    pub async fn run(&mut self, mut cluster: WorkloadCluster<'_, T>) -> WorkloadStats {
+        info!(
+            "workload: run {} ops on groups {:?} with seed {}",
+            self.cfg.ops, self.cfg.groups, self.cfg.seed
+        );
+        let events = cluster
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| ((i + 1) as u64, node.subscribe()))
+            .collect::<Vec<_>>();
+
+        let tick_handle = spawn_ticker(cluster.tickers.to_vec(), self.cfg.tick_interval);
+
+        for _ in 0..self.cfg.ops {
+            let op = self.next_op();
+            debug!("workload: issue {:?}", op);
+            match op {
+                WorkloadOp::Write => self.write(&mut cluster).await,
+                WorkloadOp::Read => self.read(&mut cluster).await,
+                WorkloadOp::Membership => self.membership(&mut cluster).await,
+                WorkloadOp::TransferLeader => self.transfer_leader(&mut cluster).await,
+                WorkloadOp::Fault => self.fault(&mut cluster).await,
+            }
+
+            for (node_id, rx) in events.iter() {
+                while let Ok(Ok(event)) = timeout(Duration::from_millis(1), rx.recv()).await {
+                    self.checker.observe_event(*node_id, &event);
+                }
+            }
+            self.drain_applys(&mut cluster);
+        }
+
+        // heal all faults, then check the state machines converge and
+        // check logs.
+        for (from, to) in std::mem::take(&mut self.partitions) {
+            cluster.transport.reconnect(from, to).await;
+        }
+        for group_id in self.cfg.groups.clone() {
+            self.wait_convergence(&mut cluster, group_id).await;
+        }
+        tick_handle.abort();
+
+        for group_id in self.cfg.groups.iter() {
+            let nodes = cluster.groups.get(group_id).cloned().unwrap_or_default();
+            InvariantChecker::check_log_matching::<T>(cluster.storages, *group_id, &nodes).await;
+        }
+
+        info!("workload: finished, stats = {:?}", self.stats);
+        self.stats.clone()
+    }
+
+    /// Wait the replicas of the group apply up to the commit index, then
+    /// check the state machines of the replicas converge.
+    async fn wait_convergence(&mut self, cluster: &mut WorkloadCluster<'_, T>, group_id: u64) {
+        let interval = self.cfg.tick_interval;
+        let mut waited = Duration::ZERO;
+        loop {
+            self.drain_applys(cluster);
+            let members = Self::members(cluster, group_id);
+            let commit_index = members
+                .iter()
+                .filter_map(|node_id| cluster.nodes[*node_id as usize - 1].group_state(group_id))
+                .map(|state| state.get_commit_index())
+                .max()
+                .unwrap_or(0);
+            let converged = members
+                .iter()
+                .all(|node_id| self.checker.applied_index(*node_id, group_id) == commit_index);
+            if converged || waited >= self.cfg.converge_timeout {
+                info!(
+                    "workload: group {} replicas on nodes {:?} applied to {}",
+                    group_id, members, commit_index
+                );
+                self.checker.check_convergence(group_id, &members);
+                return;
+            }
+            tokio::time::sleep(interval).await;
+            waited += interval;
+        }
+    }
+
+    /// Returns the nodes whose replicas are in the configuration of the group,
+    /// the configuration of the replica with the highest commit index is used.
+    fn members(cluster: &WorkloadCluster<'_, T>, group_id: u64) -> Vec<u64> {
+        let states = cluster
+            .nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(i, node)| {
+                node.group_state(group_id)
+                    .map(|state| ((i + 1) as u64, state))
+            })
+            .collect::<Vec<_>>();
+        let conf_state = match states
+            .iter()
+            .max_by_key(|(_, state)| state.get_commit_index())
+        {
+            Some((_, state)) => state.get_conf_state(),
+            None => return vec![],
+        };
+        states
+            .iter()
+            .filter(|(_, state)| {
+                let replica_id = state.get_replica_id();
+                conf_state.voters.contains(&replica_id) || conf_state.learners.contains(&replica_id)
+            })
+            .map(|(node_id, _)| *node_id)
+            .collect()
+    }
+
+    fn next_op(&mut self) -> WorkloadOp {
+        let weights = [
+            (WorkloadOp::Write, self.cfg.write_weight),
+            (WorkloadOp::Read, self.cfg.read_weight),
+            (WorkloadOp::Membership, self.cfg.membership_weight),
+            (WorkloadOp::TransferLeader, self.cfg.transfer_leader_weight),
+            (WorkloadOp::Fault, self.cfg.fault_weight),
+        ];
+        let total = weights.iter().map(|(_, w)| *w).sum::<u32>();
+        assert_ne!(total, 0, "all weights of workload are zero");
+        let mut n = self.rng.gen_range(0..total);
+        for (op, w) in weights.iter() {
+            if n < *w {
+                return *op;
+            }
+            n -= *w;
+        }
+        unreachable!()
+    }
+
+    fn rand_group(&mut self) -> u64 {
+        self.cfg.groups[self.rng.gen_range(0..self.cfg.groups.len())]
+    }
+
+    fn rand_member(&mut self, cluster: &WorkloadCluster<'_, T>, group_id: u64) -> u64 {
+        let nodes = cluster.groups.get(&group_id).expect("group not exists");
+        nodes[self.rng.gen_range(0..nodes.len())]
+    }
+
+    fn drain_applys(&mut self, cluster: &mut WorkloadCluster<'_, T>) {
+        for (i, rx) in cluster.apply_rxs.iter_mut().enumerate() {
+            let rx = match rx {
+                Some(rx) => rx,
+                None => continue,
+            };
+            while let Ok(applys) = rx.try_recv() {
+                for apply in applys.into_iter() {
+                    self.checker.observe_apply((i + 1) as u64, &apply);
+                    if let Apply::Normal(normal) = apply {
+                        normal.tx.map(|tx| tx.send(Ok((T::R::default(), None))));
+                    }
+                }
+            }
+        }
+    }
+
+    async fn write(&mut self, cluster: &mut WorkloadCluster<'_, T>) {
+        let group_id = self.rand_group();
+        let node_id = self.rand_member(cluster, group_id);
+        let data = (self.gen_write)(&mut self.rng);
+        self.stats.writes += 1;
+        let node = cluster.nodes[node_id as usize - 1].clone();
+        let mut fut = Box::pin(node.write(group_id, 0, None, data));
+        // the applys are drained while waiting, the state machines are
+        // blocked otherwise.
+        let mut waited = Duration::ZERO;
+        let res = loop {
+            match timeout(self.cfg.tick_interval, &mut fut).await {
+                Ok(res) => break Some(res),
+                Err(_) => {
+                    self.drain_applys(cluster);
+                    waited += self.cfg.tick_interval;
+                    if waited >= self.cfg.op_timeout {
+                        break None;
+                    }
+                }
+            }
+        };
+        if !matches!(res, Some(Ok(_))) {
+            debug!(
+                "workload: write group {} on node {} failed: {:?}",
+                group_id, node_id, res
+            );
+            self.stats.write_errors += 1;
+        }
+    }
+
+    async fn read(&mut self, cluster: &mut WorkloadCluster<'_, T>) {
+        let group_id = self.rand_group();
+        let node_id = self.rand_member(cluster, group_id);
+        self.stats.reads += 1;
+        let fut = cluster.nodes[node_id as usize - 1].read_index(group_id, None);
+        if !matches!(timeout(self.cfg.op_timeout, fut).await, Ok(Ok(_))) {
+            debug!(
+                "workload: read group {} on node {} failed",
+                group_id, node_id
+            );
+            self.stats.read_errors += 1;
+        }
+    }
+
+    async fn membership(&mut self, cluster: &mut WorkloadCluster<'_, T>) {
+        let group_id = self.rand_group();
+        let node_id = self.rand_member(cluster, group_id);
+        let members = cluster.groups.get(&group_id).cloned().unwrap_or_default();
+        let candidates = (1..=cluster.nodes.len() as u64)
+            .filter(|id| !members.contains(id))
+            .collect::<Vec<_>>();
+        self.stats.memberships += 1;
+
+        // add replica if there are free nodes, otherwise remove replica.
+        if !candidates.is_empty() && (members.len() <= self.cfg.min_replicas || self.rng.gen()) {
+            let target = candidates[self.rng.gen_range(0..candidates.len())];
+            let fut = cluster.nodes[node_id as usize - 1].add_replica(group_id, target, None, None);
+            match timeout(self.cfg.op_timeout, fut).await {
+                Ok(Ok(_)) => cluster.groups.get_mut(&group_id).unwrap().push(target),
+                _ => self.stats.membership_errors += 1,
+            }
+            return;
+        }
+
+        if members.len() <= self.cfg.min_replicas {
+            self.stats.membership_errors += 1;
+            return;
+        }
+
+        let target = members[self.rng.gen_range(0..members.len())];
+        let rd = cluster.storages[target as usize - 1]
+            .replica_for_node(group_id, target)
+            .await
+            .unwrap()
+            .unwrap();
+        let data = MembershipChangeData {
+            changes: vec![SingleMembershipChange {
+                node_id: target,
+                replica_id: rd.replica_id,
+                change_type: ConfChangeType::RemoveNode as i32,
+                witness: false,
+            }],
+            replicas: vec![ReplicaDesc {
+                node_id: target,
+                group_id,
+                replica_id: rd.replica_id,
+                witness: false,
+            }],
+            ..Default::default()
+        };
+        let fut = cluster.nodes[node_id as usize - 1].membership(group_id, None, None, data);
+        match timeout(self.cfg.op_timeout, fut).await {
+            Ok(Ok(_)) => cluster
+                .groups
+                .get_mut(&group_id)
+                .unwrap()
+                .retain(|id| *id != target),
+            _ => self.stats.membership_errors += 1,
+        }
+    }
+
+    async fn transfer_leader(&mut self, cluster: &mut WorkloadCluster<'_, T>) {
+        let group_id = self.rand_group();
+        let node_id = self.rand_member(cluster, group_id);
+        self.stats.transfer_leaders += 1;
+        let fut = cluster.nodes[node_id as usize - 1].campaign_group(group_id);
+        let _ = timeout(self.cfg.op_timeout, fut).await;
+    }
+
+    async fn fault(&mut self, cluster: &mut WorkloadCluster<'_, T>) {
+        // heal a partition or make a new one.
+        if !self.partitions.is_empty() && self.rng.gen() {
+            let i = self.rng.gen_range(0..self.partitions.len());
+            let (from, to) = self.partitions.swap_remove(i);
+            debug!("workload: reconnect node {} and node {}", from, to);
+            cluster.transport.reconnect(from, to).await;
+            self.stats.heals += 1;
+            return;
+        }
+
+        let nodes = cluster.nodes.len() as u64;
+        if nodes < 2 {
+            return;
+        }
+        let from = self.rng.gen_range(1..=nodes);
+        let mut to = self.rng.gen_range(1..=nodes);
+        while to == from {
+            to = self.rng.gen_range(1..=nodes);
+        }
+        debug!("workload: disconnect node {} and node {}", from, to);
+        cluster.transport.disconnect(from, to).await;
+        self.partitions.push((from, to));
+        self.stats.faults += 1;
+    }
+}
+
+/// Tick all nodes periodically in background.
+fn spawn_ticker(mut tickers: Vec<ManualTick>, interval: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            for ticker in tickers.iter_mut() {
+                ticker.non_blocking_tick();
+            }
+            tokio::time::sleep(interval).await;
+        }
+    })
+}
@@ -1,6 +1,4 @@
 use std::sync::Arc;
-#[allow(unused)]
-use std::time::Duration;
 
 use futures::future::BoxFuture;
 use tokio::sync::mpsc::unbounded_channel;
@@ -8,8 +6,6 @@ use tokio::sync::mpsc::UnboundedReceiver;
 use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::oneshot;
 use tokio::sync::Mutex;
-#[allow(unused)]
-use tokio::time::Instant;
 use tokio::time::Interval;
 
 /// Ticker periodically sends tick and provides recv future.
@@ -77,40 +73,50 @@ impl Ticker for ManualTick {
     }
 }
 
-#[tokio::test(flavor = "multi_thread")]
-async fn test_tokio_ticker() {
-    let start = tokio::time::Instant::now();
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
 
-    let mut default_ticker = tokio::time::interval_at(
-        tokio::time::Instant::now() + Duration::from_millis(10),
-        Duration::from_millis(10),
-    );
+    use tokio::time::Instant;
 
-    default_ticker.recv().await; // approximately 10ms have elapsed
-    assert!(start.elapsed() >= Duration::from_millis(10));
+    use super::ManualTick;
+    use super::Ticker;
 
-    tokio::time::sleep(Duration::from_millis(20)).await; // approximately 30ms have elapsed
-    default_ticker.reset();
-    default_ticker.recv().await; // approximately 40ms have elapsed
-    assert!(start.elapsed() >= Duration::from_millis(40));
-}
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_tokio_ticker() {
+        let start = tokio::time::Instant::now();
 
-#[tokio::test(flavor = "multi_thread")]
-async fn test_manual_ticker() {
-    let start = Instant::now();
-    let mut ticker = ManualTick::new();
+        let mut default_ticker = tokio::time::interval_at(
+            tokio::time::Instant::now() + Duration::from_millis(10),
+            Duration::from_millis(10),
+        );
 
-    for _ in 0..10 {
-        ticker.non_blocking_tick();
-        tokio::time::sleep(Duration::from_millis(10)).await;
-    }
+        default_ticker.recv().await; // approximately 10ms have elapsed
+        assert!(start.elapsed() >= Duration::from_millis(10));
 
-    let mut ticks = 0;
-    for _ in 0..10 {
-        ticker.recv().await;
-        ticks += 1;
+        tokio::time::sleep(Duration::from_millis(20)).await; // approximately 30ms have elapsed
+        default_ticker.reset();
+        default_ticker.recv().await; // approximately 40ms have elapsed
+        assert!(start.elapsed() >= Duration::from_millis(40));
     }
 
-    assert_eq!(ticks, 10);
-    assert!(start.elapsed() >= Duration::from_millis(100)); // approximately 100ms have elapsed
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_manual_ticker() {
+        let start = Instant::now();
+        let mut ticker = ManualTick::new();
+
+        for _ in 0..10 {
+            ticker.non_blocking_tick();
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let mut ticks = 0;
+        for _ in 0..10 {
+            ticker.recv().await;
+            ticks += 1;
+        }
+
+        assert_eq!(ticks, 10);
+        assert!(start.elapsed() >= Duration::from_millis(100)); // approximately 100ms have elapsed
+    }
 }
@@ -0,0 +1,166 @@
+use std::fmt::Debug;
+
+use raft::prelude::ConfState;
+use raft::StateRole;
+
+/// A member replica of the group, see `GroupTopology`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplicaTopology {
+    pub replica_id: u64,
+    /// The node of the replica, `NO_NODE` if the replica desc is not found
+    /// in the storage.
+    pub node_id: u64,
+    pub learner: bool,
+    pub witness: bool,
+    /// Whether the replica is recently active, only the leader tracks the
+    /// progress of the replicas so it's `None` unless the replica of the
+    /// group on the node is the leader.
+    pub reachable: Option<bool>,
+    /// The matched index of the replica tracked by the leader, `None` unless
+    /// the replica of the group on the node is the leader.
+    pub matched: Option<u64>,
+}
+
+/// The membership of a group as seen by the replica on the node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupTopology {
+    pub group_id: u64,
+    /// The replica of the group on the node.
+    pub replica_id: u64,
+    pub role: StateRole,
+    pub leader_id: u64,
+    /// The node of the leader, `NO_NODE` if the leader is unknown.
+    pub leader_node_id: u64,
+    /// The voters and the learners of the group in the order of replica id,
+    /// the outgoing voters of a joint configuration are included.
+    pub replicas: Vec<ReplicaTopology>,
+    /// The group is parked, the membership is the last one before parking.
+    pub parked: bool,
+}
+
+/// The groups on a node in the order of group id.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NodeTopology {
+    pub node_id: u64,
+    pub groups: Vec<GroupTopology>,
+}
+
+/// The topologies of the nodes in the order of node id, see
+/// `MultiRaft::cluster_topology`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClusterTopology {
+    pub nodes: Vec<NodeTopology>,
+}
+
+impl ClusterTopology {
+    /// Returns the nodes which report a replica of the group in their own
+    /// views, in the order of node id.
+    pub fn locate(&self, group_id: u64) -> Vec<u64> {
+        self.nodes
+            .iter()
+            .filter(|node| {
+                node.groups
+                    .binary_search_by_key(&group_id, |group| group.group_id)
+                    .is_ok()
+            })
+            .map(|node| node.node_id)
+            .collect()
+    }
+}
+
+/// TopologyProvider provides the topologies of the peer nodes for
+/// `MultiRaft::cluster_topology`, e.g. collected by gossip or fetched from
+/// a control plane which aggregates `MultiRaft::topology` of every node.
+pub trait TopologyProvider: Debug + Send + Sync + 'static {
+    /// Returns the latest known topologies of the peer nodes, the topology of
+    /// the node itself is ignored if it's returned.
+    fn peer_topologies(&self) -> Vec<NodeTopology>;
+}
+
+/// Provides no peer, the cluster view only contains the node itself. It's the
+/// default provider.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoTopologyProvider;
+
+impl TopologyProvider for NoTopologyProvider {
+    fn peer_topologies(&self) -> Vec<NodeTopology> {
+        vec![]
+    }
+}
+
+/// The (replica id, learner) of the members of the configuration in the order
+/// of replica id.
+pub(crate) fn conf_members(conf_state: &ConfState) -> Vec<(u64, bool)> {
+    let mut members = conf_state
+        .voters
+        .iter()
+        .chain(conf_state.voters_outgoing.iter())
+        .map(|id| (*id, false))
+        .chain(
+            conf_state
+                .learners
+                .iter()
+                .chain(conf_state.learners_next.iter())
+                .map(|id| (*id, true)),
+        )
+        .collect::<Vec<_>>();
+    // a voter demoted by the joint configuration is both outgoing voter and
+    // next learner, it's a voter until the configuration leaves joint.
+    members.sort_unstable();
+    members.dedup_by_key(|(id, _)| *id);
+    members
+}
+
+#[cfg(test)]
+mod test {
+    use raft::prelude::ConfState;
+    use raft::StateRole;
+
+    use super::conf_members;
+    use super::ClusterTopology;
+    use super::GroupTopology;
+    use super::NodeTopology;
+
+    #[test]
+    fn test_conf_members() {
+        let conf_state = ConfState {
+            voters: vec![3, 1],
+            voters_outgoing: vec![1, 2],
+            learners: vec![4],
+            learners_next: vec![2],
+            ..Default::default()
+        };
+        assert_eq!(
+            conf_members(&conf_state),
+            vec![(1, false), (2, false), (3, false), (4, true)]
+        );
+    }
+
+    #[test]
+    fn test_cluster_topology_locate() {
+        let group = |group_id| GroupTopology {
+            group_id,
+            replica_id: 1,
+            role: StateRole::Follower,
+            leader_id: 0,
+            leader_node_id: 0,
+            replicas: vec![],
+            parked: false,
+        };
+        let cluster = ClusterTopology {
+            nodes: vec![
+                NodeTopology {
+                    node_id: 1,
+                    groups: vec![group(1), group(2)],
+                },
+                NodeTopology {
+                    node_id: 2,
+                    groups: vec![group(2), group(3)],
+                },
+            ],
+        };
+        assert_eq!(cluster.locate(2), vec![1, 2]);
+        assert_eq!(cluster.locate(3), vec![2]);
+        assert!(cluster.locate(4).is_empty());
+    }
+}
@@ -0,0 +1,265 @@
+use std::cmp;
+use std::fmt::Debug;
+
+use futures::Future;
+
+use crate::prelude::SnapshotChunk;
+use crate::prelude::SnapshotChunkRequest;
+use crate::Error;
+
+/// The max length of the chunks requested by `SnapshotDownload` if the
+/// `max_len` of the request is `0`.
+pub const DEFAULT_SNAPSHOT_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// SnapshotChunkSource serves the chunks of the snapshots held by the node,
+/// the application keeps the data of the snapshots until the transfers are
+/// done, `snapshot_chunk` builds the chunk from the data.
+pub trait SnapshotChunkSource: Debug + Send + Sync + 'static {
+    /// Read the chunk of the request, it's called by the transport serving
+    /// the requests so the implementations should not block.
+    fn read_chunk(&self, request: &SnapshotChunkRequest) -> Result<SnapshotChunk, Error>;
+}
+
+/// SnapshotChunkTransport fetches the chunks of the snapshots from the other
+/// nodes by the offset based range requests, so the broken transfer of a
+/// large snapshot is resumed from the received offset instead of starting
+/// over, see `SnapshotDownload`.
+pub trait SnapshotChunkTransport: Send + Sync + 'static {
+    /// GAT trait for `fetch_chunk`.
+    type FetchChunkFuture<'life0>: Send + Future<Output = Result<SnapshotChunk, Error>> + 'life0
+    where
+        Self: 'life0;
+    /// Fetch the chunk of the request from `request.to_node`, the chunk is
+    /// verified by the caller.
+    fn fetch_chunk(&self, request: SnapshotChunkRequest) -> Self::FetchChunkFuture<'_>;
+}
+
+/// Build the chunk of the request from the data of the snapshot.
+pub fn snapshot_chunk(request: &SnapshotChunkRequest, data: &[u8]) -> Result<SnapshotChunk, Error> {
+    let total_size = data.len() as u64;
+    let reason = if request.max_len == 0 {
+        Some("max len is 0".to_owned())
+    } else if request.offset > total_size {
+        Some(format!("offset beyond the size {}", total_size))
+    } else {
+        None
+    };
+    if let Some(reason) = reason {
+        return Err(Error::SnapshotChunk {
+            group_id: request.group_id,
+            offset: request.offset,
+            reason,
+        });
+    }
+
+    let end = cmp::min(request.offset.saturating_add(request.max_len), total_size);
+    let data = data[request.offset as usize..end as usize].to_vec();
+    Ok(SnapshotChunk {
+        offset: request.offset,
+        crc32: crc32fast::hash(&data),
+        data,
+        total_size,
+    })
+}
+
+/// Verify the chunk fetched for the request, returns the reason if the chunk
+/// is corrupt or does not make progress.
+pub(super) fn verify_snapshot_chunk(
+    request: &SnapshotChunkRequest,
+    chunk: &SnapshotChunk,
+) -> Result<(), String> {
+    if chunk.offset != request.offset {
+        return Err(format!(
+            "expected offset {}, got {}",
+            request.offset, chunk.offset
+        ));
+    }
+
+    let len = chunk.data.len() as u64;
+    if len > request.max_len {
+        return Err(format!(
+            "expected at most {} bytes, got {}",
+            request.max_len, len
+        ));
+    }
+    if chunk.offset + len > chunk.total_size {
+        return Err(format!("chunk beyond the size {}", chunk.total_size));
+    }
+    if len == 0 && chunk.offset < chunk.total_size {
+        return Err("empty chunk".to_owned());
+    }
+
+    let crc32 = crc32fast::hash(&chunk.data);
+    if crc32 != chunk.crc32 {
+        return Err(format!(
+            "crc32 mismatch, expected {}, got {}",
+            chunk.crc32, crc32
+        ));
+    }
+    Ok(())
+}
+
+/// The resumable download of the data of a snapshot. The received data is
+/// kept across the failures, so calling `resume` again continues from the
+/// received offset.
+#[derive(Debug, Clone)]
+pub struct SnapshotDownload {
+    request: SnapshotChunkRequest,
+    data: Vec<u8>,
+    total_size: Option<u64>,
+}
+
+impl SnapshotDownload {
+    /// Create the download of the snapshot identified by the request, the
+    /// offset of the request is ignored and `DEFAULT_SNAPSHOT_CHUNK_SIZE` is
+    /// used if the `max_len` is `0`.
+    pub fn new(mut request: SnapshotChunkRequest) -> Self {
+        request.offset = 0;
+        if request.max_len == 0 {
+            request.max_len = DEFAULT_SNAPSHOT_CHUNK_SIZE;
+        }
+        Self {
+            request,
+            data: vec![],
+            total_size: None,
+        }
+    }
+
+    /// The number of bytes received.
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    /// The size of the data of the snapshot, it's known after the first
+    /// chunk is received.
+    #[inline]
+    pub fn total_size(&self) -> Option<u64> {
+        self.total_size
+    }
+
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.total_size == Some(self.offset())
+    }
+
+    /// Returns the data received.
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Fetch the chunks from the received offset until the data is complete,
+    /// the chunk failed to fetch or verify is returned as error and the
+    /// download can be resumed later.
+    pub async fn resume<T: SnapshotChunkTransport>(&mut self, transport: &T) -> Result<(), Error> {
+        while !self.is_done() {
+            let mut request = self.request.clone();
+            request.offset = self.offset();
+            let chunk = transport.fetch_chunk(request.clone()).await?;
+
+            let res = match self.total_size {
+                Some(total_size) if total_size != chunk.total_size => Err(format!(
+                    "snapshot size changed from {} to {}",
+                    total_size, chunk.total_size
+                )),
+                _ => verify_snapshot_chunk(&request, &chunk),
+            };
+            if let Err(reason) = res {
+                return Err(Error::SnapshotChunk {
+                    group_id: request.group_id,
+                    offset: request.offset,
+                    reason,
+                });
+            }
+
+            self.total_size = Some(chunk.total_size);
+            self.data.extend_from_slice(&chunk.data);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    use futures::Future;
+
+    use crate::prelude::SnapshotChunk;
+    use crate::prelude::SnapshotChunkRequest;
+    use crate::Error;
+
+    use super::snapshot_chunk;
+    use super::SnapshotChunkTransport;
+    use super::SnapshotDownload;
+
+    /// Every other fetch fails, and the third fetch returns a corrupt chunk.
+    struct FlakyTransport {
+        data: Vec<u8>,
+        fetches: AtomicUsize,
+    }
+
+    impl SnapshotChunkTransport for FlakyTransport {
+        type FetchChunkFuture<'life0> = impl Future<Output = Result<SnapshotChunk, Error>> + Send + 'life0
+        where
+            Self: 'life0;
+        fn fetch_chunk(&self, request: SnapshotChunkRequest) -> Self::FetchChunkFuture<'_> {
+            async move {
+                let fetches = self.fetches.fetch_add(1, Ordering::SeqCst);
+                if fetches % 2 == 1 {
+                    return Err(Error::BadParameter("interrupted".to_owned()));
+                }
+                let mut chunk = snapshot_chunk(&request, &self.data)?;
+                if fetches == 2 {
+                    chunk.data[0] ^= 1;
+                }
+                Ok(chunk)
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_download_resume() {
+        let data = (0..100u8).collect::<Vec<_>>();
+        let transport = FlakyTransport {
+            data: data.clone(),
+            fetches: AtomicUsize::new(0),
+        };
+        let mut download = SnapshotDownload::new(SnapshotChunkRequest {
+            group_id: 1,
+            max_len: 30,
+            ..Default::default()
+        });
+
+        let mut failures = 0;
+        while let Err(err) = download.resume(&transport).await {
+            failures += 1;
+            assert!(failures < 10, "{}", err);
+        }
+        assert!(download.is_done());
+        assert_eq!(download.total_size(), Some(100));
+        assert_eq!(download.into_data(), data);
+    }
+
+    #[test]
+    fn test_snapshot_chunk() {
+        let data = vec![7; 10];
+        let request = SnapshotChunkRequest {
+            offset: 8,
+            max_len: 4,
+            ..Default::default()
+        };
+        let chunk = snapshot_chunk(&request, &data).unwrap();
+        assert_eq!(chunk.data, vec![7; 2]);
+        assert_eq!(chunk.total_size, 10);
+        assert_eq!(chunk.crc32, crc32fast::hash(&chunk.data));
+
+        let request = SnapshotChunkRequest {
+            offset: 11,
+            max_len: 4,
+            ..Default::default()
+        };
+        assert!(snapshot_chunk(&request, &data).is_err());
+    }
+}
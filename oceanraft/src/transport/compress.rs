@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+#[cfg(feature = "compression")]
+use std::io::Read;
+#[cfg(feature = "compression")]
+use std::io::Write;
+use std::sync::RwLock;
+
+#[cfg(feature = "compression")]
+use flate2::read::DeflateDecoder;
+#[cfg(feature = "compression")]
+use flate2::write::DeflateEncoder;
+use prost::Message;
+#[cfg(feature = "compression")]
+use tracing::warn;
+
+use crate::error::DeserializationError;
+use crate::prelude::Compression;
+use crate::prelude::MultiRaftMessage;
+use crate::prelude::MultiRaftMessageResponse;
+use crate::Error;
+
+/// MessageCompressor compresses the raft messages whose encoding is larger
+/// than the threshold, e.g. the appends with many entries. The message is
+/// compressed only if the receiving node accepts compression, which is
+/// learnt from the responses of the node by `observe_response`, so the
+/// nodes of the old version keep receiving uncompressed messages.
+///
+/// The receiver decompresses the messages by `decompress_message`, it is
+/// done by `MultiRaftMessageSenderImpl` for the transports forwarding the
+/// messages by it.
+///
+/// The messages are sent uncompressed without the `compression` feature, and
+/// the node doesn't accept compression from the others.
+pub struct MessageCompressor {
+    threshold: usize,
+    accepted_nodes: RwLock<HashSet<u64>>,
+}
+
+impl MessageCompressor {
+    /// Create the compressor which compresses the messages larger than
+    /// `threshold` bytes.
+    pub fn new(threshold: usize) -> Self {
+        Self {
+            threshold,
+            accepted_nodes: RwLock::new(HashSet::new()),
+        }
+    }
+
+    /// Learn whether the node accepts compression from its response.
+    pub fn observe_response(&self, to_node: u64, response: &MultiRaftMessageResponse) {
+        let accepted = self.accepted_nodes.read().unwrap().contains(&to_node);
+        if accepted != response.accept_compression {
+            let mut accepted_nodes = self.accepted_nodes.write().unwrap();
+            if response.accept_compression {
+                accepted_nodes.insert(to_node);
+            } else {
+                accepted_nodes.remove(&to_node);
+            }
+        }
+    }
+
+    /// Returns true if the node accepts compression.
+    pub fn accepted(&self, to_node: u64) -> bool {
+        self.accepted_nodes.read().unwrap().contains(&to_node)
+    }
+
+    /// Compress the message if the receiver accepts compression and the
+    /// message is larger than the threshold, otherwise the message is
+    /// returned as is.
+    #[cfg(feature = "compression")]
+    pub fn compress(&self, mut msg: MultiRaftMessage) -> MultiRaftMessage {
+        if msg.compression() != Compression::None || !self.accepted(msg.to_node) {
+            return msg;
+        }
+
+        let raft_msg = match msg.msg.as_ref() {
+            Some(raft_msg) if raft_msg.encoded_len() >= self.threshold => raft_msg,
+            _ => return msg,
+        };
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), flate2::Compression::fast());
+        match encoder
+            .write_all(&raft_msg.encode_to_vec())
+            .and_then(|_| encoder.finish())
+        {
+            Ok(compressed) => {
+                msg.msg = None;
+                msg.compressed_msg = compressed;
+                msg.set_compression(Compression::Deflate);
+            }
+            Err(err) => warn!(
+                "node {}: compress raft msg to node {} error: {}, send uncompressed",
+                msg.from_node, msg.to_node, err
+            ),
+        }
+        msg
+    }
+
+    #[cfg(not(feature = "compression"))]
+    pub fn compress(&self, msg: MultiRaftMessage) -> MultiRaftMessage {
+        msg
+    }
+}
+
+/// Decompress the message compressed by `MessageCompressor` in place, the
+/// uncompressed message is unchanged.
+pub fn decompress_message(msg: &mut MultiRaftMessage) -> Result<(), Error> {
+    match msg.compression() {
+        Compression::None => return Ok(()),
+        Compression::Deflate => {}
+    }
+
+    let data = inflate(&msg.compressed_msg)
+        .map_err(|err| Error::Deserialization(DeserializationError::Decompress(err)))?;
+    let raft_msg = crate::prelude::Message::decode(data.as_slice())
+        .map_err(|err| Error::Deserialization(DeserializationError::Prost(err)))?;
+    msg.msg = Some(raft_msg);
+    msg.compressed_msg = vec![];
+    msg.set_compression(Compression::None);
+    Ok(())
+}
+
+#[cfg(feature = "compression")]
+fn inflate(compressed: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut data = vec![];
+    DeflateDecoder::new(compressed).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// The node doesn't accept compression without the `compression` feature, so
+/// the compressed message is unexpected.
+#[cfg(not(feature = "compression"))]
+fn inflate(_: &[u8]) -> std::io::Result<Vec<u8>> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "deflate requires the compression feature",
+    ))
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod test {
+    use super::decompress_message;
+    use super::MessageCompressor;
+    use crate::prelude::Compression;
+    use crate::prelude::Entry;
+    use crate::prelude::Message;
+    use crate::prelude::MessageType;
+    use crate::prelude::MultiRaftMessage;
+    use crate::prelude::MultiRaftMessageResponse;
+
+    fn new_append(to_node: u64, entries: usize) -> MultiRaftMessage {
+        let mut raft_msg = Message::default();
+        raft_msg.set_msg_type(MessageType::MsgAppend);
+        raft_msg.entries = (1..=entries as u64)
+            .map(|index| Entry {
+                index,
+                term: 1,
+                data: vec![b'x'; 128],
+                ..Default::default()
+            })
+            .collect();
+        MultiRaftMessage {
+            group_id: 1,
+            from_node: 1,
+            to_node,
+            msg: Some(raft_msg),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compress_negotiated_per_node() {
+        let compressor = MessageCompressor::new(1024);
+        compressor.observe_response(
+            2,
+            &MultiRaftMessageResponse {
+                accept_compression: true,
+            },
+        );
+        compressor.observe_response(3, &MultiRaftMessageResponse::default());
+
+        // the node 3 does not accept compression.
+        let msg = compressor.compress(new_append(3, 100));
+        assert_eq!(msg.compression(), Compression::None);
+        assert!(msg.msg.is_some());
+
+        // the small message is not compressed.
+        let msg = compressor.compress(new_append(2, 1));
+        assert_eq!(msg.compression(), Compression::None);
+
+        let expected = new_append(2, 100);
+        let mut msg = compressor.compress(expected.clone());
+        assert_eq!(msg.compression(), Compression::Deflate);
+        assert!(msg.msg.is_none());
+        assert!(msg.compressed_msg.len() < 100 * 128);
+
+        decompress_message(&mut msg).unwrap();
+        assert_eq!(msg, expected);
+    }
+}
@@ -0,0 +1,300 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+/// The consecutive failures after which the endpoint is unhealthy.
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+
+/// The unhealthy endpoint is probed again after the interval.
+const DEFAULT_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// An address of the peer node, e.g. of a NIC or a sidecar in mesh. The
+/// endpoints with the lowest `priority` are preferred, the messages are
+/// spread over them by `weight`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoint {
+    pub addr: String,
+    pub priority: u32,
+    pub weight: u32,
+}
+
+impl Endpoint {
+    pub fn new<A: Into<String>>(addr: A, priority: u32, weight: u32) -> Self {
+        Self {
+            addr: addr.into(),
+            priority,
+            weight,
+        }
+    }
+}
+
+/// The health of the endpoint of the peer node, see `PeerStatus::endpoints`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointHealth {
+    pub addr: String,
+    pub priority: u32,
+    pub weight: u32,
+    /// The endpoint is skipped until it's probed again after the failures
+    /// reach the threshold.
+    pub healthy: bool,
+    pub consecutive_failures: u32,
+    /// The elapsed time since the last failure, `None` if the endpoint never
+    /// failed.
+    pub last_failure: Option<Duration>,
+}
+
+struct EndpointState {
+    endpoint: Endpoint,
+    current_weight: i64,
+    consecutive_failures: u32,
+    last_failure: Option<Instant>,
+}
+
+/// PeerEndpoints selects the endpoint of the peer nodes to send by. The
+/// healthy endpoints of the lowest priority are selected by smooth weighted
+/// round-robin, the endpoints fail over to the next priority once they are
+/// unhealthy. The unhealthy endpoint is probed again after the retry interval
+/// and becomes healthy once the transport reports it succeeded.
+///
+/// The transport reports the result of each send by `report_success` and
+/// `report_failure`.
+#[derive(Clone)]
+pub struct PeerEndpoints {
+    peers: Arc<Mutex<HashMap<u64, Vec<EndpointState>>>>,
+    failure_threshold: u32,
+    retry_interval: Duration,
+}
+
+impl Default for PeerEndpoints {
+    fn default() -> Self {
+        Self {
+            peers: Default::default(),
+            failure_threshold: DEFAULT_FAILURE_THRESHOLD,
+            retry_interval: DEFAULT_RETRY_INTERVAL,
+        }
+    }
+}
+
+impl PeerEndpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The endpoint is unhealthy after the number of consecutive failures,
+    /// default is `3`.
+    pub fn with_failure_threshold(mut self, failure_threshold: u32) -> Self {
+        self.failure_threshold = failure_threshold.max(1);
+        self
+    }
+
+    /// The unhealthy endpoint is probed again after the interval, default is
+    /// `1s`.
+    pub fn with_retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    /// Replace the endpoints of the node, the health of the endpoints kept is
+    /// preserved.
+    pub fn set(&self, node_id: u64, endpoints: Vec<Endpoint>) {
+        let mut peers = self.peers.lock().unwrap();
+        let mut olds = peers.remove(&node_id).unwrap_or_default();
+        let states = endpoints
+            .into_iter()
+            .map(|endpoint| {
+                match olds
+                    .iter()
+                    .position(|old| old.endpoint.addr == endpoint.addr)
+                {
+                    Some(pos) => EndpointState {
+                        endpoint,
+                        ..olds.swap_remove(pos)
+                    },
+                    None => EndpointState {
+                        endpoint,
+                        current_weight: 0,
+                        consecutive_failures: 0,
+                        last_failure: None,
+                    },
+                }
+            })
+            .collect::<Vec<_>>();
+        if !states.is_empty() {
+            peers.insert(node_id, states);
+        }
+    }
+
+    /// Remove the endpoints of the node.
+    pub fn remove(&self, node_id: u64) {
+        self.peers.lock().unwrap().remove(&node_id);
+    }
+
+    /// The number of the endpoints of the node.
+    pub fn count(&self, node_id: u64) -> usize {
+        self.peers.lock().unwrap().get(&node_id).map_or(0, Vec::len)
+    }
+
+    /// Select the address of the node to send by, `None` if the node has no
+    /// endpoints. The endpoint with the least failures is selected if none of
+    /// the endpoints is available.
+    pub fn select(&self, node_id: u64) -> Option<String> {
+        let mut peers = self.peers.lock().unwrap();
+        let states = peers.get_mut(&node_id)?;
+        let available = states
+            .iter()
+            .map(|state| {
+                state.consecutive_failures < self.failure_threshold
+                    || state
+                        .last_failure
+                        .map_or(true, |at| at.elapsed() >= self.retry_interval)
+            })
+            .collect::<Vec<_>>();
+        let priority = match states
+            .iter()
+            .zip(available.iter())
+            .filter(|(_, available)| **available)
+            .map(|(state, _)| state.endpoint.priority)
+            .min()
+        {
+            Some(priority) => priority,
+            None => {
+                return states
+                    .iter()
+                    .min_by_key(|state| state.consecutive_failures)
+                    .map(|state| state.endpoint.addr.clone())
+            }
+        };
+
+        // smooth weighted round-robin over the endpoints of the priority.
+        let mut total = 0;
+        let mut selected: Option<(usize, i64)> = None;
+        for (i, state) in states.iter_mut().enumerate() {
+            if !available[i] || state.endpoint.priority != priority {
+                continue;
+            }
+            let weight = state.endpoint.weight.max(1) as i64;
+            state.current_weight += weight;
+            total += weight;
+            if selected.map_or(true, |(_, current)| state.current_weight > current) {
+                selected = Some((i, state.current_weight));
+            }
+        }
+        let selected = &mut states[selected?.0];
+        selected.current_weight -= total;
+        Some(selected.endpoint.addr.clone())
+    }
+
+    /// Report the message sent by the endpoint succeeded.
+    pub fn report_success(&self, node_id: u64, addr: &str) {
+        self.update(node_id, addr, |state| {
+            state.consecutive_failures = 0;
+        })
+    }
+
+    /// Report the message sent by the endpoint failed.
+    pub fn report_failure(&self, node_id: u64, addr: &str) {
+        self.update(node_id, addr, |state| {
+            state.consecutive_failures += 1;
+            state.last_failure = Some(Instant::now());
+        })
+    }
+
+    /// The health of the endpoints of the node in the order they were set.
+    pub fn health(&self, node_id: u64) -> Vec<EndpointHealth> {
+        let peers = self.peers.lock().unwrap();
+        peers.get(&node_id).map_or(vec![], |states| {
+            states
+                .iter()
+                .map(|state| EndpointHealth {
+                    addr: state.endpoint.addr.clone(),
+                    priority: state.endpoint.priority,
+                    weight: state.endpoint.weight,
+                    healthy: state.consecutive_failures < self.failure_threshold,
+                    consecutive_failures: state.consecutive_failures,
+                    last_failure: state.last_failure.map(|at| at.elapsed()),
+                })
+                .collect()
+        })
+    }
+
+    fn update<F: FnOnce(&mut EndpointState)>(&self, node_id: u64, addr: &str, f: F) {
+        let mut peers = self.peers.lock().unwrap();
+        if let Some(state) = peers
+            .get_mut(&node_id)
+            .and_then(|states| states.iter_mut().find(|state| state.endpoint.addr == addr))
+        {
+            f(state)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::Endpoint;
+    use super::PeerEndpoints;
+
+    #[test]
+    fn test_weighted_round_robin() {
+        let endpoints = PeerEndpoints::new();
+        endpoints.set(
+            1,
+            vec![
+                Endpoint::new("a", 0, 3),
+                Endpoint::new("b", 0, 1),
+                Endpoint::new("backup", 1, 1),
+            ],
+        );
+        let selected = (0..8)
+            .map(|_| endpoints.select(1).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(selected, vec!["a", "a", "b", "a", "a", "a", "b", "a"]);
+        assert_eq!(endpoints.select(2), None);
+    }
+
+    #[test]
+    fn test_failover() {
+        let endpoints = PeerEndpoints::new()
+            .with_failure_threshold(2)
+            .with_retry_interval(Duration::from_millis(50));
+        endpoints.set(
+            1,
+            vec![
+                Endpoint::new("primary", 0, 1),
+                Endpoint::new("backup", 1, 1),
+            ],
+        );
+        assert_eq!(endpoints.select(1).unwrap(), "primary");
+
+        // the primary fails over to the backup once it's unhealthy.
+        endpoints.report_failure(1, "primary");
+        assert_eq!(endpoints.select(1).unwrap(), "primary");
+        endpoints.report_failure(1, "primary");
+        assert_eq!(endpoints.select(1).unwrap(), "backup");
+        let health = endpoints.health(1);
+        assert!(!health[0].healthy);
+        assert_eq!(health[0].consecutive_failures, 2);
+        assert!(health[1].healthy);
+
+        // the endpoint with the least failures is selected if none is available.
+        endpoints.report_failure(1, "backup");
+        endpoints.report_failure(1, "backup");
+        endpoints.report_failure(1, "backup");
+        assert_eq!(endpoints.select(1).unwrap(), "primary");
+
+        // the primary is probed again after the retry interval.
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(endpoints.select(1).unwrap(), "primary");
+        endpoints.report_success(1, "primary");
+        assert!(endpoints.health(1)[0].healthy);
+
+        // the health of the endpoints kept is preserved.
+        endpoints.set(1, vec![Endpoint::new("backup", 0, 1)]);
+        let health = endpoints.health(1);
+        assert_eq!(health.len(), 1);
+        assert_eq!(health[0].consecutive_failures, 3);
+    }
+}
@@ -1,3 +1,16 @@
+use std::sync::Arc;
+
+use futures::Future;
+use prost::bytes::BufMut;
+use prost::Message;
+use tonic::client::Grpc;
+use tonic::codec::Codec;
+use tonic::codec::DecodeBuf;
+use tonic::codec::Decoder;
+use tonic::codec::EncodeBuf;
+use tonic::codec::Encoder;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::transport::Channel;
 use tonic::Request;
 use tonic::Response;
 use tonic::Status;
@@ -5,9 +18,15 @@ use tonic::Status;
 use crate::prelude::multi_raft_service_server::MultiRaftService;
 use crate::prelude::MultiRaftMessage;
 use crate::prelude::MultiRaftMessageResponse;
+use crate::prelude::SnapshotChunk;
+use crate::prelude::SnapshotChunkRequest;
+use crate::Error;
 use crate::MultiRaftMessageSender;
 use crate::MultiRaftMessageSenderImpl;
 
+use super::SnapshotChunkSource;
+use super::SnapshotChunkTransport;
+
 pub use crate::prelude::multi_raft_service_client::MultiRaftServiceClient;
 pub use crate::prelude::multi_raft_service_server::MultiRaftServiceServer;
 
@@ -15,6 +34,7 @@ pub use crate::prelude::multi_raft_service_server::MultiRaftServiceServer;
 /// users can add it to the service of their gRPC server.
 pub struct MultiRaftServiceImpl {
     forward: MultiRaftMessageSenderImpl,
+    chunk_source: Option<Arc<dyn SnapshotChunkSource>>,
 }
 
 impl MultiRaftServiceImpl {
@@ -23,7 +43,17 @@ impl MultiRaftServiceImpl {
     /// received by the server to the main thread of the Node.
     #[allow(unused)]
     pub fn new(forward: MultiRaftMessageSenderImpl) -> Self {
-        Self { forward }
+        Self {
+            forward,
+            chunk_source: None,
+        }
+    }
+
+    /// Serve the snapshot chunks from the source, the requests are rejected
+    /// as unimplemented without a source.
+    pub fn with_snapshot_chunk_source(mut self, source: Arc<dyn SnapshotChunkSource>) -> Self {
+        self.chunk_source = Some(source);
+        self
     }
 }
 
@@ -38,4 +68,105 @@ impl MultiRaftService for MultiRaftServiceImpl {
         let message = self.forward.send(msg).await.unwrap();
         Ok(Response::new(message))
     }
+
+    async fn fetch_snapshot_chunk(
+        &self,
+        request: Request<SnapshotChunkRequest>,
+    ) -> Result<Response<SnapshotChunk>, Status> {
+        let source = self
+            .chunk_source
+            .as_ref()
+            .ok_or_else(|| Status::unimplemented("snapshot chunk source is not set"))?;
+        source
+            .read_chunk(request.get_ref())
+            .map(Response::new)
+            .map_err(|err| Status::unavailable(err.to_string()))
+    }
+}
+
+/// The codec of the `Send` rpc whose request is already the protobuf encoding
+/// of `MultiRaftMessage`, see `send_encoded`.
+#[derive(Debug, Clone, Copy, Default)]
+struct EncodedCodec;
+
+impl Codec for EncodedCodec {
+    type Encode = Vec<u8>;
+    type Decode = MultiRaftMessageResponse;
+    type Encoder = EncodedCodec;
+    type Decoder = EncodedCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        EncodedCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        EncodedCodec
+    }
+}
+
+impl Encoder for EncodedCodec {
+    type Item = Vec<u8>;
+    type Error = Status;
+
+    fn encode(&mut self, item: Vec<u8>, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        dst.put_slice(&item);
+        Ok(())
+    }
+}
+
+impl Decoder for EncodedCodec {
+    type Item = MultiRaftMessageResponse;
+    type Error = Status;
+
+    fn decode(
+        &mut self,
+        src: &mut DecodeBuf<'_>,
+    ) -> Result<Option<MultiRaftMessageResponse>, Status> {
+        MultiRaftMessageResponse::decode(src)
+            .map(Some)
+            .map_err(|err| Status::internal(err.to_string()))
+    }
+}
+
+/// Send the `MultiRaftMessage` encoded by `OffloadTransport` by the `Send` rpc
+/// of `MultiRaftService`, the data is not decoded and encoded again, so the
+/// `EncodedTransport` of the gRPC clients is implemented by it.
+pub async fn send_encoded(
+    channel: Channel,
+    data: Vec<u8>,
+) -> Result<MultiRaftMessageResponse, Status> {
+    let mut client = Grpc::new(channel);
+    client
+        .ready()
+        .await
+        .map_err(|err| Status::unknown(format!("service was not ready: {}", err)))?;
+    let path = PathAndQuery::from_static("/multiraft.MultiRaftService/Send");
+    client
+        .unary(Request::new(data), path, EncodedCodec)
+        .await
+        .map(Response::into_inner)
+}
+
+/// The client connected to the node holding the snapshot fetches the chunks
+/// by the `FetchSnapshotChunk` rpc.
+impl SnapshotChunkTransport for MultiRaftServiceClient<Channel> {
+    type FetchChunkFuture<'life0> = impl Future<Output = Result<SnapshotChunk, Error>> + Send + 'life0
+    where
+        Self: 'life0;
+    fn fetch_chunk(&self, request: SnapshotChunkRequest) -> Self::FetchChunkFuture<'_> {
+        // the client is cheap to clone, it shares the underlying channel.
+        let mut client = self.clone();
+        async move {
+            let (group_id, offset) = (request.group_id, request.offset);
+            client
+                .fetch_snapshot_chunk(request)
+                .await
+                .map(Response::into_inner)
+                .map_err(|status| Error::SnapshotChunk {
+                    group_id,
+                    offset,
+                    reason: status.to_string(),
+                })
+        }
+    }
 }
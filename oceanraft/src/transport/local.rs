@@ -1,9 +1,11 @@
 use std::collections::hash_map::HashMap;
+use std::collections::HashSet;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 
+use futures::Future;
 use tokio::sync::mpsc::channel;
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::mpsc::Sender;
@@ -15,9 +17,19 @@ use tracing::error;
 use tracing::info;
 use tracing::warn;
 
+use crate::lifecycle::Lifecycle;
 use crate::multiraft::MultiRaftMessageSender;
 use crate::prelude::MultiRaftMessage;
 use crate::prelude::MultiRaftMessageResponse;
+use crate::prelude::SnapshotChunk;
+use crate::prelude::SnapshotChunkRequest;
+use crate::transport::Endpoint;
+use crate::transport::EndpointHealth;
+use crate::transport::PeerEndpoints;
+use crate::transport::SendFailure;
+use crate::transport::SendFailureReporter;
+use crate::transport::SnapshotChunkSource;
+use crate::transport::SnapshotChunkTransport;
 use crate::transport::Transport;
 use crate::Error;
 
@@ -70,13 +82,36 @@ impl<RD: MultiRaftMessageSender> LocalServer<RD> {
 pub struct LocalTransport<M: MultiRaftMessageSender> {
     servers: Arc<RwLock<HashMap<u64, LocalServer<M>>>>,
     disconnected: Arc<RwLock<HashMap<u64, Vec<u64>>>>,
+    /// The failure reporters of the nodes sending by the transport.
+    failure_reporters: Arc<std::sync::RwLock<HashMap<u64, SendFailureReporter>>>,
+    /// The sources of the snapshot chunks served by the nodes.
+    chunk_sources: Arc<std::sync::RwLock<HashMap<u64, Arc<dyn SnapshotChunkSource>>>>,
+    /// The number of the next chunk fetches failed to simulate the interrupted
+    /// transfers from the nodes.
+    chunk_interruptions: Arc<std::sync::Mutex<HashMap<u64, usize>>>,
+    /// The endpoints of the nodes, the messages to the nodes without
+    /// endpoints are sent to the servers directly.
+    endpoints: PeerEndpoints,
+    /// The endpoints failed to simulate the broken addresses of the nodes.
+    down_endpoints: Arc<std::sync::RwLock<HashSet<String>>>,
 }
 
 impl<M: MultiRaftMessageSender> LocalTransport<M> {
     pub fn new() -> Self {
+        Self::with_endpoints(PeerEndpoints::new())
+    }
+
+    /// The messages are sent by the endpoints of the nodes selected by
+    /// `endpoints`, see `set_endpoints`.
+    pub fn with_endpoints(endpoints: PeerEndpoints) -> Self {
         Self {
             servers: Default::default(),
             disconnected: Default::default(),
+            failure_reporters: Default::default(),
+            chunk_sources: Default::default(),
+            chunk_interruptions: Default::default(),
+            endpoints,
+            down_endpoints: Default::default(),
         }
     }
 }
@@ -169,6 +204,66 @@ impl<RD: MultiRaftMessageSender> LocalTransport<RD> {
         };
     }
 
+    /// Set the endpoints of the node, the endpoints are addresses of the same
+    /// server of the node which are failed by `break_endpoint`.
+    pub fn set_endpoints(&self, node_id: u64, endpoints: Vec<Endpoint>) {
+        self.endpoints.set(node_id, endpoints);
+    }
+
+    /// Fail the messages sent by the endpoint until it's repaired, which
+    /// simulates the broken address of the node.
+    pub fn break_endpoint(&self, addr: &str) {
+        self.down_endpoints.write().unwrap().insert(addr.to_owned());
+    }
+
+    pub fn repair_endpoint(&self, addr: &str) {
+        self.down_endpoints.write().unwrap().remove(addr);
+    }
+
+    /// Select the endpoint of the node to send by, the broken endpoints are
+    /// reported failed and fail over to the others. Returns `Ok(None)` if the
+    /// node has no endpoints.
+    fn select_endpoint(
+        endpoints: &PeerEndpoints,
+        down_endpoints: &std::sync::RwLock<HashSet<String>>,
+        node_id: u64,
+    ) -> Result<Option<String>, ()> {
+        for _ in 0..endpoints.count(node_id) {
+            let addr = match endpoints.select(node_id) {
+                None => break,
+                Some(addr) => addr,
+            };
+            if !down_endpoints.read().unwrap().contains(&addr) {
+                return Ok(Some(addr));
+            }
+            warn!(
+                "send to node {} by endpoint {} failed, endpoint broken",
+                node_id, addr
+            );
+            endpoints.report_failure(node_id, &addr);
+        }
+
+        if endpoints.count(node_id) == 0 {
+            Ok(None)
+        } else {
+            Err(())
+        }
+    }
+
+    /// Serve the snapshot chunks of the node from the source.
+    pub fn serve_snapshot_chunks(&self, node_id: u64, source: Arc<dyn SnapshotChunkSource>) {
+        self.chunk_sources.write().unwrap().insert(node_id, source);
+    }
+
+    /// Fail the next `count` chunk fetches from the node, which simulates the
+    /// interrupted snapshot transfers.
+    pub fn interrupt_snapshot_chunks(&self, node_id: u64, count: usize) {
+        self.chunk_interruptions
+            .lock()
+            .unwrap()
+            .insert(node_id, count);
+    }
+
     #[tracing::instrument(name = "LocalTransport::stop_all", skip(self))]
     pub async fn stop_all(&self) -> Result<(), Error> {
         let mut wl = self.servers.write().await;
@@ -179,6 +274,33 @@ impl<RD: MultiRaftMessageSender> LocalTransport<RD> {
     }
 }
 
+/// Stopping the transport stops the servers of all nodes listening on it.
+impl<RD> Lifecycle for LocalTransport<RD>
+where
+    RD: MultiRaftMessageSender,
+{
+    type StartFuture<'life0> = impl Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+    fn start(&self) -> Self::StartFuture<'_> {
+        async move { Ok(()) }
+    }
+
+    type FlushFuture<'life0> = impl Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+    fn flush(&self) -> Self::FlushFuture<'_> {
+        async move { Ok(()) }
+    }
+
+    type StopFuture<'life0> = impl Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+    fn stop(&self) -> Self::StopFuture<'_> {
+        self.stop_all()
+    }
+}
+
 impl<RD> Transport for LocalTransport<RD>
 where
     RD: MultiRaftMessageSender,
@@ -192,6 +314,15 @@ where
         );
         let servers = self.servers.clone();
         let disconnected = self.disconnected.clone();
+        let failure_reporters = self.failure_reporters.clone();
+        let endpoints = self.endpoints.clone();
+        let down_endpoints = self.down_endpoints.clone();
+        let failure = SendFailure::of(&msg);
+        let report_failure = move || {
+            if let Some(reporter) = failure_reporters.read().unwrap().get(&from_node) {
+                reporter.report_failure(failure);
+            }
+        };
         // get client
         let send_fn = async move {
             if LocalTransport::<RD>::is_disconnected(&disconnected, from_node, to_node).await {
@@ -201,9 +332,24 @@ where
                     to_node,
                     msg.get_msg().msg_type(),
                 );
+                report_failure();
                 return;
             }
 
+            let selected =
+                LocalTransport::<RD>::select_endpoint(&endpoints, &down_endpoints, to_node);
+            let endpoint = match selected {
+                Ok(endpoint) => endpoint,
+                Err(_) => {
+                    error!(
+                        "node {}: send failed, all endpoints of {} are broken",
+                        from_node, to_node
+                    );
+                    report_failure();
+                    return;
+                }
+            };
+
             // get server by to
             let rl = servers.read().await;
             if !rl.contains_key(&to_node) {
@@ -211,6 +357,7 @@ where
                     "node {}: send failed, to {} server not found",
                     from_node, to_node
                 );
+                report_failure();
                 return;
             }
 
@@ -218,6 +365,7 @@ where
             let to_server = rl.get(&to_node).unwrap();
             if to_server.stopped.load(Ordering::SeqCst) {
                 error!("server {} stopped", to_node);
+                report_failure();
                 return;
             }
 
@@ -227,16 +375,78 @@ where
                     "node {}: send msg failed, the {} node server stopped",
                     from_node, to_node
                 );
+                report_failure();
                 return;
             }
 
             // and receive response
             if let Ok(_res) = rx.await {
+                if let Some(addr) = endpoint {
+                    endpoints.report_success(to_node, &addr);
+                }
             } else {
                 error!("node {}: receive response failed, the {} node server stopped or discard the request", from_node, to_node);
+                report_failure();
             }
         };
         tokio::spawn(send_fn);
         Ok(())
     }
+
+    fn register_failure_reporter(&self, reporter: SendFailureReporter) {
+        self.failure_reporters
+            .write()
+            .unwrap()
+            .insert(reporter.node_id(), reporter);
+    }
+
+    fn endpoint_health(&self, node_id: u64) -> Vec<EndpointHealth> {
+        self.endpoints.health(node_id)
+    }
+}
+
+/// The chunks are read from the source of the target node directly, the
+/// disconnected nodes and the interruptions fail the fetches.
+impl<RD> SnapshotChunkTransport for LocalTransport<RD>
+where
+    RD: MultiRaftMessageSender,
+{
+    type FetchChunkFuture<'life0> = impl Future<Output = Result<SnapshotChunk, Error>> + Send + 'life0
+    where
+        Self: 'life0;
+    fn fetch_chunk(&self, request: SnapshotChunkRequest) -> Self::FetchChunkFuture<'_> {
+        async move {
+            let (from_node, to_node) = (request.from_node, request.to_node);
+            let chunk_error = |reason: String| Error::SnapshotChunk {
+                group_id: request.group_id,
+                offset: request.offset,
+                reason,
+            };
+            if LocalTransport::<RD>::is_disconnected(&self.disconnected, from_node, to_node).await {
+                return Err(chunk_error(format!(
+                    "node {} disconnected from node {}",
+                    from_node, to_node
+                )));
+            }
+
+            if let Some(count) = self.chunk_interruptions.lock().unwrap().get_mut(&to_node) {
+                if *count > 0 {
+                    *count -= 1;
+                    return Err(chunk_error(format!(
+                        "transfer from node {} interrupted",
+                        to_node
+                    )));
+                }
+            }
+
+            let source = self.chunk_sources.read().unwrap().get(&to_node).cloned();
+            match source {
+                None => Err(chunk_error(format!(
+                    "node {} does not serve snapshot chunks",
+                    to_node
+                ))),
+                Some(source) => source.read_chunk(&request),
+            }
+        }
+    }
 }
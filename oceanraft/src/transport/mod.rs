@@ -1,3 +1,6 @@
+use std::time::Duration;
+
+use tokio::sync::mpsc::UnboundedSender;
 use tracing::error;
 use tracing::trace;
 use tracing::Level;
@@ -7,20 +10,140 @@ use crate::prelude::MessageType;
 use crate::prelude::MultiRaftMessage;
 
 use super::error::Error;
+use super::inflight::DeliveryStats;
+use super::invariant;
+use super::multiraft::NO_GORUP;
 use super::node::NodeManager;
 use super::replica_cache::ReplicaCache;
+use super::snapshot::snapshot_checksum;
+use super::storage::strip_entries_payload;
 use super::storage::MultiRaftStorage;
 use super::storage::RaftStorage;
 
 pub trait Transport: Send + Sync + 'static {
     // TODO: should define associated error insted of Error.
     fn send(&self, msg: MultiRaftMessage) -> Result<(), Error>;
+
+    /// Called by the node when it's created. The transport reports the messages
+    /// which fail to deliver after `send` returned, e.g. the connection to the
+    /// target node is broken, to the reporter of the sending node. The default
+    /// transport reports nothing.
+    fn register_failure_reporter(&self, _reporter: SendFailureReporter) {}
+
+    /// The health of the endpoints of the node selected by the transport,
+    /// which is reported in `PeerStatus::endpoints`, see `PeerEndpoints`. The
+    /// default transport has no endpoints.
+    fn endpoint_health(&self, _node_id: u64) -> Vec<EndpointHealth> {
+        vec![]
+    }
+}
+
+/// The delivery failure of a message, see `SendFailureReporter`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SendFailure {
+    /// The message of the group to the replica.
+    Replica { group_id: u64, replica_id: u64 },
+    /// The snapshot of the group to the replica, the leader reports the
+    /// snapshot failed so that the replica is probed again.
+    Snapshot { group_id: u64, replica_id: u64 },
+    /// The node level message, e.g. the coalesced heartbeat, to the node.
+    Node(u64),
+}
+
+impl SendFailure {
+    pub(crate) fn of(msg: &MultiRaftMessage) -> Self {
+        match msg.msg.as_ref() {
+            Some(raft_msg)
+                if msg.group_id != NO_GORUP && raft_msg.msg_type() == MessageType::MsgSnapshot =>
+            {
+                SendFailure::Snapshot {
+                    group_id: msg.group_id,
+                    replica_id: raft_msg.to,
+                }
+            }
+            Some(raft_msg) if msg.group_id != NO_GORUP => SendFailure::Replica {
+                group_id: msg.group_id,
+                replica_id: raft_msg.to,
+            },
+            _ => SendFailure::Node(msg.to_node),
+        }
+    }
 }
 
-/// Call `Transport` to send the messages.
+/// SendFailureReporter reports the messages failed to deliver back to the
+/// node, the leaders of the target replicas report them unreachable to raft,
+/// so raft probes the replicas instead of replicating optimistically to the
+/// dead peers. The failure of the node level message reports all replicas on
+/// the target node. The reports of a replica are debounced by
+/// `Config::unreachable_debounce_ticks`.
+///
+/// The transport also reports the round trip time of the messages delivered,
+/// the node adapts the inflight appends to the peers by the delivery reports,
+/// see `Config::adaptive_inflight_interval_ticks`.
+#[derive(Debug, Clone)]
+pub struct SendFailureReporter {
+    node_id: u64,
+    tx: UnboundedSender<SendFailure>,
+    delivery: DeliveryStats,
+}
+
+impl SendFailureReporter {
+    pub(crate) fn new(
+        node_id: u64,
+        tx: UnboundedSender<SendFailure>,
+        delivery: DeliveryStats,
+    ) -> Self {
+        Self {
+            node_id,
+            tx,
+            delivery,
+        }
+    }
+
+    /// The id of the node which sends the messages.
+    #[inline]
+    pub fn node_id(&self) -> u64 {
+        self.node_id
+    }
+
+    /// Report the message failed to deliver.
+    pub fn report(&self, msg: &MultiRaftMessage) {
+        self.delivery.failed(msg.to_node);
+        self.report_failure(SendFailure::of(msg))
+    }
+
+    /// Report the message to the node delivered in the round trip time, e.g.
+    /// measured by the response of the message.
+    pub fn report_delivered(&self, node_id: u64, rtt: Duration) {
+        self.delivery.delivered(node_id, rtt);
+    }
+
+    /// Report the replica of the group unreachable.
+    pub fn report_replica(&self, group_id: u64, replica_id: u64) {
+        self.report_failure(SendFailure::Replica {
+            group_id,
+            replica_id,
+        })
+    }
+
+    /// Report all replicas on the node unreachable.
+    pub fn report_node(&self, node_id: u64) {
+        self.delivery.failed(node_id);
+        self.report_failure(SendFailure::Node(node_id))
+    }
+
+    /// The failures are dropped if the node stopped.
+    pub(crate) fn report_failure(&self, failure: SendFailure) {
+        let _ = self.tx.send(failure);
+    }
+}
+
+/// Call `Transport` to send the messages, the messages failed to send are
+/// reported to `failure_reporter`.
 pub async fn send_messages<TR, RS, MRS>(
     from_node_id: u64,
     transport: &TR,
+    failure_reporter: &SendFailureReporter,
     replica_cache: &mut ReplicaCache<RS, MRS>,
     node_mgr: &mut NodeManager,
     group_id: u64,
@@ -73,6 +196,7 @@ pub async fn send_messages<TR, RS, MRS>(
             send_message(
                 from_node_id,
                 transport,
+                failure_reporter,
                 replica_cache,
                 node_mgr,
                 group_id,
@@ -91,10 +215,11 @@ pub async fn send_messages<TR, RS, MRS>(
 async fn send_message<TR, RS, MRS>(
     from_node_id: u64,
     transport: &TR,
+    failure_reporter: &SendFailureReporter,
     replica_cache: &mut ReplicaCache<RS, MRS>,
     node_mgr: &mut NodeManager,
     group_id: u64,
-    msg: Message,
+    mut msg: Message,
 ) where
     TR: Transport,
     RS: RaftStorage,
@@ -126,6 +251,7 @@ async fn send_message<TR, RS, MRS>(
         },
     };
     assert_ne!(to_replica.node_id, 0);
+    invariant::check_send(from_node_id, group_id, msg.to, &to_replica);
 
     trace!(
         "node {}: send raft msg to node {}: msg_type = {:?}, group = {}, from = {}, to = {}",
@@ -140,27 +266,75 @@ async fn send_message<TR, RS, MRS>(
         node_mgr.add_group(to_replica.node_id, group_id);
     }
 
+    if to_replica.witness {
+        strip_witness_payload(&mut msg);
+    }
+
+    // the receiver verifies the snapshot data before installed.
+    let snapshot_checksum = match msg.msg_type() {
+        MessageType::MsgSnapshot => msg
+            .snapshot
+            .as_ref()
+            .map(|snapshot| snapshot_checksum(&snapshot.data)),
+        _ => None,
+    };
+
     let msg = MultiRaftMessage {
         group_id,
         from_node: from_node_id,
         to_node: to_replica.node_id,
         replicas: vec![],
         msg: Some(msg),
+        snapshot_checksum,
+        ..Default::default()
     };
 
     // FIXME: send trait should be return original msg when error occurred.
+    let failure = SendFailure::of(&msg);
     if let Err(err) = transport.send(msg) {
         error!(
             "node {}: send raft msg to node {} error: group = {}, err = {:?}",
             from_node_id, to_replica.node_id, group_id, err
         );
+        failure_reporter.report_failure(failure);
+    }
+}
+
+/// The witness stores only the log metadata, so the payload of the entries and
+/// the data of the snapshot sent to it are dropped.
+fn strip_witness_payload(msg: &mut Message) {
+    match msg.msg_type() {
+        MessageType::MsgAppend => strip_entries_payload(&mut msg.entries),
+        MessageType::MsgSnapshot => {
+            if let Some(snapshot) = msg.snapshot.as_mut() {
+                snapshot.data.clear();
+            }
+        }
+        _ => {}
     }
 }
 
+mod chunk;
+mod compress;
+mod endpoint;
 #[cfg(feature = "grpc")]
 mod grpc;
 mod local;
+mod offload;
+mod queue;
+mod stream;
 
+pub use chunk::{
+    snapshot_chunk, SnapshotChunkSource, SnapshotChunkTransport, SnapshotDownload,
+    DEFAULT_SNAPSHOT_CHUNK_SIZE,
+};
+pub use compress::{decompress_message, MessageCompressor};
+pub use endpoint::{Endpoint, EndpointHealth, PeerEndpoints};
 #[cfg(feature = "grpc")]
-pub use grpc::{MultiRaftServiceClient, MultiRaftServiceImpl, MultiRaftServiceServer};
+pub use grpc::{
+    send_encoded, MultiRaftServiceClient, MultiRaftServiceImpl, MultiRaftServiceServer,
+};
 pub use local::LocalTransport;
+pub use offload::{EncodedTransport, OffloadTransport};
+pub use queue::QueueTransport;
+pub use stream::{SnapshotStreamReceiver, SnapshotTransport, SnapshotUpload};
@@ -0,0 +1,307 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use futures::Future;
+use prost::Message;
+use tracing::error;
+
+use crate::error::ChannelError;
+use crate::lifecycle::Lifecycle;
+use crate::prelude::MultiRaftMessage;
+use crate::transport::SendFailure;
+use crate::transport::SendFailureReporter;
+use crate::transport::Transport;
+use crate::Error;
+
+/// EncodedTransport sends the messages serialized by `OffloadTransport`,
+/// `data` is the protobuf encoding of `MultiRaftMessage`, e.g. by
+/// `transport::send_encoded` of the gRPC transport.
+pub trait EncodedTransport: Send + Sync + 'static {
+    /// Prepare the message on the worker before it's encoded, e.g. compress
+    /// it by `MessageCompressor`. The default returns the message as is.
+    fn before_encode(&self, msg: MultiRaftMessage) -> MultiRaftMessage {
+        msg
+    }
+
+    fn send_encoded(&self, from_node: u64, to_node: u64, data: Vec<u8>) -> Result<(), Error>;
+}
+
+/// OffloadTransport serializes and sends the messages on a pool of worker
+/// threads instead of the node loop. The messages to the same node are always
+/// handled by the same worker, so they are sent in order.
+///
+/// The workers exit when all clones of the transport are dropped.
+pub struct OffloadTransport<T: EncodedTransport> {
+    workers: Arc<Vec<flume::Sender<MultiRaftMessage>>>,
+    /// The number of messages queued or being sent by the workers.
+    pending: Arc<AtomicUsize>,
+    inner: Arc<T>,
+    /// The messages failed to send by `inner` are reported to it.
+    failure_reporter: Arc<Mutex<Option<SendFailureReporter>>>,
+}
+
+impl<T: EncodedTransport> Clone for OffloadTransport<T> {
+    fn clone(&self) -> Self {
+        Self {
+            workers: self.workers.clone(),
+            pending: self.pending.clone(),
+            inner: self.inner.clone(),
+            failure_reporter: self.failure_reporter.clone(),
+        }
+    }
+}
+
+impl<T: EncodedTransport> OffloadTransport<T> {
+    /// Spawn `workers` threads to serialize and send the messages by `inner`.
+    ///
+    /// # Panics
+    /// Panics if `workers` is `0` or the os fails to spawn thread.
+    pub fn new(inner: T, workers: usize) -> Self {
+        assert_ne!(workers, 0, "offload transport needs at least one worker");
+        let inner = Arc::new(inner);
+        let pending = Arc::new(AtomicUsize::new(0));
+        let failure_reporter = Arc::new(Mutex::new(None::<SendFailureReporter>));
+        let workers = (0..workers)
+            .map(|i| {
+                let (tx, rx) = flume::unbounded::<MultiRaftMessage>();
+                let inner = inner.clone();
+                let pending = pending.clone();
+                let failure_reporter = failure_reporter.clone();
+                std::thread::Builder::new()
+                    .name(format!("oceanraft-encode-{}", i))
+                    .spawn(move || {
+                        while let Ok(msg) = rx.recv() {
+                            let msg = inner.before_encode(msg);
+                            let (from_node, to_node) = (msg.from_node, msg.to_node);
+                            let data = msg.encode_to_vec();
+                            if let Err(err) = inner.send_encoded(from_node, to_node, data) {
+                                error!(
+                                    "node {}: send encoded raft msg to node {} error: {}",
+                                    from_node, to_node, err
+                                );
+                                if let Some(reporter) = failure_reporter.lock().unwrap().as_ref() {
+                                    reporter.report_failure(SendFailure::of(&msg));
+                                }
+                            }
+                            pending.fetch_sub(1, Ordering::SeqCst);
+                        }
+                    })
+                    .unwrap();
+                tx
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            workers: Arc::new(workers),
+            pending,
+            inner,
+            failure_reporter,
+        }
+    }
+
+    #[inline]
+    pub fn inner(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T: EncodedTransport> Transport for OffloadTransport<T> {
+    fn send(&self, msg: MultiRaftMessage) -> Result<(), Error> {
+        let worker = &self.workers[msg.to_node as usize % self.workers.len()];
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        worker.send(msg).map_err(|_| {
+            self.pending.fetch_sub(1, Ordering::SeqCst);
+            Error::Channel(ChannelError::ReceiverClosed(
+                "offload transport worker stopped".to_owned(),
+            ))
+        })
+    }
+
+    fn register_failure_reporter(&self, reporter: SendFailureReporter) {
+        *self.failure_reporter.lock().unwrap() = Some(reporter);
+    }
+}
+
+/// The queued messages are sent by the workers before the inner transport
+/// is flushed.
+impl<T> Lifecycle for OffloadTransport<T>
+where
+    T: EncodedTransport + Lifecycle,
+{
+    type StartFuture<'life0> = T::StartFuture<'life0>
+    where
+        Self: 'life0;
+    fn start(&self) -> Self::StartFuture<'_> {
+        self.inner.start()
+    }
+
+    type FlushFuture<'life0> = impl Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+    fn flush(&self) -> Self::FlushFuture<'_> {
+        async move {
+            while self.pending.load(Ordering::SeqCst) != 0 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            self.inner.flush().await
+        }
+    }
+
+    type StopFuture<'life0> = T::StopFuture<'life0>
+    where
+        Self: 'life0;
+    fn stop(&self) -> Self::StopFuture<'_> {
+        self.inner.stop()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use std::time::Instant;
+
+    use prost::Message;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    use super::EncodedTransport;
+    use super::OffloadTransport;
+    use crate::error::ChannelError;
+    use crate::prelude::Message as RaftMessage;
+    use crate::prelude::MessageType;
+    use crate::prelude::MultiRaftMessage;
+    use crate::transport::SendFailure;
+    use crate::transport::SendFailureReporter;
+    use crate::transport::Transport;
+    use crate::Error;
+
+    #[derive(Default)]
+    struct CollectTransport {
+        msgs: Mutex<Vec<MultiRaftMessage>>,
+    }
+
+    impl EncodedTransport for CollectTransport {
+        fn before_encode(&self, mut msg: MultiRaftMessage) -> MultiRaftMessage {
+            msg.compressed_msg = b"prepared".to_vec();
+            msg
+        }
+
+        fn send_encoded(&self, _: u64, _: u64, data: Vec<u8>) -> Result<(), Error> {
+            let msg = MultiRaftMessage::decode(data.as_slice()).unwrap();
+            self.msgs.lock().unwrap().push(msg);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_offload_transport_per_node_order() {
+        let transport = OffloadTransport::new(CollectTransport::default(), 2);
+        for group_id in 1..=300 {
+            let msg = MultiRaftMessage {
+                group_id,
+                from_node: 1,
+                to_node: group_id % 3 + 1,
+                ..Default::default()
+            };
+            transport.send(msg).unwrap();
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while transport.inner().msgs.lock().unwrap().len() < 300 {
+            assert!(Instant::now() < deadline, "wait encoded messages timeout");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let msgs = transport.inner().msgs.lock().unwrap();
+        for to_node in 1..=3 {
+            let groups = msgs
+                .iter()
+                .filter(|msg| msg.to_node == to_node)
+                .map(|msg| msg.group_id)
+                .collect::<Vec<_>>();
+            assert_eq!(groups.len(), 100);
+            assert!(groups.windows(2).all(|w| w[0] < w[1]));
+        }
+        assert!(msgs.iter().all(|msg| msg.compressed_msg == b"prepared"));
+    }
+
+    struct FailTransport;
+
+    impl EncodedTransport for FailTransport {
+        fn send_encoded(&self, _: u64, _: u64, _: Vec<u8>) -> Result<(), Error> {
+            Err(Error::Channel(ChannelError::ReceiverClosed(
+                "connection broken".to_owned(),
+            )))
+        }
+    }
+
+    #[test]
+    fn test_offload_transport_report_failure() {
+        let transport = OffloadTransport::new(FailTransport, 1);
+        let (tx, mut rx) = unbounded_channel();
+        transport.register_failure_reporter(SendFailureReporter::new(1, tx, Default::default()));
+
+        let raft_msg = RaftMessage {
+            to: 5,
+            ..Default::default()
+        };
+        let mut snapshot_msg = RaftMessage {
+            to: 6,
+            ..Default::default()
+        };
+        snapshot_msg.set_msg_type(MessageType::MsgSnapshot);
+        let msgs = [
+            MultiRaftMessage {
+                group_id: 3,
+                from_node: 1,
+                to_node: 2,
+                msg: Some(raft_msg),
+                ..Default::default()
+            },
+            // the node level heartbeat.
+            MultiRaftMessage {
+                from_node: 1,
+                to_node: 2,
+                msg: Some(RaftMessage::default()),
+                ..Default::default()
+            },
+            MultiRaftMessage {
+                group_id: 3,
+                from_node: 1,
+                to_node: 2,
+                msg: Some(snapshot_msg),
+                ..Default::default()
+            },
+        ];
+        for msg in msgs {
+            transport.send(msg).unwrap();
+        }
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut failures = vec![];
+        while failures.len() < 3 {
+            assert!(Instant::now() < deadline, "wait send failures timeout");
+            match rx.try_recv() {
+                Ok(failure) => failures.push(failure),
+                Err(_) => std::thread::sleep(Duration::from_millis(1)),
+            }
+        }
+        assert_eq!(
+            failures,
+            vec![
+                SendFailure::Replica {
+                    group_id: 3,
+                    replica_id: 5
+                },
+                SendFailure::Node(2),
+                SendFailure::Snapshot {
+                    group_id: 3,
+                    replica_id: 6
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use futures::Future;
+
+use crate::lifecycle::Lifecycle;
+use crate::prelude::MultiRaftMessage;
+use crate::transport::SendFailureReporter;
+use crate::transport::Transport;
+use crate::Error;
+
+/// QueueTransport buffers the outgoing messages instead of sending them,
+/// the messages are drained by the caller and delivered by its own network.
+///
+/// It is the transport of the node driven by `NodeHandle::poll`.
+#[derive(Clone, Default)]
+pub struct QueueTransport {
+    msgs: Arc<Mutex<Vec<MultiRaftMessage>>>,
+    failure_reporter: Arc<Mutex<Option<SendFailureReporter>>>,
+}
+
+impl QueueTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take all buffered messages in the order of sending.
+    pub fn drain(&self) -> Vec<MultiRaftMessage> {
+        std::mem::take(&mut *self.msgs.lock().unwrap())
+    }
+
+    /// Report the drained message which the caller failed to deliver, so the
+    /// leader stops replicating optimistically to the unreachable replica.
+    pub fn report_failure(&self, msg: &MultiRaftMessage) {
+        if let Some(reporter) = self.failure_reporter.lock().unwrap().as_ref() {
+            reporter.report(msg);
+        }
+    }
+}
+
+/// The buffered messages are left to the caller to drain.
+impl Lifecycle for QueueTransport {
+    type StartFuture<'life0> = impl Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+    fn start(&self) -> Self::StartFuture<'_> {
+        async move { Ok(()) }
+    }
+
+    type FlushFuture<'life0> = impl Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+    fn flush(&self) -> Self::FlushFuture<'_> {
+        async move { Ok(()) }
+    }
+
+    type StopFuture<'life0> = impl Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+    fn stop(&self) -> Self::StopFuture<'_> {
+        async move { Ok(()) }
+    }
+}
+
+impl Transport for QueueTransport {
+    fn send(&self, msg: MultiRaftMessage) -> Result<(), Error> {
+        self.msgs.lock().unwrap().push(msg);
+        Ok(())
+    }
+
+    fn register_failure_reporter(&self, reporter: SendFailureReporter) {
+        *self.failure_reporter.lock().unwrap() = Some(reporter);
+    }
+}
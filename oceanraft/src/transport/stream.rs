@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use futures::Future;
+
+use crate::event::Event;
+use crate::event::EventChannel;
+use crate::event::SnapshotTransferStage;
+use crate::prelude::SnapshotChunk;
+use crate::prelude::SnapshotChunkRequest;
+use crate::storage::RaftSnapshotReader;
+use crate::storage::RaftSnapshotWriter;
+use crate::Error;
+
+use super::chunk::snapshot_chunk;
+use super::chunk::verify_snapshot_chunk;
+use super::chunk::DEFAULT_SNAPSHOT_CHUNK_SIZE;
+
+/// SnapshotTransport streams the chunks of the snapshots to the other nodes,
+/// the receiver acknowledges the offset it has received, so the broken
+/// stream of a large snapshot is resumed from the acknowledged offset, see
+/// `SnapshotUpload`.
+pub trait SnapshotTransport: Send + Sync + 'static {
+    /// GAT trait for `send_chunk`.
+    type SendChunkFuture<'life0>: Send + Future<Output = Result<u64, Error>> + 'life0
+    where
+        Self: 'life0;
+    /// Send the chunk of the snapshot identified by the request to
+    /// `request.to_node`, returns the offset acknowledged by the receiver,
+    /// e.g. by `SnapshotStreamReceiver::receive`.
+    fn send_chunk(
+        &self,
+        request: SnapshotChunkRequest,
+        chunk: SnapshotChunk,
+    ) -> Self::SendChunkFuture<'_>;
+}
+
+fn transfer_event(
+    request: &SnapshotChunkRequest,
+    offset: u64,
+    total_size: u64,
+    stage: SnapshotTransferStage,
+) -> Event {
+    Event::SnapshotTransfer {
+        group_id: request.group_id,
+        from_node: request.from_node,
+        to_node: request.to_node,
+        index: request.index,
+        offset,
+        total_size,
+        stage,
+    }
+}
+
+/// The resumable upload of the data of a snapshot loaded by
+/// `RaftSnapshotReader`. The acknowledged offset is kept across the failures,
+/// so calling `resume` again continues from it.
+#[derive(Debug, Clone)]
+pub struct SnapshotUpload {
+    request: SnapshotChunkRequest,
+    data: Vec<u8>,
+    offset: u64,
+    done: bool,
+}
+
+impl SnapshotUpload {
+    /// Load the data of the snapshot of the replica to upload, the offset of
+    /// the request is ignored and `DEFAULT_SNAPSHOT_CHUNK_SIZE` is used if
+    /// the `max_len` is `0`.
+    pub fn new<R: RaftSnapshotReader>(
+        reader: &R,
+        replica_id: u64,
+        mut request: SnapshotChunkRequest,
+    ) -> Result<Self, Error> {
+        let data = reader.load_snapshot(request.group_id, replica_id)?;
+        request.offset = 0;
+        if request.max_len == 0 {
+            request.max_len = DEFAULT_SNAPSHOT_CHUNK_SIZE;
+        }
+        Ok(Self {
+            request,
+            data,
+            offset: 0,
+            done: false,
+        })
+    }
+
+    #[inline]
+    pub fn group_id(&self) -> u64 {
+        self.request.group_id
+    }
+
+    /// The number of bytes acknowledged by the receiver.
+    #[inline]
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    #[inline]
+    pub fn total_size(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    #[inline]
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Send the chunks from the acknowledged offset until the receiver has
+    /// the whole data, the progress is reported to the events. The chunk
+    /// failed to send is returned as error and the upload can be resumed
+    /// later.
+    pub async fn resume<T: SnapshotTransport>(
+        &mut self,
+        transport: &T,
+        events: &mut EventChannel,
+    ) -> Result<(), Error> {
+        self.resume_with(transport, events, |_, _| {}).await
+    }
+
+    /// Same as `resume`, the acknowledged offset and the total size are
+    /// also reported to `on_ack`, e.g. to update the operation.
+    pub(crate) async fn resume_with<T: SnapshotTransport, F: FnMut(u64, u64)>(
+        &mut self,
+        transport: &T,
+        events: &mut EventChannel,
+        on_ack: F,
+    ) -> Result<(), Error> {
+        let total_size = self.total_size();
+        let res = self.send_chunks(transport, events, on_ack).await;
+        let stage = match res {
+            Ok(_) => SnapshotTransferStage::Sent,
+            Err(_) => SnapshotTransferStage::Failed,
+        };
+        events.push(transfer_event(
+            &self.request,
+            self.offset,
+            total_size,
+            stage,
+        ));
+        events.flush();
+        res
+    }
+
+    async fn send_chunks<T: SnapshotTransport, F: FnMut(u64, u64)>(
+        &mut self,
+        transport: &T,
+        events: &mut EventChannel,
+        mut on_ack: F,
+    ) -> Result<(), Error> {
+        let total_size = self.total_size();
+        // the chunk of the empty data is sent once, so the receiver installs it.
+        while !self.done {
+            let mut request = self.request.clone();
+            request.offset = self.offset;
+            let chunk = snapshot_chunk(&request, &self.data)?;
+            let acked = transport.send_chunk(request.clone(), chunk).await?;
+            if acked > total_size || (acked == request.offset && acked != total_size) {
+                return Err(Error::SnapshotChunk {
+                    group_id: request.group_id,
+                    offset: request.offset,
+                    reason: format!("receiver acknowledged offset {}", acked),
+                });
+            }
+
+            self.offset = acked;
+            self.done = acked == total_size;
+            on_ack(acked, total_size);
+            events.push(transfer_event(
+                &request,
+                acked,
+                total_size,
+                SnapshotTransferStage::Sending,
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The result of a chunk appended to the snapshot being received.
+enum Received {
+    /// The received offset and the total size.
+    Partial(u64, u64),
+    Complete(Vec<u8>),
+}
+
+/// The data of a snapshot being received.
+struct PartialSnapshot {
+    index: u64,
+    term: u64,
+    total_size: u64,
+    data: Vec<u8>,
+}
+
+/// SnapshotStreamReceiver assembles the chunks of the snapshots streamed by
+/// `SnapshotUpload` and installs the data by `RaftSnapshotWriter` once it is
+/// complete. A group receives one snapshot at a time, the newer snapshot
+/// replaces the one being received.
+pub struct SnapshotStreamReceiver<W: RaftSnapshotWriter> {
+    writer: W,
+    partials: Mutex<HashMap<u64, PartialSnapshot>>,
+}
+
+impl<W: RaftSnapshotWriter> SnapshotStreamReceiver<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            partials: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Receive the chunk of the snapshot for the replica, returns the offset
+    /// received to acknowledge. The chunk out of the received offset is
+    /// ignored, so the sender resumes from the acknowledged offset.
+    pub async fn receive(
+        &self,
+        replica_id: u64,
+        request: SnapshotChunkRequest,
+        chunk: SnapshotChunk,
+        events: &mut EventChannel,
+    ) -> Result<u64, Error> {
+        let data = match self.append(&request, chunk)? {
+            Received::Partial(offset, total_size) => {
+                events.push(transfer_event(
+                    &request,
+                    offset,
+                    total_size,
+                    SnapshotTransferStage::Receiving,
+                ));
+                events.flush();
+                return Ok(offset);
+            }
+            Received::Complete(data) => data,
+        };
+
+        let total_size = data.len() as u64;
+        let res = self
+            .writer
+            .install_snapshot(request.group_id, replica_id, data)
+            .await;
+        let (offset, stage) = match res {
+            Ok(_) => (total_size, SnapshotTransferStage::Installed),
+            // the sender streams the snapshot again.
+            Err(_) => (0, SnapshotTransferStage::Failed),
+        };
+        events.push(transfer_event(&request, offset, total_size, stage));
+        events.flush();
+        res?;
+        Ok(total_size)
+    }
+
+    fn append(
+        &self,
+        request: &SnapshotChunkRequest,
+        chunk: SnapshotChunk,
+    ) -> Result<Received, Error> {
+        let mut partials = self.partials.lock().unwrap();
+        let stale = partials.get(&request.group_id).map_or(true, |partial| {
+            partial.index != request.index
+                || partial.term != request.term
+                || partial.total_size != chunk.total_size
+        });
+        if stale {
+            if chunk.offset != 0 {
+                return Ok(Received::Partial(0, chunk.total_size));
+            }
+            partials.insert(
+                request.group_id,
+                PartialSnapshot {
+                    index: request.index,
+                    term: request.term,
+                    total_size: chunk.total_size,
+                    data: vec![],
+                },
+            );
+        }
+
+        let partial = partials.get_mut(&request.group_id).unwrap();
+        let received = partial.data.len() as u64;
+        if chunk.offset != received {
+            return Ok(Received::Partial(received, partial.total_size));
+        }
+        if let Err(reason) = verify_snapshot_chunk(request, &chunk) {
+            return Err(Error::SnapshotChunk {
+                group_id: request.group_id,
+                offset: chunk.offset,
+                reason,
+            });
+        }
+
+        partial.data.extend_from_slice(&chunk.data);
+        let received = partial.data.len() as u64;
+        if received < partial.total_size {
+            return Ok(Received::Partial(received, partial.total_size));
+        }
+        let partial = partials.remove(&request.group_id).unwrap();
+        Ok(Received::Complete(partial.data))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+    use std::sync::Mutex;
+
+    use futures::Future;
+    use futures::FutureExt;
+
+    use crate::event::Event;
+    use crate::event::EventChannel;
+    use crate::event::SnapshotTransferStage;
+    use crate::prelude::ConfState;
+    use crate::prelude::SnapshotChunk;
+    use crate::prelude::SnapshotChunkRequest;
+    use crate::storage::RaftSnapshotReader;
+    use crate::storage::RaftSnapshotWriter;
+    use crate::storage::Result as StorageResult;
+    use crate::Error;
+
+    use super::SnapshotStreamReceiver;
+    use super::SnapshotTransport;
+    use super::SnapshotUpload;
+
+    #[derive(Clone, Default)]
+    struct MemSnapshot {
+        data: Vec<u8>,
+        installed: Arc<Mutex<Vec<(u64, u64, Vec<u8>)>>>,
+    }
+
+    impl RaftSnapshotReader for MemSnapshot {
+        fn load_snapshot(&self, _: u64, _: u64) -> StorageResult<Vec<u8>> {
+            Ok(self.data.clone())
+        }
+    }
+
+    impl RaftSnapshotWriter for MemSnapshot {
+        type InstallSnapshotDataFuture<'life0>
+            = impl Future<Output = StorageResult<()>> + 'life0
+        where
+            Self: 'life0;
+        fn install_snapshot(
+            &self,
+            group_id: u64,
+            replica_id: u64,
+            data: Vec<u8>,
+        ) -> Self::InstallSnapshotDataFuture<'_> {
+            async move {
+                self.installed
+                    .lock()
+                    .unwrap()
+                    .push((group_id, replica_id, data));
+                Ok(())
+            }
+        }
+
+        type BuildSnapshotFuture<'life0>
+            = impl Future<Output = StorageResult<()>> + 'life0
+        where
+            Self: 'life0;
+        fn build_snapshot(
+            &self,
+            _: u64,
+            _: u64,
+            _: u64,
+            _: u64,
+            _: ConfState,
+        ) -> Self::BuildSnapshotFuture<'_> {
+            async move { Ok(()) }
+        }
+    }
+
+    /// Every third send fails, and the receiver loses the received data once.
+    struct FlakyTransport {
+        receiver: SnapshotStreamReceiver<MemSnapshot>,
+        sends: AtomicUsize,
+    }
+
+    impl SnapshotTransport for FlakyTransport {
+        type SendChunkFuture<'life0>
+            = impl Future<Output = Result<u64, Error>> + Send + 'life0
+        where
+            Self: 'life0;
+        fn send_chunk(
+            &self,
+            request: SnapshotChunkRequest,
+            chunk: SnapshotChunk,
+        ) -> Self::SendChunkFuture<'_> {
+            async move {
+                let sends = self.sends.fetch_add(1, Ordering::SeqCst);
+                if sends % 3 == 2 {
+                    return Err(Error::BadParameter("interrupted".to_owned()));
+                }
+                if sends == 4 {
+                    self.receiver.partials.lock().unwrap().clear();
+                }
+                let mut events = EventChannel::new(16);
+                self.receiver.receive(3, request, chunk, &mut events).await
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_stream_resume() {
+        let data = (0..100u8).collect::<Vec<_>>();
+        let writer = MemSnapshot::default();
+        let transport = FlakyTransport {
+            receiver: SnapshotStreamReceiver::new(writer.clone()),
+            sends: AtomicUsize::new(0),
+        };
+        let reader = MemSnapshot {
+            data: data.clone(),
+            ..Default::default()
+        };
+        let mut upload = SnapshotUpload::new(
+            &reader,
+            1,
+            SnapshotChunkRequest {
+                group_id: 1,
+                from_node: 1,
+                to_node: 2,
+                index: 10,
+                term: 2,
+                max_len: 30,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        let mut events = EventChannel::new(1024);
+        events.set_inline_flush();
+        let rx = events.subscribe();
+        let mut failures = 0;
+        let mut acks = vec![];
+        while let Err(err) = upload
+            .resume_with(&transport, &mut events, |acked, total_size| {
+                acks.push((acked, total_size))
+            })
+            .await
+        {
+            failures += 1;
+            assert!(failures < 10, "{}", err);
+        }
+        assert!(upload.is_done());
+        assert_eq!(upload.offset(), 100);
+        assert_eq!(acks.last(), Some(&(100, 100)));
+        assert_eq!(*writer.installed.lock().unwrap(), vec![(1, 3, data)]);
+
+        let mut stages = vec![];
+        while let Some(Ok(event)) = rx.recv().now_or_never() {
+            if let Event::SnapshotTransfer { stage, .. } = *event {
+                stages.push(stage);
+            }
+        }
+        assert!(stages.contains(&SnapshotTransferStage::Failed));
+        assert_eq!(stages.last(), Some(&SnapshotTransferStage::Sent));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_stream_empty() {
+        let writer = MemSnapshot::default();
+        let transport = FlakyTransport {
+            receiver: SnapshotStreamReceiver::new(writer.clone()),
+            sends: AtomicUsize::new(0),
+        };
+        let mut upload = SnapshotUpload::new(
+            &MemSnapshot::default(),
+            1,
+            SnapshotChunkRequest {
+                group_id: 1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        let mut events = EventChannel::new(16);
+        upload.resume(&transport, &mut events).await.unwrap();
+        assert!(upload.is_done());
+        assert_eq!(*writer.installed.lock().unwrap(), vec![(1, 3, vec![])]);
+    }
+}
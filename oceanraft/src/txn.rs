@@ -0,0 +1,656 @@
+//! The two-phase commit of the transactions across groups.
+//!
+//! The `TxnCoordinator` writes the prepare record of the transaction to each
+//! participant group, then the decision record to the coordinator group, and
+//! at last the commit or abort record to the participant groups. The records
+//! are surfaced to the state machines at apply time by `TxnStateMachine` via
+//! the hooks of `TxnParticipant`.
+//!
+//! The decision is durable once it's applied in the coordinator group, if the
+//! coordinator fails after that, the transaction is in doubt until the
+//! decision recorded by `TxnParticipant::decide` is resolved again by
+//! `TxnCoordinator::resolve`.
+
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use futures::future::join_all;
+use futures::Future;
+use serde::Deserialize;
+use serde::Serialize;
+use tokio::sync::oneshot;
+use tracing::debug;
+
+use crate::multiraft::MultiRaftTypeSpecialization;
+use crate::multiraft::ProposeResponse;
+use crate::prelude::ConfState;
+use crate::prelude::SnapshotMetadata;
+use crate::rsm::Apply;
+use crate::rsm::ApplyNoOp;
+use crate::rsm::ApplyNormal;
+use crate::transport::Transport;
+use crate::Error;
+use crate::GroupState;
+use crate::MultiRaft;
+use crate::ProposeData;
+use crate::StateMachine;
+
+/// The decision of the transaction recorded in the coordinator group.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TxnDecision {
+    pub txn_id: u64,
+    /// The participant groups of the transaction.
+    pub participants: Vec<u64>,
+    /// True if all participants prepared the transaction.
+    pub commit: bool,
+}
+
+/// The records of the transaction written to the groups.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TxnRecord {
+    /// Prepare the writes of the participant group, the payload is encoded by
+    /// the application.
+    Prepare {
+        txn_id: u64,
+        coordinator: u64,
+        payload: Vec<u8>,
+    },
+    /// The decision of the transaction in the coordinator group.
+    Decision(TxnDecision),
+    /// Commit the writes prepared by the participant group.
+    Commit { txn_id: u64 },
+    /// Abort the transaction in the participant group, the group may not have
+    /// prepared the transaction.
+    Abort { txn_id: u64 },
+}
+
+/// The propose data of the application which carries the records of the
+/// transactions.
+pub trait TxnData: ProposeData {
+    fn from_txn(record: TxnRecord) -> Self;
+
+    /// Returns the record if the data carries it, otherwise the data itself.
+    fn into_txn(self) -> Result<TxnRecord, Self>;
+}
+
+/// The apply time hooks of the transaction records, they are called by
+/// `TxnStateMachine` in the order of index with the other entries of the
+/// group. The hooks are called on every replica of the group, so they must
+/// be deterministic.
+pub trait TxnParticipant<R: ProposeResponse>: Send + Sync + 'static {
+    /// Prepare the transaction in the group, e.g. lock the keys and stage the
+    /// writes of the payload. Returns the reason if the participant votes to
+    /// abort, which is returned to the coordinator as
+    /// `Error::TxnPrepareRejected`.
+    fn prepare(&self, group_id: u64, txn_id: u64, payload: &[u8]) -> Result<R, String>;
+
+    /// Record the decision of the transaction in the coordinator group.
+    fn decide(&self, group_id: u64, decision: &TxnDecision) -> R;
+
+    /// Commit the writes of the transaction prepared in the group.
+    fn commit(&self, group_id: u64, txn_id: u64) -> R;
+
+    /// Abort the transaction in the group, it's idempotent and may be called
+    /// for the transaction which is not prepared in the group.
+    fn abort(&self, group_id: u64, txn_id: u64) -> R;
+}
+
+/// Proposes the records of the transactions to the groups, e.g. `MultiRaft`
+/// for the groups whose leaders are on the node, or the client which routes
+/// the proposals to the leaders of the groups.
+pub trait TxnProposer<D: TxnData>: Send + Sync {
+    /// GAT trait for `propose`.
+    type ProposeFuture<'life0>: Send + Future<Output = Result<(), Error>> + 'life0
+    where
+        Self: 'life0;
+
+    /// Propose the data to the group, returns `Ok` after it's applied.
+    fn propose(&self, group_id: u64, data: D) -> Self::ProposeFuture<'_>;
+}
+
+impl<T, TR> TxnProposer<T::D> for MultiRaft<T, TR>
+where
+    T: MultiRaftTypeSpecialization,
+    T::D: TxnData,
+    TR: Transport + Clone,
+    Self: Send + Sync,
+{
+    type ProposeFuture<'life0> = impl Future<Output = Result<(), Error>> + Send + 'life0
+    where
+        Self: 'life0;
+
+    fn propose(&self, group_id: u64, data: T::D) -> Self::ProposeFuture<'_> {
+        async move { self.write(group_id, 0, None, data).await.map(|_| ()) }
+    }
+}
+
+impl<D, P> TxnProposer<D> for Arc<P>
+where
+    D: TxnData,
+    P: TxnProposer<D>,
+{
+    type ProposeFuture<'life0> = P::ProposeFuture<'life0>
+    where
+        Self: 'life0;
+
+    fn propose(&self, group_id: u64, data: D) -> Self::ProposeFuture<'_> {
+        self.as_ref().propose(group_id, data)
+    }
+}
+
+/// The coordinator of the two-phase commit, the decisions are recorded in
+/// `coordinator_group`.
+pub struct TxnCoordinator<D, P>
+where
+    D: TxnData,
+    P: TxnProposer<D>,
+{
+    proposer: P,
+    coordinator_group: u64,
+    _m: PhantomData<D>,
+}
+
+impl<D, P> TxnCoordinator<D, P>
+where
+    D: TxnData,
+    P: TxnProposer<D>,
+{
+    pub fn new(proposer: P, coordinator_group: u64) -> Self {
+        Self {
+            proposer,
+            coordinator_group,
+            _m: PhantomData,
+        }
+    }
+
+    /// Execute the transaction with the payloads of the participant groups,
+    /// returns the decision after it's resolved by the participants. The
+    /// transaction commits only if all participants prepared it.
+    ///
+    /// ## Errors
+    /// If the decision failed to record or resolve, the transaction is in
+    /// doubt, it's resolved by the decision recorded by
+    /// `TxnParticipant::decide` in the coordinator group if any, otherwise
+    /// the decision is recorded again.
+    pub async fn execute(
+        &self,
+        txn_id: u64,
+        writes: Vec<(u64, Vec<u8>)>,
+    ) -> Result<TxnDecision, Error> {
+        if writes.is_empty() {
+            return Err(Error::BadParameter(format!(
+                "txn {} has no participant",
+                txn_id
+            )));
+        }
+        let mut groups = HashSet::new();
+        if let Some((group_id, _)) = writes
+            .iter()
+            .find(|(group_id, _)| !groups.insert(*group_id))
+        {
+            return Err(Error::BadParameter(format!(
+                "txn {} has duplicate participant group {}",
+                txn_id, group_id
+            )));
+        }
+
+        let participants = writes.iter().map(|(group_id, _)| *group_id).collect();
+        let prepares = writes.into_iter().map(|(group_id, payload)| {
+            self.proposer.propose(
+                group_id,
+                D::from_txn(TxnRecord::Prepare {
+                    txn_id,
+                    coordinator: self.coordinator_group,
+                    payload,
+                }),
+            )
+        });
+        let mut commit = true;
+        for res in join_all(prepares).await {
+            if let Err(err) = res {
+                debug!("txn {} prepare failed: {}", txn_id, err);
+                commit = false;
+            }
+        }
+
+        let decision = TxnDecision {
+            txn_id,
+            participants,
+            commit,
+        };
+        // the decision may be applied even if the proposal failed, e.g. the
+        // response is lost, so the transaction is left in doubt.
+        self.proposer
+            .propose(
+                self.coordinator_group,
+                D::from_txn(TxnRecord::Decision(decision.clone())),
+            )
+            .await?;
+
+        self.resolve(&decision).await?;
+        Ok(decision)
+    }
+
+    /// Resolve the decision of the transaction by the participant groups, it's
+    /// idempotent and resolves the transactions in doubt after the decisions
+    /// recorded.
+    pub async fn resolve(&self, decision: &TxnDecision) -> Result<(), Error> {
+        let txn_id = decision.txn_id;
+        let resolves = decision.participants.iter().map(|group_id| {
+            let record = if decision.commit {
+                TxnRecord::Commit { txn_id }
+            } else {
+                TxnRecord::Abort { txn_id }
+            };
+            self.proposer.propose(*group_id, D::from_txn(record))
+        });
+        join_all(resolves).await.into_iter().collect()
+    }
+}
+
+/// The state machine which surfaces the records of the transactions to the
+/// `TxnParticipant`, the other entries are applied by the inner state machine
+/// and the records are applied as no-op by it, so the inner state machine
+/// sees the entries without gaps.
+pub struct TxnStateMachine<M, H> {
+    inner: M,
+    participant: H,
+}
+
+impl<M, H> TxnStateMachine<M, H> {
+    pub fn new(inner: M, participant: H) -> Self {
+        Self { inner, participant }
+    }
+}
+
+/// The applys of the inner state machine and the records of the transactions
+/// in the order of index.
+enum TxnApplySegment<F, R: ProposeResponse> {
+    Inner(F),
+    Record {
+        record: TxnRecord,
+        context: Option<Vec<u8>>,
+        tx: Option<oneshot::Sender<Result<(R, Option<Vec<u8>>), Error>>>,
+    },
+}
+
+impl<M, H> TxnStateMachine<M, H> {
+    fn apply_record<R: ProposeResponse>(&self, group_id: u64, record: TxnRecord) -> Result<R, Error>
+    where
+        H: TxnParticipant<R>,
+    {
+        match record {
+            TxnRecord::Prepare {
+                txn_id, payload, ..
+            } => self
+                .participant
+                .prepare(group_id, txn_id, &payload)
+                .map_err(|reason| Error::TxnPrepareRejected {
+                    txn_id,
+                    group_id,
+                    reason,
+                }),
+            TxnRecord::Decision(decision) => Ok(self.participant.decide(group_id, &decision)),
+            TxnRecord::Commit { txn_id } => Ok(self.participant.commit(group_id, txn_id)),
+            TxnRecord::Abort { txn_id } => Ok(self.participant.abort(group_id, txn_id)),
+        }
+    }
+}
+
+impl<W, R, M, H> StateMachine<W, R> for TxnStateMachine<M, H>
+where
+    W: TxnData,
+    R: ProposeResponse,
+    M: StateMachine<W, R>,
+    H: TxnParticipant<R>,
+{
+    type ApplyFuture<'life0> = impl Future<Output = ()> + 'life0
+    where
+        Self: 'life0;
+
+    fn apply<'life0>(
+        &'life0 self,
+        group_id: u64,
+        replica_id: u64,
+        state: &GroupState,
+        applys: Vec<Apply<W, R>>,
+    ) -> Self::ApplyFuture<'life0> {
+        // the applys of the inner state machine are split by the records, the
+        // futures are created here since the state is not captured by them.
+        let mut segments = vec![];
+        let mut run = vec![];
+        for apply in applys {
+            let normal = match apply {
+                Apply::Normal(normal) => normal,
+                apply => {
+                    run.push(apply);
+                    continue;
+                }
+            };
+            let ApplyNormal {
+                group_id: normal_group_id,
+                index,
+                term,
+                data,
+                context,
+                proposer,
+                is_conf_change,
+                tx,
+            } = normal;
+            match data.into_txn() {
+                Err(data) => run.push(Apply::Normal(ApplyNormal {
+                    group_id: normal_group_id,
+                    index,
+                    term,
+                    data,
+                    context,
+                    proposer,
+                    is_conf_change,
+                    tx,
+                })),
+                Ok(record) => {
+                    if !run.is_empty() {
+                        segments.push(TxnApplySegment::Inner(self.inner.apply(
+                            group_id,
+                            replica_id,
+                            state,
+                            std::mem::take(&mut run),
+                        )));
+                    }
+                    run.push(Apply::NoOp(ApplyNoOp {
+                        group_id: normal_group_id,
+                        index,
+                        term,
+                    }));
+                    segments.push(TxnApplySegment::Record {
+                        record,
+                        context,
+                        tx,
+                    });
+                }
+            }
+        }
+        if !run.is_empty() {
+            segments.push(TxnApplySegment::Inner(
+                self.inner.apply(group_id, replica_id, state, run),
+            ));
+        }
+
+        async move {
+            for segment in segments {
+                match segment {
+                    TxnApplySegment::Inner(fut) => fut.await,
+                    TxnApplySegment::Record {
+                        record,
+                        context,
+                        tx,
+                    } => {
+                        let res = self.apply_record(group_id, record);
+                        if let Some(tx) = tx {
+                            let _ = tx.send(res.map(|res| (res, context)));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn on_group_created(&self, group_id: u64, replica_id: u64, conf_state: &ConfState) {
+        self.inner
+            .on_group_created(group_id, replica_id, conf_state)
+    }
+
+    fn on_group_removed(&self, group_id: u64, replica_id: u64) {
+        self.inner.on_group_removed(group_id, replica_id)
+    }
+
+    fn on_snapshot_installed(&self, group_id: u64, replica_id: u64, metadata: &SnapshotMetadata) {
+        self.inner
+            .on_snapshot_installed(group_id, replica_id, metadata)
+    }
+}
+
+// the test data is serialized by serde.
+#[cfg(all(test, not(feature = "prost-data")))]
+mod test {
+    use std::sync::Mutex;
+
+    use futures::future::ready;
+    use futures::future::Ready;
+    use futures::Future;
+    use serde::Deserialize;
+    use serde::Serialize;
+    use tokio::sync::oneshot;
+
+    use super::TxnCoordinator;
+    use super::TxnData;
+    use super::TxnDecision;
+    use super::TxnParticipant;
+    use super::TxnProposer;
+    use super::TxnRecord;
+    use super::TxnStateMachine;
+    use crate::Apply;
+    use crate::ApplyNormal;
+    use crate::Error;
+    use crate::GroupState;
+    use crate::StateMachine;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum TestData {
+        Put(u64),
+        Txn(TxnRecord),
+    }
+
+    impl TxnData for TestData {
+        fn from_txn(record: TxnRecord) -> Self {
+            TestData::Txn(record)
+        }
+
+        fn into_txn(self) -> Result<TxnRecord, Self> {
+            match self {
+                TestData::Txn(record) => Ok(record),
+                data => Err(data),
+            }
+        }
+    }
+
+    /// Records the proposals and rejects the prepares of the group.
+    #[derive(Default)]
+    struct TestProposer {
+        reject_group: u64,
+        proposals: Mutex<Vec<(u64, TxnRecord)>>,
+    }
+
+    impl TxnProposer<TestData> for TestProposer {
+        type ProposeFuture<'life0> = Ready<Result<(), Error>>
+        where
+            Self: 'life0;
+
+        fn propose(&self, group_id: u64, data: TestData) -> Self::ProposeFuture<'_> {
+            let record = data.into_txn().unwrap();
+            let rejected =
+                matches!(record, TxnRecord::Prepare { .. }) && group_id == self.reject_group;
+            self.proposals.lock().unwrap().push((group_id, record));
+            if rejected {
+                return ready(Err(Error::TxnPrepareRejected {
+                    txn_id: 1,
+                    group_id,
+                    reason: "locked".to_owned(),
+                }));
+            }
+            ready(Ok(()))
+        }
+    }
+
+    fn prepare(txn_id: u64, payload: &[u8]) -> TxnRecord {
+        TxnRecord::Prepare {
+            txn_id,
+            coordinator: 1,
+            payload: payload.to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_txn_coordinator() {
+        let coordinator = TxnCoordinator::new(TestProposer::default(), 1);
+        let decision = coordinator
+            .execute(7, vec![(2, b"a".to_vec()), (3, b"b".to_vec())])
+            .await
+            .unwrap();
+        let want = TxnDecision {
+            txn_id: 7,
+            participants: vec![2, 3],
+            commit: true,
+        };
+        assert_eq!(decision, want);
+        assert_eq!(
+            *coordinator.proposer.proposals.lock().unwrap(),
+            vec![
+                (2, prepare(7, b"a")),
+                (3, prepare(7, b"b")),
+                (1, TxnRecord::Decision(want)),
+                (2, TxnRecord::Commit { txn_id: 7 }),
+                (3, TxnRecord::Commit { txn_id: 7 }),
+            ]
+        );
+
+        // the transaction aborts if any participant rejects to prepare.
+        let proposer = TestProposer {
+            reject_group: 3,
+            ..Default::default()
+        };
+        let coordinator = TxnCoordinator::new(proposer, 1);
+        let decision = coordinator
+            .execute(8, vec![(2, b"a".to_vec()), (3, b"b".to_vec())])
+            .await
+            .unwrap();
+        assert!(!decision.commit);
+        let proposals = coordinator.proposer.proposals.lock().unwrap();
+        assert_eq!(
+            proposals[3..],
+            [
+                (2, TxnRecord::Abort { txn_id: 8 }),
+                (3, TxnRecord::Abort { txn_id: 8 }),
+            ]
+        );
+        drop(proposals);
+
+        assert!(coordinator.execute(9, vec![]).await.is_err());
+        let writes = vec![(2, b"a".to_vec()), (2, b"b".to_vec())];
+        assert!(coordinator.execute(9, writes).await.is_err());
+    }
+
+    /// Records the index of the applys and whether they are no-op.
+    #[derive(Default)]
+    struct TestStateMachine {
+        applied: Mutex<Vec<(u64, bool)>>,
+    }
+
+    impl StateMachine<TestData, ()> for TestStateMachine {
+        type ApplyFuture<'life0> = impl Future<Output = ()> + 'life0
+        where
+            Self: 'life0;
+
+        fn apply<'life0>(
+            &'life0 self,
+            _group_id: u64,
+            _replica_id: u64,
+            _state: &GroupState,
+            applys: Vec<Apply<TestData, ()>>,
+        ) -> Self::ApplyFuture<'life0> {
+            async move {
+                let mut applied = self.applied.lock().unwrap();
+                for apply in applys {
+                    applied.push((apply.get_index(), matches!(apply, Apply::NoOp(_))));
+                }
+            }
+        }
+    }
+
+    #[derive(Default)]
+    struct TestParticipant {
+        records: Mutex<Vec<String>>,
+    }
+
+    impl TxnParticipant<()> for TestParticipant {
+        fn prepare(&self, group_id: u64, txn_id: u64, payload: &[u8]) -> Result<(), String> {
+            if payload.is_empty() {
+                return Err("empty".to_owned());
+            }
+            let record = format!("prepare {} {}", group_id, txn_id);
+            self.records.lock().unwrap().push(record);
+            Ok(())
+        }
+
+        fn decide(&self, group_id: u64, decision: &TxnDecision) {
+            let record = format!("decide {} {}", group_id, decision.txn_id);
+            self.records.lock().unwrap().push(record);
+        }
+
+        fn commit(&self, group_id: u64, txn_id: u64) {
+            let record = format!("commit {} {}", group_id, txn_id);
+            self.records.lock().unwrap().push(record);
+        }
+
+        fn abort(&self, group_id: u64, txn_id: u64) {
+            let record = format!("abort {} {}", group_id, txn_id);
+            self.records.lock().unwrap().push(record);
+        }
+    }
+
+    fn normal(
+        index: u64,
+        data: TestData,
+    ) -> (
+        Apply<TestData, ()>,
+        oneshot::Receiver<Result<((), Option<Vec<u8>>), Error>>,
+    ) {
+        let (tx, rx) = oneshot::channel();
+        let apply = Apply::Normal(ApplyNormal {
+            group_id: 2,
+            index,
+            term: 1,
+            data,
+            context: None,
+            proposer: None,
+            is_conf_change: false,
+            tx: Some(tx),
+        });
+        (apply, rx)
+    }
+
+    #[tokio::test]
+    async fn test_txn_state_machine() {
+        let state_machine =
+            TxnStateMachine::new(TestStateMachine::default(), TestParticipant::default());
+        let (put1, _) = normal(1, TestData::Put(1));
+        let (prepare1, rx1) = normal(2, TestData::Txn(prepare(7, b"a")));
+        let (prepare2, rx2) = normal(3, TestData::Txn(prepare(8, b"")));
+        let (put2, _) = normal(4, TestData::Put(2));
+        let (commit, _) = normal(5, TestData::Txn(TxnRecord::Commit { txn_id: 7 }));
+        state_machine
+            .apply(
+                2,
+                1,
+                &GroupState::default(),
+                vec![put1, prepare1, prepare2, put2, commit],
+            )
+            .await;
+
+        // the records are applied as no-op by the inner state machine.
+        assert_eq!(
+            *state_machine.inner.applied.lock().unwrap(),
+            vec![(1, false), (2, true), (3, true), (4, false), (5, true)]
+        );
+        assert_eq!(
+            *state_machine.participant.records.lock().unwrap(),
+            vec!["prepare 2 7".to_owned(), "commit 2 7".to_owned()]
+        );
+        assert!(rx1.await.unwrap().is_ok());
+        assert!(matches!(
+            rx2.await.unwrap(),
+            Err(Error::TxnPrepareRejected { txn_id: 8, .. })
+        ));
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::collections::VecDeque;
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use crate::prelude::MultiRaftMessage;
+
+/// UnknownGroupMessages is the parking lot of raft messages whose group does
+/// not exist on the node. The messages are delivered if the group is created
+/// within `ttl_ticks`, otherwise they are dropped and counted.
+///
+/// A capacity of `0` means no message is buffered.
+pub struct UnknownGroupMessages {
+    capacity: usize,
+    ttl_ticks: u64,
+    now: u64,
+    len: usize,
+    /// (expire tick, message) of groups in the order of arrival.
+    msgs: HashMap<u64, VecDeque<(u64, MultiRaftMessage)>>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl UnknownGroupMessages {
+    pub fn new(capacity: usize, ttl_ticks: usize, dropped: Arc<AtomicU64>) -> Self {
+        Self {
+            capacity,
+            ttl_ticks: ttl_ticks as u64,
+            now: 0,
+            len: 0,
+            msgs: HashMap::new(),
+            dropped,
+        }
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Buffer the message for its group. Returns `false` and the message is
+    /// dropped if the capacity is exceeded.
+    pub fn push(&mut self, msg: MultiRaftMessage) -> bool {
+        if self.len >= self.capacity {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+
+        self.msgs
+            .entry(msg.group_id)
+            .or_default()
+            .push_back((self.now + self.ttl_ticks, msg));
+        self.len += 1;
+        true
+    }
+
+    /// Take the buffered messages of the group which are not expired.
+    pub fn take(&mut self, group_id: u64) -> Vec<MultiRaftMessage> {
+        let msgs = match self.msgs.remove(&group_id) {
+            None => return vec![],
+            Some(msgs) => msgs,
+        };
+        self.len -= msgs.len();
+
+        let now = self.now;
+        let mut expired = 0;
+        let msgs = msgs
+            .into_iter()
+            .filter_map(|(expire, msg)| {
+                if expire <= now {
+                    expired += 1;
+                    None
+                } else {
+                    Some(msg)
+                }
+            })
+            .collect();
+        self.dropped.fetch_add(expired, Ordering::Relaxed);
+        msgs
+    }
+
+    /// Advance the clock and drop the expired messages. Returns the number of
+    /// dropped messages.
+    pub fn tick(&mut self) -> usize {
+        self.now += 1;
+        if self.is_empty() {
+            return 0;
+        }
+
+        let now = self.now;
+        let mut expired = 0;
+        self.msgs.retain(|_, msgs| {
+            while let Some((expire, _)) = msgs.front() {
+                if *expire > now {
+                    break;
+                }
+                msgs.pop_front();
+                expired += 1;
+            }
+            !msgs.is_empty()
+        });
+        self.len -= expired;
+        self.dropped.fetch_add(expired as u64, Ordering::Relaxed);
+        expired
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::AtomicU64;
+    use std::sync::atomic::Ordering;
+    use std::sync::Arc;
+
+    use super::UnknownGroupMessages;
+    use crate::prelude::MultiRaftMessage;
+
+    fn new_msg(group_id: u64) -> MultiRaftMessage {
+        MultiRaftMessage {
+            group_id,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_unknown_group_messages_capacity() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let mut msgs = UnknownGroupMessages::new(2, 10, dropped.clone());
+        assert!(msgs.push(new_msg(1)));
+        assert!(msgs.push(new_msg(2)));
+        assert!(!msgs.push(new_msg(1)));
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(dropped.load(Ordering::Relaxed), 1);
+
+        assert_eq!(msgs.take(1).len(), 1);
+        assert_eq!(msgs.take(1).len(), 0);
+        assert_eq!(msgs.len(), 1);
+        assert!(msgs.push(new_msg(1)));
+    }
+
+    #[test]
+    fn test_unknown_group_messages_expire() {
+        let dropped = Arc::new(AtomicU64::new(0));
+        let mut msgs = UnknownGroupMessages::new(10, 2, dropped.clone());
+        assert!(msgs.push(new_msg(1)));
+        assert_eq!(msgs.tick(), 0);
+        assert!(msgs.push(new_msg(1)));
+        assert!(msgs.push(new_msg(2)));
+
+        // the first message of group 1 is expired.
+        assert_eq!(msgs.tick(), 1);
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(msgs.take(1).len(), 1);
+
+        assert_eq!(msgs.tick(), 1);
+        assert_eq!(msgs.len(), 0);
+        assert_eq!(dropped.load(Ordering::Relaxed), 2);
+    }
+}
@@ -8,6 +8,7 @@ use super::error::DeserializationError;
 use super::error::SerializationError;
 use super::prelude::Entry;
 use super::Error;
+use super::ProposeData;
 
 /// Defers evaluation of a block of code until the end of the scope.
 #[doc(hidden)]
@@ -113,4 +114,34 @@ where
         .map_err(|err| Error::Deserialization(DeserializationError::Flexbuffer(err)))
 }
 
+/// Encode the proposed data as the data of the entry, it's serialized by
+/// flexbuffer unless the `prost-data` feature is enabled.
+#[cfg(not(feature = "prost-data"))]
+#[inline]
+pub(crate) fn encode_propose_data<D: ProposeData>(data: &D) -> Result<Vec<u8>, Error> {
+    flexbuffer_serialize(data).map(|mut ser| ser.take_buffer())
+}
+
+/// Encode the proposed data as the data of the entry by prost, the serde
+/// codec is not involved.
+#[cfg(feature = "prost-data")]
+#[inline]
+pub(crate) fn encode_propose_data<D: ProposeData>(data: &D) -> Result<Vec<u8>, Error> {
+    Ok(data.encode_to_vec())
+}
+
+/// Decode the proposed data from the data of the entry, see
+/// `encode_propose_data`.
+#[cfg(not(feature = "prost-data"))]
+#[inline]
+pub(crate) fn decode_propose_data<D: ProposeData>(data: &[u8]) -> Result<D, Error> {
+    flexbuffer_deserialize(data)
+}
+
+#[cfg(feature = "prost-data")]
+#[inline]
+pub(crate) fn decode_propose_data<D: ProposeData>(data: &[u8]) -> Result<D, Error> {
+    D::decode(data).map_err(|err| Error::Deserialization(DeserializationError::Prost(err)))
+}
+
 pub use defer;
@@ -8,4 +8,9 @@ mod t10_bad_write;
 mod t20_basic_write;
 mod t30_stale_write;
 mod t40_read_index;
-mod t50_storage_failure;
\ No newline at end of file
+mod t50_storage_failure;
+mod t60_random_workload;
+mod t70_authorizer;
+mod t80_admin;
+mod t90_log_quota;
+mod t100_apply_backlog;
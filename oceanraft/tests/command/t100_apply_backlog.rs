@@ -0,0 +1,125 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::prelude::StoreData;
+use oceanraft::Event;
+use tokio::time::sleep;
+use tokio::time::timeout;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::rand_string;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_apply_backlog_high() {
+    let nodes = 3;
+    let group_id = 1;
+    let threshold = 5;
+    // the state machines block once an apply is left unreceived.
+    let mut env = MemStoreEnv::with_apply_capacity(nodes, 1);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .apply_backlog_threshold(threshold)
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: nodes,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    let election = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(election.leader_id, 1);
+
+    // wait the entries of the election are applied, the apply is left in
+    // the channel, so the state machine of the leader blocks on the next
+    // apply.
+    let state = cluster.nodes[0].group_state(group_id).unwrap();
+    for _ in 0..100 {
+        if state.get_commit_index() != 0 && state.get_apply_backlog() == 0 {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    let applied = state.get_applied_index();
+    assert_ne!(applied, 0);
+    assert_eq!(state.get_apply_backlog(), 0);
+
+    let events = cluster.nodes[0].subscribe();
+    let writes = 20;
+    let mut recvs = vec![];
+    for _ in 0..writes {
+        let data = StoreData {
+            key: rand_string(4),
+            value: rand_string(16).as_bytes().to_vec(),
+        };
+        recvs.push(cluster.write_command(1, group_id, data).unwrap());
+    }
+
+    // the writes are committed while the state machine is blocked.
+    for _ in 0..100 {
+        if state.get_apply_backlog() == writes as u64 {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(state.get_apply_backlog(), writes as u64);
+    assert_eq!(state.get_applied_index(), applied);
+    assert!(state.get_apply_backlog_bytes() > 0);
+
+    let applys = cluster
+        .wait_for_commands_apply(1, writes, Duration::from_millis(1000))
+        .await
+        .unwrap();
+    for apply in applys {
+        apply.tx.map(|tx| tx.send(Ok(((), None))));
+    }
+    for rx in recvs {
+        assert!(rx.await.unwrap().is_ok());
+    }
+
+    let (backlog, backlog_bytes) = timeout(Duration::from_secs(1), async {
+        loop {
+            match *events.recv().await.unwrap() {
+                Event::ApplyBacklogHigh {
+                    group_id: id,
+                    leader_id,
+                    backlog,
+                    backlog_bytes,
+                    ..
+                } => {
+                    assert_eq!(id, group_id);
+                    assert_eq!(leader_id, 1);
+                    return (backlog, backlog_bytes);
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert!(backlog > threshold);
+    assert!(backlog <= writes as u64);
+    assert!(backlog_bytes > 0);
+
+    // the backlog is drained after the state machine catches up.
+    for _ in 0..100 {
+        if state.get_apply_backlog() == 0 {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert_eq!(state.get_apply_backlog(), 0);
+    assert_eq!(state.get_applied_index(), applied + writes as u64);
+}
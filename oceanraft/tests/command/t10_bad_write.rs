@@ -51,15 +51,23 @@ async fn test_no_leader() {
             key: "key".to_string(),
             value: "data".as_bytes().to_vec(),
         };
-        let expected_err = Error::Propose(ProposeError::NotLeader {
+        // no replica contacted others, so there are no candidates.
+        let expected_err = Error::Propose(ProposeError::LeaderUnknown {
             node_id,
             group_id: plan.group_id,
             replica_id: i + 1,
+            candidates: vec![],
         });
 
         match cluster.write_command(node_id, plan.group_id, data) {
             Ok(res) => panic!("expected {:?}, got {:?}", expected_err, res),
-            Err(err) => assert_eq!(expected_err.to_string(), err.to_string()),
+            Err(err) => {
+                assert_eq!(err.request_id().is_some(), true);
+                assert_eq!(
+                    expected_err.to_string(),
+                    err.without_request_id().to_string()
+                );
+            }
         }
     }
 
@@ -110,13 +118,81 @@ async fn test_bad_group() {
             group_id: plan.group_id,
             replica_id: i + 1,
         });
-        match cluster.write_command(node_id, plan.group_id, data) {
-            Err(err) => assert_eq!(expected_err.to_string(), err.to_string()),
+        let err = match cluster.write_command(node_id, plan.group_id, data) {
+            Err(err) => err,
             Ok(rx) => match rx.await.unwrap() {
                 Ok(res) => panic!("expected {:?}, got {:?}", expected_err, res),
-                Err(err) => assert_eq!(expected_err.to_string(), err.to_string()),
+                Err(err) => err,
             },
+        };
+        assert_eq!(err.request_id().is_some(), true);
+        // the follower may not have received the append from the new leader,
+        // then the leader is unknown and the leader is the recent candidate.
+        match err.without_request_id() {
+            Error::Propose(ProposeError::NotLeader { .. }) => assert_eq!(
+                expected_err.to_string(),
+                err.without_request_id().to_string()
+            ),
+            Error::Propose(ProposeError::LeaderUnknown {
+                node_id: err_node_id,
+                replica_id,
+                candidates,
+                ..
+            }) => {
+                assert_eq!(*err_node_id, node_id);
+                assert_eq!(*replica_id, i + 1);
+                assert_eq!(candidates.first().map(|c| c.replica_id), Some(1));
+            }
+            err => panic!("expected {:?}, got {:?}", expected_err, err),
         }
     }
     // cluster.stop().await;
 }
+
+/// The followers learn the leader from the appends and expose it via the
+/// shared state.
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_follower_leader_hint() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::<MemType>::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .storages(env.storages.clone())
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id: 1,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, plan.group_id).await;
+    let _ = cluster.wait_leader_elect_event(1).await.unwrap();
+
+    let data = StoreData {
+        key: "key".to_string(),
+        value: "data".as_bytes().to_vec(),
+    };
+    let _ = cluster
+        .write_command(1, plan.group_id, data)
+        .unwrap()
+        .await
+        .unwrap()
+        .unwrap();
+
+    for node_id in 2..=3 {
+        let state = cluster.nodes[node_id - 1]
+            .group_state(plan.group_id)
+            .unwrap();
+        assert_eq!(state.get_leader_id(), 1);
+        assert_eq!(state.get_leader_node_id(), 1);
+        assert_eq!(state.recent_candidates()[0].replica_id, 1);
+    }
+}
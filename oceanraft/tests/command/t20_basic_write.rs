@@ -60,6 +60,87 @@ async fn test_group_write() {
     // cluster.stop().await;
 }
 
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_group_write_commit_metadata() {
+    let nodes = 3;
+    let mut rockstore_env = RockStoreEnv::new(nodes);
+    let mut cluster = quickstart_rockstore_group(&mut rockstore_env, nodes).await;
+
+    let group_id = 1;
+    let data = StoreData {
+        key: rand_string(4),
+        value: rand_string(8).as_bytes().to_vec(),
+    };
+    let leader = cluster.nodes[0].clone();
+    let write = tokio::spawn(async move { leader.write(group_id, 0, None, data).await });
+    cluster.tickers[0].non_blocking_tick();
+
+    let mut events = cluster
+        .wait_for_commands_apply(1, 1, Duration::from_millis(1000))
+        .await
+        .unwrap();
+    let event = events.pop().unwrap();
+    let (index, term) = (event.index, event.term);
+    event.tx.map(|tx| tx.send(Ok(((), None))));
+
+    let resp = write.await.unwrap().unwrap();
+    assert_eq!(resp.index, index);
+    assert_eq!(resp.term, term);
+    assert!(resp.commit_latency <= resp.apply_latency);
+
+    rockstore_env.destory()
+}
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_group_write_local_context() {
+    let nodes = 3;
+    let mut rockstore_env = RockStoreEnv::new(nodes);
+    let mut cluster = quickstart_rockstore_group(&mut rockstore_env, nodes).await;
+
+    let group_id = 1;
+    let data = StoreData {
+        key: rand_string(4),
+        value: rand_string(8).as_bytes().to_vec(),
+    };
+    let leader = cluster.nodes[0].clone();
+    let write = tokio::spawn(async move {
+        leader
+            .write_local_context(group_id, 0, b"context".to_vec(), data)
+            .await
+    });
+    cluster.tickers[0].non_blocking_tick();
+
+    // the context is passed to the state machine of the proposer.
+    let mut events = cluster
+        .wait_for_commands_apply(1, 1, Duration::from_millis(1000))
+        .await
+        .unwrap();
+    let event = events.pop().unwrap();
+    assert_eq!(event.context, Some(b"context".to_vec()));
+    event.tx.map(|tx| tx.send(Ok(((), None))));
+    assert!(write.await.unwrap().is_ok());
+
+    // the context is not written into the log.
+    for _ in 0..3 {
+        cluster.tickers[0].non_blocking_tick();
+    }
+    let mut events = cluster
+        .wait_for_commands_apply(2, 1, Duration::from_millis(1000))
+        .await
+        .unwrap();
+    assert_eq!(events.pop().unwrap().context, None);
+
+    rockstore_env.destory()
+}
+
 #[async_entry::test(
     flavor = "multi_thread",
     init = "init_default_ut_tracing()",
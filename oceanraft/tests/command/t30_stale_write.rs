@@ -1,11 +1,18 @@
+use std::mem::take;
 use std::time::Duration;
 
 use oceanraft::prelude::StoreData;
+use oceanraft::Error;
+use oceanraft::ProposeError;
+use tokio::time::sleep;
 
 use crate::fixtures::init_default_ut_tracing;
 use crate::fixtures::quickstart_rockstore_group;
 use crate::fixtures::rand_string;
 use crate::fixtures::Cluster;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
 use crate::fixtures::RockStoreEnv;
 use crate::fixtures::WriteChecker;
 
@@ -94,3 +101,162 @@ async fn test_group_stale_write() {
     rockstore_env.destory();
     // cluster.stop().await;
 }
+
+/// The writes beyond the max inflight proposals of the group are held until
+/// the proposals are committed, the writes beyond the held ones fail.
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_max_inflight_proposals() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .max_inflight_proposals(2, true)
+        .build()
+        .await;
+
+    let group_id = 1;
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    let _ = cluster.wait_leader_elect_event(1).await.unwrap();
+
+    // the proposals can't commit while the leader is disconnected.
+    cluster.transport.disconnect(1, 2).await;
+    cluster.transport.disconnect(1, 3).await;
+
+    let mut write_checker = WriteChecker::default();
+    let mut recvs = vec![];
+    for _ in 0..4 {
+        let data = StoreData {
+            key: rand_string(4),
+            value: rand_string(8).as_bytes().to_vec(),
+        };
+        write_checker.insert_write(group_id, data.clone());
+        recvs.push(cluster.write_command(1, group_id, data).unwrap());
+    }
+    let data = StoreData {
+        key: rand_string(4),
+        value: rand_string(8).as_bytes().to_vec(),
+    };
+    let rejected = cluster.write_command(1, group_id, data).unwrap();
+    match rejected.await.unwrap() {
+        Err(Error::Propose(ProposeError::TooManyInflightProposals { limit: 2, .. })) => {}
+        res => panic!("expected TooManyInflightProposals, got {:?}", res),
+    }
+
+    // the held writes are proposed after the inflight proposals committed.
+    cluster.transport.reconnect(1, 2).await;
+    cluster.transport.reconnect(1, 3).await;
+    for _ in 0..5 {
+        cluster.tick_node(1, Some(Duration::from_millis(10))).await;
+    }
+    let events = cluster
+        .wait_for_commands_apply(1, 4, Duration::from_millis(5000))
+        .await
+        .unwrap();
+    write_checker.check(&events);
+    for event in events {
+        event.tx.map(|tx| tx.send(Ok(((), None))));
+    }
+    for rx in recvs {
+        assert!(rx.await.unwrap().is_ok());
+    }
+}
+
+/// The write proposed with a term older than the current term of the group
+/// is rejected by the `MultiRaft` before it is sent to the node.
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_stale_term_write() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let group_id = 1;
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    let el = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(el.leader_id, 1);
+    // the term of the shared state is set after the hard state is persisted.
+    let state = cluster.nodes[0].group_state(group_id).unwrap();
+    for _ in 0..100 {
+        if state.get_term() != 0 {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    let stale_term = state.get_term();
+    assert_ne!(stale_term, 0);
+
+    // the leader is changed, so the term of the group goes ahead.
+    cluster.campaign_group(2, group_id).await;
+    let el = cluster.wait_leader_elect_event(2).await.unwrap();
+    assert_eq!(el.leader_id, 2);
+    let state = cluster.nodes[1].group_state(group_id).unwrap();
+    for _ in 0..100 {
+        if state.get_term() > stale_term {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    let current_term = state.get_term();
+    assert!(current_term > stale_term);
+
+    // the stale write returns the error instead of the receiver.
+    let data = StoreData {
+        key: rand_string(4),
+        value: rand_string(8).as_bytes().to_vec(),
+    };
+    match cluster.nodes[1].write_non_block(group_id, stale_term, None, data) {
+        Err(err) => match err.without_request_id() {
+            Error::Propose(ProposeError::Stale(term, current)) => {
+                assert_eq!(*term, stale_term);
+                assert_eq!(*current, current_term);
+            }
+            err => panic!("expected Stale, got {:?}", err),
+        },
+        Ok(_) => panic!("expected the stale write rejected"),
+    }
+
+    // the write with the current term is proposed.
+    let data = StoreData {
+        key: rand_string(4),
+        value: rand_string(8).as_bytes().to_vec(),
+    };
+    let rx = cluster.nodes[1]
+        .write_non_block(group_id, current_term, None, data)
+        .unwrap();
+    let applys = cluster
+        .wait_for_commands_apply(2, 1, Duration::from_millis(1000))
+        .await
+        .unwrap();
+    for apply in applys {
+        apply.tx.map(|tx| tx.send(Ok(((), None))));
+    }
+    assert!(rx.await.unwrap().is_ok());
+}
@@ -1,10 +1,19 @@
 use std::collections::HashMap;
+use std::mem::take;
 use std::time::Duration;
 
+use futures::StreamExt;
 use oceanraft::prelude::StoreData;
+use oceanraft::ConsistencyLevel;
+use oceanraft::Error;
+use oceanraft::ProposeError;
+use oceanraft::ReadStats;
 
 use crate::fixtures::init_default_ut_tracing;
 use crate::fixtures::quickstart_rockstore_group;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
 use crate::fixtures::RockStoreEnv;
 
 #[async_entry::test(
@@ -75,3 +84,377 @@ async fn test_group_read_index() {
     // commit -> 1 -> 2 -> 3
     // read  -> commit_index = 4 (fatal, consistency)
 }
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_group_read_consistency_levels() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .check_quorum(true)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let group_id = 1;
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    for node_id in 1..=nodes as u64 {
+        let _ = cluster.wait_leader_elect_event(node_id).await.unwrap();
+    }
+
+    // the linearizable read waits the leader to commit in its term.
+    let ctx = Some(b"ctx".to_vec());
+    let res = cluster.nodes[0].read_index(group_id, ctx.clone()).await;
+    assert_eq!(res.unwrap(), ctx);
+
+    // the leader holds the lease with check quorum.
+    let res = cluster.nodes[0]
+        .read(group_id, ConsistencyLevel::LeaderLocal, ctx.clone())
+        .await;
+    assert_eq!(res.unwrap(), ctx);
+    assert_eq!(
+        cluster.nodes[0].read_stats(),
+        ReadStats {
+            linearizable: 1,
+            leader_local: 1,
+            ..Default::default()
+        }
+    );
+
+    // the follower heard from the leader recently.
+    let bound = ConsistencyLevel::BoundedStaleness(Duration::from_secs(10));
+    let res = cluster.nodes[1].read(group_id, bound, ctx.clone()).await;
+    assert_eq!(res.unwrap(), ctx);
+
+    // the read falls back to read index if the staleness bound is exceeded.
+    let bound = ConsistencyLevel::BoundedStaleness(Duration::ZERO);
+    let res = cluster.nodes[1].read(group_id, bound, ctx.clone()).await;
+    assert_eq!(res.unwrap(), ctx);
+    assert_eq!(
+        cluster.nodes[1].read_stats(),
+        ReadStats {
+            bounded_staleness: 2,
+            bounded_staleness_fallbacks: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_group_lease_read() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .check_quorum(true)
+        .enable_lease_read(true)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let group_id = 1;
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    for node_id in 1..=nodes as u64 {
+        let _ = cluster.wait_leader_elect_event(node_id).await.unwrap();
+    }
+
+    // the first lease read falls back to read index which renews the lease,
+    // the lease reads are served with or without the round trip.
+    let ctx = Some(b"ctx".to_vec());
+    for _ in 0..3 {
+        let res = cluster.nodes[0].lease_read(group_id, ctx.clone()).await;
+        assert_eq!(res.unwrap(), ctx);
+    }
+    let stats = cluster.nodes[0].read_stats();
+    assert_eq!(stats.lease, 3);
+    assert!(stats.lease_fallbacks >= 1);
+
+    // the follower has no lease.
+    let res = cluster.nodes[1].lease_read(group_id, ctx.clone()).await;
+    assert_eq!(res.unwrap(), ctx);
+    assert_eq!(
+        cluster.nodes[1].read_stats(),
+        ReadStats {
+            lease: 1,
+            lease_fallbacks: 1,
+            ..Default::default()
+        }
+    );
+}
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_group_follower_read() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let group_id = 1;
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    for node_id in 1..=nodes as u64 {
+        let _ = cluster.wait_leader_elect_event(node_id).await.unwrap();
+    }
+
+    // the followers forward the read index request to the leader.
+    let ctx = Some(b"ctx".to_vec());
+    for node_id in 1..=nodes as u64 {
+        let res = cluster.nodes[node_id as usize - 1]
+            .follower_read(group_id, ctx.clone())
+            .await;
+        assert_eq!(res.unwrap(), ctx);
+        assert_eq!(
+            cluster.nodes[node_id as usize - 1].read_stats(),
+            ReadStats {
+                follower: 1,
+                ..Default::default()
+            }
+        );
+    }
+}
+
+/// The pending reads of the leader fail with the hint of the new leader once
+/// it lost the leadership, instead of waiting the confirmation forever.
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_group_read_index_leadership_lost() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let group_id = 1;
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    for node_id in 1..=nodes as u64 {
+        let _ = cluster.wait_leader_elect_event(node_id).await.unwrap();
+    }
+
+    // the read can't be confirmed by the quorum.
+    cluster.transport.disconnect(1, 2).await;
+    cluster.transport.disconnect(1, 3).await;
+    let node = cluster.nodes[0].clone();
+    let read = tokio::spawn(async move { node.read_index(group_id, None).await });
+
+    cluster.campaign_group(2, group_id).await;
+    for node_id in 2..=nodes as u64 {
+        let el = cluster.wait_leader_elect_event(node_id).await.unwrap();
+        assert_eq!(el.leader_id, 2);
+    }
+    cluster.transport.reconnect(1, 2).await;
+    cluster.transport.reconnect(1, 3).await;
+
+    // the old leader steps down by the heartbeat of the new leader.
+    while !read.is_finished() {
+        cluster.tickers[1].tick().await;
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    match read.await.unwrap() {
+        Err(err) => assert!(
+            matches!(
+                err.without_request_id(),
+                Error::Propose(ProposeError::NotLeader { .. })
+            ),
+            "expected not leader, got {:?}",
+            err
+        ),
+        Ok(_) => panic!("expected not leader"),
+    }
+}
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_read_index_many() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    for group_id in 1..=2 {
+        let plan = MakeGroupPlan {
+            group_id,
+            first_node_id: 1,
+            replica_nums: 3,
+        };
+        let _ = cluster.make_group(&plan).await.unwrap();
+        cluster.campaign_group(1, group_id).await;
+        let _ = cluster.wait_leader_elect_event(1).await.unwrap();
+    }
+
+    // the group 3 does not exist.
+    let reads = (1..=3)
+        .map(|group_id| (group_id, Some(vec![group_id as u8])))
+        .collect();
+    let results = cluster.nodes[0]
+        .read_index_many(reads)
+        .unwrap()
+        .collect::<HashMap<_, _>>()
+        .await;
+    assert_eq!(results.len(), 3);
+    for group_id in 1..=2 {
+        assert_eq!(
+            results[&group_id].as_ref().unwrap(),
+            &Some(vec![group_id as u8])
+        );
+    }
+    assert!(results[&3].is_err());
+}
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_read_index_batching() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .read_batching(true, 3)
+        .build()
+        .await;
+
+    let group_id = 1;
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    let _ = cluster.wait_leader_elect_event(1).await.unwrap();
+
+    // the reads wait for the round of the next tick, the read beyond the
+    // max queue length is rejected.
+    let mut rxs = vec![];
+    for i in 0..4u8 {
+        rxs.push(
+            cluster.nodes[0]
+                .read_index_non_block(group_id, Some(vec![i]))
+                .unwrap(),
+        );
+    }
+    match rxs.pop().unwrap().await.unwrap() {
+        Err(Error::Propose(ProposeError::ReadQueueFull {
+            group_id: 1,
+            len: 3,
+            ..
+        })) => {}
+        res => panic!("expected ReadQueueFull, got {:?}", res),
+    }
+
+    cluster.tickers[0].non_blocking_tick();
+    for (i, rx) in rxs.into_iter().enumerate() {
+        assert_eq!(rx.await.unwrap().unwrap(), Some(vec![i as u8]));
+    }
+}
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_read_barrier() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    for group_id in 1..=2 {
+        let plan = MakeGroupPlan {
+            group_id,
+            first_node_id: 1,
+            replica_nums: 3,
+        };
+        let _ = cluster.make_group(&plan).await.unwrap();
+        cluster.campaign_group(1, group_id).await;
+        for node_id in 1..=nodes as u64 {
+            let _ = cluster.wait_leader_elect_event(node_id).await.unwrap();
+        }
+    }
+
+    // the barrier on the follower waits for the applies of the groups.
+    let barrier = cluster.nodes[1].read_barrier(&[2, 1, 2]).await.unwrap();
+    assert_eq!(barrier.node_id, 2);
+    assert_eq!(
+        barrier
+            .indexes
+            .iter()
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    for group_id in 1..=2 {
+        let leader_commit = cluster.nodes[0]
+            .group_state(group_id)
+            .unwrap()
+            .get_commit_index();
+        assert!(barrier.index(group_id).unwrap() >= leader_commit);
+    }
+
+    // the barrier fails if any group fails.
+    assert!(cluster.nodes[0].read_barrier(&[1, 3]).await.is_err());
+}
@@ -1,7 +1,10 @@
 use std::time::Duration;
 
 use oceanraft::prelude::StoreData;
+use oceanraft::storage;
 use oceanraft::storage::MultiRaftStorage;
+use oceanraft::Error;
+use tokio::time::timeout;
 
 use crate::fixtures::init_default_ut_tracing;
 use crate::fixtures::quickstart_memstorage_group;
@@ -167,3 +170,52 @@ async fn test_multi_storeage_unavailable() {
         assert_eq!(rx.unwrap().await.unwrap().is_ok(), true);
     }
 }
+
+/// The in-flight proposals fail with the storage error once the leader
+/// fails to write the ready of them.
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_log_storage_error_fails_proposals() {
+    let command_nums = 5;
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = quickstart_memstorage_group(&mut env, nodes).await;
+
+    let group_id = 1;
+    env.storages[0]
+        .group_storage(group_id, 1)
+        .await
+        .unwrap()
+        .wl()
+        .trigger_log_permanently_unavailable(true);
+
+    let mut recvs = vec![];
+    for _ in 0..command_nums {
+        let data = StoreData {
+            key: rand_string(4),
+            value: rand_string(8).as_bytes().to_vec(),
+        };
+        recvs.push(cluster.write_command(1, group_id, data).unwrap());
+        cluster.tickers[0].non_blocking_tick();
+    }
+
+    // the group is halted after the first failed ready, the proposals
+    // arrived after that fail by the halted group.
+    for (i, rx) in recvs.into_iter().enumerate() {
+        let res = timeout(Duration::from_millis(1000), rx)
+            .await
+            .unwrap()
+            .unwrap();
+        match res {
+            Err(err) if i == 0 => match err.without_request_id() {
+                Error::Storage(err) => assert_eq!(*err, storage::Error::LogUnavailable),
+                err => panic!("expected the storage error, got {:?}", err),
+            },
+            Err(_) => {}
+            Ok(_) => panic!("expected the proposal failed by the storage error"),
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use oceanraft::prelude::StoreData;
+use oceanraft::testkit::workload::Workload;
+use oceanraft::testkit::workload::WorkloadConfig;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use tracing::info;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+use crate::fixtures::MemType;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_random_workload() {
+    // five nodes, the group placed on first three nodes so that
+    // membership changes can add replicas to the rest nodes.
+    let nodes = 5;
+    let seed = rand::thread_rng().gen();
+    info!("random workload seed = {}", seed);
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::<MemType>::new(nodes)
+        .election_ticks(2)
+        .id_seed(seed)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(std::mem::take(&mut env.rxs))
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id: 1,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, plan.group_id).await;
+
+    let cfg = WorkloadConfig {
+        seed,
+        ops: 200,
+        groups: vec![plan.group_id],
+        ..Default::default()
+    };
+    let mut workload = Workload::<MemType, _>::new(cfg, |rng| StoreData {
+        key: (0..4)
+            .map(|_| char::from(rng.sample(Alphanumeric)))
+            .collect(),
+        value: (0..8).map(|_| rng.sample(Alphanumeric)).collect(),
+    });
+
+    // the workload panics if the state machines of the replicas do not
+    // converge after the faults are healed.
+    let stats = workload.run(cluster.workload_cluster()).await;
+    info!("random workload stats = {:?}", stats);
+    assert_eq!(stats.writes + stats.reads > 0, true);
+    assert!(stats.write_errors < stats.writes);
+}
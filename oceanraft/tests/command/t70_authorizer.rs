@@ -0,0 +1,120 @@
+use std::mem::take;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+
+use oceanraft::prelude::RemoveGroupRequest;
+use oceanraft::prelude::StoreData;
+use oceanraft::Authorizer;
+use oceanraft::Error;
+use oceanraft::Operation;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+use crate::fixtures::MemType;
+
+/// Denies the group 2, and the writes to group 1 if `deny_writes` is set.
+#[derive(Debug, Default)]
+struct TenantAuthorizer {
+    deny_writes: AtomicBool,
+}
+
+impl Authorizer for TenantAuthorizer {
+    fn authorize(&self, _: u64, group_id: u64, op: Operation) -> Result<(), String> {
+        if group_id == 2 {
+            return Err("group 2 belongs to other tenant".to_owned());
+        }
+        if op == Operation::Propose && self.deny_writes.load(Ordering::SeqCst) {
+            return Err("writes are suspended".to_owned());
+        }
+        Ok(())
+    }
+}
+
+fn assert_denied(res: Result<(), Error>, group_id: u64, operation: Operation) {
+    match res.as_ref().map_err(|err| err.without_request_id()) {
+        Err(Error::PermissionDenied {
+            group_id: denied_group_id,
+            operation: denied_operation,
+            ..
+        }) if *denied_group_id == group_id && *denied_operation == operation => {}
+        _ => panic!(
+            "expected {:?} on group {} denied, got {:?}",
+            operation, group_id, res
+        ),
+    }
+}
+
+fn new_data() -> StoreData {
+    StoreData {
+        key: "key".to_string(),
+        value: "data".as_bytes().to_vec(),
+    }
+}
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_authorizer() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let authorizer = Arc::new(TenantAuthorizer::default());
+    let mut cluster = ClusterBuilder::<MemType>::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .storages(env.storages.clone())
+        .authorizer(authorizer.clone())
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id: 1,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+
+    let plan = MakeGroupPlan {
+        group_id: 2,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let res = cluster.make_group(&plan).await.map(|_| ());
+    assert_denied(res, 2, Operation::CreateGroup);
+
+    let res = cluster.write_command(1, 2, new_data()).map(|_| ());
+    assert_denied(res, 2, Operation::Propose);
+
+    let res = cluster.nodes[0]
+        .remove_group(RemoveGroupRequest {
+            group_id: 2,
+            replica_id: 1,
+            ..Default::default()
+        })
+        .await
+        .map(|_| ());
+    assert_denied(res, 2, Operation::RemoveGroup);
+
+    // the decision of group 1 is cached by the first write until invalidated,
+    // the writes fail without leader but are not denied.
+    let _ = cluster.write_command(1, 1, new_data());
+    authorizer.deny_writes.store(true, Ordering::SeqCst);
+    let res = cluster.write_command(1, 1, new_data()).map(|_| ());
+    assert!(
+        !matches!(
+            res.as_ref().map_err(|err| err.without_request_id()),
+            Err(Error::PermissionDenied { .. })
+        ),
+        "expected write allowed, got {:?}",
+        res
+    );
+
+    cluster.nodes[0].invalidate_authorization(1);
+    let res = cluster.write_command(1, 1, new_data()).map(|_| ());
+    assert_denied(res, 1, Operation::Propose);
+}
@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+use oceanraft::AdminPayload;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::quickstart_rockstore_group;
+use crate::fixtures::RockStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_propose_admin() {
+    let nodes = 3;
+    let mut rockstore_env = RockStoreEnv::new(nodes);
+    let mut cluster = quickstart_rockstore_group(&mut rockstore_env, nodes).await;
+
+    let group_id = 1;
+    let payload = AdminPayload::new("reconfigure compaction", b"count=1000".to_vec());
+    let leader = cluster.nodes[0].clone();
+    let admin = payload.clone();
+    let propose = tokio::spawn(async move { leader.propose_admin(group_id, admin).await });
+    cluster.tickers[0].non_blocking_tick();
+
+    // the admin entry is applied by each replica in the order of the log.
+    for node_id in 1..=nodes as u64 {
+        let applied = cluster
+            .wait_admin_apply(node_id, Duration::from_millis(1000))
+            .await
+            .unwrap();
+        assert_eq!(applied.group_id, group_id);
+        assert_eq!(applied.payload, payload);
+    }
+    propose.await.unwrap().unwrap();
+
+    rockstore_env.destory()
+}
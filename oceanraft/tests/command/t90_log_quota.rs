@@ -0,0 +1,107 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::prelude::StoreData;
+use oceanraft::storage::MultiRaftStorage;
+use oceanraft::storage::Storage;
+use oceanraft::Event;
+use oceanraft::LogQuotaStage;
+use tokio::time::timeout;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::rand_string;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_log_quota_compact() {
+    let nodes = 3;
+    let group_id = 1;
+    let quota = 1024;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .log_quota(quota, 2)
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: nodes,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    let election = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(election.leader_id, 1);
+
+    // the log grows beyond the quota in bytes.
+    let writes = 10;
+    let mut recvs = vec![];
+    for _ in 0..writes {
+        let data = StoreData {
+            key: rand_string(4),
+            value: rand_string(256).as_bytes().to_vec(),
+        };
+        recvs.push(cluster.write_command(1, group_id, data).unwrap());
+    }
+    let applys = cluster
+        .wait_for_commands_apply(1, writes, Duration::from_millis(1000))
+        .await
+        .unwrap();
+    for apply in applys {
+        apply.tx.map(|tx| tx.send(Ok(((), None))));
+    }
+    for rx in recvs {
+        assert!(rx.await.unwrap().is_ok());
+    }
+
+    // the leader escalates to build the snapshot, then compacts the log by
+    // the storage, the log is back under the quota after the compaction.
+    let events = cluster.nodes[0].subscribe();
+    let mut stages = vec![];
+    for _ in 0..20 {
+        cluster.tick_node(1, Some(Duration::from_millis(10))).await;
+        while let Ok(event) = timeout(Duration::from_millis(10), events.recv()).await {
+            if let Event::LogQuota {
+                group_id: id,
+                bytes,
+                stage,
+                ..
+            } = *event.unwrap()
+            {
+                assert_eq!(id, group_id);
+                if stage != LogQuotaStage::Normal {
+                    assert!(bytes > quota);
+                }
+                stages.push(stage);
+            }
+        }
+        if stages.last() == Some(&LogQuotaStage::Normal) {
+            break;
+        }
+    }
+    assert_eq!(
+        stages,
+        vec![
+            LogQuotaStage::BuildSnapshot,
+            LogQuotaStage::Compact,
+            LogQuotaStage::Normal
+        ]
+    );
+
+    let gs = env.storages[0].group_storage(group_id, 1).await.unwrap();
+    assert!(gs.first_index().unwrap() > writes as u64);
+    assert!(!cluster.nodes[0]
+        .group_state(group_id)
+        .unwrap()
+        .is_log_quota_exceeded());
+}
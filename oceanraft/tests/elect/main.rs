@@ -4,4 +4,23 @@
 #[path = "../fixtures/mod.rs"]
 mod fixtures;
 
-mod t10_multiraft_elect;
\ No newline at end of file
+mod t10_multiraft_elect;
+mod t20_lazy_group;
+mod t30_list_groups;
+mod t40_polled_node;
+mod t50_create_groups;
+mod t60_node_status;
+mod t70_storage_domain;
+mod t80_witness;
+mod t90_log_bounds;
+mod t100_auto_create_groups;
+mod t110_group_lifecycle;
+mod t120_graceful_remove;
+mod t130_replica_desc_gc;
+mod t140_dedicated_runtime;
+mod t150_fork_group;
+mod t160_transfer_leader;
+mod t170_storage_check;
+mod t180_group_park;
+mod t190_unknown_group_msgs;
+mod t200_idempotency_keys;
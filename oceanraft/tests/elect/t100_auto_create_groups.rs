@@ -0,0 +1,75 @@
+use std::mem::take;
+use std::sync::Arc;
+use std::time::Duration;
+
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::prelude::StoreData;
+use oceanraft::GroupFactory;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::rand_string;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MemStoreEnv;
+
+/// Creates the single replica groups with odd group id.
+#[derive(Debug)]
+struct OddGroupFactory;
+
+impl GroupFactory for OddGroupFactory {
+    fn create_group(&self, node_id: u64, group_id: u64) -> Option<CreateGroupRequest> {
+        if group_id % 2 == 0 {
+            return None;
+        }
+
+        Some(CreateGroupRequest {
+            group_id,
+            replica_id: 1,
+            replicas: vec![ReplicaDesc {
+                node_id,
+                group_id,
+                replica_id: 1,
+                witness: false,
+            }],
+            ..Default::default()
+        })
+    }
+}
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_auto_create_groups() {
+    let nodes = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .group_factory(Arc::new(OddGroupFactory))
+        .build()
+        .await;
+
+    let data = StoreData {
+        key: rand_string(4),
+        value: rand_string(8).as_bytes().to_vec(),
+    };
+    let rx = cluster.write_command(1, 1, data.clone()).unwrap();
+    let applys = cluster
+        .wait_for_commands_apply(1, 1, Duration::from_millis(1000))
+        .await
+        .unwrap();
+    for apply in applys {
+        apply.tx.map(|tx| tx.send(Ok(((), None))));
+    }
+    assert!(rx.await.unwrap().is_ok());
+    assert!(cluster.nodes[0].group_state(1).is_some());
+
+    // the group is not created if the factory refuses.
+    let res = cluster.write_command(1, 2, data).unwrap().await.unwrap();
+    assert!(res.is_err());
+    assert!(cluster.nodes[0].group_state(2).is_none());
+}
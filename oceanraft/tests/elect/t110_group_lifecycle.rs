@@ -0,0 +1,93 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::RemoveGroupRequest;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::prelude::Snapshot;
+use oceanraft::storage::MultiRaftStorage;
+use oceanraft::storage::StorageExt;
+use tokio::time::sleep;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::LifecycleEvent;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_group_lifecycle_hooks() {
+    let nodes = 3;
+    let group_id = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    for i in 0..nodes {
+        let node_id = (i + 1) as u64;
+        let gs = env.storages[i]
+            .group_storage(group_id, node_id)
+            .await
+            .unwrap();
+        let mut ss = Snapshot::default();
+        ss.mut_metadata().mut_conf_state().voters = (1..=nodes as u64).collect();
+        ss.mut_metadata().index = 1;
+        ss.mut_metadata().term = 1;
+        gs.install_snapshot(ss).await.unwrap();
+
+        cluster.nodes[i]
+            .create_group(CreateGroupRequest {
+                group_id,
+                replica_id: node_id,
+                replicas: (1..=nodes as u64)
+                    .map(|replica_id| ReplicaDesc {
+                        node_id: replica_id,
+                        group_id,
+                        replica_id,
+                        witness: false,
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    cluster.nodes[2]
+        .remove_group(RemoveGroupRequest {
+            group_id,
+            replica_id: 3,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    // the hooks are called by the apply worker asynchronously.
+    for i in 0..nodes {
+        let node_id = (i + 1) as u64;
+        let mut expected = vec![LifecycleEvent::GroupCreated(
+            group_id,
+            node_id,
+            (1..=nodes as u64).collect(),
+        )];
+        if node_id == 3 {
+            expected.push(LifecycleEvent::GroupRemoved(group_id, node_id));
+        }
+
+        for _ in 0..100 {
+            if env.state_machines[i].lifecycle_events().len() >= expected.len() {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(env.state_machines[i].lifecycle_events(), expected);
+    }
+}
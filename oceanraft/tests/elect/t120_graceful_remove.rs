@@ -0,0 +1,107 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::RemoveGroupRequest;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::prelude::Snapshot;
+use oceanraft::storage::MultiRaftStorage;
+use oceanraft::storage::StorageExt;
+use tokio::time::sleep;
+use tokio::time::timeout;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_remove_leader_transfers_leadership() {
+    let nodes = 3;
+    let group_id = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    for i in 0..nodes {
+        let node_id = (i + 1) as u64;
+        let gs = env.storages[i]
+            .group_storage(group_id, node_id)
+            .await
+            .unwrap();
+        let mut ss = Snapshot::default();
+        ss.mut_metadata().mut_conf_state().voters = (1..=nodes as u64).collect();
+        ss.mut_metadata().index = 1;
+        ss.mut_metadata().term = 1;
+        gs.install_snapshot(ss).await.unwrap();
+
+        cluster.nodes[i]
+            .create_group(CreateGroupRequest {
+                group_id,
+                replica_id: node_id,
+                replicas: (1..=nodes as u64)
+                    .map(|replica_id| ReplicaDesc {
+                        node_id: replica_id,
+                        group_id,
+                        replica_id,
+                        witness: false,
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    cluster.campaign_group(1, group_id).await;
+    let election = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(election.leader_id, 1);
+
+    // the leader is removed after the new leader is established.
+    let removal = timeout(
+        Duration::from_secs(1),
+        cluster.nodes[0].remove_group(RemoveGroupRequest {
+            group_id,
+            replica_id: 1,
+            ..Default::default()
+        }),
+    )
+    .await
+    .unwrap()
+    .unwrap();
+    assert_eq!(removal.replica_id, 1);
+    assert!(removal.transferred);
+    let transferee = removal.transferee.unwrap();
+    assert_ne!(transferee, 1);
+
+    let state = cluster.nodes[transferee as usize - 1]
+        .group_state(group_id)
+        .unwrap();
+    for _ in 0..100 {
+        if state.is_leader() {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    assert!(state.is_leader());
+
+    // the follower is removed immediately.
+    let follower = if transferee == 2 { 3 } else { 2 };
+    let removal = cluster.nodes[follower as usize - 1]
+        .remove_group(RemoveGroupRequest {
+            group_id,
+            replica_id: follower,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert_eq!(removal.transferee, None);
+}
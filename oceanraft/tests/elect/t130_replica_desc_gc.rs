@@ -0,0 +1,74 @@
+use std::mem::take;
+
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::storage::MultiRaftStorage;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_gc_removed_replica_descs() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .replica_desc_gc_grace_ticks(0)
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id: 1,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+
+    // the replica 4 was removed from the group, but its descriptor is left.
+    let removed = ReplicaDesc {
+        group_id: 1,
+        node_id: 3,
+        replica_id: 4,
+        witness: false,
+    };
+    env.storages[0]
+        .set_replica_desc(1, removed.clone())
+        .await
+        .unwrap();
+
+    let report = cluster.nodes[0].gc_replica_descs(true).await.unwrap();
+    assert!(report.dry_run);
+    assert_eq!(report.removed, vec![removed.clone()]);
+    assert!(env.storages[0]
+        .get_replica_desc(1, 4)
+        .await
+        .unwrap()
+        .is_some());
+
+    let report = cluster.nodes[0].gc_replica_descs(false).await.unwrap();
+    assert!(!report.dry_run);
+    assert_eq!(report.removed, vec![removed]);
+    assert!(env.storages[0]
+        .get_replica_desc(1, 4)
+        .await
+        .unwrap()
+        .is_none());
+    for replica_id in 1..=3 {
+        assert!(env.storages[0]
+            .get_replica_desc(1, replica_id)
+            .await
+            .unwrap()
+            .is_some());
+    }
+
+    let report = cluster.nodes[0].gc_replica_descs(false).await.unwrap();
+    assert!(report.removed.is_empty());
+}
@@ -0,0 +1,48 @@
+use std::mem::take;
+
+use tokio::runtime::Builder;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_elect_on_dedicated_runtime() {
+    let runtime = Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("oceanraft-consensus")
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .runtime(runtime.handle().clone())
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id: 1,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, plan.group_id).await;
+    let election = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(election.group_id, plan.group_id);
+    assert_eq!(election.leader_id, 1);
+
+    cluster.stop().await;
+    // the runtime can't be dropped in the async context.
+    runtime.shutdown_background();
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::mem::take;
+
+use oceanraft::Error;
+use oceanraft::OperationKind;
+use oceanraft::OperationState;
+use oceanraft::RaftGroupError;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_fork_group() {
+    let nodes = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id: 1,
+        first_node_id: 1,
+        replica_nums: 1,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, 1).await;
+    let _ = cluster.wait_leader_elect_event(1).await.unwrap();
+
+    // the forked group is seeded from the snapshot of the source group.
+    let index = cluster.nodes[0]
+        .fork_group(1, 2, HashMap::from([(1, 1)]))
+        .await
+        .unwrap();
+    assert!(index >= 1);
+    cluster.campaign_group(1, 2).await;
+    let elected = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(elected.group_id, 2);
+    let bounds = cluster.nodes[0].log_bounds(2).await.unwrap();
+    assert_eq!(bounds.snapshot_index, index);
+
+    // the node must be a member and the new group must not exist.
+    match cluster.nodes[0]
+        .fork_group(1, 3, HashMap::from([(2, 1)]))
+        .await
+    {
+        Err(Error::BadParameter(_)) => {}
+        res => panic!("expected bad parameter error, got {:?}", res),
+    }
+    match cluster.nodes[0]
+        .fork_group(1, 2, HashMap::from([(1, 1)]))
+        .await
+    {
+        Err(Error::RaftGroup(RaftGroupError::Exists(1, 2))) => {}
+        res => panic!("expected group exists error, got {:?}", res),
+    }
+
+    // the forks are tracked as the operations in the order of start.
+    let operations = cluster.nodes[0].operations();
+    assert_eq!(operations.len(), 3);
+    assert!(operations
+        .iter()
+        .all(|op| op.kind == OperationKind::ForkGroup && op.is_finished()));
+    assert_eq!(operations[0].state, OperationState::Succeeded);
+    assert_eq!(operations[0].group_id, 2);
+    assert!(matches!(operations[2].state, OperationState::Failed(_)));
+    assert_eq!(
+        cluster.nodes[0].operation_progress(operations[1].op_id),
+        Some(operations[1].clone())
+    );
+}
@@ -0,0 +1,93 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::prelude::Snapshot;
+use oceanraft::storage::MultiRaftStorage;
+use oceanraft::storage::StorageExt;
+use oceanraft::Error;
+use oceanraft::ProposeError;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_transfer_leader() {
+    let nodes = 3;
+    let group_id = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    for i in 0..nodes {
+        let node_id = (i + 1) as u64;
+        let gs = env.storages[i]
+            .group_storage(group_id, node_id)
+            .await
+            .unwrap();
+        let mut ss = Snapshot::default();
+        ss.mut_metadata().mut_conf_state().voters = (1..=nodes as u64).collect();
+        ss.mut_metadata().index = 1;
+        ss.mut_metadata().term = 1;
+        gs.install_snapshot(ss).await.unwrap();
+
+        cluster.nodes[i]
+            .create_group(CreateGroupRequest {
+                group_id,
+                replica_id: node_id,
+                replicas: (1..=nodes as u64)
+                    .map(|replica_id| ReplicaDesc {
+                        node_id: replica_id,
+                        group_id,
+                        replica_id,
+                        witness: false,
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    cluster.campaign_group(1, group_id).await;
+    let election = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(election.leader_id, 1);
+
+    let leader = cluster.nodes[0]
+        .transfer_leader(group_id, 2, Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(leader.node_id, 2);
+    assert_eq!(leader.replica_id, 2);
+    assert!(cluster.nodes[1].group_state(group_id).unwrap().is_leader());
+
+    // the transfer must be started on the node of the leader.
+    match cluster.nodes[0]
+        .transfer_leader(group_id, 3, Duration::from_secs(1))
+        .await
+    {
+        Err(Error::Propose(ProposeError::NotLeader { .. }))
+        | Err(Error::Propose(ProposeError::LeaderUnknown { .. })) => {}
+        res => panic!("expected not leader error, got {:?}", res),
+    }
+
+    // the transferee must be a voter.
+    match cluster.nodes[1]
+        .transfer_leader(group_id, 4, Duration::from_secs(1))
+        .await
+    {
+        Err(Error::BadParameter(_)) => {}
+        res => panic!("expected bad parameter error, got {:?}", res),
+    }
+}
@@ -0,0 +1,82 @@
+use std::mem::take;
+
+use oceanraft::prelude::ConfState;
+use oceanraft::prelude::GroupMetadata;
+use oceanraft::storage::MultiRaftStorage;
+use oceanraft::storage::StorageExt;
+use oceanraft::Error;
+use oceanraft::StorageRepair;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_storage_check() {
+    let mut env = MemStoreEnv::new(1);
+    let storage = env.storages[0].clone();
+
+    // the metadata of group 9 references the storage which does not exist.
+    storage
+        .set_group_metadata(GroupMetadata {
+            group_id: 9,
+            replica_id: 1,
+            node_id: 1,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    // the replica of group 7 is not a member of the conf state in storage.
+    let gs = storage.group_storage(7, 1).await.unwrap();
+    gs.set_confstate(ConfState {
+        voters: vec![2, 3],
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let cluster = ClusterBuilder::new(1)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+    let node = &cluster.nodes[0];
+
+    let report = node.check_storage().await.unwrap();
+    assert!(report.orphans.is_empty());
+    assert_eq!(report.missing, vec![(9, 1)]);
+    assert_eq!(report.mismatched.len(), 1);
+    assert_eq!(report.mismatched[0].group_id, 7);
+    assert_eq!(report.mismatched[0].members, vec![2, 3]);
+    // the group of the missing storage is not restored.
+    assert!(node.group_state(9).is_none());
+    assert!(node.group_state(7).is_some());
+
+    // the started group can't be repaired.
+    match node
+        .repair_storage(StorageRepair::DescribeReplica {
+            group_id: 7,
+            replica_id: 1,
+        })
+        .await
+    {
+        Err(Error::BadParameter(_)) => {}
+        res => panic!("expected bad parameter error, got {:?}", res),
+    }
+
+    node.repair_storage(StorageRepair::ForgetGroup {
+        group_id: 9,
+        replica_id: 1,
+    })
+    .await
+    .unwrap();
+    let report = node.check_storage().await.unwrap();
+    assert!(report.missing.is_empty());
+    let meta = storage.get_group_metadata(9, 1).await.unwrap().unwrap();
+    assert!(meta.deleted);
+}
@@ -0,0 +1,100 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::prelude::StoreData;
+use oceanraft::Event;
+use tokio::time::timeout;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::rand_string;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_park_idle_groups() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    // the election timeout is longer than the ticks of the test, so the idle
+    // followers never campaign.
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(10)
+        .park_groups(1, 2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    for group_id in 1..=2 {
+        let plan = MakeGroupPlan {
+            group_id,
+            first_node_id: 1,
+            replica_nums: 3,
+        };
+        let _ = cluster.make_group(&plan).await.unwrap();
+        cluster.campaign_group(1, group_id).await;
+        let election = cluster.wait_leader_elect_event(1).await.unwrap();
+        assert_eq!(election.group_id, group_id);
+    }
+
+    let events = cluster.nodes[1].subscribe();
+    cluster.tick_node(2, Some(Duration::from_millis(10))).await;
+
+    // the group 2 is active recently on node 2.
+    let data = StoreData {
+        key: rand_string(4),
+        value: rand_string(8).as_bytes().to_vec(),
+    };
+    let rx = cluster.write_command(1, 2, data).unwrap();
+    for apply in cluster
+        .wait_for_commands_apply(1, 1, Duration::from_millis(1000))
+        .await
+        .unwrap()
+    {
+        apply.tx.map(|tx| tx.send(Ok(((), None))));
+    }
+    assert!(rx.await.unwrap().is_ok());
+    cluster
+        .wait_for_commands_apply(2, 1, Duration::from_millis(1000))
+        .await
+        .unwrap();
+
+    // the least recently active group is parked.
+    cluster.tick_node(2, Some(Duration::from_millis(10))).await;
+    let parked = timeout(Duration::from_millis(100), async {
+        loop {
+            match *events.recv().await.unwrap() {
+                Event::GroupPark { group_id, .. } => return group_id,
+                _ => {}
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(parked, 1);
+    assert!(cluster.nodes[1].group_state(1).unwrap().is_parked());
+    assert!(!cluster.nodes[1].group_state(2).unwrap().is_parked());
+
+    // the idle leaders are never parked.
+    let events = cluster.nodes[0].subscribe();
+    for _ in 0..3 {
+        cluster.tick_node(1, Some(Duration::from_millis(10))).await;
+    }
+    for group_id in 1..=2 {
+        let state = cluster.nodes[0].group_state(group_id).unwrap();
+        assert!(state.is_leader());
+        assert!(!state.is_parked());
+    }
+    while let Ok(Ok(event)) = timeout(Duration::from_millis(10), events.recv()).await {
+        assert!(
+            !matches!(*event, Event::GroupPark { .. }),
+            "unexpected {:?}",
+            event
+        );
+    }
+}
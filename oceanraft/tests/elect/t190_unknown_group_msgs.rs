@@ -0,0 +1,94 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::prelude::Snapshot;
+use oceanraft::prelude::StoreData;
+use oceanraft::storage::MultiRaftStorage;
+use oceanraft::storage::StorageExt;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::rand_string;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_buffer_unknown_group_msgs() {
+    let nodes = 3;
+    let group_id = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .buffer_unknown_group_msgs()
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let create = |node_id: u64| CreateGroupRequest {
+        group_id,
+        replica_id: node_id,
+        replicas: (1..=nodes as u64)
+            .map(|replica_id| ReplicaDesc {
+                node_id: replica_id,
+                group_id,
+                replica_id,
+                witness: false,
+            })
+            .collect(),
+        ..Default::default()
+    };
+    for i in 0..nodes {
+        let node_id = (i + 1) as u64;
+        let gs = env.storages[i]
+            .group_storage(group_id, node_id)
+            .await
+            .unwrap();
+        let mut ss = Snapshot::default();
+        ss.mut_metadata().mut_conf_state().voters = (1..=nodes as u64).collect();
+        ss.mut_metadata().index = 1;
+        ss.mut_metadata().term = 1;
+        gs.install_snapshot(ss).await.unwrap();
+    }
+
+    // the group is not created on node 3 yet.
+    for node_id in 1..=2 {
+        cluster.nodes[node_id as usize - 1]
+            .create_group(create(node_id))
+            .await
+            .unwrap();
+    }
+    cluster.campaign_group(1, group_id).await;
+    let election = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(election.leader_id, 1);
+
+    let data = StoreData {
+        key: rand_string(4),
+        value: rand_string(8).as_bytes().to_vec(),
+    };
+    let rx = cluster.write_command(1, group_id, data).unwrap();
+    for apply in cluster
+        .wait_for_commands_apply(1, 1, Duration::from_millis(1000))
+        .await
+        .unwrap()
+    {
+        apply.tx.map(|tx| tx.send(Ok(((), None))));
+    }
+    assert!(rx.await.unwrap().is_ok());
+    assert!(cluster.nodes[2].group_state(group_id).is_none());
+
+    // the nodes are not ticked, so the leader sends nothing more to node 3
+    // and the replica catches up by the buffered messages.
+    cluster.nodes[2].create_group(create(3)).await.unwrap();
+    cluster
+        .wait_for_commands_apply(3, 1, Duration::from_millis(1000))
+        .await
+        .unwrap();
+    assert_eq!(cluster.nodes[2].dropped_unknown_group_messages(), 0);
+}
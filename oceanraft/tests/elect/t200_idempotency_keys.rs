@@ -0,0 +1,72 @@
+use std::mem::take;
+
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::RemoveGroupRequest;
+use oceanraft::Error;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_idempotency_keys_restart() {
+    let group_id = 1;
+    let mut env = MemStoreEnv::new(1);
+    let mut cluster = ClusterBuilder::new(1)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 1,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    let request = RemoveGroupRequest {
+        group_id,
+        replica_id: 1,
+        replicas: vec![],
+        idempotency_key: "remove-1".to_owned(),
+    };
+    let removal = cluster.nodes[0]
+        .remove_group(request.clone())
+        .await
+        .unwrap();
+    cluster.stop().await;
+
+    // the node restarts on the same storage, the retry gets the original
+    // outcome instead of failing for the removed group.
+    let mut restarted = MemStoreEnv::new(1);
+    let cluster = ClusterBuilder::new(1)
+        .election_ticks(2)
+        .state_machines(restarted.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut restarted.rxs))
+        .build()
+        .await;
+    assert_eq!(
+        cluster.nodes[0].remove_group(request).await.unwrap(),
+        removal
+    );
+
+    // the key reused by the other operation is still rejected.
+    let request = CreateGroupRequest {
+        group_id,
+        replica_id: 1,
+        idempotency_key: "remove-1".to_owned(),
+        ..Default::default()
+    };
+    match cluster.nodes[0].create_group(request).await {
+        Err(Error::BadParameter(_)) => {}
+        res => panic!("expected bad parameter error, got {:?}", res),
+    }
+}
@@ -0,0 +1,65 @@
+use std::mem::take;
+
+use futures::StreamExt;
+use oceanraft::Error;
+use oceanraft::GroupSummary;
+use raft::StateRole;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_list_groups() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    for group_id in 1..=5 {
+        let plan = MakeGroupPlan {
+            group_id,
+            first_node_id: 1,
+            replica_nums: 3,
+        };
+        let _ = cluster.make_group(&plan).await.unwrap();
+    }
+
+    cluster.campaign_group(1, 1).await;
+    let _ = cluster.wait_leader_elect_event(1).await.unwrap();
+
+    let node = &cluster.nodes[0];
+    let page = node.list_groups_paged(0, 2).await.unwrap();
+    assert_eq!(
+        page.groups.iter().map(|g| g.group_id).collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+    assert_eq!(page.next_cursor, Some(2));
+    assert_eq!(page.groups[0].role, StateRole::Leader);
+
+    let page = node.list_groups_paged(4, 2).await.unwrap();
+    assert_eq!(
+        page.groups.iter().map(|g| g.group_id).collect::<Vec<_>>(),
+        vec![5]
+    );
+    assert_eq!(page.next_cursor, None);
+
+    let groups: Vec<Result<GroupSummary, Error>> = node.list_groups_stream(2).collect().await;
+    assert_eq!(
+        groups
+            .into_iter()
+            .map(|g| g.unwrap().group_id)
+            .collect::<Vec<_>>(),
+        vec![1, 2, 3, 4, 5]
+    );
+}
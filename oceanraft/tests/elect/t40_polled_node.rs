@@ -0,0 +1,196 @@
+use std::time::Duration;
+use std::time::Instant;
+
+use oceanraft::prelude::ConfState;
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::storage::MultiRaftStorage;
+use oceanraft::storage::StorageExt;
+use oceanraft::transport::QueueTransport;
+use oceanraft::Config;
+use oceanraft::MultiRaft;
+use oceanraft::Work;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::MemStoreEnv;
+use crate::fixtures::MemType;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_polled_node_campaign() {
+    let env = MemStoreEnv::new(1);
+    let cfg = Config {
+        node_id: 1,
+        election_tick: 2,
+        heartbeat_tick: 1,
+        tick_interval: 10,
+        max_batch_apply_msgs: 1,
+        ..Default::default()
+    };
+    let (node, mut handle) = MultiRaft::<MemType, QueueTransport>::new_polled(
+        cfg,
+        env.storages[0].clone(),
+        env.state_machines[0].clone(),
+    )
+    .unwrap();
+
+    // the first poll restores the node, the membership of the group is
+    // initialized in storage after that, otherwise the group is restored.
+    let now = Instant::now();
+    drive_io(handle.poll(now)).await;
+    let gs = env.storages[0].group_storage(1, 1).await.unwrap();
+    gs.set_confstate(ConfState {
+        voters: vec![1],
+        ..Default::default()
+    })
+    .await
+    .unwrap();
+
+    let create = node.create_group(CreateGroupRequest {
+        group_id: 1,
+        replica_id: 1,
+        replicas: vec![ReplicaDesc {
+            group_id: 1,
+            node_id: 1,
+            replica_id: 1,
+            witness: false,
+        }],
+        applied_hint: 0,
+        priority: 0,
+        lazy: false,
+        storage_domain: String::new(),
+        idempotency_key: String::new(),
+        archive: false,
+        learners: vec![],
+    });
+    tokio::pin!(create);
+    assert!(futures::poll!(&mut create).is_pending());
+
+    // nothing is handled until the node is polled.
+    let works = handle.poll(now);
+    assert!(
+        matches!(works.last(), Some(Work::WakeAt(at)) if *at == now + Duration::from_millis(10))
+    );
+    drive_io(works).await;
+    create.await.unwrap();
+
+    let mut rx = node.campaign_group_non_block(1);
+    for _ in 0..10 {
+        let works = handle.poll(now);
+        // single replica group sends nothing to other nodes.
+        assert!(!works.iter().any(|work| matches!(work, Work::Send(_))));
+        drive_io(works).await;
+        if node.group_state(1).unwrap().is_leader() {
+            break;
+        }
+    }
+    assert!(node.group_state(1).unwrap().is_leader());
+    assert!(rx.try_recv().unwrap().is_ok());
+
+    node.stop().await;
+    assert!(handle.poll(now + Duration::from_secs(1)).is_empty());
+}
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_polled_nodes_step() {
+    let env = MemStoreEnv::new(2);
+    let replicas = (1..=2)
+        .map(|id| ReplicaDesc {
+            group_id: 1,
+            node_id: id,
+            replica_id: id,
+            witness: false,
+        })
+        .collect::<Vec<_>>();
+    let now = Instant::now();
+    let mut nodes = vec![];
+    for id in 1..=2 {
+        let cfg = Config {
+            node_id: id,
+            election_tick: 2,
+            heartbeat_tick: 1,
+            tick_interval: 10,
+            max_batch_apply_msgs: 1,
+            ..Default::default()
+        };
+        let i = (id - 1) as usize;
+        let (node, mut handle) = MultiRaft::<MemType, QueueTransport>::new_polled(
+            cfg,
+            env.storages[i].clone(),
+            env.state_machines[i].clone(),
+        )
+        .unwrap();
+        drive_io(handle.poll(now)).await;
+        let gs = env.storages[i].group_storage(1, id).await.unwrap();
+        gs.set_confstate(ConfState {
+            voters: vec![1, 2],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        let create = node.create_group(CreateGroupRequest {
+            group_id: 1,
+            replica_id: id,
+            replicas: replicas.clone(),
+            ..Default::default()
+        });
+        tokio::pin!(create);
+        assert!(futures::poll!(&mut create).is_pending());
+        drive_io(handle.poll(now)).await;
+        create.await.unwrap();
+        nodes.push((node, handle));
+    }
+
+    // the messages sent by one handle are stepped by the other, the
+    // responses are received after the next poll of the receiver.
+    let mut rx = nodes[0].0.campaign_group_non_block(1);
+    let mut responses = vec![];
+    let mut now = now;
+    for _ in 0..50 {
+        now += Duration::from_millis(10);
+        for i in 0..2 {
+            let mut ios = vec![];
+            for work in nodes[i].1.poll(now) {
+                match work {
+                    Work::Send(msg) => {
+                        let to = (msg.to_node - 1) as usize;
+                        responses.push(nodes[to].1.step(msg));
+                    }
+                    Work::Io(io) => ios.push(Work::Io(io)),
+                    _ => {}
+                }
+            }
+            drive_io(ios).await;
+        }
+        if nodes[0].0.group_state(1).unwrap().is_leader() {
+            break;
+        }
+    }
+    assert!(nodes[0].0.group_state(1).unwrap().is_leader());
+    assert!(rx.try_recv().unwrap().is_ok());
+    assert!(responses
+        .iter_mut()
+        .any(|rx| matches!(rx.try_recv(), Ok(Ok(_)))));
+
+    for (node, _) in nodes.iter() {
+        node.stop().await;
+    }
+}
+
+/// The storage IO not completed inline by the poll is driven by the runtime
+/// of the test.
+async fn drive_io(works: Vec<Work>) {
+    for work in works {
+        if let Work::Io(io) = work {
+            io.await;
+        }
+    }
+}
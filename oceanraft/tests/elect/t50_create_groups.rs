@@ -0,0 +1,153 @@
+use std::mem::take;
+
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::prelude::Snapshot;
+use oceanraft::storage::MultiRaftStorage;
+use oceanraft::storage::StorageExt;
+use oceanraft::Error;
+use oceanraft::FatalError;
+use oceanraft::RaftGroupError;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_create_groups() {
+    let nodes = 3;
+    let groups = 100;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    for i in 0..nodes {
+        let node_id = (i + 1) as u64;
+        let mut requests = vec![];
+        for group_id in 1..=groups {
+            let replicas = (1..=nodes as u64)
+                .map(|replica_id| ReplicaDesc {
+                    node_id: replica_id,
+                    group_id,
+                    replica_id,
+                    witness: false,
+                })
+                .collect::<Vec<_>>();
+
+            let gs = env.storages[i]
+                .group_storage(group_id, node_id)
+                .await
+                .unwrap();
+            let mut ss = Snapshot::default();
+            ss.mut_metadata().mut_conf_state().voters = (1..=nodes as u64).collect();
+            ss.mut_metadata().index = 1;
+            ss.mut_metadata().term = 1;
+            gs.install_snapshot(ss).await.unwrap();
+
+            requests.push(CreateGroupRequest {
+                group_id,
+                replica_id: node_id,
+                replicas,
+                applied_hint: 0,
+                priority: 0,
+                lazy: false,
+                storage_domain: String::new(),
+                idempotency_key: String::new(),
+                archive: false,
+                learners: vec![],
+            });
+        }
+
+        // the duplicated and invalid requests fail without affecting others.
+        requests.push(CreateGroupRequest {
+            group_id: 1,
+            replica_id: node_id,
+            ..Default::default()
+        });
+        requests.push(CreateGroupRequest {
+            group_id: 0,
+            replica_id: node_id,
+            ..Default::default()
+        });
+
+        let results = cluster.nodes[i].create_groups(requests).await.unwrap();
+        assert_eq!(results.len(), groups as usize + 2);
+        assert!(results[..groups as usize].iter().all(|res| res.is_ok()));
+        match &results[groups as usize] {
+            Err(Error::RaftGroup(RaftGroupError::Exists(_, 1))) => {}
+            res => panic!("expected group exists error, got {:?}", res),
+        }
+        match &results[groups as usize + 1] {
+            Err(Error::BadParameter(_)) => {}
+            res => panic!("expected bad parameter error, got {:?}", res),
+        }
+    }
+
+    cluster.campaign_group(1, groups).await;
+    let election = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(election.group_id, groups);
+    assert_eq!(election.leader_id, 1);
+}
+
+/// The applied hint of the request is used if it's ahead of the applied
+/// index of the storage, the hint beyond the committed logs is rejected.
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_create_groups_with_applied_hint() {
+    let nodes = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let mut requests = vec![];
+    for (group_id, applied_hint) in [(1, 5), (2, 10)] {
+        let gs = env.storages[0].group_storage(group_id, 1).await.unwrap();
+        let mut ss = Snapshot::default();
+        ss.mut_metadata().mut_conf_state().voters = vec![1];
+        ss.mut_metadata().index = 5;
+        ss.mut_metadata().term = 1;
+        gs.install_snapshot(ss).await.unwrap();
+
+        requests.push(CreateGroupRequest {
+            group_id,
+            replica_id: 1,
+            replicas: vec![ReplicaDesc {
+                node_id: 1,
+                group_id,
+                replica_id: 1,
+                witness: false,
+            }],
+            applied_hint,
+            ..Default::default()
+        });
+    }
+
+    let results = cluster.nodes[0].create_groups(requests).await.unwrap();
+    assert!(results[0].is_ok());
+    match &results[1] {
+        Err(Error::Fatal(FatalError::AppliedOutOfRange {
+            group_id: 2,
+            applied: 10,
+            committed: 5,
+            ..
+        })) => {}
+        res => panic!("expected applied out of range error, got {:?}", res),
+    }
+}
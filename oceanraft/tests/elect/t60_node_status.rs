@@ -0,0 +1,173 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::transport::Endpoint;
+use oceanraft::Error;
+use raft::StateRole;
+use tokio::time::sleep;
+use tokio::time::Instant;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_node_status() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    for group_id in 1..=3 {
+        let plan = MakeGroupPlan {
+            group_id,
+            first_node_id: 1,
+            replica_nums: 3,
+        };
+        let _ = cluster.make_group(&plan).await.unwrap();
+    }
+
+    cluster.campaign_group(1, 1).await;
+    let _ = cluster.wait_leader_elect_event(1).await.unwrap();
+
+    let status = cluster.nodes[0].node_status().await.unwrap();
+    assert_eq!(status.node_id, 1);
+    assert_eq!(status.leaders, 1);
+    assert_eq!(status.leaders + status.followers + status.candidates, 3);
+    assert_eq!(status.parked, 0);
+    assert!(status.storage_bytes.is_some());
+
+    // the votes of the election are received from the peers.
+    assert_eq!(
+        status
+            .peers
+            .iter()
+            .map(|peer| peer.node_id)
+            .collect::<Vec<_>>(),
+        vec![2, 3]
+    );
+    assert!(status.peers.iter().all(|peer| peer.groups == 3));
+    assert!(status.peers.iter().all(|peer| peer.last_contact.is_some()));
+}
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_group_status() {
+    let nodes = 3;
+    let group_id = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    let _ = cluster.wait_leader_elect_event(1).await.unwrap();
+
+    let status = cluster.nodes[0].group_status(group_id).await.unwrap();
+    assert_eq!(status.replica_id, 1);
+    assert_eq!(status.role, StateRole::Leader);
+    assert_eq!((status.leader_id, status.leader_node_id), (1, 1));
+    assert!(!status.parked);
+    assert!(status.commit_index <= status.last_index);
+    // the leader tracks the progress of the replicas.
+    assert_eq!(
+        status
+            .progress
+            .iter()
+            .map(|pr| pr.replica_id)
+            .collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert!(status.progress.iter().all(|pr| !pr.learner));
+
+    // the follower learns the leader by the heartbeats.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let status = loop {
+        let status = cluster.nodes[1].group_status(group_id).await.unwrap();
+        if status.leader_id == 1 || Instant::now() >= deadline {
+            break status;
+        }
+        sleep(Duration::from_millis(10)).await;
+    };
+    assert_eq!(status.role, StateRole::Follower);
+    assert_eq!(status.leader_id, 1);
+    assert!(status.progress.is_empty());
+
+    match cluster.nodes[0].group_status(100).await {
+        Err(Error::RaftGroup(_)) => {}
+        res => panic!("expected raft group error, got {:?}", res),
+    }
+}
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_node_health() {
+    let nodes = 3;
+    let group_id = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    // the messages to node 2 fail over to the second endpoint.
+    cluster.transport.set_endpoints(
+        2,
+        vec![Endpoint::new("n2-a", 0, 1), Endpoint::new("n2-b", 1, 1)],
+    );
+    cluster.transport.break_endpoint("n2-a");
+
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    let _ = cluster.wait_leader_elect_event(1).await.unwrap();
+
+    let health = cluster.nodes[0].node_health(2);
+    assert_eq!(
+        health
+            .iter()
+            .map(|endpoint| endpoint.addr.as_str())
+            .collect::<Vec<_>>(),
+        vec!["n2-a", "n2-b"]
+    );
+    assert!(health[0].consecutive_failures > 0);
+    assert!(health[0].last_failure.is_some());
+    assert!(health[1].healthy);
+    assert_eq!(health[1].consecutive_failures, 0);
+
+    // the node without endpoints is sent to directly.
+    assert!(cluster.nodes[0].node_health(3).is_empty());
+}
@@ -0,0 +1,173 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::prelude::Snapshot;
+use oceanraft::prelude::StoreData;
+use oceanraft::storage::MultiRaftStorage;
+use oceanraft::storage::StorageExt;
+use oceanraft::Error;
+use oceanraft::Event;
+use oceanraft::RaftGroupError;
+use tokio::time::timeout;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::rand_string;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_pause_storage_domain() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    // the group 1 is on disk1 and the group 2 is on disk2 of every node.
+    for i in 0..nodes {
+        let node_id = (i + 1) as u64;
+        for (group_id, domain) in [(1, "disk1"), (2, "disk2")] {
+            let gs = env.storages[i]
+                .group_storage(group_id, node_id)
+                .await
+                .unwrap();
+            let mut ss = Snapshot::default();
+            ss.mut_metadata().mut_conf_state().voters = (1..=nodes as u64).collect();
+            ss.mut_metadata().index = 1;
+            ss.mut_metadata().term = 1;
+            gs.install_snapshot(ss).await.unwrap();
+
+            cluster.nodes[i]
+                .create_group(CreateGroupRequest {
+                    group_id,
+                    replica_id: node_id,
+                    replicas: (1..=nodes as u64)
+                        .map(|replica_id| ReplicaDesc {
+                            node_id: replica_id,
+                            group_id,
+                            replica_id,
+                            witness: false,
+                        })
+                        .collect(),
+                    applied_hint: 0,
+                    priority: 0,
+                    lazy: false,
+                    storage_domain: domain.to_owned(),
+                    idempotency_key: String::new(),
+                    archive: false,
+                    learners: vec![],
+                })
+                .await
+                .unwrap();
+        }
+
+        // the domain is persisted to the metadata of the group.
+        let meta = env.storages[i]
+            .get_group_metadata(1, node_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(meta.storage_domain, "disk1");
+    }
+
+    for group_id in 1..=2 {
+        cluster.campaign_group(1, group_id).await;
+        let election = cluster.wait_leader_elect_event(1).await.unwrap();
+        assert_eq!(election.group_id, group_id);
+    }
+
+    let events = cluster.nodes[0].subscribe();
+    cluster.nodes[0]
+        .pause_storage_domain("disk1")
+        .await
+        .unwrap();
+    let paused = timeout(Duration::from_millis(100), async {
+        loop {
+            match &*events.recv().await.unwrap() {
+                Event::StorageDomainPaused { domain, groups } => {
+                    return (domain.clone(), groups.clone())
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(paused, ("disk1".to_owned(), vec![1]));
+
+    // the leader of group 1 is parked after the leadership transferred or timeout.
+    for _ in 0..2 {
+        cluster.tick_node(1, Some(Duration::from_millis(10))).await;
+    }
+
+    let data = StoreData {
+        key: rand_string(4),
+        value: rand_string(8).as_bytes().to_vec(),
+    };
+    let res = cluster
+        .write_command(1, 1, data.clone())
+        .unwrap()
+        .await
+        .unwrap();
+    match res.as_ref().map_err(|err| err.without_request_id()) {
+        Err(Error::RaftGroup(RaftGroupError::Paused(1, 1, domain))) => {
+            assert_eq!(domain, "disk1")
+        }
+        res => panic!("expected group paused error, got {:?}", res.map(|_| ())),
+    }
+
+    // the group on the healthy disk is still available.
+    let rx = cluster.write_command(1, 2, data).unwrap();
+    let applys = cluster
+        .wait_for_commands_apply(1, 1, Duration::from_millis(1000))
+        .await
+        .unwrap();
+    for apply in applys {
+        apply.tx.map(|tx| tx.send(Ok(((), None))));
+    }
+    assert!(rx.await.unwrap().is_ok());
+
+    // the group can't be created in the paused domain.
+    match cluster.nodes[0]
+        .create_group(CreateGroupRequest {
+            group_id: 3,
+            replica_id: 1,
+            storage_domain: "disk1".to_owned(),
+            ..Default::default()
+        })
+        .await
+    {
+        Err(Error::RaftGroup(RaftGroupError::Paused(1, 3, _))) => {}
+        res => panic!("expected group paused error, got {:?}", res),
+    }
+
+    cluster.nodes[0]
+        .resume_storage_domain("disk1")
+        .await
+        .unwrap();
+    let data = StoreData {
+        key: rand_string(4),
+        value: rand_string(8).as_bytes().to_vec(),
+    };
+    // the replica may be a follower after resumed, only the pause is checked.
+    let rx = cluster.write_command(1, 1, data).unwrap();
+    if let Ok(Ok(Err(err))) = timeout(Duration::from_millis(100), rx).await {
+        assert!(
+            !matches!(
+                err.without_request_id(),
+                Error::RaftGroup(RaftGroupError::Paused(..))
+            ),
+            "expected group resumed"
+        );
+    }
+}
@@ -0,0 +1,159 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::EntryType;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::prelude::Snapshot;
+use oceanraft::prelude::StoreData;
+use oceanraft::storage::MultiRaftStorage;
+use oceanraft::storage::MultiRaftWitnessStorage;
+use oceanraft::storage::Storage;
+use oceanraft::storage::StorageExt;
+use oceanraft::tick::ManualTick;
+use oceanraft::Error;
+use oceanraft::MultiRaft;
+use oceanraft::RaftGroupError;
+use tokio::time::sleep;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::rand_string;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MemStoreEnv;
+use crate::fixtures::WitnessMemType;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_witness_replica() {
+    let nodes = 3;
+    let group_id = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let _witness_rx = env.rxs.pop().unwrap();
+    let mut builder = ClusterBuilder::new(nodes - 1)
+        .election_ticks(2)
+        .state_machines(env.state_machines[..2].to_vec())
+        .storages(env.storages[..2].to_vec())
+        .apply_rxs(take(&mut env.rxs));
+    let witness_cfg = builder.node_config(3);
+    let mut cluster = builder.build().await;
+
+    // the node 3 hosts the witness of 2+1 deployment on the witness storage,
+    // it joins the cluster by the transport of the cluster.
+    let mut witness_ticker = ManualTick::new();
+    let witness_storage = MultiRaftWitnessStorage::new(env.storages[2].clone());
+    let witness_node = MultiRaft::<WitnessMemType, _>::new(
+        witness_cfg,
+        cluster.transport.clone(),
+        witness_storage.clone(),
+        env.state_machines[2].clone(),
+        Some(Box::new(witness_ticker.clone())),
+    )
+    .unwrap();
+    cluster
+        .transport
+        .listen(3, "test://node/3", witness_node.message_sender())
+        .await
+        .unwrap();
+
+    let replicas = (1..=nodes as u64)
+        .map(|replica_id| ReplicaDesc {
+            node_id: replica_id,
+            group_id,
+            replica_id,
+            witness: replica_id == 3,
+        })
+        .collect::<Vec<_>>();
+    let mut ss = Snapshot::default();
+    ss.mut_metadata().mut_conf_state().voters = (1..=nodes as u64).collect();
+    ss.mut_metadata().index = 1;
+    ss.mut_metadata().term = 1;
+    for i in 0..nodes - 1 {
+        let node_id = (i + 1) as u64;
+        let gs = env.storages[i]
+            .group_storage(group_id, node_id)
+            .await
+            .unwrap();
+        gs.install_snapshot(ss.clone()).await.unwrap();
+
+        cluster.nodes[i]
+            .create_group(CreateGroupRequest {
+                group_id,
+                replica_id: node_id,
+                replicas: replicas.clone(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+    witness_storage
+        .group_storage(group_id, 3)
+        .await
+        .unwrap()
+        .install_snapshot(ss)
+        .await
+        .unwrap();
+    witness_node
+        .create_group(CreateGroupRequest {
+            group_id,
+            replica_id: 3,
+            replicas: replicas.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    match witness_node.campaign_group(group_id).await {
+        Err(Error::RaftGroup(RaftGroupError::Witness(3, 1))) => {}
+        res => panic!("expected witness error, got {:?}", res),
+    }
+
+    // the witness never campaigns after the election timeout.
+    for _ in 0..4 {
+        witness_ticker.tick().await;
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    cluster.campaign_group(1, group_id).await;
+    let election = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(election.leader_id, 1);
+
+    let data = StoreData {
+        key: rand_string(4),
+        value: rand_string(8).as_bytes().to_vec(),
+    };
+    let rx = cluster.write_command(1, group_id, data).unwrap();
+    let applys = cluster
+        .wait_for_commands_apply(1, 1, Duration::from_millis(1000))
+        .await
+        .unwrap();
+    for apply in applys {
+        apply.tx.map(|tx| tx.send(Ok(((), None))));
+    }
+    assert!(rx.await.unwrap().is_ok());
+
+    // the witness storage drops the payload of the entries before written
+    // to the underlying storage.
+    let leader = env.storages[0].group_storage(group_id, 1).await.unwrap();
+    let witness = env.storages[2].group_storage(group_id, 3).await.unwrap();
+    let last_index = leader.last_index().unwrap();
+    for _ in 0..100 {
+        if witness.last_index().unwrap() >= last_index {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    let entries = witness
+        .entries(2, last_index + 1, None, Default::default())
+        .unwrap();
+    assert!(entries
+        .iter()
+        .filter(|ent| ent.entry_type() == EntryType::EntryNormal)
+        .all(|ent| ent.data.is_empty()));
+    let entries = leader
+        .entries(2, last_index + 1, None, Default::default())
+        .unwrap();
+    assert!(entries.iter().any(|ent| !ent.data.is_empty()));
+}
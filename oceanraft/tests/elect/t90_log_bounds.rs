@@ -0,0 +1,50 @@
+use std::mem::take;
+
+use oceanraft::Error;
+use oceanraft::RaftGroupError;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_log_bounds() {
+    let nodes = 3;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id: 1,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, 1).await;
+    let _ = cluster.wait_leader_elect_event(1).await.unwrap();
+
+    // the group is created from the snapshot at index 1 and term 1.
+    let bounds = cluster.nodes[0].log_bounds(1).await.unwrap();
+    assert_eq!(bounds.group_id, 1);
+    assert_eq!(bounds.replica_id, 1);
+    assert_eq!(bounds.first_index, 2);
+    assert_eq!(bounds.truncated_index, 1);
+    assert_eq!(bounds.truncated_term, Some(1));
+    assert_eq!(bounds.snapshot_index, 1);
+    assert!(bounds.last_index >= bounds.truncated_index);
+
+    match cluster.nodes[0].log_bounds(2).await {
+        Err(Error::RaftGroup(RaftGroupError::Deleted(1, 2))) => {}
+        res => panic!("expected group deleted error, got {:?}", res),
+    }
+}
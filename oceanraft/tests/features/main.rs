@@ -0,0 +1 @@
+mod t10_build_matrix;
@@ -0,0 +1,47 @@
+use std::path::Path;
+use std::process::Command;
+
+/// The feature sets the crate is built with, the first one is the minimal
+/// build of `default-features = false`.
+const FEATURE_MATRIX: &[&str] = &[
+    "",
+    "store-rocksdb",
+    "grpc",
+    "compression",
+    "metrics",
+    "log,bt",
+    "testkit",
+    "txn,prost-data",
+    "store-rocksdb,grpc,compression,metrics,log,testkit,txn",
+];
+
+/// Check the crate with each set of the features, it builds the crate once
+/// per set in the target dir of the test, so the following runs are
+/// incremental.
+#[test]
+fn test_build_matrix() {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_owned());
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml");
+    // the target dir of the test is locked by the running cargo.
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("build_matrix");
+
+    let mut failed = vec![];
+    for features in FEATURE_MATRIX {
+        let status = Command::new(&cargo)
+            .arg("check")
+            .arg("--lib")
+            .arg("--manifest-path")
+            .arg(&manifest)
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .arg("--no-default-features")
+            .arg("--features")
+            .arg(features)
+            .status()
+            .expect("run cargo check");
+        if !status.success() {
+            failed.push(*features);
+        }
+    }
+    assert!(failed.is_empty(), "build failed with features {:?}", failed);
+}
@@ -2,14 +2,33 @@ use std::collections::HashMap;
 use std::mem::take;
 use std::sync::Arc;
 
+use tokio::runtime::Handle;
 use tokio::sync::mpsc::Receiver;
 
 use oceanraft::tick::ManualTick;
 use oceanraft::transport::LocalTransport;
+use oceanraft::AcceptAllGate;
+use oceanraft::AllowAll;
 use oceanraft::Apply;
+use oceanraft::Authorizer;
+use oceanraft::BackgroundBudget;
+use oceanraft::BalancedPlacement;
 use oceanraft::Config;
+use oceanraft::EntryCompression;
+use oceanraft::EntryGate;
+use oceanraft::GroupFactory;
+use oceanraft::MonotonicClock;
 use oceanraft::MultiRaft;
 use oceanraft::MultiRaftTypeSpecialization;
+use oceanraft::NoCompaction;
+use oceanraft::NoGroupFactory;
+use oceanraft::NoLearnerPromotion;
+use oceanraft::NoLogCompactor;
+use oceanraft::NoSnapshotValidator;
+use oceanraft::NoTopologyProvider;
+use oceanraft::RandomIdGenerator;
+use oceanraft::SeededIdGenerator;
+use oceanraft::SnapshotValidator;
 
 use super::Cluster;
 
@@ -22,6 +41,25 @@ where
     storages: Vec<T::MS>,
     apply_rxs: Vec<Option<Receiver<Vec<Apply<T::D, T::R>>>>>,
     state_machines: Vec<Option<T::M>>,
+    id_seed: Option<u64>,
+    entry_gates: HashMap<u64, Arc<dyn EntryGate>>,
+    check_quorum: bool,
+    enable_lease_read: bool,
+    batch_read_index: bool,
+    max_read_queue_len: usize,
+    max_inflight_proposals: usize,
+    await_inflight_proposals: bool,
+    group_factory: Option<Arc<dyn GroupFactory>>,
+    snapshot_validators: HashMap<u64, Arc<dyn SnapshotValidator>>,
+    authorizer: Option<Arc<dyn Authorizer>>,
+    replica_desc_gc_grace_ticks: usize,
+    max_active_groups: usize,
+    group_park_idle_ticks: usize,
+    buffer_unknown_group_msgs: bool,
+    group_log_quota: u64,
+    log_quota_escalation_ticks: usize,
+    apply_backlog_threshold: u64,
+    runtime: Option<Handle>,
 }
 
 impl<T> ClusterBuilder<T>
@@ -35,6 +73,25 @@ where
             storages: Vec::new(),
             state_machines: Vec::new(),
             apply_rxs: Vec::new(),
+            id_seed: None,
+            entry_gates: HashMap::new(),
+            check_quorum: false,
+            enable_lease_read: false,
+            batch_read_index: false,
+            max_read_queue_len: 0,
+            max_inflight_proposals: 0,
+            await_inflight_proposals: false,
+            group_factory: None,
+            snapshot_validators: HashMap::new(),
+            authorizer: None,
+            replica_desc_gc_grace_ticks: 600,
+            max_active_groups: 0,
+            group_park_idle_ticks: 600,
+            buffer_unknown_group_msgs: false,
+            group_log_quota: 0,
+            log_quota_escalation_ticks: 100,
+            apply_backlog_threshold: 0,
+            runtime: None,
         }
     }
 
@@ -82,6 +139,201 @@ where
         self
     }
 
+    /// Generate request ids of nodes from the seed, so that runs with the
+    /// same seed produce identical ids.
+    pub fn id_seed(mut self, seed: u64) -> Self {
+        self.id_seed = Some(seed);
+        self
+    }
+
+    /// Set the entry gate of the node, other nodes accept all entries.
+    pub fn entry_gate(mut self, node_id: u64, gate: Arc<dyn EntryGate>) -> Self {
+        self.entry_gates.insert(node_id, gate);
+        self
+    }
+
+    pub fn check_quorum(mut self, check_quorum: bool) -> Self {
+        self.check_quorum = check_quorum;
+        self
+    }
+
+    pub fn enable_lease_read(mut self, enable_lease_read: bool) -> Self {
+        self.enable_lease_read = enable_lease_read;
+        self
+    }
+
+    /// Batch the read index requests into rounds and bound the outstanding
+    /// reads of a group, `0` is unlimited.
+    pub fn read_batching(mut self, batch_read_index: bool, max_read_queue_len: usize) -> Self {
+        self.batch_read_index = batch_read_index;
+        self.max_read_queue_len = max_read_queue_len;
+        self
+    }
+
+    /// Bound the uncommitted proposals of a group, the writes beyond it are
+    /// held if `await_inflight` is set.
+    pub fn max_inflight_proposals(mut self, limit: usize, await_inflight: bool) -> Self {
+        self.max_inflight_proposals = limit;
+        self.await_inflight_proposals = await_inflight;
+        self
+    }
+
+    /// Create the groups by the factory when writing to unknown groups.
+    pub fn group_factory(mut self, factory: Arc<dyn GroupFactory>) -> Self {
+        self.group_factory = Some(factory);
+        self
+    }
+
+    /// Set the snapshot validator of the node, other nodes accept all snapshots.
+    pub fn snapshot_validator(
+        mut self,
+        node_id: u64,
+        validator: Arc<dyn SnapshotValidator>,
+    ) -> Self {
+        self.snapshot_validators.insert(node_id, validator);
+        self
+    }
+
+    /// Authorize the management operations and the proposals of all nodes.
+    pub fn authorizer(mut self, authorizer: Arc<dyn Authorizer>) -> Self {
+        self.authorizer = Some(authorizer);
+        self
+    }
+
+    pub fn replica_desc_gc_grace_ticks(mut self, ticks: usize) -> Self {
+        self.replica_desc_gc_grace_ticks = ticks;
+        self
+    }
+
+    /// Park the idle groups of the nodes over `max_active_groups`.
+    pub fn park_groups(mut self, max_active_groups: usize, idle_ticks: usize) -> Self {
+        self.max_active_groups = max_active_groups;
+        self.group_park_idle_ticks = idle_ticks;
+        self
+    }
+
+    /// Buffer the messages of the groups which do not exist on the nodes
+    /// instead of creating the groups.
+    pub fn buffer_unknown_group_msgs(mut self) -> Self {
+        self.buffer_unknown_group_msgs = true;
+        self
+    }
+
+    /// Bound the bytes of the raft log of groups, the groups over the quota
+    /// escalate to the next stage every `escalation_ticks` ticks.
+    pub fn log_quota(mut self, bytes: u64, escalation_ticks: usize) -> Self {
+        self.group_log_quota = bytes;
+        self.log_quota_escalation_ticks = escalation_ticks;
+        self
+    }
+
+    /// Emit `Event::ApplyBacklogHigh` when the apply backlog of groups
+    /// exceeds `threshold` entries.
+    pub fn apply_backlog_threshold(mut self, threshold: u64) -> Self {
+        self.apply_backlog_threshold = threshold;
+        self
+    }
+
+    /// Spawn the actors of all nodes on the runtime.
+    pub fn runtime(mut self, runtime: Handle) -> Self {
+        self.runtime = Some(runtime);
+        self
+    }
+
+    /// Returns the config of the node `node_id`, it's used to build the nodes
+    /// of the cluster, or a standalone node which joins the cluster by its
+    /// transport.
+    pub fn node_config(&mut self, node_id: u64) -> Config {
+        Config {
+            node_id,
+            batch_append: false,
+            election_tick: self.election_ticks.max(2),
+            event_capacity: 100,
+            heartbeat_tick: 1,
+            max_size_per_msg: 0,
+            max_inflight_msgs: 256,
+            tick_interval: 10, // hour ms
+            max_batch_apply_msgs: 1,
+            batch_apply: false,
+            batch_size: 0,
+            proposal_queue_size: 1000,
+            max_inflight_proposals: self.max_inflight_proposals,
+            await_inflight_proposals: self.await_inflight_proposals,
+            replica_sync: true,
+            apply_backlog_threshold: self.apply_backlog_threshold,
+            max_apply_unapplied_size: 0,
+            memory_budget: 0,
+            memory_budget_idle_ticks: 100,
+            max_active_groups: self.max_active_groups,
+            group_park_idle_ticks: self.group_park_idle_ticks,
+            buffer_unknown_group_msgs: self.buffer_unknown_group_msgs,
+            unknown_group_msg_capacity: 1024,
+            unknown_group_msg_ttl_ticks: 50,
+            stale_msg_term_gap: 0,
+            msg_dedup_window: 0,
+            record_latency: false,
+            manage_idempotency_window_ticks: 6000,
+            group_log_quota: self.group_log_quota,
+            log_quota_escalation_ticks: self.log_quota_escalation_ticks,
+            log_compactor: Arc::new(NoLogCompactor),
+            archive_log_entries: 64,
+            learner_promoter: Arc::new(NoLearnerPromotion),
+            unreachable_debounce_ticks: 2,
+            topology_provider: Arc::new(NoTopologyProvider),
+            placement_driver: Arc::new(BalancedPlacement::default()),
+            id_generator: match self.id_seed {
+                None => Arc::new(RandomIdGenerator),
+                Some(seed) => Arc::new(SeededIdGenerator::new(seed.wrapping_add(node_id))),
+            },
+            entry_gate: self
+                .entry_gates
+                .remove(&node_id)
+                .unwrap_or_else(|| Arc::new(AcceptAllGate)),
+            storage_domain_failure_threshold: 0,
+            check_quorum: self.check_quorum,
+            enable_lease_read: self.enable_lease_read,
+            time_source: Arc::new(MonotonicClock::default()),
+            batch_read_index: self.batch_read_index,
+            max_read_queue_len: self.max_read_queue_len,
+            auto_create_groups: self.group_factory.is_some(),
+            group_factory: self
+                .group_factory
+                .clone()
+                .unwrap_or_else(|| Arc::new(NoGroupFactory)),
+            snapshot_validator: self
+                .snapshot_validators
+                .remove(&node_id)
+                .unwrap_or_else(|| Arc::new(NoSnapshotValidator)),
+            max_concurrent_snapshots: 0,
+            snapshot_max_bytes_per_sec: 0,
+            snapshot_send_timeout_ticks: 6000,
+            election_ramp_ticks: 0,
+            apply_checkpoint_entries: 0,
+            authorize_proposals: self.authorizer.is_some(),
+            annotate_proposer: false,
+            authorizer: self
+                .authorizer
+                .clone()
+                .unwrap_or_else(|| Arc::new(AllowAll)),
+            replica_desc_gc_interval_ticks: 0,
+            replica_desc_gc_grace_ticks: self.replica_desc_gc_grace_ticks,
+            replica_desc_gc_dry_run: false,
+            entry_compression: EntryCompression::None,
+            entry_compression_threshold: 4096,
+            adaptive_inflight_interval_ticks: 0,
+            adaptive_inflight_min_msgs: 16,
+            adaptive_inflight_max_msgs: 4096,
+            adaptive_inflight_base_rtt_ms: 1,
+            compaction_policy: Arc::new(NoCompaction),
+            compaction_check_ticks: 10,
+            background_budget: BackgroundBudget::default(),
+            batch_ready_writes: false,
+            overload_shedding: None,
+            runtime: self.runtime.clone(),
+            apply_runtime: None,
+        }
+    }
+
     pub async fn build(mut self) -> Cluster<T> {
         assert_eq!(
             self.storages.len(),
@@ -114,21 +366,7 @@ where
         let transport = LocalTransport::new();
         for i in 0..self.node_size {
             let node_id = (i + 1) as u64;
-            let config = Config {
-                node_id,
-                batch_append: false,
-                election_tick: 2,
-                event_capacity: 100,
-                heartbeat_tick: 1,
-                max_size_per_msg: 0,
-                max_inflight_msgs: 256,
-                tick_interval: 10, // hour ms
-                max_batch_apply_msgs: 1,
-                batch_apply: false,
-                batch_size: 0,
-                proposal_queue_size: 1000,
-                replica_sync: true,
-            };
+            let config = self.node_config(node_id);
             let ticker = ManualTick::new();
             let node = MultiRaft::new(
                 config,
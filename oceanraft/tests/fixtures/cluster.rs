@@ -22,14 +22,17 @@ use oceanraft::prelude::ReplicaDesc;
 use oceanraft::prelude::Snapshot;
 use oceanraft::storage::MultiRaftStorage;
 use oceanraft::storage::StorageExt;
+use oceanraft::testkit::workload::WorkloadCluster;
 use oceanraft::tick::ManualTick;
 use oceanraft::transport::LocalTransport;
 use oceanraft::Apply;
+use oceanraft::ApplyAdmin;
 use oceanraft::ApplyMembership;
 use oceanraft::ApplyNormal;
 use oceanraft::Error;
 use oceanraft::Event;
 use oceanraft::LeaderElectionEvent;
+use oceanraft::Lifecycle;
 use oceanraft::MultiRaft;
 use oceanraft::MultiRaftMessageSenderImpl;
 
@@ -115,6 +118,23 @@ where
     /// - replica_nums specifies the number of consensus groups. The replica_nums limit the number
     /// of nodes to `FixtureCluster` nodes.
     pub async fn make_group(&mut self, plan: &MakeGroupPlan) -> Result<MakeGroupPlanStatus, Error> {
+        self.make_group_inner(plan, false).await
+    }
+
+    /// Same as `make_group`, but the replicas of the consensus group are created lazily,
+    /// the raft group is materialized when the first message or proposal arrives.
+    pub async fn make_lazy_group(
+        &mut self,
+        plan: &MakeGroupPlan,
+    ) -> Result<MakeGroupPlanStatus, Error> {
+        self.make_group_inner(plan, true).await
+    }
+
+    async fn make_group_inner(
+        &mut self,
+        plan: &MakeGroupPlan,
+        lazy: bool,
+    ) -> Result<MakeGroupPlanStatus, Error> {
         assert!(
             plan.first_node_id != 0 && plan.first_node_id - 1 < self.nodes.len() as u64,
             "first_node_id violates the current constraint"
@@ -145,6 +165,7 @@ where
                 node_id,
                 group_id: plan.group_id,
                 replica_id,
+                witness: false,
             });
         }
 
@@ -168,7 +189,7 @@ where
                 .conf_states
                 .push(ss.get_metadata().get_conf_state().clone());
 
-            gs.install_snapshot(ss).unwrap();
+            gs.install_snapshot(ss).await.unwrap();
 
             let node = &self.nodes[place_node_index];
 
@@ -183,6 +204,12 @@ where
                     replica_id,
                     replicas: replicas.clone(),
                     applied_hint: 0,
+                    priority: 0,
+                    lazy,
+                    storage_domain: String::new(),
+                    idempotency_key: String::new(),
+                    archive: false,
+                    learners: vec![],
                 })
                 .await?;
 
@@ -198,6 +225,18 @@ where
         Ok(status)
     }
 
+    /// Borrows the cluster as the target of `testkit::workload::Workload`.
+    pub fn workload_cluster(&mut self) -> WorkloadCluster<'_, T> {
+        WorkloadCluster {
+            nodes: &self.nodes,
+            storages: &self.storages,
+            transport: &self.transport,
+            tickers: &self.tickers,
+            apply_rxs: &mut self.apply_events,
+            groups: &mut self.groups,
+        }
+    }
+
     /// Gets a `ReplicaDesc` of the consensus group on the node by given `node_id` and `group_id`.
     pub async fn replica_desc(&self, node_id: u64, group_id: u64) -> ReplicaDesc {
         let storage = &self.storages[to_index(node_id)];
@@ -236,8 +275,8 @@ where
                 };
 
                 // for event in events {
-                match event {
-                    Event::LederElection(leader_elect) => return Ok(leader_elect),
+                match &*event {
+                    Event::LederElection(leader_elect) => return Ok(leader_elect.clone()),
                     _ => {}
                 }
                 // }
@@ -315,6 +354,34 @@ where
         }
     }
 
+    /// Wait the admin entry applied on the node.
+    pub async fn wait_admin_apply(
+        &mut self,
+        node_id: u64,
+        timeout: Duration,
+    ) -> Result<ApplyAdmin<T::R>, String> {
+        let rx = self.apply_events[to_index(node_id)].as_mut().unwrap();
+        let wait_loop_fut = async {
+            loop {
+                let events = match rx.recv().await {
+                    None => return Err(String::from("the event sender dropped")),
+                    Some(evs) => evs,
+                };
+
+                for event in events {
+                    match event {
+                        Apply::Admin(admin) => return Ok(admin),
+                        _ => {}
+                    }
+                }
+            }
+        };
+        match timeout_at(Instant::now() + timeout, wait_loop_fut).await {
+            Err(_) => Err(format!("wait for apply admin event timeouted")),
+            Ok(res) => res,
+        }
+    }
+
     /// Write data to raft. return a onshot::Receiver to recv apply result.
     pub fn write_command(
         &self,
@@ -335,9 +402,12 @@ where
         }
     }
 
-    pub async fn stop(&mut self) {
+    pub async fn stop(&mut self)
+    where
+        T::MS: Lifecycle,
+    {
         for node in std::mem::take(&mut self.nodes).into_iter() {
-            node.stop().await
+            node.shutdown().await.unwrap()
         }
     }
 }
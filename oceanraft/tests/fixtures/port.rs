@@ -9,9 +9,11 @@ use oceanraft::define_multiraft;
 use oceanraft::prelude::StoreData;
 use oceanraft::storage::MemStorage;
 use oceanraft::storage::MultiRaftMemoryStorage;
+use oceanraft::storage::MultiRaftWitnessStorage;
 use oceanraft::storage::RockStore;
 use oceanraft::storage::RockStoreCore;
 use oceanraft::storage::StateMachineStore;
+use oceanraft::storage::WitnessStorage;
 use oceanraft::Apply;
 use oceanraft::ProposeResponse;
 
@@ -40,6 +42,15 @@ define_multiraft! {
         MS = MultiRaftMemoryStorage
 }
 
+define_multiraft! {
+    pub WitnessMemType:
+        D = StoreData,
+        R= (),
+        M= MemStoreStateMachine<StoreData>,
+        S= WitnessStorage<MemStorage>,
+        MS = MultiRaftWitnessStorage<MemStorage, MultiRaftMemoryStorage>
+}
+
 pub fn new_rock_kv_store<P>(node_id: u64, path: P) -> StateMachineStore<()>
 where
     P: AsRef<Path>,
@@ -113,11 +124,18 @@ impl MemStoreEnv {
     /// - storages (multi-raft memory storage),
     /// - and state_machines (memory state machine implementation).
     pub fn new(nodes: usize) -> Self {
+        Self::with_apply_capacity(nodes, 100)
+    }
+
+    /// Same as `new`, but the apply channels of the state machines are
+    /// bounded by `capacity`, the state machines block once the tests
+    /// leave `capacity` applies unreceived.
+    pub fn with_apply_capacity(nodes: usize, capacity: usize) -> Self {
         let mut rxs = vec![];
         let mut storages = vec![];
         let mut state_machines = vec![];
         for i in 0..nodes {
-            let (tx, rx) = channel(100);
+            let (tx, rx) = channel(capacity);
             rxs.push(Some(rx));
             state_machines.push(MemStoreStateMachine::new(tx));
             storages.push(MultiRaftMemoryStorage::new((i + 1) as u64));
@@ -1,4 +1,9 @@
+use std::sync::Arc;
+use std::sync::Mutex;
+
 use futures::Future;
+use oceanraft::prelude::ConfState;
+use oceanraft::prelude::SnapshotMetadata;
 use oceanraft::prelude::StoreData;
 use oceanraft::storage::StateMachineStore;
 use oceanraft::Apply;
@@ -10,12 +15,24 @@ use oceanraft::StateMachine;
 use tokio::sync::mpsc::Sender;
 use tracing::info;
 
+/// The lifecycle hook called on the state machine.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LifecycleEvent {
+    /// (group_id, replica_id, voters)
+    GroupCreated(u64, u64, Vec<u64>),
+    /// (group_id, replica_id)
+    GroupRemoved(u64, u64),
+    /// (group_id, replica_id, snapshot index)
+    SnapshotInstalled(u64, u64, u64),
+}
+
 #[derive(Clone)]
 pub struct MemStoreStateMachine<W>
 where
     W: ProposeData,
 {
     tx: Sender<Vec<Apply<W, ()>>>,
+    lifecycle_events: Arc<Mutex<Vec<LifecycleEvent>>>,
 }
 
 impl<W> StateMachine<W, ()> for MemStoreStateMachine<W>
@@ -46,12 +63,44 @@ where
                             .take()
                             .map(|tx| tx.send(Ok(((), membership.ctx.take()))));
                     }
+                    Apply::Admin(admin) => {
+                        admin.tx.take().map(|tx| tx.send(Ok(((), None))));
+                    }
                 }
             }
 
             tx.send(applys).await;
         }
     }
+
+    fn on_group_created(&self, group_id: u64, replica_id: u64, conf_state: &ConfState) {
+        self.lifecycle_events
+            .lock()
+            .unwrap()
+            .push(LifecycleEvent::GroupCreated(
+                group_id,
+                replica_id,
+                conf_state.voters.clone(),
+            ));
+    }
+
+    fn on_group_removed(&self, group_id: u64, replica_id: u64) {
+        self.lifecycle_events
+            .lock()
+            .unwrap()
+            .push(LifecycleEvent::GroupRemoved(group_id, replica_id));
+    }
+
+    fn on_snapshot_installed(&self, group_id: u64, replica_id: u64, metadata: &SnapshotMetadata) {
+        self.lifecycle_events
+            .lock()
+            .unwrap()
+            .push(LifecycleEvent::SnapshotInstalled(
+                group_id,
+                replica_id,
+                metadata.index,
+            ));
+    }
 }
 
 impl<W> MemStoreStateMachine<W>
@@ -59,7 +108,15 @@ where
     W: ProposeData,
 {
     pub fn new(tx: Sender<Vec<Apply<W, ()>>>) -> Self {
-        Self { tx }
+        Self {
+            tx,
+            lifecycle_events: Arc::new(Mutex::new(vec![])),
+        }
+    }
+
+    /// Returns the lifecycle hooks called on the state machine in order.
+    pub fn lifecycle_events(&self) -> Vec<LifecycleEvent> {
+        self.lifecycle_events.lock().unwrap().clone()
     }
 }
 
@@ -107,6 +164,10 @@ impl StateMachine<StoreData, ()> for RockStoreStateMachine {
                         batch.set_applied_term(membership.term);
                         batch.put_conf_state(&membership.conf_state);
                     }
+                    Apply::Admin(admin) => {
+                        batch.set_applied_index(admin.index);
+                        batch.set_applied_term(admin.term);
+                    }
                 }
             }
             self.kv_store.write_apply_bath(group_id, batch).unwrap();
@@ -123,6 +184,9 @@ impl StateMachine<StoreData, ()> for RockStoreStateMachine {
                             .take()
                             .map(|tx| tx.send(Ok(((), membership.ctx.take()))));
                     }
+                    Apply::Admin(admin) => {
+                        admin.tx.take().map(|tx| tx.send(Ok(((), None))));
+                    }
                 }
             }
 
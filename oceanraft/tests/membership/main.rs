@@ -4,4 +4,6 @@
 #[path = "../fixtures/mod.rs"]
 mod fixtures;
 
-mod t10_membership;
\ No newline at end of file
+mod t10_membership;
+mod t20_learner;
+mod t30_relocation;
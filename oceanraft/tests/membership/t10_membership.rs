@@ -5,12 +5,15 @@ use oceanraft::prelude::ConfChangeTransition;
 use oceanraft::prelude::ConfChangeType;
 use oceanraft::prelude::ConfState;
 use oceanraft::prelude::MembershipChangeData;
+use oceanraft::prelude::ReplicaDesc;
 use oceanraft::prelude::SingleMembershipChange;
 use oceanraft::prelude::StoreData;
 use oceanraft::storage::MultiRaftStorage;
 use oceanraft::storage::Storage;
 use oceanraft::Apply;
+use oceanraft::Event;
 use tokio::time::sleep;
+use tokio::time::timeout;
 
 use crate::fixtures::init_default_ut_tracing;
 use crate::fixtures::rand_string;
@@ -53,6 +56,7 @@ async fn test_single_step() {
         .unwrap();
 
     let leader = cluster.nodes[0].clone();
+    let events = leader.subscribe();
 
     // execute single step membership change for node 2 and replica 2 in group 1.
     let mut change = SingleMembershipChange::default();
@@ -73,6 +77,38 @@ async fn test_single_step() {
         .await
         .unwrap();
 
+    // the leader emits the health of the new configuration.
+    let change_event = timeout(Duration::from_millis(1000), async {
+        loop {
+            if let Event::MembershipChange(event) = &*events.recv().await.unwrap() {
+                return event.clone();
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(change_event.group_id, group_id);
+    let mut voters = change_event.conf_state.voters.clone();
+    voters.sort();
+    assert_eq!(voters, vec![1, 2]);
+    // the membership is exposed by the shared state of the group.
+    let mut voters = leader
+        .group_state(group_id)
+        .unwrap()
+        .get_conf_state()
+        .voters;
+    voters.sort();
+    assert_eq!(voters, vec![1, 2]);
+    assert_eq!(change_event.health.len(), 2);
+    let leader_health = change_event
+        .health
+        .iter()
+        .find(|health| health.replica_id == 1)
+        .unwrap();
+    assert_eq!(leader_health.node_id, 1);
+    assert!(leader_health.reachable);
+    assert!(leader_health.matched >= change_event.index);
+
     // execute single step membership change from 3..5
     for i in 3..=5 {
         loop {
@@ -673,3 +709,62 @@ async fn test_remove() {
     }
     // TODO: submmit command to bad node
 }
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_change_membership() {
+    // start five nodes.
+    let nodes = 5;
+    let mut rockstore_env = RockStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::<RockType>::new(nodes)
+        .election_ticks(2)
+        .state_machines(rockstore_env.state_machines.clone())
+        .storages(rockstore_env.storages.clone())
+        .apply_rxs(take(&mut rockstore_env.rxs))
+        .build()
+        .await;
+
+    // create three replicas and elect node 1 became leader.
+    let group_id = 1;
+    let node_id = 1;
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(node_id, plan.group_id).await;
+    let _ = Cluster::wait_leader_elect_event(&mut cluster, node_id)
+        .await
+        .unwrap();
+    let leader = cluster.nodes[0].clone();
+
+    // replace the replicas 2..3 with 4..5 in joint consensus.
+    let targets = [1, 4, 5]
+        .into_iter()
+        .map(|id| ReplicaDesc {
+            node_id: id,
+            group_id,
+            replica_id: id,
+            witness: false,
+        })
+        .collect::<Vec<_>>();
+    let mut conf_state = leader
+        .change_membership(group_id, targets.clone(), Duration::from_secs(5))
+        .await
+        .unwrap();
+    conf_state.voters.sort();
+    assert_eq!(conf_state.voters, vec![1, 4, 5]);
+    assert!(conf_state.voters_outgoing.is_empty());
+
+    // the membership of the targets has nothing to change.
+    let mut conf_state = leader
+        .change_membership(group_id, targets, Duration::from_secs(5))
+        .await
+        .unwrap();
+    conf_state.voters.sort();
+    assert_eq!(conf_state.voters, vec![1, 4, 5]);
+}
@@ -0,0 +1,103 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::prelude::CreateGroupRequest;
+use oceanraft::prelude::ReplicaDesc;
+use oceanraft::Error;
+use tokio::time::sleep;
+use tokio::time::Instant;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_learner_lifecycle() {
+    let nodes = 3;
+    let group_id = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    // the group is created with the voter on node 1 and the learner on node 2,
+    // the membership is initialized from the request.
+    for node_id in 1..=2 {
+        cluster.nodes[node_id as usize - 1]
+            .create_group(CreateGroupRequest {
+                group_id,
+                replica_id: node_id,
+                replicas: vec![ReplicaDesc {
+                    node_id: 1,
+                    group_id,
+                    replica_id: 1,
+                    witness: false,
+                }],
+                learners: vec![ReplicaDesc {
+                    node_id: 2,
+                    group_id,
+                    replica_id: 2,
+                    witness: false,
+                }],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    }
+
+    cluster.campaign_group(1, group_id).await;
+    let election = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(election.leader_id, 1);
+    let leader = cluster.nodes[0].clone();
+    let conf_state = leader.group_state(group_id).unwrap().get_conf_state();
+    assert_eq!(conf_state.voters, vec![1]);
+    assert_eq!(conf_state.learners, vec![2]);
+
+    // the voter can not be promoted.
+    match leader.promote_learner(group_id, 1, 1, 0).await {
+        Err(Error::BadParameter(_)) => {}
+        res => panic!("expected bad parameter error, got {:?}", res),
+    }
+
+    // the learner is promoted once it caught up with the leader.
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        match leader.promote_learner(group_id, 2, 2, 0).await {
+            Ok(_) => break,
+            Err(Error::LearnerNotCaughtUp { .. }) if Instant::now() < deadline => {
+                sleep(Duration::from_millis(10)).await
+            }
+            Err(err) => panic!("promote learner error: {}", err),
+        }
+    }
+    let mut conf_state = leader.group_state(group_id).unwrap().get_conf_state();
+    conf_state.voters.sort();
+    assert_eq!(conf_state.voters, vec![1, 2]);
+    assert!(conf_state.learners.is_empty());
+
+    // the learner on node 3 is provisioned, but the group is not created on
+    // node 3, so it never catches up.
+    let replica_id = leader.add_learner(group_id, 3).await.unwrap();
+    assert_eq!(replica_id, 3);
+    let conf_state = leader.group_state(group_id).unwrap().get_conf_state();
+    assert_eq!(conf_state.learners, vec![replica_id]);
+    match leader.promote_learner(group_id, 3, replica_id, 0).await {
+        Err(Error::LearnerNotCaughtUp {
+            replica_id: 3,
+            matched,
+            committed,
+            ..
+        }) => assert!(matched < committed),
+        res => panic!("expected learner not caught up error, got {:?}", res),
+    }
+    let conf_state = leader.group_state(group_id).unwrap().get_conf_state();
+    assert_eq!(conf_state.learners, vec![replica_id]);
+}
@@ -0,0 +1,104 @@
+use std::mem::take;
+use std::time::Duration;
+
+use oceanraft::Event;
+use oceanraft::OperationKind;
+use oceanraft::OperationState;
+use oceanraft::RelocationStage;
+use tokio::time::sleep;
+use tokio::time::timeout;
+
+use crate::fixtures::init_default_ut_tracing;
+use crate::fixtures::ClusterBuilder;
+use crate::fixtures::MakeGroupPlan;
+use crate::fixtures::MemStoreEnv;
+
+#[async_entry::test(
+    flavor = "multi_thread",
+    init = "init_default_ut_tracing()",
+    tracing_span = "debug"
+)]
+async fn test_relocate_replica() {
+    let nodes = 5;
+    let group_id = 1;
+    let mut env = MemStoreEnv::new(nodes);
+    let mut cluster = ClusterBuilder::new(nodes)
+        .election_ticks(2)
+        .state_machines(env.state_machines.clone())
+        .storages(env.storages.clone())
+        .apply_rxs(take(&mut env.rxs))
+        .build()
+        .await;
+
+    let plan = MakeGroupPlan {
+        group_id,
+        first_node_id: 1,
+        replica_nums: 3,
+    };
+    let _ = cluster.make_group(&plan).await.unwrap();
+    cluster.campaign_group(1, group_id).await;
+    let election = cluster.wait_leader_elect_event(1).await.unwrap();
+    assert_eq!(election.leader_id, 1);
+    let leader = cluster.nodes[0].clone();
+
+    // relocate the follower on node 2 to node 4, the operation is tracked
+    // before the relocation finishes.
+    let (op_id, handle) = leader.start_relocate_replica(group_id, 2, 2, 4, Duration::from_secs(5));
+    let progress = leader.operation_progress(op_id).unwrap();
+    assert_eq!(progress.kind, OperationKind::RelocateReplica);
+    assert_eq!(progress.group_id, group_id);
+    let follower_target = handle.await.unwrap().unwrap();
+    assert_eq!(
+        leader.operation_progress(op_id).unwrap().state,
+        OperationState::Succeeded
+    );
+    let mut voters = leader
+        .group_state(group_id)
+        .unwrap()
+        .get_conf_state()
+        .voters;
+    voters.sort();
+    let mut expected = vec![1, 3, follower_target];
+    expected.sort();
+    assert_eq!(voters, expected);
+    assert!(leader.group_state(group_id).unwrap().is_leader());
+
+    // relocate the leader on node 1 to node 5, the leadership is transferred
+    // to the target before the leader is removed.
+    let events = leader.subscribe();
+    let leader_target = leader
+        .relocate_replica(group_id, 1, 1, 5, Duration::from_secs(5))
+        .await
+        .unwrap();
+    let transferred = timeout(Duration::from_secs(1), async {
+        loop {
+            match *events.recv().await.unwrap() {
+                Event::Relocation {
+                    stage: RelocationStage::LeaderTransferred,
+                    to_replica_id,
+                    ..
+                } => return to_replica_id,
+                _ => {}
+            }
+        }
+    })
+    .await
+    .unwrap();
+    assert_eq!(transferred, leader_target);
+
+    let state = cluster.nodes[4].group_state(group_id).unwrap();
+    assert!(state.is_leader());
+    let mut expected = vec![3, follower_target, leader_target];
+    expected.sort();
+    for _ in 0..100 {
+        let mut voters = state.get_conf_state().voters;
+        voters.sort();
+        if voters == expected {
+            break;
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    let mut voters = state.get_conf_state().voters;
+    voters.sort();
+    assert_eq!(voters, expected);
+}